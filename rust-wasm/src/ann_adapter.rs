@@ -0,0 +1,248 @@
+//! 外部ANN库的距离后端
+//!
+//! instant-distance、hora、hnsw_rs等图索引库只需要"两点之间的距离"。
+//! 这里把1位打包码和修正项编码为一段字节（码），提供与这些库形状一致的距离对象：
+//!
+//! - hnsw_rs风格：`CodeDistance::eval(&self, a: &[u8], b: &[u8]) -> f32`，
+//!   包一层新类型实现 `hnsw_rs::dist::Distance<u8>` 即可
+//! - instant-distance风格：`PackedPoint::distance(&self, other: &Self) -> f32`，
+//!   实现 `instant_distance::Point` 时直接转发
+//!
+//! 本crate不依赖这些库，适配只需要在调用方写一行转发。
+//! 距离由1位对1位的估计分数换算（见 `score_to_distance`），越小越近，且对两侧对称
+//!
+//! 码的布局：`dimension.div_ceil(8)` 字节的打包向量，后接下界、上界、附加修正、量化分量和（小端f32）
+
+use std::sync::Arc;
+
+use crate::error_codes::BbqError;
+use crate::binary_quantized_scorer::BinaryQuantizedScorer;
+use crate::edge_scorer::CORRECTION_FIELDS;
+use crate::optimized_scalar_quantizer::{CorrectionPrecision, QuantizationResult};
+use crate::vector_similarity::SimilarityFunction;
+use crate::vector_utils::compute_dot_product;
+
+/// 从索引导出的（序号, 码）
+pub type IndexedCodes = Vec<(usize, Vec<u8>)>;
+
+/// 码之间的距离，越小越近
+pub trait CodeDistance: Send + Sync {
+    /// 两个码之间的距离；码的长度不符时返回 `f32::MAX`（视为最远）
+    fn eval(&self, a: &[u8], b: &[u8]) -> f32;
+}
+
+/// 由估计分数换算距离
+///
+/// - 欧氏距离：分数为 `1 / (1 + d²)`，换算回 `d²`
+/// - 余弦：分数为 `(1 + cos) / 2`，距离为 `1 - 分数`，即 `(1 - cos) / 2`
+/// - 最大内积：分数恒为正且随内积单调递增，距离取倒数
+pub fn score_to_distance(similarity_function: SimilarityFunction, score: f32) -> f32 {
+    match similarity_function {
+        SimilarityFunction::Euclidean => {
+            if score > 0.0 { 1.0 / score - 1.0 } else { f32::MAX }
+        }
+        SimilarityFunction::Cosine => 1.0 - score,
+        SimilarityFunction::MaximumInnerProduct => {
+            if score > 0.0 { 1.0 / score } else { f32::MAX }
+        }
+    }
+}
+
+/// 1位打包码的度量
+pub struct PackedCodeMetric {
+    similarity_function: SimilarityFunction,
+    scorer: BinaryQuantizedScorer,
+    dimension: usize,
+    /// 质心模长的平方
+    centroid_dp: f32,
+}
+
+impl PackedCodeMetric {
+    /// 创建度量
+    ///
+    /// # 参数
+    /// * `similarity_function` - 构建码时使用的相似性函数
+    /// * `centroid` - 构建码时的质心
+    pub fn new(similarity_function: SimilarityFunction, centroid: &[f32]) -> Result<Self, BbqError> {
+        if centroid.is_empty() {
+            return Err(BbqError::empty_input("质心不能为空", "centroid must not be empty"));
+        }
+        Ok(Self {
+            similarity_function,
+            scorer: BinaryQuantizedScorer::new(similarity_function),
+            dimension: centroid.len(),
+            centroid_dp: compute_dot_product(centroid, centroid),
+        })
+    }
+
+    /// 设置修正项与评分的累加精度，需与构建码时一致
+    pub fn with_correction_precision(mut self, precision: CorrectionPrecision) -> Self {
+        self.scorer = self.scorer.with_correction_precision(precision);
+        self
+    }
+
+    /// 由1位索引创建度量，并导出所有未删除向量的（序号, 码）
+    #[cfg(feature = "index")]
+    pub fn from_index(index: &crate::quantized_index::QuantizedIndex) -> Result<(Self, IndexedCodes), BbqError> {
+        let config = index.get_config();
+        if config.index_bits != 1 {
+            return Err(BbqError::invalid_argument(
+                format!("只支持1位索引，当前为{}位", config.index_bits),
+                format!("only 1-bit indexes are supported, got {} bits", config.index_bits),
+            ));
+        }
+        let generation = index.snapshot()?;
+        let values = generation.values();
+        let metric = Self {
+            similarity_function: config.similarity_function,
+            scorer: BinaryQuantizedScorer::new(config.similarity_function)
+                .with_correction_precision(config.correction_precision)
+                .with_scoring_precision(config.scoring_precision),
+            dimension: values.dimension(),
+            centroid_dp: values.get_centroid_dp(None),
+        };
+        let codes = values.iter()
+            .filter(|(ord, ..)| !generation.is_deleted(*ord))
+            .map(|(ord, packed, corrections)| Ok((ord, metric.encode(packed, &corrections)?)))
+            .collect::<Result<_, BbqError>>()?;
+        Ok((metric, codes))
+    }
+
+    /// 向量维度
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// 每个码的字节数
+    pub fn code_len(&self) -> usize {
+        self.dimension.div_ceil(8) + CORRECTION_FIELDS * 4
+    }
+
+    /// 把打包向量和修正项编码为码
+    ///
+    /// # 参数
+    /// * `packed` - 1位打包向量，可按64位对齐，超出 `dimension.div_ceil(8)` 的字节被忽略
+    /// * `corrections` - 修正项
+    pub fn encode(&self, packed: &[u8], corrections: &QuantizationResult) -> Result<Vec<u8>, BbqError> {
+        let packed_size = self.dimension.div_ceil(8);
+        if packed.len() < packed_size {
+            return Err(BbqError::dimension_mismatch(
+                format!("打包向量长度 {} 小于打包维度 {}", packed.len(), packed_size),
+                format!("packed vector length {} is less than the packed dimension {}", packed.len(), packed_size),
+            ));
+        }
+        let mut code = Vec::with_capacity(self.code_len());
+        code.extend_from_slice(&packed[..packed_size]);
+        for value in [
+            corrections.lower_interval,
+            corrections.upper_interval,
+            corrections.additional_correction,
+            corrections.quantized_component_sum,
+        ] {
+            code.extend_from_slice(&value.to_le_bytes());
+        }
+        Ok(code)
+    }
+
+    /// 两个码之间的估计分数（越大越相似）
+    pub fn similarity(&self, a: &[u8], b: &[u8]) -> Result<f32, BbqError> {
+        let (a_packed, a_corrections) = self.decode(a)?;
+        let (b_packed, b_corrections) = self.decode(b)?;
+        self.scorer.compute_packed_pair_score(a_packed, &a_corrections, b_packed, &b_corrections, self.dimension, self.centroid_dp)
+    }
+
+    /// 拆分码为打包向量和修正项
+    fn decode<'a>(&self, code: &'a [u8]) -> Result<(&'a [u8], QuantizationResult), BbqError> {
+        if code.len() != self.code_len() {
+            return Err(BbqError::dimension_mismatch(
+                format!("码长度 {} 与预期 {} 不符", code.len(), self.code_len()),
+                format!("code length {} does not match the expected {}", code.len(), self.code_len()),
+            ));
+        }
+        let (packed, terms) = code.split_at(self.dimension.div_ceil(8));
+        let read_f32 = |i: usize| f32::from_le_bytes([terms[i * 4], terms[i * 4 + 1], terms[i * 4 + 2], terms[i * 4 + 3]]);
+        Ok((packed, QuantizationResult {
+            lower_interval: read_f32(0),
+            upper_interval: read_f32(1),
+            additional_correction: read_f32(2),
+            quantized_component_sum: read_f32(3),
+        }))
+    }
+}
+
+impl CodeDistance for PackedCodeMetric {
+    fn eval(&self, a: &[u8], b: &[u8]) -> f32 {
+        match self.similarity(a, b) {
+            Ok(score) => score_to_distance(self.similarity_function, score),
+            Err(_) => f32::MAX,
+        }
+    }
+}
+
+/// 带度量的码，对应instant-distance的 `Point`
+#[derive(Clone)]
+pub struct PackedPoint {
+    code: Vec<u8>,
+    metric: Arc<PackedCodeMetric>,
+}
+
+impl PackedPoint {
+    /// 创建点，码的长度必须与度量一致
+    pub fn new(code: Vec<u8>, metric: Arc<PackedCodeMetric>) -> Result<Self, BbqError> {
+        if code.len() != metric.code_len() {
+            return Err(BbqError::dimension_mismatch(
+                format!("码长度 {} 与预期 {} 不符", code.len(), metric.code_len()),
+                format!("code length {} does not match the expected {}", code.len(), metric.code_len()),
+            ));
+        }
+        Ok(Self { code, metric })
+    }
+
+    /// 码
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    /// 与另一个点的距离
+    pub fn distance(&self, other: &Self) -> f32 {
+        self.metric.eval(&self.code, &other.code)
+    }
+}
+
+#[cfg(all(test, feature = "graph"))]
+mod tests {
+    use super::*;
+    use crate::quantized_index::{QuantizedIndex, QuantizedIndexConfig};
+    use crate::vector_utils::generate_gaussian_mixture;
+
+    #[test]
+    fn test_distance_follows_knn_graph() {
+        let vectors = generate_gaussian_mixture(80, 24, 3, 0.3, 5).unwrap();
+        for similarity_function in [SimilarityFunction::Euclidean, SimilarityFunction::Cosine, SimilarityFunction::MaximumInnerProduct] {
+            let mut index = QuantizedIndex::new(QuantizedIndexConfig { similarity_function, ..QuantizedIndexConfig::default() }).unwrap();
+            index.build_index(&vectors).unwrap();
+            index.delete(3).unwrap();
+            let graph = index.build_knn_graph(5).unwrap();
+
+            let (metric, codes) = PackedCodeMetric::from_index(&index).unwrap();
+            assert_eq!(codes.len(), 79);
+            assert!(codes.iter().all(|(ord, code)| *ord != 3 && code.len() == metric.code_len()));
+            let metric = Arc::new(metric);
+            let points: Vec<(usize, PackedPoint)> = codes.into_iter()
+                .map(|(ord, code)| (ord, PackedPoint::new(code, metric.clone()).unwrap()))
+                .collect();
+
+            // 按距离排序的最近邻与k近邻图（按分数）一致，距离对称
+            let (ord, point) = &points[10];
+            let mut nearest: Vec<(usize, f32)> = points.iter()
+                .filter(|(other, _)| other != ord)
+                .map(|(other, other_point)| (*other, point.distance(other_point)))
+                .collect();
+            nearest.sort_by(|a, b| a.1.total_cmp(&b.1));
+            let expected: Vec<usize> = graph.neighbors[*ord].iter().map(|&(other, _)| other).collect();
+            assert_eq!(nearest.iter().take(5).map(|&(other, _)| other).collect::<Vec<_>>(), expected);
+            assert_eq!(point.distance(&points[20].1), points[20].1.distance(point));
+            assert_eq!(metric.eval(point.code(), &point.code()[1..]), f32::MAX);
+        }
+    }
+}
@@ -1,7 +1,10 @@
-/// 批量点积优化算法
-/// 对应TypeScript中的computeBatchFourBitDotProductDirectPacked.ts
-/// 
-/// 使用八路循环展开和SIMD优化批量计算
+//! 批量点积优化算法
+//! 对应TypeScript中的computeBatchFourBitDotProductDirectPacked.ts
+//! 
+//! 使用八路循环展开和SIMD优化批量计算
+
+use crate::filter::OrdinalBitset;
+use crate::optimized_scalar_quantizer::OptimizedScalarQuantizer;
 
 /// 优化的4位批量点积（查询未打包，目标打包）
 /// 
@@ -20,10 +23,10 @@ pub fn compute_batch_four_bit_dot_product_direct_packed(
     dimension: usize,
 ) -> Vec<i32> {
     let mut results = vec![0i32; num_vectors];
-    let packed_dimension = (dimension + 7) / 8; // Math.ceil(dimension / 8)
+    let packed_dimension = dimension.div_ceil(8); // Math.ceil(dimension / 8)
     let main_packed_dimension = dimension / 8;
 
-    for i in 0..num_vectors {
+    for (i, result) in results.iter_mut().enumerate() {
         let mut dot_product = 0i32;
         let target_offset = i * packed_dimension;
 
@@ -47,14 +50,14 @@ pub fn compute_batch_four_bit_dot_product_direct_packed(
         let remainder_start_dim = main_packed_dimension * 8;
         if remainder_start_dim < dimension {
             let last_packed_value = continuous_buffer[target_offset + main_packed_dimension];
-            for dim in remainder_start_dim..dimension {
+            for (dim, &query_value) in query_vector.iter().enumerate().take(dimension).skip(remainder_start_dim) {
                 let bit_index = 7 - (dim % 8);
                 let target_value = ((last_packed_value >> bit_index) & 1) as i32;
-                dot_product += (query_vector[dim] as i32) * target_value;
+                dot_product += (query_value as i32) * target_value;
             }
         }
 
-        results[i] = dot_product;
+        *result = dot_product;
     }
 
     results
@@ -78,13 +81,12 @@ pub fn compute_batch_one_bit_dot_product_direct_packed(
 ) -> Vec<i32> {
     let mut results = vec![0i32; num_vectors];
 
-    for i in 0..num_vectors {
+    for (i, result) in results.iter_mut().enumerate() {
         let target_offset = i * packed_dimension;
         let mut dot_product = 0i32;
 
         // 使用XOR+POPCNT优化
-        for j in 0..packed_dimension {
-            let q_byte = query_vector[j];
+        for (j, &q_byte) in query_vector.iter().enumerate().take(packed_dimension) {
             let d_byte = continuous_buffer[target_offset + j];
             
             // XOR得到不同的位
@@ -96,7 +98,7 @@ pub fn compute_batch_one_bit_dot_product_direct_packed(
             dot_product += 8 - 2 * hamming_distance;
         }
 
-        results[i] = dot_product;
+        *result = dot_product;
     }
 
     results
@@ -104,29 +106,506 @@ pub fn compute_batch_one_bit_dot_product_direct_packed(
 
 /// 创建直接打包缓冲区
 /// 将多个向量连续打包到一个缓冲区中，提升缓存局部性
-/// 
+///
+/// 被排除的向量（如已删除的墓碑）在打包前跳过，不进入缓冲区，也就不参与点积计算
+///
 /// # 参数
 /// * `vectors` - 向量列表
 /// * `indices` - 要打包的向量索引
 /// * `packed_size` - 每个向量的打包大小
-/// 
+/// * `excluded` - 需要跳过的向量索引
+///
 /// # 返回
-/// 连续打包的缓冲区
+/// (连续打包的缓冲区, 被打包的向量在indices中的位置)
 pub fn create_direct_packed_buffer(
     vectors: &[Vec<u8>],
     indices: &[usize],
     packed_size: usize,
-) -> Vec<u8> {
-    let mut buffer = vec![0u8; indices.len() * packed_size];
-    
-    for (i, &index) in indices.iter().enumerate() {
-        let offset = i * packed_size;
-        let vector = &vectors[index];
-        buffer[offset..offset + packed_size.min(vector.len())]
-            .copy_from_slice(&vector[..packed_size.min(vector.len())]);
+    excluded: Option<&OrdinalBitset>,
+) -> (Vec<u8>, Vec<usize>) {
+    let kept: Vec<usize> = (0..indices.len())
+        .filter(|&i| !excluded.is_some_and(|excluded| excluded.contains(indices[i])))
+        .collect();
+    let mut buffer = vec![0u8; kept.len() * packed_size];
+
+    for (chunk, &i) in buffer.chunks_exact_mut(packed_size.max(1)).zip(&kept) {
+        let vector = &vectors[indices[i]];
+        let len = packed_size.min(vector.len());
+        chunk[..len].copy_from_slice(&vector[..len]);
+    }
+
+    (buffer, kept)
+}
+
+/// 批量1位点积计算（u64位计数实现）
+/// 每次处理8个字节，减少位计数指令数量
+///
+/// # 参数
+/// * `query_vector` - 打包的1位查询向量
+/// * `continuous_buffer` - 连续打包的1位目标向量
+/// * `num_vectors` - 向量数量
+/// * `packed_dimension` - 打包后的维度（字节数）
+///
+/// # 返回
+/// 点积结果数组
+pub fn compute_batch_one_bit_dot_product_u64(
+    query_vector: &[u8],
+    continuous_buffer: &[u8],
+    num_vectors: usize,
+    packed_dimension: usize,
+) -> Vec<i32> {
+    let query = &query_vector[..packed_dimension];
+    let total_bits = (packed_dimension * 8) as i32;
+
+    (0..num_vectors)
+        .map(|i| {
+            let target = &continuous_buffer[i * packed_dimension..(i + 1) * packed_dimension];
+            let hamming_distance = popcount_xor_u64(query, target) as i32;
+            total_bits - 2 * hamming_distance
+        })
+        .collect()
+}
+
+/// 优化的4位批量点积（位平面 + u64位计数实现）
+/// 将查询拆成若干个打包的位平面，点积 = Σ 2^b * popcount(plane_b & target)
+///
+/// # 参数
+/// * `query_vector` - 4比特量化查询向量（未打包格式）
+/// * `continuous_buffer` - 连续打包的1比特目标向量
+/// * `num_vectors` - 向量数量
+/// * `dimension` - 向量维度
+///
+/// # 返回
+/// 点积结果数组
+pub fn compute_batch_four_bit_dot_product_bit_planes(
+    query_vector: &[u8],
+    continuous_buffer: &[u8],
+    num_vectors: usize,
+    dimension: usize,
+) -> Vec<i32> {
+    let packed_dimension = dimension.div_ceil(8);
+    let planes = build_query_bit_planes(&query_vector[..dimension]);
+
+    (0..num_vectors)
+        .map(|i| {
+            let target = &continuous_buffer[i * packed_dimension..(i + 1) * packed_dimension];
+            planes.iter()
+                .enumerate()
+                .map(|(bit, plane)| (popcount_and_u64(plane, target) as i32) << bit)
+                .sum()
+        })
+        .collect()
+}
+
+/// 按序号直接读取连续存储的1位批量点积（不收集）
+///
+/// 过滤后的候选稀疏时，收集到临时缓冲区的复制和分配比评分本身还贵，
+/// 这里按 `ord * stride` 直接定位每个目标向量
+///
+/// # 参数
+/// * `query_vector` - 打包的1位查询向量，长度即参与计算的字节数
+/// * `storage` - 连续存放的全部打包向量
+/// * `ords` - 目标向量序号（调用方保证在范围内）
+/// * `stride` - 存储中每个向量占用的字节数，不小于查询长度
+pub fn compute_batch_one_bit_dot_product_strided(
+    query_vector: &[u8],
+    storage: &[u8],
+    ords: &[usize],
+    stride: usize,
+) -> Vec<i32> {
+    let packed_dimension = query_vector.len();
+    let total_bits = (packed_dimension * 8) as i32;
+    ords.iter()
+        .map(|&ord| {
+            let target = &storage[ord * stride..ord * stride + packed_dimension];
+            total_bits - 2 * popcount_xor_u64(query_vector, target) as i32
+        })
+        .collect()
+}
+
+/// 按序号直接读取连续存储的4位批量点积（位平面，不收集）
+///
+/// # 参数
+/// * `query_vector` - 量化查询向量（未打包格式）
+/// * `storage` - 连续存放的全部打包向量
+/// * `ords` - 目标向量序号（调用方保证在范围内）
+/// * `dimension` - 向量维度
+/// * `stride` - 存储中每个向量占用的字节数，不小于 `dimension.div_ceil(8)`
+pub fn compute_batch_four_bit_dot_product_strided(
+    query_vector: &[u8],
+    storage: &[u8],
+    ords: &[usize],
+    dimension: usize,
+    stride: usize,
+) -> Vec<i32> {
+    let packed_dimension = dimension.div_ceil(8);
+    let planes = build_query_bit_planes(&query_vector[..dimension]);
+    ords.iter()
+        .map(|&ord| {
+            let target = &storage[ord * stride..ord * stride + packed_dimension];
+            planes.iter()
+                .enumerate()
+                .map(|(bit, plane)| (popcount_and_u64(plane, target) as i32) << bit)
+                .sum()
+        })
+        .collect()
+}
+
+/// 位运算点积的累加类型
+///
+/// 点积的上界为 维度 × 查询分量最大值，超出i32时需要i64累加。
+/// 常见维度下i32足够，只有极高维度（或更多查询位数）时才切换到较慢的i64内核
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DotProductAccumulator {
+    I32,
+    I64,
+}
+
+impl DotProductAccumulator {
+    /// 由点积的上界选择累加类型
+    ///
+    /// # 参数
+    /// * `dimension` - 向量维度
+    /// * `max_value` - 查询分量的最大值（1位查询为1，b位查询为 `2^b - 1`）
+    pub fn for_bounds(dimension: usize, max_value: u32) -> Self {
+        match (dimension as u64).checked_mul(max_value as u64) {
+            Some(bound) if bound <= i32::MAX as u64 => Self::I32,
+            _ => Self::I64,
+        }
+    }
+
+    /// 由查询位数选择累加类型
+    pub fn for_query_bits(dimension: usize, query_bits: u8) -> Self {
+        Self::for_bounds(dimension, (1u32 << query_bits.min(31)) - 1)
+    }
+}
+
+/// 按累加类型计算的一批点积
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchDotProducts {
+    I32(Vec<i32>),
+    I64(Vec<i64>),
+}
+
+impl BatchDotProducts {
+    /// 点积数量
+    pub fn len(&self) -> usize {
+        match self {
+            Self::I32(values) => values.len(),
+            Self::I64(values) => values.len(),
+        }
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 第 `i` 个点积
+    pub fn get(&self, i: usize) -> Option<i64> {
+        match self {
+            Self::I32(values) => values.get(i).map(|&value| value as i64),
+            Self::I64(values) => values.get(i).copied(),
+        }
+    }
+}
+
+/// 以i64累加的1位批量点积
+///
+/// # 参数
+/// * `query_vector` - 打包的1位查询向量，长度即参与计算的字节数
+/// * `continuous_buffer` - 连续打包的1位目标向量，每个 `stride` 字节
+/// * `num_vectors` - 向量数量
+/// * `stride` - 每个目标占用的字节数，不小于查询长度
+pub fn compute_batch_one_bit_dot_product_wide(
+    query_vector: &[u8],
+    continuous_buffer: &[u8],
+    num_vectors: usize,
+    stride: usize,
+) -> Vec<i64> {
+    let packed_dimension = query_vector.len();
+    let total_bits = packed_dimension as i64 * 8;
+    (0..num_vectors)
+        .map(|i| {
+            let target = &continuous_buffer[i * stride..i * stride + packed_dimension];
+            total_bits - 2 * popcount_xor_u64(query_vector, target) as i64
+        })
+        .collect()
+}
+
+/// 以i64累加的多位批量点积（位平面）
+///
+/// # 参数
+/// * `query_vector` - 量化查询向量（未打包格式）
+/// * `continuous_buffer` - 连续打包的1位目标向量，每个 `stride` 字节
+/// * `num_vectors` - 向量数量
+/// * `dimension` - 向量维度
+/// * `stride` - 每个目标占用的字节数，不小于 `dimension.div_ceil(8)`
+pub fn compute_batch_four_bit_dot_product_wide(
+    query_vector: &[u8],
+    continuous_buffer: &[u8],
+    num_vectors: usize,
+    dimension: usize,
+    stride: usize,
+) -> Vec<i64> {
+    let packed_dimension = dimension.div_ceil(8);
+    let planes = build_query_bit_planes(&query_vector[..dimension]);
+    (0..num_vectors)
+        .map(|i| {
+            let target = &continuous_buffer[i * stride..i * stride + packed_dimension];
+            planes.iter()
+                .enumerate()
+                .map(|(bit, plane)| (popcount_and_u64(plane, target) as i64) << bit)
+                .sum()
+        })
+        .collect()
+}
+
+/// 查询块与目标块分块计算时每块的目标数量
+///
+/// 一块目标转换为u64后约为 `BLOCK_TARGET_TILE * dimension / 8` 字节，1024维时8KB，可以留在L1中
+const BLOCK_TARGET_TILE: usize = 64;
+
+/// 多查询×多目标的4位点积（位平面 + 分块）
+///
+/// 逐个查询调用单查询内核时，每个查询都要把整个目标缓冲区读一遍；
+/// 这里先把一块目标转换为u64，再让所有查询依次与这一块计算，目标只读取一次
+///
+/// # 参数
+/// * `queries` - 各查询的量化值（未打包格式），长度都不小于 `dimension`
+/// * `continuous_buffer` - 连续打包的1位目标向量，每个 `stride` 字节
+/// * `num_vectors` - 目标数量
+/// * `dimension` - 向量维度
+/// * `stride` - 每个目标占用的字节数，不小于 `dimension.div_ceil(8)`
+///
+/// # 返回
+/// 按查询排列的点积，第q个查询与第t个目标的点积位于 `q * num_vectors + t`
+pub fn compute_block_four_bit_dot_products(
+    queries: &[&[u8]],
+    continuous_buffer: &[u8],
+    num_vectors: usize,
+    dimension: usize,
+    stride: usize,
+) -> Vec<i32> {
+    let packed_dimension = dimension.div_ceil(8);
+    let query_planes: Vec<Vec<Vec<u64>>> = queries.iter()
+        .map(|query| build_query_bit_planes(&query[..dimension]).iter().map(|plane| to_words(plane)).collect())
+        .collect();
+    compute_block(&query_planes, continuous_buffer, num_vectors, packed_dimension, stride, |planes, target| {
+        planes.iter()
+            .enumerate()
+            .map(|(bit, plane)| (and_popcount_words(plane, target) as i32) << bit)
+            .sum()
+    })
+}
+
+/// 多查询×多目标的1位点积（分块）
+///
+/// # 参数
+/// * `queries` - 各查询的打包向量，长度都为 `packed_dimension`
+/// * `continuous_buffer` - 连续打包的1位目标向量，每个 `stride` 字节
+/// * `num_vectors` - 目标数量
+/// * `packed_dimension` - 打包后的维度（字节数）
+/// * `stride` - 每个目标占用的字节数，不小于 `packed_dimension`
+///
+/// # 返回
+/// 按查询排列的点积，第q个查询与第t个目标的点积位于 `q * num_vectors + t`
+pub fn compute_block_one_bit_dot_products(
+    queries: &[&[u8]],
+    continuous_buffer: &[u8],
+    num_vectors: usize,
+    packed_dimension: usize,
+    stride: usize,
+) -> Vec<i32> {
+    let total_bits = (packed_dimension * 8) as i32;
+    let query_words: Vec<Vec<u64>> = queries.iter().map(|query| to_words(&query[..packed_dimension])).collect();
+    compute_block(&query_words, continuous_buffer, num_vectors, packed_dimension, stride, |query, target| {
+        let hamming_distance: u32 = query.iter().zip(target).map(|(&q, &t)| (q ^ t).count_ones()).sum();
+        total_bits - 2 * hamming_distance as i32
+    })
+}
+
+/// 分块遍历：目标块在外层转换一次，查询在中层，块内目标在内层
+fn compute_block<Q>(
+    queries: &[Q],
+    continuous_buffer: &[u8],
+    num_vectors: usize,
+    packed_dimension: usize,
+    stride: usize,
+    dot: impl Fn(&Q, &[u64]) -> i32,
+) -> Vec<i32> {
+    let words_per_target = packed_dimension.div_ceil(8);
+    let mut results = vec![0i32; queries.len() * num_vectors];
+    let mut tile = vec![0u64; BLOCK_TARGET_TILE * words_per_target];
+    for tile_start in (0..num_vectors).step_by(BLOCK_TARGET_TILE) {
+        let tile_len = BLOCK_TARGET_TILE.min(num_vectors - tile_start);
+        for (i, words) in tile.chunks_exact_mut(words_per_target.max(1)).take(tile_len).enumerate() {
+            let offset = (tile_start + i) * stride;
+            fill_words(&continuous_buffer[offset..offset + packed_dimension], words);
+        }
+        for (q, query) in queries.iter().enumerate() {
+            let row = &mut results[q * num_vectors + tile_start..q * num_vectors + tile_start + tile_len];
+            for (i, result) in row.iter_mut().enumerate() {
+                *result = dot(query, &tile[i * words_per_target..(i + 1) * words_per_target]);
+            }
+        }
+    }
+    results
+}
+
+/// 将字节按小端转换为u64，末尾不足8字节的部分补0
+fn to_words(bytes: &[u8]) -> Vec<u64> {
+    let mut words = vec![0u64; bytes.len().div_ceil(8)];
+    fill_words(bytes, &mut words);
+    words
+}
+
+fn fill_words(bytes: &[u8], words: &mut [u64]) {
+    for (word, chunk) in words.iter_mut().zip(bytes.chunks(8)) {
+        let mut buffer = [0u8; 8];
+        buffer[..chunk.len()].copy_from_slice(chunk);
+        *word = u64::from_le_bytes(buffer);
+    }
+}
+
+/// 计算 popcount(a & b)，两者已转换为u64
+fn and_popcount_words(a: &[u64], b: &[u64]) -> u32 {
+    a.iter().zip(b).map(|(&a, &b)| (a & b).count_ones()).sum()
+}
+
+/// 将未打包的查询拆分为打包的位平面
+/// 位序与 `pack_as_binary` 一致（高位在前），平面数量由查询的最大值决定
+///
+/// # 参数
+/// * `query_vector` - 未打包的量化查询向量
+///
+/// # 返回
+/// 第b个元素为查询各分量第b位组成的打包向量
+pub fn build_query_bit_planes(query_vector: &[u8]) -> Vec<Vec<u8>> {
+    let max_value = query_vector.iter().copied().max().unwrap_or(0);
+    let plane_count = (8 - max_value.leading_zeros()) as usize;
+    let packed_dimension = query_vector.len().div_ceil(8);
+
+    // 每次读取8个分量，各平面右移后收集最低位
+    let mut planes = vec![vec![0u8; packed_dimension]; plane_count];
+    for (i, chunk) in query_vector.chunks(8).enumerate() {
+        let mut lanes = [0u8; 8];
+        lanes[..chunk.len()].copy_from_slice(chunk);
+        let word = u64::from_le_bytes(lanes);
+        for (bit, plane) in planes.iter_mut().enumerate() {
+            plane[i] = OptimizedScalarQuantizer::pack_lanes(word >> bit);
+        }
+    }
+    planes
+}
+
+/// 计算 popcount(a ^ b)，按u64分块
+fn popcount_xor_u64(a: &[u8], b: &[u8]) -> u32 {
+    let mut count = 0u32;
+    let mut a_chunks = a.chunks_exact(8);
+    let mut b_chunks = b.chunks_exact(8);
+    for (a_chunk, b_chunk) in (&mut a_chunks).zip(&mut b_chunks) {
+        let a_word = u64::from_le_bytes(a_chunk.try_into().unwrap_or([0; 8]));
+        let b_word = u64::from_le_bytes(b_chunk.try_into().unwrap_or([0; 8]));
+        count += (a_word ^ b_word).count_ones();
+    }
+    for (&a_byte, &b_byte) in a_chunks.remainder().iter().zip(b_chunks.remainder()) {
+        count += (a_byte ^ b_byte).count_ones();
+    }
+    count
+}
+
+/// 计算 popcount(a & b)，按u64分块
+fn popcount_and_u64(a: &[u8], b: &[u8]) -> u32 {
+    let mut count = 0u32;
+    let mut a_chunks = a.chunks_exact(8);
+    let mut b_chunks = b.chunks_exact(8);
+    for (a_chunk, b_chunk) in (&mut a_chunks).zip(&mut b_chunks) {
+        let a_word = u64::from_le_bytes(a_chunk.try_into().unwrap_or([0; 8]));
+        let b_word = u64::from_le_bytes(b_chunk.try_into().unwrap_or([0; 8]));
+        count += (a_word & b_word).count_ones();
+    }
+    for (&a_byte, &b_byte) in a_chunks.remainder().iter().zip(b_chunks.remainder()) {
+        count += (a_byte & b_byte).count_ones();
+    }
+    count
+}
+
+/// WASM SIMD128内核
+/// 仅在以 `-C target-feature=+simd128` 编译时可用
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+pub mod simd128 {
+    use core::arch::wasm32::*;
+
+    /// 批量1位点积计算（SIMD128实现）
+    pub fn compute_batch_one_bit_dot_product_simd128(
+        query_vector: &[u8],
+        continuous_buffer: &[u8],
+        num_vectors: usize,
+        packed_dimension: usize,
+    ) -> Vec<i32> {
+        let query = &query_vector[..packed_dimension];
+        let total_bits = (packed_dimension * 8) as i32;
+
+        (0..num_vectors)
+            .map(|i| {
+                let target = &continuous_buffer[i * packed_dimension..(i + 1) * packed_dimension];
+                let hamming_distance = popcount_simd(query, target, v128_xor) as i32;
+                total_bits - 2 * hamming_distance
+            })
+            .collect()
+    }
+
+    /// 优化的4位批量点积（位平面 + SIMD128实现）
+    pub fn compute_batch_four_bit_dot_product_simd128(
+        query_vector: &[u8],
+        continuous_buffer: &[u8],
+        num_vectors: usize,
+        dimension: usize,
+    ) -> Vec<i32> {
+        let packed_dimension = dimension.div_ceil(8);
+        let planes = super::build_query_bit_planes(&query_vector[..dimension]);
+
+        (0..num_vectors)
+            .map(|i| {
+                let target = &continuous_buffer[i * packed_dimension..(i + 1) * packed_dimension];
+                planes.iter()
+                    .enumerate()
+                    .map(|(bit, plane)| (popcount_simd(plane, target, v128_and) as i32) << bit)
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// 按16字节分块计算 popcount(op(a, b))
+    fn popcount_simd(a: &[u8], b: &[u8], op: fn(v128, v128) -> v128) -> u32 {
+        let len = a.len().min(b.len());
+        let chunks = len / 16;
+        let mut acc = u32x4_splat(0);
+
+        for c in 0..chunks {
+            // SAFETY: c * 16 + 16 <= len，两个切片都至少有16个可读字节；wasm的v128_load允许非对齐地址
+            let (a_lane, b_lane) = unsafe {
+                (
+                    v128_load(a.as_ptr().add(c * 16) as *const v128),
+                    v128_load(b.as_ptr().add(c * 16) as *const v128),
+                )
+            };
+            let counts = u8x16_popcnt(op(a_lane, b_lane));
+            acc = u32x4_add(acc, u32x4_extadd_pairwise_u16x8(u16x8_extadd_pairwise_u8x16(counts)));
+        }
+
+        let mut count = u32x4_extract_lane::<0>(acc)
+            + u32x4_extract_lane::<1>(acc)
+            + u32x4_extract_lane::<2>(acc)
+            + u32x4_extract_lane::<3>(acc);
+        for i in chunks * 16..len {
+            let a_lane = u8x16_splat(a[i]);
+            let b_lane = u8x16_splat(b[i]);
+            count += (u8x16_extract_lane::<0>(op(a_lane, b_lane))).count_ones();
+        }
+        count
     }
-    
-    buffer
 }
 
 #[cfg(test)]
@@ -176,10 +655,153 @@ mod tests {
             vec![7, 8, 9],
         ];
         let indices = vec![0, 2];
-        let buffer = create_direct_packed_buffer(&vectors, &indices, 3);
-        
+        let (buffer, kept) = create_direct_packed_buffer(&vectors, &indices, 3, None);
+
         assert_eq!(buffer.len(), 6); // 2个向量 × 3字节
         assert_eq!(&buffer[0..3], &[1, 2, 3]);
         assert_eq!(&buffer[3..6], &[7, 8, 9]);
+        assert_eq!(kept, vec![0, 1]);
+
+        // 被排除的向量不进入缓冲区
+        let excluded = OrdinalBitset::from_ordinals(&[0]);
+        let (buffer, kept) = create_direct_packed_buffer(&vectors, &[0, 1, 2], 3, Some(&excluded));
+        assert_eq!(buffer, vec![4, 5, 6, 7, 8, 9]);
+        assert_eq!(kept, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_one_bit_u64_matches_scalar() {
+        let packed_dimension = 19;
+        let query: Vec<u8> = (0..packed_dimension).map(|i| (i * 37 + 11) as u8).collect();
+        let buffer: Vec<u8> = (0..packed_dimension * 5).map(|i| (i * 91 + 3) as u8).collect();
+
+        assert_eq!(
+            compute_batch_one_bit_dot_product_u64(&query, &buffer, 5, packed_dimension),
+            compute_batch_one_bit_dot_product_direct_packed(&query, &buffer, 5, packed_dimension),
+        );
+    }
+
+    #[test]
+    fn test_four_bit_bit_planes_matches_scalar() {
+        let dimension: usize = 77;
+        let packed_dimension = dimension.div_ceil(8);
+        let query: Vec<u8> = (0..dimension).map(|i| (i * 7 % 16) as u8).collect();
+        let mut buffer: Vec<u8> = (0..packed_dimension * 4).map(|i| (i * 53 + 7) as u8).collect();
+        // 清除最后一个字节中的填充位，与pack_as_binary的输出一致
+        for v in 0..4 {
+            buffer[v * packed_dimension + packed_dimension - 1] &= 0xF8;
+        }
+
+        assert_eq!(
+            compute_batch_four_bit_dot_product_bit_planes(&query, &buffer, 4, dimension),
+            compute_batch_four_bit_dot_product_direct_packed(&query, &buffer, 4, dimension),
+        );
+    }
+
+    #[test]
+    fn test_strided_kernels_match_gathered() {
+        let dimension: usize = 77;
+        let packed_dimension = dimension.div_ceil(8);
+        // 存储按16字节对齐，多出的字节不参与计算
+        let stride = 16;
+        let mut storage: Vec<u8> = (0..stride * 9).map(|i| (i * 53 + 7) as u8).collect();
+        for ord in 0..9 {
+            storage[ord * stride + packed_dimension - 1] &= 0xF8;
+        }
+        let ords = [7, 2, 8];
+        let gathered: Vec<u8> = ords.iter()
+            .flat_map(|&ord| storage[ord * stride..ord * stride + packed_dimension].to_vec())
+            .collect();
+
+        let query: Vec<u8> = (0..dimension).map(|i| (i * 7 % 16) as u8).collect();
+        assert_eq!(
+            compute_batch_four_bit_dot_product_strided(&query, &storage, &ords, dimension, stride),
+            compute_batch_four_bit_dot_product_direct_packed(&query, &gathered, 3, dimension),
+        );
+        let packed_query: Vec<u8> = (0..packed_dimension).map(|i| (i * 37 + 11) as u8).collect();
+        assert_eq!(
+            compute_batch_one_bit_dot_product_strided(&packed_query, &storage, &ords, stride),
+            compute_batch_one_bit_dot_product_u64(&packed_query, &gathered, 3, packed_dimension),
+        );
+    }
+
+    #[test]
+    fn test_accumulator_follows_bounds() {
+        assert_eq!(DotProductAccumulator::for_query_bits(4096, 4), DotProductAccumulator::I32);
+        assert_eq!(DotProductAccumulator::for_query_bits(i32::MAX as usize, 1), DotProductAccumulator::I32);
+        assert_eq!(DotProductAccumulator::for_query_bits(i32::MAX as usize / 15 + 1, 4), DotProductAccumulator::I64);
+        assert_eq!(DotProductAccumulator::for_bounds(usize::MAX, 255), DotProductAccumulator::I64);
+
+        // i64内核与i32内核在i32范围内一致，且只读取每个目标的前缀
+        let dimension = 20;
+        let query: Vec<u8> = (0..dimension).map(|i| (i * 13 % 256) as u8).collect();
+        let target: Vec<u8> = vec![0b1011_0110, 0b0101_1101, 0b0000_1111];
+        let expected: i64 = query.iter()
+            .enumerate()
+            .filter(|&(i, _)| target[i / 8] & (1 << (7 - i % 8)) != 0)
+            .map(|(_, &q)| q as i64)
+            .sum();
+        let mut buffer = target.clone();
+        buffer.resize(16, 0xFF);
+        assert_eq!(compute_batch_four_bit_dot_product_wide(&query, &buffer, 1, dimension, 16), vec![expected]);
+        assert_eq!(
+            compute_batch_four_bit_dot_product_wide(&query, &target, 1, dimension, 3),
+            compute_batch_four_bit_dot_product_bit_planes(&query, &target, 1, dimension).into_iter().map(i64::from).collect::<Vec<_>>(),
+        );
+        assert_eq!(
+            compute_batch_one_bit_dot_product_wide(&target, &buffer, 1, 16),
+            compute_batch_one_bit_dot_product_u64(&target, &target, 1, 3).into_iter().map(i64::from).collect::<Vec<_>>(),
+        );
+        let products = BatchDotProducts::I32(vec![3, -1]);
+        assert_eq!((products.len(), products.get(1), products.get(2)), (2, Some(-1), None));
+    }
+
+    #[test]
+    fn test_block_kernels_match_single_query() {
+        let dimension: usize = 77;
+        let packed_dimension = dimension.div_ceil(8);
+        let stride = 16;
+        let num_vectors = 150;
+        let mut buffer: Vec<u8> = (0..stride * num_vectors).map(|i| (i * 53 + 7) as u8).collect();
+        for t in 0..num_vectors {
+            buffer[t * stride + packed_dimension - 1] &= 0xF8;
+        }
+        let gathered: Vec<u8> = (0..num_vectors)
+            .flat_map(|t| buffer[t * stride..t * stride + packed_dimension].to_vec())
+            .collect();
+
+        let queries: Vec<Vec<u8>> = (0..3)
+            .map(|q| (0..dimension).map(|i| ((i * 7 + q * 5) % 16) as u8).collect())
+            .collect();
+        let query_refs: Vec<&[u8]> = queries.iter().map(Vec::as_slice).collect();
+        let block = compute_block_four_bit_dot_products(&query_refs, &buffer, num_vectors, dimension, stride);
+        for (q, query) in queries.iter().enumerate() {
+            assert_eq!(
+                &block[q * num_vectors..(q + 1) * num_vectors],
+                &compute_batch_four_bit_dot_product_direct_packed(query, &gathered, num_vectors, dimension)[..],
+            );
+        }
+
+        let packed_queries: Vec<Vec<u8>> = (0..3)
+            .map(|q| (0..packed_dimension).map(|i| (i * 37 + q * 11) as u8).collect())
+            .collect();
+        let packed_refs: Vec<&[u8]> = packed_queries.iter().map(Vec::as_slice).collect();
+        let block = compute_block_one_bit_dot_products(&packed_refs, &buffer, num_vectors, packed_dimension, stride);
+        for (q, query) in packed_queries.iter().enumerate() {
+            assert_eq!(
+                &block[q * num_vectors..(q + 1) * num_vectors],
+                &compute_batch_one_bit_dot_product_u64(query, &gathered, num_vectors, packed_dimension)[..],
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_query_bit_planes() {
+        let planes = build_query_bit_planes(&[15, 0, 1, 2, 0, 0, 0, 0, 8]);
+        assert_eq!(planes.len(), 4);
+        assert_eq!(planes[0], vec![0b10100000, 0]);
+        assert_eq!(planes[1], vec![0b10010000, 0]);
+        assert_eq!(planes[3], vec![0b10000000, 0b10000000]);
+        assert!(build_query_bit_planes(&[0, 0]).is_empty());
     }
 }
@@ -0,0 +1,145 @@
+//! 自适应批大小
+//!
+//! 批量评分时每批要把打包向量收集到连续缓冲区、计算点积再写回分数，
+//! 一批的工作集放得进缓存时最快。64维向量每个只有8字节，1536维则有192字节，
+//! 最优批大小相差一个数量级以上，因此按维度、k和缓存大小提示计算批大小，
+//! 并提供在实际设备上对候选批大小计时的基准
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::error_codes::BbqError;
+use crate::kernel_dispatch::dispatch_batch_four_bit;
+use crate::timer::{elapsed_ms, now_ms};
+
+/// 未设置缓存提示时假定的缓存大小（字节），接近常见设备的L2缓存
+pub const DEFAULT_CACHE_BYTES: usize = 256 * 1024;
+
+/// 批大小下限
+pub const MIN_BATCH_SIZE: usize = 64;
+
+/// 批大小上限
+pub const MAX_BATCH_SIZE: usize = 16_384;
+
+/// 每个向量除打包数据外的工作集：修正项16字节、分数4字节、（序号, 分数）结果16字节
+const PER_VECTOR_OVERHEAD: usize = 16 + 4 + 16;
+
+/// 基准中每个批大小至少计时的毫秒数
+const BENCHMARK_MIN_MS: f64 = 2.0;
+
+/// 基准使用的向量总数
+const BENCHMARK_VECTORS: usize = 16_384;
+
+/// 缓存大小提示（字节），0表示未设置
+static CACHE_SIZE_HINT: AtomicUsize = AtomicUsize::new(0);
+
+/// 设置缓存大小提示（字节），传入0恢复默认值
+pub fn set_cache_size_hint(bytes: usize) {
+    CACHE_SIZE_HINT.store(bytes, Ordering::Relaxed);
+}
+
+/// 当前使用的缓存大小（字节）
+pub fn cache_size_hint() -> usize {
+    match CACHE_SIZE_HINT.load(Ordering::Relaxed) {
+        0 => DEFAULT_CACHE_BYTES,
+        bytes => bytes,
+    }
+}
+
+/// 按维度和k计算推荐的批大小
+///
+/// 缓存预算先扣除4位查询向量和前k个结果，剩余部分按每个向量的工作集
+/// （打包向量加修正项、分数和结果）平分，结果限制在 [MIN_BATCH_SIZE, MAX_BATCH_SIZE]
+///
+/// # 参数
+/// * `dimension` - 向量维度
+/// * `k` - 返回的结果数量
+/// * `override_size` - 显式指定的批大小，优先于启发式结果
+pub fn recommended_batch_size(dimension: usize, k: usize, override_size: Option<usize>) -> usize {
+    if let Some(size) = override_size.filter(|&size| size > 0) {
+        return size;
+    }
+    let reserved = dimension.saturating_add(k.saturating_mul(16));
+    let budget = cache_size_hint().saturating_sub(reserved);
+    let per_vector = dimension.div_ceil(8) + PER_VECTOR_OVERHEAD;
+    (budget / per_vector).clamp(MIN_BATCH_SIZE, MAX_BATCH_SIZE)
+}
+
+/// 单个批大小的计时结果
+#[derive(Debug, Clone)]
+pub struct BatchSizeTiming {
+    /// 批大小
+    pub batch_size: usize,
+    /// 每个向量的平均耗时（微秒）
+    pub us_per_vector: f64,
+}
+
+/// 对候选批大小计时，用于验证启发式结果
+///
+/// 每批先把分散存放的打包向量收集到连续缓冲区，再用选中的4位内核计算点积，
+/// 与搜索时的批量评分路径一致
+///
+/// # 参数
+/// * `dimension` - 向量维度
+/// * `batch_sizes` - 候选批大小
+pub fn benchmark_batch_sizes(dimension: usize, batch_sizes: &[usize]) -> Result<Vec<BatchSizeTiming>, BbqError> {
+    if dimension == 0 {
+        return Err(BbqError::invalid_argument("维度必须大于0", "dimension must be greater than 0"));
+    }
+    let packed_size = dimension.div_ceil(8);
+    let mut rng = fastrand::Rng::with_seed(0xba7c);
+    let query: Vec<u8> = (0..dimension).map(|_| rng.u8(0..16)).collect();
+    let vectors: Vec<Vec<u8>> = (0..BENCHMARK_VECTORS)
+        .map(|_| (0..packed_size).map(|_| rng.u8(..)).collect())
+        .collect();
+
+    let mut timings = Vec::with_capacity(batch_sizes.len());
+    for &batch_size in batch_sizes {
+        if batch_size == 0 {
+            return Err(BbqError::invalid_argument("批大小必须大于0", "batch size must be greater than 0"));
+        }
+        let pass = || {
+            let mut buffer = Vec::with_capacity(batch_size.min(BENCHMARK_VECTORS) * packed_size);
+            for batch in vectors.chunks(batch_size) {
+                buffer.clear();
+                for vector in batch {
+                    buffer.extend_from_slice(vector);
+                }
+                std::hint::black_box(dispatch_batch_four_bit(&query, &buffer, batch.len(), dimension));
+            }
+        };
+
+        // 先运行一次，排除首次分配的开销
+        pass();
+        let start = now_ms();
+        let mut reps = 0usize;
+        while reps == 0 || elapsed_ms(start) < BENCHMARK_MIN_MS {
+            pass();
+            reps += 1;
+        }
+        timings.push(BatchSizeTiming {
+            batch_size,
+            us_per_vector: elapsed_ms(start) * 1000.0 / (reps * BENCHMARK_VECTORS) as f64,
+        });
+    }
+    Ok(timings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_size_scales_with_dimension() {
+        let small = recommended_batch_size(64, 10, None);
+        let large = recommended_batch_size(1536, 10, None);
+        assert!(small >= 4 * large, "{} vs {}", small, large);
+        assert!((MIN_BATCH_SIZE..=MAX_BATCH_SIZE).contains(&large));
+        assert!(recommended_batch_size(1536, 5000, None) <= large);
+        assert_eq!(recommended_batch_size(1536, 10, Some(777)), 777);
+        assert_eq!(recommended_batch_size(1_000_000, 10, None), MIN_BATCH_SIZE);
+
+        let timings = benchmark_batch_sizes(128, &[256, 4096]).unwrap();
+        assert_eq!(timings.len(), 2);
+        assert!(timings.iter().all(|t| t.us_per_vector > 0.0));
+    }
+}
@@ -0,0 +1,973 @@
+//! 高层门面API
+//!
+//! 为浏览器演示等场景提供的简化入口：只需要维度和度量方式即可
+//! 逐条添加向量、查询、保存和加载，内部复用QuantizedIndex完成量化与搜索。
+//!
+//! 每次写入（添加/覆盖/删除）都会递增版本号，`export_delta` 可以导出
+//! 某个版本之后的增量变更，`apply_delta` 在另一个实例上重放，
+//! 便于浏览器应用把增量同步到服务器或IndexedDB而不必每次上传完整快照
+//!
+//! 公开方法返回带错误码的 `BbqError`，消息语言见 `error_codes`
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::quantized_index::{PackedVectorRecord, QuantizedIndex, QuantizedIndexConfig};
+use crate::warmup::WarmupReport;
+use crate::embedding_cache::EmbeddingCache;
+pub use crate::vector_similarity::parse_metric;
+use crate::vector_similarity::SimilarityFunction;
+use crate::memory_limits::{checked_region_len, try_with_capacity};
+use crate::byte_reader::{metric_from_code, metric_to_code, write_f32, ByteReader};
+use crate::warm_stats::WarmStats;
+use crate::error_codes::{BbqError, Subject};
+
+/// 快照文件魔数
+pub(crate) const BBQ_MAGIC: &[u8; 4] = b"BBQF";
+
+/// 快照格式版本（版本2增加了写入版本号，版本3增加了使用统计，版本4增加了重复ID策略和历史版本）
+const BBQ_FORMAT_VERSION: u8 = 4;
+
+/// 增量文件魔数
+const BBQ_DELTA_MAGIC: &[u8; 4] = b"BBQD";
+
+/// 增量格式版本
+const BBQ_DELTA_FORMAT_VERSION: u8 = 1;
+
+/// 门面构造选项
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub struct BbqOptions {
+    /// 向量维度
+    pub dims: usize,
+    /// 度量方式: "cosine" | "euclidean" | "dot_product"（默认cosine）
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub metric: Option<String>,
+    /// 重复ID的处理策略: "replace" | "ignore" | "version"（默认replace）
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub duplicates: Option<String>,
+}
+
+/// 写入已存在的外部ID时的处理策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// 覆盖原向量（默认）
+    #[default]
+    Replace,
+    /// 保留原向量，忽略新的写入
+    Ignore,
+    /// 保留原向量作为历史版本，查询只返回最新版本
+    Version,
+}
+
+impl DuplicatePolicy {
+    /// 根据名称解析策略
+    ///
+    /// # 参数
+    /// * `name` - "replace" | "ignore" | "version"
+    pub fn from_name(name: &str) -> Result<Self, BbqError> {
+        match name.to_lowercase().as_str() {
+            "replace" => Ok(DuplicatePolicy::Replace),
+            "ignore" => Ok(DuplicatePolicy::Ignore),
+            "version" | "versioned" => Ok(DuplicatePolicy::Version),
+            _ => Err(BbqError::invalid_argument(format!("不支持的重复ID策略: {}", name), format!("unsupported duplicate ID policy: {}", name))),
+        }
+    }
+
+    /// 策略名称
+    pub fn name(self) -> &'static str {
+        match self {
+            DuplicatePolicy::Replace => "replace",
+            DuplicatePolicy::Ignore => "ignore",
+            DuplicatePolicy::Version => "version",
+        }
+    }
+
+    fn code(self) -> u8 {
+        match self {
+            DuplicatePolicy::Replace => 0,
+            DuplicatePolicy::Ignore => 1,
+            DuplicatePolicy::Version => 2,
+        }
+    }
+
+    fn from_code(code: u8) -> Result<Self, BbqError> {
+        match code {
+            0 => Ok(DuplicatePolicy::Replace),
+            1 => Ok(DuplicatePolicy::Ignore),
+            2 => Ok(DuplicatePolicy::Version),
+            _ => Err(BbqError::corrupt_data(format!("无效的重复ID策略代码: {}", code), format!("invalid duplicate ID policy code: {}", code))),
+        }
+    }
+}
+
+/// 版本模式下被新写入取代的向量
+#[derive(Debug, Clone, PartialEq)]
+struct ArchivedVersion {
+    id: String,
+    vector: Vec<f32>,
+    /// 写入该向量时的版本号
+    version: u64,
+}
+
+/// 门面查询结果
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct BbqHit {
+    /// 外部ID
+    pub id: String,
+    /// 相似性分数
+    pub score: f32,
+}
+
+/// 应用增量的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct DeltaSummary {
+    /// 增量的起始版本（不含）
+    pub from_version: u64,
+    /// 增量的结束版本（含）
+    pub to_version: u64,
+    /// 写入的向量数量
+    pub upserts: usize,
+    /// 删除的向量数量
+    pub deletes: usize,
+}
+
+/// 高层门面：外部ID + 原始向量 + 量化索引
+pub struct Bbq {
+    dims: usize,
+    metric: SimilarityFunction,
+    ids: Vec<String>,
+    vectors: Vec<Vec<f32>>,
+    /// 每个向量最后一次写入时的版本号
+    entry_versions: Vec<u64>,
+    /// 删除记录：(ID, 删除时的版本号)
+    tombstones: Vec<(String, u64)>,
+    /// 当前版本号，每次写入递增
+    version: u64,
+    /// 删除记录从该版本之后才完整（加载快照时删除历史丢失）
+    history_floor: u64,
+    /// 重复ID的处理策略
+    duplicate_policy: DuplicatePolicy,
+    /// 版本模式下被取代的向量（按写入顺序），不参与查询
+    archived: Vec<ArchivedVersion>,
+    index: QuantizedIndex,
+    /// 自上次构建后是否有新增向量
+    dirty: bool,
+    /// 是否已冻结为只读
+    frozen: bool,
+}
+
+impl Bbq {
+    /// 创建新的门面实例
+    pub fn new(dims: usize, metric: SimilarityFunction) -> Result<Self, BbqError> {
+        if dims == 0 {
+            return Err(BbqError::invalid_argument("维度必须大于0", "dimension must be greater than 0"));
+        }
+
+        let index = QuantizedIndex::new(QuantizedIndexConfig {
+            similarity_function: metric,
+            ..QuantizedIndexConfig::default()
+        })?;
+
+        Ok(Self {
+            dims,
+            metric,
+            ids: Vec::new(),
+            vectors: Vec::new(),
+            entry_versions: Vec::new(),
+            tombstones: Vec::new(),
+            version: 0,
+            history_floor: 0,
+            duplicate_policy: DuplicatePolicy::default(),
+            archived: Vec::new(),
+            index,
+            dirty: false,
+            frozen: false,
+        })
+    }
+
+    /// 根据构造选项创建实例
+    pub fn from_options(options: &BbqOptions) -> Result<Self, BbqError> {
+        let metric = match options.metric.as_deref() {
+            Some(name) => parse_metric(name)?,
+            None => SimilarityFunction::Cosine,
+        };
+        let mut bbq = Self::new(options.dims, metric)?;
+        if let Some(name) = options.duplicates.as_deref() {
+            bbq.set_duplicate_policy(DuplicatePolicy::from_name(name)?);
+        }
+        Ok(bbq)
+    }
+
+    /// 重复ID的处理策略
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.duplicate_policy
+    }
+
+    /// 设置重复ID的处理策略，只影响之后的写入
+    ///
+    /// 离开版本模式时已保存的历史版本保留，直到对应ID被删除
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy) {
+        self.duplicate_policy = policy;
+    }
+
+    /// 冻结为只读：之后 `add`、`remove`、`apply_delta` 等写入都返回错误
+    ///
+    /// 用于从CDN加载的已发布快照，防止内存中的数据与规范快照分叉；查询仍会按需构建索引
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    /// 解除只读冻结
+    pub fn unfreeze(&mut self) {
+        self.frozen = false;
+    }
+
+    /// 是否已冻结为只读
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    fn ensure_writable(&self) -> Result<(), BbqError> {
+        if self.frozen {
+            return Err(BbqError::read_only(Subject::new("实例", "instance")));
+        }
+        Ok(())
+    }
+
+    /// 获取向量维度
+    pub fn dims(&self) -> usize {
+        self.dims
+    }
+
+    /// 获取度量方式
+    pub fn metric(&self) -> SimilarityFunction {
+        self.metric
+    }
+
+    /// 获取向量数量
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// 当前版本号
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// 按插入顺序排列的原始向量
+    pub(crate) fn vectors(&self) -> &[Vec<f32>] {
+        &self.vectors
+    }
+
+    /// 是否包含指定ID
+    pub fn contains(&self, id: &str) -> bool {
+        self.position(id).is_some()
+    }
+
+    /// 获取指定ID的原始向量
+    pub fn get(&self, id: &str) -> Option<&[f32]> {
+        self.position(id).map(|ord| self.vectors[ord].as_slice())
+    }
+
+    /// 获取指定ID的全部版本（按写入顺序，最后一项为当前版本），返回（写入版本号, 向量）
+    ///
+    /// 只有版本模式下写入的重复ID才有历史版本
+    pub fn versions(&self, id: &str) -> Vec<(u64, &[f32])> {
+        let mut versions: Vec<(u64, &[f32])> = self.archived.iter()
+            .filter(|archived| archived.id == id)
+            .map(|archived| (archived.version, archived.vector.as_slice()))
+            .collect();
+        if let Some(ord) = self.position(id) {
+            versions.push((self.entry_versions[ord], self.vectors[ord].as_slice()));
+        }
+        versions
+    }
+
+    /// 添加向量，ID已存在时按重复ID策略处理
+    ///
+    /// # 返回
+    /// 向量被写入时返回true；忽略策略下ID已存在时返回false
+    pub fn add(&mut self, id: &str, vector: &[f32]) -> Result<bool, BbqError> {
+        self.ensure_writable()?;
+        self.validate_vector(vector)?;
+        if self.duplicate_policy == DuplicatePolicy::Ignore && self.contains(id) {
+            return Ok(false);
+        }
+        self.version += 1;
+        self.upsert_at(id, vector.to_vec(), self.version);
+        Ok(true)
+    }
+
+    /// 按文本添加向量，嵌入优先从缓存读取
+    ///
+    /// 未命中时调用 `embed` 计算嵌入并写入缓存；写入索引的始终是缓存中的半精度嵌入
+    ///
+    /// # 返回
+    /// 命中缓存时返回true
+    pub fn add_text<F>(&mut self, id: &str, text: &str, cache: &mut EmbeddingCache, embed: F) -> Result<bool, BbqError>
+    where
+        F: FnOnce(&str) -> Result<Vec<f32>, BbqError>,
+    {
+        if self.add_cached(id, text, cache)? {
+            return Ok(true);
+        }
+        let embedding = cache.put(text, &embed(text)?)?;
+        self.add(id, &embedding)?;
+        Ok(false)
+    }
+
+    /// 文本的嵌入已缓存时直接添加，未命中时不做修改
+    ///
+    /// 供嵌入模型只能异步调用的JS使用：未命中时由调用方计算嵌入后 `put` 进缓存再 `add`
+    ///
+    /// # 返回
+    /// 命中缓存并已添加时返回true
+    pub fn add_cached(&mut self, id: &str, text: &str, cache: &mut EmbeddingCache) -> Result<bool, BbqError> {
+        self.ensure_writable()?;
+        if cache.dims() != self.dims {
+            return Err(BbqError::dimension_mismatch(
+                format!("嵌入缓存维度 {} 与索引维度 {} 不匹配", cache.dims(), self.dims),
+                format!("embedding cache dimension {} does not match index dimension {}", cache.dims(), self.dims),
+            ));
+        }
+        match cache.get(text) {
+            Some(embedding) => {
+                self.add(id, &embedding)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// 删除向量
+    ///
+    /// # 返回
+    /// ID存在并被删除时返回true
+    pub fn remove(&mut self, id: &str) -> Result<bool, BbqError> {
+        self.ensure_writable()?;
+        Ok(match self.position(id) {
+            Some(ord) => {
+                self.version += 1;
+                let live: Vec<usize> = (0..self.len()).filter(|&other| other != ord).collect();
+                self.index.remap_warm_stats(&live);
+                self.ids.remove(ord);
+                self.vectors.remove(ord);
+                self.entry_versions.remove(ord);
+                self.archived.retain(|archived| archived.id != id);
+                self.tombstones.push((id.to_string(), self.version));
+                self.dirty = true;
+                true
+            }
+            None => false,
+        })
+    }
+
+    fn validate_vector(&self, vector: &[f32]) -> Result<(), BbqError> {
+        if vector.len() != self.dims {
+            return Err(BbqError::dimension_mismatch(
+                format!("向量维度 {} 与索引维度 {} 不匹配", vector.len(), self.dims),
+                format!("vector dimension {} does not match index dimension {}", vector.len(), self.dims),
+            ));
+        }
+        if let Some(j) = vector.iter().position(|v| !v.is_finite()) {
+            return Err(BbqError::invalid_value(
+                format!("向量位置 {} 包含无效值: {}", j, vector[j]),
+                format!("vector position {} contains an invalid value: {}", j, vector[j]),
+            ));
+        }
+        Ok(())
+    }
+
+    /// 以指定版本号写入向量（调用方已校验向量），版本模式下保留被取代的向量
+    fn upsert_at(&mut self, id: &str, vector: Vec<f32>, version: u64) {
+        match self.position(id) {
+            Some(ord) => {
+                let previous = std::mem::replace(&mut self.vectors[ord], vector);
+                let previous_version = std::mem::replace(&mut self.entry_versions[ord], version);
+                if self.duplicate_policy == DuplicatePolicy::Version {
+                    self.archived.push(ArchivedVersion { id: id.to_string(), vector: previous, version: previous_version });
+                }
+            }
+            None => {
+                self.ids.push(id.to_string());
+                self.vectors.push(vector);
+                self.entry_versions.push(version);
+            }
+        }
+        self.tombstones.retain(|(deleted, _)| deleted != id);
+        self.dirty = true;
+    }
+
+    fn position(&self, id: &str) -> Option<usize> {
+        self.ids.iter().position(|existing| existing == id)
+    }
+
+    /// 查询最相似的k个向量
+    ///
+    /// 有未构建的新增向量时会先重建量化索引
+    pub fn query(&mut self, vector: &[f32], k: usize) -> Result<Vec<BbqHit>, BbqError> {
+        if self.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.ensure_built()?;
+
+        let results = self.index.search_nearest_neighbors(vector, k)?;
+        Ok(results
+            .into_iter()
+            .map(|result| BbqHit {
+                id: self.ids[result.index].clone(),
+                score: result.score,
+            })
+            .collect())
+    }
+
+    /// 按外部ID批量获取打包向量、修正项和（可选）重建向量
+    ///
+    /// 供混合检索在别处用新查询对关键词召回的候选重新评分；
+    /// 有未构建的新增向量时会先重建量化索引
+    ///
+    /// # 返回
+    /// 与 `ids` 一一对应，不存在的ID为None
+    pub fn get_vectors_by_ids(&mut self, ids: &[&str], include_reconstructions: bool) -> Result<Vec<Option<PackedVectorRecord>>, BbqError> {
+        if self.is_empty() {
+            return Ok(vec![None; ids.len()]);
+        }
+        self.ensure_built()?;
+
+        let positions: Vec<Option<usize>> = ids.iter().map(|id| self.position(id)).collect();
+        let found: Vec<usize> = positions.iter().flatten().copied().collect();
+        let mut records = self.index.get_packed_vectors(&found, include_reconstructions)?.into_iter();
+        Ok(positions.into_iter()
+            .map(|position| position.and_then(|_| records.next()))
+            .collect())
+    }
+
+    /// 使用统计（查询次数、平均k、热点向量），随快照保存
+    pub fn warm_stats(&self) -> WarmStats {
+        self.index.get_warm_stats()
+    }
+
+    /// 预热最常被查询命中的向量
+    ///
+    /// 加载快照后调用，按快照中保存的使用统计只预热热点向量，
+    /// 并按统计建议调整结果缓存容量
+    pub fn warmup(&mut self, limit: usize) -> Result<WarmupReport, BbqError> {
+        if self.is_empty() {
+            return Ok(WarmupReport::default());
+        }
+        self.ensure_built()?;
+        let capacity = self.index.get_warm_stats().suggested_result_cache_capacity();
+        self.index.set_result_cache_capacity(capacity);
+        self.index.warmup_hot(limit)
+    }
+
+    /// 序列化为字节数组
+    ///
+    /// 格式（小端）：魔数 | 格式版本 | 度量 | 维度 | 数量 | 写入版本号 | (ID长度, ID, 向量)* |
+    /// 重复ID策略 | 历史版本数量 | (写入版本号, ID长度, ID, 向量)* | 使用统计长度 | 使用统计
+    pub fn save(&self) -> Result<Vec<u8>, BbqError> {
+        let entry_bytes = checked_region_len(self.dims, 4, Subject::new("BBQ快照向量", "BBQ snapshot vector"))?.saturating_add(8);
+        let mut bytes = try_with_capacity(checked_region_len(self.len(), entry_bytes, Subject::new("BBQ快照", "BBQ snapshot"))?.saturating_add(22), Subject::new("BBQ快照", "BBQ snapshot"))?;
+        bytes.extend_from_slice(BBQ_MAGIC);
+        bytes.push(BBQ_FORMAT_VERSION);
+        bytes.push(metric_to_code(self.metric));
+        bytes.extend_from_slice(&(self.dims as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+
+        for (id, vector) in self.ids.iter().zip(self.vectors.iter()) {
+            write_entry(&mut bytes, id, vector);
+        }
+
+        bytes.push(self.duplicate_policy.code());
+        bytes.extend_from_slice(&(self.archived.len() as u32).to_le_bytes());
+        for archived in &self.archived {
+            bytes.extend_from_slice(&archived.version.to_le_bytes());
+            write_entry(&mut bytes, &archived.id, &archived.vector);
+        }
+
+        let stats = self.index.get_warm_stats().serialize();
+        bytes.extend_from_slice(&(stats.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&stats);
+
+        Ok(bytes)
+    }
+
+    /// 从字节数组加载
+    pub fn load(bytes: &[u8]) -> Result<Self, BbqError> {
+        let mut reader = ByteReader::new(bytes);
+
+        if reader.take(4)? != BBQ_MAGIC {
+            return Err(BbqError::corrupt_data("无效的BBQ快照：魔数不匹配", "invalid BBQ snapshot: magic mismatch"));
+        }
+        let format_version = reader.read_u8()?;
+        if format_version == 0 || format_version > BBQ_FORMAT_VERSION {
+            return Err(BbqError::unsupported_version(
+                format!("不支持的BBQ快照版本: {}", format_version),
+                format!("unsupported BBQ snapshot version: {}", format_version),
+            ));
+        }
+        let metric = metric_from_code(reader.read_u8()?)?;
+        let dims = reader.read_u32()? as usize;
+        let count = reader.read_u32()? as usize;
+        // 版本1的快照没有写入版本号
+        let version = if format_version >= 2 { reader.read_u64()? } else { 0 };
+
+        // 在分配之前确认声明的数量和维度与实际数据长度相符
+        let vector_bytes = checked_region_len(dims, 4, Subject::new("BBQ快照向量", "BBQ snapshot vector"))?;
+        let min_entry_bytes = vector_bytes.checked_add(4)
+            .ok_or_else(|| BbqError::corrupt_data("无效的BBQ快照：维度过大", "invalid BBQ snapshot: dimension is too large"))?;
+        if checked_region_len(count, min_entry_bytes, Subject::new("BBQ快照", "BBQ snapshot"))? > reader.remaining() {
+            return Err(BbqError::corrupt_data("无效的BBQ快照：数据被截断", "invalid BBQ snapshot: data is truncated"));
+        }
+
+        let mut bbq = Self::new(dims, metric)?;
+        for _ in 0..count {
+            let (id, vector) = reader.read_entry(dims)?;
+            bbq.validate_vector(&vector)?;
+            bbq.upsert_at(&id, vector, version);
+        }
+
+        // 版本4之前的快照没有重复ID策略和历史版本
+        if format_version >= 4 {
+            bbq.duplicate_policy = DuplicatePolicy::from_code(reader.read_u8()?)?;
+            let archived_count = reader.read_u32()? as usize;
+            if checked_region_len(archived_count, min_entry_bytes.saturating_add(8), Subject::new("BBQ快照历史版本", "BBQ snapshot history"))? > reader.remaining() {
+                return Err(BbqError::corrupt_data("无效的BBQ快照：数据被截断", "invalid BBQ snapshot: data is truncated"));
+            }
+            for _ in 0..archived_count {
+                let archived_version = reader.read_u64()?;
+                let (id, vector) = reader.read_entry(dims)?;
+                bbq.validate_vector(&vector)?;
+                bbq.archived.push(ArchivedVersion { id, vector, version: archived_version });
+            }
+        }
+
+        // 版本3之前的快照没有使用统计
+        if format_version >= 3 {
+            let stats_len = reader.read_u32()? as usize;
+            let stats = WarmStats::deserialize(reader.take(stats_len)?)?;
+            bbq.index.set_warm_stats(stats);
+        }
+
+        if !reader.is_empty() {
+            return Err(BbqError::corrupt_data("无效的BBQ快照：存在多余数据", "invalid BBQ snapshot: trailing data"));
+        }
+
+        // 快照中不含删除记录，只能从快照版本开始导出增量
+        bbq.version = version;
+        bbq.history_floor = version;
+        Ok(bbq)
+    }
+
+    /// 导出指定版本之后的增量变更
+    ///
+    /// 格式（小端）：魔数 | 格式版本 | 度量 | 维度 | 起始版本 | 结束版本 |
+    /// 写入数量 | (ID长度, ID, 向量)* | 删除数量 | (ID长度, ID)*
+    ///
+    /// # 参数
+    /// * `since_version` - 接收方已同步到的版本号
+    pub fn export_delta(&self, since_version: u64) -> Result<Vec<u8>, BbqError> {
+        if since_version > self.version {
+            return Err(BbqError::out_of_range(
+                format!("增量起始版本 {} 超过当前版本 {}", since_version, self.version),
+                format!("delta start version {} exceeds the current version {}", since_version, self.version),
+            ));
+        }
+        if since_version < self.history_floor {
+            return Err(BbqError::out_of_range(
+                format!("版本 {} 之前的删除记录已不可用，请改为同步完整快照（最早可导出版本 {}）", since_version, self.history_floor),
+                format!("deletes before version {} are no longer available; sync a full snapshot instead (earliest exportable version {})", since_version, self.history_floor),
+            ));
+        }
+
+        let upserts: Vec<usize> = (0..self.len())
+            .filter(|&ord| self.entry_versions[ord] > since_version)
+            .collect();
+        let deletes: Vec<&str> = self.tombstones.iter()
+            .filter(|(_, version)| *version > since_version)
+            .map(|(id, _)| id.as_str())
+            .collect();
+
+        let entry_bytes = checked_region_len(self.dims, 4, Subject::new("BBQ增量向量", "BBQ delta vector"))?.saturating_add(8);
+        let mut bytes = try_with_capacity(checked_region_len(upserts.len(), entry_bytes, Subject::new("BBQ增量", "BBQ delta"))?.saturating_add(34), Subject::new("BBQ增量", "BBQ delta"))?;
+        bytes.extend_from_slice(BBQ_DELTA_MAGIC);
+        bytes.push(BBQ_DELTA_FORMAT_VERSION);
+        bytes.push(metric_to_code(self.metric));
+        bytes.extend_from_slice(&(self.dims as u32).to_le_bytes());
+        bytes.extend_from_slice(&since_version.to_le_bytes());
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+
+        bytes.extend_from_slice(&(upserts.len() as u32).to_le_bytes());
+        for ord in upserts {
+            write_entry(&mut bytes, &self.ids[ord], &self.vectors[ord]);
+        }
+        bytes.extend_from_slice(&(deletes.len() as u32).to_le_bytes());
+        for id in deletes {
+            bytes.extend_from_slice(&(id.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(id.as_bytes());
+        }
+
+        Ok(bytes)
+    }
+
+    /// 应用增量变更
+    ///
+    /// 增量中的写入和删除作为本地写入重放（递增本地版本号），
+    /// 整个增量先完整解析校验，失败时不修改任何数据。
+    /// 写入在导出方已按其策略处理过，这里总是生效；版本模式下被取代的向量仍作为历史版本保留
+    pub fn apply_delta(&mut self, bytes: &[u8]) -> Result<DeltaSummary, BbqError> {
+        self.ensure_writable()?;
+        let mut reader = ByteReader::new(bytes);
+
+        if reader.take(4)? != BBQ_DELTA_MAGIC {
+            return Err(BbqError::corrupt_data("无效的BBQ增量：魔数不匹配", "invalid BBQ delta: magic mismatch"));
+        }
+        let format_version = reader.read_u8()?;
+        if format_version != BBQ_DELTA_FORMAT_VERSION {
+            return Err(BbqError::unsupported_version(
+                format!("不支持的BBQ增量版本: {}", format_version),
+                format!("unsupported BBQ delta version: {}", format_version),
+            ));
+        }
+        let metric = metric_from_code(reader.read_u8()?)?;
+        let dims = reader.read_u32()? as usize;
+        if metric != self.metric || dims != self.dims {
+            return Err(BbqError::dimension_mismatch(
+                format!("增量的度量/维度 ({:?}, {}) 与当前实例 ({:?}, {}) 不一致", metric, dims, self.metric, self.dims),
+                format!("delta metric/dimension ({:?}, {}) differ from this instance ({:?}, {})", metric, dims, self.metric, self.dims),
+            ));
+        }
+        let from_version = reader.read_u64()?;
+        let to_version = reader.read_u64()?;
+
+        let upsert_count = reader.read_u32()? as usize;
+        let min_entry_bytes = checked_region_len(dims, 4, Subject::new("BBQ增量向量", "BBQ delta vector"))?
+            .checked_add(4)
+            .ok_or_else(|| BbqError::corrupt_data("无效的BBQ增量：维度过大", "invalid BBQ delta: dimension is too large"))?;
+        if checked_region_len(upsert_count, min_entry_bytes, Subject::new("BBQ增量", "BBQ delta"))? > reader.remaining() {
+            return Err(BbqError::corrupt_data("无效的BBQ增量：数据被截断", "invalid BBQ delta: data is truncated"));
+        }
+        let mut upserts = Vec::with_capacity(upsert_count);
+        for _ in 0..upsert_count {
+            let (id, vector) = reader.read_entry(dims)?;
+            self.validate_vector(&vector)?;
+            upserts.push((id, vector));
+        }
+
+        let delete_count = reader.read_u32()? as usize;
+        if checked_region_len(delete_count, 4, Subject::new("BBQ增量", "BBQ delta"))? > reader.remaining() {
+            return Err(BbqError::corrupt_data("无效的BBQ增量：数据被截断", "invalid BBQ delta: data is truncated"));
+        }
+        let mut deletes = Vec::with_capacity(delete_count);
+        for _ in 0..delete_count {
+            deletes.push(reader.read_id()?);
+        }
+
+        if !reader.is_empty() {
+            return Err(BbqError::corrupt_data("无效的BBQ增量：存在多余数据", "invalid BBQ delta: trailing data"));
+        }
+
+        let summary = DeltaSummary {
+            from_version,
+            to_version,
+            upserts: upserts.len(),
+            deletes: deletes.len(),
+        };
+        for (id, vector) in upserts {
+            self.version += 1;
+            self.upsert_at(&id, vector, self.version);
+        }
+        for id in deletes {
+            self.remove(&id)?;
+        }
+
+        Ok(summary)
+    }
+
+    /// 如有新增向量则重建索引
+    fn ensure_built(&mut self) -> Result<(), BbqError> {
+        if self.dirty {
+            self.index.build_index(&self.vectors)?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+}
+
+/// 写入一条 (ID长度, ID, 向量) 记录
+pub(crate) fn write_entry(bytes: &mut Vec<u8>, id: &str, vector: &[f32]) {
+    bytes.extend_from_slice(&(id.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(id.as_bytes());
+    for &value in vector {
+        write_f32(bytes, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector_utils::create_random_vector;
+
+    #[test]
+    fn test_add_and_query() {
+        let mut bbq = Bbq::new(32, SimilarityFunction::Cosine).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..20)
+            .map(|_| create_random_vector(32, -1.0, 1.0))
+            .collect();
+        for (i, vector) in vectors.iter().enumerate() {
+            bbq.add(&format!("doc-{}", i), vector).unwrap();
+        }
+
+        let hits = bbq.query(&vectors[7], 3).unwrap();
+        assert_eq!(hits.len(), 3);
+        assert!(hits.iter().any(|hit| hit.id == "doc-7"));
+
+        // 重复ID覆盖而不是新增
+        bbq.add("doc-7", &vectors[0]).unwrap();
+        assert_eq!(bbq.len(), 20);
+    }
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let mut bbq = Bbq::new(16, SimilarityFunction::Euclidean).unwrap();
+        for i in 0..5 {
+            bbq.add(&i.to_string(), &create_random_vector(16, -1.0, 1.0)).unwrap();
+        }
+
+        let bytes = bbq.save().unwrap();
+        let mut loaded = Bbq::load(&bytes).unwrap();
+        assert_eq!(loaded.len(), 5);
+        assert_eq!(loaded.dims(), 16);
+        assert_eq!(loaded.metric(), SimilarityFunction::Euclidean);
+        assert_eq!(loaded.save().unwrap(), bytes);
+
+        let query = create_random_vector(16, -1.0, 1.0);
+        assert_eq!(loaded.query(&query, 2).unwrap().len(), 2);
+
+        assert!(Bbq::load(&bytes[..bytes.len() - 1]).is_err());
+
+        // 声明超大数量的快照应在分配前被拒绝
+        let mut forged = bytes[..22].to_vec();
+        forged[10..14].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Bbq::load(&forged).is_err());
+    }
+
+    #[test]
+    fn test_save_is_byte_for_byte_deterministic() {
+        let mut bbq = Bbq::new(2, SimilarityFunction::Euclidean).unwrap();
+        bbq.add("a", &[1.0, -0.0]).unwrap();
+        let mut expected = Vec::new();
+        expected.extend_from_slice(b"BBQF");
+        expected.extend_from_slice(&[4, 0]);
+        expected.extend_from_slice(&[2, 0, 0, 0, 1, 0, 0, 0]);
+        expected.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+        expected.extend_from_slice(&[1, 0, 0, 0, b'a']);
+        // 负零按正零写入
+        expected.extend_from_slice(&[0x00, 0x00, 0x80, 0x3f, 0x00, 0x00, 0x00, 0x00]);
+        // 覆盖策略，没有历史版本
+        expected.extend_from_slice(&[0, 0, 0, 0, 0]);
+        expected.extend_from_slice(&[41, 0, 0, 0]);
+        expected.extend_from_slice(b"BBQW");
+        expected.push(1);
+        expected.extend_from_slice(&[0; 36]);
+        assert_eq!(bbq.save().unwrap(), expected);
+
+        // 使用统计存放在HashMap中，两个实例的遍历顺序不同，快照仍须相同
+        let vectors: Vec<Vec<f32>> = (0..40).map(|_| create_random_vector(8, -1.0, 1.0)).collect();
+        let build = || {
+            let mut bbq = Bbq::new(8, SimilarityFunction::Cosine).unwrap();
+            for (i, vector) in vectors.iter().enumerate() {
+                bbq.add(&format!("doc-{}", i), vector).unwrap();
+            }
+            for vector in &vectors[..10] {
+                bbq.query(vector, 6).unwrap();
+            }
+            bbq.save().unwrap()
+        };
+        let bytes = build();
+        assert_eq!(build(), bytes);
+        assert_eq!(Bbq::load(&bytes).unwrap().save().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_warm_stats_survive_reload() {
+        let mut bbq = Bbq::new(16, SimilarityFunction::Cosine).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..30).map(|_| create_random_vector(16, -1.0, 1.0)).collect();
+        for (i, vector) in vectors.iter().enumerate() {
+            bbq.add(&format!("doc-{}", i), vector).unwrap();
+        }
+        let mut hit_ids: Vec<String> = Vec::new();
+        for _ in 0..3 {
+            hit_ids = bbq.query(&vectors[12], 4).unwrap().into_iter().map(|hit| hit.id).collect();
+        }
+        hit_ids.retain(|id| id != "doc-0");
+        hit_ids.sort();
+        let hot_ids = |bbq: &Bbq| {
+            let mut ids: Vec<String> = bbq.warm_stats().hot_ordinals(4).into_iter().map(|ord| bbq.ids[ord].clone()).collect();
+            ids.retain(|id| id != "doc-0");
+            ids.sort();
+            ids
+        };
+        assert_eq!(hot_ids(&bbq), hit_ids);
+
+        // 删除前面的向量后热点序号随之前移
+        bbq.remove("doc-0").unwrap();
+        let mut loaded = Bbq::load(&bbq.save().unwrap()).unwrap();
+        let stats = loaded.warm_stats();
+        assert_eq!(stats.query_count(), 3);
+        assert_eq!(stats.average_k(), Some(4.0));
+        assert_eq!(hot_ids(&loaded), hit_ids);
+
+        let report = loaded.warmup(8).unwrap();
+        assert!(report.vectors_touched > 0 && report.vectors_touched <= 8);
+        assert_eq!(loaded.index.get_result_cache_stats().capacity, stats.suggested_result_cache_capacity());
+    }
+
+    #[test]
+    fn test_get_vectors_by_ids() {
+        let mut bbq = Bbq::new(16, SimilarityFunction::Euclidean).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..10).map(|_| create_random_vector(16, -1.0, 1.0)).collect();
+        for (i, vector) in vectors.iter().enumerate() {
+            bbq.add(&format!("doc-{}", i), vector).unwrap();
+        }
+
+        let records = bbq.get_vectors_by_ids(&["doc-7", "missing", "doc-2"], true).unwrap();
+        assert_eq!(records.len(), 3);
+        assert!(records[1].is_none());
+        let record = records[0].as_ref().unwrap();
+        assert_eq!(record.ord, 7);
+        assert_eq!(record.packed.len(), 2);
+        let reconstruction = record.reconstruction.as_ref().unwrap();
+        assert!(reconstruction.iter().zip(&vectors[7]).all(|(a, b)| (a - b).abs() < 1.0));
+        assert_eq!(records[2].as_ref().unwrap().ord, 2);
+
+        // 与直接从索引读取的打包数据一致
+        let direct = bbq.index.get_packed_vectors(&[7], false).unwrap();
+        assert_eq!(direct[0].packed, record.packed);
+        assert_eq!(direct[0].corrections.lower_interval, record.corrections.lower_interval);
+        assert!(direct[0].reconstruction.is_none());
+        assert!(bbq.index.get_packed_vectors(&[10], false).is_err());
+    }
+
+    #[test]
+    fn test_delta_sync() {
+        let mut source = Bbq::new(8, SimilarityFunction::Cosine).unwrap();
+        for i in 0..4 {
+            source.add(&i.to_string(), &create_random_vector(8, -1.0, 1.0)).unwrap();
+        }
+        let mut replica = Bbq::load(&source.save().unwrap()).unwrap();
+        let synced = source.version();
+
+        source.add("1", &create_random_vector(8, -1.0, 1.0)).unwrap();
+        source.add("9", &create_random_vector(8, -1.0, 1.0)).unwrap();
+        assert!(source.remove("2").unwrap());
+        assert!(!source.remove("missing").unwrap());
+
+        let delta = source.export_delta(synced).unwrap();
+        assert!(delta.len() < source.save().unwrap().len());
+        let summary = replica.apply_delta(&delta).unwrap();
+        assert_eq!((summary.upserts, summary.deletes), (2, 1));
+        assert_eq!(summary.to_version, source.version());
+
+        let mut expected: Vec<(String, Vec<f32>)> = source.ids.iter().cloned().zip(source.vectors.clone()).collect();
+        let mut actual: Vec<(String, Vec<f32>)> = replica.ids.iter().cloned().zip(replica.vectors.clone()).collect();
+        expected.sort_by(|a, b| a.0.cmp(&b.0));
+        actual.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(actual, expected);
+
+        // 空增量
+        let empty = source.export_delta(source.version()).unwrap();
+        assert_eq!(replica.apply_delta(&empty).unwrap().upserts, 0);
+    }
+
+    #[test]
+    fn test_delta_rejects_unavailable_history() {
+        let mut bbq = Bbq::new(4, SimilarityFunction::Cosine).unwrap();
+        bbq.add("a", &[1.0, 0.0, 0.0, 0.0]).unwrap();
+        bbq.add("b", &[0.0, 1.0, 0.0, 0.0]).unwrap();
+        let loaded = Bbq::load(&bbq.save().unwrap()).unwrap();
+        assert_eq!(loaded.version(), 2);
+        assert!(loaded.export_delta(1).is_err());
+        assert!(loaded.export_delta(3).is_err());
+
+        let delta = bbq.export_delta(0).unwrap();
+        let mut other = Bbq::new(4, SimilarityFunction::Euclidean).unwrap();
+        assert!(other.apply_delta(&delta).is_err());
+        assert!(other.apply_delta(&delta[..delta.len() - 1]).is_err());
+        assert!(other.is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_policies() {
+        let (first, second) = ([1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0]);
+        let options = |duplicates: &str| BbqOptions { dims: 4, metric: None, duplicates: Some(duplicates.to_string()) };
+
+        let mut ignore = Bbq::from_options(&options("ignore")).unwrap();
+        assert!(ignore.add("a", &first).unwrap());
+        assert!(!ignore.add("a", &second).unwrap());
+        assert_eq!(ignore.get("a"), Some(&first[..]));
+        assert_eq!(ignore.version(), 1);
+
+        let mut versioned = Bbq::from_options(&options("version")).unwrap();
+        versioned.add("a", &first).unwrap();
+        versioned.add("b", &[0.0, 0.0, 1.0, 0.0]).unwrap();
+        versioned.add("a", &second).unwrap();
+        assert_eq!(versioned.len(), 2);
+        assert_eq!(versioned.versions("a"), vec![(1, &first[..]), (3, &second[..])]);
+        // 查询只返回最新版本
+        let hits = versioned.query(&first, 2).unwrap();
+        assert_eq!(hits.iter().filter(|hit| hit.id == "a").count(), 1);
+        assert_eq!(versioned.query(&second, 1).unwrap()[0].id, "a");
+
+        // 策略和历史版本随快照保存
+        let mut loaded = Bbq::load(&versioned.save().unwrap()).unwrap();
+        assert_eq!(loaded.duplicate_policy(), DuplicatePolicy::Version);
+        assert_eq!(loaded.versions("a"), versioned.versions("a"));
+        assert!(loaded.remove("a").unwrap());
+        assert!(loaded.versions("a").is_empty());
+
+        assert!(Bbq::from_options(&options("merge")).is_err());
+        assert_eq!(DuplicatePolicy::from_name("Version").unwrap().name(), "version");
+    }
+
+    #[test]
+    fn test_freeze() {
+        let mut source = Bbq::new(2, SimilarityFunction::Euclidean).unwrap();
+        source.add("a", &[1.0, 0.0]).unwrap();
+        let mut bbq = Bbq::load(&source.save().unwrap()).unwrap();
+        source.add("b", &[0.0, 1.0]).unwrap();
+        bbq.freeze();
+
+        assert!(bbq.add("c", &[1.0, 1.0]).is_err());
+        assert!(bbq.remove("a").is_err());
+        assert!(bbq.apply_delta(&source.export_delta(0).unwrap()).is_err());
+        assert_eq!((bbq.len(), bbq.version()), (1, 1));
+        // 加载后的首次查询仍可构建索引
+        assert_eq!(bbq.query(&[1.0, 0.0], 1).unwrap()[0].id, "a");
+
+        bbq.unfreeze();
+        assert!(bbq.remove("a").unwrap());
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        let mut bbq = Bbq::new(4, SimilarityFunction::Cosine).unwrap();
+        let error = bbq.add("a", &[1.0, 2.0]).unwrap_err();
+        assert_eq!(error.code(), crate::error_codes::ErrorCode::DimensionMismatch);
+        assert_eq!(error.message_in(crate::error_codes::ErrorLocale::English), "vector dimension 2 does not match index dimension 4");
+        assert!(bbq.add("a", &[1.0, f32::NAN, 0.0, 0.0]).is_err());
+        assert!(bbq.query(&[1.0, 0.0, 0.0, 0.0], 3).unwrap().is_empty());
+        assert!(Bbq::new(0, SimilarityFunction::Cosine).is_err());
+        assert!(parse_metric("manhattan").is_err());
+    }
+}
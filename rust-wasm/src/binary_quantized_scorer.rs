@@ -1,18 +1,72 @@
-/// 二值量化评分器
-/// 对应TypeScript中的binaryQuantizedScorer.ts
-/// 
-/// 实现量化向量的相似性计算
-/// 基于Lucene的二值量化实现
-
-use crate::constants::FOUR_BIT_SCALE;
-use crate::vector_similarity::SimilarityFunction;
-use crate::optimized_scalar_quantizer::QuantizationResult;
-use crate::bitwise_dot_product::{compute_int1_bit_dot_product, compute_int4_bit_dot_product};
+//! 二值量化评分器
+//! 对应TypeScript中的binaryQuantizedScorer.ts
+//! 
+//! 实现量化向量的相似性计算
+//! 基于Lucene的二值量化实现
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+use crate::error_codes::{BbqError, Subject};
+use crate::vector_similarity::{fast_dot_product, fast_squared_distance, SimilarityFunction};
+use crate::optimized_scalar_quantizer::{CorrectionPrecision, OptimizedScalarQuantizer, QuantizationResult};
+use crate::bitwise_dot_product::{compute_int1_bit_dot_product, compute_int4_bit_dot_product, compute_packed_bit_dot_product};
 use crate::batch_dot_product::{
     compute_batch_four_bit_dot_product_direct_packed,
     compute_batch_one_bit_dot_product_direct_packed,
+    compute_batch_one_bit_dot_product_strided,
+    compute_batch_four_bit_dot_product_strided,
+    compute_block_four_bit_dot_products,
+    compute_block_one_bit_dot_products,
+    compute_batch_four_bit_dot_product_wide,
+    compute_batch_one_bit_dot_product_wide,
+    BatchDotProducts,
+    DotProductAccumulator,
     create_direct_packed_buffer,
 };
+use crate::correction_layout::{CorrectionColumns, CorrectionLayout, CorrectionStore};
+use crate::quantized_vector_values::QuantizedVectorValues;
+use crate::query_context::QueryContext;
+use crate::filter::OrdinalBitset;
+use crate::kernel_dispatch::{run_batch_four_bit, run_batch_one_bit, selected_kernels, KernelSelection};
+use crate::memory_limits::checked_region_len;
+use crate::timer::{elapsed_ms, now_ms};
+
+/// 评分公式的计算方式
+///
+/// 快速模式只影响把估计的距离或点积映射到分数的最后一步：
+/// - 欧氏距离：`1/(1+d)` 用位运算初值加一次牛顿迭代近似，相对误差不超过 2.6e-3，且不再截断到0
+/// - 余弦：`(1+s)/2` 不再截断到0，s < -1 时返回负分
+/// - 最大内积：负分支的 `1/(1-s)` 同样使用近似倒数，相对误差不超过 2.6e-3
+///
+/// 双精度（`CorrectionPrecision::Double`）总是使用严格公式。
+/// 启用 `fast-math` 特性时默认值为快速模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoringPrecision {
+    /// 严格公式
+    Strict,
+    /// 快速近似
+    Fast,
+}
+
+impl Default for ScoringPrecision {
+    fn default() -> Self {
+        if cfg!(feature = "fast-math") {
+            ScoringPrecision::Fast
+        } else {
+            ScoringPrecision::Strict
+        }
+    }
+}
+
+/// 近似倒数：位运算得到初值，再做一次牛顿迭代
+///
+/// 对正的有限x，相对误差不超过 2.6e-3
+#[inline]
+fn fast_reciprocal(x: f32) -> f32 {
+    let y = f32::from_bits(0x7EF3_11C3u32.wrapping_sub(x.to_bits()));
+    y * (2.0 - x * y)
+}
 
 
 /// 量化评分结果
@@ -25,17 +79,82 @@ pub struct QuantizedScoreResult {
 }
 
 /// 二值量化评分器结构体
+#[derive(Debug, Clone)]
 pub struct BinaryQuantizedScorer {
     similarity_function: SimilarityFunction,
+    correction_precision: CorrectionPrecision,
+    scoring_precision: ScoringPrecision,
+    /// 批量点积使用的内核，为None时使用全局选中的内核
+    kernels: Option<KernelSelection>,
 }
 
 impl BinaryQuantizedScorer {
     /// 创建新的评分器实例
     pub fn new(similarity_function: SimilarityFunction) -> Self {
-        Self { similarity_function }
+        Self {
+            similarity_function,
+            correction_precision: CorrectionPrecision::Single,
+            scoring_precision: ScoringPrecision::default(),
+            kernels: None,
+        }
+    }
+
+    /// 设置评分公式使用严格公式还是快速近似
+    pub fn with_scoring_precision(mut self, precision: ScoringPrecision) -> Self {
+        self.scoring_precision = precision;
+        self
+    }
+
+    /// 指定批量点积使用的内核，为None时使用全局选中的内核（见 `kernel_dispatch`）
+    pub fn with_kernel_selection(mut self, kernels: Option<KernelSelection>) -> Self {
+        self.kernels = kernels;
+        self
+    }
+
+    /// 评分公式的计算方式（启用 `lucene_parity` 时总是严格公式）
+    pub fn scoring_precision(&self) -> ScoringPrecision {
+        if cfg!(feature = "lucene_parity") {
+            ScoringPrecision::Strict
+        } else {
+            self.scoring_precision
+        }
+    }
+
+    /// 把估计的平方欧氏距离映射为分数
+    #[inline]
+    fn euclidean_score(&self, distance: f32) -> f32 {
+        match self.scoring_precision() {
+            ScoringPrecision::Strict => (1.0 / (1.0 + distance)).max(0.0),
+            ScoringPrecision::Fast => fast_reciprocal(1.0 + distance),
+        }
+    }
+
+    /// 把估计的余弦相似度映射为分数
+    #[inline]
+    fn cosine_score(&self, similarity: f32) -> f32 {
+        match self.scoring_precision() {
+            ScoringPrecision::Strict => ((1.0 + similarity) / 2.0).max(0.0),
+            ScoringPrecision::Fast => (1.0 + similarity) * 0.5,
+        }
+    }
+
+    /// 缩放最大内积分数
+    #[inline]
+    fn max_inner_product_score(&self, score: f32) -> f32 {
+        match self.scoring_precision() {
+            ScoringPrecision::Fast if score < 0.0 => fast_reciprocal(1.0 - score),
+            _ => scale_max_inner_product_score(score),
+        }
+    }
+
+    /// 设置评分公式的累加精度
+    pub fn with_correction_precision(mut self, precision: CorrectionPrecision) -> Self {
+        self.correction_precision = precision;
+        self
     }
 
     /// 计算量化相似性分数
+    #[allow(clippy::too_many_arguments)]
     pub fn compute_quantized_score(
         &self,
         quantized_query: &[u8],
@@ -46,7 +165,7 @@ impl BinaryQuantizedScorer {
         dimension: usize,
         centroid_dp: f32,
         _original_query_vector: Option<&[f32]>,
-    ) -> Result<QuantizedScoreResult, String> {
+    ) -> Result<QuantizedScoreResult, BbqError> {
         if query_bits == 1 {
             // 1位量化：使用单比特相似性计算
             self.compute_one_bit_quantized_score(
@@ -57,18 +176,22 @@ impl BinaryQuantizedScorer {
                 dimension,
                 centroid_dp,
             )
-        } else if query_bits == 4 {
-            // 4位查询 + 1位索引：使用4位-1位相似性计算
-            self.compute_four_bit_quantized_score(
+        } else if (2..=8).contains(&query_bits) {
+            // 多位查询 + 1位索引（默认4位）
+            self.compute_multi_bit_quantized_score(
                 quantized_query,
                 query_corrections,
                 quantized_index,
                 index_corrections,
+                query_bits,
                 dimension,
                 centroid_dp,
             )
         } else {
-            Err(format!("不支持的查询位数: {}，只支持1位和4位", query_bits))
+            Err(BbqError::out_of_range(
+                format!("不支持的查询位数: {}，只支持1-8位", query_bits),
+                format!("unsupported query bits: {}, only 1-8 are supported", query_bits),
+            ))
         }
     }
 
@@ -81,13 +204,13 @@ impl BinaryQuantizedScorer {
         index_corrections: &QuantizationResult,
         dimension: usize,
         centroid_dp: f32,
-    ) -> Result<QuantizedScoreResult, String> {
+    ) -> Result<QuantizedScoreResult, BbqError> {
         // 计算位运算点积
         let qc_dist = compute_int1_bit_dot_product(quantized_query, quantized_index)?;
 
         // 计算相似性分数
         let score = self.compute_one_bit_similarity_score(
-            qc_dist,
+            qc_dist.into(),
             query_corrections,
             index_corrections,
             dimension,
@@ -102,26 +225,29 @@ impl BinaryQuantizedScorer {
         })
     }
 
-    /// 计算4位查询+1位索引相似性分数
-    fn compute_four_bit_quantized_score(
+    /// 计算多位查询+1位索引相似性分数
+    #[allow(clippy::too_many_arguments)]
+    fn compute_multi_bit_quantized_score(
         &self,
         quantized_query: &[u8],
         query_corrections: &QuantizationResult,
         quantized_index: &[u8],
         index_corrections: &QuantizationResult,
+        query_bits: u8,
         dimension: usize,
         centroid_dp: f32,
-    ) -> Result<QuantizedScoreResult, String> {
-        // 计算位运算点积
+    ) -> Result<QuantizedScoreResult, BbqError> {
+        // 计算位运算点积（逐分量相乘，适用于任意查询位数）
         let qc_dist = compute_int4_bit_dot_product(quantized_query, quantized_index)?;
 
         // 计算相似性分数
-        let score = self.compute_four_bit_similarity_score(
-            qc_dist,
+        let score = self.compute_multi_bit_similarity_score(
+            qc_dist.into(),
             query_corrections,
             index_corrections,
             dimension,
             centroid_dp,
+            OptimizedScalarQuantizer::points(query_bits),
         );
 
         Ok(QuantizedScoreResult {
@@ -135,12 +261,17 @@ impl BinaryQuantizedScorer {
     /// 计算1位量化相似性分数（底层实现）
     fn compute_one_bit_similarity_score(
         &self,
-        qc_dist: i32,
+        qc_dist: i64,
         query_corrections: &QuantizationResult,
         index_corrections: &QuantizationResult,
         dimension: usize,
         centroid_dp: f32,
     ) -> f32 {
+        if self.correction_precision == CorrectionPrecision::Double {
+            return self.compute_similarity_score_f64(
+                qc_dist, query_corrections, index_corrections, dimension, centroid_dp, 1.0,
+            );
+        }
         let x1 = index_corrections.quantized_component_sum;
         let ax = index_corrections.lower_interval;
         let lx = index_corrections.upper_interval - ax;
@@ -158,37 +289,50 @@ impl BinaryQuantizedScorer {
                 score = query_corrections.additional_correction +
                     index_corrections.additional_correction -
                     2.0 * score;
-                (1.0 / (1.0 + score)).max(0.0)
+                self.euclidean_score(score)
             }
             SimilarityFunction::Cosine => {
                 score += query_corrections.additional_correction +
                     index_corrections.additional_correction -
                     centroid_dp;
-                ((1.0 + score) / 2.0).max(0.0)
+                self.cosine_score(score)
             }
             SimilarityFunction::MaximumInnerProduct => {
                 score += query_corrections.additional_correction +
                     index_corrections.additional_correction -
                     centroid_dp;
-                scale_max_inner_product_score(score)
+                self.max_inner_product_score(score)
             }
         }
     }
 
-    /// 计算4位量化相似性分数（底层实现）
-    fn compute_four_bit_similarity_score(
+    /// 计算多位查询的量化相似性分数（底层实现）
+    ///
+    /// 查询量化值在 `[0, points - 1]` 之间，区间按 `1 / (points - 1)` 缩放为步长；
+    /// 默认的4位查询即Lucene的 `FOUR_BIT_SCALE`
+    ///
+    /// # 参数
+    /// * `query_points` - 查询量化网格的点数，见 `OptimizedScalarQuantizer::points`
+    fn compute_multi_bit_similarity_score(
         &self,
-        qc_dist: i32,
+        qc_dist: i64,
         query_corrections: &QuantizationResult,
         index_corrections: &QuantizationResult,
         dimension: usize,
         centroid_dp: f32,
+        query_points: i32,
     ) -> f32 {
+        let query_scale = 1.0 / (query_points - 1) as f32;
+        if self.correction_precision == CorrectionPrecision::Double {
+            return self.compute_similarity_score_f64(
+                qc_dist, query_corrections, index_corrections, dimension, centroid_dp, query_scale,
+            );
+        }
         let x1 = index_corrections.quantized_component_sum;
         let ax = index_corrections.lower_interval;
         let lx = index_corrections.upper_interval - ax;
         let ay = query_corrections.lower_interval;
-        let ly = (query_corrections.upper_interval - ay) * FOUR_BIT_SCALE;
+        let ly = (query_corrections.upper_interval - ay) * query_scale;
         let y1 = query_corrections.quantized_component_sum;
 
         let score = ax * ay * dimension as f32 +
@@ -201,23 +345,67 @@ impl BinaryQuantizedScorer {
                 let euclidean_score = query_corrections.additional_correction +
                     index_corrections.additional_correction -
                     2.0 * score;
-                (1.0 / (1.0 + euclidean_score)).max(0.0)
+                self.euclidean_score(euclidean_score)
             }
             SimilarityFunction::Cosine | SimilarityFunction::MaximumInnerProduct => {
-                let adjusted_score = score + query_corrections.additional_correction +
-                    index_corrections.additional_correction -
-                    centroid_dp;
+                // Lucene先合并修正项再加到分数上（`score += a + b - c`），舍入与逐项相加不同
+                let adjusted_score = if cfg!(feature = "lucene_parity") {
+                    score + (query_corrections.additional_correction +
+                        index_corrections.additional_correction -
+                        centroid_dp)
+                } else {
+                    score + query_corrections.additional_correction +
+                        index_corrections.additional_correction -
+                        centroid_dp
+                };
 
                 if self.similarity_function == SimilarityFunction::MaximumInnerProduct {
-                    scale_max_inner_product_score(adjusted_score)
+                    self.max_inner_product_score(adjusted_score)
                 } else {
-                    ((1.0 + adjusted_score) / 2.0).max(0.0)
+                    self.cosine_score(adjusted_score)
                 }
             }
         }
     }
 
+    /// 以f64计算相似性分数（双精度模式）
+    ///
+    /// # 参数
+    /// * `query_scale` - 查询区间的缩放（1位查询为1，多位查询为 `1 / (points - 1)`）
+    fn compute_similarity_score_f64(
+        &self,
+        qc_dist: i64,
+        query_corrections: &QuantizationResult,
+        index_corrections: &QuantizationResult,
+        dimension: usize,
+        centroid_dp: f32,
+        query_scale: f32,
+    ) -> f32 {
+        let x1 = index_corrections.quantized_component_sum as f64;
+        let ax = index_corrections.lower_interval as f64;
+        let lx = index_corrections.upper_interval as f64 - ax;
+        let ay = query_corrections.lower_interval as f64;
+        let ly = (query_corrections.upper_interval as f64 - ay) * query_scale as f64;
+        let y1 = query_corrections.quantized_component_sum as f64;
+
+        let score = ax * ay * dimension as f64 + ay * lx * x1 + ax * ly * y1 + lx * ly * qc_dist as f64;
+        let corrections = query_corrections.additional_correction as f64 + index_corrections.additional_correction as f64;
+
+        match self.similarity_function {
+            SimilarityFunction::Euclidean => {
+                (1.0 / (1.0 + (corrections - 2.0 * score))).max(0.0) as f32
+            }
+            SimilarityFunction::Cosine => {
+                ((1.0 + score + corrections - centroid_dp as f64) / 2.0).max(0.0) as f32
+            }
+            SimilarityFunction::MaximumInnerProduct => {
+                scale_max_inner_product_score((score + corrections - centroid_dp as f64) as f32)
+            }
+        }
+    }
+
     /// 批量计算量化相似性分数
+    #[allow(clippy::too_many_arguments)]
     pub fn compute_batch_quantized_scores(
         &self,
         quantized_query: &[u8],
@@ -228,13 +416,13 @@ impl BinaryQuantizedScorer {
         query_bits: u8,
         dimension: usize,
         centroid_dp: f32,
-    ) -> Result<Vec<QuantizedScoreResult>, String> {
+    ) -> Result<Vec<QuantizedScoreResult>, BbqError> {
         let mut results = Vec::with_capacity(target_ords.len());
 
-        if query_bits == 4 {
-            // 4位量化：使用批量优化算法
-            let packed_vector_size = (dimension + 7) / 8;
-            let direct_packed_buffer = create_direct_packed_buffer(target_vectors, target_ords, packed_vector_size);
+        if (2..=8).contains(&query_bits) {
+            // 多位查询（默认4位）：使用批量优化算法
+            let packed_vector_size = dimension.div_ceil(8);
+            let (direct_packed_buffer, _) = create_direct_packed_buffer(target_vectors, target_ords, packed_vector_size, None);
              
             let qc_dists = compute_batch_four_bit_dot_product_direct_packed(
                 quantized_query,
@@ -245,12 +433,13 @@ impl BinaryQuantizedScorer {
 
             for (i, &qc_dist) in qc_dists.iter().enumerate() {
                 let index_corrections = &target_corrections[i];
-                let score = self.compute_four_bit_similarity_score(
-                    qc_dist,
+                let score = self.compute_multi_bit_similarity_score(
+                    qc_dist.into(),
                     query_corrections,
                     index_corrections,
                     dimension,
                     centroid_dp,
+                    OptimizedScalarQuantizer::points(query_bits),
                 );
 
                 results.push(QuantizedScoreResult {
@@ -263,15 +452,15 @@ impl BinaryQuantizedScorer {
         } else if query_bits == 1 {
             // 1位量化：需要特殊处理向量格式
             // 1. 创建打包的查询向量
-            let packed_query_size = (dimension + 7) / 8;
+            let packed_query_size = dimension.div_ceil(8);
             let mut packed_query = vec![0u8; packed_query_size];
             crate::optimized_scalar_quantizer::OptimizedScalarQuantizer::pack_as_binary(
                 quantized_query,
                 &mut packed_query
-            ).map_err(|e| format!("查询向量打包失败: {}", e))?;
+            ).map_err(|e| e.context(Subject::new("查询向量打包失败", "query packing failed")))?;
 
             // 2. 创建直接打包的目标向量缓冲区
-            let direct_packed_buffer = create_direct_packed_buffer(target_vectors, target_ords, packed_query_size);
+            let (direct_packed_buffer, _) = create_direct_packed_buffer(target_vectors, target_ords, packed_query_size, None);
 
             // 3. 使用批量1位点积计算
             let qc_dists = compute_batch_one_bit_dot_product_direct_packed(
@@ -284,7 +473,7 @@ impl BinaryQuantizedScorer {
             for (i, &qc_dist) in qc_dists.iter().enumerate() {
                 let index_corrections = &target_corrections[i];
                 let score = self.compute_one_bit_similarity_score(
-                    qc_dist,
+                    qc_dist.into(),
                     query_corrections,
                     index_corrections,
                     dimension,
@@ -317,6 +506,651 @@ impl BinaryQuantizedScorer {
 
         Ok(results)
     }
+
+    /// 使用原始向量计算精确分数
+    ///
+    /// 分数与量化分数处于同一尺度，可直接用于重排；
+    /// 余弦相似度要求两个向量都已归一化。
+    /// 使用分通道补偿求和的展开/SIMD128内核，是过采样重排的主要开销
+    pub fn compute_exact_score(&self, query_vector: &[f32], target_vector: &[f32]) -> f32 {
+        match self.similarity_function {
+            SimilarityFunction::Euclidean => {
+                1.0 / (1.0 + fast_squared_distance(query_vector, target_vector))
+            }
+            SimilarityFunction::Cosine => {
+                ((1.0 + fast_dot_product(query_vector, target_vector)) / 2.0).max(0.0)
+            }
+            SimilarityFunction::MaximumInnerProduct => {
+                scale_max_inner_product_score(fast_dot_product(query_vector, target_vector))
+            }
+        }
+    }
+
+    /// 按维度加权计算精确分数
+    ///
+    /// 欧氏距离为 `Σ w(q - t)²`，其余相似度的点积为 `Σ w·q·t`；
+    /// 权重为0的维度完全不参与
+    pub fn compute_weighted_exact_score(&self, query_vector: &[f32], target_vector: &[f32], weights: &[f32]) -> f32 {
+        let weighted = query_vector.iter().zip(target_vector).zip(weights);
+        match self.similarity_function {
+            SimilarityFunction::Euclidean => {
+                let square_distance: f32 = weighted.map(|((&q, &t), &w)| w * (q - t) * (q - t)).sum();
+                1.0 / (1.0 + square_distance)
+            }
+            SimilarityFunction::Cosine => {
+                let dot: f32 = weighted.map(|((&q, &t), &w)| w * q * t).sum();
+                ((1.0 + dot) / 2.0).max(0.0)
+            }
+            SimilarityFunction::MaximumInnerProduct => {
+                scale_max_inner_product_score(weighted.map(|((&q, &t), &w)| w * q * t).sum())
+            }
+        }
+    }
+
+    /// 使用构建时缓存的模长计算精确分数
+    ///
+    /// 欧氏距离按 `|q|² + |t|² - 2q·t` 展开，每个候选只需一次点积；
+    /// 其余相似度与 `compute_exact_score` 相同
+    ///
+    /// # 参数
+    /// * `query_norm_sq` - 查询向量模长的平方（每个查询计算一次）
+    /// * `target_norm` - 目标向量的模长
+    pub fn compute_exact_score_with_norms(
+        &self,
+        query_vector: &[f32],
+        query_norm_sq: f32,
+        target_vector: &[f32],
+        target_norm: f32,
+    ) -> f32 {
+        match self.similarity_function {
+            SimilarityFunction::Euclidean => {
+                let dot = fast_dot_product(query_vector, target_vector);
+                let square_distance = (query_norm_sq + target_norm * target_norm - 2.0 * dot).max(0.0);
+                1.0 / (1.0 + square_distance)
+            }
+            _ => self.compute_exact_score(query_vector, target_vector),
+        }
+    }
+
+    /// 使用查询上下文批量计算分数
+    ///
+    /// 查询的量化、打包和centroid_dp都取自上下文，目标向量直接从向量值中按序号读取
+    ///
+    /// # 参数
+    /// * `context` - 查询上下文
+    /// * `target_vectors` - 量化向量值
+    /// * `target_ords` - 目标向量序号
+    ///
+    /// # 返回
+    /// 与 `target_ords` 一一对应的分数
+    pub fn compute_batch_scores_with_context(
+        &self,
+        context: &QueryContext,
+        target_vectors: &dyn QuantizedVectorValues,
+        target_ords: &[usize],
+    ) -> Result<Vec<f32>, BbqError> {
+        let dimension = target_vectors.dimension();
+        let packed_size = target_vectors.packed_size();
+        let corrections = gather_corrections(target_vectors, target_ords)?;
+
+        // 序号连续且存储连续时（无过滤的搜索批次）直接读取存储中的字节，不再收集
+        if let Some(slice) = contiguous_range(target_ords).and_then(|range| target_vectors.packed_slice(range)) {
+            if slice.len() == target_ords.len() * packed_size {
+                return self.compute_batch_scores_store(context, slice, &corrections, dimension);
+            }
+        }
+        // 候选稀疏时按序号直接读取存储，避免大块的临时缓冲区
+        if prefers_strided(target_ords)
+            && DotProductAccumulator::for_query_bits(dimension, context.query_bits) == DotProductAccumulator::I32
+        {
+            let size = target_vectors.size();
+            if let Some(storage) = target_vectors.packed_slice(0..size).filter(|storage| storage.len() == size * packed_size) {
+                let (qc_dists, one_bit) = self.strided_bit_dot_products(context, storage, target_ords, dimension, packed_size)?;
+                return Ok(self.score_store(&qc_dists, one_bit, context, &corrections, dimension));
+            }
+        }
+
+        let buffer = gather_packed(target_vectors, target_ords)?;
+        self.compute_batch_scores_store(context, &buffer, &corrections, dimension)
+    }
+
+    /// 两个1位打包码之间的估计分数
+    ///
+    /// 与k近邻图相同：一侧的码和修正项作为1位查询，中心项取质心模长的平方；
+    /// 1位对1位的估计对两侧完全对称，交换 `a` 和 `b` 分数不变
+    ///
+    /// # 参数
+    /// * `a` / `b` - 1位打包码，长度不小于 `dimension.div_ceil(8)`
+    /// * `a_corrections` / `b_corrections` - 对应的修正项
+    /// * `dimension` - 向量维度
+    /// * `centroid_dp` - 质心模长的平方
+    pub fn compute_packed_pair_score(
+        &self,
+        a: &[u8],
+        a_corrections: &QuantizationResult,
+        b: &[u8],
+        b_corrections: &QuantizationResult,
+        dimension: usize,
+        centroid_dp: f32,
+    ) -> Result<f32, BbqError> {
+        let packed_size = dimension.div_ceil(8);
+        if a.len() < packed_size || b.len() < packed_size {
+            return Err(BbqError::dimension_mismatch(
+                format!("打包码长度 {} / {} 小于打包维度 {}", a.len(), b.len(), packed_size),
+                format!("packed code lengths {} / {} are less than the packed dimension {}", a.len(), b.len(), packed_size),
+            ));
+        }
+        let qc_dist = compute_packed_bit_dot_product(&a[..packed_size], &b[..packed_size])?;
+        Ok(self.compute_one_bit_similarity_score(qc_dist.into(), a_corrections, b_corrections, dimension, centroid_dp))
+    }
+
+    /// 多个查询对同一批目标计算分数
+    ///
+    /// 目标的修正项和打包向量只收集一次。查询位数一致时使用分块内核，
+    /// 每块目标读取一次即与全部查询计算（批量搜索、k-NN图构建）；位数混合时逐个查询评分
+    ///
+    /// # 参数
+    /// * `contexts` - 各查询的上下文
+    /// * `target_vectors` - 量化向量值
+    /// * `target_ords` - 目标向量序号
+    ///
+    /// # 返回
+    /// 每个查询一行，与 `target_ords` 一一对应的分数
+    pub fn compute_block_scores_with_contexts(
+        &self,
+        contexts: &[QueryContext],
+        target_vectors: &dyn QuantizedVectorValues,
+        target_ords: &[usize],
+    ) -> Result<Vec<Vec<f32>>, BbqError> {
+        let dimension = target_vectors.dimension();
+        let packed_size = target_vectors.packed_size();
+        let corrections = gather_corrections(target_vectors, target_ords)?;
+        if target_ords.is_empty() {
+            return Ok(vec![Vec::new(); contexts.len()]);
+        }
+        if let Some(context) = contexts.iter().find(|context| context.quantized_query.len() != dimension) {
+            return Err(BbqError::dimension_mismatch(
+                format!("查询维度 {} 与索引维度 {} 不匹配", context.quantized_query.len(), dimension),
+                format!("query dimension {} does not match index dimension {}", context.quantized_query.len(), dimension),
+            ));
+        }
+
+        let buffer = match contiguous_range(target_ords).and_then(|range| target_vectors.packed_slice(range)) {
+            Some(slice) if slice.len() == target_ords.len() * packed_size => Cow::Borrowed(slice),
+            _ => Cow::Owned(gather_packed(target_vectors, target_ords)?),
+        };
+        let num_vectors = target_ords.len();
+
+        let narrow = contexts.iter()
+            .all(|context| DotProductAccumulator::for_query_bits(dimension, context.query_bits) == DotProductAccumulator::I32);
+        let (qc_dists, one_bit) = if !narrow {
+            return contexts.iter()
+                .map(|context| self.compute_batch_scores_store(context, &buffer, &corrections, dimension))
+                .collect();
+        } else if contexts.iter().all(|context| (2..=8).contains(&context.query_bits)) {
+            let queries: Vec<&[u8]> = contexts.iter().map(|context| context.quantized_query.as_slice()).collect();
+            (compute_block_four_bit_dot_products(&queries, &buffer, num_vectors, dimension, packed_size), false)
+        } else if let Some(queries) = contexts.iter()
+            .map(|context| context.packed_query.as_deref().filter(|_| context.query_bits == 1))
+            .collect::<Option<Vec<&[u8]>>>()
+        {
+            (compute_block_one_bit_dot_products(&queries, &buffer, num_vectors, dimension.div_ceil(8), packed_size), true)
+        } else {
+            return contexts.iter()
+                .map(|context| self.compute_batch_scores_store(context, &buffer, &corrections, dimension))
+                .collect();
+        };
+        Ok(qc_dists.chunks_exact(num_vectors)
+            .zip(contexts)
+            .map(|(qc_dists, context)| self.score_store(qc_dists, one_bit, context, &corrections, dimension))
+            .collect())
+    }
+
+    /// 多个查询对同一批目标计算分数，跳过被排除的序号
+    ///
+    /// # 返回
+    /// （未被排除的序号, 每个查询一行与之对应的分数）
+    pub fn compute_block_scores_excluding(
+        &self,
+        contexts: &[QueryContext],
+        target_vectors: &dyn QuantizedVectorValues,
+        target_ords: &[usize],
+        excluded: Option<&OrdinalBitset>,
+    ) -> Result<(Vec<usize>, Vec<Vec<f32>>), BbqError> {
+        let kept: Vec<usize> = match excluded {
+            Some(excluded) => target_ords.iter().copied().filter(|&ord| !excluded.contains(ord)).collect(),
+            None => target_ords.to_vec(),
+        };
+        let scores = self.compute_block_scores_with_contexts(contexts, target_vectors, &kept)?;
+        Ok((kept, scores))
+    }
+
+    /// 使用查询上下文批量计算分数，跳过被排除的序号
+    ///
+    /// 被排除的序号（如墓碑）在收集打包缓冲区之前就被跳过，不产生任何计算
+    ///
+    /// # 参数
+    /// * `context` - 查询上下文
+    /// * `target_vectors` - 量化向量值
+    /// * `target_ords` - 目标向量序号
+    /// * `excluded` - 需要跳过的序号，为None时与 `compute_batch_scores_with_context` 相同
+    ///
+    /// # 返回
+    /// 未被排除的（序号, 分数），顺序与 `target_ords` 一致
+    pub fn compute_batch_scores_excluding(
+        &self,
+        context: &QueryContext,
+        target_vectors: &dyn QuantizedVectorValues,
+        target_ords: &[usize],
+        excluded: Option<&OrdinalBitset>,
+    ) -> Result<Vec<(usize, f32)>, BbqError> {
+        let Some(excluded) = excluded else {
+            let scores = self.compute_batch_scores_with_context(context, target_vectors, target_ords)?;
+            return Ok(target_ords.iter().copied().zip(scores).collect());
+        };
+        let kept: Vec<usize> = target_ords.iter()
+            .copied()
+            .filter(|&ord| !excluded.contains(ord))
+            .collect();
+        let scores = self.compute_batch_scores_with_context(context, target_vectors, &kept)?;
+        Ok(kept.into_iter().zip(scores).collect())
+    }
+
+    /// 直接对连续打包缓冲区批量计算分数（无需按序号收集）
+    ///
+    /// # 参数
+    /// * `context` - 查询上下文
+    /// * `buffer` - 连续存放的1位打包向量，每个向量 `dimension.div_ceil(8)` 字节，
+    ///   或按Lucene的布局对齐到64位（见 `OptimizedScalarQuantizer::packed_len`）
+    /// * `corrections` - 与缓冲区中向量一一对应的修正项
+    /// * `dimension` - 向量维度
+    ///
+    /// # 返回
+    /// 与缓冲区中向量一一对应的分数
+    pub fn compute_batch_scores_packed(
+        &self,
+        context: &QueryContext,
+        buffer: &[u8],
+        corrections: &[QuantizationResult],
+        dimension: usize,
+    ) -> Result<Vec<f32>, BbqError> {
+        let (qc_dists, one_bit) = self.batch_bit_dot_products(context, buffer, corrections.len(), dimension)?;
+        Ok(match qc_dists {
+            BatchDotProducts::I32(qc_dists) => self.score_rows(&qc_dists, one_bit, context, corrections, dimension),
+            BatchDotProducts::I64(qc_dists) => self.score_rows(&qc_dists, one_bit, context, corrections, dimension),
+        })
+    }
+
+    /// 对连续打包缓冲区批量计算分数，修正项按字段分列
+    ///
+    /// 与 `compute_batch_scores_packed` 结果相同，循环按列顺序读取修正项
+    pub fn compute_batch_scores_packed_columns(
+        &self,
+        context: &QueryContext,
+        buffer: &[u8],
+        corrections: &CorrectionColumns,
+        dimension: usize,
+    ) -> Result<Vec<f32>, BbqError> {
+        let (qc_dists, one_bit) = self.batch_bit_dot_products(context, buffer, corrections.len(), dimension)?;
+        Ok(match qc_dists {
+            BatchDotProducts::I32(qc_dists) => self.score_columns(&qc_dists, one_bit, context, corrections, dimension),
+            BatchDotProducts::I64(qc_dists) => self.score_columns(&qc_dists, one_bit, context, corrections, dimension),
+        })
+    }
+
+    /// 由点积和按向量存放的修正项计算分数
+    fn score_rows<D: Copy + Into<i64>>(
+        &self,
+        qc_dists: &[D],
+        one_bit: bool,
+        context: &QueryContext,
+        corrections: &[QuantizationResult],
+        dimension: usize,
+    ) -> Vec<f32> {
+        qc_dists.iter()
+            .zip(corrections.iter())
+            .map(|(&qc_dist, index_corrections)| {
+                self.score_from_bit_dot_product(qc_dist.into(), context, index_corrections, dimension, one_bit)
+            })
+            .collect()
+    }
+
+    /// 由点积和压缩保存的修正项（半精度或分组）计算分数，修正项逐个展开为f32
+    fn score_decoded_rows<D: Copy + Into<i64>>(
+        &self,
+        qc_dists: &[D],
+        one_bit: bool,
+        context: &QueryContext,
+        corrections: &CorrectionStore,
+        dimension: usize,
+    ) -> Vec<f32> {
+        qc_dists.iter()
+            .zip((0..).map_while(|ord| corrections.get(ord)))
+            .map(|(&qc_dist, index_corrections)| {
+                self.score_from_bit_dot_product(qc_dist.into(), context, &index_corrections, dimension, one_bit)
+            })
+            .collect()
+    }
+
+    /// 由点积和按字段分列的修正项计算分数
+    fn score_columns<D: Copy + Into<i64>>(
+        &self,
+        qc_dists: &[D],
+        one_bit: bool,
+        context: &QueryContext,
+        corrections: &CorrectionColumns,
+        dimension: usize,
+    ) -> Vec<f32> {
+        qc_dists.iter()
+            .zip(&corrections.lower_interval)
+            .zip(&corrections.upper_interval)
+            .zip(&corrections.additional_correction)
+            .zip(&corrections.quantized_component_sum)
+            .map(|((((&qc_dist, &lower_interval), &upper_interval), &additional_correction), &quantized_component_sum)| {
+                let index_corrections = QuantizationResult {
+                    lower_interval,
+                    upper_interval,
+                    additional_correction,
+                    quantized_component_sum,
+                };
+                self.score_from_bit_dot_product(qc_dist.into(), context, &index_corrections, dimension, one_bit)
+            })
+            .collect()
+    }
+
+    /// 按修正项的布局选择评分循环
+    fn score_store<D: Copy + Into<i64>>(
+        &self,
+        qc_dists: &[D],
+        one_bit: bool,
+        context: &QueryContext,
+        corrections: &CorrectionStore,
+        dimension: usize,
+    ) -> Vec<f32> {
+        match corrections {
+            CorrectionStore::ArrayOfStructs(corrections) => self.score_rows(qc_dists, one_bit, context, corrections, dimension),
+            CorrectionStore::StructOfArrays(columns) => self.score_columns(qc_dists, one_bit, context, columns, dimension),
+            CorrectionStore::HalfPrecision(_) | CorrectionStore::Grouped(_) => {
+                self.score_decoded_rows(qc_dists, one_bit, context, corrections, dimension)
+            }
+        }
+    }
+
+    /// 按修正项的布局选择批量评分循环
+    pub fn compute_batch_scores_store(
+        &self,
+        context: &QueryContext,
+        buffer: &[u8],
+        corrections: &CorrectionStore,
+        dimension: usize,
+    ) -> Result<Vec<f32>, BbqError> {
+        match corrections {
+            CorrectionStore::ArrayOfStructs(corrections) => self.compute_batch_scores_packed(context, buffer, corrections, dimension),
+            CorrectionStore::StructOfArrays(columns) => self.compute_batch_scores_packed_columns(context, buffer, columns, dimension),
+            CorrectionStore::HalfPrecision(_) | CorrectionStore::Grouped(_) => {
+                let (qc_dists, one_bit) = self.batch_bit_dot_products(context, buffer, corrections.len(), dimension)?;
+                Ok(match qc_dists {
+                    BatchDotProducts::I32(qc_dists) => self.score_decoded_rows(&qc_dists, one_bit, context, corrections, dimension),
+                    BatchDotProducts::I64(qc_dists) => self.score_decoded_rows(&qc_dists, one_bit, context, corrections, dimension),
+                })
+            }
+        }
+    }
+
+    /// 以多位量化的索引向量（未打包，每维一个量化值）计算分数
+    ///
+    /// 索引区间按 `1 / (points - 1)` 缩放为步长，与查询区间的缩放对称；
+    /// 用于混合精度索引中提升了位数的向量，分数与1位索引向量的分数可以直接比较
+    ///
+    /// # 参数
+    /// * `quantized_index` - 索引向量的量化值
+    /// * `index_corrections` - 以 `index_bits` 量化时的修正项
+    /// * `index_bits` - 索引向量的量化位数
+    pub fn compute_multi_bit_index_score(
+        &self,
+        context: &QueryContext,
+        quantized_index: &[u8],
+        index_corrections: &QuantizationResult,
+        index_bits: u8,
+    ) -> Result<f32, BbqError> {
+        if !(1..=8).contains(&index_bits) {
+            return Err(BbqError::out_of_range(
+                format!("不支持的索引位数: {}，只支持1-8位", index_bits),
+                format!("unsupported index bits: {}, only 1-8 are supported", index_bits),
+            ));
+        }
+        if quantized_index.len() != context.quantized_query.len() {
+            return Err(BbqError::dimension_mismatch("查询向量和索引向量维度不匹配", "query and index vector dimensions differ"));
+        }
+        let qc_dist = compute_int4_bit_dot_product(&context.quantized_query, quantized_index)?;
+        let index_scale = 1.0 / (OptimizedScalarQuantizer::points(index_bits) - 1) as f32;
+        let scaled = QuantizationResult {
+            upper_interval: index_corrections.lower_interval
+                + (index_corrections.upper_interval - index_corrections.lower_interval) * index_scale,
+            ..index_corrections.clone()
+        };
+        Ok(self.score_from_bit_dot_product(qc_dist.into(), context, &scaled, quantized_index.len(), context.query_bits == 1))
+    }
+
+    /// 按序号直接读取连续存储计算位运算点积
+    ///
+    /// # 参数
+    /// * `storage` - 连续存放的全部打包向量，每个 `stride` 字节
+    /// * `ords` - 目标向量序号，调用方已检查范围
+    ///
+    /// # 返回
+    /// （每个向量的点积, 是否为1位查询）
+    fn strided_bit_dot_products(
+        &self,
+        context: &QueryContext,
+        storage: &[u8],
+        ords: &[usize],
+        dimension: usize,
+        stride: usize,
+    ) -> Result<(Vec<i32>, bool), BbqError> {
+        if context.quantized_query.len() != dimension {
+            return Err(BbqError::dimension_mismatch(
+                format!("查询维度 {} 与索引维度 {} 不匹配", context.quantized_query.len(), dimension),
+                format!("query dimension {} does not match index dimension {}", context.quantized_query.len(), dimension),
+            ));
+        }
+        match (context.query_bits, &context.packed_query) {
+            (2..=8, _) => Ok((
+                compute_batch_four_bit_dot_product_strided(&context.quantized_query, storage, ords, dimension, stride),
+                false,
+            )),
+            (1, Some(packed_query)) => Ok((
+                compute_batch_one_bit_dot_product_strided(packed_query, storage, ords, stride),
+                true,
+            )),
+            (bits, _) => Err(BbqError::out_of_range(
+                format!("不支持的查询位数: {}，只支持1-8位", bits),
+                format!("unsupported query bits: {}, only 1-8 are supported", bits),
+            )),
+        }
+    }
+
+    /// 对连续打包缓冲区批量计算位运算点积
+    ///
+    /// 点积上界超出i32时（见 `DotProductAccumulator`）改用i64内核
+    ///
+    /// # 返回
+    /// （每个向量的点积, 是否为1位查询）
+    fn batch_bit_dot_products(
+        &self,
+        context: &QueryContext,
+        buffer: &[u8],
+        num_vectors: usize,
+        dimension: usize,
+    ) -> Result<(BatchDotProducts, bool), BbqError> {
+        let packed_size = dimension.div_ceil(8);
+        let discretized_size = OptimizedScalarQuantizer::packed_len(dimension, true);
+        // 缓冲区按长度区分紧凑布局和Lucene的64位对齐布局
+        let stride = if buffer.len() == checked_region_len(num_vectors, packed_size, Subject::new("批量打包缓冲区", "batch packed buffer"))? {
+            packed_size
+        } else if buffer.len() == checked_region_len(num_vectors, discretized_size, Subject::new("批量打包缓冲区", "batch packed buffer"))? {
+            discretized_size
+        } else {
+            return Err(BbqError::dimension_mismatch(
+                format!("打包缓冲区长度 {} 与向量数量 {} × 打包维度 {} 不符", buffer.len(), num_vectors, packed_size),
+                format!("packed buffer length {} does not fit {} vectors × packed dimension {}", buffer.len(), num_vectors, packed_size),
+            ));
+        };
+
+        if DotProductAccumulator::for_query_bits(dimension, context.query_bits) == DotProductAccumulator::I64 {
+            if context.quantized_query.len() != dimension {
+                return Err(BbqError::dimension_mismatch(
+                    format!("查询维度 {} 与索引维度 {} 不匹配", context.quantized_query.len(), dimension),
+                    format!("query dimension {} does not match index dimension {}", context.quantized_query.len(), dimension),
+                ));
+            }
+            return match (context.query_bits, &context.packed_query) {
+                (2..=8, _) => Ok((
+                    BatchDotProducts::I64(compute_batch_four_bit_dot_product_wide(
+                        &context.quantized_query, buffer, num_vectors, dimension, stride,
+                    )),
+                    false,
+                )),
+                (1, Some(packed_query)) => Ok((
+                    BatchDotProducts::I64(compute_batch_one_bit_dot_product_wide(packed_query, buffer, num_vectors, stride)),
+                    true,
+                )),
+                (bits, _) => Err(BbqError::out_of_range(
+                    format!("不支持的查询位数: {}，只支持1-8位", bits),
+                    format!("unsupported query bits: {}, only 1-8 are supported", bits),
+                )),
+            };
+        }
+
+        let kernels = self.kernels.unwrap_or_else(selected_kernels);
+        let (qc_dists, one_bit) = match (context.query_bits, &context.packed_query) {
+            (2..=8, _) if stride == packed_size => (
+                run_batch_four_bit(
+                    kernels.four_bit,
+                    &context.quantized_query,
+                    buffer,
+                    num_vectors,
+                    dimension,
+                ),
+                false,
+            ),
+            (2..=8, _) => {
+                // 查询补0到对齐后的维度，补出的分量不贡献点积
+                let mut padded_query = context.quantized_query.clone();
+                padded_query.resize(stride * 8, 0);
+                (run_batch_four_bit(kernels.four_bit, &padded_query, buffer, num_vectors, stride * 8), false)
+            }
+            (1, Some(packed_query)) if stride == packed_size => (
+                run_batch_one_bit(
+                    kernels.one_bit,
+                    packed_query,
+                    buffer,
+                    num_vectors,
+                    packed_size,
+                ),
+                true,
+            ),
+            (1, Some(packed_query)) => {
+                // 1位点积按位相同计数，补出的0位两侧相同，需要扣除
+                let mut padded_query = packed_query.clone();
+                padded_query.resize(stride, 0);
+                let padding_bits = ((stride - packed_size) * 8) as i32;
+                let qc_dists = run_batch_one_bit(kernels.one_bit, &padded_query, buffer, num_vectors, stride)
+                    .into_iter()
+                    .map(|qc_dist| qc_dist - padding_bits)
+                    .collect();
+                (qc_dists, true)
+            }
+            (bits, _) => return Err(BbqError::out_of_range(
+                format!("不支持的查询位数: {}，只支持1-8位", bits),
+                format!("unsupported query bits: {}, only 1-8 are supported", bits),
+            )),
+        };
+        Ok((BatchDotProducts::I32(qc_dists), one_bit))
+    }
+
+    /// 由位运算点积和修正项计算一个分数
+    #[inline]
+    fn score_from_bit_dot_product(
+        &self,
+        qc_dist: i64,
+        context: &QueryContext,
+        index_corrections: &QuantizationResult,
+        dimension: usize,
+        one_bit: bool,
+    ) -> f32 {
+        if one_bit {
+            self.compute_one_bit_similarity_score(
+                qc_dist,
+                &context.query_corrections,
+                index_corrections,
+                dimension,
+                context.centroid_dp,
+            )
+        } else {
+            self.compute_multi_bit_similarity_score(
+                qc_dist,
+                &context.query_corrections,
+                index_corrections,
+                dimension,
+                context.centroid_dp,
+                OptimizedScalarQuantizer::points(context.query_bits),
+            )
+        }
+    }
+}
+
+/// 按索引的修正项布局收集目标的修正项，评分时走对应布局的循环（同时检查序号范围）
+fn gather_corrections(
+    target_vectors: &dyn QuantizedVectorValues,
+    target_ords: &[usize],
+) -> Result<CorrectionStore, BbqError> {
+    Ok(match target_vectors.correction_layout() {
+        // 半精度和分组的修正项在读取时已展开，按向量评分
+        CorrectionLayout::ArrayOfStructs | CorrectionLayout::HalfPrecision | CorrectionLayout::Grouped => CorrectionStore::ArrayOfStructs(
+            target_ords.iter()
+                .map(|&ord| target_vectors.try_get_corrective_terms(ord))
+                .collect::<Result<_, _>>()?,
+        ),
+        CorrectionLayout::StructOfArrays => {
+            let mut columns = CorrectionColumns::with_capacity(target_ords.len());
+            for &ord in target_ords {
+                columns.push(&target_vectors.try_get_corrective_terms(ord)?);
+            }
+            CorrectionStore::StructOfArrays(columns)
+        }
+    })
+}
+
+/// 按序号把目标的打包向量收集到连续缓冲区，每个 `packed_size()` 字节
+fn gather_packed(target_vectors: &dyn QuantizedVectorValues, target_ords: &[usize]) -> Result<Vec<u8>, BbqError> {
+    let packed_size = target_vectors.packed_size();
+    let mut buffer = vec![0u8; checked_region_len(target_ords.len(), packed_size, Subject::new("批量打包缓冲区", "batch packed buffer"))?];
+    for (chunk, &ord) in buffer.chunks_exact_mut(packed_size.max(1)).zip(target_ords.iter()) {
+        let vector = target_vectors.try_vector_value(ord)?;
+        let len = packed_size.min(vector.len());
+        chunk[..len].copy_from_slice(&vector[..len]);
+    }
+    Ok(buffer)
+}
+
+/// 候选在其覆盖的序号范围内的密度不超过该值时，按序号直接读取而不收集
+const STRIDED_MAX_DENSITY: f32 = 0.25;
+
+/// 候选是否稀疏到值得按序号直接读取
+///
+/// 密集的候选收集后顺序扫描更快；稀疏的候选收集会为很少的向量分配和复制整块缓冲区
+fn prefers_strided(ords: &[usize]) -> bool {
+    let (Some(&min), Some(&max)) = (ords.iter().min(), ords.iter().max()) else {
+        return false;
+    };
+    ords.len() as f32 <= (max - min + 1) as f32 * STRIDED_MAX_DENSITY
+}
+
+/// 序号是否为连续递增的一段
+fn contiguous_range(ords: &[usize]) -> Option<Range<usize>> {
+    let (&first, &last) = (ords.first()?, ords.last()?);
+    ords.windows(2).all(|pair| pair[1] == pair[0] + 1).then_some(first..last + 1)
 }
 
 /// 缩放最大内积分数
@@ -328,13 +1162,211 @@ fn scale_max_inner_product_score(score: f32) -> f32 {
     }
 }
 
+/// 严格公式与快速近似的对比结果
+#[derive(Debug, Clone)]
+pub struct ScoringPrecisionBenchmark {
+    /// 严格公式每个分数的耗时（纳秒）
+    pub strict_ns_per_score: f64,
+    /// 快速近似每个分数的耗时（纳秒）
+    pub fast_ns_per_score: f64,
+    /// 快速近似相对严格公式的最大相对误差
+    pub max_relative_error: f32,
+}
+
+/// 在随机修正项上对比严格公式与快速近似的耗时和误差
+///
+/// # 参数
+/// * `similarity_function` - 相似性函数
+/// * `dimension` - 向量维度
+/// * `count` - 评分次数
+pub fn benchmark_scoring_precision(
+    similarity_function: SimilarityFunction,
+    dimension: usize,
+    count: usize,
+) -> Result<ScoringPrecisionBenchmark, BbqError> {
+    if dimension == 0 || count == 0 {
+        return Err(BbqError::invalid_argument("维度和评分次数必须大于0", "dimension and scoring count must be greater than 0"));
+    }
+    let mut rng = fastrand::Rng::with_seed(0x5c0e);
+    let scale = 1.0 / dimension as f32;
+    let corrections = |rng: &mut fastrand::Rng| QuantizationResult {
+        lower_interval: -rng.f32() * 0.1,
+        upper_interval: rng.f32() * 0.1,
+        additional_correction: rng.f32() * 0.5,
+        quantized_component_sum: rng.f32() * dimension as f32 * 0.5,
+    };
+    let query = corrections(&mut rng);
+    let targets: Vec<(i32, QuantizationResult)> = (0..count)
+        .map(|_| ((rng.f32() * dimension as f32 * 7.5) as i32, corrections(&mut rng)))
+        .collect();
+
+    let run = |scorer: &BinaryQuantizedScorer| {
+        let start = now_ms();
+        let scores: Vec<f32> = targets.iter()
+            .map(|(qc_dist, target)| {
+                scorer.compute_multi_bit_similarity_score((*qc_dist).into(), &query, target, dimension, scale, 16)
+            })
+            .collect();
+        (std::hint::black_box(scores), elapsed_ms(start) * 1e6 / count as f64)
+    };
+    let (strict, strict_ns) = run(&BinaryQuantizedScorer::new(similarity_function)
+        .with_scoring_precision(ScoringPrecision::Strict));
+    let (fast, fast_ns) = run(&BinaryQuantizedScorer::new(similarity_function)
+        .with_scoring_precision(ScoringPrecision::Fast));
+
+    // 只在严格公式未截断的分数上比较误差
+    let max_relative_error = strict.iter()
+        .zip(&fast)
+        .filter(|(strict, _)| **strict > 0.0)
+        .map(|(strict, fast)| ((fast - strict) / strict).abs())
+        .fold(0.0f32, f32::max);
+
+    Ok(ScoringPrecisionBenchmark {
+        strict_ns_per_score: strict_ns,
+        fast_ns_per_score: fast_ns,
+        max_relative_error,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::constants::FOUR_BIT_SCALE;
+
+    #[test]
+    fn test_fast_scoring_error_bound() {
+        let mut x = 1e-6f32;
+        while x < 1e6 {
+            let relative = ((fast_reciprocal(x) as f64 - 1.0 / x as f64) * x as f64).abs();
+            assert!(relative < 2.6e-3, "x = {}: 相对误差 {:e}", x, relative);
+            x *= 1.01;
+        }
+        for similarity_function in [
+            SimilarityFunction::Euclidean,
+            SimilarityFunction::Cosine,
+            SimilarityFunction::MaximumInnerProduct,
+        ] {
+            let report = benchmark_scoring_precision(similarity_function, 256, 2000).unwrap();
+            assert!(report.max_relative_error < 2.6e-3, "{:?}: {:?}", similarity_function, report);
+        }
+    }
 
     #[test]
     fn test_scale_max_inner_product_score() {
         assert_eq!(scale_max_inner_product_score(1.0), 2.0);
         assert_eq!(scale_max_inner_product_score(-1.0), 0.5);
     }
+
+    #[test]
+    fn test_compute_exact_score() {
+        let euclidean = BinaryQuantizedScorer::new(SimilarityFunction::Euclidean);
+        assert_eq!(euclidean.compute_exact_score(&[0.0, 0.0], &[1.0, 1.0]), 1.0 / 3.0);
+
+        let cosine = BinaryQuantizedScorer::new(SimilarityFunction::Cosine);
+        assert_eq!(cosine.compute_exact_score(&[1.0, 0.0], &[1.0, 0.0]), 1.0);
+        assert_eq!(cosine.compute_exact_score(&[1.0, 0.0], &[0.0, 1.0]), 0.5);
+    }
+
+    #[cfg(feature = "index")]
+    #[test]
+    fn test_sparse_candidates_read_storage_directly() {
+        use crate::quantized_index::{QuantizedIndex, QuantizedIndexConfig};
+        use crate::vector_utils::create_random_vector;
+
+        let vectors: Vec<Vec<f32>> = (0..400)
+            .map(|_| create_random_vector(40, -1.0, 1.0))
+            .collect();
+        for query_bits in [4, 1] {
+            let mut index = QuantizedIndex::new(QuantizedIndexConfig { query_bits, ..QuantizedIndexConfig::default() }).unwrap();
+            index.build_index(&vectors).unwrap();
+            let generation = index.snapshot().unwrap();
+            let context = index.prepare_query(&vectors[0]).unwrap();
+            let scorer = index.get_scorer();
+
+            // 稀疏候选按序号直接读取，密集候选收集，单个序号直接切片，结果一致
+            let sparse = [390, 3, 200];
+            assert!(prefers_strided(&sparse) && !prefers_strided(&[1, 3, 4, 2]));
+            let strided = scorer.compute_batch_scores_with_context(&context, generation.values(), &sparse).unwrap();
+            for (&ord, &score) in sparse.iter().zip(&strided) {
+                assert_eq!(scorer.compute_batch_scores_with_context(&context, generation.values(), &[ord]).unwrap(), vec![score]);
+            }
+            let dense = scorer.compute_batch_scores_with_context(&context, generation.values(), &[3, 2, 4, 1]).unwrap();
+            assert_eq!(dense[0], strided[1]);
+            assert!(scorer.compute_batch_scores_with_context(&context, generation.values(), &[3, 400]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_query_scale_follows_points() {
+        use crate::optimized_scalar_quantizer::OptimizedScalarQuantizer;
+
+        // 量化评分等于两个重建向量的点积，查询步长随位数变化
+        let dimension = 48;
+        let mut rng = fastrand::Rng::with_seed(7);
+        let mut sample = || (0..dimension).map(|_| rng.f32() * 2.0 - 1.0).collect::<Vec<f32>>();
+        let (query, target, centroid) = (sample(), sample(), vec![0.0; dimension]);
+        let quantizer = OptimizedScalarQuantizer::new(None, None, Some(SimilarityFunction::Euclidean));
+        let mut quantized_target = vec![0u8; dimension];
+        let tc = quantizer.scalar_quantize(&target, &mut quantized_target, 1, &centroid).unwrap();
+        let target_hat = OptimizedScalarQuantizer::dequantize(&quantized_target, 1, &tc, &centroid);
+        let mut packed_target = vec![0u8; dimension / 8];
+        OptimizedScalarQuantizer::pack_as_binary(&quantized_target, &mut packed_target).unwrap();
+
+        let scorer = BinaryQuantizedScorer::new(SimilarityFunction::Euclidean);
+        for query_bits in 2..=8 {
+            let mut quantized_query = vec![0u8; dimension];
+            let qc = quantizer.scalar_quantize(&query, &mut quantized_query, query_bits, &centroid).unwrap();
+            let query_hat = OptimizedScalarQuantizer::dequantize(&quantized_query, query_bits, &qc, &centroid);
+            let dot: f64 = query_hat.iter().zip(&target_hat).map(|(&q, &t)| q as f64 * t as f64).sum();
+            let expected = 1.0 / (1.0 + qc.additional_correction as f64 + tc.additional_correction as f64 - 2.0 * dot);
+
+            let score = scorer.compute_quantized_score(
+                &quantized_query, &qc, &quantized_target, &tc, query_bits, dimension, 0.0, None,
+            ).unwrap().score as f64;
+            assert!((score - expected).abs() < 1e-4 * expected, "{}位: {} != {}", query_bits, score, expected);
+            let batch = scorer.compute_batch_quantized_scores(
+                &quantized_query, &qc, std::slice::from_ref(&packed_target), std::slice::from_ref(&tc), &[0], query_bits, dimension, 0.0,
+            ).unwrap();
+            assert_eq!(batch[0].score as f64, score);
+        }
+        assert!(scorer.compute_quantized_score(&[0; 4], &tc, &[0; 4], &tc, 9, 4, 0.0, None).is_err());
+    }
+
+    #[test]
+    fn test_f32_correction_error_at_high_dimensions() {
+        use crate::optimized_scalar_quantizer::OptimizedScalarQuantizer;
+
+        // 数据偏离质心较远时，欧氏距离 = 两个约 dimension * 100 的修正项之差，f32下相互抵消
+        for dimension in [1536, 3072, 4096] {
+            let mut rng = fastrand::Rng::with_seed(dimension as u64);
+            let mut sample = |offset: f32| (0..dimension).map(|_| offset + rng.f32() * 0.2 - 0.1).collect::<Vec<f32>>();
+            let (query, target, centroid) = (sample(10.0), sample(10.0), sample(0.0));
+
+            let quantizer = OptimizedScalarQuantizer::new(None, None, Some(SimilarityFunction::Euclidean))
+                .with_correction_precision(CorrectionPrecision::Double);
+            let mut quantized_query = vec![0u8; dimension];
+            let qc = quantizer.scalar_quantize(&query, &mut quantized_query, 4, &centroid).unwrap();
+            let mut quantized_target = vec![0u8; dimension];
+            let tc = quantizer.scalar_quantize(&target, &mut quantized_target, 1, &centroid).unwrap();
+            let qc_dist = compute_int4_bit_dot_product(&quantized_query, &quantized_target).unwrap();
+
+            // 按公式以f64逐项计算的参考值
+            let (ax, ay) = (tc.lower_interval as f64, qc.lower_interval as f64);
+            let lx = tc.upper_interval as f64 - ax;
+            let ly = (qc.upper_interval as f64 - ay) * FOUR_BIT_SCALE as f64;
+            let dot = ax * ay * dimension as f64 + ay * lx * tc.quantized_component_sum as f64
+                + ax * ly * qc.quantized_component_sum as f64 + lx * ly * qc_dist as f64;
+            let reference = 1.0 / (1.0 + qc.additional_correction as f64 + tc.additional_correction as f64 - 2.0 * dot);
+
+            let score = |precision| {
+                BinaryQuantizedScorer::new(SimilarityFunction::Euclidean)
+                    .with_correction_precision(precision)
+                    .compute_multi_bit_similarity_score(qc_dist.into(), &qc, &tc, dimension, 0.0, 16) as f64
+            };
+            let single_error = ((score(CorrectionPrecision::Single) - reference) / reference).abs();
+            let double_error = ((score(CorrectionPrecision::Double) - reference) / reference).abs();
+            assert!(double_error < 1e-6, "dim {}: f64误差 {:e}", dimension, double_error);
+            assert!(single_error > 1e-4, "dim {}: f32误差 {:e}", dimension, single_error);
+        }
+    }
 }
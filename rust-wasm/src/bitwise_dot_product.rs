@@ -1,8 +1,10 @@
-/// 位运算点积计算
-/// 对应TypeScript中的bitwiseDotProduct.ts
-/// 
-/// JavaScript实现下，直接计算比Lucene中使用的位运算版本更加高效
-/// 在Rust中，我们可以利用SIMD和更精确的位操作优化
+//! 位运算点积计算
+//! 对应TypeScript中的bitwiseDotProduct.ts
+//! 
+//! JavaScript实现下，直接计算比Lucene中使用的位运算版本更加高效
+//! 在Rust中，我们可以利用SIMD和更精确的位操作优化
+
+use crate::error_codes::BbqError;
 
 /// 量化向量点积计算（朴素实现）
 /// 直接使用字节乘法计算点积，不使用位运算
@@ -13,12 +15,11 @@
 /// 
 /// # 返回
 /// 点积结果
-pub fn compute_quantized_dot_product(q: &[u8], d: &[u8]) -> Result<i32, String> {
+pub fn compute_quantized_dot_product(q: &[u8], d: &[u8]) -> Result<i32, BbqError> {
     if q.len() != d.len() {
-        return Err(format!(
-            "向量长度不匹配：查询向量长度{}，索引向量长度{}",
-            q.len(),
-            d.len()
+        return Err(BbqError::dimension_mismatch(
+            format!("向量长度不匹配：查询向量长度{}，索引向量长度{}", q.len(), d.len()),
+            format!("vector lengths differ: query {}, index {}", q.len(), d.len()),
         ));
     }
 
@@ -41,7 +42,7 @@ pub fn compute_quantized_dot_product(q: &[u8], d: &[u8]) -> Result<i32, String>
 /// # 返回
 /// 点积结果
 #[inline]
-pub fn compute_int4_bit_dot_product(q: &[u8], d: &[u8]) -> Result<i32, String> {
+pub fn compute_int4_bit_dot_product(q: &[u8], d: &[u8]) -> Result<i32, BbqError> {
     // 对于4位查询+1位索引，应该使用未打包的向量进行直接点积计算
     // 这与TypeScript版本保持一致
     compute_quantized_dot_product(q, d)
@@ -58,7 +59,7 @@ pub fn compute_int4_bit_dot_product(q: &[u8], d: &[u8]) -> Result<i32, String> {
 /// # 返回
 /// 点积结果
 #[inline]
-pub fn compute_int1_bit_dot_product(q: &[u8], d: &[u8]) -> Result<i32, String> {
+pub fn compute_int1_bit_dot_product(q: &[u8], d: &[u8]) -> Result<i32, BbqError> {
     // 对于1位量化，应该使用未打包的向量进行直接点积计算
     // 这与TypeScript版本保持一致
     compute_quantized_dot_product(q, d)
@@ -73,12 +74,11 @@ pub fn compute_int1_bit_dot_product(q: &[u8], d: &[u8]) -> Result<i32, String> {
 /// 
 /// # 返回
 /// 点积结果
-pub fn compute_packed_bit_dot_product(q: &[u8], d: &[u8]) -> Result<i32, String> {
+pub fn compute_packed_bit_dot_product(q: &[u8], d: &[u8]) -> Result<i32, BbqError> {
     if q.len() != d.len() {
-        return Err(format!(
-            "向量长度不匹配：查询向量长度{}，索引向量长度{}",
-            q.len(),
-            d.len()
+        return Err(BbqError::dimension_mismatch(
+            format!("向量长度不匹配：查询向量长度{}，索引向量长度{}", q.len(), d.len()),
+            format!("vector lengths differ: query {}, index {}", q.len(), d.len()),
         ));
     }
 
@@ -97,6 +97,28 @@ pub fn compute_packed_bit_dot_product(q: &[u8], d: &[u8]) -> Result<i32, String>
     Ok(total_bits - 2 * hamming_distance)
 }
 
+/// 打包二进制向量的汉明距离（XOR+POPCNT）
+///
+/// # 参数
+/// * `q` - 打包的单比特查询向量
+/// * `d` - 打包的单比特索引向量
+///
+/// # 返回
+/// 不同的位数
+pub fn compute_packed_hamming_distance(q: &[u8], d: &[u8]) -> Result<u32, BbqError> {
+    if q.len() != d.len() {
+        return Err(BbqError::dimension_mismatch(
+            format!("向量长度不匹配：查询向量长度{}，索引向量长度{}", q.len(), d.len()),
+            format!("vector lengths differ: query {}, index {}", q.len(), d.len()),
+        ));
+    }
+
+    Ok(q.iter()
+        .zip(d.iter())
+        .map(|(&qval, &dval)| (qval ^ dval).count_ones())
+        .sum())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
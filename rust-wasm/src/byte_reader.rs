@@ -0,0 +1,175 @@
+//! 二进制格式的读写工具
+//!
+//! BBQ快照、查询包、副本增量和序列化的查询都按小端顺序读写，
+//! 度量方式统一编码为一个字节。
+//!
+//! 同一份数据在原生平台和WASM上必须序列化出完全相同的字节，内容寻址缓存和增量同步才可靠：
+//! 整数一律按小端写入，序号和长度固定为u32；浮点数经 `canonical_f32` 规范化后写入，
+//! 不同平台运算产生的NaN载荷和负零不会出现在输出中；按键遍历的集合先排序再写入
+
+use crate::error_codes::BbqError;
+use crate::vector_similarity::SimilarityFunction;
+
+pub(crate) fn metric_to_code(metric: SimilarityFunction) -> u8 {
+    match metric {
+        SimilarityFunction::Euclidean => 0,
+        SimilarityFunction::Cosine => 1,
+        SimilarityFunction::MaximumInnerProduct => 2,
+    }
+}
+
+#[cfg(feature = "index")]
+pub(crate) fn metric_from_code(code: u8) -> Result<SimilarityFunction, BbqError> {
+    match code {
+        0 => Ok(SimilarityFunction::Euclidean),
+        1 => Ok(SimilarityFunction::Cosine),
+        2 => Ok(SimilarityFunction::MaximumInnerProduct),
+        _ => Err(BbqError::corrupt_data(format!("无效的BBQ快照：未知的度量编码 {}", code), format!("invalid BBQ snapshot: unknown similarity code {}", code))),
+    }
+}
+
+/// 规范化浮点数的位模式：所有NaN写为同一个静默NaN，负零写为正零
+///
+/// 两者在比较和评分中与规范值等价，但位模式随平台和运算路径不同
+pub(crate) fn canonical_f32(value: f32) -> f32 {
+    if value.is_nan() {
+        f32::NAN
+    } else if value == 0.0 {
+        0.0
+    } else {
+        value
+    }
+}
+
+/// 以小端规范形式写入一个f32
+pub(crate) fn write_f32(bytes: &mut Vec<u8>, value: f32) {
+    bytes.extend_from_slice(&canonical_f32(value).to_le_bytes());
+}
+
+/// 64位FNV-1a哈希，结果与平台和编译版本无关
+pub(crate) struct Fnv1a {
+    hash: u64,
+}
+
+impl Fnv1a {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    pub(crate) fn new() -> Self {
+        Self { hash: Self::OFFSET }
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.hash ^= byte as u64;
+            self.hash = self.hash.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    pub(crate) fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    /// 按规范形式计入一个f32，见 `canonical_f32`
+    pub(crate) fn write_f32(&mut self, value: f32) {
+        self.write(&canonical_f32(value).to_le_bytes());
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// 顺序读取字节的小工具
+pub(crate) struct ByteReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, offset: 0 }
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], BbqError> {
+        let end = self.offset.checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| BbqError::corrupt_data("无效的BBQ快照：数据被截断", "invalid BBQ snapshot: data is truncated"))?;
+        let slice = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(slice)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, BbqError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32, BbqError> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64, BbqError> {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    #[cfg(feature = "index")]
+    pub(crate) fn read_id(&mut self) -> Result<String, BbqError> {
+        let id_len = self.read_u32()? as usize;
+        std::str::from_utf8(self.take(id_len)?)
+            .map(|id| id.to_string())
+            .map_err(|_| BbqError::corrupt_data("无效的BBQ快照：ID不是合法的UTF-8", "invalid BBQ snapshot: ID is not valid UTF-8"))
+    }
+
+    #[cfg(feature = "index")]
+    pub(crate) fn read_entry(&mut self, dims: usize) -> Result<(String, Vec<f32>), BbqError> {
+        let id = self.read_id()?;
+        let mut vector = Vec::with_capacity(dims);
+        for _ in 0..dims {
+            vector.push(self.read_f32()?);
+        }
+        Ok((id, vector))
+    }
+
+    pub(crate) fn read_f32(&mut self) -> Result<f32, BbqError> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(f32::from_le_bytes(buf))
+    }
+
+    pub(crate) fn remaining(&self) -> usize {
+        self.bytes.len() - self.offset
+    }
+
+    #[cfg(feature = "index")]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.offset == self.bytes.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical_float_bytes() {
+        let mut bytes = Vec::new();
+        write_f32(&mut bytes, -0.0);
+        write_f32(&mut bytes, f32::from_bits(0xffc0_0001));
+        write_f32(&mut bytes, -f32::NAN);
+        write_f32(&mut bytes, 1.5);
+        assert_eq!(bytes, [
+            0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0xc0, 0x7f,
+            0x00, 0x00, 0xc0, 0x7f,
+            0x00, 0x00, 0xc0, 0x3f,
+        ]);
+
+        let mut reader = ByteReader::new(&bytes);
+        assert_eq!(reader.read_f32().unwrap().to_bits(), 0);
+        assert!(reader.read_f32().unwrap().is_nan());
+    }
+}
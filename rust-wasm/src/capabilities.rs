@@ -0,0 +1,157 @@
+//! 能力检测与报告
+//!
+//! 报告当前模块编译时启用的可选特性，以及运行环境实际支持的特性，
+//! 便于JS加载器在多个WASM构建（如普通版/SIMD版）之间做选择
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::kernel_dispatch::available_kernels;
+
+/// 检测SIMD128的最小模块（v128常量 + i8x16.popcnt）
+const SIMD_PROBE: &[u8] = &[
+    0, 97, 115, 109, 1, 0, 0, 0, 1, 5, 1, 96, 0, 1, 123, 3, 2, 1, 0, 10, 10, 1, 8, 0, 65, 0, 253, 15, 253, 98, 11,
+];
+
+/// 检测线程（共享内存 + 原子操作）的最小模块
+const THREADS_PROBE: &[u8] = &[
+    0, 97, 115, 109, 1, 0, 0, 0, 1, 4, 1, 96, 0, 0, 3, 2, 1, 0, 5, 4, 1, 3, 1, 1, 10, 11, 1, 9, 0, 65, 0, 254, 16, 2, 0,
+    26, 11,
+];
+
+/// 检测memory64（64位索引内存）的最小模块
+const MEMORY64_PROBE: &[u8] = &[0, 97, 115, 109, 1, 0, 0, 0, 5, 3, 1, 4, 1];
+
+/// 编译时启用的特性
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct BuildCapabilities {
+    /// 以 `+simd128` 编译
+    pub simd128: bool,
+    /// 以 `+atomics` 编译（多线程构建）
+    pub threads: bool,
+    /// 64位内存（wasm64 / memory64）
+    pub memory64: bool,
+    /// 启用了console_error_panic_hook特性
+    pub panic_hook: bool,
+    /// 可用的批量点积内核
+    pub kernels: Vec<&'static str>,
+    /// 启用的cargo特性（scorer、index、ivf、graph、eval、serde）
+    pub features: Vec<&'static str>,
+}
+
+/// 运行环境检测到的特性（非浏览器环境均为false）
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct RuntimeCapabilities {
+    /// 运行环境支持SIMD128
+    pub simd128: bool,
+    /// 运行环境支持WASM线程
+    pub threads: bool,
+    /// 存在SharedArrayBuffer（跨源隔离页面才有）
+    pub shared_array_buffer: bool,
+    /// 运行环境支持memory64
+    pub memory64: bool,
+}
+
+/// 完整的能力报告
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct Capabilities {
+    /// 库版本
+    pub version: &'static str,
+    /// 编译时特性
+    pub build: BuildCapabilities,
+    /// 运行时特性
+    pub runtime: RuntimeCapabilities,
+}
+
+/// 获取编译时特性
+pub fn build_capabilities() -> BuildCapabilities {
+    BuildCapabilities {
+        simd128: cfg!(target_feature = "simd128"),
+        threads: cfg!(target_feature = "atomics"),
+        memory64: cfg!(all(target_family = "wasm", target_pointer_width = "64")),
+        panic_hook: cfg!(feature = "console_error_panic_hook"),
+        kernels: available_kernels().iter().map(|kernel| kernel.name()).collect(),
+        features: enabled_features(),
+    }
+}
+
+/// 编译时启用的cargo特性
+fn enabled_features() -> Vec<&'static str> {
+    [
+        ("scorer", cfg!(feature = "scorer")),
+        ("index", cfg!(feature = "index")),
+        ("ivf", cfg!(feature = "ivf")),
+        ("graph", cfg!(feature = "graph")),
+        ("eval", cfg!(feature = "eval")),
+        ("serde", cfg!(feature = "serde")),
+        ("lucene_parity", cfg!(feature = "lucene_parity")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect()
+}
+
+/// 检测运行时特性
+#[cfg(target_arch = "wasm32")]
+pub fn runtime_capabilities() -> RuntimeCapabilities {
+    let shared_array_buffer = js_sys::Reflect::has(&js_sys::global(), &"SharedArrayBuffer".into())
+        .unwrap_or(false);
+    RuntimeCapabilities {
+        simd128: validate_module(SIMD_PROBE),
+        threads: shared_array_buffer && validate_module(THREADS_PROBE),
+        shared_array_buffer,
+        memory64: validate_module(MEMORY64_PROBE),
+    }
+}
+
+/// 检测运行时特性
+#[cfg(not(target_arch = "wasm32"))]
+pub fn runtime_capabilities() -> RuntimeCapabilities {
+    RuntimeCapabilities::default()
+}
+
+/// 获取完整的能力报告
+pub fn capabilities() -> Capabilities {
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        build: build_capabilities(),
+        runtime: runtime_capabilities(),
+    }
+}
+
+/// 所有探测模块，供测试与调试使用
+pub fn probe_modules() -> [(&'static str, &'static [u8]); 3] {
+    [("simd128", SIMD_PROBE), ("threads", THREADS_PROBE), ("memory64", MEMORY64_PROBE)]
+}
+
+#[cfg(target_arch = "wasm32")]
+fn validate_module(bytes: &[u8]) -> bool {
+    js_sys::WebAssembly::validate(&js_sys::Uint8Array::from(bytes).into()).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_capabilities_on_host() {
+        let build = build_capabilities();
+        assert!(!build.simd128);
+        assert!(!build.memory64);
+        assert!(build.kernels.contains(&"scalar"));
+    }
+
+    #[test]
+    fn test_probe_modules_have_wasm_header() {
+        for (_, bytes) in probe_modules() {
+            assert_eq!(&bytes[..8], &[0, 97, 115, 109, 1, 0, 0, 0]);
+        }
+        assert!(!runtime_capabilities().simd128);
+    }
+}
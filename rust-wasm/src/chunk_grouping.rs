@@ -0,0 +1,202 @@
+//! 文本块来源与结果分组
+//!
+//! RAG应用通常把文档切成有重叠或相邻的文本块分别嵌入，搜索后再把同一文档中
+//! 相邻的命中块合并成一段上下文。每个向量可以记录来源（文档ID、块在文档中的偏移和长度），
+//! `group_adjacent_chunks` 把同一文档中相邻或重叠的命中块合并为一个结果，分数按指定方式合并
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::error_codes::BbqError;
+use crate::quantized_index::QueryResult;
+use crate::vector_similarity::descending_score_order;
+
+/// 向量对应的文本块来源
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct ChunkProvenance {
+    /// 文档ID
+    pub doc_id: String,
+    /// 块在文档中的起始偏移（字符或字节，由调用方约定，同一文档内一致即可）
+    pub chunk_offset: usize,
+    /// 块的长度
+    pub length: usize,
+}
+
+impl ChunkProvenance {
+    /// 块的结束偏移（不含）
+    pub fn end(&self) -> usize {
+        self.chunk_offset.saturating_add(self.length)
+    }
+}
+
+/// 合并块的分数合并方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkScoreCombine {
+    /// 取最高分
+    #[default]
+    Max,
+    /// 分数求和，命中块越多越靠前
+    Sum,
+    /// 分数取平均
+    Mean,
+}
+
+impl ChunkScoreCombine {
+    /// 根据名称解析合并方式: "max" | "sum" | "mean"
+    pub fn from_name(name: &str) -> Result<Self, BbqError> {
+        match name.to_lowercase().as_str() {
+            "max" => Ok(ChunkScoreCombine::Max),
+            "sum" => Ok(ChunkScoreCombine::Sum),
+            "mean" | "avg" => Ok(ChunkScoreCombine::Mean),
+            _ => Err(BbqError::invalid_argument(format!("不支持的分数合并方式: {}", name), format!("unsupported score combination: {}", name))),
+        }
+    }
+
+    fn combine(self, scores: &[f32]) -> f32 {
+        match self {
+            ChunkScoreCombine::Max => scores.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+            ChunkScoreCombine::Sum => scores.iter().sum(),
+            ChunkScoreCombine::Mean => scores.iter().sum::<f32>() / scores.len() as f32,
+        }
+    }
+}
+
+/// 分组参数
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ChunkGroupingParams {
+    /// 两个块之间允许的最大间隔，间隔不超过该值的块视为相邻（默认0：只合并紧邻或重叠的块）
+    pub max_gap: usize,
+    /// 分数合并方式
+    pub combine: ChunkScoreCombine,
+}
+
+/// 合并后的结果
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct GroupedHit {
+    /// 文档ID，没有来源的向量为None
+    pub doc_id: Option<String>,
+    /// 合并范围的起始偏移
+    pub chunk_offset: usize,
+    /// 合并范围的长度
+    pub length: usize,
+    /// 合并后的分数
+    pub score: f32,
+    /// 参与合并的向量序号，按偏移升序
+    pub ords: Vec<usize>,
+}
+
+/// 把同一文档中相邻或重叠的命中块合并为一个结果
+///
+/// 没有来源的结果原样保留为单独的结果；合并结果按分数降序排列，同分时保持首个命中块的原始顺序
+///
+/// # 参数
+/// * `results` - 搜索结果
+/// * `provenance` - 按序号查询来源
+/// * `params` - 分组参数
+pub fn group_adjacent_chunks<'a, F>(results: &[QueryResult], provenance: F, params: &ChunkGroupingParams) -> Vec<GroupedHit>
+where
+    F: Fn(usize) -> Option<&'a ChunkProvenance>,
+{
+    // (首次出现的位置, 结果)；先按文档和偏移排序，再按首次出现的位置稳定排序
+    let mut chunks: Vec<(usize, &ChunkProvenance, &QueryResult)> = Vec::new();
+    let mut grouped: Vec<(usize, GroupedHit)> = Vec::new();
+    for (rank, result) in results.iter().enumerate() {
+        match provenance(result.index) {
+            Some(source) => chunks.push((rank, source, result)),
+            None => grouped.push((rank, GroupedHit {
+                doc_id: None,
+                chunk_offset: 0,
+                length: 0,
+                score: result.score,
+                ords: vec![result.index],
+            })),
+        }
+    }
+    chunks.sort_by(|a, b| a.1.doc_id.cmp(&b.1.doc_id).then(a.1.chunk_offset.cmp(&b.1.chunk_offset)));
+
+    let mut start = 0;
+    while start < chunks.len() {
+        let (mut first_rank, source, _) = chunks[start];
+        let mut end = source.end();
+        let mut next = start + 1;
+        while next < chunks.len()
+            && chunks[next].1.doc_id == source.doc_id
+            && chunks[next].1.chunk_offset <= end.saturating_add(params.max_gap)
+        {
+            end = end.max(chunks[next].1.end());
+            first_rank = first_rank.min(chunks[next].0);
+            next += 1;
+        }
+        let run = &chunks[start..next];
+        let scores: Vec<f32> = run.iter().map(|(_, _, result)| result.score).collect();
+        grouped.push((first_rank, GroupedHit {
+            doc_id: Some(source.doc_id.clone()),
+            chunk_offset: source.chunk_offset,
+            length: end - source.chunk_offset,
+            score: params.combine.combine(&scores),
+            ords: run.iter().map(|(_, _, result)| result.index).collect(),
+        }));
+        start = next;
+    }
+
+    grouped.sort_by(|a, b| descending_score_order(a.1.score, b.1.score).then(a.0.cmp(&b.0)));
+    grouped.into_iter().map(|(_, hit)| hit).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(index: usize, score: f32) -> QueryResult {
+        QueryResult { index, score, original_score: None, distances: None }
+    }
+
+    fn source(doc_id: &str, chunk_offset: usize, length: usize) -> ChunkProvenance {
+        ChunkProvenance { doc_id: doc_id.to_string(), chunk_offset, length }
+    }
+
+    #[test]
+    fn test_group_adjacent_chunks() {
+        let sources = [
+            Some(source("a", 0, 100)),
+            Some(source("a", 100, 100)),
+            Some(source("a", 250, 100)),
+            Some(source("b", 0, 100)),
+            None,
+            Some(source("a", 150, 100)),
+        ];
+        let results = vec![result(1, 0.9), result(3, 0.8), result(0, 0.7), result(4, 0.6), result(2, 0.5)];
+        let lookup = |ord: usize| sources[ord].as_ref();
+
+        // 间隔为50的a:250不与a:0..200相邻
+        let hits = group_adjacent_chunks(&results, lookup, &ChunkGroupingParams::default());
+        let summary: Vec<_> = hits.iter()
+            .map(|hit| (hit.doc_id.as_deref(), hit.chunk_offset, hit.length, hit.score, hit.ords.clone()))
+            .collect();
+        assert_eq!(summary, vec![
+            (Some("a"), 0, 200, 0.9, vec![0, 1]),
+            (Some("b"), 0, 100, 0.8, vec![3]),
+            (None, 0, 0, 0.6, vec![4]),
+            (Some("a"), 250, 100, 0.5, vec![2]),
+        ]);
+
+        let params = ChunkGroupingParams { max_gap: 50, combine: ChunkScoreCombine::Sum };
+        let hits = group_adjacent_chunks(&results, lookup, &params);
+        assert_eq!((hits[0].ords.clone(), hits[0].length), (vec![0, 1, 2], 350));
+        assert!((hits[0].score - 2.1).abs() < 1e-6);
+
+        // 重叠的块合并为并集
+        let results = vec![result(5, 0.4), result(1, 0.2)];
+        let params = ChunkGroupingParams { max_gap: 0, combine: ChunkScoreCombine::Mean };
+        let hits = group_adjacent_chunks(&results, lookup, &params);
+        assert_eq!((hits[0].chunk_offset, hits[0].length, hits[0].ords.clone()), (100, 150, vec![1, 5]));
+        assert!((hits[0].score - 0.3).abs() < 1e-6);
+
+        assert_eq!(ChunkScoreCombine::from_name("AVG").unwrap(), ChunkScoreCombine::Mean);
+        assert!(ChunkScoreCombine::from_name("median").is_err());
+    }
+}
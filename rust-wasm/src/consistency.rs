@@ -0,0 +1,154 @@
+//! 搜索时的一致性检查（`paranoid` 特性）
+//!
+//! 手工构造或从外部加载的缓冲区可能长度不对、修正项含NaN，
+//! 这时评分不会报错，只会静默产生无意义的分数。
+//! 启用 `paranoid` 特性后，搜索在评分前检查每个候选的打包长度、修正项和序号范围，
+//! 评分后检查分数是否落在度量对应的范围内；不一致的向量通过错误接收器上报后跳过
+
+use crate::error_codes::BbqError;
+use crate::error_reporting::{report_error, ErrorKind};
+use crate::quantized_vector_values::QuantizedVectorValues;
+use crate::vector_similarity::SimilarityFunction;
+
+/// 分数范围的余量：量化估计的相似度可能略微超出理论范围
+const SCORE_ENVELOPE_SLACK: f32 = 0.5;
+
+/// 按索引位数计算的打包向量字节数
+pub fn expected_packed_len(dimension: usize, index_bits: u8) -> usize {
+    if index_bits == 1 {
+        dimension.div_ceil(8)
+    } else {
+        dimension
+    }
+}
+
+/// 检查单个向量的打包长度、修正项和序号范围
+///
+/// # 返回
+/// 不一致时返回描述问题的错误
+pub fn check_vector(values: &dyn QuantizedVectorValues, index_bits: u8, ord: usize) -> Result<(), BbqError> {
+    let packed = values.try_vector_value(ord)?;
+    let expected = if index_bits == 1 { values.packed_size() } else { expected_packed_len(values.dimension(), index_bits) };
+    if packed.len() != expected {
+        return Err(BbqError::corrupt_data(
+            format!("序号 {} 的打包向量长度为 {}，应为 {}", ord, packed.len(), expected),
+            format!("packed vector {} has length {}, expected {}", ord, packed.len(), expected),
+        ));
+    }
+    let corrections = values.try_get_corrective_terms(ord)?;
+    let terms = [
+        corrections.lower_interval,
+        corrections.upper_interval,
+        corrections.additional_correction,
+        corrections.quantized_component_sum,
+    ];
+    if terms.iter().any(|term| !term.is_finite()) {
+        return Err(BbqError::invalid_value(
+            format!("序号 {} 的修正项包含无效值: {:?}", ord, terms),
+            format!("corrections of ordinal {} contain invalid values: {:?}", ord, terms),
+        ));
+    }
+    if corrections.lower_interval > corrections.upper_interval {
+        return Err(BbqError::corrupt_data(
+            format!("序号 {} 的量化区间无效: [{}, {}]", ord, corrections.lower_interval, corrections.upper_interval),
+            format!("quantization interval of ordinal {} is invalid: [{}, {}]", ord, corrections.lower_interval, corrections.upper_interval),
+        ));
+    }
+    Ok(())
+}
+
+/// 分数是否落在度量对应的范围内
+///
+/// 欧氏距离和余弦的分数理论上在 `[0, 1]`，最大内积的分数为正但没有上界；
+/// 两端各留 `SCORE_ENVELOPE_SLACK` 的余量
+pub fn score_in_envelope(similarity_function: SimilarityFunction, score: f32) -> bool {
+    let upper = match similarity_function {
+        SimilarityFunction::Euclidean | SimilarityFunction::Cosine => 1.0 + SCORE_ENVELOPE_SLACK,
+        SimilarityFunction::MaximumInnerProduct => f32::MAX,
+    };
+    score.is_finite() && (-SCORE_ENVELOPE_SLACK..=upper).contains(&score)
+}
+
+/// 只保留一致的候选，其余上报后跳过
+///
+/// # 返回
+/// 跳过的候选数量
+pub fn retain_consistent_candidates(values: &dyn QuantizedVectorValues, index_bits: u8, candidates: &mut Vec<usize>) -> usize {
+    let before = candidates.len();
+    candidates.retain(|&ord| match check_vector(values, index_bits, ord) {
+        Ok(()) => true,
+        Err(error) => {
+            report_error(ErrorKind::Inconsistency, error.message());
+            false
+        }
+    });
+    before - candidates.len()
+}
+
+/// 只保留分数在范围内的结果，其余上报后跳过
+///
+/// # 返回
+/// 跳过的结果数量
+pub fn retain_scores_in_envelope(similarity_function: SimilarityFunction, results: &mut Vec<(usize, f32)>) -> usize {
+    let before = results.len();
+    results.retain(|&(ord, score)| {
+        let valid = score_in_envelope(similarity_function, score);
+        if !valid {
+            report_error(
+                ErrorKind::Inconsistency,
+                &format!("序号 {} 的分数 {} 超出 {:?} 的预期范围", ord, score, similarity_function),
+            );
+        }
+        valid
+    });
+    before - results.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimized_scalar_quantizer::QuantizationResult;
+    use crate::quantized_vector_values::QuantizedVectorValuesImpl;
+
+    fn corrections(lower: f32, upper: f32) -> QuantizationResult {
+        QuantizationResult {
+            lower_interval: lower,
+            upper_interval: upper,
+            additional_correction: 0.0,
+            quantized_component_sum: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_check_vector() {
+        let values = QuantizedVectorValuesImpl::new(
+            vec![vec![0; 2], vec![0; 3], vec![0; 2], vec![0; 2]],
+            vec![vec![0; 16]; 4],
+            vec![corrections(-1.0, 1.0), corrections(-1.0, 1.0), corrections(f32::NAN, 1.0), corrections(1.0, -1.0)],
+            vec![0.0; 16],
+            vec![1.0; 4],
+        );
+        assert!(check_vector(&values, 1, 0).is_ok());
+        assert!(check_vector(&values, 1, 1).is_err());
+        assert!(check_vector(&values, 1, 2).is_err());
+        assert!(check_vector(&values, 1, 3).is_err());
+        assert!(check_vector(&values, 1, 4).is_err());
+
+        let mut candidates = vec![0, 1, 2, 3, 4];
+        assert_eq!(retain_consistent_candidates(&values, 1, &mut candidates), 4);
+        assert_eq!(candidates, vec![0]);
+    }
+
+    #[test]
+    fn test_score_envelope() {
+        assert!(score_in_envelope(SimilarityFunction::Cosine, 0.9));
+        assert!(!score_in_envelope(SimilarityFunction::Cosine, 3.0));
+        assert!(!score_in_envelope(SimilarityFunction::Euclidean, f32::NAN));
+        assert!(score_in_envelope(SimilarityFunction::MaximumInnerProduct, 40.0));
+        assert!(!score_in_envelope(SimilarityFunction::MaximumInnerProduct, f32::INFINITY));
+
+        let mut results = vec![(0, 0.5), (1, -2.0), (2, f32::NAN)];
+        assert_eq!(retain_scores_in_envelope(SimilarityFunction::Cosine, &mut results), 2);
+        assert_eq!(results, vec![(0, 0.5)]);
+    }
+}
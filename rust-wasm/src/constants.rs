@@ -1,5 +1,5 @@
-/// 常量定义
-/// 对应TypeScript中的constants.ts
+//! 常量定义
+//! 对应TypeScript中的constants.ts
 
 /// 查询向量量化位数（默认4位）
 pub const QUERY_BITS: u8 = 4;
@@ -11,6 +11,9 @@ pub const INDEX_BITS: u8 = 1;
 /// 用于将4位量化值（0-15）映射到浮点数范围
 pub const FOUR_BIT_SCALE: f32 = 1.0 / 15.0;
 
+/// Lucene打包前对齐维度的粒度（位）
+pub const LUCENE_DIMENSION_BUCKET: usize = 64;
+
 /// 最大量化位数
 pub const MAX_BITS: u8 = 8;
 
@@ -23,6 +26,15 @@ pub const DEFAULT_LAMBDA: f32 = 0.1;
 /// 默认优化迭代次数
 pub const DEFAULT_ITERS: usize = 5;
 
+/// 默认重排过采样倍数
+pub const DEFAULT_RESCORE_OVERSAMPLE: f32 = 3.0;
+
+/// 自适应过采样的最大倍数
+pub const MAX_RESCORE_OVERSAMPLE: f32 = 32.0;
+
+/// 限时搜索在需要重排时留给量化扫描的时间比例，其余留给重排
+pub const BUDGET_SCAN_SHARE: f64 = 0.75;
+
 /// 最小MSE网格 - 基于均匀分布的最优MSE网格
 /// 每个位数的间隔值经过理论推导和数值优化
 pub const MINIMUM_MSE_GRID: [[f64; 2]; 8] = [
@@ -37,6 +49,7 @@ pub const MINIMUM_MSE_GRID: [[f64; 2]; 8] = [
 ];
 
 /// 数值精度常量
+#[allow(non_snake_case)]
 pub mod NUMERICAL_CONSTANTS {
     /// 收敛阈值
     pub const CONVERGENCE_THRESHOLD: f64 = 1e-8;
@@ -0,0 +1,638 @@
+//! 修正项布局
+//!
+//! 修正项默认按向量逐个存放（AoS，每个向量一个 `QuantizationResult`）；
+//! 也可以在构建时选择按字段分列存放（SoA，下界、上界、附加修正、量化分量和各一列）。
+//! 批量评分时两种布局各有专门的循环，哪种更快取决于维度和候选数量，
+//! 可用 `benchmark_correction_layouts` 在目标环境上实测。
+//!
+//! 低维时每个向量16字节的修正项往往比打包向量本身还大，可以选择按向量存放的半精度布局：
+//! 修正项以f16保存（每个向量8字节），评分时再展开为f32，代价是约3位有效数字的精度。
+//! f16最大约65504，未归一化的欧氏距离数据（如0-255的SIFT特征）附加修正会超出范围，此时无法使用该布局。
+//!
+//! 规模很大的索引还可以选择分组布局：构建时把相近的量化区间聚成至多 `CORRECTION_GROUPS` 组，
+//! 每个向量改用所在组的共享区间重新量化，只逐个保存组号、f16附加修正和量化分量和（每个向量6字节）。
+//! 共享区间不再是每个向量的最优区间，召回率会有所下降，可用召回评估确认
+
+use crate::error_codes::BbqError;
+use crate::batch_sizing::recommended_batch_size;
+use crate::binary_quantized_scorer::BinaryQuantizedScorer;
+use crate::half_precision::{f16_to_f32, f32_to_f16};
+use crate::optimized_scalar_quantizer::QuantizationResult;
+use crate::query_context::QueryContext;
+use crate::timer::{elapsed_ms, now_ms};
+use crate::vector_similarity::SimilarityFunction;
+use std::collections::HashMap;
+
+/// 每种布局至少计时的毫秒数
+const BENCHMARK_MIN_MS: f64 = 2.0;
+
+/// 分组布局聚类时的目标组数
+pub const CORRECTION_GROUPS: usize = 256;
+
+/// 分组布局的组号以u16保存，组数的上限
+const MAX_CORRECTION_GROUPS: usize = u16::MAX as usize + 1;
+
+/// 修正项布局
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CorrectionLayout {
+    /// 按向量存放（默认）
+    #[default]
+    ArrayOfStructs,
+    /// 按字段分列存放
+    StructOfArrays,
+    /// 按向量存放，以f16保存
+    HalfPrecision,
+    /// 相近的区间分组共享，每个向量只保存组号和两个修正项
+    Grouped,
+}
+
+impl CorrectionLayout {
+    /// 布局名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            CorrectionLayout::ArrayOfStructs => "aos",
+            CorrectionLayout::StructOfArrays => "soa",
+            CorrectionLayout::HalfPrecision => "f16",
+            CorrectionLayout::Grouped => "grouped",
+        }
+    }
+
+    /// 由名称解析布局
+    pub fn parse(name: &str) -> Result<Self, BbqError> {
+        match name.to_lowercase().as_str() {
+            "aos" => Ok(CorrectionLayout::ArrayOfStructs),
+            "soa" => Ok(CorrectionLayout::StructOfArrays),
+            "f16" => Ok(CorrectionLayout::HalfPrecision),
+            "grouped" => Ok(CorrectionLayout::Grouped),
+            _ => Err(BbqError::invalid_argument(
+                format!("不支持的修正项布局: {}，只支持aos、soa、f16和grouped", name),
+                format!("unsupported correction layout: {}, only aos, soa, f16 and grouped are supported", name),
+            )),
+        }
+    }
+
+    /// 每个向量的修正项字节数
+    pub fn bytes_per_vector(&self) -> usize {
+        match self {
+            CorrectionLayout::ArrayOfStructs | CorrectionLayout::StructOfArrays => 16,
+            CorrectionLayout::HalfPrecision => 8,
+            CorrectionLayout::Grouped => 6,
+        }
+    }
+}
+
+/// 一个向量以f16保存的修正项：下界、上界、附加修正、量化分量和
+///
+/// 量化分量和是整数，不超过2048时精确；f16最大约65504，超出时为无穷
+pub type HalfCorrection = [u16; 4];
+
+/// 把修正项编码为半精度
+pub fn encode_half_correction(correction: &QuantizationResult) -> HalfCorrection {
+    [
+        f32_to_f16(correction.lower_interval),
+        f32_to_f16(correction.upper_interval),
+        f32_to_f16(correction.additional_correction),
+        f32_to_f16(correction.quantized_component_sum),
+    ]
+}
+
+/// 把修正项编码为半精度，有限的值编码后变为无穷（超出f16范围）时返回None
+pub fn try_encode_half_correction(correction: &QuantizationResult) -> Option<HalfCorrection> {
+    let half = encode_half_correction(correction);
+    let decoded = decode_half_correction(&half);
+    let fits = [
+        (correction.lower_interval, decoded.lower_interval),
+        (correction.upper_interval, decoded.upper_interval),
+        (correction.additional_correction, decoded.additional_correction),
+        (correction.quantized_component_sum, decoded.quantized_component_sum),
+    ]
+    .iter()
+    .all(|&(value, decoded)| !value.is_finite() || decoded.is_finite());
+    fits.then_some(half)
+}
+
+/// 把半精度修正项展开为f32
+pub fn decode_half_correction(half: &HalfCorrection) -> QuantizationResult {
+    QuantizationResult {
+        lower_interval: f16_to_f32(half[0]),
+        upper_interval: f16_to_f32(half[1]),
+        additional_correction: f16_to_f32(half[2]),
+        quantized_component_sum: f16_to_f32(half[3]),
+    }
+}
+
+/// 按字段分列的修正项
+#[derive(Debug, Clone, Default)]
+pub struct CorrectionColumns {
+    pub lower_interval: Vec<f32>,
+    pub upper_interval: Vec<f32>,
+    pub additional_correction: Vec<f32>,
+    pub quantized_component_sum: Vec<f32>,
+}
+
+impl CorrectionColumns {
+    /// 创建可容纳capacity个向量的空列
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            lower_interval: Vec::with_capacity(capacity),
+            upper_interval: Vec::with_capacity(capacity),
+            additional_correction: Vec::with_capacity(capacity),
+            quantized_component_sum: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// 追加一个向量的修正项
+    pub fn push(&mut self, correction: &QuantizationResult) {
+        self.lower_interval.push(correction.lower_interval);
+        self.upper_interval.push(correction.upper_interval);
+        self.additional_correction.push(correction.additional_correction);
+        self.quantized_component_sum.push(correction.quantized_component_sum);
+    }
+
+    /// 向量数量（以最短的列为准，损坏的数据不会越界）
+    pub fn len(&self) -> usize {
+        self.lower_interval.len()
+            .min(self.upper_interval.len())
+            .min(self.additional_correction.len())
+            .min(self.quantized_component_sum.len())
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 取出第ord个向量的修正项
+    pub fn get(&self, ord: usize) -> Option<QuantizationResult> {
+        (ord < self.len()).then(|| QuantizationResult {
+            lower_interval: self.lower_interval[ord],
+            upper_interval: self.upper_interval[ord],
+            additional_correction: self.additional_correction[ord],
+            quantized_component_sum: self.quantized_component_sum[ord],
+        })
+    }
+}
+
+/// 分组共享区间的修正项
+///
+/// 区间按组保存一份，每个向量保存组号、f16附加修正和u16量化分量和
+#[derive(Debug, Clone, Default)]
+pub struct GroupedCorrections {
+    /// 每组共享的（下界, 上界）
+    pub intervals: Vec<(f32, f32)>,
+    /// 每个向量所在的组
+    pub group_of: Vec<u16>,
+    /// 每个向量的附加修正（f16）
+    pub additional_correction: Vec<u16>,
+    /// 每个向量的量化分量和
+    pub quantized_component_sum: Vec<u16>,
+}
+
+impl GroupedCorrections {
+    /// 按完全相同的区间分组收集修正项
+    ///
+    /// 区间种类超过组号上限、量化分量和不是u16能表示的整数，
+    /// 或附加修正超出f16范围时无法分组，返回None
+    pub fn try_collect<I: IntoIterator<Item = QuantizationResult>>(corrections: I) -> Option<Self> {
+        let corrections = corrections.into_iter();
+        let capacity = corrections.size_hint().0;
+        let mut grouped = GroupedCorrections {
+            intervals: Vec::new(),
+            group_of: Vec::with_capacity(capacity),
+            additional_correction: Vec::with_capacity(capacity),
+            quantized_component_sum: Vec::with_capacity(capacity),
+        };
+        let mut groups: HashMap<(u32, u32), u16> = HashMap::new();
+        for correction in corrections {
+            let key = (correction.lower_interval.to_bits(), correction.upper_interval.to_bits());
+            let group = match groups.get(&key) {
+                Some(&group) => group,
+                None => {
+                    if grouped.intervals.len() == MAX_CORRECTION_GROUPS {
+                        return None;
+                    }
+                    let group = grouped.intervals.len() as u16;
+                    grouped.intervals.push((correction.lower_interval, correction.upper_interval));
+                    groups.insert(key, group);
+                    group
+                }
+            };
+            let sum = correction.quantized_component_sum;
+            if !((0.0..=u16::MAX as f32).contains(&sum) && sum.fract() == 0.0) {
+                return None;
+            }
+            let additional = f32_to_f16(correction.additional_correction);
+            if correction.additional_correction.is_finite() && !f16_to_f32(additional).is_finite() {
+                return None;
+            }
+            grouped.group_of.push(group);
+            grouped.additional_correction.push(additional);
+            grouped.quantized_component_sum.push(sum as u16);
+        }
+        Some(grouped)
+    }
+
+    /// 向量数量
+    pub fn len(&self) -> usize {
+        self.group_of.len()
+            .min(self.additional_correction.len())
+            .min(self.quantized_component_sum.len())
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 组数
+    pub fn group_count(&self) -> usize {
+        self.intervals.len()
+    }
+
+    /// 取出第ord个向量的修正项（组号损坏时返回None）
+    pub fn get(&self, ord: usize) -> Option<QuantizationResult> {
+        if ord >= self.len() {
+            return None;
+        }
+        let &(lower_interval, upper_interval) = self.intervals.get(self.group_of[ord] as usize)?;
+        Some(QuantizationResult {
+            lower_interval,
+            upper_interval,
+            additional_correction: f16_to_f32(self.additional_correction[ord]),
+            quantized_component_sum: self.quantized_component_sum[ord] as f32,
+        })
+    }
+}
+
+/// 把修正项的区间聚成至多groups组，返回每组的共享区间
+///
+/// 区间种类不超过groups时原样返回；否则按下界和上界的分位数各切成 √groups 段，
+/// 每个非空格子取其中区间的均值。退化向量（下界等于上界）不参与聚类，
+/// 它们的区间不能与其他向量共享
+pub fn cluster_intervals(corrections: &[QuantizationResult], groups: usize) -> Vec<(f32, f32)> {
+    let mut intervals: Vec<(f32, f32)> = corrections.iter()
+        .filter(|correction| !correction.is_degenerate())
+        .map(|correction| (correction.lower_interval, correction.upper_interval))
+        .collect();
+    if intervals.is_empty() || groups == 0 {
+        return Vec::new();
+    }
+    let mut distinct = intervals.clone();
+    distinct.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    distinct.dedup();
+    if distinct.len() <= groups {
+        return distinct;
+    }
+
+    let side = ((groups as f64).sqrt() as usize).max(1);
+    let edges = |mut values: Vec<f32>| -> Vec<f32> {
+        values.sort_by(f32::total_cmp);
+        (1..side).map(|i| values[i * values.len() / side]).collect()
+    };
+    let lower_edges = edges(intervals.iter().map(|interval| interval.0).collect());
+    let upper_edges = edges(intervals.iter().map(|interval| interval.1).collect());
+    let cell = |edges: &[f32], value: f32| edges.partition_point(|&edge| edge <= value);
+
+    let mut sums = vec![(0.0f64, 0.0f64, 0usize); side * side];
+    for &(lower, upper) in &intervals {
+        let slot = &mut sums[cell(&lower_edges, lower) * side + cell(&upper_edges, upper)];
+        slot.0 += lower as f64;
+        slot.1 += upper as f64;
+        slot.2 += 1;
+    }
+    intervals.clear();
+    intervals.extend(sums.into_iter()
+        .filter(|&(_, _, count)| count > 0)
+        .map(|(lower, upper, count)| ((lower / count as f64) as f32, (upper / count as f64) as f32)));
+    intervals
+}
+
+/// 与给定区间最接近（下界和上界的平方差之和最小）的组
+pub fn nearest_interval(intervals: &[(f32, f32)], interval: (f32, f32)) -> Option<usize> {
+    intervals.iter()
+        .map(|&(lower, upper)| (lower - interval.0).powi(2) + (upper - interval.1).powi(2))
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(group, _)| group)
+}
+
+/// 按所选布局存放的修正项
+#[derive(Debug, Clone)]
+pub enum CorrectionStore {
+    /// 按向量存放
+    ArrayOfStructs(Vec<QuantizationResult>),
+    /// 按字段分列存放
+    StructOfArrays(CorrectionColumns),
+    /// 按向量存放，以f16保存
+    HalfPrecision(Vec<HalfCorrection>),
+    /// 分组共享区间
+    Grouped(GroupedCorrections),
+}
+
+impl CorrectionStore {
+    /// 按布局收集修正项
+    ///
+    /// 分组布局只按完全相同的区间分组（共享区间由构建时的聚类决定），无法分组时按向量存放；
+    /// 半精度布局在有修正项超出f16范围时同样按向量存放
+    pub fn collect<I: IntoIterator<Item = QuantizationResult>>(layout: CorrectionLayout, corrections: I) -> Self {
+        match layout {
+            CorrectionLayout::ArrayOfStructs => CorrectionStore::ArrayOfStructs(corrections.into_iter().collect()),
+            CorrectionLayout::StructOfArrays => {
+                let corrections = corrections.into_iter();
+                let mut columns = CorrectionColumns::with_capacity(corrections.size_hint().0);
+                for correction in corrections {
+                    columns.push(&correction);
+                }
+                CorrectionStore::StructOfArrays(columns)
+            }
+            CorrectionLayout::HalfPrecision => {
+                // 有修正项超出f16范围时退回按向量存放
+                let corrections: Vec<QuantizationResult> = corrections.into_iter().collect();
+                match corrections.iter().map(try_encode_half_correction).collect::<Option<Vec<_>>>() {
+                    Some(half) => CorrectionStore::HalfPrecision(half),
+                    None => CorrectionStore::ArrayOfStructs(corrections),
+                }
+            }
+            CorrectionLayout::Grouped => {
+                // 无法分组时退回按向量存放
+                let corrections: Vec<QuantizationResult> = corrections.into_iter().collect();
+                match GroupedCorrections::try_collect(corrections.iter().cloned()) {
+                    Some(grouped) => CorrectionStore::Grouped(grouped),
+                    None => CorrectionStore::ArrayOfStructs(corrections),
+                }
+            }
+        }
+    }
+
+    /// 当前布局
+    pub fn layout(&self) -> CorrectionLayout {
+        match self {
+            CorrectionStore::ArrayOfStructs(_) => CorrectionLayout::ArrayOfStructs,
+            CorrectionStore::StructOfArrays(_) => CorrectionLayout::StructOfArrays,
+            CorrectionStore::HalfPrecision(_) => CorrectionLayout::HalfPrecision,
+            CorrectionStore::Grouped(_) => CorrectionLayout::Grouped,
+        }
+    }
+
+    /// 分组布局下各组共享的区间，其他布局返回None
+    pub fn shared_intervals(&self) -> Option<&[(f32, f32)]> {
+        match self {
+            CorrectionStore::Grouped(grouped) => Some(&grouped.intervals),
+            _ => None,
+        }
+    }
+
+    /// 转换为另一种布局，布局相同时原样返回
+    ///
+    /// 由半精度或分组布局转换为其他布局时得到展开后的值，不会恢复原来的f32精度和各自的区间
+    pub fn into_layout(self, layout: CorrectionLayout) -> Self {
+        match self {
+            store if store.layout() == layout => store,
+            CorrectionStore::ArrayOfStructs(corrections) => CorrectionStore::collect(layout, corrections),
+            store => CorrectionStore::collect(layout, (0..store.len()).filter_map(|ord| store.get(ord))),
+        }
+    }
+
+    /// 向量数量
+    pub fn len(&self) -> usize {
+        match self {
+            CorrectionStore::ArrayOfStructs(corrections) => corrections.len(),
+            CorrectionStore::StructOfArrays(columns) => columns.len(),
+            CorrectionStore::HalfPrecision(corrections) => corrections.len(),
+            CorrectionStore::Grouped(grouped) => grouped.len(),
+        }
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 取出第ord个向量的修正项
+    pub fn get(&self, ord: usize) -> Option<QuantizationResult> {
+        match self {
+            CorrectionStore::ArrayOfStructs(corrections) => corrections.get(ord).cloned(),
+            CorrectionStore::StructOfArrays(columns) => columns.get(ord),
+            CorrectionStore::HalfPrecision(corrections) => corrections.get(ord).map(decode_half_correction),
+            CorrectionStore::Grouped(grouped) => grouped.get(ord),
+        }
+    }
+}
+
+/// 一组维度和k下两种布局的计时结果
+#[derive(Debug, Clone)]
+pub struct CorrectionLayoutTiming {
+    /// 向量维度
+    pub dimension: usize,
+    /// 返回的结果数量
+    pub k: usize,
+    /// 每批评分的向量数（该维度和k下的推荐批大小）
+    pub batch_size: usize,
+    /// 按向量存放时每个向量的平均耗时（纳秒）
+    pub aos_ns_per_vector: f64,
+    /// 按字段分列时每个向量的平均耗时（纳秒）
+    pub soa_ns_per_vector: f64,
+}
+
+impl CorrectionLayoutTiming {
+    /// 较快的布局
+    pub fn faster(&self) -> CorrectionLayout {
+        if self.soa_ns_per_vector < self.aos_ns_per_vector {
+            CorrectionLayout::StructOfArrays
+        } else {
+            CorrectionLayout::ArrayOfStructs
+        }
+    }
+}
+
+/// 对两种修正项布局的批量评分计时
+///
+/// 每组维度和k按推荐批大小生成一批随机的1位打包向量和修正项，
+/// 用4位查询分别以两种布局评分，与搜索时的批量评分路径一致
+///
+/// # 参数
+/// * `dimensions` - 候选维度
+/// * `ks` - 候选k
+pub fn benchmark_correction_layouts(dimensions: &[usize], ks: &[usize]) -> Result<Vec<CorrectionLayoutTiming>, BbqError> {
+    let scorer = BinaryQuantizedScorer::new(SimilarityFunction::Cosine);
+    let mut rng = fastrand::Rng::with_seed(0xc0aa);
+    let mut timings = Vec::with_capacity(dimensions.len() * ks.len());
+    for &dimension in dimensions {
+        if dimension == 0 {
+            return Err(BbqError::invalid_argument("维度必须大于0", "dimension must be greater than 0"));
+        }
+        let query_corrections = random_correction(&mut rng, dimension);
+        let context = QueryContext::new(
+            vec![0.0; dimension],
+            (0..dimension).map(|_| rng.u8(0..16)).collect(),
+            query_corrections,
+            0.0,
+            4,
+        )?;
+        for &k in ks {
+            let batch_size = recommended_batch_size(dimension, k, None);
+            let buffer: Vec<u8> = (0..batch_size * dimension.div_ceil(8)).map(|_| rng.u8(..)).collect();
+            let corrections: Vec<QuantizationResult> = (0..batch_size).map(|_| random_correction(&mut rng, dimension)).collect();
+
+            let time = |layout: CorrectionLayout| -> Result<f64, BbqError> {
+                let store = CorrectionStore::collect(layout, corrections.iter().cloned());
+                // 先运行一次，排除首次分配的开销
+                std::hint::black_box(scorer.compute_batch_scores_store(&context, &buffer, &store, dimension)?);
+                let start = now_ms();
+                let mut reps = 0usize;
+                while reps == 0 || elapsed_ms(start) < BENCHMARK_MIN_MS {
+                    std::hint::black_box(scorer.compute_batch_scores_store(&context, &buffer, &store, dimension)?);
+                    reps += 1;
+                }
+                Ok(elapsed_ms(start) * 1e6 / (reps * batch_size) as f64)
+            };
+            let aos_ns_per_vector = time(CorrectionLayout::ArrayOfStructs)?;
+            let soa_ns_per_vector = time(CorrectionLayout::StructOfArrays)?;
+            timings.push(CorrectionLayoutTiming { dimension, k, batch_size, aos_ns_per_vector, soa_ns_per_vector });
+        }
+    }
+    Ok(timings)
+}
+
+/// 随机修正项，量级与真实数据相近
+fn random_correction(rng: &mut fastrand::Rng, dimension: usize) -> QuantizationResult {
+    QuantizationResult {
+        lower_interval: -rng.f32() * 0.1,
+        upper_interval: rng.f32() * 0.1,
+        additional_correction: rng.f32() * 0.5,
+        quantized_component_sum: rng.f32() * dimension as f32 * 0.5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layouts_round_trip() {
+        let corrections: Vec<QuantizationResult> = (0..5)
+            .map(|i| QuantizationResult {
+                lower_interval: -(i as f32),
+                upper_interval: i as f32,
+                additional_correction: 0.5 * i as f32,
+                quantized_component_sum: 2.0 * i as f32,
+            })
+            .collect();
+        let soa = CorrectionStore::collect(CorrectionLayout::StructOfArrays, corrections.iter().cloned());
+        assert_eq!(soa.layout(), CorrectionLayout::StructOfArrays);
+        assert_eq!(soa.len(), 5);
+        for (ord, expected) in corrections.iter().enumerate() {
+            let actual = soa.get(ord).unwrap();
+            assert_eq!((actual.lower_interval, actual.upper_interval), (expected.lower_interval, expected.upper_interval));
+            assert_eq!(actual.quantized_component_sum, expected.quantized_component_sum);
+        }
+        assert!(soa.get(5).is_none());
+
+        let aos = soa.into_layout(CorrectionLayout::ArrayOfStructs);
+        assert_eq!(aos.layout(), CorrectionLayout::ArrayOfStructs);
+        assert_eq!(aos.get(3).unwrap().additional_correction, 1.5);
+        let half = aos.into_layout(CorrectionLayout::HalfPrecision);
+        assert_eq!(half.layout(), CorrectionLayout::HalfPrecision);
+        assert_eq!(half.len(), 5);
+        // 这组值在f16中都能精确表示
+        assert_eq!(half.get(3).unwrap().quantized_component_sum, 6.0);
+        assert_eq!(half.into_layout(CorrectionLayout::StructOfArrays).get(4).unwrap().lower_interval, -4.0);
+        assert_eq!(CorrectionLayout::parse("SoA").unwrap(), CorrectionLayout::StructOfArrays);
+        assert_eq!(CorrectionLayout::parse("F16").unwrap(), CorrectionLayout::HalfPrecision);
+        assert!(CorrectionLayout::parse("columns").is_err());
+    }
+
+    #[test]
+    fn test_layouts_score_identically() {
+        let dimension = 96;
+        let mut rng = fastrand::Rng::with_seed(3);
+        let context = QueryContext::new(
+            vec![0.0; dimension],
+            (0..dimension).map(|_| rng.u8(0..16)).collect(),
+            random_correction(&mut rng, dimension),
+            0.2,
+            4,
+        ).unwrap();
+        let buffer: Vec<u8> = (0..50 * dimension.div_ceil(8)).map(|_| rng.u8(..)).collect();
+        let corrections: Vec<QuantizationResult> = (0..50).map(|_| random_correction(&mut rng, dimension)).collect();
+
+        let scorer = BinaryQuantizedScorer::new(SimilarityFunction::Euclidean);
+        let aos = CorrectionStore::collect(CorrectionLayout::ArrayOfStructs, corrections.iter().cloned());
+        let soa = CorrectionStore::collect(CorrectionLayout::StructOfArrays, corrections);
+        let expected = scorer.compute_batch_scores_store(&context, &buffer, &aos, dimension).unwrap();
+        assert_eq!(scorer.compute_batch_scores_store(&context, &buffer, &soa, dimension).unwrap(), expected);
+        assert!(scorer.compute_batch_scores_store(&context, &buffer[1..], &soa, dimension).is_err());
+
+        // 半精度修正项评分时逐个展开，与先展开再评分相同
+        let half = soa.into_layout(CorrectionLayout::HalfPrecision);
+        let widened = CorrectionStore::collect(CorrectionLayout::ArrayOfStructs, (0..half.len()).filter_map(|ord| half.get(ord)));
+        assert_eq!(
+            scorer.compute_batch_scores_store(&context, &buffer, &half, dimension).unwrap(),
+            scorer.compute_batch_scores_store(&context, &buffer, &widened, dimension).unwrap(),
+        );
+
+        // 分组布局同样逐个展开
+        let grouped = CorrectionStore::collect(CorrectionLayout::Grouped, (0..50).map(|i| QuantizationResult {
+            lower_interval: -0.05,
+            upper_interval: if i % 2 == 0 { 0.05 } else { 0.08 },
+            additional_correction: 0.25,
+            quantized_component_sum: i as f32,
+        }));
+        let widened = CorrectionStore::collect(CorrectionLayout::ArrayOfStructs, (0..grouped.len()).filter_map(|ord| grouped.get(ord)));
+        assert_eq!(
+            scorer.compute_batch_scores_store(&context, &buffer, &grouped, dimension).unwrap(),
+            scorer.compute_batch_scores_store(&context, &buffer, &widened, dimension).unwrap(),
+        );
+
+        let timings = benchmark_correction_layouts(&[128], &[10]).unwrap();
+        assert_eq!(timings.len(), 1);
+        assert!(timings[0].aos_ns_per_vector > 0.0 && timings[0].soa_ns_per_vector > 0.0);
+    }
+
+    #[test]
+    fn test_grouped_layout() {
+        let correction = |lower: f32, upper: f32, sum: f32| QuantizationResult {
+            lower_interval: lower,
+            upper_interval: upper,
+            additional_correction: 1.5,
+            quantized_component_sum: sum,
+        };
+        let corrections = vec![correction(-1.0, 1.0, 3.0), correction(-0.5, 0.5, 7.0), correction(-1.0, 1.0, 0.0)];
+        let grouped = CorrectionStore::collect(CorrectionLayout::Grouped, corrections.iter().cloned());
+        assert_eq!(grouped.layout(), CorrectionLayout::Grouped);
+        match &grouped {
+            CorrectionStore::Grouped(grouped) => assert_eq!(grouped.group_count(), 2),
+            other => panic!("{:?}", other.layout()),
+        }
+        for (ord, expected) in corrections.iter().enumerate() {
+            let actual = grouped.get(ord).unwrap();
+            assert_eq!((actual.lower_interval, actual.upper_interval), (expected.lower_interval, expected.upper_interval));
+            assert_eq!(actual.quantized_component_sum, expected.quantized_component_sum);
+            assert_eq!(actual.additional_correction, 1.5);
+        }
+        assert!(grouped.get(3).is_none());
+        assert_eq!(CorrectionLayout::parse("Grouped").unwrap(), CorrectionLayout::Grouped);
+
+        // 量化分量和不是整数时无法分组，退回按向量存放
+        let fallback = CorrectionStore::collect(CorrectionLayout::Grouped, [correction(-1.0, 1.0, 0.5)]);
+        assert_eq!(fallback.layout(), CorrectionLayout::ArrayOfStructs);
+        // 附加修正超出f16范围时半精度布局同样退回按向量存放
+        let mut wide = correction(-1.0, 1.0, 3.0);
+        wide.additional_correction = 1.0e6;
+        assert!(try_encode_half_correction(&wide).is_none());
+        let fallback = CorrectionStore::collect(CorrectionLayout::HalfPrecision, [correction(-1.0, 1.0, 3.0), wide]);
+        assert_eq!(fallback.layout(), CorrectionLayout::ArrayOfStructs);
+
+        // 聚类结果不超过目标组数，退化向量不参与
+        let mut rng = fastrand::Rng::with_seed(5);
+        let mut many: Vec<QuantizationResult> = (0..1000).map(|_| random_correction(&mut rng, 64)).collect();
+        many.push(correction(0.25, 0.25, 0.0));
+        let intervals = cluster_intervals(&many, 64);
+        assert!(!intervals.is_empty() && intervals.len() <= 64);
+        assert!(intervals.iter().all(|&(lower, upper)| lower < upper));
+        let exact = cluster_intervals(&corrections, 64);
+        assert_eq!(exact, vec![(-1.0, 1.0), (-0.5, 0.5)]);
+        assert_eq!(nearest_interval(&exact, (-0.6, 0.4)), Some(1));
+        assert_eq!(nearest_interval(&[], (0.0, 1.0)), None);
+    }
+}
@@ -0,0 +1,188 @@
+//! 每维中心化值的直方图统计
+//!
+//! 量化前每个向量先减去质心，区间拟合作用在中心化后的值上。
+//! 按维度统计这些值的分布，并与量化重建值比较，得到每维的重建误差：
+//! 误差接近方差的维度几乎没有被1位量化保留下来，
+//! 可以据此决定是否先做随机旋转或白化
+
+use crate::error_codes::BbqError;
+
+/// 一个维度的直方图
+#[derive(Debug, Clone, PartialEq)]
+pub struct DimensionHistogram {
+    /// 中心化值的最小值（第一个桶的下界）
+    pub min: f32,
+    /// 中心化值的最大值（最后一个桶的上界）
+    pub max: f32,
+    /// 每个桶的计数，桶宽相等
+    pub counts: Vec<u32>,
+    /// 中心化值的均值
+    pub mean: f32,
+    /// 中心化值的标准差
+    pub std: f32,
+}
+
+impl DimensionHistogram {
+    /// 桶宽
+    pub fn bin_width(&self) -> f32 {
+        (self.max - self.min) / self.counts.len() as f32
+    }
+}
+
+/// 一个维度的量化质量
+#[derive(Debug, Clone, PartialEq)]
+pub struct DimensionQuality {
+    /// 中心化值的直方图
+    pub histogram: DimensionHistogram,
+    /// 重建值与原始值之差的均方
+    pub reconstruction_mse: f32,
+    /// 被量化保留下来的方差比例：1 - 均方误差/方差，方差为0时为1
+    pub explained_variance: f32,
+}
+
+/// 计算每维中心化值的直方图
+///
+/// # 参数
+/// * `vectors` - 向量集合
+/// * `centroid` - 质心
+/// * `bins` - 每维的桶数
+///
+/// # 返回
+/// 按维度排列的直方图
+pub fn compute_dimension_histograms(
+    vectors: &[Vec<f32>],
+    centroid: &[f32],
+    bins: usize,
+) -> Result<Vec<DimensionHistogram>, BbqError> {
+    if bins == 0 {
+        return Err(BbqError::invalid_argument("桶数必须大于0", "bin count must be greater than 0"));
+    }
+    if vectors.is_empty() {
+        return Err(BbqError::empty_input("向量集合不能为空", "vector collection must not be empty"));
+    }
+    let dimension = centroid.len();
+    let mut min = vec![f32::MAX; dimension];
+    let mut max = vec![f32::MIN; dimension];
+    let mut sum = vec![0.0f64; dimension];
+    for vector in vectors {
+        if vector.len() != dimension {
+            return Err(BbqError::dimension_mismatch(
+                format!("向量维度 {} 与质心维度 {} 不匹配", vector.len(), dimension),
+                format!("vector dimension {} does not match centroid dimension {}", vector.len(), dimension),
+            ));
+        }
+        for j in 0..dimension {
+            let centered = vector[j] - centroid[j];
+            if !centered.is_finite() {
+                return Err(BbqError::invalid_value(format!("维度 {} 包含非有限值", j), format!("dimension {} contains a non-finite value", j)));
+            }
+            min[j] = min[j].min(centered);
+            max[j] = max[j].max(centered);
+            sum[j] += centered as f64;
+        }
+    }
+
+    let count = vectors.len() as f64;
+    let mean: Vec<f64> = sum.iter().map(|&s| s / count).collect();
+    let mut variance = vec![0.0f64; dimension];
+    let mut counts = vec![vec![0u32; bins]; dimension];
+    for vector in vectors {
+        for j in 0..dimension {
+            let centered = vector[j] - centroid[j];
+            variance[j] += (centered as f64 - mean[j]).powi(2);
+            let range = max[j] - min[j];
+            let bin = if range > 0.0 {
+                (((centered - min[j]) / range * bins as f32) as usize).min(bins - 1)
+            } else {
+                0
+            };
+            counts[j][bin] += 1;
+        }
+    }
+
+    Ok(counts.into_iter()
+        .enumerate()
+        .map(|(j, counts)| DimensionHistogram {
+            min: min[j],
+            max: max[j],
+            counts,
+            mean: mean[j] as f32,
+            std: (variance[j] / count).sqrt() as f32,
+        })
+        .collect())
+}
+
+/// 在直方图之外计算每维的重建误差
+///
+/// # 参数
+/// * `vectors` - 原始向量
+/// * `reconstructions` - 与原始向量一一对应的量化重建值
+/// * `centroid` - 质心
+/// * `bins` - 每维的桶数
+pub fn compute_dimension_quality(
+    vectors: &[Vec<f32>],
+    reconstructions: &[Vec<f32>],
+    centroid: &[f32],
+    bins: usize,
+) -> Result<Vec<DimensionQuality>, BbqError> {
+    if vectors.len() != reconstructions.len() {
+        return Err(BbqError::dimension_mismatch(
+            format!("重建向量数量 {} 与原始向量数量 {} 不匹配", reconstructions.len(), vectors.len()),
+            format!("reconstruction count {} does not match vector count {}", reconstructions.len(), vectors.len()),
+        ));
+    }
+    let histograms = compute_dimension_histograms(vectors, centroid, bins)?;
+    let mut squared_error = vec![0.0f64; centroid.len()];
+    for (vector, reconstruction) in vectors.iter().zip(reconstructions) {
+        if reconstruction.len() != vector.len() {
+            return Err(BbqError::dimension_mismatch(
+                format!("重建向量维度 {} 与原始向量维度 {} 不匹配", reconstruction.len(), vector.len()),
+                format!("reconstruction dimension {} does not match vector dimension {}", reconstruction.len(), vector.len()),
+            ));
+        }
+        for ((error, &x), &r) in squared_error.iter_mut().zip(vector).zip(reconstruction) {
+            *error += (x as f64 - r as f64).powi(2);
+        }
+    }
+
+    let count = vectors.len() as f64;
+    Ok(histograms.into_iter()
+        .zip(squared_error)
+        .map(|(histogram, error)| {
+            let mse = (error / count) as f32;
+            let variance = histogram.std * histogram.std;
+            let explained_variance = if variance > 0.0 { 1.0 - mse / variance } else { 1.0 };
+            DimensionQuality { histogram, reconstruction_mse: mse, explained_variance }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dimension_histograms() {
+        let vectors = vec![
+            vec![1.0, 5.0],
+            vec![2.0, 5.0],
+            vec![3.0, 5.0],
+            vec![5.0, 5.0],
+        ];
+        let histograms = compute_dimension_histograms(&vectors, &[1.0, 0.0], 4).unwrap();
+        assert_eq!(histograms.len(), 2);
+        // 中心化值 0,1,2,4 落在宽度为1的4个桶中，最大值计入最后一个桶
+        assert_eq!(histograms[0].counts, vec![1, 1, 1, 1]);
+        assert_eq!((histograms[0].min, histograms[0].max), (0.0, 4.0));
+        assert_eq!(histograms[0].bin_width(), 1.0);
+        assert!((histograms[0].mean - 1.75).abs() < 1e-6);
+        // 常量维度全部计入第一个桶
+        assert_eq!(histograms[1].counts, vec![4, 0, 0, 0]);
+        assert_eq!(histograms[1].std, 0.0);
+        assert!(compute_dimension_histograms(&vectors, &[0.0, 0.0], 0).is_err());
+        assert!(compute_dimension_histograms(&vectors, &[0.0], 4).is_err());
+
+        let quality = compute_dimension_quality(&vectors, &vectors, &[1.0, 0.0], 4).unwrap();
+        assert!(quality.iter().all(|q| q.reconstruction_mse == 0.0 && q.explained_variance == 1.0));
+    }
+}
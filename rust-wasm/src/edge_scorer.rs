@@ -0,0 +1,181 @@
+//! 边缘评分器
+//!
+//! 只做查询量化和打包缓冲区评分的最小入口，面向Cloudflare Workers等对模块体积有严格限制的边缘运行时。
+//! 索引在别处构建，边缘只拿到质心、连续的1位打包向量和修正项（与查询包中的布局相同），
+//! 量化和评分与完整构建共用同一份实现，只需 `scorer` 特性：
+//! `cargo build --no-default-features --features scorer`
+
+use crate::error_codes::{BbqError, Subject};
+use crate::binary_quantized_scorer::BinaryQuantizedScorer;
+use crate::memory_limits::checked_region_len;
+use crate::optimized_scalar_quantizer::{CorrectionPrecision, OptimizedScalarQuantizer, QuantizationResult};
+use crate::query_context::QueryContext;
+use crate::vector_similarity::{descending_score_order, SimilarityFunction};
+
+/// 每个向量的修正项个数：下界、上界、附加修正、量化分量和
+pub const CORRECTION_FIELDS: usize = 4;
+
+/// 边缘评分器
+pub struct EdgeScorer {
+    similarity_function: SimilarityFunction,
+    query_bits: u8,
+    correction_precision: CorrectionPrecision,
+    quantizer: OptimizedScalarQuantizer,
+    scorer: BinaryQuantizedScorer,
+    centroid: Vec<f32>,
+}
+
+impl EdgeScorer {
+    /// 创建边缘评分器
+    ///
+    /// # 参数
+    /// * `similarity_function` - 构建索引时使用的相似性函数
+    /// * `query_bits` - 查询向量位数（1或4）
+    /// * `centroid` - 构建索引时的质心
+    pub fn new(similarity_function: SimilarityFunction, query_bits: u8, centroid: Vec<f32>) -> Result<Self, BbqError> {
+        if query_bits != 1 && query_bits != 4 {
+            return Err(BbqError::invalid_argument(
+                format!("不支持的查询位数: {}，只支持1位和4位", query_bits),
+                format!("unsupported query bits: {}, only 1 and 4 are supported", query_bits),
+            ));
+        }
+        if centroid.is_empty() {
+            return Err(BbqError::empty_input("质心不能为空", "centroid must not be empty"));
+        }
+        Ok(Self {
+            similarity_function,
+            query_bits,
+            correction_precision: CorrectionPrecision::Single,
+            quantizer: OptimizedScalarQuantizer::new(None, None, Some(similarity_function)),
+            scorer: BinaryQuantizedScorer::new(similarity_function),
+            centroid,
+        })
+    }
+
+    /// 使用与构建索引时相同的量化参数（未设置时为默认值）
+    pub fn with_quantizer_params(mut self, lambda: Option<f32>, iters: Option<usize>) -> Self {
+        self.quantizer = OptimizedScalarQuantizer::new(lambda, iters, Some(self.similarity_function))
+            .with_correction_precision(self.correction_precision);
+        self
+    }
+
+    /// 设置修正项与评分的累加精度，需与构建索引时一致
+    pub fn with_correction_precision(mut self, precision: CorrectionPrecision) -> Self {
+        self.correction_precision = precision;
+        self.quantizer = self.quantizer.with_correction_precision(precision);
+        self.scorer = self.scorer.with_correction_precision(precision);
+        self
+    }
+
+    /// 向量维度
+    pub fn dimension(&self) -> usize {
+        self.centroid.len()
+    }
+
+    /// 预处理并量化查询，结果可对多个缓冲区复用
+    pub fn prepare_query(&self, query_vector: &[f32]) -> Result<QueryContext, BbqError> {
+        QueryContext::quantize(query_vector, self.similarity_function, &self.quantizer, self.query_bits, &self.centroid)
+    }
+
+    /// 对连续的1位打包缓冲区评分
+    ///
+    /// # 参数
+    /// * `context` - `prepare_query` 得到的查询上下文
+    /// * `packed` - 连续存放的1位打包向量，每个向量 `dimension.div_ceil(8)` 字节
+    /// * `corrections` - 每个向量 `CORRECTION_FIELDS` 个修正项，顺序与打包向量一致
+    ///
+    /// # 返回
+    /// 与打包向量一一对应的分数
+    pub fn score_packed(&self, context: &QueryContext, packed: &[u8], corrections: &[f32]) -> Result<Vec<f32>, BbqError> {
+        if context.dimension() != self.dimension() {
+            return Err(BbqError::dimension_mismatch(
+                format!("查询维度 {} 与评分器维度 {} 不匹配", context.dimension(), self.dimension()),
+                format!("query dimension {} does not match scorer dimension {}", context.dimension(), self.dimension()),
+            ));
+        }
+        if !corrections.len().is_multiple_of(CORRECTION_FIELDS) {
+            return Err(BbqError::dimension_mismatch(
+                format!("修正项长度 {} 不是{}的倍数", corrections.len(), CORRECTION_FIELDS),
+                format!("correction length {} is not a multiple of {}", corrections.len(), CORRECTION_FIELDS),
+            ));
+        }
+        let count = corrections.len() / CORRECTION_FIELDS;
+        let expected = checked_region_len(count, self.dimension().div_ceil(8), Subject::new("打包缓冲区", "packed buffer"))?;
+        if packed.len() != expected {
+            return Err(BbqError::dimension_mismatch(
+                format!("打包缓冲区长度 {} 与向量数量 {} 不符，应为 {}", packed.len(), count, expected),
+                format!("packed buffer length {} does not fit {} vectors, expected {}", packed.len(), count, expected),
+            ));
+        }
+        let corrections: Vec<QuantizationResult> = corrections
+            .chunks_exact(CORRECTION_FIELDS)
+            .map(|chunk| QuantizationResult {
+                lower_interval: chunk[0],
+                upper_interval: chunk[1],
+                additional_correction: chunk[2],
+                quantized_component_sum: chunk[3],
+            })
+            .collect();
+        self.scorer.compute_batch_scores_packed(context, packed, &corrections, self.dimension())
+    }
+
+    /// 量化查询并返回缓冲区中分数最高的k个向量
+    ///
+    /// # 返回
+    /// （缓冲区内位置, 分数），按分数降序
+    pub fn search_packed(&self, query_vector: &[f32], packed: &[u8], corrections: &[f32], k: usize) -> Result<Vec<(usize, f32)>, BbqError> {
+        let context = self.prepare_query(query_vector)?;
+        let mut scored: Vec<(usize, f32)> = self.score_packed(&context, packed, corrections)?
+            .into_iter()
+            .enumerate()
+            .collect();
+        scored.sort_by(|a, b| descending_score_order(a.1, b.1));
+        scored.truncate(k);
+        Ok(scored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector_utils::{create_random_vector, normalize_vector};
+
+    #[test]
+    fn test_search_packed_finds_self() {
+        let dimension = 128;
+        let vectors: Vec<Vec<f32>> = (0..40)
+            .map(|_| {
+                let mut vector = create_random_vector(dimension, -1.0, 1.0);
+                normalize_vector(&mut vector);
+                vector
+            })
+            .collect();
+        let centroid: Vec<f32> = (0..dimension)
+            .map(|d| vectors.iter().map(|v| v[d]).sum::<f32>() / vectors.len() as f32)
+            .collect();
+
+        // 按索引构建时的方式得到打包向量和修正项
+        let quantizer = OptimizedScalarQuantizer::new(None, None, Some(SimilarityFunction::Cosine));
+        let mut packed = Vec::new();
+        let mut corrections = Vec::new();
+        for vector in &vectors {
+            let mut bits = vec![0u8; dimension.div_ceil(8)];
+            let result = quantizer.scalar_quantize_packed(vector, &mut bits, &centroid).unwrap();
+            packed.extend(bits);
+            corrections.extend([result.lower_interval, result.upper_interval, result.additional_correction, result.quantized_component_sum]);
+        }
+
+        let scorer = EdgeScorer::new(SimilarityFunction::Cosine, 4, centroid).unwrap();
+        for target in [0, 17, 39] {
+            let hits = scorer.search_packed(&vectors[target], &packed, &corrections, 3).unwrap();
+            assert_eq!(hits.len(), 3);
+            assert_eq!(hits[0].0, target);
+            assert!(hits[0].1 >= hits[1].1);
+        }
+
+        assert!(scorer.search_packed(&vectors[0], &packed[1..], &corrections, 3).is_err());
+        assert!(scorer.search_packed(&vectors[0], &packed, &corrections[1..], 3).is_err());
+        assert!(scorer.search_packed(&vectors[0][..64], &packed, &corrections, 3).is_err());
+        assert!(EdgeScorer::new(SimilarityFunction::Cosine, 2, vec![0.0; 4]).is_err());
+    }
+}
@@ -0,0 +1,325 @@
+//! 嵌入缓存
+//!
+//! RAG应用在每次会话中都会为同样的文本块重新计算嵌入，而嵌入模型的调用远比量化昂贵。
+//! 这里以文本内容的FNV-1a哈希为键缓存嵌入，写入时量化为半精度以减半占用，
+//! 超出容量时淘汰最久未使用的条目。缓存可以序列化后保存到IndexedDB，
+//! 下次会话加载后由 `Bbq::add_text` / `Bbq::add_cached` 在命中时直接写入索引。
+//!
+//! 命中与未命中时写入索引的都是半精度解码后的向量，同一段文本无论是否命中，
+//! 索引内容都完全相同
+
+use std::collections::HashMap;
+
+use crate::error_codes::{BbqError, Subject};
+use crate::byte_reader::{ByteReader, Fnv1a};
+use crate::half_precision::{decode_f16_into, encode_f16};
+use crate::memory_limits::{checked_region_len, try_with_capacity};
+
+/// 缓存文件魔数
+const EMBEDDING_CACHE_MAGIC: &[u8; 4] = b"BBQE";
+
+/// 缓存格式版本
+const EMBEDDING_CACHE_FORMAT_VERSION: u8 = 1;
+
+/// 文本内容的哈希，作为缓存的键
+///
+/// 64位FNV-1a，与平台和编译版本无关，保存的缓存在不同设备上可以直接复用
+pub fn text_content_hash(text: &str) -> u64 {
+    let mut hasher = Fnv1a::new();
+    hasher.write(text.as_bytes());
+    hasher.finish()
+}
+
+/// 缓存统计信息
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EmbeddingCacheStats {
+    /// 命中次数
+    pub hits: u64,
+    /// 未命中次数
+    pub misses: u64,
+    /// 当前缓存条目数
+    pub len: usize,
+    /// 最大缓存条目数
+    pub capacity: usize,
+}
+
+/// 缓存条目
+struct CachedEmbedding {
+    /// 半精度嵌入
+    half: Vec<u16>,
+    last_used: u64,
+}
+
+/// 以文本哈希为键的LRU嵌入缓存
+pub struct EmbeddingCache {
+    dims: usize,
+    capacity: usize,
+    entries: HashMap<u64, CachedEmbedding>,
+    /// 逻辑时钟，用于LRU淘汰
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl EmbeddingCache {
+    /// 创建缓存
+    ///
+    /// # 参数
+    /// * `dims` - 嵌入维度
+    /// * `capacity` - 最多缓存的嵌入数量，为0时不缓存
+    pub fn new(dims: usize, capacity: usize) -> Result<Self, BbqError> {
+        if dims == 0 {
+            return Err(BbqError::invalid_argument("维度必须大于0", "dimension must be greater than 0"));
+        }
+        Ok(Self {
+            dims,
+            capacity,
+            entries: HashMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        })
+    }
+
+    /// 嵌入维度
+    pub fn dims(&self) -> usize {
+        self.dims
+    }
+
+    /// 当前缓存条目数
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 查找文本的嵌入（半精度解码后）
+    pub fn get(&mut self, text: &str) -> Option<Vec<f32>> {
+        self.tick += 1;
+        match self.entries.get_mut(&text_content_hash(text)) {
+            Some(entry) => {
+                entry.last_used = self.tick;
+                self.hits += 1;
+                let mut embedding = vec![0.0f32; self.dims];
+                decode_f16_into(&entry.half, &mut embedding);
+                Some(embedding)
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// 写入文本的嵌入，超出容量时淘汰最久未使用的条目
+    ///
+    /// # 返回
+    /// 半精度量化后再解码的嵌入，即之后命中时返回的值
+    pub fn put(&mut self, text: &str, embedding: &[f32]) -> Result<Vec<f32>, BbqError> {
+        if embedding.len() != self.dims {
+            return Err(BbqError::dimension_mismatch(
+                format!("嵌入维度 {} 与缓存维度 {} 不匹配", embedding.len(), self.dims),
+                format!("embedding dimension {} does not match the cache dimension {}", embedding.len(), self.dims),
+            ));
+        }
+        let half = encode_f16(embedding);
+        let mut decoded = vec![0.0f32; self.dims];
+        decode_f16_into(&half, &mut decoded);
+        if let Some(j) = decoded.iter().position(|value| !value.is_finite()) {
+            return Err(BbqError::out_of_range(
+                format!("嵌入位置 {} 的值 {} 超出半精度范围", j, embedding[j]),
+                format!("embedding value {1} at position {0} is outside the half precision range", j, embedding[j]),
+            ));
+        }
+
+        if self.capacity == 0 {
+            return Ok(decoded);
+        }
+        self.tick += 1;
+        let key = text_content_hash(text);
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            if let Some(&oldest) = self.entries.iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key)
+            {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, CachedEmbedding { half, last_used: self.tick });
+        Ok(decoded)
+    }
+
+    /// 查找文本的嵌入，未命中时调用 `embed` 计算并写入缓存
+    pub fn get_or_embed<F>(&mut self, text: &str, embed: F) -> Result<Vec<f32>, BbqError>
+    where
+        F: FnOnce(&str) -> Result<Vec<f32>, BbqError>,
+    {
+        match self.get(text) {
+            Some(embedding) => Ok(embedding),
+            None => {
+                let embedding = embed(text)?;
+                self.put(text, &embedding)
+            }
+        }
+    }
+
+    /// 删除文本的嵌入，存在时返回true
+    pub fn remove(&mut self, text: &str) -> bool {
+        self.entries.remove(&text_content_hash(text)).is_some()
+    }
+
+    /// 清空缓存（更换嵌入模型时调用）
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// 获取统计信息
+    pub fn stats(&self) -> EmbeddingCacheStats {
+        EmbeddingCacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            len: self.entries.len(),
+            capacity: self.capacity,
+        }
+    }
+
+    /// 序列化为字节数组
+    ///
+    /// 格式（小端）：魔数 | 格式版本 | 维度 | 容量 | 数量 | (文本哈希, 半精度嵌入)*；
+    /// 条目按最近使用时间从旧到新排列，加载后保持淘汰顺序
+    pub fn save(&self) -> Result<Vec<u8>, BbqError> {
+        let mut entries: Vec<(&u64, &CachedEmbedding)> = self.entries.iter().collect();
+        entries.sort_by_key(|(_, entry)| entry.last_used);
+
+        let entry_bytes = checked_region_len(self.dims, 2, Subject::new("嵌入缓存向量", "embedding cache vector"))?.saturating_add(8);
+        let mut bytes = try_with_capacity(checked_region_len(entries.len(), entry_bytes, Subject::new("嵌入缓存", "embedding cache"))?.saturating_add(17), Subject::new("嵌入缓存", "embedding cache"))?;
+        bytes.extend_from_slice(EMBEDDING_CACHE_MAGIC);
+        bytes.push(EMBEDDING_CACHE_FORMAT_VERSION);
+        bytes.extend_from_slice(&(self.dims as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.capacity as u32).to_le_bytes());
+        bytes.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        for (key, entry) in entries {
+            bytes.extend_from_slice(&key.to_le_bytes());
+            for half in &entry.half {
+                bytes.extend_from_slice(&half.to_le_bytes());
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// 从字节数组加载，命中统计从0开始
+    pub fn load(bytes: &[u8]) -> Result<Self, BbqError> {
+        let mut reader = ByteReader::new(bytes);
+
+        if reader.take(4)? != EMBEDDING_CACHE_MAGIC {
+            return Err(BbqError::corrupt_data("无效的嵌入缓存：魔数不匹配", "invalid embedding cache: magic mismatch"));
+        }
+        let format_version = reader.read_u8()?;
+        if format_version == 0 || format_version > EMBEDDING_CACHE_FORMAT_VERSION {
+            return Err(BbqError::unsupported_version(
+                format!("不支持的嵌入缓存版本: {}", format_version),
+                format!("unsupported embedding cache version: {}", format_version),
+            ));
+        }
+        let dims = reader.read_u32()? as usize;
+        let capacity = reader.read_u32()? as usize;
+        let count = reader.read_u32()? as usize;
+
+        // 在分配之前确认声明的数量和维度与实际数据长度相符
+        let entry_bytes = checked_region_len(dims, 2, Subject::new("嵌入缓存向量", "embedding cache vector"))?.checked_add(8)
+            .ok_or_else(|| BbqError::corrupt_data("无效的嵌入缓存：维度过大", "invalid embedding cache: dimension is too large"))?;
+        if checked_region_len(count, entry_bytes, Subject::new("嵌入缓存", "embedding cache"))? != reader.remaining() {
+            return Err(BbqError::corrupt_data("无效的嵌入缓存：数据长度与条目数量不符", "invalid embedding cache: data length does not match the entry count"));
+        }
+        if count > capacity {
+            return Err(BbqError::corrupt_data(
+                format!("无效的嵌入缓存：条目数量 {} 超过容量 {}", count, capacity),
+                format!("invalid embedding cache: entry count {} exceeds the capacity {}", count, capacity),
+            ));
+        }
+
+        let mut cache = Self::new(dims, capacity)?;
+        for _ in 0..count {
+            let key = reader.read_u64()?;
+            let half: Vec<u16> = reader.take(dims * 2)?
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect();
+            cache.tick += 1;
+            cache.entries.insert(key, CachedEmbedding { half, last_used: cache.tick });
+        }
+        Ok(cache)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bbq::Bbq;
+    use crate::vector_similarity::SimilarityFunction;
+
+    fn embedding(seed: f32) -> Vec<f32> {
+        (0..8).map(|i| (seed + i as f32 * 0.37).sin()).collect()
+    }
+
+    #[test]
+    fn test_lru_eviction_and_persistence() {
+        let mut cache = EmbeddingCache::new(8, 2).unwrap();
+        let stored = cache.put("a", &embedding(1.0)).unwrap();
+        cache.put("b", &embedding(2.0)).unwrap();
+        // 访问a后，b成为最久未使用
+        assert_eq!(cache.get("a").unwrap(), stored);
+        cache.put("c", &embedding(3.0)).unwrap();
+        assert!(cache.get("b").is_none());
+        assert_eq!(cache.stats(), EmbeddingCacheStats { hits: 1, misses: 1, len: 2, capacity: 2 });
+
+        // 加载后淘汰顺序不变：a比c更旧
+        let mut loaded = EmbeddingCache::load(&cache.save().unwrap()).unwrap();
+        loaded.put("d", &embedding(4.0)).unwrap();
+        assert!(loaded.get("a").is_none());
+        assert!(loaded.get("c").is_some());
+
+        let bytes = cache.save().unwrap();
+        assert!(EmbeddingCache::load(&bytes[..bytes.len() - 1]).is_err());
+        assert!(cache.put("e", &[1e6; 8]).is_err());
+        assert!(cache.put("e", &[0.0; 4]).is_err());
+        assert_eq!(EmbeddingCache::new(8, 0).unwrap().put("a", &embedding(1.0)).unwrap(), stored);
+    }
+
+    #[test]
+    fn test_bbq_add_text_reuses_cached_embeddings() {
+        let texts = ["alpha", "beta", "gamma", "delta"];
+        let mut cache = EmbeddingCache::new(8, 16).unwrap();
+        let mut first = Bbq::new(8, SimilarityFunction::Cosine).unwrap();
+        let mut calls = 0;
+        for (i, text) in texts.iter().enumerate() {
+            let hit = first.add_text(text, text, &mut cache, |_| {
+                calls += 1;
+                Ok(embedding(i as f32))
+            }).unwrap();
+            assert!(!hit);
+        }
+
+        // 新会话：加载缓存后不再调用嵌入模型，索引内容与首次写入完全相同
+        let mut cache = EmbeddingCache::load(&cache.save().unwrap()).unwrap();
+        let mut second = Bbq::new(8, SimilarityFunction::Cosine).unwrap();
+        for text in texts {
+            assert!(second.add_cached(text, text, &mut cache).unwrap());
+            assert!(second.add_text(text, text, &mut cache, |_| Err(BbqError::invalid_argument("不应调用", "should not be called"))).unwrap());
+        }
+        assert_eq!(calls, texts.len());
+        for text in texts {
+            assert_eq!(first.get(text), second.get(text));
+        }
+        let query = embedding(1.0);
+        let hits = |bbq: &mut Bbq| bbq.query(&query, 2).unwrap().into_iter().map(|hit| (hit.id, hit.score)).collect::<Vec<_>>();
+        assert_eq!(hits(&mut first), hits(&mut second));
+
+        assert!(!second.add_cached("omega", "omega", &mut cache).unwrap());
+        assert!(!second.contains("omega"));
+        assert!(second.add_cached("x", "alpha", &mut EmbeddingCache::new(4, 1).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,392 @@
+//! 与语言无关的错误码和中英文错误消息
+//!
+//! 库中所有可失败的操作都返回 `BbqError`。错误码在出错处由对应的构造函数确定，
+//! 不从消息文本推断，改写消息不会改变错误码；中文和英文消息由同一组数值分别格式化。
+//! 当前语言的消息由 `message` 给出：默认英文，可通过 `set_error_locale` 切换为中文，
+//! 两种语言的消息始终可以分别从 `english` 和 `detail` 取得
+
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::memory_limits::OutOfMemory;
+
+/// 错误码
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// 分配失败
+    OutOfMemory,
+    /// 实例已冻结为只读
+    ReadOnly,
+    /// 操作期间索引被其他操作替换
+    ConcurrentModification,
+    /// 不支持的格式版本
+    UnsupportedVersion,
+    /// 二进制数据无效或已损坏
+    CorruptData,
+    /// 需要的数据尚未加载
+    NotReady,
+    /// 索引或划分尚未构建
+    NotBuilt,
+    /// 操作需要原始向量
+    MissingOriginalVectors,
+    /// 维度不匹配
+    DimensionMismatch,
+    /// 序号或数值超出范围
+    OutOfRange,
+    /// 输入包含NaN或无穷
+    InvalidValue,
+    /// 输入为空
+    EmptyInput,
+    /// 其他无效参数
+    InvalidArgument,
+    /// 无法归类的错误
+    Unknown,
+}
+
+impl ErrorCode {
+    /// 错误码名称，供调用方匹配
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorCode::OutOfMemory => "out_of_memory",
+            ErrorCode::ReadOnly => "read_only",
+            ErrorCode::ConcurrentModification => "concurrent_modification",
+            ErrorCode::UnsupportedVersion => "unsupported_version",
+            ErrorCode::CorruptData => "corrupt_data",
+            ErrorCode::NotReady => "not_ready",
+            ErrorCode::NotBuilt => "not_built",
+            ErrorCode::MissingOriginalVectors => "missing_original_vectors",
+            ErrorCode::DimensionMismatch => "dimension_mismatch",
+            ErrorCode::OutOfRange => "out_of_range",
+            ErrorCode::InvalidValue => "invalid_value",
+            ErrorCode::EmptyInput => "empty_input",
+            ErrorCode::InvalidArgument => "invalid_argument",
+            ErrorCode::Unknown => "unknown",
+        }
+    }
+
+    /// 英文概述
+    pub fn summary(self) -> &'static str {
+        match self {
+            ErrorCode::OutOfMemory => "out of memory",
+            ErrorCode::ReadOnly => "the instance is frozen read-only; call unfreeze first",
+            ErrorCode::ConcurrentModification => "the index was replaced by a concurrent operation; retry",
+            ErrorCode::UnsupportedVersion => "unsupported format version",
+            ErrorCode::CorruptData => "invalid or corrupt binary data",
+            ErrorCode::NotReady => "the required data has not been loaded yet",
+            ErrorCode::NotBuilt => "the index has not been built yet",
+            ErrorCode::MissingOriginalVectors => "this operation requires original vectors; enable keep_original_vectors",
+            ErrorCode::DimensionMismatch => "vector dimension mismatch",
+            ErrorCode::OutOfRange => "value out of range",
+            ErrorCode::InvalidValue => "input contains NaN or infinite values",
+            ErrorCode::EmptyInput => "input must not be empty",
+            ErrorCode::InvalidArgument => "invalid argument",
+            ErrorCode::Unknown => "operation failed",
+        }
+    }
+}
+
+/// 错误消息中的数据或用途描述，中英文各一份
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Subject {
+    /// 中文描述
+    pub zh: &'static str,
+    /// 英文描述
+    pub en: &'static str,
+}
+
+impl Subject {
+    /// 创建描述
+    pub const fn new(zh: &'static str, en: &'static str) -> Self {
+        Self { zh, en }
+    }
+
+    /// 指定语言下的描述
+    pub fn in_locale(self, locale: ErrorLocale) -> &'static str {
+        match locale {
+            ErrorLocale::English => self.en,
+            ErrorLocale::Chinese => self.zh,
+        }
+    }
+}
+
+/// 错误消息的语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorLocale {
+    /// 英文（默认）
+    #[default]
+    English,
+    /// 中文（库内部的原始消息）
+    Chinese,
+}
+
+impl ErrorLocale {
+    /// 从名称解析："en" | "zh"（也接受 "en-US"、"zh-CN" 等带地区的写法）
+    pub fn from_name(name: &str) -> Result<Self, BbqError> {
+        let language = name.split(['-', '_']).next().unwrap_or_default().to_ascii_lowercase();
+        match language.as_str() {
+            "en" | "english" => Ok(ErrorLocale::English),
+            "zh" | "chinese" => Ok(ErrorLocale::Chinese),
+            _ => Err(BbqError::invalid_argument(format!("不支持的错误消息语言: {}", name), format!("unsupported error message locale: {}", name))),
+        }
+    }
+
+    /// 语言名称
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorLocale::English => "en",
+            ErrorLocale::Chinese => "zh",
+        }
+    }
+}
+
+static ERROR_LOCALE: AtomicU8 = AtomicU8::new(0);
+
+/// 设置全局的错误消息语言
+pub fn set_error_locale(locale: ErrorLocale) {
+    ERROR_LOCALE.store(locale as u8, Ordering::Relaxed);
+}
+
+/// 当前的错误消息语言
+pub fn error_locale() -> ErrorLocale {
+    match ERROR_LOCALE.load(Ordering::Relaxed) {
+        0 => ErrorLocale::English,
+        _ => ErrorLocale::Chinese,
+    }
+}
+
+/// 带错误码的错误
+///
+/// 在出错处用对应错误码的构造函数创建，中文和英文消息由同一组数值分别格式化
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BbqError {
+    code: ErrorCode,
+    detail: String,
+    english: String,
+    out_of_memory: Option<Box<OutOfMemory>>,
+}
+
+impl BbqError {
+    /// 由错误码和中英文消息构造
+    pub fn new(code: ErrorCode, detail: impl Into<String>, english: impl Into<String>) -> Self {
+        Self { code, detail: detail.into(), english: english.into(), out_of_memory: None }
+    }
+
+    /// 分配失败，消息由请求的字节数和堆状态生成
+    pub fn out_of_memory(details: OutOfMemory) -> Self {
+        let (mut detail, mut english) = (
+            format!("内存不足：{}需要 {} 字节", details.what.zh, details.requested_bytes),
+            format!("out of memory: {} requires {} bytes", details.what.en, details.requested_bytes),
+        );
+        match details.heap.heap_bytes {
+            Some(heap_bytes) => {
+                detail.push_str(&format!("，当前堆 {} 字节，上限 {} 字节", heap_bytes, details.heap.max_bytes));
+                english.push_str(&format!(", heap is {} bytes of at most {}", heap_bytes, details.heap.max_bytes));
+            }
+            None => {
+                detail.push_str(&format!("，寻址上限 {} 字节", details.heap.max_bytes));
+                english.push_str(&format!(", addressable limit is {} bytes", details.heap.max_bytes));
+            }
+        }
+        Self { out_of_memory: Some(Box::new(details)), ..Self::new(ErrorCode::OutOfMemory, detail, english) }
+    }
+
+    /// 实例已冻结为只读
+    pub fn read_only(target: Subject) -> Self {
+        Self::new(
+            ErrorCode::ReadOnly,
+            format!("{}已冻结为只读，请先调用unfreeze", target.zh),
+            format!("the {} is frozen read-only; call unfreeze first", target.en),
+        )
+    }
+
+    /// 操作期间索引被其他操作替换
+    pub fn concurrent_modification(operation: Subject) -> Self {
+        Self::new(
+            ErrorCode::ConcurrentModification,
+            format!("{}期间索引已被替换，请重试", operation.zh),
+            format!("the index was replaced during {}; retry", operation.en),
+        )
+    }
+
+    /// 操作需要原始向量
+    pub fn missing_original_vectors(purpose: Subject) -> Self {
+        Self::new(
+            ErrorCode::MissingOriginalVectors,
+            format!("{}需要原始向量，请在配置中启用keep_original_vectors", purpose.zh),
+            format!("{} requires original vectors; enable keep_original_vectors", purpose.en),
+        )
+    }
+
+    /// 不支持的格式版本
+    pub fn unsupported_version(detail: impl Into<String>, english: impl Into<String>) -> Self {
+        Self::new(ErrorCode::UnsupportedVersion, detail, english)
+    }
+
+    /// 二进制数据无效或已损坏
+    pub fn corrupt_data(detail: impl Into<String>, english: impl Into<String>) -> Self {
+        Self::new(ErrorCode::CorruptData, detail, english)
+    }
+
+    /// 需要的数据尚未加载
+    pub fn not_ready(detail: impl Into<String>, english: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotReady, detail, english)
+    }
+
+    /// 索引或划分尚未构建
+    pub fn not_built(detail: impl Into<String>, english: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotBuilt, detail, english)
+    }
+
+    /// 维度不匹配
+    pub fn dimension_mismatch(detail: impl Into<String>, english: impl Into<String>) -> Self {
+        Self::new(ErrorCode::DimensionMismatch, detail, english)
+    }
+
+    /// 序号或数值超出范围
+    pub fn out_of_range(detail: impl Into<String>, english: impl Into<String>) -> Self {
+        Self::new(ErrorCode::OutOfRange, detail, english)
+    }
+
+    /// 输入包含NaN或无穷
+    pub fn invalid_value(detail: impl Into<String>, english: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidValue, detail, english)
+    }
+
+    /// 输入为空
+    pub fn empty_input(detail: impl Into<String>, english: impl Into<String>) -> Self {
+        Self::new(ErrorCode::EmptyInput, detail, english)
+    }
+
+    /// 其他无效参数
+    pub fn invalid_argument(detail: impl Into<String>, english: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidArgument, detail, english)
+    }
+
+    /// 在消息前加上发生错误的上下文，错误码不变
+    pub fn context(mut self, context: Subject) -> Self {
+        self.detail = format!("{}: {}", context.zh, self.detail);
+        self.english = format!("{}: {}", context.en, self.english);
+        self
+    }
+
+    /// 错误码
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    /// 中文消息
+    pub fn detail(&self) -> &str {
+        &self.detail
+    }
+
+    /// 英文消息
+    pub fn english(&self) -> &str {
+        &self.english
+    }
+
+    /// 内存不足时分配失败的详情
+    pub fn out_of_memory_details(&self) -> Option<&OutOfMemory> {
+        self.out_of_memory.as_deref()
+    }
+
+    /// 当前语言下的消息
+    pub fn message(&self) -> &str {
+        self.message_in(error_locale())
+    }
+
+    /// 指定语言下的消息
+    pub fn message_in(&self, locale: ErrorLocale) -> &str {
+        match locale {
+            ErrorLocale::Chinese => &self.detail,
+            ErrorLocale::English => &self.english,
+        }
+    }
+}
+
+impl fmt::Display for BbqError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for BbqError {}
+
+/// 转为字符串时保留中文消息
+impl From<BbqError> for String {
+    fn from(error: BbqError) -> Self {
+        error.detail
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_limits::HeapStats;
+    use crate::vector_similarity::{compute_similarity, parse_metric, SimilarityFunction};
+    use crate::vector_utils::compute_centroid_compensated;
+
+    #[test]
+    fn test_codes_at_failure_sites() {
+        let mismatch = compute_similarity(&[1.0, 2.0], &[1.0], SimilarityFunction::Cosine).unwrap_err();
+        assert_eq!(mismatch.code(), ErrorCode::DimensionMismatch);
+        assert_eq!(compute_centroid_compensated(&[]).unwrap_err().code(), ErrorCode::EmptyInput);
+        assert_eq!(parse_metric("hamming").unwrap_err().code(), ErrorCode::InvalidArgument);
+    }
+
+    #[test]
+    #[cfg(feature = "index")]
+    fn test_instance_codes() {
+        use crate::bbq::Bbq;
+
+        assert_eq!(Bbq::load(b"nope").err().map(|e| e.code()), Some(ErrorCode::CorruptData));
+        let mut bbq = Bbq::new(2, SimilarityFunction::Cosine).unwrap();
+        bbq.freeze();
+        let frozen = bbq.add("a", &[1.0, 0.0]).unwrap_err();
+        assert_eq!(frozen.code(), ErrorCode::ReadOnly);
+        assert_eq!(frozen.detail(), "实例已冻结为只读，请先调用unfreeze");
+        assert_eq!(frozen.english(), "the instance is frozen read-only; call unfreeze first");
+    }
+
+    #[test]
+    fn test_messages_in_both_locales() {
+        let error = BbqError::dimension_mismatch(
+            format!("查询维度 {} 与索引维度 {} 不匹配", 3, 128),
+            format!("query dimension {} does not match index dimension {}", 3, 128),
+        );
+        assert_eq!(error.message_in(ErrorLocale::English), "query dimension 3 does not match index dimension 128");
+        assert_eq!(error.message_in(ErrorLocale::Chinese), "查询维度 3 与索引维度 128 不匹配");
+        // 上下文同时加在两种语言的消息前，错误码不变
+        let error = error.context(Subject::new("批量搜索", "batch search"));
+        assert_eq!(error.code(), ErrorCode::DimensionMismatch);
+        assert_eq!(error.detail(), "批量搜索: 查询维度 3 与索引维度 128 不匹配");
+        assert_eq!(error.english(), "batch search: query dimension 3 does not match index dimension 128");
+        // 转回字符串时保留中文消息
+        assert_eq!(String::from(error), "批量搜索: 查询维度 3 与索引维度 128 不匹配");
+
+        let missing = BbqError::missing_original_vectors(Subject::new("重排", "rescoring"));
+        assert_eq!(missing.code(), ErrorCode::MissingOriginalVectors);
+        assert_eq!(missing.english(), "rescoring requires original vectors; enable keep_original_vectors");
+    }
+
+    #[test]
+    fn test_out_of_memory_fields() {
+        let details = OutOfMemory {
+            what: Subject::new("量化向量", "quantized vectors"),
+            requested_bytes: 1024,
+            heap: HeapStats { heap_bytes: None, max_bytes: 4096 },
+        };
+        let error = BbqError::out_of_memory(details.clone());
+        assert_eq!(error.code(), ErrorCode::OutOfMemory);
+        assert_eq!(error.out_of_memory_details(), Some(&details));
+        assert_eq!(error.detail(), "内存不足：量化向量需要 1024 字节，寻址上限 4096 字节");
+        assert_eq!(error.english(), "out of memory: quantized vectors requires 1024 bytes, addressable limit is 4096 bytes");
+        assert!(BbqError::read_only(Subject::new("索引", "index")).out_of_memory_details().is_none());
+    }
+
+    #[test]
+    fn test_locale_names() {
+        assert_eq!(ErrorLocale::from_name("zh-CN").unwrap(), ErrorLocale::Chinese);
+        assert_eq!(ErrorLocale::from_name("EN").unwrap(), ErrorLocale::English);
+        assert_eq!(ErrorLocale::from_name("fr").unwrap_err().code(), ErrorCode::InvalidArgument);
+    }
+}
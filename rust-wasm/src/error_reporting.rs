@@ -0,0 +1,192 @@
+//! 错误与panic上报
+//!
+//! 浏览器中的错误和panic默认只出现在控制台里，应用无法收集。
+//! 这里允许注册一个全局的错误接收器，每次操作返回错误或发生panic时
+//! 收到结构化的报告（种类、操作名、消息、配置指纹），由应用转发给自己的遥测系统。
+//!
+//! 操作名和配置指纹来自当前线程上的 `OperationScope`：
+//! 入口函数开始时进入一个作用域，作用域内的错误和panic都带上它的信息
+
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::sync::{Arc, Once, RwLock};
+
+/// 报告种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// 操作返回了错误
+    Error,
+    /// 发生了panic
+    Panic,
+    /// 搜索时发现不一致的数据并已跳过（`paranoid` 特性）
+    Inconsistency,
+    /// 分配失败，操作已放弃而实例仍可继续使用（见 `memory_limits`）
+    OutOfMemory,
+}
+
+impl ErrorKind {
+    /// 种类名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::Error => "error",
+            ErrorKind::Panic => "panic",
+            ErrorKind::Inconsistency => "inconsistency",
+            ErrorKind::OutOfMemory => "out_of_memory",
+        }
+    }
+}
+
+/// 结构化的错误报告
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorReport {
+    /// 报告种类
+    pub kind: ErrorKind,
+    /// 出错的操作，不在任何作用域内时为None
+    pub operation: Option<&'static str>,
+    /// 错误消息（panic时包含源码位置）
+    pub message: String,
+    /// 出错时所用配置的指纹，见 `config_fingerprint`
+    pub config_fingerprint: Option<u64>,
+}
+
+/// 错误接收器
+pub trait ErrorSink: Send + Sync {
+    /// 收到一份报告；在出错的线程上同步调用
+    fn report(&self, report: &ErrorReport);
+}
+
+impl<F: Fn(&ErrorReport) + Send + Sync> ErrorSink for F {
+    fn report(&self, report: &ErrorReport) {
+        self(report)
+    }
+}
+
+static ERROR_SINK: RwLock<Option<Arc<dyn ErrorSink>>> = RwLock::new(None);
+
+static PANIC_HOOK: Once = Once::new();
+
+thread_local! {
+    static CURRENT_OPERATION: RefCell<Option<(&'static str, Option<u64>)>> = const { RefCell::new(None) };
+}
+
+/// 注册或移除全局错误接收器
+pub fn set_error_sink(sink: Option<Arc<dyn ErrorSink>>) {
+    match ERROR_SINK.write() {
+        Ok(mut guard) => *guard = sink,
+        Err(poisoned) => *poisoned.into_inner() = sink,
+    }
+}
+
+/// 把一条错误报告给接收器（未注册时忽略），操作名和指纹取当前作用域
+pub fn report_error(kind: ErrorKind, message: &str) {
+    let sink = match ERROR_SINK.read() {
+        Ok(guard) => guard.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    };
+    let Some(sink) = sink else {
+        return;
+    };
+    let (operation, config_fingerprint) = CURRENT_OPERATION
+        .with(|current| *current.borrow())
+        .map_or((None, None), |(operation, fingerprint)| (Some(operation), fingerprint));
+    sink.report(&ErrorReport {
+        kind,
+        operation,
+        message: message.to_string(),
+        config_fingerprint,
+    });
+}
+
+/// 安装panic hook（只安装一次）：panic时先上报，再交给之前的hook（如console_error_panic_hook）
+pub fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let payload = info.payload();
+            let message = payload.downcast_ref::<&str>().copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("未知panic");
+            let message = match info.location() {
+                Some(location) => format!("{} ({}:{}:{})", message, location.file(), location.line(), location.column()),
+                None => message.to_string(),
+            };
+            report_error(ErrorKind::Panic, &message);
+            previous(info);
+        }));
+    });
+}
+
+/// 配置指纹：对配置的Debug表示取FNV-1a哈希
+///
+/// 同一版本的库中，相同的配置得到相同的指纹，用于在遥测中把错误按配置归类
+pub fn config_fingerprint<T: Debug>(config: &T) -> u64 {
+    const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+    format!("{:?}", config).bytes().fold(FNV_OFFSET, |hash, byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME))
+}
+
+/// 当前线程上的操作作用域，离开作用域时恢复外层作用域
+pub struct OperationScope {
+    previous: Option<(&'static str, Option<u64>)>,
+}
+
+impl OperationScope {
+    /// 进入一个操作
+    ///
+    /// # 参数
+    /// * `operation` - 操作名
+    /// * `config_fingerprint` - 操作所用配置的指纹
+    pub fn enter(operation: &'static str, config_fingerprint: Option<u64>) -> Self {
+        let previous = CURRENT_OPERATION.with(|current| current.replace(Some((operation, config_fingerprint))));
+        Self { previous }
+    }
+}
+
+impl Drop for OperationScope {
+    fn drop(&mut self) {
+        CURRENT_OPERATION.with(|current| *current.borrow_mut() = self.previous);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_error_reports_carry_operation_scope() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let collected = reports.clone();
+        set_error_sink(Some(Arc::new(move |report: &ErrorReport| {
+            // 其它测试线程的报告不计入
+            if report.message.starts_with("scope-test") {
+                collected.lock().unwrap().push(report.clone());
+            }
+        })));
+        install_panic_hook();
+
+        report_error(ErrorKind::Error, "scope-test outside");
+        {
+            let _outer = OperationScope::enter("build_index", Some(7));
+            {
+                let _inner = OperationScope::enter("search", None);
+                report_error(ErrorKind::Error, "scope-test inner");
+            }
+            report_error(ErrorKind::Error, "scope-test outer");
+            let _ = std::panic::catch_unwind(|| panic!("scope-test panic"));
+        }
+        set_error_sink(None);
+        report_error(ErrorKind::Error, "scope-test after removal");
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 4);
+        assert_eq!((reports[0].operation, reports[0].config_fingerprint), (None, None));
+        assert_eq!((reports[1].operation, reports[1].config_fingerprint), (Some("search"), None));
+        assert_eq!((reports[2].operation, reports[2].config_fingerprint), (Some("build_index"), Some(7)));
+        assert_eq!(reports[3].kind, ErrorKind::Panic);
+        assert_eq!(reports[3].operation, Some("build_index"));
+        assert!(reports[3].message.contains("error_reporting.rs"));
+        assert_eq!(config_fingerprint(&(1, "a")), config_fingerprint(&(1, "a")));
+        assert_ne!(config_fingerprint(&(1, "a")), config_fingerprint(&(2, "a")));
+    }
+}
@@ -0,0 +1,184 @@
+//! 读取Elasticsearch/OpenSearch的BBQ向量段
+//!
+//! ES的 `bbq_hnsw` / `bbq_flat` 和OpenSearch的二值量化字段使用Lucene 10.2的二值量化格式，
+//! 量化算法与本库相同（索引1位、查询4位）。字段的量化向量数据（.veb文件中该字段的区域）
+//! 按序号连续存放，每个向量依次为：
+//!
+//! - 1位打包向量，维度按64位对齐，高位在前
+//! - lowerInterval、upperInterval、additionalCorrection（小端f32）
+//! - quantizedComponentSum（小端u16）
+//!
+//! 质心和相似性函数保存在元数据（.vemb文件）中，由调用方解析后传入。
+//! 加载结果是一个查询包，浏览器可以在下载的段上离线搜索，评分与服务端的量化评分一致；
+//! 结果中的index为段内的向量序号，换算为文档ID需要段的ord到doc映射
+
+use crate::error_codes::{BbqError, Subject};
+use crate::memory_limits::checked_region_len;
+use crate::optimized_scalar_quantizer::{OptimizedScalarQuantizer, QuantizationResult};
+use crate::query_pack::QueryPack;
+use crate::vector_similarity::SimilarityFunction;
+
+/// Lucene二值量化向量的查询位数
+const LUCENE_QUERY_BITS: u8 = 4;
+
+/// 每个向量修正项的字节数：3个f32和1个u16
+const LUCENE_CORRECTION_BYTES: usize = 3 * 4 + 2;
+
+/// Lucene打包向量的字节数：维度先按64位对齐
+pub fn lucene_packed_len(dimension: usize) -> usize {
+    OptimizedScalarQuantizer::packed_len(dimension, true)
+}
+
+/// 段中每个向量占用的字节数
+pub fn lucene_vector_stride(dimension: usize) -> usize {
+    lucene_packed_len(dimension) + LUCENE_CORRECTION_BYTES
+}
+
+/// 由Lucene `VectorSimilarityFunction` 的序号解析相似性函数
+///
+/// EUCLIDEAN=0、DOT_PRODUCT=1、COSINE=2、MAXIMUM_INNER_PRODUCT=3；
+/// Lucene对DOT_PRODUCT和COSINE使用同一种量化评分，对应本库的余弦
+pub fn similarity_from_lucene_ordinal(ordinal: u32) -> Result<SimilarityFunction, BbqError> {
+    match ordinal {
+        0 => Ok(SimilarityFunction::Euclidean),
+        1 | 2 => Ok(SimilarityFunction::Cosine),
+        3 => Ok(SimilarityFunction::MaximumInnerProduct),
+        _ => Err(BbqError::corrupt_data(
+            format!("未知的Lucene相似性函数序号: {}", ordinal),
+            format!("unknown Lucene similarity function ordinal: {}", ordinal),
+        )),
+    }
+}
+
+/// 由ES/OpenSearch映射中的 `similarity` 名称解析相似性函数
+///
+/// 注意ES的 `dot_product` 要求单位向量，对应本库的余弦，而不是最大内积
+pub fn similarity_from_es_name(name: &str) -> Result<SimilarityFunction, BbqError> {
+    match name.to_lowercase().as_str() {
+        "l2_norm" | "l2" => Ok(SimilarityFunction::Euclidean),
+        "dot_product" | "cosine" | "cosinesimil" => Ok(SimilarityFunction::Cosine),
+        "max_inner_product" | "innerproduct" => Ok(SimilarityFunction::MaximumInnerProduct),
+        _ => Err(BbqError::invalid_argument(format!("不支持的ES相似性: {}", name), format!("unsupported ES similarity: {}", name))),
+    }
+}
+
+/// 解析字段的量化向量数据，加载为查询包
+///
+/// # 参数
+/// * `bytes` - .veb文件中该字段的量化向量区域（元数据中的vectorDataOffset和vectorDataLength）
+/// * `similarity_function` - 字段的相似性函数
+/// * `centroid` - 元数据中的质心，长度即向量维度
+pub fn load_lucene_bbq_vectors(
+    bytes: &[u8],
+    similarity_function: SimilarityFunction,
+    centroid: &[f32],
+) -> Result<QueryPack, BbqError> {
+    let dimension = centroid.len();
+    if dimension == 0 {
+        return Err(BbqError::empty_input("质心维度不能为0", "centroid dimension must not be 0"));
+    }
+    if centroid.iter().any(|value| !value.is_finite()) {
+        return Err(BbqError::invalid_value("质心包含无效值", "centroid contains invalid values"));
+    }
+    let stride = lucene_vector_stride(dimension);
+    if !bytes.len().is_multiple_of(stride) {
+        return Err(BbqError::corrupt_data(
+            format!("无效的BBQ段：数据长度 {} 不是每个向量 {} 字节的整数倍", bytes.len(), stride),
+            format!("invalid BBQ segment: data length {} is not a multiple of {} bytes per vector", bytes.len(), stride),
+        ));
+    }
+    let count = bytes.len() / stride;
+    let lucene_packed = lucene_packed_len(dimension);
+    let packed_size = dimension.div_ceil(8);
+
+    let mut packed = Vec::with_capacity(checked_region_len(count, packed_size, Subject::new("BBQ段打包向量", "BBQ segment packed vectors"))?);
+    let mut corrections = Vec::with_capacity(count);
+    for (ord, record) in bytes.chunks_exact(stride).enumerate() {
+        let (bits, terms) = record.split_at(lucene_packed);
+        // 按64位对齐补出的字节不参与评分
+        packed.extend_from_slice(&bits[..packed_size]);
+        let read_f32 = |offset: usize| f32::from_le_bytes([terms[offset], terms[offset + 1], terms[offset + 2], terms[offset + 3]]);
+        let correction = QuantizationResult {
+            lower_interval: read_f32(0),
+            upper_interval: read_f32(4),
+            additional_correction: read_f32(8),
+            quantized_component_sum: u16::from_le_bytes([terms[12], terms[13]]) as f32,
+        };
+        if !(correction.lower_interval.is_finite()
+            && correction.upper_interval.is_finite()
+            && correction.additional_correction.is_finite())
+        {
+            return Err(BbqError::invalid_value(
+                format!("无效的BBQ段：序号 {} 的修正项包含无效值", ord),
+                format!("invalid BBQ segment: corrections of ordinal {} contain invalid values", ord),
+            ));
+        }
+        corrections.push(correction);
+    }
+
+    QueryPack::from_parts(similarity_function, LUCENE_QUERY_BITS, centroid.to_vec(), corrections, packed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quantized_index::{QuantizedIndex, QuantizedIndexConfig};
+    use crate::vector_utils::create_random_vector;
+
+    /// 按Lucene的布局写出索引中的量化向量
+    fn encode_segment(index: &QuantizedIndex) -> Vec<u8> {
+        let generation = index.snapshot().unwrap();
+        let values = generation.values();
+        let dimension = values.dimension();
+        let mut bytes = Vec::new();
+        for (_, packed, correction) in values.iter() {
+            let mut bits = packed.to_vec();
+            bits.resize(lucene_packed_len(dimension), 0);
+            bytes.extend_from_slice(&bits);
+            for value in [correction.lower_interval, correction.upper_interval, correction.additional_correction] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            bytes.extend_from_slice(&(correction.quantized_component_sum as u16).to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_segment_scores_match_index() {
+        for similarity_function in [SimilarityFunction::Euclidean, SimilarityFunction::Cosine, SimilarityFunction::MaximumInnerProduct] {
+            let vectors: Vec<Vec<f32>> = (0..300)
+                .map(|_| create_random_vector(40, -1.0, 1.0))
+                .collect();
+            let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+                similarity_function,
+                ..QuantizedIndexConfig::default()
+            }).unwrap();
+            index.build_index(&vectors).unwrap();
+
+            let bytes = encode_segment(&index);
+            assert_eq!(bytes.len(), 300 * (8 + 14));
+            let centroid = index.snapshot().unwrap().values().get_centroid().to_vec();
+            let pack = load_lucene_bbq_vectors(&bytes, similarity_function, &centroid).unwrap();
+            assert_eq!(pack.len(), 300);
+
+            let expected = index.search_nearest_neighbors(&vectors[9], 10).unwrap();
+            let actual = pack.search_nearest_neighbors(&vectors[9], 10).unwrap();
+            assert_eq!(
+                actual.iter().map(|r| (r.index, r.score)).collect::<Vec<_>>(),
+                expected.iter().map(|r| (r.index, r.score)).collect::<Vec<_>>(),
+            );
+
+            assert!(load_lucene_bbq_vectors(&bytes[..bytes.len() - 1], similarity_function, &centroid).is_err());
+        }
+    }
+
+    #[test]
+    fn test_similarity_names() {
+        assert_eq!(similarity_from_lucene_ordinal(1).unwrap(), SimilarityFunction::Cosine);
+        assert_eq!(similarity_from_lucene_ordinal(3).unwrap(), SimilarityFunction::MaximumInnerProduct);
+        assert!(similarity_from_lucene_ordinal(4).is_err());
+        assert_eq!(similarity_from_es_name("L2_NORM").unwrap(), SimilarityFunction::Euclidean);
+        assert_eq!(similarity_from_es_name("dot_product").unwrap(), SimilarityFunction::Cosine);
+        assert!(similarity_from_es_name("hamming").is_err());
+    }
+}
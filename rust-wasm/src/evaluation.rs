@@ -0,0 +1,170 @@
+//! 搜索质量评估
+//!
+//! 以原始向量的精确搜索为基准估计量化搜索的召回率，
+//! 用于自适应调整重排过采样倍数；也可以从索引中抽样自查询生成真值并缓存，
+//! 变更后无需调用方提供查询集即可重新估计召回率
+
+use crate::binary_quantized_scorer::BinaryQuantizedScorer;
+use crate::vector_similarity::descending_score_order;
+
+/// 计算召回率：近似结果中命中精确Top-K的比例
+///
+/// # 参数
+/// * `approximate` - 近似搜索返回的向量索引
+/// * `exact` - 精确搜索返回的向量索引
+///
+/// # 返回
+/// 召回率（0到1之间），精确结果为空时返回1
+pub fn recall_at_k(approximate: &[usize], exact: &[usize]) -> f32 {
+    if exact.is_empty() {
+        return 1.0;
+    }
+    let hits = exact.iter()
+        .filter(|index| approximate.contains(index))
+        .count();
+    hits as f32 / exact.len() as f32
+}
+
+/// 暴力计算精确Top-K
+///
+/// # 参数
+/// * `vectors` - 原始向量集合（与索引相同的预处理）
+/// * `query_vector` - 预处理后的查询向量
+/// * `k` - 返回数量
+/// * `scorer` - 评分器，决定相似性函数
+///
+/// # 返回
+/// 按精确分数降序排列的向量索引
+pub fn compute_exact_top_k(
+    vectors: &[Vec<f32>],
+    query_vector: &[f32],
+    k: usize,
+    scorer: &BinaryQuantizedScorer,
+) -> Vec<usize> {
+    compute_exact_top_k_where(vectors, query_vector, k, scorer, |_| true)
+}
+
+/// 暴力计算满足条件的向量中的精确Top-K
+///
+/// # 参数
+/// * `vectors` - 原始向量集合（与索引相同的预处理）
+/// * `query_vector` - 预处理后的查询向量
+/// * `k` - 返回数量
+/// * `scorer` - 评分器，决定相似性函数
+/// * `include` - 判断序号是否参与排序（如排除已删除向量）
+///
+/// # 返回
+/// 按精确分数降序排列的向量索引
+pub fn compute_exact_top_k_where<F: Fn(usize) -> bool>(
+    vectors: &[Vec<f32>],
+    query_vector: &[f32],
+    k: usize,
+    scorer: &BinaryQuantizedScorer,
+    include: F,
+) -> Vec<usize> {
+    let mut scored: Vec<(usize, f32)> = vectors.iter()
+        .enumerate()
+        .filter(|(i, _)| include(*i))
+        .map(|(i, vector)| (i, scorer.compute_exact_score(query_vector, vector)))
+        .collect();
+    scored.sort_by(|a, b| descending_score_order(a.1, b.1));
+    scored.into_iter().take(k).map(|(i, _)| i).collect()
+}
+
+/// 计算多组查询的平均召回率
+///
+/// # 参数
+/// * `approximate` - 每个查询的近似结果
+/// * `exact` - 每个查询的精确结果
+///
+/// # 返回
+/// 平均召回率
+pub fn mean_recall(approximate: &[Vec<usize>], exact: &[Vec<usize>]) -> f32 {
+    if exact.is_empty() {
+        return 1.0;
+    }
+    let total: f32 = approximate.iter()
+        .zip(exact.iter())
+        .map(|(a, e)| recall_at_k(a, e))
+        .sum();
+    total / exact.len() as f32
+}
+
+/// 自查询真值
+///
+/// 以抽样的已存储向量作为查询，记录其精确近邻。
+/// 近邻按2k的深度保存，删除部分向量后仍可取出前k个未删除的精确近邻
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroundTruth {
+    /// 评估使用的k
+    pub k: usize,
+    /// 作为查询的向量序号（升序）
+    pub queries: Vec<usize>,
+    /// 每个查询的精确近邻（按精确分数降序）
+    pub neighbors: Vec<Vec<usize>>,
+}
+
+impl GroundTruth {
+    /// 按压缩后的序号重新编号
+    ///
+    /// # 参数
+    /// * `live` - 压缩后保留的旧序号（升序），新序号即其下标
+    ///
+    /// # 返回
+    /// 已删除的查询和近邻被移除后的真值
+    pub fn remap(&self, live: &[usize]) -> GroundTruth {
+        let map = |ord: &usize| live.binary_search(ord).ok();
+        let mut queries = Vec::with_capacity(self.queries.len());
+        let mut neighbors = Vec::with_capacity(self.neighbors.len());
+        for (query, list) in self.queries.iter().zip(&self.neighbors) {
+            if let Some(query) = map(query) {
+                queries.push(query);
+                neighbors.push(list.iter().filter_map(map).collect());
+            }
+        }
+        GroundTruth { k: self.k, queries, neighbors }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector_similarity::SimilarityFunction;
+    use crate::vector_utils::reservoir_sample;
+
+    #[test]
+    fn test_recall_at_k() {
+        assert_eq!(recall_at_k(&[1, 2, 3], &[3, 2, 1]), 1.0);
+        assert_eq!(recall_at_k(&[1, 5], &[1, 2]), 0.5);
+        assert_eq!(recall_at_k(&[], &[]), 1.0);
+    }
+
+    #[test]
+    fn test_exact_top_k() {
+        let scorer = BinaryQuantizedScorer::new(SimilarityFunction::Euclidean);
+        let vectors = vec![vec![0.0, 0.0], vec![5.0, 5.0], vec![1.0, 1.0]];
+        let top = compute_exact_top_k(&vectors, &[0.9, 0.9], 2, &scorer);
+        assert_eq!(top, vec![2, 0]);
+    }
+
+    #[test]
+    fn test_reservoir_sample_and_remap() {
+        let mut rng = fastrand::Rng::with_seed(1);
+        let sample = reservoir_sample(0..100, 10, &mut rng);
+        assert_eq!(sample.len(), 10);
+        assert!(sample.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(reservoir_sample(0..3, 10, &mut rng), vec![0, 1, 2]);
+
+        let truth = GroundTruth { k: 1, queries: vec![1, 4], neighbors: vec![vec![1, 3], vec![4, 1]] };
+        let remapped = truth.remap(&[0, 3, 4]);
+        assert_eq!(remapped.queries, vec![2]);
+        assert_eq!(remapped.neighbors, vec![vec![2]]);
+    }
+
+    #[test]
+    fn test_mean_recall() {
+        let approximate = vec![vec![1, 2], vec![3, 9]];
+        let exact = vec![vec![1, 2], vec![3, 4]];
+        assert!((mean_recall(&approximate, &exact) - 0.75).abs() < 1e-6);
+    }
+}
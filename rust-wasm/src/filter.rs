@@ -0,0 +1,253 @@
+//! 过滤条件与向量属性
+//!
+//! 过滤搜索和批量删除共用同一套谓词：序号位图、属性比较以及它们的与/或/非组合。
+//! 属性按向量序号存放，值可以是数字、字符串或布尔值；
+//! JS侧以JSON描述过滤条件，例如
+//! `{ "and": [{ "attribute": { "key": "tenant", "predicate": { "eq": "a" } } }, { "ordinals": [1, 2] }] }`
+
+use crate::error_codes::BbqError;
+use std::collections::BTreeMap;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// 属性值
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum AttributeValue {
+    /// 布尔值
+    Bool(bool),
+    /// 数字
+    Number(f64),
+    /// 字符串
+    Text(String),
+}
+
+impl AttributeValue {
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            AttributeValue::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// 单个向量的属性集合
+pub type Attributes = BTreeMap<String, AttributeValue>;
+
+/// 属性谓词
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Predicate {
+    /// 等于
+    Eq(AttributeValue),
+    /// 不等于（属性不存在时也成立）
+    Ne(AttributeValue),
+    /// 小于（仅数字）
+    Lt(f64),
+    /// 小于等于（仅数字）
+    Le(f64),
+    /// 大于（仅数字）
+    Gt(f64),
+    /// 大于等于（仅数字）
+    Ge(f64),
+    /// 闭区间 [min, max]（仅数字）
+    Range {
+        /// 下界
+        min: f64,
+        /// 上界
+        max: f64,
+    },
+    /// 属于集合
+    In(Vec<AttributeValue>),
+    /// 属性存在
+    Exists,
+}
+
+impl Predicate {
+    /// 判断属性值是否满足谓词
+    pub fn matches(&self, value: Option<&AttributeValue>) -> bool {
+        let number = value.and_then(AttributeValue::as_number);
+        match self {
+            Predicate::Eq(expected) => value == Some(expected),
+            Predicate::Ne(expected) => value != Some(expected),
+            Predicate::Lt(bound) => number.is_some_and(|n| n < *bound),
+            Predicate::Le(bound) => number.is_some_and(|n| n <= *bound),
+            Predicate::Gt(bound) => number.is_some_and(|n| n > *bound),
+            Predicate::Ge(bound) => number.is_some_and(|n| n >= *bound),
+            Predicate::Range { min, max } => number.is_some_and(|n| (*min..=*max).contains(&n)),
+            Predicate::In(candidates) => value.is_some_and(|v| candidates.contains(v)),
+            Predicate::Exists => value.is_some(),
+        }
+    }
+}
+
+/// 过滤条件中序号的上限
+///
+/// 位图按最大序号分配，来自JS或过滤表达式的序号不超过这个值，单个过滤条件的位图最多占用2 MiB
+pub const MAX_FILTER_ORDINAL: usize = (1 << 24) - 1;
+
+/// 序号位图
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OrdinalBitset {
+    words: Vec<u64>,
+}
+
+impl OrdinalBitset {
+    /// 创建空位图
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 由序号列表创建位图
+    pub fn from_ordinals(ordinals: &[usize]) -> Self {
+        let mut bitset = Self::new();
+        for &ord in ordinals {
+            bitset.insert(ord);
+        }
+        bitset
+    }
+
+    /// 由外部输入的序号列表创建位图，序号超过 `MAX_FILTER_ORDINAL` 时返回错误
+    pub fn try_from_ordinals(ordinals: &[usize]) -> Result<Self, BbqError> {
+        if let Some(&ord) = ordinals.iter().find(|&&ord| ord > MAX_FILTER_ORDINAL) {
+            return Err(BbqError::out_of_range(
+                format!("过滤条件中的序号 {} 超出上限 {}", ord, MAX_FILTER_ORDINAL),
+                format!("filter ordinal {} exceeds the maximum {}", ord, MAX_FILTER_ORDINAL),
+            ));
+        }
+        Ok(Self::from_ordinals(ordinals))
+    }
+
+    /// 设置序号
+    ///
+    /// # 返回
+    /// 序号之前未被设置时返回true
+    pub fn insert(&mut self, ord: usize) -> bool {
+        let (word, bit) = (ord / 64, ord % 64);
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        let was_set = self.words[word] & (1 << bit) != 0;
+        self.words[word] |= 1 << bit;
+        !was_set
+    }
+
+    /// 是否包含序号
+    pub fn contains(&self, ord: usize) -> bool {
+        self.words.get(ord / 64).is_some_and(|word| word & (1 << (ord % 64)) != 0)
+    }
+
+    /// 已设置的序号数量
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// 是否没有设置任何序号
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&word| word == 0)
+    }
+
+    /// 清空位图
+    pub fn clear(&mut self) {
+        self.words.clear();
+    }
+
+    /// 按升序遍历已设置的序号
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &word)| {
+            (0..64).filter(move |bit| word & (1 << bit) != 0).map(move |bit| i * 64 + bit)
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for OrdinalBitset {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let ordinals = Vec::<usize>::deserialize(deserializer)?;
+        Self::try_from_ordinals(&ordinals).map_err(serde::de::Error::custom)
+    }
+}
+
+/// 过滤条件
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Filter {
+    /// 匹配所有向量
+    All,
+    /// 匹配位图中的序号
+    Ordinals(OrdinalBitset),
+    /// 属性谓词
+    Attribute {
+        /// 属性名
+        key: String,
+        /// 谓词
+        predicate: Predicate,
+    },
+    /// 所有子条件都成立
+    And(Vec<Filter>),
+    /// 任一子条件成立
+    Or(Vec<Filter>),
+    /// 子条件不成立
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    /// 判断向量是否满足条件
+    ///
+    /// # 参数
+    /// * `ord` - 向量序号
+    /// * `attributes` - 该向量的属性（没有属性时为None）
+    pub fn matches(&self, ord: usize, attributes: Option<&Attributes>) -> bool {
+        match self {
+            Filter::All => true,
+            Filter::Ordinals(bitset) => bitset.contains(ord),
+            Filter::Attribute { key, predicate } => {
+                predicate.matches(attributes.and_then(|attrs| attrs.get(key)))
+            }
+            Filter::And(filters) => filters.iter().all(|filter| filter.matches(ord, attributes)),
+            Filter::Or(filters) => filters.iter().any(|filter| filter.matches(ord, attributes)),
+            Filter::Not(filter) => !filter.matches(ord, attributes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitset() {
+        let mut bitset = OrdinalBitset::from_ordinals(&[3, 64, 200]);
+        assert!(bitset.contains(64));
+        assert!(!bitset.contains(65));
+        assert!(!bitset.insert(3));
+        assert!(bitset.insert(4));
+        assert_eq!(bitset.count(), 4);
+        assert_eq!(bitset.iter().collect::<Vec<_>>(), vec![3, 4, 64, 200]);
+
+        // 外部输入的序号有上限，超出时不分配位图
+        assert!(OrdinalBitset::try_from_ordinals(&[MAX_FILTER_ORDINAL]).is_ok());
+        assert!(OrdinalBitset::try_from_ordinals(&[1, u32::MAX as usize]).is_err());
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let mut attrs = Attributes::new();
+        attrs.insert("tenant".to_string(), AttributeValue::Text("a".to_string()));
+        attrs.insert("day".to_string(), AttributeValue::Number(12.0));
+
+        let filter = Filter::And(vec![
+            Filter::Attribute { key: "tenant".to_string(), predicate: Predicate::Eq(AttributeValue::Text("a".to_string())) },
+            Filter::Attribute { key: "day".to_string(), predicate: Predicate::Range { min: 10.0, max: 20.0 } },
+        ]);
+        assert!(filter.matches(0, Some(&attrs)));
+        assert!(!filter.matches(0, None));
+        assert!(Filter::Not(Box::new(filter)).matches(0, None));
+        assert!(!Filter::Attribute { key: "day".to_string(), predicate: Predicate::Gt(12.0) }.matches(0, Some(&attrs)));
+        assert!(Filter::Or(vec![Filter::Ordinals(OrdinalBitset::from_ordinals(&[5])), Filter::All]).matches(1, None));
+    }
+}
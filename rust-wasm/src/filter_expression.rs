@@ -0,0 +1,483 @@
+//! 过滤表达式
+//!
+//! 把类似搜索引擎查询语言的字符串编译为 `Filter`，应用不必手工拼装JSON谓词，例如
+//! `tag:news AND price<100 AND NOT archived`。
+//!
+//! 语法（关键字不区分大小写）：
+//!
+//! - 组合：`a AND b`、`a OR b`、`NOT a`，也可写作 `&&`、`||`、`!`；相邻的条件之间省略AND时按AND处理；
+//!   优先级 NOT > AND > OR，可以用括号分组
+//! - 等于：`key:value`、`key=value`；不等于：`key!=value`
+//! - 比较（仅数字）：`key<10`、`key<=10`、`key>10`、`key>=10`
+//! - 闭区间：`key:[10 TO 20]`，`*` 表示不限，如 `key:[10 TO *]`
+//! - 属于集合：`key IN (a, b, c)`
+//! - 属性存在：`key:*`；单独的属性名表示该属性为true，如 `archived`
+//! - 序号：保留属性名 `_ord`，`_ord:5` 或 `_ord IN (1, 2, 3)` 编译为序号位图，序号不超过 `MAX_FILTER_ORDINAL`
+//!
+//! 值为 `true` / `false` 时是布尔值，能解析为有限数字时是数字，否则是字符串；
+//! 包含空格、特殊字符或与关键字同名的字符串用双引号括起，引号内用 `\"` 和 `\\` 转义
+
+use crate::error_codes::{BbqError, Subject};
+use crate::filter::{AttributeValue, Filter, OrdinalBitset, Predicate, MAX_FILTER_ORDINAL};
+
+/// 序号条件使用的保留属性名
+pub const ORDINAL_KEY: &str = "_ord";
+
+/// 括号的最大嵌套深度，防止恶意输入耗尽栈
+const MAX_NESTING: usize = 64;
+
+/// 比较运算符
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// 词法单元
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+    Comma,
+    Colon,
+    Star,
+    Compare(Comparison),
+    And,
+    Or,
+    Not,
+    To,
+    In,
+    Word(String),
+    Quoted(String),
+}
+
+/// 不能出现在未加引号的单词中的字符
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "()[],:<>=!\"*&|".contains(c)
+}
+
+/// 拆分词法单元，返回 (单元, 起始字符位置)
+fn tokenize(expression: &str) -> Result<Vec<(Token, usize)>, BbqError> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let next = chars.get(i + 1).copied();
+        let token = match c {
+            _ if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            '[' => Token::LeftBracket,
+            ']' => Token::RightBracket,
+            ',' => Token::Comma,
+            ':' => Token::Colon,
+            '*' => Token::Star,
+            '&' | '|' => {
+                if next != Some(c) {
+                    return Err(syntax_error(start, &format!("应为 {}{}", c, c), &format!("expected {}{}", c, c)));
+                }
+                i += 1;
+                if c == '&' { Token::And } else { Token::Or }
+            }
+            '=' => {
+                if next == Some('=') {
+                    i += 1;
+                }
+                Token::Compare(Comparison::Eq)
+            }
+            '!' if next == Some('=') => {
+                i += 1;
+                Token::Compare(Comparison::Ne)
+            }
+            '!' => Token::Not,
+            '<' | '>' => {
+                let inclusive = next == Some('=');
+                if inclusive {
+                    i += 1;
+                }
+                Token::Compare(match (c, inclusive) {
+                    ('<', false) => Comparison::Lt,
+                    ('<', true) => Comparison::Le,
+                    ('>', false) => Comparison::Gt,
+                    _ => Comparison::Ge,
+                })
+            }
+            '"' => {
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(syntax_error(start, "引号未闭合", "unterminated quote")),
+                        Some('"') => break,
+                        Some('\\') => {
+                            match chars.get(i + 1) {
+                                Some(&escaped @ ('"' | '\\')) => text.push(escaped),
+                                _ => return Err(syntax_error(i, "无效的转义，只支持 \\\" 和 \\\\", "invalid escape, only \\\" and \\\\ are supported")),
+                            }
+                            i += 2;
+                        }
+                        Some(&other) => {
+                            text.push(other);
+                            i += 1;
+                        }
+                    }
+                }
+                Token::Quoted(text)
+            }
+            _ => {
+                let mut end = i;
+                while end < chars.len() && !is_delimiter(chars[end]) {
+                    end += 1;
+                }
+                let word: String = chars[i..end].iter().collect();
+                i = end - 1;
+                match word.to_uppercase().as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    "TO" => Token::To,
+                    "IN" => Token::In,
+                    _ => Token::Word(word),
+                }
+            }
+        };
+        tokens.push((token, start));
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+fn syntax_error(position: usize, detail: &str, english: &str) -> BbqError {
+    BbqError::invalid_argument(
+        format!("无效的过滤表达式（位置 {}）: {}", position, detail),
+        format!("invalid filter expression (position {}): {}", position, english),
+    )
+}
+
+/// 递归下降解析器
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    cursor: usize,
+    /// 表达式的字符数，用于报告结尾处的错误
+    end: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.cursor).map(|(token, _)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.cursor).map_or(self.end, |&(_, position)| position)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.cursor).map(|(token, _)| token.clone());
+        self.cursor += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token, description: Subject) -> Result<(), BbqError> {
+        if self.peek() == Some(&expected) {
+            self.cursor += 1;
+            Ok(())
+        } else {
+            Err(syntax_error(self.position(), &format!("应为{}", description.zh), &format!("expected {}", description.en)))
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Filter, BbqError> {
+        let mut filters = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.cursor += 1;
+            filters.push(self.parse_and()?);
+        }
+        Ok(if filters.len() == 1 { filters.remove(0) } else { Filter::Or(filters) })
+    }
+
+    fn parse_and(&mut self) -> Result<Filter, BbqError> {
+        let mut filters = vec![self.parse_unary()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.cursor += 1,
+                // 省略AND的相邻条件
+                Some(Token::Not | Token::LeftParen | Token::Word(_) | Token::Quoted(_)) => {}
+                _ => break,
+            }
+            filters.push(self.parse_unary()?);
+        }
+        Ok(if filters.len() == 1 { filters.remove(0) } else { Filter::And(filters) })
+    }
+
+    fn parse_unary(&mut self) -> Result<Filter, BbqError> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.cursor += 1;
+                self.nested(|parser| parser.parse_unary()).map(|filter| Filter::Not(Box::new(filter)))
+            }
+            Some(Token::LeftParen) => {
+                self.cursor += 1;
+                let filter = self.nested(Self::parse_or)?;
+                self.expect(Token::RightParen, Subject::new("右括号", "a closing parenthesis"))?;
+                Ok(filter)
+            }
+            _ => self.parse_condition(),
+        }
+    }
+
+    /// 进入一层嵌套，超过最大深度时报错
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, BbqError>) -> Result<T, BbqError> {
+        if self.depth >= MAX_NESTING {
+            return Err(syntax_error(self.position(), &format!("嵌套超过 {} 层", MAX_NESTING), &format!("nesting exceeds {} levels", MAX_NESTING)));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_condition(&mut self) -> Result<Filter, BbqError> {
+        let position = self.position();
+        let key = match self.advance() {
+            Some(Token::Word(key) | Token::Quoted(key)) => key,
+            _ => return Err(syntax_error(position, "应为属性名", "expected an attribute name")),
+        };
+        if key == ORDINAL_KEY {
+            return self.parse_ordinals(position);
+        }
+
+        let predicate = match self.peek() {
+            Some(Token::Colon) => {
+                self.cursor += 1;
+                match self.peek() {
+                    Some(Token::Star) => {
+                        self.cursor += 1;
+                        Predicate::Exists
+                    }
+                    Some(Token::LeftBracket) => {
+                        self.cursor += 1;
+                        let min = self.parse_bound(f64::NEG_INFINITY)?;
+                        self.expect(Token::To, Subject::new("TO", "TO"))?;
+                        let max = self.parse_bound(f64::INFINITY)?;
+                        self.expect(Token::RightBracket, Subject::new("右方括号", "a closing bracket"))?;
+                        Predicate::Range { min, max }
+                    }
+                    _ => Predicate::Eq(self.parse_value()?),
+                }
+            }
+            Some(&Token::Compare(comparison)) => {
+                self.cursor += 1;
+                match comparison {
+                    Comparison::Eq => Predicate::Eq(self.parse_value()?),
+                    Comparison::Ne => Predicate::Ne(self.parse_value()?),
+                    Comparison::Lt => Predicate::Lt(self.parse_number()?),
+                    Comparison::Le => Predicate::Le(self.parse_number()?),
+                    Comparison::Gt => Predicate::Gt(self.parse_number()?),
+                    Comparison::Ge => Predicate::Ge(self.parse_number()?),
+                }
+            }
+            Some(Token::In) => {
+                self.cursor += 1;
+                Predicate::In(self.parse_list()?)
+            }
+            // 单独的属性名表示布尔属性为true
+            _ => Predicate::Eq(AttributeValue::Bool(true)),
+        };
+        Ok(Filter::Attribute { key, predicate })
+    }
+
+    /// 保留属性名 `_ord` 的条件
+    fn parse_ordinals(&mut self, position: usize) -> Result<Filter, BbqError> {
+        let values = match self.advance() {
+            Some(Token::Colon | Token::Compare(Comparison::Eq)) => vec![self.parse_value()?],
+            Some(Token::In) => self.parse_list()?,
+            _ => return Err(syntax_error(position, "序号条件只支持 _ord:n 和 _ord IN (...)", "ordinal conditions only support _ord:n and _ord IN (...)")),
+        };
+        let ordinals = values.iter()
+            .map(|value| match value {
+                AttributeValue::Number(n) if n.fract() == 0.0 && *n >= 0.0 => {
+                    if *n > MAX_FILTER_ORDINAL as f64 {
+                        return Err(syntax_error(
+                            position,
+                            &format!("过滤条件中的序号 {} 超出上限 {}", n, MAX_FILTER_ORDINAL),
+                            &format!("filter ordinal {} exceeds the maximum {}", n, MAX_FILTER_ORDINAL),
+                        ));
+                    }
+                    Ok(*n as usize)
+                }
+                _ => Err(syntax_error(position, &format!("无效的序号: {:?}", value), &format!("invalid ordinal: {:?}", value))),
+            })
+            .collect::<Result<Vec<usize>, BbqError>>()?;
+        Ok(Filter::Ordinals(OrdinalBitset::from_ordinals(&ordinals)))
+    }
+
+    fn parse_value(&mut self) -> Result<AttributeValue, BbqError> {
+        let position = self.position();
+        match self.advance() {
+            Some(Token::Quoted(text)) => Ok(AttributeValue::Text(text)),
+            Some(Token::Word(word)) => Ok(match word.as_str() {
+                "true" => AttributeValue::Bool(true),
+                "false" => AttributeValue::Bool(false),
+                _ => match word.parse::<f64>() {
+                    Ok(number) if number.is_finite() => AttributeValue::Number(number),
+                    _ => AttributeValue::Text(word),
+                },
+            }),
+            _ => Err(syntax_error(position, "应为值", "expected a value")),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<f64, BbqError> {
+        let position = self.position();
+        match self.parse_value()? {
+            AttributeValue::Number(number) => Ok(number),
+            _ => Err(syntax_error(position, "比较运算只支持数字", "comparisons only support numbers")),
+        }
+    }
+
+    /// 区间的一端，`*` 表示不限
+    fn parse_bound(&mut self, unbounded: f64) -> Result<f64, BbqError> {
+        if self.peek() == Some(&Token::Star) {
+            self.cursor += 1;
+            return Ok(unbounded);
+        }
+        self.parse_number()
+    }
+
+    /// 括号中逗号分隔的值
+    fn parse_list(&mut self) -> Result<Vec<AttributeValue>, BbqError> {
+        self.expect(Token::LeftParen, Subject::new("左括号", "an opening parenthesis"))?;
+        let mut values = vec![self.parse_value()?];
+        while self.peek() == Some(&Token::Comma) {
+            self.cursor += 1;
+            values.push(self.parse_value()?);
+        }
+        self.expect(Token::RightParen, Subject::new("右括号", "a closing parenthesis"))?;
+        Ok(values)
+    }
+}
+
+/// 把过滤表达式编译为过滤条件，空表达式匹配所有向量
+pub fn parse_filter_expression(expression: &str) -> Result<Filter, BbqError> {
+    let tokens = tokenize(expression)?;
+    if tokens.is_empty() {
+        return Ok(Filter::All);
+    }
+    let mut parser = Parser { tokens, cursor: 0, end: expression.chars().count(), depth: 0 };
+    let filter = parser.parse_or()?;
+    if parser.cursor < parser.tokens.len() {
+        return Err(syntax_error(parser.position(), "多余的内容", "unexpected trailing input"));
+    }
+    Ok(filter)
+}
+
+impl std::str::FromStr for Filter {
+    type Err = BbqError;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        parse_filter_expression(expression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Attributes;
+
+    fn attribute(key: &str, predicate: Predicate) -> Filter {
+        Filter::Attribute { key: key.to_string(), predicate }
+    }
+
+    fn text(value: &str) -> AttributeValue {
+        AttributeValue::Text(value.to_string())
+    }
+
+    #[test]
+    fn test_parse_expressions() {
+        assert_eq!(
+            parse_filter_expression("tag:news AND price<100 AND NOT archived").unwrap(),
+            Filter::And(vec![
+                attribute("tag", Predicate::Eq(text("news"))),
+                attribute("price", Predicate::Lt(100.0)),
+                Filter::Not(Box::new(attribute("archived", Predicate::Eq(AttributeValue::Bool(true))))),
+            ]),
+        );
+        // NOT > AND > OR，省略的AND与显式AND相同
+        assert_eq!(
+            parse_filter_expression("a:1 b:2 || !(c:* && d != \"x \\\"y\\\"\")").unwrap(),
+            Filter::Or(vec![
+                Filter::And(vec![
+                    attribute("a", Predicate::Eq(AttributeValue::Number(1.0))),
+                    attribute("b", Predicate::Eq(AttributeValue::Number(2.0))),
+                ]),
+                Filter::Not(Box::new(Filter::And(vec![
+                    attribute("c", Predicate::Exists),
+                    attribute("d", Predicate::Ne(text("x \"y\""))),
+                ]))),
+            ]),
+        );
+        assert_eq!(
+            parse_filter_expression("day:[10 TO *] or lang in (en, \"zh\", true)").unwrap(),
+            Filter::Or(vec![
+                attribute("day", Predicate::Range { min: 10.0, max: f64::INFINITY }),
+                attribute("lang", Predicate::In(vec![text("en"), text("zh"), AttributeValue::Bool(true)])),
+            ]),
+        );
+        assert_eq!(
+            parse_filter_expression("_ord IN (3, 64)").unwrap(),
+            Filter::Ordinals(OrdinalBitset::from_ordinals(&[3, 64])),
+        );
+        assert_eq!(
+            parse_filter_expression("_ord:16777215").unwrap(),
+            Filter::Ordinals(OrdinalBitset::from_ordinals(&[MAX_FILTER_ORDINAL])),
+        );
+        assert_eq!(parse_filter_expression("  ").unwrap(), Filter::All);
+        assert_eq!("score>=0.5".parse::<Filter>().unwrap(), attribute("score", Predicate::Ge(0.5)));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for expression in [
+            "tag:",
+            "price<cheap",
+            "(a:1",
+            "a:1)",
+            "a:[1 2]",
+            "\"open",
+            "a & b",
+            "_ord<3",
+            "_ord:-1",
+            "_ord:4294967295",
+            "_ord IN (1, 16777216)",
+            "AND a",
+        ] {
+            assert!(parse_filter_expression(expression).is_err(), "{}", expression);
+        }
+        assert!(parse_filter_expression(&"(".repeat(100)).unwrap_err().detail().contains("嵌套"));
+        assert!(parse_filter_expression("price<cheap").unwrap_err().detail().contains("位置 6"));
+        assert!(parse_filter_expression("_ord:4294967295").unwrap_err().detail().contains("超出上限"));
+    }
+
+    #[test]
+    fn test_compiled_filter_matches() {
+        let filter = parse_filter_expression("tag:news AND price<100 AND NOT archived").unwrap();
+        let mut attrs = Attributes::new();
+        attrs.insert("tag".to_string(), text("news"));
+        attrs.insert("price".to_string(), AttributeValue::Number(42.0));
+        assert!(filter.matches(0, Some(&attrs)));
+        attrs.insert("archived".to_string(), AttributeValue::Bool(true));
+        assert!(!filter.matches(0, Some(&attrs)));
+    }
+}
@@ -0,0 +1,82 @@
+//! 浮点输入类型
+//!
+//! 量化器、质心和相似性计算除f32外也接受f64输入。
+//! f64数据在中心化（减去质心）之前保持原精度：远离原点的数据中心化后的差值很小，
+//! 先转成f32再相减会丢掉这些差值；中心化后的值再以f32参与区间优化
+
+/// 可作为量化和相似性输入的浮点类型
+pub trait Float: Copy + PartialOrd + Send + Sync + 'static {
+    /// 是否为双精度：为true时统计量和修正项总是以f64累加
+    const DOUBLE_PRECISION: bool;
+
+    /// 转为f64
+    fn to_f64(self) -> f64;
+
+    /// 由f64转换
+    fn from_f64(value: f64) -> Self;
+
+    /// 以本类型的精度计算 `self - other`，再转为f32
+    fn centered(self, other: Self) -> f32;
+
+    /// 以本类型的精度计算点积
+    fn dot(a: &[Self], b: &[Self]) -> f32;
+
+    /// 本类型为f32时直接借用为f32切片，否则为None
+    fn as_f32_slice(values: &[Self]) -> Option<&[f32]>;
+}
+
+impl Float for f32 {
+    const DOUBLE_PRECISION: bool = false;
+
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    #[inline]
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+
+    #[inline]
+    fn centered(self, other: Self) -> f32 {
+        self - other
+    }
+
+    fn dot(a: &[Self], b: &[Self]) -> f32 {
+        crate::vector_utils::compute_dot_product(a, b)
+    }
+
+    #[inline]
+    fn as_f32_slice(values: &[Self]) -> Option<&[f32]> {
+        Some(values)
+    }
+}
+
+impl Float for f64 {
+    const DOUBLE_PRECISION: bool = true;
+
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    #[inline]
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+
+    #[inline]
+    fn centered(self, other: Self) -> f32 {
+        (self - other) as f32
+    }
+
+    fn dot(a: &[Self], b: &[Self]) -> f32 {
+        a.iter().zip(b).map(|(&x, &y)| x * y).sum::<f64>() as f32
+    }
+
+    #[inline]
+    fn as_f32_slice(_values: &[Self]) -> Option<&[f32]> {
+        None
+    }
+}
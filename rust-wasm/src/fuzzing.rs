@@ -0,0 +1,196 @@
+//! 模糊测试入口
+//!
+//! WASM中的panic会终止整个实例，对外暴露的函数必须把非法输入转成错误。
+//! 这里的入口把任意字节解释为反序列化输入或构建、搜索参数，只要求不panic、
+//! 不关心返回值；`fuzz/` 下的cargo-fuzz目标直接调用它们，
+//! 单元测试用随机数据和变异后的合法字节块跑同样的入口
+
+use crate::bbq::Bbq;
+use crate::ivf::ProbeStrategy;
+use crate::optimized_scalar_quantizer::OptimizedScalarQuantizer;
+use crate::quantized_index::{QuantizedIndex, QuantizedIndexConfig, RescoreOversample, SearchParams};
+use crate::query_context::QueryContext;
+use crate::query_pack::{export_query_pack, QueryPack, QueryPackLayout};
+use crate::replica::Replica;
+use crate::validation::validate_vectors;
+use crate::vector_similarity::SimilarityFunction;
+
+/// 构建入口中维度的上限，避免单个输入耗时过长
+const MAX_FUZZ_DIMENSION: usize = 32;
+
+/// 构建入口中向量数量的上限
+const MAX_FUZZ_VECTORS: usize = 256;
+
+/// 从字节中按顺序取值，数据用完后返回0
+struct FuzzInput<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> FuzzInput<'a> {
+    fn byte(&mut self) -> u8 {
+        match self.data.split_first() {
+            Some((&first, rest)) => {
+                self.data = rest;
+                first
+            }
+            None => 0,
+        }
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes([self.byte(), self.byte(), self.byte(), self.byte()])
+    }
+
+    fn f32(&mut self) -> f32 {
+        f32::from_bits(self.u32())
+    }
+
+    fn metric(&mut self) -> SimilarityFunction {
+        match self.byte() % 3 {
+            0 => SimilarityFunction::Euclidean,
+            1 => SimilarityFunction::Cosine,
+            _ => SimilarityFunction::MaximumInnerProduct,
+        }
+    }
+}
+
+/// 把任意字节交给所有反序列化入口
+pub fn fuzz_deserialize(data: &[u8]) {
+    let mut input = FuzzInput { data };
+    let (start, end) = (input.u32() as usize % 1024, input.u32() as usize % 1024);
+
+    let _ = QueryPack::load(data);
+    let _ = QueryPack::deserialize_range(data, start, end);
+    if let Ok(layout) = QueryPackLayout::parse(data) {
+        let _ = layout.range_regions(start, end);
+    }
+    if let Ok(mut pack) = QueryPack::deserialize_range(data, 0, start) {
+        let _ = pack.extend_range(data, start, end);
+        let _ = pack.deserialize_prepared_query(data);
+        let _ = pack.search_nearest_neighbors(&vec![0.5; pack.dimension()], 3);
+    }
+
+    let _ = QueryContext::deserialize(data, input.u32() as u64);
+    if let Ok(mut bbq) = Bbq::load(data) {
+        let _ = bbq.query(&vec![1.0; bbq.dims()], 3);
+        let _ = bbq.apply_delta(data);
+    }
+    if let Ok(mut bbq) = Bbq::new(4, SimilarityFunction::Cosine) {
+        let _ = bbq.apply_delta(data);
+    }
+    if let Ok(mut replica) = Replica::new("fuzz", 4, SimilarityFunction::Cosine) {
+        let _ = replica.merge(data);
+    }
+    let _ = Replica::from_snapshot(data, "fuzz");
+}
+
+/// 把任意字节解释为向量和参数，依次构建、搜索、导出查询包
+pub fn fuzz_build_search(data: &[u8]) {
+    let mut input = FuzzInput { data };
+    let dimension = input.byte() as usize % (MAX_FUZZ_DIMENSION + 1);
+    let metric = input.metric();
+    let query_bits = if input.byte() & 1 == 0 { 1 } else { 4 };
+    let k = input.byte() as usize;
+    let count = input.byte() as usize % (MAX_FUZZ_VECTORS + 1);
+
+    let flat: Vec<f32> = (0..count * dimension.max(1)).map(|_| input.f32()).collect();
+    let query: Vec<f32> = (0..dimension).map(|_| input.f32()).collect();
+    let _ = validate_vectors(&flat, dimension);
+
+    let quantizer = OptimizedScalarQuantizer::new(None, None, Some(metric));
+    let mut destination = vec![0u8; query.len()];
+    let _ = quantizer.scalar_quantize(&query, &mut destination, query_bits, &flat[..query.len().min(flat.len())]);
+
+    let Ok(mut index) = QuantizedIndex::new(QuantizedIndexConfig {
+        query_bits,
+        similarity_function: metric,
+        keep_original_vectors: true,
+        ..QuantizedIndexConfig::default()
+    }) else {
+        return;
+    };
+    let vectors: Vec<Vec<f32>> = flat.chunks(dimension.max(1)).map(<[f32]>::to_vec).collect();
+    if index.build_index(&vectors).is_err() {
+        let _ = index.search_nearest_neighbors(&query, k);
+        return;
+    }
+
+    let _ = index.search_nearest_neighbors(&query, k);
+    let _ = index.search_with_params(&query, k, &SearchParams {
+        rescore_oversample: RescoreOversample::Fixed(input.f32()),
+        include_distances: true,
+        ..SearchParams::default()
+    });
+    let _ = index.search_sampled(&query, k, input.f32());
+    let _ = index.score_ords(&query, &[input.byte() as usize, input.u32() as usize]);
+    let _ = index.delete(input.byte() as usize);
+    let _ = index.search_weighted(&query, k, &query, &SearchParams::default());
+
+    if index.build_ivf(input.byte() as usize % 8, 2, 7).is_ok() {
+        let _ = index.search_with_params(&query, k, &SearchParams {
+            nprobe: Some(ProbeStrategy::Fixed(input.byte() as usize)),
+            ..SearchParams::default()
+        });
+    }
+    let _ = index.compact(input.byte() & 1 == 0);
+
+    if let Ok(bytes) = export_query_pack(&index, None) {
+        if let Ok(pack) = QueryPack::load(&bytes) {
+            let _ = pack.search_nearest_neighbors(&query, k);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector_utils::create_random_vector;
+
+    /// 随机翻转、截断合法字节块
+    fn mutate(rng: &mut fastrand::Rng, bytes: &[u8]) -> Vec<u8> {
+        let mut mutated = bytes.to_vec();
+        match rng.u8(0..3) {
+            0 => mutated.truncate(rng.usize(0..=bytes.len())),
+            1 => {
+                for _ in 0..rng.usize(1..8) {
+                    if let Some(byte) = mutated.get_mut(rng.usize(0..bytes.len().max(1))) {
+                        *byte = rng.u8(..);
+                    }
+                }
+            }
+            _ => mutated.extend((0..rng.usize(1..16)).map(|_| rng.u8(..))),
+        }
+        mutated
+    }
+
+    #[test]
+    fn test_fuzz_entry_points_do_not_panic() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..20).map(|_| create_random_vector(8, -1.0, 1.0)).collect();
+        index.build_index(&vectors).unwrap();
+        let mut bbq = Bbq::new(4, SimilarityFunction::Cosine).unwrap();
+        bbq.add("a", &[1.0, 2.0, 3.0, 4.0]).unwrap();
+        let seeds = [
+            export_query_pack(&index, None).unwrap(),
+            index.prepare_query(&vectors[0]).unwrap().serialize(index.quantization_fingerprint().unwrap()).unwrap(),
+            bbq.save().unwrap(),
+            bbq.export_delta(0).unwrap(),
+        ];
+
+        let mut rng = fastrand::Rng::with_seed(0xf022);
+        for _ in 0..500 {
+            let seed = &seeds[rng.usize(0..seeds.len())];
+            fuzz_deserialize(&mutate(&mut rng, seed));
+
+            let len = rng.usize(0..400);
+            let mut data: Vec<u8> = (0..len).map(|_| rng.u8(..)).collect();
+            // 一半输入使用有限的小数值，能走到构建之后的路径
+            if rng.bool() {
+                for chunk in data.get_mut(5..).unwrap_or_default().chunks_exact_mut(4) {
+                    chunk.copy_from_slice(&(rng.f32() * 2.0 - 1.0).to_le_bytes());
+                }
+            }
+            fuzz_build_search(&data);
+        }
+    }
+}
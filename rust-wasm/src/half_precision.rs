@@ -0,0 +1,109 @@
+//! 半精度浮点转换
+//!
+//! 原始向量以f16保存时只在重排阶段解码，重排的候选数量通常只有k的几倍，
+//! 因此解码放在评分前逐个向量进行，使用可复用的缓冲区而不分配新向量。
+//! 切片转换按8个一组处理，便于编译器在启用simd128时向量化
+
+/// f32转换为半精度位模式（就近舍入，超出范围时为无穷）
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+
+    if exponent == 0xff {
+        // 无穷或NaN
+        return sign | 0x7c00 | if mantissa != 0 { 0x0200 } else { 0 };
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        // 非规格化数或下溢为0
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - exponent) as u32;
+        let half = mantissa >> shift;
+        let round = (mantissa >> (shift - 1)) & 1;
+        let sticky = mantissa & ((1 << (shift - 1)) - 1);
+        return sign | (half + (round & (sticky != 0 || half & 1 != 0) as u32)) as u16;
+    }
+    let half = ((exponent as u32) << 10) | (mantissa >> 13);
+    let round = (mantissa >> 12) & 1;
+    let sticky = mantissa & 0x0fff;
+    // 尾数进位可能进到指数位，结果仍然正确（最大时变为无穷）
+    sign | (half + (round & (sticky != 0 || half & 1 != 0) as u32)) as u16
+}
+
+/// 半精度位模式转换为f32
+pub fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half & 0x8000) as u32) << 16;
+    let exponent = ((half >> 10) & 0x1f) as u32;
+    let mantissa = (half & 0x03ff) as u32;
+    let bits = match exponent {
+        0 if mantissa == 0 => sign,
+        0 => {
+            // 非规格化数：规格化后再组装
+            let shift = mantissa.leading_zeros() - 21;
+            sign | ((113 - shift) << 23) | ((mantissa << shift) & 0x03ff) << 13
+        }
+        0x1f => sign | 0x7f80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 112) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+/// 把一组f32编码为半精度
+pub fn encode_f16(src: &[f32]) -> Vec<u16> {
+    let mut dst = vec![0u16; src.len()];
+    for (out, chunk) in dst.chunks_mut(8).zip(src.chunks(8)) {
+        for (half, &value) in out.iter_mut().zip(chunk) {
+            *half = f32_to_f16(value);
+        }
+    }
+    dst
+}
+
+/// 把一组半精度解码到dst（长度必须相同）
+pub fn decode_f16_into(src: &[u16], dst: &mut [f32]) {
+    debug_assert_eq!(src.len(), dst.len());
+    for (out, chunk) in dst.chunks_mut(8).zip(src.chunks(8)) {
+        for (value, &half) in out.iter_mut().zip(chunk) {
+            *value = f16_to_f32(half);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_f16_roundtrip() {
+        for &value in &[0.0f32, -0.0, 1.0, -2.5, 0.333_333, 65504.0, 6.103_515_6e-5, 5.960_464_5e-8, 1e-3] {
+            let decoded = f16_to_f32(f32_to_f16(value));
+            assert!((decoded - value).abs() <= value.abs() * 1e-3, "{} -> {}", value, decoded);
+        }
+        assert_eq!(f16_to_f32(f32_to_f16(1e6)), f32::INFINITY);
+        assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+        // 每个有限半精度值转换为f32再转回不变
+        for half in (0..0x7c00u16).chain(0x8000..0xfc00) {
+            assert_eq!(f32_to_f16(f16_to_f32(half)), half);
+        }
+    }
+
+    #[test]
+    fn test_slice_kernels() {
+        let values: Vec<f32> = (0..21).map(|i| (i as f32 - 10.0) * 0.37).collect();
+        let encoded = encode_f16(&values);
+        assert_eq!(encoded, values.iter().map(|&x| f32_to_f16(x)).collect::<Vec<_>>());
+        let mut decoded = vec![0.0; values.len()];
+        decode_f16_into(&encoded, &mut decoded);
+        for (a, b) in decoded.iter().zip(&values) {
+            assert!((a - b).abs() <= b.abs() * 1e-3);
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! 增量搜索
+//!
+//! 输入联想等交互式搜索不能长时间占用主线程。增量搜索在开始时取得当前代的快照，
+//! 之后每次 `pump` 只评分几批向量并合并到内部的前k个结果中，
+//! 可以在requestAnimationFrame或requestIdleCallback中反复调用，连续输入时不会阻塞渲染。
+//! 搜索期间索引被压缩或重建不影响进行中的搜索：它始终读取开始时的那一代
+
+use std::sync::Arc;
+
+use crate::error_codes::BbqError;
+use crate::batch_sizing::recommended_batch_size;
+use crate::index_generation::IndexGeneration;
+use crate::quantized_index::{QuantizedIndex, QueryResult};
+use crate::query_context::QueryContext;
+use crate::query_pack::ProgressiveResults;
+use crate::timer::{elapsed_ms, now_ms};
+use crate::vector_similarity::{descending_score_order, SimilarityFunction};
+
+/// 增量搜索状态
+pub struct IncrementalSearch {
+    generation: Arc<IndexGeneration>,
+    context: QueryContext,
+    similarity_function: SimilarityFunction,
+    k: usize,
+    batch_size: usize,
+    /// 按评分顺序排列的候选序号（开始时未过期的向量）
+    candidates: Vec<usize>,
+    /// 已评分的候选数
+    cursor: usize,
+    /// 当前前k个（序号, 分数），按分数降序
+    top: Vec<(usize, f32)>,
+}
+
+impl IncrementalSearch {
+    /// 开始增量搜索（由 `QuantizedIndex::start_incremental_search` 调用）
+    pub(crate) fn new(index: &QuantizedIndex, query_vector: &[f32], k: usize) -> Result<Self, BbqError> {
+        let generation = index.snapshot()?;
+        let context = index.prepare_query_in(&generation, query_vector)?;
+        let now = generation.next_expiry.map(|_| now_ms());
+        let candidates = (0..generation.size())
+            .filter(|&ord| !now.is_some_and(|now| generation.is_expired_at(ord, now)))
+            .collect();
+        Ok(Self {
+            batch_size: recommended_batch_size(generation.values().dimension(), k, None),
+            generation,
+            context,
+            similarity_function: index.get_config().similarity_function,
+            k,
+            candidates,
+            cursor: 0,
+            top: Vec::new(),
+        })
+    }
+
+    /// 设置每批评分的向量数（默认按维度和缓存大小推荐）
+    ///
+    /// 较小的批让 `pump_for` 更贴近时间预算，较大的批吞吐更高
+    pub fn set_batch_size(&mut self, batch_size: usize) -> Result<(), BbqError> {
+        if batch_size == 0 {
+            return Err(BbqError::invalid_argument("批大小必须大于0", "batch size must be greater than 0"));
+        }
+        self.batch_size = batch_size;
+        Ok(())
+    }
+
+    /// 最多评分 `max_batches` 批，返回合并后的临时结果
+    ///
+    /// # 参数
+    /// * `index` - 开始搜索时的索引，只用于取得评分器
+    /// * `max_batches` - 本次最多评分的批数
+    pub fn pump(&mut self, index: &QuantizedIndex, max_batches: usize) -> Result<ProgressiveResults, BbqError> {
+        self.check_index(index)?;
+        for _ in 0..max_batches {
+            if !self.score_next_batch(index)? {
+                break;
+            }
+        }
+        Ok(self.results())
+    }
+
+    /// 在 `budget_ms` 毫秒内尽量多地评分，返回合并后的临时结果
+    ///
+    /// 每批之后检查耗时，预计下一批会超出预算时停止；至少评分一批，保证每次调用都有进展。
+    /// 在requestAnimationFrame中调用时预算通常取几毫秒，给渲染留出时间
+    pub fn pump_for(&mut self, index: &QuantizedIndex, budget_ms: f64) -> Result<ProgressiveResults, BbqError> {
+        if !(budget_ms.is_finite() && budget_ms > 0.0) {
+            return Err(BbqError::invalid_argument(format!("时间预算必须为正数，当前为{}", budget_ms), format!("time budget must be positive, got {}", budget_ms)));
+        }
+        self.check_index(index)?;
+        let start = now_ms();
+        let mut last_batch_ms = 0.0;
+        let mut batches = 0;
+        while batches == 0 || elapsed_ms(start) + last_batch_ms <= budget_ms {
+            let batch_start = now_ms();
+            if !self.score_next_batch(index)? {
+                break;
+            }
+            last_batch_ms = elapsed_ms(batch_start);
+            batches += 1;
+        }
+        Ok(self.results())
+    }
+
+    /// 是否所有候选都已评分
+    pub fn is_complete(&self) -> bool {
+        self.cursor == self.candidates.len()
+    }
+
+    /// 已评分候选占全部候选的比例
+    pub fn coverage(&self) -> f32 {
+        if self.candidates.is_empty() {
+            1.0
+        } else {
+            self.cursor as f32 / self.candidates.len() as f32
+        }
+    }
+
+    /// 搜索读取的那一代的代号
+    pub fn generation_number(&self) -> u64 {
+        self.generation.number()
+    }
+
+    /// 当前的临时结果
+    pub fn results(&self) -> ProgressiveResults {
+        ProgressiveResults {
+            results: self.top.iter()
+                .map(|&(index, score)| QueryResult { index, score, original_score: None, distances: None })
+                .collect(),
+            coverage: self.coverage(),
+            is_final: self.is_complete(),
+        }
+    }
+
+    /// 评分下一批并合并到前k个结果中，没有剩余候选时返回false
+    fn score_next_batch(&mut self, index: &QuantizedIndex) -> Result<bool, BbqError> {
+        if self.is_complete() {
+            return Ok(false);
+        }
+        let end = (self.cursor + self.batch_size).min(self.candidates.len());
+        if self.k > 0 {
+            let mut scores = index.get_scorer().compute_batch_scores_excluding(
+                &self.context,
+                self.generation.values(),
+                &self.candidates[self.cursor..end],
+                self.generation.tombstones(),
+            )?;
+            self.generation.rescore_promoted(index.get_scorer(), &self.context, &mut scores)?;
+            self.top.extend(scores);
+            self.top.sort_by(|a, b| descending_score_order(a.1, b.1));
+            self.top.truncate(self.k);
+        }
+        self.cursor = end;
+        Ok(true)
+    }
+
+    /// 评分器来自传入的索引，度量方式必须与开始搜索时一致
+    fn check_index(&self, index: &QuantizedIndex) -> Result<(), BbqError> {
+        if index.get_config().similarity_function != self.similarity_function {
+            return Err(BbqError::invalid_argument("增量搜索只能用开始搜索时的同一个索引推进", "an incremental search can only be advanced with the index it started on"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quantized_index::QuantizedIndexConfig;
+    use crate::vector_utils::create_random_vector;
+
+    #[test]
+    fn test_pump_matches_full_search() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..3000)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+        index.delete(7).unwrap();
+
+        let mut search = index.start_incremental_search(&vectors[7], 10).unwrap();
+        assert!(search.set_batch_size(0).is_err());
+        search.set_batch_size(256).unwrap();
+        let first = search.pump(&index, 1).unwrap();
+        assert!(!first.is_final && first.coverage < 1.0);
+        assert_eq!(first.results.len(), 10);
+
+        let mut pumps = 1;
+        while !search.is_complete() {
+            search.pump_for(&index, 4.0).unwrap();
+            pumps += 1;
+            assert!(pumps < 10_000);
+        }
+        let last = search.results();
+        assert!(last.is_final);
+        assert_eq!(last.coverage, 1.0);
+
+        let expected = index.search_nearest_neighbors(&vectors[7], 10).unwrap();
+        assert_eq!(
+            last.results.iter().map(|r| (r.index, r.score)).collect::<Vec<_>>(),
+            expected.iter().map(|r| (r.index, r.score)).collect::<Vec<_>>(),
+        );
+        assert!(last.results.iter().all(|r| r.index != 7));
+
+        // 已完成的搜索不再评分
+        assert_eq!(search.pump(&index, 5).unwrap().results.len(), 10);
+        assert!(search.pump_for(&index, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_search_survives_compaction() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..200)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+        let expected = index.search_nearest_neighbors(&vectors[150], 5).unwrap();
+        let mut search = index.start_incremental_search(&vectors[150], 5).unwrap();
+        let generation = search.generation_number();
+
+        index.delete(0).unwrap();
+        index.compact(false).unwrap();
+        // 仍读取开始时的那一代，序号不受压缩影响
+        let results = search.pump(&index, usize::MAX).unwrap();
+        assert!(results.is_final);
+        assert_eq!(search.generation_number(), generation);
+        assert_eq!(
+            results.results.iter().map(|r| r.index).collect::<Vec<_>>(),
+            expected.iter().map(|r| r.index).collect::<Vec<_>>(),
+        );
+    }
+}
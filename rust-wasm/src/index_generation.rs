@@ -0,0 +1,203 @@
+//! 索引代
+//!
+//! 一代包含某一时刻所有按序号存放的数据（量化向量、原始向量、量化质量、属性、
+//! 文本块来源、墓碑、过期时间和提升了位数的向量）。搜索开始时持有当前代的Arc引用，整个搜索只读这一代；
+//! 压缩或质心刷新在旁边构建新的一代，完成后原子替换，
+//! 因此长时间运行的搜索不会读到迁移了一半的缓冲区
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+#[cfg(feature = "eval")]
+use crate::evaluation::GroundTruth;
+use crate::binary_quantized_scorer::BinaryQuantizedScorer;
+use crate::error_codes::{BbqError, Subject};
+use crate::chunk_grouping::ChunkProvenance;
+use crate::filter::{Attributes, OrdinalBitset};
+#[cfg(feature = "ivf")]
+use crate::ivf::IvfPartition;
+use crate::original_vectors::OriginalVectors;
+use crate::promoted_vectors::PromotedVectors;
+use crate::query_context::QueryContext;
+use crate::search_layout::SearchLayout;
+use crate::quantized_index::QuantizedVectorValues;
+
+/// 索引的一代
+#[derive(Clone)]
+pub struct IndexGeneration {
+    /// 代号，每次构建、压缩后递增
+    pub(crate) number: u64,
+    /// 质心版本，只在质心变化（重建或刷新质心）时递增
+    pub(crate) centroid_epoch: u64,
+    /// 量化向量值
+    pub(crate) values: Arc<dyn QuantizedVectorValues>,
+    /// 预处理后的原始向量（仅在keep_original_vectors时保留，按配置的编码压缩）
+    pub(crate) original_vectors: Option<Arc<OriginalVectors>>,
+    /// 每个向量的量化质量（1 - 相对重建误差）
+    pub(crate) quality_scores: Vec<f32>,
+    /// 每个向量的属性
+    pub(crate) attributes: Vec<Attributes>,
+    /// 每个向量对应的文本块来源
+    pub(crate) provenance: Vec<Option<ChunkProvenance>>,
+    /// 已删除（墓碑）的序号，搜索时跳过
+    pub(crate) deleted: OrdinalBitset,
+    /// 每个向量的过期时间（毫秒时间戳），过期后搜索时跳过
+    pub(crate) expires_at: Vec<Option<f64>>,
+    /// 最早的过期时间（可能早于实际值，清理过期向量时重新计算）
+    pub(crate) next_expiry: Option<f64>,
+    /// 以更高位数额外量化的向量（混合精度），搜索时改用它们评分
+    pub(crate) promoted: Option<Arc<PromotedVectors>>,
+    /// 缓存的自查询真值
+    #[cfg(feature = "eval")]
+    pub(crate) ground_truth: Option<GroundTruth>,
+    /// IVF粗划分
+    #[cfg(feature = "ivf")]
+    pub(crate) ivf: Option<Arc<IvfPartition>>,
+    /// 搜索优化布局，见 `QuantizedIndex::finalize_for_search`
+    pub(crate) search_layout: Option<Arc<SearchLayout>>,
+}
+
+impl IndexGeneration {
+    /// 创建新的一代，所有向量都未删除、无属性、无来源、不过期
+    pub(crate) fn new(
+        number: u64,
+        centroid_epoch: u64,
+        values: Arc<dyn QuantizedVectorValues>,
+        original_vectors: Option<OriginalVectors>,
+        quality_scores: Vec<f32>,
+    ) -> Self {
+        let size = values.size();
+        Self {
+            number,
+            centroid_epoch,
+            values,
+            original_vectors: original_vectors.map(Arc::new),
+            quality_scores,
+            attributes: vec![Attributes::new(); size],
+            provenance: vec![None; size],
+            deleted: OrdinalBitset::new(),
+            expires_at: vec![None; size],
+            next_expiry: None,
+            promoted: None,
+            #[cfg(feature = "eval")]
+            ground_truth: None,
+            #[cfg(feature = "ivf")]
+            ivf: None,
+            search_layout: None,
+        }
+    }
+
+    /// 代号
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+
+    /// 质心版本
+    pub fn centroid_epoch(&self) -> u64 {
+        self.centroid_epoch
+    }
+
+    /// 量化向量值
+    pub fn values(&self) -> &dyn QuantizedVectorValues {
+        self.values.as_ref()
+    }
+
+    /// 向量总数（含已删除）
+    pub fn size(&self) -> usize {
+        self.values.size()
+    }
+
+    /// 未删除的向量数量
+    pub fn live_count(&self) -> usize {
+        self.size() - self.deleted.count()
+    }
+
+    /// 向量是否已删除
+    pub fn is_deleted(&self, ord: usize) -> bool {
+        self.deleted.contains(ord)
+    }
+
+    /// 存在墓碑时返回墓碑位图，供批量评分在打包前跳过
+    pub fn tombstones(&self) -> Option<&OrdinalBitset> {
+        (!self.deleted.is_empty()).then_some(&self.deleted)
+    }
+
+    /// 向量是否为退化向量（减去质心后为常量），见 `QuantizationResult::is_degenerate`
+    pub fn is_degenerate(&self, ord: usize) -> bool {
+        self.values.try_get_corrective_terms(ord).is_ok_and(|correction| correction.is_degenerate())
+    }
+
+    /// 所有退化向量的序号
+    pub fn degenerate_ordinals(&self) -> Vec<usize> {
+        (0..self.size()).filter(|&ord| self.is_degenerate(ord)).collect()
+    }
+
+    /// 向量在now时刻是否已过期
+    pub fn is_expired_at(&self, ord: usize, now: f64) -> bool {
+        self.expiry(ord).is_some_and(|expiry| expiry <= now)
+    }
+
+    /// 向量的过期时间
+    pub fn expiry(&self, ord: usize) -> Option<f64> {
+        self.expires_at.get(ord).copied().flatten()
+    }
+
+    /// 向量的属性
+    pub fn attributes(&self, ord: usize) -> Option<&Attributes> {
+        self.attributes.get(ord)
+    }
+
+    /// 向量对应的文本块来源
+    pub fn provenance(&self, ord: usize) -> Option<&ChunkProvenance> {
+        self.provenance.get(ord).and_then(Option::as_ref)
+    }
+
+    /// 提升了位数的向量
+    pub fn promoted(&self) -> Option<&PromotedVectors> {
+        self.promoted.as_deref().filter(|promoted| !promoted.is_empty())
+    }
+
+    /// 把候选中已提升的向量改用提升后的量化值评分，见 `PromotedVectors::rescore`
+    pub(crate) fn rescore_promoted(
+        &self,
+        scorer: &BinaryQuantizedScorer,
+        context: &QueryContext,
+        scored: &mut [(usize, f32)],
+    ) -> Result<(), BbqError> {
+        match self.promoted() {
+            Some(promoted) => promoted.rescore(scorer, context, scored),
+            None => Ok(()),
+        }
+    }
+
+    /// 向量的量化质量
+    pub fn quality_score(&self, ord: usize) -> Option<f32> {
+        self.quality_scores.get(ord).copied()
+    }
+
+    /// 搜索优化布局，未调用 `finalize_for_search` 或之后修改过时为None
+    pub fn search_layout(&self) -> Option<&SearchLayout> {
+        self.search_layout.as_deref()
+    }
+
+    /// IVF粗划分
+    #[cfg(feature = "ivf")]
+    pub fn ivf(&self) -> Option<&IvfPartition> {
+        self.ivf.as_deref()
+    }
+
+    /// 预处理后的原始向量（压缩存储时为解码后的近似值）
+    pub fn original_vector(&self, ord: usize) -> Option<Cow<'_, [f32]>> {
+        self.original_vectors.as_ref()?.get(ord, self.values())
+    }
+
+    /// 解码全部原始向量，未保留时返回错误
+    ///
+    /// # 参数
+    /// * `purpose` - 用于错误信息的用途描述
+    pub(crate) fn require_original_vectors(&self, purpose: Subject) -> Result<Cow<'_, [Vec<f32>]>, BbqError> {
+        self.original_vectors.as_ref()
+            .ok_or_else(|| BbqError::missing_original_vectors(purpose))?
+            .decode_all(self.values())
+    }
+}
@@ -0,0 +1,540 @@
+//! 量化索引的二进制序列化
+//!
+//! 保存的是量化后的状态（配置、质心、打包向量、修正项等），加载时不重新量化，
+//! 浏览器应用可以把它存入IndexedDB，页面加载时直接恢复而不必重建索引。
+//!
+//! 格式（小端）：魔数 | 格式版本 | 配置 | 学习的过采样倍数 | 维度 | 数量 | 每个打包向量的字节数 |
+//! 质心 | 模长* | 修正项* | 量化质量* | 打包向量* | 墓碑 | 过期时间 | 属性 | 文本块来源 | 提升的向量 | 原始向量。
+//! 可选值以一个字节的存在标记开头；墓碑、过期时间、属性和来源只写入非默认的序号。
+//! 版本1没有提升的向量和相关配置，仍然可以读取
+//! 保留的原始向量按f32写入，加载时按配置的编码重新压缩。
+//! IVF划分、搜索布局、结果缓存和使用统计不保存，加载后按需重新建立
+
+use crate::error_codes::{BbqError, Subject};
+use crate::byte_reader::{metric_from_code, metric_to_code, write_f32, ByteReader};
+use crate::binary_quantized_scorer::ScoringPrecision;
+use crate::chunk_grouping::ChunkProvenance;
+use crate::correction_layout::CorrectionLayout;
+use crate::filter::{AttributeValue, Attributes, OrdinalBitset};
+use crate::index_generation::IndexGeneration;
+use crate::memory_limits::checked_region_len;
+use crate::optimized_scalar_quantizer::{CorrectionPrecision, OptimizedScalarQuantizer, QuantizationResult};
+use crate::original_vectors::{OriginalVectorEncoding, OriginalVectors};
+use crate::promoted_vectors::{PromotedVector, PromotedVectors};
+use crate::quantized_index::{DegenerateVectorPolicy, QuantizedIndexConfig};
+use crate::quantized_vector_values::QuantizedVectorValuesImpl;
+use std::sync::Arc;
+
+/// 索引序列化魔数
+const INDEX_MAGIC: &[u8; 4] = b"BBQI";
+
+/// 索引序列化格式版本
+const INDEX_FORMAT_VERSION: u8 = 2;
+
+/// 属性值类型标记
+const ATTRIBUTE_BOOL: u8 = 0;
+const ATTRIBUTE_NUMBER: u8 = 1;
+const ATTRIBUTE_TEXT: u8 = 2;
+
+/// 反序列化得到的索引内容
+pub(crate) struct IndexSnapshot {
+    /// 索引配置
+    pub(crate) config: QuantizedIndexConfig,
+    /// 校准得到的过采样倍数
+    pub(crate) learned_oversample: Option<f32>,
+    /// 量化向量值
+    values: QuantizedVectorValuesImpl,
+    /// 预处理后的原始向量
+    original_vectors: Option<Vec<Vec<f32>>>,
+    /// 每个向量的量化质量
+    quality_scores: Vec<f32>,
+    /// 已删除的序号
+    deleted: OrdinalBitset,
+    /// 每个向量的过期时间
+    expires_at: Vec<Option<f64>>,
+    /// 每个向量的属性
+    attributes: Vec<Attributes>,
+    /// 每个向量对应的文本块来源
+    provenance: Vec<Option<ChunkProvenance>>,
+    /// 提升了位数的向量
+    promoted: Option<PromotedVectors>,
+}
+
+impl IndexSnapshot {
+    /// 组装为新的一代
+    pub(crate) fn into_generation(self, number: u64) -> Result<IndexGeneration, BbqError> {
+        let original_vectors = match self.original_vectors {
+            Some(vectors) => Some(OriginalVectors::encode(
+                vectors,
+                self.config.original_encoding,
+                &self.values,
+                self.config.index_bits,
+            )?),
+            None => None,
+        };
+        let mut generation = IndexGeneration::new(number, number, Arc::new(self.values), original_vectors, self.quality_scores);
+        generation.deleted = self.deleted;
+        generation.next_expiry = self.expires_at.iter().flatten().copied().reduce(f64::min);
+        generation.expires_at = self.expires_at;
+        generation.attributes = self.attributes;
+        generation.provenance = self.provenance;
+        generation.promoted = self.promoted.map(Arc::new);
+        Ok(generation)
+    }
+}
+
+/// 把索引配置和当前代写为字节
+pub(crate) fn serialize_index(
+    config: &QuantizedIndexConfig,
+    learned_oversample: Option<f32>,
+    generation: &IndexGeneration,
+) -> Result<Vec<u8>, BbqError> {
+    let values = generation.values();
+    let count = values.size();
+    let dimension = values.dimension();
+    let packed_size = values.packed_size();
+    let original_vectors = if config.keep_original_vectors {
+        Some(generation.require_original_vectors(Subject::new("序列化", "serialization"))?)
+    } else {
+        None
+    };
+
+    let per_vector = packed_size.saturating_add(24);
+    let mut bytes = Vec::with_capacity(checked_region_len(count, per_vector, Subject::new("索引序列化", "index serialization"))?.saturating_add(dimension * 4 + 64));
+    bytes.extend_from_slice(INDEX_MAGIC);
+    bytes.push(INDEX_FORMAT_VERSION);
+    write_config(&mut bytes, config)?;
+    write_optional_f32(&mut bytes, learned_oversample);
+    write_len(&mut bytes, dimension)?;
+    write_len(&mut bytes, count)?;
+    write_len(&mut bytes, packed_size)?;
+
+    for &value in values.get_centroid() {
+        write_f32(&mut bytes, value);
+    }
+    for ord in 0..count {
+        write_f32(&mut bytes, values.get_norm(ord));
+    }
+    for ord in 0..count {
+        let correction = values.try_get_corrective_terms(ord)?;
+        write_f32(&mut bytes, correction.lower_interval);
+        write_f32(&mut bytes, correction.upper_interval);
+        write_f32(&mut bytes, correction.additional_correction);
+        write_f32(&mut bytes, correction.quantized_component_sum);
+    }
+    for ord in 0..count {
+        write_f32(&mut bytes, generation.quality_score(ord).unwrap_or(0.0));
+    }
+    for ord in 0..count {
+        bytes.extend_from_slice(values.vector_value(ord));
+    }
+
+    let deleted: Vec<usize> = generation.deleted.iter().filter(|&ord| ord < count).collect();
+    write_len(&mut bytes, deleted.len())?;
+    for ord in deleted {
+        write_len(&mut bytes, ord)?;
+    }
+
+    let expiring: Vec<(usize, f64)> = (0..count).filter_map(|ord| generation.expiry(ord).map(|expiry| (ord, expiry))).collect();
+    write_len(&mut bytes, expiring.len())?;
+    for (ord, expiry) in expiring {
+        write_len(&mut bytes, ord)?;
+        bytes.extend_from_slice(&expiry.to_le_bytes());
+    }
+
+    let attributed: Vec<(usize, &Attributes)> = (0..count)
+        .filter_map(|ord| generation.attributes(ord).filter(|attributes| !attributes.is_empty()).map(|attributes| (ord, attributes)))
+        .collect();
+    write_len(&mut bytes, attributed.len())?;
+    for (ord, attributes) in attributed {
+        write_len(&mut bytes, ord)?;
+        write_len(&mut bytes, attributes.len())?;
+        // BTreeMap按键排序遍历，同样的属性总是写出同样的字节
+        for (key, value) in attributes {
+            write_string(&mut bytes, key)?;
+            match value {
+                AttributeValue::Bool(value) => {
+                    bytes.push(ATTRIBUTE_BOOL);
+                    bytes.push(*value as u8);
+                }
+                AttributeValue::Number(value) => {
+                    bytes.push(ATTRIBUTE_NUMBER);
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+                AttributeValue::Text(value) => {
+                    bytes.push(ATTRIBUTE_TEXT);
+                    write_string(&mut bytes, value)?;
+                }
+            }
+        }
+    }
+
+    let sourced: Vec<(usize, &ChunkProvenance)> = (0..count)
+        .filter_map(|ord| generation.provenance(ord).map(|provenance| (ord, provenance)))
+        .collect();
+    write_len(&mut bytes, sourced.len())?;
+    for (ord, provenance) in sourced {
+        write_len(&mut bytes, ord)?;
+        write_string(&mut bytes, &provenance.doc_id)?;
+        bytes.extend_from_slice(&(provenance.chunk_offset as u64).to_le_bytes());
+        bytes.extend_from_slice(&(provenance.length as u64).to_le_bytes());
+    }
+
+    let promoted = generation.promoted();
+    bytes.push(promoted.is_some() as u8);
+    if let Some(promoted) = promoted {
+        bytes.push(promoted.bits());
+        write_len(&mut bytes, promoted.len())?;
+        for (ord, vector) in promoted.iter() {
+            write_len(&mut bytes, ord)?;
+            write_f32(&mut bytes, vector.corrections.lower_interval);
+            write_f32(&mut bytes, vector.corrections.upper_interval);
+            write_f32(&mut bytes, vector.corrections.additional_correction);
+            write_f32(&mut bytes, vector.corrections.quantized_component_sum);
+            bytes.extend_from_slice(&vector.quantized);
+        }
+    }
+
+    bytes.push(original_vectors.is_some() as u8);
+    if let Some(original_vectors) = original_vectors {
+        for vector in original_vectors.iter() {
+            for &value in vector {
+                write_f32(&mut bytes, value);
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+/// 读取 `serialize_index` 写出的字节
+pub(crate) fn deserialize_index(bytes: &[u8]) -> Result<IndexSnapshot, BbqError> {
+    let mut reader = ByteReader::new(bytes);
+    if reader.take(4)? != INDEX_MAGIC {
+        return Err(BbqError::corrupt_data("无效的索引数据：魔数不匹配", "invalid index data: magic mismatch"));
+    }
+    let format_version = reader.read_u8()?;
+    if !(1..=INDEX_FORMAT_VERSION).contains(&format_version) {
+        return Err(BbqError::unsupported_version(
+            format!("不支持的索引数据版本: {}", format_version),
+            format!("unsupported index data version: {}", format_version),
+        ));
+    }
+    let config = read_config(&mut reader, format_version)?;
+    let learned_oversample = read_optional_f32(&mut reader)?;
+    let dimension = reader.read_u32()? as usize;
+    let count = reader.read_u32()? as usize;
+    let packed_size = reader.read_u32()? as usize;
+
+    if dimension == 0 {
+        return Err(BbqError::corrupt_data("无效的索引数据：维度为0", "invalid index data: dimension is 0"));
+    }
+    let expected_packed_size = if config.index_bits == 1 {
+        OptimizedScalarQuantizer::packed_len(dimension, config.discretize_dimensions)
+    } else {
+        dimension
+    };
+    if packed_size != expected_packed_size {
+        return Err(BbqError::corrupt_data(
+            format!("无效的索引数据：打包向量长度 {} 与维度 {} 不符", packed_size, dimension),
+            format!("invalid index data: packed vector length {} does not fit dimension {}", packed_size, dimension),
+        ));
+    }
+    // 在分配之前确认声明的数量与实际数据长度相符
+    let per_vector = packed_size.checked_add(24).ok_or_else(|| BbqError::corrupt_data("无效的索引数据：维度过大", "invalid index data: dimension is too large"))?;
+    let fixed = checked_region_len(dimension, 4, Subject::new("索引质心", "index centroid"))?;
+    if checked_region_len(count, per_vector, Subject::new("索引数据", "index data"))?.saturating_add(fixed) > reader.remaining() {
+        return Err(BbqError::corrupt_data("无效的索引数据：数据被截断", "invalid index data: data is truncated"));
+    }
+
+    let centroid = read_f32s(&mut reader, dimension)?;
+    if centroid.iter().any(|value| !value.is_finite()) {
+        return Err(BbqError::corrupt_data("无效的索引数据：质心包含无效值", "invalid index data: centroid contains invalid values"));
+    }
+    let norms = read_f32s(&mut reader, count)?;
+    let mut corrections = Vec::with_capacity(count);
+    for _ in 0..count {
+        corrections.push(QuantizationResult {
+            lower_interval: reader.read_f32()?,
+            upper_interval: reader.read_f32()?,
+            additional_correction: reader.read_f32()?,
+            quantized_component_sum: reader.read_f32()?,
+        });
+    }
+    let quality_scores = read_f32s(&mut reader, count)?;
+
+    let max_value = (1u16 << config.index_bits) - 1;
+    let mut packed_vectors = Vec::with_capacity(count);
+    let mut unpacked_vectors = Vec::with_capacity(count);
+    for ord in 0..count {
+        let packed = reader.take(packed_size)?.to_vec();
+        let unpacked = if config.index_bits == 1 {
+            OptimizedScalarQuantizer::unpack_binary(&packed[..dimension.div_ceil(8)], dimension)?
+        } else {
+            if packed.iter().any(|&value| u16::from(value) > max_value) {
+                return Err(BbqError::corrupt_data(
+                    format!("无效的索引数据：向量 {} 的量化值超出{}位范围", ord, config.index_bits),
+                    format!("invalid index data: quantized values of vector {} exceed the {}-bit range", ord, config.index_bits),
+                ));
+            }
+            packed.clone()
+        };
+        packed_vectors.push(packed);
+        unpacked_vectors.push(unpacked);
+    }
+
+    let mut deleted = OrdinalBitset::new();
+    for _ in 0..reader.read_u32()? {
+        deleted.insert(read_ordinal(&mut reader, count)?);
+    }
+
+    let mut expires_at = vec![None; count];
+    for _ in 0..reader.read_u32()? {
+        let ord = read_ordinal(&mut reader, count)?;
+        let expiry = f64::from_bits(reader.read_u64()?);
+        if expiry.is_nan() {
+            return Err(BbqError::corrupt_data(
+                format!("无效的索引数据：向量 {} 的过期时间无效", ord),
+                format!("invalid index data: expiry of vector {} is invalid", ord),
+            ));
+        }
+        expires_at[ord] = Some(expiry);
+    }
+
+    let mut attributes = vec![Attributes::new(); count];
+    for _ in 0..reader.read_u32()? {
+        let ord = read_ordinal(&mut reader, count)?;
+        for _ in 0..reader.read_u32()? {
+            let key = read_string(&mut reader)?;
+            let value = match reader.read_u8()? {
+                ATTRIBUTE_BOOL => AttributeValue::Bool(read_bool(&mut reader)?),
+                ATTRIBUTE_NUMBER => AttributeValue::Number(f64::from_bits(reader.read_u64()?)),
+                ATTRIBUTE_TEXT => AttributeValue::Text(read_string(&mut reader)?),
+                tag => return Err(BbqError::corrupt_data(
+                    format!("无效的索引数据：未知的属性类型 {}", tag),
+                    format!("invalid index data: unknown attribute type {}", tag),
+                )),
+            };
+            attributes[ord].insert(key, value);
+        }
+    }
+
+    let mut provenance = vec![None; count];
+    for _ in 0..reader.read_u32()? {
+        let ord = read_ordinal(&mut reader, count)?;
+        let doc_id = read_string(&mut reader)?;
+        let chunk_offset = read_usize(&mut reader)?;
+        let length = read_usize(&mut reader)?;
+        provenance[ord] = Some(ChunkProvenance { doc_id, chunk_offset, length });
+    }
+
+    let promoted = if format_version >= 2 && read_bool(&mut reader)? {
+        let mut promoted = PromotedVectors::new(reader.read_u8()?)?;
+        let max_value = (1u16 << promoted.bits()) - 1;
+        for _ in 0..reader.read_u32()? {
+            let ord = read_ordinal(&mut reader, count)?;
+            let corrections = QuantizationResult {
+                lower_interval: reader.read_f32()?,
+                upper_interval: reader.read_f32()?,
+                additional_correction: reader.read_f32()?,
+                quantized_component_sum: reader.read_f32()?,
+            };
+            let quantized = reader.take(dimension)?.to_vec();
+            if quantized.iter().any(|&value| u16::from(value) > max_value) {
+                return Err(BbqError::corrupt_data(
+                    format!("无效的索引数据：提升的向量 {} 的量化值超出{}位范围", ord, promoted.bits()),
+                    format!("invalid index data: quantized values of promoted vector {} exceed the {}-bit range", ord, promoted.bits()),
+                ));
+            }
+            promoted.insert(ord, PromotedVector { quantized, corrections });
+        }
+        Some(promoted)
+    } else {
+        None
+    };
+
+    let original_vectors = if read_bool(&mut reader)? {
+        if checked_region_len(count, fixed, Subject::new("索引原始向量", "index original vectors"))? != reader.remaining() {
+            return Err(BbqError::corrupt_data("无效的索引数据：原始向量长度与数量不一致", "invalid index data: original vector length does not match the count"));
+        }
+        Some((0..count).map(|_| read_f32s(&mut reader, dimension)).collect::<Result<Vec<_>, _>>()?)
+    } else {
+        None
+    };
+    if original_vectors.is_some() != config.keep_original_vectors {
+        return Err(BbqError::corrupt_data("无效的索引数据：原始向量与keep_original_vectors配置不一致", "invalid index data: original vectors disagree with the keep_original_vectors setting"));
+    }
+    if !reader.is_empty() {
+        return Err(BbqError::corrupt_data("无效的索引数据：存在多余数据", "invalid index data: trailing data"));
+    }
+
+    let values = QuantizedVectorValuesImpl::new(packed_vectors, unpacked_vectors, corrections, centroid, norms)
+        .with_correction_layout(config.correction_layout)?
+        .with_discretized_dimensions(config.discretize_dimensions);
+    Ok(IndexSnapshot {
+        config,
+        learned_oversample,
+        values,
+        original_vectors,
+        quality_scores,
+        deleted,
+        expires_at,
+        attributes,
+        provenance,
+        promoted,
+    })
+}
+
+/// 写入配置
+fn write_config(bytes: &mut Vec<u8>, config: &QuantizedIndexConfig) -> Result<(), BbqError> {
+    bytes.push(metric_to_code(config.similarity_function));
+    bytes.push(config.query_bits);
+    bytes.push(config.index_bits);
+    write_optional_f32(bytes, config.lambda);
+    bytes.push(config.iters.is_some() as u8);
+    write_len(bytes, config.iters.unwrap_or(0))?;
+    bytes.push(config.keep_original_vectors as u8);
+    bytes.push(match config.original_encoding {
+        OriginalVectorEncoding::F32 => 0,
+        OriginalVectorEncoding::F16 => 1,
+        OriginalVectorEncoding::Int8Residual => 2,
+    });
+    write_len(bytes, config.result_cache_capacity)?;
+    bytes.push((config.correction_precision == CorrectionPrecision::Double) as u8);
+    bytes.push((config.scoring_precision == ScoringPrecision::Fast) as u8);
+    bytes.push(match config.degenerate_vectors {
+        DegenerateVectorPolicy::Keep => 0,
+        DegenerateVectorPolicy::Skip => 1,
+        DegenerateVectorPolicy::Reject => 2,
+    });
+    bytes.push(match config.correction_layout {
+        CorrectionLayout::ArrayOfStructs => 0,
+        CorrectionLayout::StructOfArrays => 1,
+        CorrectionLayout::HalfPrecision => 2,
+        CorrectionLayout::Grouped => 3,
+    });
+    bytes.push(config.discretize_dimensions as u8);
+    bytes.push(config.prenormalized as u8);
+    write_f32(bytes, config.promote_fraction);
+    bytes.push(config.promoted_bits);
+    Ok(())
+}
+
+/// 读取配置，版本1没有混合精度的配置，使用默认值
+fn read_config(reader: &mut ByteReader, format_version: u8) -> Result<QuantizedIndexConfig, BbqError> {
+    let similarity_function = metric_from_code(reader.read_u8()?)?;
+    let query_bits = reader.read_u8()?;
+    let index_bits = reader.read_u8()?;
+    if !(1..=8).contains(&index_bits) {
+        return Err(BbqError::corrupt_data(format!("无效的索引数据：索引位数 {}", index_bits), format!("invalid index data: index bits {}", index_bits)));
+    }
+    let lambda = read_optional_f32(reader)?;
+    let has_iters = read_bool(reader)?;
+    let iters = reader.read_u32()? as usize;
+    Ok(QuantizedIndexConfig {
+        similarity_function,
+        query_bits,
+        index_bits,
+        lambda,
+        iters: has_iters.then_some(iters),
+        keep_original_vectors: read_bool(reader)?,
+        original_encoding: match reader.read_u8()? {
+            0 => OriginalVectorEncoding::F32,
+            1 => OriginalVectorEncoding::F16,
+            2 => OriginalVectorEncoding::Int8Residual,
+            code => return Err(BbqError::corrupt_data(
+                format!("无效的索引数据：未知的原始向量编码 {}", code),
+                format!("invalid index data: unknown original vector encoding {}", code),
+            )),
+        },
+        result_cache_capacity: reader.read_u32()? as usize,
+        correction_precision: if read_bool(reader)? { CorrectionPrecision::Double } else { CorrectionPrecision::Single },
+        scoring_precision: if read_bool(reader)? { ScoringPrecision::Fast } else { ScoringPrecision::Strict },
+        degenerate_vectors: match reader.read_u8()? {
+            0 => DegenerateVectorPolicy::Keep,
+            1 => DegenerateVectorPolicy::Skip,
+            2 => DegenerateVectorPolicy::Reject,
+            code => return Err(BbqError::corrupt_data(
+                format!("无效的索引数据：未知的退化向量策略 {}", code),
+                format!("invalid index data: unknown degenerate vector policy {}", code),
+            )),
+        },
+        correction_layout: match reader.read_u8()? {
+            0 => CorrectionLayout::ArrayOfStructs,
+            1 => CorrectionLayout::StructOfArrays,
+            2 => CorrectionLayout::HalfPrecision,
+            3 => CorrectionLayout::Grouped,
+            code => return Err(BbqError::corrupt_data(
+                format!("无效的索引数据：未知的修正项布局 {}", code),
+                format!("invalid index data: unknown correction layout {}", code),
+            )),
+        },
+        discretize_dimensions: read_bool(reader)?,
+        prenormalized: read_bool(reader)?,
+        promote_fraction: if format_version >= 2 { reader.read_f32()? } else { 0.0 },
+        promoted_bits: if format_version >= 2 { reader.read_u8()? } else { 4 },
+    })
+}
+
+/// 以u32写入长度或序号
+fn write_len(bytes: &mut Vec<u8>, value: usize) -> Result<(), BbqError> {
+    let value = u32::try_from(value).map_err(|_| BbqError::out_of_range(
+        format!("数值 {} 超出u32范围", value),
+        format!("value {} exceeds the u32 range", value),
+    ))?;
+    bytes.extend_from_slice(&value.to_le_bytes());
+    Ok(())
+}
+
+/// 写入长度前缀的UTF-8字符串
+fn write_string(bytes: &mut Vec<u8>, value: &str) -> Result<(), BbqError> {
+    write_len(bytes, value.len())?;
+    bytes.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+/// 写入带存在标记的f32
+fn write_optional_f32(bytes: &mut Vec<u8>, value: Option<f32>) {
+    bytes.push(value.is_some() as u8);
+    write_f32(bytes, value.unwrap_or(0.0));
+}
+
+fn read_optional_f32(reader: &mut ByteReader) -> Result<Option<f32>, BbqError> {
+    let present = read_bool(reader)?;
+    let value = reader.read_f32()?;
+    Ok(present.then_some(value))
+}
+
+fn read_bool(reader: &mut ByteReader) -> Result<bool, BbqError> {
+    match reader.read_u8()? {
+        0 => Ok(false),
+        1 => Ok(true),
+        value => Err(BbqError::corrupt_data(format!("无效的索引数据：布尔值 {}", value), format!("invalid index data: boolean {}", value))),
+    }
+}
+
+fn read_f32s(reader: &mut ByteReader, len: usize) -> Result<Vec<f32>, BbqError> {
+    (0..len).map(|_| reader.read_f32()).collect()
+}
+
+/// 读取序号并检查范围
+fn read_ordinal(reader: &mut ByteReader, count: usize) -> Result<usize, BbqError> {
+    let ord = reader.read_u32()? as usize;
+    if ord >= count {
+        return Err(BbqError::corrupt_data(
+            format!("无效的索引数据：序号 {} 超出范围 {}", ord, count),
+            format!("invalid index data: ordinal {} out of range {}", ord, count),
+        ));
+    }
+    Ok(ord)
+}
+
+fn read_usize(reader: &mut ByteReader) -> Result<usize, BbqError> {
+    usize::try_from(reader.read_u64()?).map_err(|_| BbqError::corrupt_data("无效的索引数据：偏移超出范围", "invalid index data: offset out of range"))
+}
+
+fn read_string(reader: &mut ByteReader) -> Result<String, BbqError> {
+    let len = reader.read_u32()? as usize;
+    std::str::from_utf8(reader.take(len)?)
+        .map(str::to_string)
+        .map_err(|_| BbqError::corrupt_data("无效的索引数据：字符串不是合法的UTF-8", "invalid index data: string is not valid UTF-8"))
+}
@@ -0,0 +1,359 @@
+//! 倒排文件（IVF）粗划分
+//!
+//! 用k-means把向量划分到nlist个列表，搜索时只对与查询最接近的nprobe个列表中的向量评分。
+//! 划分基于预处理后的原始向量（余弦相似度时已归一化），因此需要保留原始向量；
+//! 压缩时列表随序号一起重映射。数据分布漂移后列表会失衡、路由质量下降，
+//! 可以从当前中心继续迭代并重新分配所有向量
+
+use crate::error_codes::BbqError;
+use crate::vector_similarity::{descending_score_order, fast_dot_product, fast_squared_distance, SimilarityFunction};
+use crate::rng::RngSource;
+use crate::vector_utils::reservoir_sample;
+
+/// 默认的自适应探测阈值
+pub const DEFAULT_PROBE_MARGIN: f32 = 0.1;
+
+/// 列表探测策略
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProbeStrategy {
+    /// 固定探测nprobe个列表
+    Fixed(usize),
+    /// 按查询难度自适应：先探测min个列表，之后只要下一个列表中心与最佳中心的亲和度差距
+    /// 不超过 `margin * (最佳 - 最差)`（路由有歧义）就继续扩展，最多探测max个列表
+    Adaptive {
+        /// 最少探测的列表数
+        min: usize,
+        /// 最多探测的列表数
+        max: usize,
+        /// 相对差距阈值
+        margin: f32,
+    },
+}
+
+impl ProbeStrategy {
+    /// 使用默认阈值的自适应策略
+    pub fn adaptive(min: usize, max: usize) -> Self {
+        ProbeStrategy::Adaptive { min, max, margin: DEFAULT_PROBE_MARGIN }
+    }
+
+    /// 校验参数
+    pub fn validate(&self) -> Result<(), BbqError> {
+        match *self {
+            ProbeStrategy::Fixed(_) => Ok(()),
+            ProbeStrategy::Adaptive { min, max, margin } => {
+                if min > max {
+                    return Err(BbqError::invalid_argument(
+                        format!("最少探测列表数 {} 大于最多探测列表数 {}", min, max),
+                        format!("minimum probe count {} exceeds the maximum {}", min, max),
+                    ));
+                }
+                if !margin.is_finite() || margin < 0.0 {
+                    return Err(BbqError::invalid_argument(format!("无效的探测阈值: {}", margin), format!("invalid probe margin: {}", margin)));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// IVF划分统计
+#[derive(Debug, Clone, PartialEq)]
+pub struct IvfStatistics {
+    /// 每个列表中未删除的向量数量
+    pub list_sizes: Vec<usize>,
+    /// 不平衡因子 `nlist * Σ size² / (Σ size)²`，完全均衡时为1
+    pub imbalance_factor: f32,
+    /// 向量到所属列表中心的平均欧氏距离
+    pub mean_intra_list_distance: f32,
+    /// 空列表数量
+    pub empty_lists: usize,
+}
+
+/// IVF粗划分
+#[derive(Debug, Clone)]
+pub struct IvfPartition {
+    similarity_function: SimilarityFunction,
+    /// 列表中心
+    centroids: Vec<Vec<f32>>,
+    /// 每个列表中的序号（升序）
+    lists: Vec<Vec<usize>>,
+    /// 每个向量到所属列表中心的平方距离
+    distances: Vec<f32>,
+}
+
+impl IvfPartition {
+    /// 训练划分
+    ///
+    /// # 参数
+    /// * `vectors` - 预处理后的向量
+    /// * `nlist` - 列表数量
+    /// * `iterations` - k-means迭代次数
+    /// * `seed` - 选取初始中心的随机种子
+    /// * `similarity_function` - 相似性函数，最大内积时按内积分配，其余按欧氏距离
+    pub fn train(
+        vectors: &[Vec<f32>],
+        nlist: usize,
+        iterations: usize,
+        seed: u64,
+        similarity_function: SimilarityFunction,
+    ) -> Result<Self, BbqError> {
+        Self::train_with(vectors, nlist, iterations, &mut fastrand::Rng::with_seed(seed), similarity_function)
+    }
+
+    /// 使用指定的随机来源选取初始中心并训练划分，其余参数见 `train`
+    pub fn train_with<R: RngSource + ?Sized>(
+        vectors: &[Vec<f32>],
+        nlist: usize,
+        iterations: usize,
+        rng: &mut R,
+        similarity_function: SimilarityFunction,
+    ) -> Result<Self, BbqError> {
+        if nlist == 0 || nlist > vectors.len() {
+            return Err(BbqError::out_of_range(
+                format!("列表数量必须在1到向量数量 {} 之间，当前为{}", vectors.len(), nlist),
+                format!("list count must be between 1 and the vector count {}, got {}", vectors.len(), nlist),
+            ));
+        }
+        let centroids = reservoir_sample(0..vectors.len(), nlist, rng)
+            .into_iter()
+            .map(|ord| vectors[ord].clone())
+            .collect();
+        Ok(Self::refine(vectors, centroids, iterations, similarity_function, |_| true))
+    }
+
+    /// 从当前中心继续迭代并重新分配所有向量
+    ///
+    /// # 参数
+    /// * `vectors` - 与划分序号一致的预处理后向量
+    /// * `iterations` - k-means迭代次数
+    /// * `include` - 参与更新中心的序号（已删除的向量仍会被分配，但不影响中心）
+    pub fn rebalance<F: Fn(usize) -> bool>(&self, vectors: &[Vec<f32>], iterations: usize, include: F) -> Self {
+        Self::refine(vectors, self.centroids.clone(), iterations, self.similarity_function, include)
+    }
+
+    /// Lloyd迭代：分配向量，再用参与更新的向量均值替换中心（空列表保留原中心）
+    fn refine<F: Fn(usize) -> bool>(
+        vectors: &[Vec<f32>],
+        mut centroids: Vec<Vec<f32>>,
+        iterations: usize,
+        similarity_function: SimilarityFunction,
+        include: F,
+    ) -> Self {
+        let dimension = centroids.first().map_or(0, Vec::len);
+        let mut assignment = assign(vectors, &centroids, similarity_function);
+        for _ in 0..iterations {
+            let mut sums = vec![vec![0.0f64; dimension]; centroids.len()];
+            let mut counts = vec![0usize; centroids.len()];
+            for (ord, &list) in assignment.iter().enumerate().filter(|&(ord, _)| include(ord)) {
+                counts[list] += 1;
+                for (sum, &value) in sums[list].iter_mut().zip(&vectors[ord]) {
+                    *sum += value as f64;
+                }
+            }
+            for ((centroid, sum), &count) in centroids.iter_mut().zip(&sums).zip(&counts) {
+                if count > 0 {
+                    for (value, &total) in centroid.iter_mut().zip(sum) {
+                        *value = (total / count as f64) as f32;
+                    }
+                }
+            }
+            assignment = assign(vectors, &centroids, similarity_function);
+        }
+
+        let mut lists = vec![Vec::new(); centroids.len()];
+        let mut distances = Vec::with_capacity(vectors.len());
+        for (ord, &list) in assignment.iter().enumerate() {
+            lists[list].push(ord);
+            distances.push(fast_squared_distance(&vectors[ord], &centroids[list]));
+        }
+        Self { similarity_function, centroids, lists, distances }
+    }
+
+    /// 列表数量
+    pub fn nlist(&self) -> usize {
+        self.centroids.len()
+    }
+
+    /// 列表中心
+    pub fn centroids(&self) -> &[Vec<f32>] {
+        &self.centroids
+    }
+
+    /// 列表中的序号
+    pub fn list(&self, list: usize) -> &[usize] {
+        self.lists.get(list).map_or(&[], Vec::as_slice)
+    }
+
+    /// 按与查询的接近程度对列表排序
+    ///
+    /// # 返回
+    /// （列表, 亲和度）按亲和度降序；亲和度为内积（最大内积）或负的平方距离
+    pub fn rank_lists(&self, query_vector: &[f32]) -> Vec<(usize, f32)> {
+        let mut ranked: Vec<(usize, f32)> = self.centroids.iter()
+            .enumerate()
+            .map(|(list, centroid)| (list, affinity(query_vector, centroid, self.similarity_function)))
+            .collect();
+        ranked.sort_by(|a, b| descending_score_order(a.1, b.1));
+        ranked
+    }
+
+    /// 与查询最接近的nprobe个列表中的所有序号（升序）
+    pub fn probe(&self, query_vector: &[f32], nprobe: usize) -> Vec<usize> {
+        self.probe_with(query_vector, ProbeStrategy::Fixed(nprobe)).0
+    }
+
+    /// 按策略选择列表
+    ///
+    /// # 返回
+    /// （所选列表中的所有序号（升序）, 实际探测的列表数）
+    pub fn probe_with(&self, query_vector: &[f32], strategy: ProbeStrategy) -> (Vec<usize>, usize) {
+        let ranked = self.rank_lists(query_vector);
+        let nprobe = match strategy {
+            ProbeStrategy::Fixed(nprobe) => nprobe.max(1),
+            ProbeStrategy::Adaptive { min, max, margin } => {
+                let best = ranked.first().map_or(0.0, |&(_, score)| score);
+                let spread = best - ranked.last().map_or(0.0, |&(_, score)| score);
+                let ambiguous = ranked.iter()
+                    .take_while(|&&(_, score)| best - score <= margin * spread)
+                    .count();
+                ambiguous.clamp(min.max(1), max.max(1))
+            }
+        }.min(ranked.len());
+
+        let mut ords: Vec<usize> = ranked[..nprobe].iter()
+            .flat_map(|&(list, _)| self.lists[list].iter().copied())
+            .collect();
+        ords.sort_unstable();
+        (ords, nprobe)
+    }
+
+    /// 统计划分质量
+    ///
+    /// # 参数
+    /// * `is_live` - 参与统计的序号（通常为未删除的向量）
+    pub fn statistics<F: Fn(usize) -> bool>(&self, is_live: F) -> IvfStatistics {
+        let list_sizes: Vec<usize> = self.lists.iter()
+            .map(|list| list.iter().filter(|&&ord| is_live(ord)).count())
+            .collect();
+        let total: usize = list_sizes.iter().sum();
+        let sum_sq: f64 = list_sizes.iter().map(|&size| (size as f64).powi(2)).sum();
+        let imbalance_factor = if total == 0 {
+            1.0
+        } else {
+            (self.nlist() as f64 * sum_sq / (total as f64).powi(2)) as f32
+        };
+        let distance_sum: f64 = self.distances.iter()
+            .enumerate()
+            .filter(|&(ord, _)| is_live(ord))
+            .map(|(_, &distance)| (distance as f64).sqrt())
+            .sum();
+
+        IvfStatistics {
+            empty_lists: list_sizes.iter().filter(|&&size| size == 0).count(),
+            list_sizes,
+            imbalance_factor,
+            mean_intra_list_distance: if total == 0 { 0.0 } else { (distance_sum / total as f64) as f32 },
+        }
+    }
+
+    /// 把写入了新向量的序号重新分配到最接近的列表，中心不变
+    ///
+    /// # 参数
+    /// * `written` - （序号, 预处理后的向量），超出当前范围的序号为新追加的向量，需按序号升序给出
+    pub(crate) fn reassign(&self, written: &[(usize, &[f32])]) -> Self {
+        let mut partition = self.clone();
+        for &(ord, vector) in written {
+            if ord < partition.distances.len() {
+                for list in partition.lists.iter_mut() {
+                    if let Ok(position) = list.binary_search(&ord) {
+                        list.remove(position);
+                        break;
+                    }
+                }
+            } else {
+                partition.distances.resize(ord + 1, 0.0);
+            }
+            let list = nearest(vector, &partition.centroids, partition.similarity_function);
+            let position = partition.lists[list].binary_search(&ord).unwrap_or_else(|position| position);
+            partition.lists[list].insert(position, ord);
+            partition.distances[ord] = fast_squared_distance(vector, &partition.centroids[list]);
+        }
+        partition
+    }
+
+    /// 压缩后重映射：只保留live中的序号，并按其在live中的位置重新编号
+    pub(crate) fn remap(&self, live: &[usize]) -> Self {
+        let mut new_ord = vec![usize::MAX; self.distances.len()];
+        for (new, &old) in live.iter().enumerate() {
+            new_ord[old] = new;
+        }
+        Self {
+            similarity_function: self.similarity_function,
+            centroids: self.centroids.clone(),
+            lists: self.lists.iter()
+                .map(|list| list.iter().map(|&ord| new_ord[ord]).filter(|&ord| ord != usize::MAX).collect())
+                .collect(),
+            distances: live.iter().map(|&ord| self.distances[ord]).collect(),
+        }
+    }
+}
+
+/// 向量与中心的亲和度，越大越接近
+fn affinity(vector: &[f32], centroid: &[f32], similarity_function: SimilarityFunction) -> f32 {
+    match similarity_function {
+        SimilarityFunction::MaximumInnerProduct => fast_dot_product(vector, centroid),
+        _ => -fast_squared_distance(vector, centroid),
+    }
+}
+
+/// 把每个向量分配到亲和度最大的中心
+fn assign(vectors: &[Vec<f32>], centroids: &[Vec<f32>], similarity_function: SimilarityFunction) -> Vec<usize> {
+    vectors.iter()
+        .map(|vector| nearest(vector, centroids, similarity_function))
+        .collect()
+}
+
+/// 与向量最接近的中心
+fn nearest(vector: &[f32], centroids: &[Vec<f32>], similarity_function: SimilarityFunction) -> usize {
+    centroids.iter()
+        .map(|centroid| affinity(vector, centroid, similarity_function))
+        .enumerate()
+        .fold((0, f32::NEG_INFINITY), |best, (list, score)| if score > best.1 { (list, score) } else { best })
+        .0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector_utils::generate_gaussian_mixture;
+
+    #[test]
+    fn test_train_statistics_and_remap() {
+        let vectors = generate_gaussian_mixture(400, 8, 4, 0.05, 3).unwrap();
+        let partition = IvfPartition::train(&vectors, 4, 10, 1, SimilarityFunction::Euclidean).unwrap();
+        let stats = partition.statistics(|_| true);
+        assert_eq!(stats.list_sizes.iter().sum::<usize>(), 400);
+        assert!(stats.imbalance_factor >= 1.0);
+        assert!(stats.mean_intra_list_distance > 0.0);
+
+        // 每个列表的向量都应路由回自身列表
+        let probed = partition.probe(&vectors[10], 1);
+        assert!(probed.contains(&10));
+
+        let live: Vec<usize> = (0..400).filter(|ord| ord % 2 == 0).collect();
+        let remapped = partition.remap(&live);
+        assert_eq!(remapped.statistics(|_| true).list_sizes.iter().sum::<usize>(), 200);
+        assert!(remapped.probe(&vectors[10], 1).contains(&5));
+
+        // 查询位于两个中心的中点时路由有歧义，自适应策略应扩展探测范围
+        let centroids = partition.centroids();
+        let midpoint: Vec<f32> = centroids[0].iter().zip(&centroids[1]).map(|(a, b)| (a + b) / 2.0).collect();
+        assert!(partition.probe_with(&midpoint, ProbeStrategy::adaptive(1, 4)).1 >= 2);
+        assert_eq!(partition.probe_with(&centroids[2], ProbeStrategy::adaptive(1, 4)).1, 1);
+        assert_eq!(partition.probe_with(&midpoint, ProbeStrategy::Adaptive { min: 1, max: 1, margin: 1.0 }).1, 1);
+        assert!(ProbeStrategy::adaptive(3, 2).validate().is_err());
+
+        assert!(IvfPartition::train(&vectors, 0, 10, 1, SimilarityFunction::Euclidean).is_err());
+        assert!(IvfPartition::train(&vectors[..3], 4, 10, 1, SimilarityFunction::Euclidean).is_err());
+    }
+}
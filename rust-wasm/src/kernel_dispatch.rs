@@ -0,0 +1,321 @@
+//! 运行时内核选择
+//!
+//! 不同浏览器/设备上WASM SIMD的性能差异很大，
+//! 因此在实际设备上对可用内核（标量、u64位计数、SIMD128）计时，
+//! 按操作分别选出最快的实现并记录下来，之后的批量计算都走选中的内核
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::error_codes::BbqError;
+use crate::batch_dot_product::{
+    compute_batch_four_bit_dot_product_bit_planes,
+    compute_batch_four_bit_dot_product_direct_packed,
+    compute_batch_one_bit_dot_product_direct_packed,
+    compute_batch_one_bit_dot_product_u64,
+};
+use crate::timer::{elapsed_ms, now_ms};
+
+/// 校准用的向量维度
+const CALIBRATION_DIMENSION: usize = 768;
+
+/// 校准用的向量数量
+const CALIBRATION_VECTORS: usize = 256;
+
+/// 每个内核至少计时的毫秒数（浏览器中Date.now()只有毫秒精度）
+const CALIBRATION_MIN_MS: f64 = 2.0;
+
+/// 每个内核最多重复的次数
+const CALIBRATION_MAX_REPS: usize = 10_000;
+
+/// 未校准标记
+const UNSET: u8 = 0;
+
+static ONE_BIT_KERNEL: AtomicU8 = AtomicU8::new(UNSET);
+static FOUR_BIT_KERNEL: AtomicU8 = AtomicU8::new(UNSET);
+
+/// 内核实现
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KernelVariant {
+    /// 逐字节标量实现
+    Scalar,
+    /// u64分块位计数实现
+    U64Popcount,
+    /// WASM SIMD128实现
+    Simd128,
+}
+
+impl KernelVariant {
+    /// 内核名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            KernelVariant::Scalar => "scalar",
+            KernelVariant::U64Popcount => "u64_popcount",
+            KernelVariant::Simd128 => "simd128",
+        }
+    }
+
+    /// 根据名称解析内核
+    pub fn from_name(name: &str) -> Result<Self, BbqError> {
+        match name.to_lowercase().as_str() {
+            "scalar" => Ok(KernelVariant::Scalar),
+            "u64_popcount" | "u64" => Ok(KernelVariant::U64Popcount),
+            "simd128" | "simd" => Ok(KernelVariant::Simd128),
+            _ => Err(BbqError::invalid_argument(format!("未知的内核: {}", name), format!("unknown kernel: {}", name))),
+        }
+    }
+
+    /// 当前构建中是否可用
+    pub fn is_available(&self) -> bool {
+        match self {
+            KernelVariant::Scalar | KernelVariant::U64Popcount => true,
+            KernelVariant::Simd128 => cfg!(all(target_arch = "wasm32", target_feature = "simd128")),
+        }
+    }
+
+    fn code(&self) -> u8 {
+        match self {
+            KernelVariant::Scalar => 1,
+            KernelVariant::U64Popcount => 2,
+            KernelVariant::Simd128 => 3,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(KernelVariant::Scalar),
+            2 => Some(KernelVariant::U64Popcount),
+            3 => Some(KernelVariant::Simd128),
+            _ => None,
+        }
+    }
+}
+
+/// 每种操作选中的内核
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KernelSelection {
+    /// 1位查询 × 1位索引批量点积
+    pub one_bit: KernelVariant,
+    /// 4位查询 × 1位索引批量点积
+    pub four_bit: KernelVariant,
+}
+
+/// 单个内核的计时结果
+#[derive(Debug, Clone)]
+pub struct KernelTiming {
+    /// 操作名称："one_bit" | "four_bit"
+    pub operation: &'static str,
+    /// 内核
+    pub variant: KernelVariant,
+    /// 每批（CALIBRATION_VECTORS个向量）平均耗时（毫秒）
+    pub ms_per_batch: f64,
+}
+
+/// 校准报告
+#[derive(Debug, Clone)]
+pub struct KernelCalibrationReport {
+    /// 选中的内核
+    pub selection: KernelSelection,
+    /// 所有内核的计时
+    pub timings: Vec<KernelTiming>,
+}
+
+/// 当前构建中可用的内核
+pub fn available_kernels() -> Vec<KernelVariant> {
+    [KernelVariant::Scalar, KernelVariant::U64Popcount, KernelVariant::Simd128]
+        .into_iter()
+        .filter(|variant| variant.is_available())
+        .collect()
+}
+
+/// 对所有可用内核计时并记录最快的选择
+pub fn calibrate_kernels() -> KernelCalibrationReport {
+    let packed_dimension = CALIBRATION_DIMENSION.div_ceil(8);
+    let mut rng = fastrand::Rng::with_seed(0x5eed);
+    let four_bit_query: Vec<u8> = (0..CALIBRATION_DIMENSION).map(|_| rng.u8(0..16)).collect();
+    let one_bit_query: Vec<u8> = (0..packed_dimension).map(|_| rng.u8(..)).collect();
+    let buffer: Vec<u8> = (0..packed_dimension * CALIBRATION_VECTORS).map(|_| rng.u8(..)).collect();
+
+    let mut timings = Vec::new();
+    for variant in available_kernels() {
+        timings.push(KernelTiming {
+            operation: "one_bit",
+            variant,
+            ms_per_batch: time_kernel(|| {
+                run_batch_one_bit(variant, &one_bit_query, &buffer, CALIBRATION_VECTORS, packed_dimension)
+            }),
+        });
+        timings.push(KernelTiming {
+            operation: "four_bit",
+            variant,
+            ms_per_batch: time_kernel(|| {
+                run_batch_four_bit(variant, &four_bit_query, &buffer, CALIBRATION_VECTORS, CALIBRATION_DIMENSION)
+            }),
+        });
+    }
+
+    let fastest = |operation: &str| {
+        timings.iter()
+            .filter(|t| t.operation == operation)
+            .min_by(|a, b| a.ms_per_batch.partial_cmp(&b.ms_per_batch).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|t| t.variant)
+            .unwrap_or(KernelVariant::Scalar)
+    };
+    let selection = KernelSelection {
+        one_bit: fastest("one_bit"),
+        four_bit: fastest("four_bit"),
+    };
+    set_kernel_selection(selection);
+
+    KernelCalibrationReport { selection, timings }
+}
+
+/// 获取选中的内核，尚未校准时先校准
+pub fn selected_kernels() -> KernelSelection {
+    let one_bit = KernelVariant::from_code(ONE_BIT_KERNEL.load(Ordering::Relaxed));
+    let four_bit = KernelVariant::from_code(FOUR_BIT_KERNEL.load(Ordering::Relaxed));
+    match (one_bit, four_bit) {
+        (Some(one_bit), Some(four_bit)) => KernelSelection { one_bit, four_bit },
+        _ => calibrate_kernels().selection,
+    }
+}
+
+/// 是否已经选定内核（校准过或手动指定过）
+pub fn kernels_selected() -> bool {
+    KernelVariant::from_code(ONE_BIT_KERNEL.load(Ordering::Relaxed)).is_some()
+        && KernelVariant::from_code(FOUR_BIT_KERNEL.load(Ordering::Relaxed)).is_some()
+}
+
+/// 手动指定内核（不可用的内核回退为标量实现）
+pub fn set_kernel_selection(selection: KernelSelection) {
+    let usable = |variant: KernelVariant| {
+        if variant.is_available() { variant } else { KernelVariant::Scalar }
+    };
+    ONE_BIT_KERNEL.store(usable(selection.one_bit).code(), Ordering::Relaxed);
+    FOUR_BIT_KERNEL.store(usable(selection.four_bit).code(), Ordering::Relaxed);
+}
+
+/// 使用选中的内核计算1位批量点积
+pub fn dispatch_batch_one_bit(
+    query_vector: &[u8],
+    continuous_buffer: &[u8],
+    num_vectors: usize,
+    packed_dimension: usize,
+) -> Vec<i32> {
+    run_batch_one_bit(selected_kernels().one_bit, query_vector, continuous_buffer, num_vectors, packed_dimension)
+}
+
+/// 使用选中的内核计算4位批量点积
+pub fn dispatch_batch_four_bit(
+    query_vector: &[u8],
+    continuous_buffer: &[u8],
+    num_vectors: usize,
+    dimension: usize,
+) -> Vec<i32> {
+    run_batch_four_bit(selected_kernels().four_bit, query_vector, continuous_buffer, num_vectors, dimension)
+}
+
+/// 使用指定内核计算1位批量点积
+pub fn run_batch_one_bit(
+    variant: KernelVariant,
+    query_vector: &[u8],
+    continuous_buffer: &[u8],
+    num_vectors: usize,
+    packed_dimension: usize,
+) -> Vec<i32> {
+    match variant {
+        KernelVariant::U64Popcount => {
+            compute_batch_one_bit_dot_product_u64(query_vector, continuous_buffer, num_vectors, packed_dimension)
+        }
+        #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+        KernelVariant::Simd128 => crate::batch_dot_product::simd128::compute_batch_one_bit_dot_product_simd128(
+            query_vector,
+            continuous_buffer,
+            num_vectors,
+            packed_dimension,
+        ),
+        _ => compute_batch_one_bit_dot_product_direct_packed(query_vector, continuous_buffer, num_vectors, packed_dimension),
+    }
+}
+
+/// 使用指定内核计算4位批量点积
+pub fn run_batch_four_bit(
+    variant: KernelVariant,
+    query_vector: &[u8],
+    continuous_buffer: &[u8],
+    num_vectors: usize,
+    dimension: usize,
+) -> Vec<i32> {
+    match variant {
+        KernelVariant::U64Popcount => {
+            compute_batch_four_bit_dot_product_bit_planes(query_vector, continuous_buffer, num_vectors, dimension)
+        }
+        #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+        KernelVariant::Simd128 => crate::batch_dot_product::simd128::compute_batch_four_bit_dot_product_simd128(
+            query_vector,
+            continuous_buffer,
+            num_vectors,
+            dimension,
+        ),
+        _ => compute_batch_four_bit_dot_product_direct_packed(query_vector, continuous_buffer, num_vectors, dimension),
+    }
+}
+
+/// 重复运行内核直到超过最短计时，返回平均每次耗时
+fn time_kernel<F: FnMut() -> Vec<i32>>(mut kernel: F) -> f64 {
+    // 先运行一次，排除首次分配的开销
+    std::hint::black_box(kernel());
+
+    let start = now_ms();
+    let mut reps = 0usize;
+    while reps < CALIBRATION_MAX_REPS {
+        std::hint::black_box(kernel());
+        reps += 1;
+        if elapsed_ms(start) >= CALIBRATION_MIN_MS {
+            break;
+        }
+    }
+    elapsed_ms(start) / reps as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all_kernels_agree() {
+        let dimension = 100;
+        let packed_dimension = dimension / 8 + 1;
+        let mut rng = fastrand::Rng::with_seed(7);
+        let four_bit_query: Vec<u8> = (0..dimension).map(|_| rng.u8(0..16)).collect();
+        let one_bit_query: Vec<u8> = (0..packed_dimension).map(|_| rng.u8(..)).collect();
+        let mut buffer: Vec<u8> = (0..packed_dimension * 6).map(|_| rng.u8(..)).collect();
+        for v in 0..6 {
+            buffer[v * packed_dimension + packed_dimension - 1] &= 0xF0;
+        }
+
+        let expected_one = run_batch_one_bit(KernelVariant::Scalar, &one_bit_query, &buffer, 6, packed_dimension);
+        let expected_four = run_batch_four_bit(KernelVariant::Scalar, &four_bit_query, &buffer, 6, dimension);
+        for variant in available_kernels() {
+            assert_eq!(run_batch_one_bit(variant, &one_bit_query, &buffer, 6, packed_dimension), expected_one);
+            assert_eq!(run_batch_four_bit(variant, &four_bit_query, &buffer, 6, dimension), expected_four);
+        }
+    }
+
+    #[test]
+    fn test_calibration_selects_available_kernels() {
+        let report = calibrate_kernels();
+        assert!(report.selection.one_bit.is_available());
+        assert!(report.selection.four_bit.is_available());
+        assert_eq!(report.timings.len(), available_kernels().len() * 2);
+        // 其他测试可能并发重新校准，这里只检查选择结果可用
+        assert!(selected_kernels().one_bit.is_available());
+    }
+
+    #[test]
+    fn test_variant_names() {
+        for variant in [KernelVariant::Scalar, KernelVariant::U64Popcount, KernelVariant::Simd128] {
+            assert_eq!(KernelVariant::from_name(variant.name()).unwrap(), variant);
+        }
+        assert!(KernelVariant::from_name("avx512").is_err());
+    }
+}
@@ -0,0 +1,135 @@
+//! 位运算内核的稳定公共接口
+//!
+//! 其他Rust ANN库只需要1位/4位点积内核时依赖这个模块即可，
+//! 以 `default-features = false` 引入本crate时不会编译索引、评分器等模块。
+//!
+//! # 稳定性
+//!
+//! - 本模块列出的函数和类型遵循semver：签名、位序和返回值的含义只在主版本变化时改变。
+//!   `KERNELS_API_VERSION` 随不兼容的变化递增
+//! - 没有在这里重新导出的 `bitwise_dot_product` / `batch_dot_product` / `kernel_dispatch` 项属于内部实现，可能随时调整
+//! - 同一输入在所有内核实现（标量、u64位计数、SIMD128）上结果逐位相同，只有速度不同
+//!
+//! # 数据布局与对齐
+//!
+//! - 1位打包向量按 `pack_bits` 的位序存放：第i维位于第 `i / 8` 字节的第 `7 - i % 8` 位（高位在前），
+//!   末尾不足8维的位补0。补出的位参与1位点积的计数（两侧都为0时算作相同），调用方需自行扣除
+//! - 批量内核的目标向量连续存放，第t个目标从 `t * stride` 开始；紧凑布局的 `stride` 为 `dimension.div_ceil(8)`，
+//!   按64位对齐的Lucene布局见 `OptimizedScalarQuantizer::packed_len`。带 `stride` 参数的内核只读取每个目标的前缀
+//! - 不要求任何内存对齐：内核按字节读取后用 `u64::from_le_bytes` 组字，任意偏移的切片都可以直接传入
+//! - 多位查询以未打包格式传入（每维一个0..2^b的值）；点积上界 `dimension × (2^b - 1)` 超出i32时
+//!   使用 `DotProductAccumulator` 选出的i64内核
+//! - 长度不足的切片会panic（越界），内核本身不做校验；需要校验时使用返回 `Result` 的单向量函数
+//!
+//! # 参考性能
+//!
+//! 768维、每批256个目标，release构建，x86_64（Intel Xeon，未开启target-cpu=native）：
+//!
+//! | 内核 | 每批耗时 |
+//! |------|---------|
+//! | 1位 标量 | 0.037 ms |
+//! | 1位 u64位计数 | 0.0044 ms |
+//! | 4位 标量 | 0.103 ms |
+//! | 4位 位平面 | 0.023 ms |
+//! | 4位 分块（16个查询，平均每个查询） | 0.021 ms |
+//!
+//! 浏览器和移动设备上差异很大，实际选择以 `calibrate_kernels` 在目标设备上的计时为准
+
+use crate::error_codes::BbqError;
+use crate::optimized_scalar_quantizer::OptimizedScalarQuantizer;
+
+pub use crate::bitwise_dot_product::{
+    compute_packed_bit_dot_product,
+    compute_packed_hamming_distance,
+    compute_quantized_dot_product,
+};
+pub use crate::batch_dot_product::{
+    build_query_bit_planes,
+    compute_batch_four_bit_dot_product_bit_planes,
+    compute_batch_four_bit_dot_product_direct_packed,
+    compute_batch_four_bit_dot_product_strided,
+    compute_batch_four_bit_dot_product_wide,
+    compute_batch_one_bit_dot_product_direct_packed,
+    compute_batch_one_bit_dot_product_strided,
+    compute_batch_one_bit_dot_product_u64,
+    compute_batch_one_bit_dot_product_wide,
+    compute_block_four_bit_dot_products,
+    compute_block_one_bit_dot_products,
+    BatchDotProducts,
+    DotProductAccumulator,
+};
+pub use crate::kernel_dispatch::{
+    available_kernels,
+    calibrate_kernels,
+    dispatch_batch_four_bit,
+    dispatch_batch_one_bit,
+    run_batch_four_bit,
+    run_batch_one_bit,
+    selected_kernels,
+    set_kernel_selection,
+    KernelCalibrationReport,
+    KernelSelection,
+    KernelTiming,
+    KernelVariant,
+};
+
+/// 内核接口的版本，接口发生不兼容变化时递增
+pub const KERNELS_API_VERSION: u32 = 1;
+
+/// 把每维一个0/1值的向量打包为1位向量（高位在前）
+///
+/// # 参数
+/// * `vector` - 每维一个0或1
+/// * `packed` - 打包结果，长度不小于 `vector.len().div_ceil(8)`
+pub fn pack_bits(vector: &[u8], packed: &mut [u8]) -> Result<(), BbqError> {
+    OptimizedScalarQuantizer::pack_as_binary(vector, packed)
+}
+
+/// 把1位打包向量解包为每维一个0/1值，`pack_bits` 的逆操作
+///
+/// # 参数
+/// * `packed` - 打包向量
+/// * `destination` - 解包结果，长度即维度
+pub fn unpack_bits(packed: &[u8], destination: &mut [u8]) -> Result<(), BbqError> {
+    OptimizedScalarQuantizer::unpack_from_binary(packed, destination)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kernels_agree_on_documented_layout() {
+        let dimension: usize = 100;
+        let packed_dimension = dimension.div_ceil(8);
+        let mut rng = fastrand::Rng::with_seed(7);
+        let targets: Vec<Vec<u8>> = (0..5)
+            .map(|_| (0..dimension).map(|_| rng.u8(0..2)).collect())
+            .collect();
+        let query: Vec<u8> = (0..dimension).map(|_| rng.u8(0..16)).collect();
+
+        // 目标按文档的布局打包后，从未对齐的偏移开始连续存放
+        let mut storage = vec![0xAAu8];
+        for target in &targets {
+            let mut packed = vec![0u8; packed_dimension];
+            pack_bits(target, &mut packed).unwrap();
+            storage.extend(packed);
+        }
+        let buffer = &storage[1..];
+
+        let expected: Vec<i32> = targets.iter()
+            .map(|target| compute_quantized_dot_product(&query, target).unwrap())
+            .collect();
+        for variant in available_kernels() {
+            assert_eq!(run_batch_four_bit(variant, &query, buffer, targets.len(), dimension), expected);
+        }
+        assert_eq!(
+            compute_batch_four_bit_dot_product_strided(&query, buffer, &[4, 0], dimension, packed_dimension),
+            vec![expected[4], expected[0]],
+        );
+
+        let mut unpacked = vec![0u8; dimension];
+        unpack_bits(&buffer[packed_dimension..2 * packed_dimension], &mut unpacked).unwrap();
+        assert_eq!(unpacked, targets[1]);
+    }
+}
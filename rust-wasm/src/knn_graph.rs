@@ -0,0 +1,308 @@
+//! k近邻图
+//!
+//! 为每个未删除的向量计算k个最近邻，得到邻接表，用于聚类、UMAP式的可视化，
+//! 也可以作为分层图索引的底层。
+//!
+//! 每个向量以自己存储的1位码和修正项作为1位查询，与序号更大的向量批量评分；
+//! 中心项取质心模长的平方（而不是查询与质心的点积），
+//! 1位码对1位码的估计对两个向量完全对称，每对向量只评分一次，
+//! 同一个分数同时提供给两端的前k个。
+//!
+//! 在图上按相似度阈值做并查集合并，得到每个序号的簇标签，
+//! 可以直接在设备上对嵌入做主题分组
+
+use crate::error_codes::BbqError;
+use crate::batch_sizing::recommended_batch_size;
+use crate::binary_quantized_scorer::BinaryQuantizedScorer;
+use crate::index_generation::IndexGeneration;
+use crate::original_vectors::reconstruct_vector;
+use crate::query_context::QueryContext;
+use crate::vector_similarity::descending_score_order;
+
+/// k近邻图
+#[derive(Debug, Clone, PartialEq)]
+pub struct KnnGraph {
+    /// 每个向量保留的近邻数量
+    pub k: usize,
+    /// 序号 → 按分数降序排列的（近邻序号, 分数）；已删除的向量没有近邻，也不作为近邻出现
+    pub neighbors: Vec<Vec<(usize, f32)>>,
+    /// 序号是否参与了构建（未删除）
+    pub live: Vec<bool>,
+}
+
+/// 聚类结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clustering {
+    /// 序号 → 簇标签；标签按簇中最小序号的顺序从0编号，已删除的序号为None
+    pub labels: Vec<Option<usize>>,
+    /// 每个簇的大小，按标签索引
+    pub sizes: Vec<usize>,
+}
+
+impl Clustering {
+    /// 簇的数量
+    pub fn cluster_count(&self) -> usize {
+        self.sizes.len()
+    }
+}
+
+/// 并查集，按大小合并并在查找时折半压缩路径
+struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(count: usize) -> Self {
+        Self { parent: (0..count).collect(), size: vec![1; count] }
+    }
+
+    fn find(&mut self, mut node: usize) -> usize {
+        while self.parent[node] != node {
+            self.parent[node] = self.parent[self.parent[node]];
+            node = self.parent[node];
+        }
+        node
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        let (large, small) = if self.size[a] >= self.size[b] { (a, b) } else { (b, a) };
+        self.parent[small] = large;
+        self.size[large] += self.size[small];
+    }
+}
+
+impl KnnGraph {
+    /// 节点数量（含已删除的序号）
+    pub fn len(&self) -> usize {
+        self.neighbors.len()
+    }
+
+    /// 是否没有节点
+    pub fn is_empty(&self) -> bool {
+        self.neighbors.is_empty()
+    }
+
+    /// 边的总数（有向）
+    pub fn edge_count(&self) -> usize {
+        self.neighbors.iter().map(Vec::len).sum()
+    }
+
+    /// 连通分量聚类：分数不低于阈值的边把两端合并到同一个簇
+    ///
+    /// 边按无向处理，只要任一端的前k个中包含对方且分数达到阈值就合并；
+    /// 没有达到阈值的边的向量单独成簇
+    ///
+    /// # 参数
+    /// * `threshold` - 相似度阈值，与图中分数的尺度相同
+    pub fn connected_components(&self, threshold: f32) -> Clustering {
+        let mut sets = UnionFind::new(self.len());
+        for (ord, list) in self.neighbors.iter().enumerate() {
+            for &(other, score) in list {
+                if score >= threshold {
+                    sets.union(ord, other);
+                }
+            }
+        }
+
+        let mut root_labels = vec![None; self.len()];
+        let mut sizes = Vec::new();
+        let labels = (0..self.len())
+            .map(|ord| {
+                if !self.live[ord] {
+                    return None;
+                }
+                let root = sets.find(ord);
+                let label = *root_labels[root].get_or_insert_with(|| {
+                    sizes.push(0);
+                    sizes.len() - 1
+                });
+                sizes[label] += 1;
+                Some(label)
+            })
+            .collect();
+        Clustering { labels, sizes }
+    }
+}
+
+/// 保留前k个的有序列表，k通常很小，直接插入排序
+struct TopList {
+    k: usize,
+    items: Vec<(usize, f32)>,
+}
+
+impl TopList {
+    fn new(k: usize) -> Self {
+        Self { k, items: Vec::with_capacity(k + 1) }
+    }
+
+    fn offer(&mut self, ord: usize, score: f32) {
+        if self.items.len() == self.k && self.items.last().is_some_and(|&(_, lowest)| score <= lowest) {
+            return;
+        }
+        let position = self.items.partition_point(|&(_, existing)| descending_score_order(existing, score).is_le());
+        self.items.insert(position, (ord, score));
+        self.items.truncate(self.k);
+    }
+}
+
+/// 每次一起评分的查询向量数，同一批目标读取一次即与整块查询计算
+const QUERY_BLOCK: usize = 16;
+
+/// 以向量自己的1位码和修正项构建1位查询上下文
+fn self_query(generation: &IndexGeneration, ord: usize, centroid_dp: f32) -> Result<QueryContext, BbqError> {
+    let values = generation.values();
+    let corrections = values.try_get_corrective_terms(ord)?.clone();
+    let vector = match generation.original_vector(ord) {
+        Some(vector) => vector.into_owned(),
+        None => reconstruct_vector(values, 1, ord)?,
+    };
+    QueryContext::new(
+        vector,
+        values.try_get_unpacked_vector(ord)?.to_vec(),
+        corrections,
+        centroid_dp,
+        1,
+    )
+}
+
+/// 在一代上构建k近邻图（要求1位索引）
+///
+/// # 参数
+/// * `generation` - 索引的一代
+/// * `scorer` - 索引的评分器
+/// * `k` - 每个向量的近邻数量
+pub(crate) fn build_knn_graph(
+    generation: &IndexGeneration,
+    scorer: &BinaryQuantizedScorer,
+    k: usize,
+) -> Result<KnnGraph, BbqError> {
+    if k == 0 {
+        return Err(BbqError::invalid_argument("近邻数量必须大于0", "neighbour count must be greater than 0"));
+    }
+    let values = generation.values();
+    let size = generation.size();
+    let live: Vec<usize> = (0..size).filter(|&ord| !generation.is_deleted(ord)).collect();
+    let batch_size = recommended_batch_size(values.dimension(), k, None);
+    let mut lists: Vec<TopList> = (0..size).map(|_| TopList::new(k)).collect();
+    let centroid_dp = values.get_centroid_dp(None);
+
+    for (block_index, block) in live.chunks(QUERY_BLOCK).enumerate() {
+        let start = block_index * QUERY_BLOCK;
+        let contexts = block.iter()
+            .map(|&ord| self_query(generation, ord, centroid_dp))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // 块内第i个查询只与位置在它之后的向量配对，块内靠前的目标对它跳过
+        let targets = &live[start + 1..];
+        for (batch_index, batch) in targets.chunks(batch_size).enumerate() {
+            let offset = batch_index * batch_size;
+            let scores = scorer.compute_block_scores_with_contexts(&contexts, values, batch)?;
+            for (i, (&ord, scores)) in block.iter().zip(scores).enumerate() {
+                let skip = i.saturating_sub(offset);
+                for (&other, score) in batch.iter().zip(scores).skip(skip) {
+                    lists[ord].offer(other, score);
+                    lists[other].offer(ord, score);
+                }
+            }
+        }
+    }
+
+    let mut is_live = vec![false; size];
+    for &ord in &live {
+        is_live[ord] = true;
+    }
+    Ok(KnnGraph {
+        k,
+        neighbors: lists.into_iter().map(|list| list.items).collect(),
+        live: is_live,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quantized_index::{QuantizedIndex, QuantizedIndexConfig};
+    use crate::vector_utils::generate_gaussian_mixture;
+
+    #[test]
+    fn test_top_list() {
+        let mut list = TopList::new(3);
+        for (ord, score) in [(0, 0.5), (1, 0.9), (2, 0.1), (3, 0.7), (4, 0.6), (5, f32::NAN)] {
+            list.offer(ord, score);
+        }
+        assert_eq!(list.items, vec![(1, 0.9), (3, 0.7), (4, 0.6)]);
+    }
+
+    #[test]
+    fn test_knn_graph_matches_brute_force() {
+        let vectors = generate_gaussian_mixture(120, 32, 4, 0.3, 11).unwrap();
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        index.build_index(&vectors).unwrap();
+        index.delete(5).unwrap();
+        let graph = index.build_knn_graph(6).unwrap();
+
+        assert_eq!(graph.len(), 120);
+        assert!(graph.neighbors[5].is_empty());
+        assert_eq!(graph.edge_count(), 119 * 6);
+        // 逐个向量暴力计算的1位分数与图中的近邻一致
+        let generation = index.snapshot().unwrap();
+        let values = generation.values();
+        for ord in [0, 42, 119] {
+            let list = &graph.neighbors[ord];
+            assert!(list.iter().all(|&(other, _)| other != ord && other != 5));
+            assert!(list.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+            for &(other, score) in list {
+                let back = graph.neighbors[other].iter().find(|&&(o, _)| o == ord);
+                // 对称：若ord也在other的前k个中，两端分数相同
+                assert!(back.is_none_or(|&(_, s)| s == score));
+                let context = QueryContext::new(
+                    reconstruct_vector(values, 1, other).unwrap(),
+                    values.get_unpacked_vector(other).to_vec(),
+                    values.get_corrective_terms(other).clone(),
+                    values.get_centroid_dp(None),
+                    1,
+                ).unwrap();
+                let reverse = index.get_scorer().compute_batch_scores_with_context(&context, values, &[ord]).unwrap();
+                assert!((reverse[0] - score).abs() <= 1e-5 * score.abs().max(1.0));
+            }
+            let context = QueryContext::new(
+                reconstruct_vector(values, 1, ord).unwrap(),
+                values.get_unpacked_vector(ord).to_vec(),
+                values.get_corrective_terms(ord).clone(),
+                values.get_centroid_dp(None),
+                1,
+            ).unwrap();
+            let others: Vec<usize> = (0..120).filter(|&o| o != ord && o != 5).collect();
+            let mut scored: Vec<f32> = index.get_scorer()
+                .compute_batch_scores_with_context(&context, values, &others).unwrap();
+            scored.sort_by(|a, b| descending_score_order(*a, *b));
+            assert_eq!(list.iter().map(|&(_, s)| s).collect::<Vec<_>>(), scored[..6].to_vec());
+        }
+    }
+
+    #[test]
+    fn test_connected_components() {
+        let graph = KnnGraph {
+            k: 2,
+            neighbors: vec![
+                vec![(1, 0.9), (2, 0.2)],
+                vec![(0, 0.9), (2, 0.3)],
+                vec![(3, 0.8), (1, 0.3)],
+                vec![(2, 0.8), (1, 0.1)],
+                vec![],
+                vec![(0, 0.1)],
+            ],
+            live: vec![true, true, true, true, false, true],
+        };
+        let clustering = graph.connected_components(0.5);
+        assert_eq!(clustering.labels, vec![Some(0), Some(0), Some(1), Some(1), None, Some(2)]);
+        assert_eq!(clustering.sizes, vec![2, 2, 1]);
+        // 阈值足够低时所有未删除的向量连成一个簇
+        assert_eq!(graph.connected_components(0.0).cluster_count(), 1);
+    }
+}
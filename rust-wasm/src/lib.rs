@@ -1,23 +1,122 @@
-/// Better Binary Quantization - Rust WebAssembly实现
-/// 
-/// 基于Lucene的二值量化算法，提供优化的向量量化和搜索功能
-/// 通过Rust的精确内存控制实现更好的内存压缩效果
+//! Better Binary Quantization - Rust WebAssembly实现
+//! 
+//! 基于Lucene的二值量化算法，提供优化的向量量化和搜索功能
+//! 通过Rust的精确内存控制实现更好的内存压缩效果
 
-// 模块声明
+// 模块声明（可选模块见Cargo.toml中的特性）
 pub mod constants;
 pub mod vector_similarity;
+pub mod rng;
 pub mod vector_utils;
+pub mod float;
 pub mod bitwise_dot_product;
 pub mod batch_dot_product;
+pub mod kernel_dispatch;
+pub mod kernels;
+pub mod runtime_init;
+pub mod batch_sizing;
+pub mod capabilities;
 pub mod optimized_scalar_quantizer;
+#[cfg(feature = "lucene_parity")]
+pub mod lucene_parity;
+#[cfg(feature = "scorer")]
+pub mod byte_reader;
+#[cfg(feature = "scorer")]
+pub mod correction_layout;
+#[cfg(feature = "scorer")]
+pub mod quantized_vector_values;
+#[cfg(feature = "scorer")]
 pub mod binary_quantized_scorer;
+#[cfg(feature = "scorer")]
+pub mod query_context;
+#[cfg(feature = "scorer")]
+pub mod edge_scorer;
+#[cfg(feature = "scorer")]
+pub mod ann_adapter;
+#[cfg(feature = "eval")]
+pub mod evaluation;
+#[cfg(feature = "index")]
+pub mod result_cache;
+pub mod timer;
+pub mod error_reporting;
+pub mod error_codes;
+pub mod progress;
+pub mod memory_limits;
+#[cfg(feature = "index")]
+pub mod warmup;
+#[cfg(feature = "index")]
+pub mod warm_stats;
+#[cfg(feature = "index")]
+pub mod ordinal_remap;
+#[cfg(feature = "paranoid")]
+pub mod consistency;
+pub mod validation;
+pub mod filter;
+pub mod filter_expression;
+#[cfg(feature = "ivf")]
+pub mod ivf;
+#[cfg(feature = "graph")]
+pub mod knn_graph;
+#[cfg(feature = "graph")]
+pub mod projection;
+pub mod dimension_stats;
+pub mod half_precision;
+#[cfg(feature = "index")]
+pub mod original_vectors;
+#[cfg(feature = "index")]
+pub mod index_generation;
+#[cfg(feature = "index")]
+pub mod index_serialization;
+#[cfg(feature = "index")]
+pub mod search_layout;
+#[cfg(feature = "index")]
+pub mod search_experiment;
+#[cfg(feature = "index")]
+pub mod search_scratch;
+#[cfg(feature = "index")]
+pub mod promoted_vectors;
+#[cfg(feature = "index")]
 pub mod quantized_index;
-#[cfg(test)]
+#[cfg(feature = "index")]
+pub mod query_pack;
+#[cfg(feature = "index")]
+pub mod es_segment;
+#[cfg(feature = "index")]
+pub mod incremental_search;
+#[cfg(feature = "index")]
+pub mod search_streams;
+#[cfg(feature = "index")]
+pub mod sharded_index;
+#[cfg(feature = "index")]
+pub mod migration;
+#[cfg(feature = "index")]
+pub mod score_normalization;
+#[cfg(feature = "index")]
+pub mod score_transform;
+#[cfg(feature = "index")]
+pub mod score_histogram;
+#[cfg(feature = "index")]
+pub mod result_encoding;
+#[cfg(feature = "index")]
+pub mod score_fusion;
+#[cfg(feature = "index")]
+pub mod chunk_grouping;
+#[cfg(feature = "index")]
+pub mod bbq;
+#[cfg(feature = "index")]
+pub mod embedding_cache;
+#[cfg(feature = "index")]
+pub mod replica;
+#[cfg(all(test, feature = "index"))]
 pub mod quantized_index_test;
+#[cfg(feature = "ivf")]
+#[doc(hidden)]
+pub mod fuzzing;
 pub mod wasm_interface;
 
 // 重新导出主要类型和函数
 pub use constants::*;
+pub use float::Float;
 pub use vector_similarity::{
     SimilarityFunction,
     compute_euclidean_distance,
@@ -25,50 +124,234 @@ pub use vector_similarity::{
     compute_cosine_similarity,
     compute_maximum_inner_product,
     compute_similarity,
+    compute_batch_dot_products,
+    compute_batch_squared_distances,
+    fast_dot_product,
+    fast_squared_distance,
 };
+pub use rng::{set_default_rng, FnRng, RngSource};
 pub use vector_utils::{
     compute_vector_magnitude,
     create_random_vector,
+    create_random_vector_with,
     create_zero_vector,
+    generate_gaussian_mixture,
+    generate_gaussian_mixture_with,
+    generate_heavy_tailed,
+    generate_heavy_tailed_with,
+    generate_unit_sphere,
+    generate_unit_sphere_with,
     normalize_vector,
     compute_dot_product,
+    compute_dot_product_compensated,
+    compute_centroid_compensated,
+    compute_dimension_statistics,
+    CompensatedSum,
+    DimensionStatistics,
 };
 pub use bitwise_dot_product::{
     compute_quantized_dot_product,
     compute_int4_bit_dot_product,
     compute_int1_bit_dot_product,
     compute_packed_bit_dot_product,
+    compute_packed_hamming_distance,
 };
 pub use batch_dot_product::{
     compute_batch_four_bit_dot_product_direct_packed,
     compute_batch_one_bit_dot_product_direct_packed,
     create_direct_packed_buffer,
 };
+pub use capabilities::{
+    Capabilities,
+    capabilities,
+};
+pub use batch_sizing::{
+    BatchSizeTiming,
+    benchmark_batch_sizes,
+    cache_size_hint,
+    recommended_batch_size,
+    set_cache_size_hint,
+};
+pub use kernel_dispatch::{
+    KernelSelection,
+    KernelVariant,
+    calibrate_kernels,
+    selected_kernels,
+};
+pub use error_reporting::{
+    ErrorKind,
+    ErrorReport,
+    ErrorSink,
+    OperationScope,
+    config_fingerprint,
+    install_panic_hook,
+    report_error,
+    set_error_sink,
+};
+pub use error_codes::{
+    BbqError,
+    ErrorCode,
+    ErrorLocale,
+    error_locale,
+    set_error_locale,
+};
+pub use runtime_init::{
+    RuntimeInitReport,
+    initialize_runtime,
+    is_runtime_initialized,
+};
 pub use optimized_scalar_quantizer::{
+    CorrectionPrecision,
     OptimizedScalarQuantizer,
     QuantizationResult,
+    QuantizationScratch,
 };
+#[cfg(feature = "scorer")]
 pub use binary_quantized_scorer::{
+    benchmark_scoring_precision,
     BinaryQuantizedScorer,
     QuantizedScoreResult,
+    ScoringPrecision,
+    ScoringPrecisionBenchmark,
 };
+#[cfg(feature = "scorer")]
+pub use correction_layout::{
+    benchmark_correction_layouts,
+    CorrectionLayout,
+    CorrectionLayoutTiming,
+    CorrectionStore,
+};
+#[cfg(feature = "scorer")]
+pub use quantized_vector_values::{QuantizedVectorValues, QuantizedVectorValuesImpl};
+#[cfg(feature = "scorer")]
+pub use query_context::{quantization_fingerprint, QueryContext};
+#[cfg(feature = "scorer")]
+pub use edge_scorer::EdgeScorer;
+#[cfg(feature = "index")]
+pub use index_generation::IndexGeneration;
+#[cfg(feature = "index")]
+pub use search_layout::SearchLayout;
+#[cfg(feature = "index")]
+pub use search_scratch::SearchScratch;
+#[cfg(feature = "index")]
+pub use promoted_vectors::{PromotedVector, PromotedVectors};
+#[cfg(feature = "index")]
+pub use search_experiment::{ExperimentLayout, SearchExperiment, SearchExperimentStats};
+#[cfg(feature = "index")]
 pub use quantized_index::{
+    BatchReport,
+    BudgetedSearchResults,
+    CompactionReport,
+    DegenerateVectorPolicy,
+    HistogramSearchResults,
+    HitDistances,
+    IndexOp,
+    PackedVectorRecord,
     QuantizedIndex,
     QuantizedIndexConfig,
-    QuantizedVectorValues,
-    QuantizedVectorValuesImpl,
     QueryResult,
+    RescoreOversample,
+    SampledSearchResults,
+    SearchParams,
+};
+pub use validation::{validate_vectors, ValidationReport};
+#[cfg(feature = "index")]
+pub use sharded_index::{ConcurrentTopK, ShardHit, ShardedIndex};
+pub use filter::{
+    AttributeValue,
+    Attributes,
+    Filter,
+    OrdinalBitset,
+    Predicate,
+};
+pub use filter_expression::parse_filter_expression;
+#[cfg(feature = "ivf")]
+pub use ivf::{
+    IvfPartition,
+    IvfStatistics,
+    ProbeStrategy,
+};
+#[cfg(feature = "index")]
+pub use query_pack::{
+    QueryPack,
+    QueryPackLayout,
+    ProgressiveSearch,
+    ProgressiveResults,
+    export_query_pack,
+};
+#[cfg(feature = "index")]
+pub use es_segment::{
+    load_lucene_bbq_vectors,
+    lucene_vector_stride,
+    similarity_from_es_name,
+    similarity_from_lucene_ordinal,
+};
+#[cfg(feature = "index")]
+pub use incremental_search::IncrementalSearch;
+#[cfg(feature = "index")]
+pub use search_streams::SearchStreams;
+#[cfg(feature = "index")]
+pub use result_cache::ResultCacheStats;
+#[cfg(feature = "index")]
+pub use warmup::WarmupReport;
+#[cfg(feature = "index")]
+pub use warm_stats::WarmStats;
+#[cfg(feature = "index")]
+pub use ordinal_remap::OrdinalRemap;
+#[cfg(feature = "index")]
+pub use score_normalization::{
+    ScoreNormalization,
+    normalize_scores,
+};
+#[cfg(feature = "index")]
+pub use score_transform::{ScoreCurve, ScoreTransform};
+#[cfg(feature = "index")]
+pub use score_histogram::ScoreHistogram;
+#[cfg(feature = "index")]
+pub use result_encoding::{decode_query_results, encode_query_results};
+#[cfg(feature = "index")]
+pub use score_fusion::{
+    FusionStrategy,
+    fuse_with_external_scores,
+};
+#[cfg(feature = "index")]
+pub use chunk_grouping::{
+    group_adjacent_chunks,
+    ChunkGroupingParams,
+    ChunkProvenance,
+    ChunkScoreCombine,
+    GroupedHit,
+};
+#[cfg(feature = "index")]
+pub use bbq::{
+    Bbq,
+    BbqHit,
+    BbqOptions,
+    DeltaSummary,
+    DuplicatePolicy,
+};
+#[cfg(feature = "index")]
+pub use embedding_cache::{text_content_hash, EmbeddingCache, EmbeddingCacheStats};
+#[cfg(feature = "index")]
+pub use replica::{
+    MergeSummary,
+    Replica,
+    VersionVector,
 };
 
 // WASM绑定
 use wasm_bindgen::prelude::*;
 
 /// WASM模块初始化
+///
+/// 实例化时自动执行，只做轻量设置；较重的初始化见 `runtime_init`
 #[wasm_bindgen(start)]
 pub fn init() {
     // 设置panic hook以便在浏览器控制台看到更好的错误信息
     #[cfg(feature = "console_error_panic_hook")]
     console_error_panic_hook::set_once();
+    // 在上面的hook之外包一层，把panic报告给已注册的错误接收器
+    error_reporting::install_panic_hook();
 }
 
 /// 获取版本信息
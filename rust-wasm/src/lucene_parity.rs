@@ -0,0 +1,305 @@
+//! 与Lucene逐位一致的运算（`lucene_parity` 特性）
+//!
+//! 本库的量化和评分在数学上与Lucene的 `OptimizedScalarQuantizer` 和二值量化评分器相同，
+//! 但为了精度和速度调整过运算顺序：均值和方差分两遍累加、初始区间按 `网格 * 标准差 + 均值` 计算、
+//! 1位量化按区间中点二值化、评分减去查询与质心的点积。这些差异使分数与Java端相差几个ULP，
+//! 从Java迁移时无法逐位比对。
+//!
+//! 启用 `lucene_parity` 特性后，单精度量化、质心、质心点积和4位查询评分都改用Lucene 10.2的
+//! 运算顺序和常量（包括MINIMUM_MSE_GRID的f32取值、`(网格 + 均值) * 标准差` 的初始区间、
+//! f64的Welford统计量、`Math.round` 的取整和MIP分数缩放），相同输入得到与Java相同的位模式。
+//! 余弦相似度的归一化不在此列：Lucene的向量点积随JVM是否启用向量化而不同，
+//! 需要逐位比对时应传入已归一化的向量
+
+use crate::error_codes::BbqError;
+use crate::optimized_scalar_quantizer::{QuantizationResult, QuantizedOutput};
+use crate::vector_similarity::SimilarityFunction;
+
+/// Lucene的最小MSE网格（Java中为float，取值与本库的f64网格转为f32后相同）
+const LUCENE_MINIMUM_MSE_GRID: [[f32; 2]; 8] = [
+    [-0.798, 0.798],
+    [-1.493, 1.493],
+    [-2.051, 2.051],
+    [-2.514, 2.514],
+    [-2.916, 2.916],
+    [-3.278, 3.278],
+    [-3.611, 3.611],
+    [-3.922, 3.922],
+];
+
+/// Lucene `DefaultVectorUtilSupport.dotProduct`：超过32维时四路FMA展开，余数顺序累加
+pub fn dot_product(a: &[f32], b: &[f32]) -> f32 {
+    let mut res = 0.0f32;
+    let mut i = 0;
+    if a.len() > 32 {
+        let mut acc = [0.0f32; 4];
+        let upper_bound = a.len() & !3;
+        while i < upper_bound {
+            for (lane, acc) in acc.iter_mut().enumerate() {
+                *acc = a[i + lane].mul_add(b[i + lane], *acc);
+            }
+            i += 4;
+        }
+        res += acc[0] + acc[1] + acc[2] + acc[3];
+    }
+    while i < a.len() {
+        res = a[i].mul_add(b[i], res);
+        i += 1;
+    }
+    res
+}
+
+/// Lucene写入量化向量时的质心：按f32逐个累加后除以数量
+pub fn centroid(vectors: &[Vec<f32>]) -> Result<Vec<f32>, BbqError> {
+    let first = vectors.first().ok_or_else(|| BbqError::empty_input("向量集合不能为空", "vector collection must not be empty"))?;
+    let mut centroid = vec![0.0f32; first.len()];
+    for vector in vectors {
+        if vector.len() != centroid.len() {
+            return Err(BbqError::dimension_mismatch("所有向量必须具有相同维度", "all vectors must have the same dimension"));
+        }
+        for (sum, &value) in centroid.iter_mut().zip(vector) {
+            *sum += value;
+        }
+    }
+    let count = vectors.len() as f32;
+    for value in &mut centroid {
+        *value /= count;
+    }
+    Ok(centroid)
+}
+
+/// Lucene `OptimizedScalarQuantizer.scalarQuantize`
+///
+/// `working` 为与向量等长的临时缓冲区，返回时存放中心化后的向量。
+/// 减去质心后为常量时Lucene会除以0，这里与默认实现一样返回中性修正项
+#[allow(clippy::too_many_arguments)]
+pub fn scalar_quantize(
+    vector: &[f32],
+    destination: &mut [u8],
+    bits: u8,
+    centroid: &[f32],
+    similarity_function: SimilarityFunction,
+    lambda: f32,
+    iters: usize,
+    working: &mut [f32],
+) -> Result<QuantizationResult, BbqError> {
+    scalar_quantize_into(vector, QuantizedOutput::Unpacked(destination), bits, centroid, similarity_function, lambda, iters, working)
+}
+
+/// 同 `scalar_quantize`，1位量化时可以直接按 `pack_as_binary` 的位序写入打包缓冲区
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn scalar_quantize_into(
+    vector: &[f32],
+    output: QuantizedOutput,
+    bits: u8,
+    centroid: &[f32],
+    similarity_function: SimilarityFunction,
+    lambda: f32,
+    iters: usize,
+    working: &mut [f32],
+) -> Result<QuantizationResult, BbqError> {
+    let points = 1i32 << bits;
+    let mut vec_mean = 0.0f64;
+    let mut vec_var = 0.0f64;
+    let mut norm2 = 0.0f32;
+    let mut centroid_dot = 0.0f32;
+    let mut min = f32::MAX;
+    let mut max = -f32::MAX;
+    for i in 0..vector.len() {
+        if similarity_function != SimilarityFunction::Euclidean {
+            centroid_dot += vector[i] * centroid[i];
+        }
+        let value = vector[i] - centroid[i];
+        working[i] = value;
+        min = min.min(value);
+        max = max.max(value);
+        norm2 += value * value;
+        let delta = value as f64 - vec_mean;
+        vec_mean += delta / (i + 1) as f64;
+        vec_var += delta * (value as f64 - vec_mean);
+    }
+    if !(min.is_finite() && max.is_finite()) {
+        return Err(BbqError::invalid_value("向量或质心包含非有限值（NaN或无穷）", "vector or centroid contains non-finite values (NaN or infinity)"));
+    }
+    let additional_correction = if similarity_function == SimilarityFunction::Euclidean { norm2 } else { centroid_dot };
+    if max <= min {
+        match output {
+            QuantizedOutput::Unpacked(destination) => destination.fill(0),
+            QuantizedOutput::Packed(packed) => packed.fill(0),
+        }
+        return Ok(QuantizationResult {
+            lower_interval: min,
+            upper_interval: min,
+            additional_correction,
+            quantized_component_sum: 0.0,
+        });
+    }
+
+    vec_var /= vector.len() as f64;
+    let vec_std = vec_var.sqrt();
+    let grid = LUCENE_MINIMUM_MSE_GRID[bits as usize - 1];
+    let mut interval = [
+        ((grid[0] as f64 + vec_mean) * vec_std).max(min as f64).min(max as f64) as f32,
+        ((grid[1] as f64 + vec_mean) * vec_std).max(min as f64).min(max as f64) as f32,
+    ];
+    optimize_intervals(&mut interval, working, norm2, points, lambda, iters);
+
+    let n_steps = (points - 1) as f32;
+    let [a, b] = interval;
+    let step = (b - a) / n_steps;
+    let assign = |value: f32| ((value.max(a).min(b) - a) / step).round() as u32;
+    let mut sum = 0u32;
+    match output {
+        QuantizedOutput::Unpacked(destination) => {
+            for (slot, &value) in destination.iter_mut().zip(working.iter()) {
+                let assignment = assign(value);
+                sum += assignment;
+                *slot = assignment as u8;
+            }
+        }
+        QuantizedOutput::Packed(packed) => {
+            packed.fill(0);
+            for (byte, chunk) in packed.iter_mut().zip(working.chunks(8)) {
+                *byte = chunk.iter()
+                    .enumerate()
+                    .fold(0u8, |acc, (j, &value)| acc | (assign(value) as u8) << (7 - j));
+            }
+            sum = packed.iter().map(|byte| byte.count_ones()).sum();
+        }
+    }
+    Ok(QuantizationResult {
+        lower_interval: a,
+        upper_interval: b,
+        additional_correction,
+        quantized_component_sum: sum as f32,
+    })
+}
+
+/// Lucene `optimizeIntervals`：统计量以f64累加，区间以f32保存
+fn optimize_intervals(interval: &mut [f32; 2], vector: &[f32], norm2: f32, points: i32, lambda: f32, iters: usize) {
+    let mut initial_loss = loss(vector, *interval, points, norm2, lambda);
+    let scale = (1.0f32 - lambda) / norm2;
+    if !scale.is_finite() {
+        return;
+    }
+    let (scale, lambda_d) = (scale as f64, lambda as f64);
+    for _ in 0..iters {
+        let [a, b] = *interval;
+        let step_inv = (points as f32 - 1.0) / (b - a);
+        let (mut daa, mut dab, mut dbb, mut dax, mut dbx) = (0.0f64, 0.0f64, 0.0f64, 0.0f64, 0.0f64);
+        for &xi in vector {
+            let k = ((xi.max(a).min(b) as f64 - a as f64) * step_inv as f64).round() as f32;
+            let s = (k / (points - 1) as f32) as f64;
+            daa += (1.0 - s) * (1.0 - s);
+            dab += (1.0 - s) * s;
+            dbb += s * s;
+            dax += xi as f64 * (1.0 - s);
+            dbx += xi as f64 * s;
+        }
+        let m0 = scale * dax * dax + lambda_d * daa;
+        let m1 = scale * dax * dbx + lambda_d * dab;
+        let m2 = scale * dbx * dbx + lambda_d * dbb;
+        let det = m0 * m2 - m1 * m1;
+        if det == 0.0 {
+            return;
+        }
+        let a_opt = ((m2 * dax - m1 * dbx) / det) as f32;
+        let b_opt = ((m0 * dbx - m1 * dax) / det) as f32;
+        if ((interval[0] - a_opt).abs() as f64) < 1e-8 && ((interval[1] - b_opt).abs() as f64) < 1e-8 {
+            return;
+        }
+        let new_loss = loss(vector, [a_opt, b_opt], points, norm2, lambda);
+        if new_loss > initial_loss {
+            return;
+        }
+        *interval = [a_opt, b_opt];
+        initial_loss = new_loss;
+    }
+}
+
+/// Lucene `loss`：全部以f64计算
+fn loss(vector: &[f32], interval: [f32; 2], points: i32, norm2: f32, lambda: f32) -> f64 {
+    let (a, b) = (interval[0] as f64, interval[1] as f64);
+    let step = (b - a) / (points as f32 - 1.0) as f64;
+    let step_inv = 1.0 / step;
+    let mut xe = 0.0;
+    let mut e = 0.0;
+    for &xi in vector {
+        let xi = xi as f64;
+        let xiq = a + step * ((xi.max(a).min(b) - a) * step_inv).round();
+        let diff = xi - xiq;
+        e += diff * diff;
+        xe += xi * diff;
+    }
+    let lambda = lambda as f64;
+    (1.0 - lambda) * xe * xe / norm2 as f64 + lambda * e
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary_quantized_scorer::BinaryQuantizedScorer;
+
+    // 期望值按Lucene 10.2的Java源码逐步计算得到（f32运算逐步舍入，double运算保持f64）
+    const DOC: [f32; 10] = [0.12, -0.53, 0.97, 0.05, -0.31, 0.44, -0.88, 0.26, 0.71, -0.09];
+    const QUERY: [f32; 10] = [0.33, -0.12, 0.58, -0.47, 0.21, 0.9, -0.65, 0.02, 0.38, -0.27];
+    const CENTROID: [f32; 10] = [0.05, -0.1, 0.2, 0.0, -0.05, 0.15, -0.2, 0.1, 0.25, 0.0];
+
+    fn quantize(vector: &[f32], bits: u8, similarity_function: SimilarityFunction) -> (Vec<u8>, QuantizationResult) {
+        let mut destination = vec![0u8; vector.len()];
+        let mut working = vec![0.0; vector.len()];
+        let result = scalar_quantize(vector, &mut destination, bits, &CENTROID, similarity_function, 0.1, 5, &mut working).unwrap();
+        (destination, result)
+    }
+
+    fn assert_bits(actual: f32, expected: u32) {
+        assert_eq!(actual.to_bits(), expected, "{} != {}", actual, f32::from_bits(expected));
+    }
+
+    #[test]
+    fn test_quantization_fixture() {
+        for (similarity_function, doc_correction, query_correction) in [
+            (SimilarityFunction::Euclidean, 0x3fd2_8241, 0x3faf_bb2f),
+            (SimilarityFunction::MaximumInnerProduct, 0x3f36_c8b5, 0x3efd_f3b6),
+        ] {
+            let (doc, doc_terms) = quantize(&DOC, 1, similarity_function);
+            assert_eq!(doc, [1, 0, 1, 1, 0, 1, 0, 1, 1, 0]);
+            assert_bits(doc_terms.lower_interval, 0xbf07_fbe7);
+            assert_bits(doc_terms.upper_interval, 0x3edf_890c);
+            assert_bits(doc_terms.additional_correction, doc_correction);
+            assert_eq!(doc_terms.quantized_component_sum, 6.0);
+
+            let (query, query_terms) = quantize(&QUERY, 4, similarity_function);
+            assert_eq!(query, [9, 5, 10, 0, 9, 15, 0, 5, 7, 2]);
+            assert_bits(query_terms.lower_interval, 0xbee7_06cb);
+            assert_bits(query_terms.upper_interval, 0x3f43_9e50);
+            assert_bits(query_terms.additional_correction, query_correction);
+            assert_eq!(query_terms.quantized_component_sum, 62.0);
+        }
+    }
+
+    #[test]
+    fn test_score_fixture() {
+        let centroid_dp = dot_product(&CENTROID, &CENTROID);
+        assert_bits(centroid_dp, 0x3e42_8f5d);
+        for (similarity_function, expected) in [
+            (SimilarityFunction::Euclidean, 0x3ec5_ece6),
+            (SimilarityFunction::MaximumInnerProduct, 0x402f_0fb3),
+        ] {
+            let (doc, doc_terms) = quantize(&DOC, 1, similarity_function);
+            let (query, query_terms) = quantize(&QUERY, 4, similarity_function);
+            let score = BinaryQuantizedScorer::new(similarity_function)
+                .compute_quantized_score(&query, &query_terms, &doc, &doc_terms, 4, 10, centroid_dp, None)
+                .unwrap()
+                .score;
+            assert_bits(score, expected);
+        }
+    }
+
+    #[test]
+    fn test_dot_product_fixture() {
+        let a: Vec<f32> = (0..41).map(|i| ((i as f64 * 0.37) % 1.9 - 0.95) as f32).collect();
+        let b: Vec<f32> = (0..41).map(|i| ((i as f64 * 0.61) % 1.3 - 0.6) as f32).collect();
+        assert_bits(dot_product(&a, &b), 0xbe21_cabd);
+    }
+}
@@ -0,0 +1,224 @@
+//! 内存寻址上限
+//!
+//! wasm32下线性内存最多4GB，`usize`也只有32位；
+//! 所有与向量数量相乘的偏移/长度都通过这里做溢出检查，
+//! 超出上限时返回错误而不是在乘法溢出后越界或中止。
+//! 需要更大的索引时使用memory64（wasm64）构建，见Cargo.toml中的release-memory64配置
+//!
+//! 在寻址范围内，线性内存增长仍可能失败（浏览器限制、设备内存不足）。
+//! 构建缓冲区、导出数据等大块分配通过 `try_alloc_zeroed` / `try_with_capacity` / `reserve_headroom`
+//! 做可失败分配，失败时返回错误码为 `OutOfMemory` 的错误，其中带有包含请求大小和当前堆状态的
+//! `OutOfMemory`（最近一次也可由 `take_last_out_of_memory` 取得），
+//! 而不是让分配器中止整个实例，宿主可以据此降载或分片
+
+use std::cell::RefCell;
+
+use crate::error_codes::{BbqError, Subject};
+
+#[cfg(feature = "index")]
+use crate::original_vectors::OriginalVectorEncoding;
+
+/// wasm32线性内存上限（4GB）
+pub const WASM32_MAX_MEMORY_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+
+/// 当前构建可寻址的最大字节数
+pub fn max_addressable_bytes() -> u64 {
+    if cfg!(all(target_family = "wasm", target_pointer_width = "32")) {
+        WASM32_MAX_MEMORY_BYTES
+    } else {
+        usize::MAX as u64
+    }
+}
+
+/// 检查字节数是否在当前构建的寻址范围内
+///
+/// # 参数
+/// * `bytes` - 需要的字节数
+/// * `what` - 用于错误信息的数据描述
+pub fn ensure_addressable(bytes: u64, what: Subject) -> Result<(), BbqError> {
+    let limit = max_addressable_bytes();
+    if bytes > limit {
+        return Err(BbqError::out_of_range(
+            format!("{}需要 {} 字节，超过当前构建的寻址上限 {} 字节，请使用memory64构建", what.zh, bytes, limit),
+            format!("{} requires {} bytes, beyond the addressable limit of {} bytes for this build; use a memory64 build", what.en, bytes, limit),
+        ));
+    }
+    Ok(())
+}
+
+/// 带溢出检查的区域长度计算：count * stride
+///
+/// # 参数
+/// * `count` - 元素数量
+/// * `stride` - 每个元素的字节数
+/// * `what` - 用于错误信息的数据描述
+pub fn checked_region_len(count: usize, stride: usize, what: Subject) -> Result<usize, BbqError> {
+    let bytes = (count as u64).checked_mul(stride as u64).ok_or_else(|| {
+        BbqError::out_of_range(format!("{}的大小计算溢出", what.zh), format!("size of {} overflows", what.en))
+    })?;
+    ensure_addressable(bytes, what)?;
+    usize_bytes(bytes, what)
+}
+
+/// 字节数转为usize，超出范围时返回错误
+fn usize_bytes(bytes: u64, what: Subject) -> Result<usize, BbqError> {
+    usize::try_from(bytes).map_err(|_| {
+        BbqError::out_of_range(
+            format!("{}需要 {} 字节，超过usize范围", what.zh, bytes),
+            format!("{} requires {} bytes, beyond the usize range", what.en, bytes),
+        )
+    })
+}
+
+/// WASM线性内存页大小（64KB）
+#[cfg(target_arch = "wasm32")]
+const WASM_PAGE_BYTES: u64 = 64 * 1024;
+
+/// 堆状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapStats {
+    /// 当前线性内存大小（字节），非WASM构建中无法获得时为None
+    pub heap_bytes: Option<u64>,
+    /// 当前构建可寻址的最大字节数
+    pub max_bytes: u64,
+}
+
+/// 获取当前堆状态
+pub fn heap_stats() -> HeapStats {
+    #[cfg(target_arch = "wasm32")]
+    let heap_bytes = Some(core::arch::wasm32::memory_size::<0>() as u64 * WASM_PAGE_BYTES);
+    #[cfg(not(target_arch = "wasm32"))]
+    let heap_bytes = None;
+    HeapStats { heap_bytes, max_bytes: max_addressable_bytes() }
+}
+
+/// 结构化的内存不足错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutOfMemory {
+    /// 分配的数据描述
+    pub what: Subject,
+    /// 请求的字节数
+    pub requested_bytes: u64,
+    /// 分配失败时的堆状态
+    pub heap: HeapStats,
+}
+
+thread_local! {
+    static LAST_OUT_OF_MEMORY: RefCell<Option<OutOfMemory>> = const { RefCell::new(None) };
+}
+
+/// 记录一次分配失败并返回错误
+fn out_of_memory(what: Subject, requested_bytes: u64) -> BbqError {
+    let details = OutOfMemory { what, requested_bytes, heap: heap_stats() };
+    LAST_OUT_OF_MEMORY.with(|last| *last.borrow_mut() = Some(details.clone()));
+    BbqError::out_of_memory(details)
+}
+
+/// 取出当前线程最近一次分配失败的详情
+pub fn take_last_out_of_memory() -> Option<OutOfMemory> {
+    LAST_OUT_OF_MEMORY.with(|last| last.borrow_mut().take())
+}
+
+/// 可失败地分配容量为 `capacity` 的空Vec
+///
+/// # 参数
+/// * `capacity` - 元素数量
+/// * `what` - 用于错误信息的数据描述
+pub fn try_with_capacity<T>(capacity: usize, what: Subject) -> Result<Vec<T>, BbqError> {
+    let bytes = checked_region_len(capacity, std::mem::size_of::<T>(), what)?;
+    let mut vec = Vec::new();
+    vec.try_reserve_exact(capacity).map_err(|_| out_of_memory(what, bytes as u64))?;
+    Ok(vec)
+}
+
+/// 可失败地分配 `len` 个零字节
+pub fn try_alloc_zeroed(len: usize, what: Subject) -> Result<Vec<u8>, BbqError> {
+    let mut vec = try_with_capacity(len, what)?;
+    vec.resize(len, 0);
+    Ok(vec)
+}
+
+/// 确认堆中还能容纳 `bytes` 字节
+///
+/// 由许多小分配组成的大结构（如构建时逐个向量的缓冲区）无法整体可失败地分配，
+/// 先试分配同样大小的一块再立即释放：WASM线性内存增长后不会收缩，
+/// 之后的小分配从已增长的内存中取得，不会在中途失败
+pub fn reserve_headroom(bytes: u64, what: Subject) -> Result<(), BbqError> {
+    ensure_addressable(bytes, what)?;
+    let len = usize_bytes(bytes, what)?;
+    drop(try_with_capacity::<u8>(len, what)?);
+    Ok(())
+}
+
+/// 估算索引占用的字节数（按u64计算，不会溢出）
+///
+/// # 参数
+/// * `count` - 向量数量
+/// * `dimension` - 向量维度
+/// * `index_bits` - 索引向量位数
+/// * `original_encoding` - 保留原始向量时的存储编码，不保留时为None
+#[cfg(feature = "index")]
+pub fn estimate_index_bytes(
+    count: usize,
+    dimension: usize,
+    index_bits: u8,
+    original_encoding: Option<OriginalVectorEncoding>,
+) -> u64 {
+    let originals = original_encoding.map_or(0, |encoding| encoding.bytes_per_vector(dimension));
+    let count = count as u64;
+    let dimension = dimension as u64;
+    let packed = if index_bits == 1 { dimension.div_ceil(8) } else { dimension };
+    // 打包向量 + 未打包向量 + 4个f32修正项
+    let per_vector = packed.saturating_add(dimension).saturating_add(16).saturating_add(originals);
+    count.saturating_mul(per_vector)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_codes::ErrorCode;
+
+    const TEST: Subject = Subject::new("测试", "test");
+
+    #[test]
+    fn test_checked_region_len() {
+        assert_eq!(checked_region_len(1000, 96, TEST).unwrap(), 96_000);
+        assert!(checked_region_len(usize::MAX, 2, TEST).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "index")]
+    fn test_estimate_index_bytes() {
+        // 8维1位：1字节打包 + 8字节未打包 + 16字节修正项
+        assert_eq!(estimate_index_bytes(10, 8, 1, None), 250);
+        assert_eq!(estimate_index_bytes(10, 8, 1, Some(OriginalVectorEncoding::F32)), 250 + 320);
+        assert_eq!(estimate_index_bytes(10, 8, 1, Some(OriginalVectorEncoding::F16)), 250 + 160);
+        assert_eq!(estimate_index_bytes(10, 8, 1, Some(OriginalVectorEncoding::Int8Residual)), 250 + 120);
+        assert_eq!(estimate_index_bytes(usize::MAX, usize::MAX, 1, Some(OriginalVectorEncoding::F32)), u64::MAX);
+    }
+
+    #[test]
+    fn test_fallible_allocation() {
+        assert_eq!(try_alloc_zeroed(64, TEST).unwrap(), vec![0u8; 64]);
+        assert!(try_with_capacity::<u32>(16, TEST).unwrap().capacity() >= 16);
+        assert!(reserve_headroom(1 << 20, TEST).is_ok());
+        assert!(take_last_out_of_memory().is_none());
+
+        // 寻址范围内但无法满足的请求返回结构化的内存不足错误
+        let what = Subject::new("超大缓冲区", "huge buffer");
+        let error = try_with_capacity::<u8>(isize::MAX as usize, what).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::OutOfMemory);
+        let details = error.out_of_memory_details().unwrap();
+        assert_eq!((details.what, details.requested_bytes), (what, isize::MAX as u64));
+        assert_eq!(details.heap, heap_stats());
+        assert!(error.english().starts_with(&format!("out of memory: huge buffer requires {} bytes", isize::MAX)));
+        assert_eq!(take_last_out_of_memory().as_ref(), Some(details));
+        assert!(take_last_out_of_memory().is_none());
+    }
+
+    #[test]
+    fn test_ensure_addressable() {
+        assert!(ensure_addressable(1024, TEST).is_ok());
+        assert!(ensure_addressable(u64::MAX, TEST).is_err() || max_addressable_bytes() == u64::MAX);
+    }
+}
@@ -0,0 +1,124 @@
+//! 索引迁移
+//!
+//! 把已序列化的索引按新的配置（位数、相似性函数、预处理参数）重新量化，
+//! 用户不需要保留原始嵌入即可升级精度设置。
+//! BBQ快照保存了原始向量，直接用原始向量重建；查询包只有1位码，
+//! 用码和修正项重建近似向量后再量化，精度受原来的1位量化限制
+
+use crate::error_codes::BbqError;
+use crate::bbq::{Bbq, BBQ_MAGIC};
+use crate::quantized_index::{QuantizedIndex, QuantizedIndexConfig};
+use crate::query_pack::{QueryPack, QUERY_PACK_MAGIC};
+
+/// 迁移时向量的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationSource {
+    /// 快照中保存的原始向量
+    Originals,
+    /// 由量化码和修正项重建的近似向量
+    Reconstructed,
+}
+
+/// 迁移报告
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationReport {
+    /// 向量来源
+    pub source: MigrationSource,
+    /// 迁移的向量数量
+    pub vectors: usize,
+    /// 向量维度
+    pub dimension: usize,
+    /// 新序号 → 原索引序号（查询包可能只包含部分序号）
+    pub original_ordinals: Vec<usize>,
+}
+
+/// 按新配置重新量化已序列化的索引
+///
+/// # 参数
+/// * `index_bytes` - BBQ快照（`Bbq::save`）或查询包（`export_query_pack`）
+/// * `new_config` - 新索引的配置
+///
+/// # 返回
+/// (新索引, 迁移报告)
+///
+/// 查询包中余弦索引的向量已归一化，原始模长不可恢复；
+/// 改为点积或欧氏距离时使用的是归一化后的方向
+pub fn migrate(
+    index_bytes: &[u8],
+    new_config: QuantizedIndexConfig,
+) -> Result<(QuantizedIndex, MigrationReport), BbqError> {
+    let (source, vectors, original_ordinals) = match index_bytes.get(..4) {
+        Some(magic) if magic == BBQ_MAGIC => {
+            let bbq = Bbq::load(index_bytes)?;
+            let vectors = bbq.vectors().to_vec();
+            let ordinals = (0..vectors.len()).collect();
+            (MigrationSource::Originals, vectors, ordinals)
+        }
+        Some(magic) if magic == QUERY_PACK_MAGIC => {
+            let pack = QueryPack::load(index_bytes)?;
+            let (ordinals, vectors) = (0..pack.len())
+                .map(|position| pack.reconstruct(position))
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .unzip();
+            (MigrationSource::Reconstructed, vectors, ordinals)
+        }
+        _ => return Err(BbqError::unsupported_version("无法识别的索引格式：需要BBQ快照或查询包", "unrecognised index format: expected a BBQ snapshot or query pack")),
+    };
+    if vectors.is_empty() {
+        return Err(BbqError::empty_input("索引中没有可迁移的向量", "the index has no vectors to migrate"));
+    }
+
+    let dimension = vectors[0].len();
+    let mut index = QuantizedIndex::new(new_config)?;
+    index.build_index(&vectors)?;
+    Ok((index, MigrationReport {
+        source,
+        vectors: vectors.len(),
+        dimension,
+        original_ordinals,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query_pack::export_query_pack;
+    use crate::vector_similarity::SimilarityFunction;
+    use crate::vector_utils::create_random_vector;
+
+    #[test]
+    fn test_migrate_snapshot_and_pack() {
+        let vectors: Vec<Vec<f32>> = (0..200).map(|_| create_random_vector(32, -1.0, 1.0)).collect();
+        let mut bbq = Bbq::new(32, SimilarityFunction::Cosine).unwrap();
+        for (i, vector) in vectors.iter().enumerate() {
+            bbq.add(&format!("v{}", i), vector).unwrap();
+        }
+        let euclidean = QuantizedIndexConfig {
+            similarity_function: SimilarityFunction::Euclidean,
+            lambda: Some(0.5),
+            ..QuantizedIndexConfig::default()
+        };
+
+        let (index, report) = migrate(&bbq.save().unwrap(), euclidean.clone()).unwrap();
+        assert_eq!(report.source, MigrationSource::Originals);
+        assert_eq!((report.vectors, report.dimension), (200, 32));
+        assert_eq!(index.get_config().similarity_function, SimilarityFunction::Euclidean);
+        assert_eq!(index.search_nearest_neighbors(&vectors[5], 1).unwrap()[0].index, 5);
+
+        // 从查询包迁移：重建的向量仍应让每个向量找回自己
+        let mut one_bit = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        one_bit.build_index(&vectors).unwrap();
+        one_bit.delete(0).unwrap();
+        let pack = export_query_pack(&one_bit, None).unwrap();
+        let (index, report) = migrate(&pack, euclidean).unwrap();
+        assert_eq!(report.source, MigrationSource::Reconstructed);
+        assert_eq!(report.vectors, 199);
+        assert_eq!(report.original_ordinals[0], 1);
+        let (_, reconstructed) = QueryPack::load(&pack).unwrap().reconstruct(4).unwrap();
+        let hit = index.search_nearest_neighbors(&reconstructed, 1).unwrap()[0].index;
+        assert_eq!(report.original_ordinals[hit], 5);
+
+        assert!(migrate(b"nope", QuantizedIndexConfig::default()).is_err());
+    }
+}
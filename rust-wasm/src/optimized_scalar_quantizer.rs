@@ -1,12 +1,38 @@
-/// 优化的标量量化器
-/// 对应TypeScript中的optimizedScalarQuantizer.ts
-/// 
-/// 基于Lucene的二值量化实现
-/// 实现了各向异性损失函数和坐标下降优化算法
+//! 优化的标量量化器
+//! 对应TypeScript中的optimizedScalarQuantizer.ts
+//! 
+//! 基于Lucene的二值量化实现
+//! 实现了各向异性损失函数和坐标下降优化算法
 
-use crate::constants::{DEFAULT_LAMBDA, DEFAULT_ITERS, MINIMUM_MSE_GRID, NUMERICAL_CONSTANTS};
+use std::cell::RefCell;
+
+use crate::error_codes::BbqError;
+use crate::constants::{DEFAULT_LAMBDA, DEFAULT_ITERS, LUCENE_DIMENSION_BUCKET, MINIMUM_MSE_GRID, NUMERICAL_CONSTANTS};
+use crate::float::Float;
 use crate::vector_similarity::SimilarityFunction;
-use crate::vector_utils::compute_dot_product;
+
+/// 每个字节只保留最低位的掩码
+const BINARY_LANE_MASK: u64 = 0x0101_0101_0101_0101;
+
+/// 把8个字节的最低位收集到最高字节的乘数，见 `OptimizedScalarQuantizer::pack_lanes`
+const PACK_LANES_MULTIPLIER: u64 = 0x8040_2010_0804_0201;
+
+/// 解包查找表：打包字节 → 8个0/1分量（小端u64，第0个分量对应最高位）
+const UNPACK_TABLE: [u64; 256] = build_unpack_table();
+
+const fn build_unpack_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut lane = 0;
+        while lane < 8 {
+            table[byte] |= (((byte >> (7 - lane)) & 1) as u64) << (lane * 8);
+            lane += 1;
+        }
+        byte += 1;
+    }
+    table
+}
 
 /// 量化结果结构体
 #[derive(Debug, Clone)]
@@ -17,11 +43,88 @@ pub struct QuantizationResult {
     pub quantized_component_sum: f32,
 }
 
+impl QuantizationResult {
+    /// 是否为退化向量（减去质心后各分量相同，包括全零）
+    ///
+    /// 退化向量的区间收缩为一点 `[c, c]`，量化值全为0，
+    /// 由修正项即可精确重建，评分公式不需要特殊处理
+    pub fn is_degenerate(&self) -> bool {
+        self.lower_interval == self.upper_interval
+    }
+}
+
+/// 修正项与评分的累加精度
+///
+/// 评分公式把 `dimension * 区间` 量级的大项和较小的修正项相加，
+/// 高维（1536维以上）且数据偏离原点较远时f32会损失有效位；
+/// 双精度模式下修正项的统计量和评分公式都以f64累加，结果仍以f32存储
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CorrectionPrecision {
+    /// f32累加（默认）
+    #[default]
+    Single,
+    /// f64累加
+    Double,
+}
+
+/// 量化临时缓冲区
+///
+/// 每次量化都需要一个与向量等长的中心化工作向量。构建时逐个量化大量向量，
+/// 把工作向量放在可复用的缓冲区里可以避免每个向量一次短命的堆分配（WASM中malloc相对较慢）
+#[derive(Debug, Default)]
+pub struct QuantizationScratch {
+    working: Vec<f32>,
+}
+
+impl QuantizationScratch {
+    /// 创建空缓冲区，首次使用时按维度分配
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 按维度预先分配缓冲区
+    pub fn with_dimension(dimension: usize) -> Self {
+        Self { working: vec![0.0; dimension] }
+    }
+
+    /// 取得长度为len的工作向量，内容未定义，由调用方全部覆盖
+    fn working(&mut self, len: usize) -> &mut [f32] {
+        if self.working.len() != len {
+            self.working.resize(len, 0.0);
+        }
+        &mut self.working
+    }
+}
+
+/// 按区间中点把中心化后的向量二值化，每8维组成一个字节（高位在前）写入 `packed`
+fn pack_signs(working_vector: &[f32], (a, b): (f32, f32), packed: &mut [u8]) {
+    let threshold = (a + b) / 2.0;
+    for (byte, chunk) in packed.iter_mut().zip(working_vector.chunks(8)) {
+        *byte = chunk.iter()
+            .enumerate()
+            .fold(0u8, |acc, (j, &xi)| acc | ((xi.clamp(a, b) >= threshold) as u8) << (7 - j));
+    }
+}
+
+thread_local! {
+    /// 未显式传入缓冲区时使用的线程内缓冲区
+    static THREAD_SCRATCH: RefCell<QuantizationScratch> = RefCell::new(QuantizationScratch::new());
+}
+
+/// 量化结果的写入位置
+pub(crate) enum QuantizedOutput<'a> {
+    /// 每维一个量化值
+    Unpacked(&'a mut [u8]),
+    /// 1位量化直接按 `pack_as_binary` 的位序写入打包缓冲区，多出的字节补0
+    Packed(&'a mut [u8]),
+}
+
 /// 优化的标量量化器结构体
 pub struct OptimizedScalarQuantizer {
     lambda: f32,
     iters: usize,
     similarity_function: SimilarityFunction,
+    correction_precision: CorrectionPrecision,
 }
 
 impl OptimizedScalarQuantizer {
@@ -32,12 +135,19 @@ impl OptimizedScalarQuantizer {
         similarity_function: Option<SimilarityFunction>,
     ) -> Self {
         Self {
-            lambda: lambda.unwrap_or(DEFAULT_LAMBDA as f32),
-            iters: iters.unwrap_or(DEFAULT_ITERS as usize),
+            lambda: lambda.unwrap_or(DEFAULT_LAMBDA),
+            iters: iters.unwrap_or(DEFAULT_ITERS),
             similarity_function: similarity_function.unwrap_or(SimilarityFunction::Euclidean),
+            correction_precision: CorrectionPrecision::Single,
         }
     }
 
+    /// 设置修正项的累加精度
+    pub fn with_correction_precision(mut self, precision: CorrectionPrecision) -> Self {
+        self.correction_precision = precision;
+        self
+    }
+
     /// 标量量化
     /// 对单个向量进行标量量化
     /// 
@@ -55,83 +165,276 @@ impl OptimizedScalarQuantizer {
         destination: &mut [u8],
         bits: u8,
         centroid: &[f32],
-    ) -> Result<QuantizationResult, String> {
+    ) -> Result<QuantizationResult, BbqError> {
+        self.scalar_quantize_with_initial_std(vector, destination, bits, centroid, None)
+    }
+
+    /// 使用给定标准差初始化区间的标量量化
+    ///
+    /// 默认的初始区间来自向量自身的统计量；当数据集统计量已知时，
+    /// 用数据集的合并标准差初始化区间，再由坐标下降优化
+    ///
+    /// # 参数
+    /// * `initial_std` - 初始区间使用的标准差，为None时使用向量自身的标准差
+    pub fn scalar_quantize_with_initial_std(
+        &self,
+        vector: &[f32],
+        destination: &mut [u8],
+        bits: u8,
+        centroid: &[f32],
+        initial_std: Option<f32>,
+    ) -> Result<QuantizationResult, BbqError> {
+        THREAD_SCRATCH.with(|scratch| {
+            self.scalar_quantize_with_scratch(vector, destination, bits, centroid, initial_std, &mut scratch.borrow_mut())
+        })
+    }
+
+    /// 使用调用方提供的临时缓冲区进行标量量化
+    ///
+    /// 批量量化时在循环外创建一个 `QuantizationScratch` 并重复传入，
+    /// 结果与 `scalar_quantize_with_initial_std` 完全相同
+    pub fn scalar_quantize_with_scratch(
+        &self,
+        vector: &[f32],
+        destination: &mut [u8],
+        bits: u8,
+        centroid: &[f32],
+        initial_std: Option<f32>,
+        scratch: &mut QuantizationScratch,
+    ) -> Result<QuantizationResult, BbqError> {
+        self.quantize_impl(vector, QuantizedOutput::Unpacked(destination), bits, centroid, initial_std, scratch)
+    }
+
+    /// 1位量化，直接写入打包缓冲区
+    ///
+    /// 与先 `scalar_quantize` 再 `pack_as_binary` 结果相同，但不经过每维一个字节的中间数组：
+    /// 最后一遍按阈值比较时每8维直接组成一个字节，分量和由位计数得到
+    ///
+    /// # 参数
+    /// * `vector` - 输入向量
+    /// * `packed` - 打包结果，长度不小于 `vector.len().div_ceil(8)`（可按64位对齐，多出的字节补0）
+    /// * `centroid` - 质心向量
+    pub fn scalar_quantize_packed(
+        &self,
+        vector: &[f32],
+        packed: &mut [u8],
+        centroid: &[f32],
+    ) -> Result<QuantizationResult, BbqError> {
+        THREAD_SCRATCH.with(|scratch| {
+            self.scalar_quantize_packed_with_scratch(vector, packed, centroid, None, &mut scratch.borrow_mut())
+        })
+    }
+
+    /// 使用调用方提供的临时缓冲区进行1位打包量化，见 `scalar_quantize_packed`
+    pub fn scalar_quantize_packed_with_scratch(
+        &self,
+        vector: &[f32],
+        packed: &mut [u8],
+        centroid: &[f32],
+        initial_std: Option<f32>,
+        scratch: &mut QuantizationScratch,
+    ) -> Result<QuantizationResult, BbqError> {
+        self.quantize_impl(vector, QuantizedOutput::Packed(packed), 1, centroid, initial_std, scratch)
+    }
+
+    /// 对f32或f64输入进行标量量化
+    ///
+    /// f64输入以原精度减去质心，统计量和修正项总是以f64累加（不受 `CorrectionPrecision` 影响）；
+    /// f32输入的结果与 `scalar_quantize` 完全相同
+    ///
+    /// # 参数
+    /// * `vector` - 输入向量
+    /// * `destination` - 量化结果存储数组（会被修改）
+    /// * `bits` - 量化位数
+    /// * `centroid` - 质心向量，与输入同类型
+    pub fn scalar_quantize_generic<T: Float>(
+        &self,
+        vector: &[T],
+        destination: &mut [u8],
+        bits: u8,
+        centroid: &[T],
+    ) -> Result<QuantizationResult, BbqError> {
+        THREAD_SCRATCH.with(|scratch| {
+            self.quantize_impl(vector, QuantizedOutput::Unpacked(destination), bits, centroid, None, &mut scratch.borrow_mut())
+        })
+    }
+
+    /// 标量量化的实现，输入在减去质心之前保持原类型
+    fn quantize_impl<T: Float>(
+        &self,
+        vector: &[T],
+        mut output: QuantizedOutput,
+        bits: u8,
+        centroid: &[T],
+        initial_std: Option<f32>,
+        scratch: &mut QuantizationScratch,
+    ) -> Result<QuantizationResult, BbqError> {
         // 输入验证
         if vector.len() != centroid.len() {
-            return Err("向量和质心维度不匹配".to_string());
+            return Err(BbqError::dimension_mismatch("向量和质心维度不匹配", "vector and centroid dimensions differ"));
+        }
+        match &output {
+            QuantizedOutput::Unpacked(destination) if destination.len() != vector.len() => {
+                return Err(BbqError::dimension_mismatch("目标数组长度与向量长度不匹配", "destination length does not match the vector length"));
+            }
+            QuantizedOutput::Packed(packed) if packed.len() < vector.len().div_ceil(8) => {
+                return Err(BbqError::dimension_mismatch("打包数组长度不足", "packed buffer is too short"));
+            }
+            _ => {}
         }
-        if destination.len() != vector.len() {
-            return Err("目标数组长度与向量长度不匹配".to_string());
+        if !(1..=8).contains(&bits) {
+            return Err(BbqError::out_of_range("位数必须在1-8之间", "bits must be within 1-8"));
         }
-        if bits < 1 || bits > 8 {
-            return Err("位数必须在1-8之间".to_string());
+
+        // 单精度且使用向量自身统计量时按Lucene的运算顺序量化
+        #[cfg(feature = "lucene_parity")]
+        if let (Some(vector), Some(centroid)) = (T::as_f32_slice(vector), T::as_f32_slice(centroid)) {
+            if self.correction_precision == CorrectionPrecision::Single && initial_std.is_none() {
+                return crate::lucene_parity::scalar_quantize_into(
+                    vector,
+                    output,
+                    bits,
+                    centroid,
+                    self.similarity_function,
+                    self.lambda,
+                    self.iters,
+                    scratch.working(vector.len()),
+                );
+            }
         }
 
         // 1. 计算原始向量与质心的点积（用于非欧氏距离的additionalCorrection）
+        let precision = if T::DOUBLE_PRECISION { CorrectionPrecision::Double } else { self.correction_precision };
         let mut centroid_dot = 0.0;
         if self.similarity_function != SimilarityFunction::Euclidean {
-            centroid_dot = compute_dot_product(vector, centroid);
+            centroid_dot = match precision {
+                CorrectionPrecision::Single => T::dot(vector, centroid),
+                CorrectionPrecision::Double => vector.iter()
+                    .zip(centroid)
+                    .map(|(&v, &c)| v.to_f64() * c.to_f64())
+                    .sum::<f64>() as f32,
+            };
         }
 
         // 2. 质心中心化并计算统计信息
-        let mut working_vector = vec![0.0; vector.len()];
+        let working_vector = scratch.working(vector.len());
         let mut min = f32::MAX;
         let mut max = f32::MIN;
-        let mut sum = 0.0;
-        let mut sum_sq = 0.0;
 
         for i in 0..vector.len() {
-            let centered_val = vector[i] - centroid[i];
+            let centered_val = vector[i].centered(centroid[i]);
             working_vector[i] = centered_val;
             
             if centered_val < min { min = centered_val; }
             if centered_val > max { max = centered_val; }
-            
-            sum += centered_val;
-            sum_sq += centered_val * centered_val;
         }
 
-        let vec_mean = sum / vector.len() as f32;
-        
-        // 计算标准差
-        let mut variance_sum = 0.0;
-        for &val in &working_vector {
-            let diff = val - vec_mean;
-            variance_sum += diff * diff;
+        let working_vector: &[f32] = working_vector;
+        if !(min.is_finite() && max.is_finite()) {
+            return Err(BbqError::invalid_value("向量或质心包含非有限值（NaN或无穷）", "vector or centroid contains non-finite values (NaN or infinity)"));
+        }
+
+        // 均值、标准差和L2范数的平方
+        let (vec_mean, vec_std, norm2) = match precision {
+            CorrectionPrecision::Single => {
+                let mut sum = 0.0;
+                let mut sum_sq = 0.0;
+                for &val in working_vector {
+                    sum += val;
+                    sum_sq += val * val;
+                }
+                let vec_mean = sum / vector.len() as f32;
+                let mut variance_sum = 0.0;
+                for &val in working_vector {
+                    let diff = val - vec_mean;
+                    variance_sum += diff * diff;
+                }
+                (vec_mean, (variance_sum / vector.len() as f32).sqrt(), sum_sq)
+            }
+            CorrectionPrecision::Double => {
+                let n = vector.len() as f64;
+                let sum: f64 = working_vector.iter().map(|&val| val as f64).sum();
+                let sum_sq: f64 = working_vector.iter().map(|&val| val as f64 * val as f64).sum();
+                let vec_mean = sum / n;
+                let variance_sum: f64 = working_vector.iter()
+                    .map(|&val| (val as f64 - vec_mean).powi(2))
+                    .sum();
+                (vec_mean as f32, (variance_sum / n).sqrt() as f32, sum_sq as f32)
+            }
+        };
+
+        // 减去质心后为常量（含全零）时区间优化退化，直接使用中性修正项：
+        // 区间收缩为该常量、量化值全为0，重建结果与原向量完全一致
+        if max <= min {
+            match output {
+                QuantizedOutput::Unpacked(destination) => destination.fill(0),
+                QuantizedOutput::Packed(packed) => packed.fill(0),
+            }
+            return Ok(QuantizationResult {
+                lower_interval: min,
+                upper_interval: min,
+                additional_correction: if self.similarity_function == SimilarityFunction::Euclidean {
+                    norm2
+                } else {
+                    centroid_dot
+                },
+                quantized_component_sum: 0.0,
+            });
         }
-        let vec_std = (variance_sum / vector.len() as f32).sqrt();
-        let norm2 = sum_sq; // L2范数的平方
 
         // 4. 获取初始间隔
-        let mut interval = self.get_initial_interval(bits, vec_std, vec_mean, min, max)?;
+        let mut interval = self.get_initial_interval(bits, initial_std.unwrap_or(vec_std), vec_mean, min, max)?;
+        // 数值极大时统计量可能溢出，退回到 [min, max]
+        if !(interval.0.is_finite() && interval.1.is_finite() && interval.0 <= interval.1) {
+            interval = (min, max);
+        }
 
         // 5. 优化间隔
-        self.optimize_intervals(&mut interval, &working_vector, norm2, 1 << bits);
+        // 1位打包时优化的每一遍统计顺带按当前区间的阈值写入符号位，
+        // 最后一遍统计所用的区间就是最终区间时（收敛或损失不再下降）省去单独的阈值比较
+        let points = Self::points(bits);
+        let packed_ready = match &mut output {
+            QuantizedOutput::Packed(packed) => {
+                packed.fill(0);
+                self.optimize_intervals(&mut interval, working_vector, norm2, points, Some(packed))
+            }
+            QuantizedOutput::Unpacked(_) => self.optimize_intervals(&mut interval, working_vector, norm2, points, None),
+        };
 
         // 6. 量化向量并计算 quantizedComponentSum
         let (a, b) = interval;
-        let points = 1 << bits;
         let n_steps = points - 1;
         let step = if n_steps > 0 { (b - a) / n_steps as f32 } else { 0.0 };
         let step_inv = if step > 0.0 { 1.0 / step } else { 0.0 };
         let mut quantized_component_sum = 0.0;
 
-        for i in 0..working_vector.len() {
-            let xi = working_vector[i];
-            let clamped = xi.clamp(a, b);
-
-            if bits == 1 {
-                // 1bit量化：使用阈值二值化
-                let threshold = (a + b) / 2.0;
-                let quantized_value = if clamped >= threshold { 1 } else { 0 };
-                destination[i] = quantized_value;
-                quantized_component_sum += quantized_value as f32;
-            } else {
-                // 其他位数：使用原有的四舍五入方法
-                let assignment = ((clamped - a) * step_inv).round();
-                let quantized_value = assignment.min(n_steps as f32) as u8;
-                destination[i] = quantized_value;
-                quantized_component_sum += assignment;
+        match output {
+            QuantizedOutput::Packed(packed) => {
+                // 1位直接打包：每8维按阈值比较组成一个字节（高位在前）
+                if !packed_ready {
+                    pack_signs(working_vector, (a, b), packed);
+                }
+                quantized_component_sum = packed.iter().map(|byte| byte.count_ones()).sum::<u32>() as f32;
+            }
+            QuantizedOutput::Unpacked(destination) => {
+                for i in 0..working_vector.len() {
+                    let xi = working_vector[i];
+                    let clamped = xi.clamp(a, b);
+
+                    if bits == 1 {
+                        // 1bit量化：使用阈值二值化
+                        let threshold = (a + b) / 2.0;
+                        let quantized_value = if clamped >= threshold { 1 } else { 0 };
+                        destination[i] = quantized_value;
+                        quantized_component_sum += quantized_value as f32;
+                    } else {
+                        // 其他位数：使用原有的四舍五入方法
+                        let assignment = ((clamped - a) * step_inv).round();
+                        let quantized_value = assignment.min(n_steps as f32) as u8;
+                        destination[i] = quantized_value;
+                        quantized_component_sum += assignment;
+                    }
+                }
             }
         }
 
@@ -158,14 +461,14 @@ impl OptimizedScalarQuantizer {
         vec_mean: f32,
         min: f32,
         max: f32,
-    ) -> Result<(f32, f32), String> {
-        if bits < 1 || bits > 8 {
-            return Err(format!("位数必须在1-8之间，当前为{}", bits));
+    ) -> Result<(f32, f32), BbqError> {
+        if !(1..=8).contains(&bits) {
+            return Err(BbqError::out_of_range(format!("位数必须在1-8之间，当前为{}", bits), format!("bits must be within 1-8, got {}", bits)));
         }
         
         let grid_idx = (bits - 1) as usize;
         if grid_idx >= MINIMUM_MSE_GRID.len() {
-            return Err(format!("未找到位数 {} 对应的网格配置", bits));
+            return Err(BbqError::out_of_range(format!("未找到位数 {} 对应的网格配置", bits), format!("no grid configuration for {} bits", bits)));
         }
 
         let grid = &MINIMUM_MSE_GRID[grid_idx];
@@ -179,22 +482,32 @@ impl OptimizedScalarQuantizer {
     }
 
     /// 优化间隔
+    ///
+    /// `packed` 不为None时（1位打包）每一遍统计同时按当前区间写入符号位
+    ///
+    /// # 返回
+    /// `packed` 中的符号位是否对应最终区间
     fn optimize_intervals(
         &self,
         interval: &mut (f32, f32),
         vector: &[f32],
         norm2: f32,
         points: i32,
-    ) {
+        mut packed: Option<&mut [u8]>,
+    ) -> bool {
         let mut initial_loss = self.compute_loss(vector, *interval, points, norm2);
         let scale = (1.0 - self.lambda) / norm2;
 
         if !scale.is_finite() {
-            return;
+            return false;
         }
 
         for _ in 0..self.iters {
             let (a, b) = *interval;
+            // 区间收缩为一点时无法继续优化
+            if b <= a {
+                return false;
+            }
             let step_inv = (points - 1) as f32 / (b - a);
 
             let mut daa = 0.0;
@@ -202,8 +515,7 @@ impl OptimizedScalarQuantizer {
             let mut dbb = 0.0;
             let mut dax = 0.0;
             let mut dbx = 0.0;
-
-            for &xi in vector {
+            let mut accumulate = |xi: f32| {
                 let clamped = xi.clamp(a, b);
                 let k = ((clamped - a) * step_inv).round();
                 let s = k / (points - 1) as f32;
@@ -213,6 +525,23 @@ impl OptimizedScalarQuantizer {
                 dbb += s * s;
                 dax += xi * (1.0 - s);
                 dbx += xi * s;
+                clamped
+            };
+
+            match packed.as_deref_mut() {
+                Some(packed) => {
+                    let threshold = (a + b) / 2.0;
+                    for (byte, chunk) in packed.iter_mut().zip(vector.chunks(8)) {
+                        *byte = chunk.iter()
+                            .enumerate()
+                            .fold(0u8, |acc, (j, &xi)| acc | ((accumulate(xi) >= threshold) as u8) << (7 - j));
+                    }
+                }
+                None => {
+                    for &xi in vector {
+                        accumulate(xi);
+                    }
+                }
             }
 
             let m0 = scale * dax * dax + self.lambda * daa;
@@ -221,7 +550,7 @@ impl OptimizedScalarQuantizer {
 
             let det = m0 * m2 - m1 * m1;
             if det.abs() < NUMERICAL_CONSTANTS::MIN_DETERMINANT as f32 {
-                return;
+                return true;
             }
 
             let a_opt = (m2 * dax - m1 * dbx) / det;
@@ -229,18 +558,24 @@ impl OptimizedScalarQuantizer {
 
             if (interval.0 - a_opt).abs() < NUMERICAL_CONSTANTS::EPSILON as f32 &&
                (interval.1 - b_opt).abs() < NUMERICAL_CONSTANTS::EPSILON as f32 {
-                return;
+                return true;
+            }
+
+            // 数值溢出或区间反转时保留上一轮的结果
+            if !(a_opt.is_finite() && b_opt.is_finite() && a_opt < b_opt) {
+                return true;
             }
 
             let new_loss = self.compute_loss(vector, (a_opt, b_opt), points, norm2);
 
             if new_loss > initial_loss {
-                return;
+                return true;
             }
 
             *interval = (a_opt, b_opt);
             initial_loss = new_loss;
         }
+        false
     }
 
     /// 计算损失函数
@@ -271,32 +606,206 @@ impl OptimizedScalarQuantizer {
     }
 
     /// 二进制打包
-    pub fn pack_as_binary(vector: &[u8], packed: &mut [u8]) -> Result<(), String> {
-        let mut i = 0;
-        while i < vector.len() {
-            let mut result = 0u8;
-            
-            for j in (0..8).rev() {
-                if i < vector.len() {
-                    let val = vector[i];
-                    if val != 0 && val != 1 {
-                        return Err("1位量化值必须为0或1".to_string());
-                    }
-                    result |= (val & 1) << j;
-                    i += 1;
-                } else {
-                    break;
-                }
+    ///
+    /// 每次读取8个分量作为一个u64，校验后用一次乘法收集8个最低位，高位在前
+    pub fn pack_as_binary(vector: &[u8], packed: &mut [u8]) -> Result<(), BbqError> {
+        if packed.len() < vector.len().div_ceil(8) {
+            return Err(BbqError::dimension_mismatch("打包数组长度不足", "packed buffer is too short"));
+        }
+        for (byte, chunk) in packed.iter_mut().zip(vector.chunks(8)) {
+            let mut lanes = [0u8; 8];
+            lanes[..chunk.len()].copy_from_slice(chunk);
+            let word = u64::from_le_bytes(lanes);
+            if word & !BINARY_LANE_MASK != 0 {
+                return Err(BbqError::out_of_range("1位量化值必须为0或1", "1-bit quantized values must be 0 or 1"));
             }
-            
-            let index = (i - 1) / 8;
-            if index >= packed.len() {
-                return Err("打包数组长度不足".to_string());
+            *byte = Self::pack_lanes(word);
+        }
+        Ok(())
+    }
+
+    /// 把u64中8个字节的最低位收集为一个字节，第0个字节的位在最高位（与 `pack_as_binary` 的位序一致）
+    ///
+    /// 乘数把第i个字节的最低位移到第 `63 - i` 位，各项落在不同的位上，不会产生进位
+    #[inline]
+    pub fn pack_lanes(word: u64) -> u8 {
+        ((word & BINARY_LANE_MASK).wrapping_mul(PACK_LANES_MULTIPLIER) >> 56) as u8
+    }
+
+    /// 二进制解包，`pack_as_binary` 的逆操作
+    ///
+    /// 每个打包字节查表得到8个0/1分量，一次写入
+    ///
+    /// # 参数
+    /// * `packed` - 打包向量
+    /// * `destination` - 解包结果，长度即维度
+    pub fn unpack_from_binary(packed: &[u8], destination: &mut [u8]) -> Result<(), BbqError> {
+        if packed.len() < destination.len().div_ceil(8) {
+            return Err(BbqError::dimension_mismatch(
+                format!("打包长度 {} 不足以解包 {} 维", packed.len(), destination.len()),
+                format!("packed length {} is too short to unpack {} dimensions", packed.len(), destination.len()),
+            ));
+        }
+        for (chunk, &byte) in destination.chunks_mut(8).zip(packed) {
+            let lanes = UNPACK_TABLE[byte as usize].to_le_bytes();
+            chunk.copy_from_slice(&lanes[..chunk.len()]);
+        }
+        Ok(())
+    }
+
+    /// 量化网格的点数：`bits` 位量化把区间等分为 `points - 1` 段
+    pub fn points(bits: u8) -> i32 {
+        1 << bits
+    }
+
+    /// 将值向上取整到 `bucket` 的倍数（Lucene `discretize`）
+    pub fn discretize(value: usize, bucket: usize) -> usize {
+        value.div_ceil(bucket) * bucket
+    }
+
+    /// 1位打包向量的字节数
+    ///
+    /// 按Lucene的布局时维度先对齐到64位，补出的位为0，不参与点积
+    pub fn packed_len(dimension: usize, discretized: bool) -> usize {
+        if discretized {
+            Self::discretize(dimension, LUCENE_DIMENSION_BUCKET) / 8
+        } else {
+            dimension.div_ceil(8)
+        }
+    }
+
+    /// 将4位量化查询转置为4个连续的位平面（Lucene `transposeHalfByte`）
+    ///
+    /// 第b个平面由各分量的第b位组成，位序与 `pack_as_binary` 一致；
+    /// `destination` 的长度必须是4的倍数，每个平面占四分之一，查询不足的部分补0
+    pub fn transpose_half_byte(quantized: &[u8], destination: &mut [u8]) -> Result<(), BbqError> {
+        let plane_len = destination.len() / 4;
+        if !destination.len().is_multiple_of(4) || quantized.len() > plane_len * 8 {
+            return Err(BbqError::dimension_mismatch(
+                format!("转置数组长度 {} 不足以容纳 {} 维的4位查询", destination.len(), quantized.len()),
+                format!("transposed buffer length {} cannot hold a 4-bit query of {} dimensions", destination.len(), quantized.len()),
+            ));
+        }
+        destination.fill(0);
+        for (i, &value) in quantized.iter().enumerate() {
+            if value > 15 {
+                return Err(BbqError::out_of_range("4位量化值必须在0-15之间", "4-bit quantized values must be within 0-15"));
+            }
+            for bit in 0..4 {
+                destination[bit * plane_len + i / 8] |= ((value >> bit) & 1) << (7 - i % 8);
             }
-            packed[index] = result;
         }
         Ok(())
     }
+
+    /// 将 `pack_as_binary` 打包的向量还原为每维一个0/1值
+    pub fn unpack_binary(packed: &[u8], dimension: usize) -> Result<Vec<u8>, BbqError> {
+        if packed.len() != dimension.div_ceil(8) {
+            return Err(BbqError::dimension_mismatch(
+                format!("打包长度 {} 与维度 {} 不匹配", packed.len(), dimension),
+                format!("packed length {} does not match dimension {}", packed.len(), dimension),
+            ));
+        }
+        let mut unpacked = vec![0u8; dimension];
+        Self::unpack_from_binary(packed, &mut unpacked)?;
+        Ok(unpacked)
+    }
+
+    /// 由量化值和修正项重建向量：质心 + 下界 + 量化值 * 步长
+    ///
+    /// # 参数
+    /// * `quantized` - 未打包的量化值
+    /// * `bits` - 量化位数
+    /// * `result` - 量化结果（提供区间）
+    /// * `centroid` - 量化时使用的质心
+    pub fn dequantize(quantized: &[u8], bits: u8, result: &QuantizationResult, centroid: &[f32]) -> Vec<f32> {
+        let n_steps = ((1u32 << bits.clamp(1, 8)) - 1) as f32;
+        let step = (result.upper_interval - result.lower_interval) / n_steps;
+        quantized.iter()
+            .zip(centroid.iter())
+            .map(|(&q, &c)| c + result.lower_interval + q as f32 * step)
+            .collect()
+    }
+
+    /// 以给定区间量化向量，不再优化区间
+    ///
+    /// 与量化的第6步相同：1位按区间中点二值化，其他位数四舍五入到最近的格点。
+    /// 附加修正只取决于向量本身，调用方沿用原来的值即可
+    ///
+    /// # 参数
+    /// * `vector` - 原始（预处理后的）向量
+    /// * `centroid` - 质心向量
+    /// * `bits` - 量化位数
+    /// * `interval` - 使用的（下界, 上界）
+    /// * `destination` - 未打包的量化结果
+    ///
+    /// # 返回
+    /// 量化分量和
+    pub fn quantize_with_interval(
+        vector: &[f32],
+        centroid: &[f32],
+        bits: u8,
+        interval: (f32, f32),
+        destination: &mut [u8],
+    ) -> Result<f32, BbqError> {
+        if vector.len() != centroid.len() || destination.len() != vector.len() {
+            return Err(BbqError::dimension_mismatch("向量、质心和目标数组的维度不匹配", "vector, centroid and destination dimensions differ"));
+        }
+        if !(1..=8).contains(&bits) {
+            return Err(BbqError::out_of_range("位数必须在1-8之间", "bits must be within 1-8"));
+        }
+        let (a, b) = interval;
+        let n_steps = Self::points(bits) - 1;
+        let step_inv = if b > a { n_steps as f32 / (b - a) } else { 0.0 };
+        let threshold = (a + b) / 2.0;
+        let mut quantized_component_sum = 0u32;
+        for ((&x, &c), quantized) in vector.iter().zip(centroid).zip(destination.iter_mut()) {
+            let clamped = (x - c).clamp(a, b);
+            *quantized = if bits == 1 {
+                (clamped >= threshold) as u8
+            } else {
+                ((clamped - a) * step_inv).round().min(n_steps as f32) as u8
+            };
+            quantized_component_sum += *quantized as u32;
+        }
+        Ok(quantized_component_sum as f32)
+    }
+
+    /// 计算量化质量
+    ///
+    /// 以中心化向量的相对重建误差 ||x - x̂||² / ||x||² 衡量，
+    /// 质量 = 1 - 相对误差，截断到[0, 1]；零向量视为完美重建
+    ///
+    /// # 参数
+    /// * `vector` - 原始（预处理后的）向量
+    /// * `centroid` - 质心向量
+    /// * `quantized` - 未打包的量化值
+    /// * `bits` - 量化位数
+    /// * `result` - 量化结果（提供区间）
+    pub fn compute_quantization_quality(
+        vector: &[f32],
+        centroid: &[f32],
+        quantized: &[u8],
+        bits: u8,
+        result: &QuantizationResult,
+    ) -> f32 {
+        let n_steps = ((1u32 << bits.clamp(1, 8)) - 1) as f32;
+        let step = (result.upper_interval - result.lower_interval) / n_steps;
+
+        let mut error = 0.0f32;
+        let mut norm2 = 0.0f32;
+        for ((&x, &c), &q) in vector.iter().zip(centroid.iter()).zip(quantized.iter()) {
+            let centered = x - c;
+            let reconstructed = result.lower_interval + q as f32 * step;
+            error += (centered - reconstructed) * (centered - reconstructed);
+            norm2 += centered * centered;
+        }
+
+        if norm2 <= 0.0 {
+            return 1.0;
+        }
+        (1.0 - error / norm2).clamp(0.0, 1.0)
+    }
 }
 
 #[cfg(test)]
@@ -325,4 +834,164 @@ mod tests {
         OptimizedScalarQuantizer::pack_as_binary(&vector, &mut packed).unwrap();
         assert_eq!(packed[0], 0b10101010);
     }
+
+    #[test]
+    fn test_pack_and_unpack_binary() {
+        for dimension in [1usize, 7, 8, 9, 64, 77] {
+            let bits: Vec<u8> = (0..dimension).map(|i| ((i * 7 + i / 3) % 3 == 0) as u8).collect();
+            let mut packed = vec![0u8; dimension.div_ceil(8)];
+            OptimizedScalarQuantizer::pack_as_binary(&bits, &mut packed).unwrap();
+            // 与逐位打包的参考实现一致：高位在前
+            for (i, &byte) in packed.iter().enumerate() {
+                let expected = (0..8)
+                    .filter(|&j| bits.get(i * 8 + j) == Some(&1))
+                    .fold(0u8, |acc, j| acc | 1 << (7 - j));
+                assert_eq!(byte, expected);
+            }
+            assert_eq!(OptimizedScalarQuantizer::unpack_binary(&packed, dimension).unwrap(), bits);
+            let mut destination = vec![9u8; dimension];
+            OptimizedScalarQuantizer::unpack_from_binary(&packed, &mut destination).unwrap();
+            assert_eq!(destination, bits);
+        }
+
+        let mut packed = [0u8; 2];
+        assert!(OptimizedScalarQuantizer::pack_as_binary(&[0, 1, 2], &mut packed).is_err());
+        assert!(OptimizedScalarQuantizer::pack_as_binary(&[0; 17], &mut packed).is_err());
+        assert!(OptimizedScalarQuantizer::unpack_from_binary(&packed, &mut [0u8; 17]).is_err());
+    }
+
+    #[test]
+    fn test_packed_quantization_matches_pack_as_binary() {
+        // 迭代次数不同时优化分别以收敛、损失上升或用完迭代结束，符号位分别在统计中或单独写入
+        for (similarity_function, iters) in [
+            (SimilarityFunction::Euclidean, None),
+            (SimilarityFunction::Cosine, None),
+            (SimilarityFunction::Euclidean, Some(0)),
+            (SimilarityFunction::Euclidean, Some(1)),
+            (SimilarityFunction::MaximumInnerProduct, Some(50)),
+        ] {
+            let quantizer = OptimizedScalarQuantizer::new(None, iters, Some(similarity_function));
+            for dimension in [5usize, 64, 77] {
+                let centroid = vec![0.1; dimension];
+                for vector in [
+                    (0..dimension).map(|i| ((i * 37 % 11) as f32 - 5.0) / 5.0).collect::<Vec<f32>>(),
+                    crate::vector_utils::create_random_vector(dimension, -1.0, 1.0),
+                    vec![0.1; dimension],
+                ] {
+                    let mut quantized = vec![0u8; dimension];
+                    let expected = quantizer.scalar_quantize(&vector, &mut quantized, 1, &centroid).unwrap();
+                    let mut expected_packed = vec![0u8; OptimizedScalarQuantizer::packed_len(dimension, true)];
+                    OptimizedScalarQuantizer::pack_as_binary(&quantized, &mut expected_packed).unwrap();
+
+                    // 按64位对齐的缓冲区：多出的字节补0
+                    let mut packed = vec![0xFFu8; expected_packed.len()];
+                    let result = quantizer.scalar_quantize_packed(&vector, &mut packed, &centroid).unwrap();
+                    assert_eq!(packed, expected_packed);
+                    assert_eq!(
+                        (result.lower_interval, result.upper_interval, result.additional_correction, result.quantized_component_sum),
+                        (expected.lower_interval, expected.upper_interval, expected.additional_correction, expected.quantized_component_sum),
+                    );
+                }
+            }
+        }
+        let quantizer = OptimizedScalarQuantizer::new(None, None, None);
+        assert!(quantizer.scalar_quantize_packed(&[1.0; 9], &mut [0u8; 1], &[0.0; 9]).is_err());
+    }
+
+    #[test]
+    fn test_discretized_layout() {
+        assert_eq!(OptimizedScalarQuantizer::discretize(100, 64), 128);
+        assert_eq!(OptimizedScalarQuantizer::packed_len(100, false), 13);
+        assert_eq!(OptimizedScalarQuantizer::packed_len(100, true), 16);
+        assert_eq!(OptimizedScalarQuantizer::packed_len(128, true), 16);
+
+        // 补出的字节保持为0
+        let vector = vec![1u8; 10];
+        let mut packed = vec![0u8; OptimizedScalarQuantizer::packed_len(10, true)];
+        OptimizedScalarQuantizer::pack_as_binary(&vector, &mut packed).unwrap();
+        assert_eq!(packed, vec![0xff, 0xc0, 0, 0, 0, 0, 0, 0]);
+
+        let query = vec![0b0001, 0b0010, 0b0100, 0b1000, 15, 0, 0, 0, 1];
+        let mut transposed = vec![0u8; 4 * 8];
+        OptimizedScalarQuantizer::transpose_half_byte(&query, &mut transposed).unwrap();
+        assert_eq!(&transposed[0..2], &[0b1000_1000, 0b1000_0000]);
+        assert_eq!(transposed[8], 0b0100_1000);
+        assert_eq!(transposed[16], 0b0010_1000);
+        assert_eq!(transposed[24], 0b0001_1000);
+        assert!(OptimizedScalarQuantizer::transpose_half_byte(&query, &mut [0u8; 4]).is_err());
+    }
+
+    #[test]
+    fn test_quantization_quality() {
+        let quantizer = OptimizedScalarQuantizer::new(None, None, None);
+        let centroid = vec![0.0; 4];
+
+        // 两值向量可以被1位量化精确表示
+        let exact = vec![1.0, -1.0, 1.0, -1.0];
+        let mut dest = vec![0u8; 4];
+        let result = quantizer.scalar_quantize(&exact, &mut dest, 1, &centroid).unwrap();
+        let exact_quality = OptimizedScalarQuantizer::compute_quantization_quality(&exact, &centroid, &dest, 1, &result);
+        assert!(exact_quality > 0.99);
+
+        // 单个大分量的向量在1位下损失较大
+        let spiky = vec![4.0, 0.1, -0.1, 0.0];
+        let result = quantizer.scalar_quantize(&spiky, &mut dest, 1, &centroid).unwrap();
+        let spiky_quality = OptimizedScalarQuantizer::compute_quantization_quality(&spiky, &centroid, &dest, 1, &result);
+        assert!(spiky_quality < exact_quality);
+        assert!((0.0..=1.0).contains(&spiky_quality));
+    }
+
+    #[test]
+    fn test_scratch_reuse_matches_fresh_buffers() {
+        let quantizer = OptimizedScalarQuantizer::new(None, None, Some(SimilarityFunction::Cosine));
+        let centroid = vec![0.1; 8];
+        let mut scratch = QuantizationScratch::new();
+        for vector in [vec![0.3, -0.2, 0.9, 0.0, -0.7, 0.5, 0.1, -0.4], vec![2.0, 1.0, 0.0, 1.5, -1.0, 0.3, 0.9, 1.1], vec![-0.5, 0.5, -0.5, 0.5, 0.0, 0.2, 0.4, 0.8]] {
+            for bits in [1, 4] {
+                let mut expected = vec![0u8; 8];
+                let mut actual = vec![0u8; 8];
+                let fresh = quantizer.scalar_quantize(&vector, &mut expected, bits, &centroid).unwrap();
+                let reused = quantizer.scalar_quantize_with_scratch(&vector, &mut actual, bits, &centroid, None, &mut scratch).unwrap();
+                assert_eq!(actual, expected);
+                assert_eq!(reused.lower_interval, fresh.lower_interval);
+                assert_eq!(reused.upper_interval, fresh.upper_interval);
+                assert_eq!(reused.additional_correction, fresh.additional_correction);
+            }
+        }
+    }
+    #[test]
+    fn test_f64_inputs_keep_offsets_lost_in_f32() {
+        use crate::vector_utils::compute_centroid_precise;
+
+        // 远离原点的数据：各向量之间的差异小于f32在1e6附近的间隔（0.0625）
+        let vectors: Vec<Vec<f64>> = (0..4)
+            .map(|i| (0..8).map(|d| 1e6 + 0.004 * ((i * 3 + d * 5) % 7) as f64).collect())
+            .collect();
+        let centroid = compute_centroid_precise(&vectors).unwrap();
+        let quantizer = OptimizedScalarQuantizer::new(None, None, None);
+
+        // f32输入时差异在转换中丢失，向量退化
+        let vector_f32: Vec<f32> = vectors[1].iter().map(|&v| v as f32).collect();
+        let centroid_f32: Vec<f32> = centroid.iter().map(|&c| c as f32).collect();
+        let mut dest = vec![0u8; 8];
+        assert!(quantizer.scalar_quantize(&vector_f32, &mut dest, 4, &centroid_f32).unwrap().is_degenerate());
+
+        // f64输入与先以f64中心化、再以双精度量化的结果一致
+        let result = quantizer.scalar_quantize_generic(&vectors[1], &mut dest, 4, &centroid).unwrap();
+        assert!(!result.is_degenerate());
+        let centered: Vec<f32> = vectors[1].iter().zip(&centroid).map(|(&v, &c)| (v - c) as f32).collect();
+        let mut expected = vec![0u8; 8];
+        let reference = OptimizedScalarQuantizer::new(None, None, None)
+            .with_correction_precision(CorrectionPrecision::Double)
+            .scalar_quantize(&centered, &mut expected, 4, &[0.0; 8])
+            .unwrap();
+        assert_eq!(dest, expected);
+        assert_eq!(result.lower_interval, reference.lower_interval);
+        assert_eq!(result.upper_interval, reference.upper_interval);
+        assert_eq!(result.additional_correction, reference.additional_correction);
+
+        // f32输入经泛型入口与原入口完全相同
+        let generic = quantizer.scalar_quantize_generic(&vector_f32, &mut dest, 4, &centroid_f32).unwrap();
+        assert!(generic.is_degenerate());
+    }
 }
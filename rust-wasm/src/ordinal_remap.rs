@@ -0,0 +1,144 @@
+//! 序号重映射
+//!
+//! 压缩会移除已删除的向量并把剩余向量按原顺序重新编号。
+//! 压缩报告附带旧序号到新序号的映射，持有之前搜索结果或自存序号的应用
+//! 可以据此更新引用，而不必重新搜索
+
+use crate::error_codes::BbqError;
+use crate::quantized_index::QueryResult;
+
+/// 压缩前后的序号映射
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OrdinalRemap {
+    /// 压缩前的向量数量
+    old_len: usize,
+    /// 保留的旧序号（升序），新序号即其下标
+    live: Vec<usize>,
+}
+
+impl OrdinalRemap {
+    /// 由保留的旧序号创建
+    ///
+    /// # 参数
+    /// * `old_len` - 压缩前的向量数量
+    /// * `live` - 保留的旧序号（升序），新序号即其下标
+    pub fn new(old_len: usize, live: Vec<usize>) -> Self {
+        debug_assert!(live.windows(2).all(|pair| pair[0] < pair[1]));
+        debug_assert!(live.last().is_none_or(|&last| last < old_len));
+        Self { old_len, live }
+    }
+
+    /// 不改变任何序号的映射
+    pub fn identity(len: usize) -> Self {
+        Self { old_len: len, live: (0..len).collect() }
+    }
+
+    /// 压缩前的向量数量
+    pub fn old_len(&self) -> usize {
+        self.old_len
+    }
+
+    /// 压缩后的向量数量
+    pub fn new_len(&self) -> usize {
+        self.live.len()
+    }
+
+    /// 被移除的向量数量
+    pub fn removed(&self) -> usize {
+        self.old_len - self.live.len()
+    }
+
+    /// 是否没有任何序号变化
+    pub fn is_identity(&self) -> bool {
+        self.live.len() == self.old_len
+    }
+
+    /// 旧序号对应的新序号，向量已被移除或序号越界时为None
+    pub fn translate(&self, old: usize) -> Option<usize> {
+        self.live.binary_search(&old).ok()
+    }
+
+    /// 新序号对应的旧序号
+    pub fn original(&self, new: usize) -> Option<usize> {
+        self.live.get(new).copied()
+    }
+
+    /// 批量转换旧序号，已移除的为None，顺序与输入一致
+    pub fn translate_all(&self, ordinals: &[usize]) -> Vec<Option<usize>> {
+        ordinals.iter().map(|&ord| self.translate(ord)).collect()
+    }
+
+    /// 就地更新之前的搜索结果：改写为新序号，丢弃已移除的向量，其余顺序不变
+    ///
+    /// # 返回
+    /// 丢弃的结果数量
+    pub fn translate_results(&self, results: &mut Vec<QueryResult>) -> usize {
+        let before = results.len();
+        results.retain_mut(|result| match self.translate(result.index) {
+            Some(new) => {
+                result.index = new;
+                true
+            }
+            None => false,
+        });
+        before - results.len()
+    }
+
+    /// 按新序号顺序遍历 `(旧序号, 新序号)`
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.live.iter().enumerate().map(|(new, &old)| (old, new))
+    }
+
+    /// 按旧序号展开的映射表，已移除的为None
+    pub fn to_table(&self) -> Vec<Option<usize>> {
+        let mut table = vec![None; self.old_len];
+        for (old, new) in self.iter() {
+            table[old] = Some(new);
+        }
+        table
+    }
+
+    /// 接在另一次映射之后：先应用 `self` 再应用 `next`
+    ///
+    /// 只保存了较早序号的应用可以把多次压缩的映射合并后一次转换
+    pub fn then(&self, next: &OrdinalRemap) -> Result<OrdinalRemap, BbqError> {
+        if next.old_len != self.new_len() {
+            return Err(BbqError::invalid_argument(
+                format!("序号映射不连续：前一次压缩后有{}个向量，后一次压缩前有{}个", self.new_len(), next.old_len),
+                format!("ordinal remaps are not contiguous: {} vectors after the earlier compaction, {} before the later one", self.new_len(), next.old_len),
+            ));
+        }
+        Ok(Self {
+            old_len: self.old_len,
+            live: next.live.iter().map(|&ord| self.live[ord]).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_and_compose() {
+        let remap = OrdinalRemap::new(6, vec![0, 2, 3, 5]);
+        assert_eq!(remap.removed(), 2);
+        assert!(!remap.is_identity());
+        assert_eq!(remap.translate_all(&[5, 1, 0, 9]), vec![Some(3), None, Some(0), None]);
+        assert_eq!(remap.original(1), Some(2));
+        assert_eq!(remap.to_table(), vec![Some(0), None, Some(1), Some(2), None, Some(3)]);
+
+        let mut results: Vec<QueryResult> = [4, 3, 1, 5]
+            .into_iter()
+            .map(|index| QueryResult { index, score: 1.0, original_score: None, distances: None })
+            .collect();
+        assert_eq!(remap.translate_results(&mut results), 2);
+        assert_eq!(results.iter().map(|result| result.index).collect::<Vec<_>>(), vec![2, 3]);
+
+        let next = OrdinalRemap::new(4, vec![1, 3]);
+        let combined = remap.then(&next).unwrap();
+        assert_eq!(combined.translate_all(&[2, 5, 0]), vec![Some(0), Some(1), None]);
+        assert!(next.then(&remap).is_err());
+        assert!(OrdinalRemap::identity(3).is_identity());
+    }
+}
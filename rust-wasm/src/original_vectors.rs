@@ -0,0 +1,203 @@
+//! 索引内保留的原始向量
+//!
+//! 重排、召回评估、IVF划分和迁移都需要预处理后的原始向量。
+//! 除了直接保存f32之外，还可以压缩保存：
+//! - f16：每维2字节，相对误差约1e-3；只用于重排时内存减半，重排召回率与f32基本相同
+//! - int8残差：保存原始向量与量化重建值之差，按每个向量的最大残差缩放到int8，
+//!   每维1字节加每个向量4字节的缩放系数；解码时需要同一代的量化向量
+
+use std::borrow::Cow;
+
+use crate::error_codes::BbqError;
+use crate::half_precision::{decode_f16_into, encode_f16};
+use crate::optimized_scalar_quantizer::OptimizedScalarQuantizer;
+use crate::quantized_index::QuantizedVectorValues;
+
+/// 原始向量的存储编码
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OriginalVectorEncoding {
+    /// 不压缩（默认）
+    #[default]
+    F32,
+    /// 半精度浮点
+    F16,
+    /// 相对量化重建值的int8残差
+    Int8Residual,
+}
+
+impl OriginalVectorEncoding {
+    /// 每个向量占用的字节数
+    pub fn bytes_per_vector(self, dimension: usize) -> u64 {
+        let dimension = dimension as u64;
+        match self {
+            OriginalVectorEncoding::F32 => dimension.saturating_mul(4),
+            OriginalVectorEncoding::F16 => dimension.saturating_mul(2),
+            OriginalVectorEncoding::Int8Residual => dimension.saturating_add(4),
+        }
+    }
+}
+
+/// 按编码存放的数据
+#[derive(Debug, Clone)]
+enum Storage {
+    F32(Vec<Vec<f32>>),
+    F16(Vec<Vec<u16>>),
+    Int8Residual {
+        residuals: Vec<Vec<i8>>,
+        scales: Vec<f32>,
+    },
+}
+
+/// 按序号存放的原始向量
+#[derive(Debug, Clone)]
+pub struct OriginalVectors {
+    /// 量化向量的位数，int8残差解码时用于重建
+    index_bits: u8,
+    storage: Storage,
+}
+
+impl OriginalVectors {
+    /// 按编码保存一组预处理后的向量
+    ///
+    /// # 参数
+    /// * `vectors` - 预处理后的原始向量，序号与values一致
+    /// * `encoding` - 存储编码
+    /// * `values` - 同一代的量化向量（int8残差以其重建值为基准）
+    /// * `index_bits` - 量化向量的位数
+    pub fn encode(
+        vectors: Vec<Vec<f32>>,
+        encoding: OriginalVectorEncoding,
+        values: &dyn QuantizedVectorValues,
+        index_bits: u8,
+    ) -> Result<Self, BbqError> {
+        let storage = match encoding {
+            OriginalVectorEncoding::F32 => Storage::F32(vectors),
+            OriginalVectorEncoding::F16 => Storage::F16(vectors.iter().map(|vector| encode_f16(vector)).collect()),
+            OriginalVectorEncoding::Int8Residual => {
+                let mut residuals = Vec::with_capacity(vectors.len());
+                let mut scales = Vec::with_capacity(vectors.len());
+                for (ord, vector) in vectors.iter().enumerate() {
+                    let reconstruction = reconstruct_vector(values, index_bits, ord)?;
+                    let residual: Vec<f32> = vector.iter().zip(&reconstruction).map(|(&x, &r)| x - r).collect();
+                    let max = residual.iter().fold(0.0f32, |max, &r| max.max(r.abs()));
+                    let scale = if max > 0.0 { max / 127.0 } else { 0.0 };
+                    residuals.push(residual.iter()
+                        .map(|&r| if scale > 0.0 { (r / scale).round().clamp(-127.0, 127.0) as i8 } else { 0 })
+                        .collect());
+                    scales.push(scale);
+                }
+                Storage::Int8Residual { residuals, scales }
+            }
+        };
+        Ok(Self { index_bits, storage })
+    }
+
+    /// 存储编码
+    pub fn encoding(&self) -> OriginalVectorEncoding {
+        match self.storage {
+            Storage::F32(_) => OriginalVectorEncoding::F32,
+            Storage::F16(_) => OriginalVectorEncoding::F16,
+            Storage::Int8Residual { .. } => OriginalVectorEncoding::Int8Residual,
+        }
+    }
+
+    /// 向量数量
+    pub fn len(&self) -> usize {
+        match &self.storage {
+            Storage::F32(vectors) => vectors.len(),
+            Storage::F16(vectors) => vectors.len(),
+            Storage::Int8Residual { scales, .. } => scales.len(),
+        }
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 占用的字节数
+    pub fn memory_bytes(&self, dimension: usize) -> u64 {
+        (self.len() as u64).saturating_mul(self.encoding().bytes_per_vector(dimension))
+    }
+
+    /// 解码一个向量；f32存储时直接借用
+    ///
+    /// # 参数
+    /// * `ord` - 向量序号
+    /// * `values` - 保存时使用的同一代量化向量
+    pub fn get<'a>(&'a self, ord: usize, values: &dyn QuantizedVectorValues) -> Option<Cow<'a, [f32]>> {
+        if let Storage::F32(vectors) = &self.storage {
+            return vectors.get(ord).map(|vector| Cow::Borrowed(vector.as_slice()));
+        }
+        let mut buffer = Vec::new();
+        self.get_with_buffer(ord, values, &mut buffer)?;
+        Some(Cow::Owned(buffer))
+    }
+
+    /// 解码一个向量到可复用的缓冲区；f32存储时直接借用而不写缓冲区
+    ///
+    /// 重排逐个候选调用，所有候选共用一个缓冲区
+    pub fn get_with_buffer<'a>(
+        &'a self,
+        ord: usize,
+        values: &dyn QuantizedVectorValues,
+        buffer: &'a mut Vec<f32>,
+    ) -> Option<&'a [f32]> {
+        match &self.storage {
+            Storage::F32(vectors) => vectors.get(ord).map(Vec::as_slice),
+            Storage::F16(vectors) => {
+                let vector = vectors.get(ord)?;
+                buffer.resize(vector.len(), 0.0);
+                decode_f16_into(vector, buffer);
+                Some(buffer.as_slice())
+            }
+            Storage::Int8Residual { residuals, scales } => {
+                let (residual, &scale) = residuals.get(ord).zip(scales.get(ord))?;
+                *buffer = reconstruct_vector(values, self.index_bits, ord).ok()?;
+                for (x, &r) in buffer.iter_mut().zip(residual) {
+                    *x += r as f32 * scale;
+                }
+                Some(buffer.as_slice())
+            }
+        }
+    }
+
+    /// 解码全部向量；f32存储时直接借用
+    pub fn decode_all<'a>(&'a self, values: &dyn QuantizedVectorValues) -> Result<Cow<'a, [Vec<f32>]>, BbqError> {
+        if let Storage::F32(vectors) = &self.storage {
+            return Ok(Cow::Borrowed(vectors.as_slice()));
+        }
+        (0..self.len())
+            .map(|ord| self.get(ord, values)
+                .map(Cow::into_owned)
+                .ok_or_else(|| BbqError::corrupt_data(format!("原始向量 {} 无法解码", ord), format!("original vector {} cannot be decoded", ord))))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Cow::Owned)
+    }
+
+    /// 按序号选出一部分向量（量化向量同样按这些序号选出时残差仍然有效）
+    pub fn select(&self, ords: &[usize]) -> Self {
+        fn pick<T: Clone>(items: &[T], ords: &[usize]) -> Vec<T> {
+            ords.iter().map(|&ord| items[ord].clone()).collect()
+        }
+        let storage = match &self.storage {
+            Storage::F32(vectors) => Storage::F32(pick(vectors, ords)),
+            Storage::F16(vectors) => Storage::F16(pick(vectors, ords)),
+            Storage::Int8Residual { residuals, scales } => Storage::Int8Residual {
+                residuals: pick(residuals, ords),
+                scales: pick(scales, ords),
+            },
+        };
+        Self { index_bits: self.index_bits, storage }
+    }
+}
+
+/// 由量化值和修正项重建预处理后的向量
+pub(crate) fn reconstruct_vector(values: &dyn QuantizedVectorValues, index_bits: u8, ord: usize) -> Result<Vec<f32>, BbqError> {
+    Ok(OptimizedScalarQuantizer::dequantize(
+        values.try_get_unpacked_vector(ord)?,
+        index_bits,
+        &values.try_get_corrective_terms(ord)?,
+        values.get_centroid(),
+    ))
+}
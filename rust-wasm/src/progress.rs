@@ -0,0 +1,78 @@
+//! 长时间操作的进度事件
+//!
+//! 构建、压缩和搜索在关键步骤向可选的观察者发送结构化事件，
+//! 调用方据此显示进度条或记录日志；未设置观察者时不产生任何开销。
+//! WASM中观察者是一个JS回调，事件以普通对象传入
+
+/// 构建时每量化这么多个向量发送一次 `ChunkQuantized`
+pub const PROGRESS_CHUNK_SIZE: usize = 1024;
+
+/// 进度事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// 开始构建（或压缩时重新量化）
+    BuildStarted {
+        /// 待量化的向量数量
+        total: usize,
+        /// 向量维度
+        dimension: usize,
+    },
+    /// 质心已计算（或取自给定的统计量）
+    CentroidComputed {
+        /// 向量维度
+        dimension: usize,
+    },
+    /// 又量化了一批向量
+    ChunkQuantized {
+        /// 已量化的向量数量（累计）
+        n: usize,
+        /// 待量化的向量数量
+        total: usize,
+    },
+    /// 搜索中一批候选已评分
+    SearchBatchScored {
+        /// 已评分的候选数量（累计）
+        scored: usize,
+        /// 候选总数
+        total: usize,
+    },
+    /// 压缩完成
+    Compacted {
+        /// 新的代号
+        generation: u64,
+        /// 移除的向量数量
+        removed: usize,
+        /// 剩余的向量数量
+        remaining: usize,
+    },
+}
+
+impl ProgressEvent {
+    /// 事件名称，与WASM中事件对象的 `type` 字段一致
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProgressEvent::BuildStarted { .. } => "buildStarted",
+            ProgressEvent::CentroidComputed { .. } => "centroidComputed",
+            ProgressEvent::ChunkQuantized { .. } => "chunkQuantized",
+            ProgressEvent::SearchBatchScored { .. } => "searchBatchScored",
+            ProgressEvent::Compacted { .. } => "compacted",
+        }
+    }
+}
+
+/// 进度观察者
+///
+/// 要求Send + Sync，因为分片索引会在多个线程上评分同一个索引
+pub trait ProgressObserver: Send + Sync {
+    /// 接收一个事件；应尽快返回，评分循环会同步等待
+    fn on_progress(&self, event: &ProgressEvent);
+}
+
+impl<F> ProgressObserver for F
+where
+    F: Fn(&ProgressEvent) + Send + Sync,
+{
+    fn on_progress(&self, event: &ProgressEvent) {
+        self(event)
+    }
+}
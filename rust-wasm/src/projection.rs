@@ -0,0 +1,229 @@
+//! 二维投影
+//!
+//! 在浏览器中绘制嵌入分布图时，需要为每个向量给出x/y坐标：
+//! - PCA：幂迭代求协方差矩阵的前两个主成分（不显式构造d×d矩阵），向量投影到这两个方向上
+//! - 图细化（可选）：以PCA结果为初始布局，在k近邻图上做类似UMAP的力导向迭代，
+//!   近邻之间相互吸引、随机采样的非近邻之间相互排斥，使局部结构更清晰
+
+use crate::error_codes::BbqError;
+use crate::knn_graph::KnnGraph;
+use crate::vector_similarity::fast_dot_product;
+use crate::vector_utils::{compute_centroid_compensated, normalize_vector};
+
+/// 默认的幂迭代次数
+pub const DEFAULT_POWER_ITERATIONS: usize = 64;
+
+/// 排斥力每个分量的上限，避免距离很近的两点被弹得过远
+const MAX_REPULSION: f32 = 4.0;
+
+/// 细化前布局缩放到的标准差；相对排斥力上限要足够大，否则紧密的簇会被整体弹散
+const LAYOUT_SCALE: f32 = 10.0;
+
+/// 投影配置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProjectionConfig {
+    /// 幂迭代次数
+    pub power_iterations: usize,
+    /// 图细化的迭代次数，0表示只做PCA
+    pub refine_iterations: usize,
+    /// 图细化使用的k近邻数量
+    pub neighbors: usize,
+    /// 随机种子
+    pub seed: u64,
+}
+
+impl Default for ProjectionConfig {
+    fn default() -> Self {
+        Self {
+            power_iterations: DEFAULT_POWER_ITERATIONS,
+            refine_iterations: 0,
+            neighbors: 15,
+            seed: 42,
+        }
+    }
+}
+
+/// 把向量投影到前两个主成分上
+///
+/// # 参数
+/// * `vectors` - 向量集合
+/// * `iterations` - 幂迭代次数
+/// * `seed` - 初始方向的随机种子
+///
+/// # 返回
+/// 与输入顺序一致的二维坐标
+pub fn project_pca(vectors: &[Vec<f32>], iterations: usize, seed: u64) -> Result<Vec<[f32; 2]>, BbqError> {
+    if iterations == 0 {
+        return Err(BbqError::invalid_argument("幂迭代次数必须大于0", "power iteration count must be greater than 0"));
+    }
+    let mean = compute_centroid_compensated(vectors)?;
+    let dimension = mean.len();
+    if let Some(vector) = vectors.iter().find(|vector| vector.len() != dimension) {
+        return Err(BbqError::dimension_mismatch(
+            format!("向量维度 {} 与第一个向量维度 {} 不匹配", vector.len(), dimension),
+            format!("vector dimension {} does not match the first vector's dimension {}", vector.len(), dimension),
+        ));
+    }
+    let centered: Vec<Vec<f32>> = vectors.iter()
+        .map(|vector| vector.iter().zip(&mean).map(|(v, m)| v - m).collect())
+        .collect();
+
+    let mut rng = fastrand::Rng::with_seed(seed);
+    let mut components: Vec<Vec<f32>> = Vec::with_capacity(2);
+    for _ in 0..2 {
+        let mut direction: Vec<f32> = (0..dimension).map(|_| rng.f32() - 0.5).collect();
+        for _ in 0..iterations {
+            // 协方差乘向量：Σ x (x·v)
+            let mut next = vec![0.0f32; dimension];
+            for vector in &centered {
+                let projection = fast_dot_product(vector, &direction);
+                for (n, &x) in next.iter_mut().zip(vector) {
+                    *n += x * projection;
+                }
+            }
+            // 去掉已求出的主成分方向
+            for component in &components {
+                let overlap = fast_dot_product(&next, component);
+                for (n, &c) in next.iter_mut().zip(component) {
+                    *n -= overlap * c;
+                }
+            }
+            if next.iter().all(|&n| n == 0.0) {
+                break;
+            }
+            normalize_vector(&mut next);
+            direction = next;
+        }
+        components.push(direction);
+    }
+
+    Ok(centered.iter()
+        .map(|vector| [fast_dot_product(vector, &components[0]), fast_dot_product(vector, &components[1])])
+        .collect())
+}
+
+/// 在k近邻图上做力导向细化
+///
+/// 坐标先缩放到标准差为10；每次迭代中，每个节点被近邻吸引（UMAP在a=b=1时的梯度），
+/// 并被一个随机采样的节点排斥，步长随迭代线性衰减。已删除的节点不移动，也不参与排斥
+///
+/// # 参数
+/// * `points` - 按序号排列的坐标，长度与图的节点数相同
+/// * `graph` - k近邻图
+/// * `iterations` - 迭代次数
+/// * `seed` - 负采样的随机种子
+pub fn refine_with_graph(points: &mut [[f32; 2]], graph: &KnnGraph, iterations: usize, seed: u64) -> Result<(), BbqError> {
+    if points.len() != graph.len() {
+        return Err(BbqError::dimension_mismatch(
+            format!("坐标数量 {} 与图的节点数 {} 不匹配", points.len(), graph.len()),
+            format!("point count {} does not match the graph node count {}", points.len(), graph.len()),
+        ));
+    }
+    let live: Vec<usize> = (0..graph.len()).filter(|&ord| graph.live[ord]).collect();
+    if live.len() < 2 {
+        return Ok(());
+    }
+
+    let count = live.len() as f32;
+    let mut mean = [0.0f32; 2];
+    for &ord in &live {
+        mean[0] += points[ord][0] / count;
+        mean[1] += points[ord][1] / count;
+    }
+    let variance: f32 = live.iter()
+        .map(|&ord| (points[ord][0] - mean[0]).powi(2) + (points[ord][1] - mean[1]).powi(2))
+        .sum::<f32>() / count;
+    let scale = if variance > 0.0 { LAYOUT_SCALE / variance.sqrt() } else { 1.0 };
+    for &ord in &live {
+        points[ord] = [(points[ord][0] - mean[0]) * scale, (points[ord][1] - mean[1]) * scale];
+    }
+
+    let mut rng = fastrand::Rng::with_seed(seed);
+    for iteration in 0..iterations {
+        let rate = 1.0 - iteration as f32 / iterations as f32;
+        for &ord in &live {
+            for &(other, _) in &graph.neighbors[ord] {
+                let delta = [points[other][0] - points[ord][0], points[other][1] - points[ord][1]];
+                let distance_sq = delta[0] * delta[0] + delta[1] * delta[1];
+                let pull = rate * 2.0 / (1.0 + distance_sq) / graph.k as f32;
+                points[ord][0] += pull * delta[0];
+                points[ord][1] += pull * delta[1];
+            }
+
+            let other = live[rng.usize(0..live.len())];
+            if other == ord {
+                continue;
+            }
+            let delta = [points[ord][0] - points[other][0], points[ord][1] - points[other][1]];
+            let distance_sq = delta[0] * delta[0] + delta[1] * delta[1];
+            let push = 2.0 / ((0.001 + distance_sq) * (1.0 + distance_sq));
+            points[ord][0] += rate * (push * delta[0]).clamp(-MAX_REPULSION, MAX_REPULSION);
+            points[ord][1] += rate * (push * delta[1]).clamp(-MAX_REPULSION, MAX_REPULSION);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pca_finds_dominant_directions() {
+        // 沿(1,1,0,...)方向的大方差加上沿第3维的中等方差和少量噪声
+        let mut rng = fastrand::Rng::with_seed(3);
+        let vectors: Vec<Vec<f32>> = (0..200)
+            .map(|_| {
+                let major = rng.f32() * 20.0 - 10.0;
+                let minor = rng.f32() * 4.0 - 2.0;
+                let mut vector: Vec<f32> = (0..8).map(|_| rng.f32() * 0.1).collect();
+                vector[0] += major;
+                vector[1] += major;
+                vector[2] += minor;
+                vector
+            })
+            .collect();
+        let points = project_pca(&vectors, DEFAULT_POWER_ITERATIONS, 1).unwrap();
+        assert_eq!(points.len(), 200);
+        let mean_x = points.iter().map(|p| p[0]).sum::<f32>() / 200.0;
+        assert!(mean_x.abs() < 1e-3);
+        // 第一个坐标恢复主方向上的位置（差一个符号）
+        let major: Vec<f32> = vectors.iter().map(|v| v[0] + v[1]).collect();
+        let major_mean = major.iter().sum::<f32>() / 200.0;
+        let covariance: f32 = points.iter().zip(&major).map(|(p, m)| p[0] * (m - major_mean)).sum();
+        let norm_points: f32 = points.iter().map(|p| p[0] * p[0]).sum::<f32>().sqrt();
+        let norm_major: f32 = major.iter().map(|m| (m - major_mean).powi(2)).sum::<f32>().sqrt();
+        assert!((covariance / (norm_points * norm_major)).abs() > 0.999);
+        let spread_x = points.iter().map(|p| p[0] * p[0]).sum::<f32>();
+        let spread_y = points.iter().map(|p| p[1] * p[1]).sum::<f32>();
+        assert!(spread_x > 10.0 * spread_y);
+        assert!(project_pca(&vectors, 0, 1).is_err());
+    }
+
+    #[test]
+    fn test_refine_pulls_neighbors_together() {
+        // 两组各5个点在PCA布局中交错，图中每组内部互为近邻
+        let mut points: Vec<[f32; 2]> = (0..10).map(|i| [i as f32, 0.0]).collect();
+        let group = |ord: usize| ord % 2;
+        let neighbors = (0..10)
+            .map(|ord| (0..10).filter(|&o| o != ord && group(o) == group(ord)).map(|o| (o, 1.0)).collect())
+            .collect();
+        let graph = KnnGraph { k: 4, neighbors, live: vec![true; 10] };
+        refine_with_graph(&mut points, &graph, 200, 7).unwrap();
+
+        let distance = |a: [f32; 2], b: [f32; 2]| ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt();
+        let mut within = 0.0;
+        let mut between = 0.0;
+        for a in 0..10 {
+            for b in a + 1..10 {
+                if group(a) == group(b) {
+                    within += distance(points[a], points[b]) / 20.0;
+                } else {
+                    between += distance(points[a], points[b]) / 25.0;
+                }
+            }
+        }
+        assert!(points.iter().all(|p| p[0].is_finite() && p[1].is_finite()));
+        assert!(within < between, "组内 {} 组间 {}", within, between);
+    }
+}
@@ -0,0 +1,183 @@
+//! 混合精度索引中提升了位数的向量
+//!
+//! 索引中的向量默认都以索引位数（通常1位）量化。量化误差最大的一小部分向量（或调用方标记的重要向量）
+//! 可以再以更高的位数（默认4位）量化一份，搜索时这些向量改用高位数的量化值评分，
+//! 其余向量仍走1位的批量评分。只为少数向量多存一份每维一个字节的量化值，换取更高的召回率
+
+use crate::error_codes::BbqError;
+use crate::binary_quantized_scorer::BinaryQuantizedScorer;
+use crate::optimized_scalar_quantizer::QuantizationResult;
+use crate::query_context::QueryContext;
+use std::collections::HashMap;
+use std::mem::size_of;
+
+/// 一个提升了位数的向量
+#[derive(Debug, Clone)]
+pub struct PromotedVector {
+    /// 以提升的位数量化的值（未打包，每维一个）
+    pub quantized: Vec<u8>,
+    /// 以提升的位数量化时的修正项
+    pub corrections: QuantizationResult,
+}
+
+/// 一代中所有提升了位数的向量（按序号）
+#[derive(Debug, Clone)]
+pub struct PromotedVectors {
+    /// 提升后的量化位数
+    bits: u8,
+    /// 序号到提升后的向量
+    entries: HashMap<usize, PromotedVector>,
+}
+
+impl PromotedVectors {
+    /// 创建空的集合
+    ///
+    /// # 参数
+    /// * `bits` - 提升后的量化位数（2-8）
+    pub fn new(bits: u8) -> Result<Self, BbqError> {
+        if !(2..=8).contains(&bits) {
+            return Err(BbqError::out_of_range(format!("提升的位数必须在2-8之间，当前为{}", bits), format!("promotion bits must be within 2-8, got {}", bits)));
+        }
+        Ok(Self { bits, entries: HashMap::new() })
+    }
+
+    /// 提升后的量化位数
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    /// 提升的向量数量
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 序号处的向量是否已提升
+    pub fn contains(&self, ord: usize) -> bool {
+        self.entries.contains_key(&ord)
+    }
+
+    /// 序号处提升后的向量
+    pub fn get(&self, ord: usize) -> Option<&PromotedVector> {
+        self.entries.get(&ord)
+    }
+
+    /// 所有提升的序号（升序）
+    pub fn ordinals(&self) -> Vec<usize> {
+        let mut ords: Vec<usize> = self.entries.keys().copied().collect();
+        ords.sort_unstable();
+        ords
+    }
+
+    /// 按序号升序遍历（序号, 提升后的向量）
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &PromotedVector)> {
+        self.ordinals().into_iter().filter_map(|ord| self.get(ord).map(|vector| (ord, vector)))
+    }
+
+    /// 提升或替换序号处的向量
+    pub(crate) fn insert(&mut self, ord: usize, vector: PromotedVector) {
+        self.entries.insert(ord, vector);
+    }
+
+    /// 取消序号处的提升，返回是否原本已提升
+    pub(crate) fn remove(&mut self, ord: usize) -> bool {
+        self.entries.remove(&ord).is_some()
+    }
+
+    /// 额外占用的字节数（量化值和修正项）
+    pub fn memory_bytes(&self) -> usize {
+        self.entries.values()
+            .map(|vector| vector.quantized.len() + size_of::<QuantizationResult>() + size_of::<usize>())
+            .sum()
+    }
+
+    /// 压缩后按新序号重新编号
+    ///
+    /// # 参数
+    /// * `live` - 压缩后保留的旧序号（升序），新序号为其位置
+    pub fn remap(&self, live: &[usize]) -> PromotedVectors {
+        let entries = self.entries.iter()
+            .filter_map(|(ord, vector)| live.binary_search(ord).ok().map(|new_ord| (new_ord, vector.clone())))
+            .collect();
+        PromotedVectors { bits: self.bits, entries }
+    }
+
+    /// 把候选中已提升的向量改用提升后的量化值评分
+    ///
+    /// # 参数
+    /// * `scored` - （序号, 分数），已提升的序号的分数被替换
+    pub fn rescore(
+        &self,
+        scorer: &BinaryQuantizedScorer,
+        context: &QueryContext,
+        scored: &mut [(usize, f32)],
+    ) -> Result<(), BbqError> {
+        if self.entries.is_empty() {
+            return Ok(());
+        }
+        for (ord, score) in scored.iter_mut() {
+            if let Some(vector) = self.entries.get(ord) {
+                *score = scorer.compute_multi_bit_index_score(context, &vector.quantized, &vector.corrections, self.bits)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 按量化质量从低到高选出要提升的序号
+///
+/// # 参数
+/// * `quality_scores` - 每个向量的量化质量
+/// * `fraction` - 提升的比例（0到1之间），至少提升一个（比例为0时不提升）
+/// * `eligible` - 可以提升的序号（如未删除、非退化）
+pub fn lowest_quality_ordinals(quality_scores: &[f32], fraction: f32, eligible: impl Fn(usize) -> bool) -> Vec<usize> {
+    let mut candidates: Vec<usize> = (0..quality_scores.len()).filter(|&ord| eligible(ord)).collect();
+    if fraction <= 0.0 || candidates.is_empty() {
+        return Vec::new();
+    }
+    let count = ((candidates.len() as f32 * fraction).ceil() as usize).clamp(1, candidates.len());
+    candidates.sort_by(|&a, &b| quality_scores[a].total_cmp(&quality_scores[b]).then(a.cmp(&b)));
+    candidates.truncate(count);
+    candidates.sort_unstable();
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lowest_quality_and_remap() {
+        let quality = [0.9, 0.2, 0.5, 0.1, 0.8];
+        assert_eq!(lowest_quality_ordinals(&quality, 0.4, |_| true), vec![1, 3]);
+        assert_eq!(lowest_quality_ordinals(&quality, 0.4, |ord| ord != 3), vec![1, 2]);
+        assert_eq!(lowest_quality_ordinals(&quality, 0.01, |_| true), vec![3]);
+        assert!(lowest_quality_ordinals(&quality, 0.0, |_| true).is_empty());
+
+        assert!(PromotedVectors::new(1).is_err());
+        let mut promoted = PromotedVectors::new(4).unwrap();
+        let vector = |value: u8| PromotedVector {
+            quantized: vec![value; 8],
+            corrections: QuantizationResult {
+                lower_interval: -1.0,
+                upper_interval: 1.0,
+                additional_correction: 0.0,
+                quantized_component_sum: 8.0 * value as f32,
+            },
+        };
+        promoted.insert(1, vector(1));
+        promoted.insert(3, vector(3));
+        assert_eq!(promoted.ordinals(), vec![1, 3]);
+        assert!(promoted.memory_bytes() >= 16);
+
+        // 序号1被压缩掉，序号3变为2
+        let remapped = promoted.remap(&[0, 2, 3]);
+        assert_eq!(remapped.ordinals(), vec![2]);
+        assert_eq!(remapped.get(2).unwrap().quantized[0], 3);
+        assert!(promoted.remove(1) && !promoted.remove(1));
+    }
+}
@@ -1,108 +1,70 @@
-/// 量化索引结构
-/// 对应TypeScript中的BinaryQuantizationFormat
-/// 
-/// 实现完整的二值量化索引系统，包括：
-/// - 索引构建
-/// - 查询功能
-/// - TopK搜索
-/// - 批量计算优化
-
-use crate::constants::{QUERY_BITS, INDEX_BITS};
-use crate::vector_similarity::SimilarityFunction;
-use crate::optimized_scalar_quantizer::{OptimizedScalarQuantizer, QuantizationResult};
-use crate::binary_quantized_scorer::BinaryQuantizedScorer;
-use crate::vector_utils::{compute_centroid, normalize_vector};
-
-/// 量化向量值接口
-pub trait QuantizedVectorValues {
-    /// 获取向量维度
-    fn dimension(&self) -> usize;
-    
-    /// 获取向量数量
-    fn size(&self) -> usize;
-    
-    /// 获取量化向量值
-    fn vector_value(&self, ord: usize) -> &[u8];
-    
-    /// 获取未打包的1位向量（用于4位查询）
-    fn get_unpacked_vector(&self, ord: usize) -> &[u8];
-    
-    /// 获取修正项
-    fn get_corrective_terms(&self, ord: usize) -> &QuantizationResult;
-    
-    /// 获取质心向量
-    fn get_centroid(&self) -> &[f32];
-    
-    /// 计算查询向量与质心的点积
-    fn get_centroid_dp(&self, query_vector: Option<&[f32]>) -> f32;
-}
+//! 量化索引结构
+//! 对应TypeScript中的BinaryQuantizationFormat
+//! 
+//! 实现完整的二值量化索引系统，包括：
+//! - 索引构建
+//! - 查询功能
+//! - TopK搜索
+//! - 批量计算优化
 
-/// 量化向量值实现
-pub struct QuantizedVectorValuesImpl {
-    /// 量化向量数组（打包格式）
-    vectors: Vec<Vec<u8>>,
-    /// 未打包的1位向量数组（用于4位查询）
-    unpacked_vectors: Vec<Vec<u8>>,
-    /// 修正项数组
-    corrections: Vec<QuantizationResult>,
-    /// 质心向量
-    centroid: Vec<f32>,
-    /// 向量维度
-    dimension: usize,
-}
+use crate::error_codes::{BbqError, Subject};
+use crate::batch_sizing::recommended_batch_size;
+use crate::constants::{QUERY_BITS, INDEX_BITS, DEFAULT_RESCORE_OVERSAMPLE, BUDGET_SCAN_SHARE};
+#[cfg(feature = "eval")]
+use crate::constants::MAX_RESCORE_OVERSAMPLE;
+use crate::vector_similarity::{descending_score_order, fast_dot_product, SimilarityFunction};
+use crate::optimized_scalar_quantizer::{CorrectionPrecision, OptimizedScalarQuantizer, QuantizationResult, QuantizationScratch};
+use crate::binary_quantized_scorer::{BinaryQuantizedScorer, ScoringPrecision};
+use crate::correction_layout::{cluster_intervals, nearest_interval, CorrectionLayout, CORRECTION_GROUPS};
+use crate::vector_utils::{compute_centroid_compensated, compute_dimension_statistics, normalize_vector, DimensionStatistics};
+#[cfg(feature = "eval")]
+use crate::vector_utils::reservoir_sample;
+#[cfg(any(feature = "ivf", feature = "eval"))]
+use crate::rng::RngSource;
+use crate::score_normalization::{normalize_scores, ScoreNormalization};
+use crate::score_transform::ScoreTransform;
+use crate::score_histogram::ScoreHistogram;
+use crate::query_context::{quantization_fingerprint, QueryContext};
+use crate::bitwise_dot_product::{compute_int4_bit_dot_product, compute_packed_hamming_distance};
+use crate::byte_reader::{metric_to_code, Fnv1a};
+#[cfg(feature = "ivf")]
+use crate::ivf::{IvfPartition, IvfStatistics, ProbeStrategy};
+#[cfg(feature = "graph")]
+use crate::knn_graph::{build_knn_graph, Clustering, KnnGraph};
+use crate::dimension_stats::{compute_dimension_quality, DimensionQuality};
+#[cfg(feature = "graph")]
+use crate::projection::{project_pca, refine_with_graph, ProjectionConfig};
+#[cfg(feature = "eval")]
+use crate::evaluation::{compute_exact_top_k_where, mean_recall, GroundTruth};
+use crate::result_cache::{query_fingerprint, ResultCache, ResultCacheStats};
+use crate::timer::{elapsed_ms, now_ms};
+use crate::memory_limits::{estimate_index_bytes, reserve_headroom};
+use crate::warmup::{touch_vector_values, WarmupReport};
+use crate::warm_stats::WarmStats;
+use crate::ordinal_remap::OrdinalRemap;
+use crate::incremental_search::IncrementalSearch;
+use crate::search_streams::SearchStreams;
+use crate::search_scratch::SearchScratch;
+use crate::promoted_vectors::{lowest_quality_ordinals, PromotedVector, PromotedVectors};
+use crate::query_pack::ProgressiveResults;
+#[cfg(feature = "paranoid")]
+use crate::consistency;
+use crate::chunk_grouping::{group_adjacent_chunks, ChunkGroupingParams, ChunkProvenance, GroupedHit};
+use crate::filter::{Attributes, Filter, OrdinalBitset};
+use crate::index_generation::IndexGeneration;
+use crate::index_serialization::{deserialize_index, serialize_index};
+use crate::search_layout::SearchLayout;
+use crate::search_experiment::{ExperimentLayout, ExperimentRun, ExperimentState, SearchExperiment, SearchExperimentStats};
+use crate::original_vectors::{reconstruct_vector, OriginalVectorEncoding, OriginalVectors};
+use crate::progress::{ProgressEvent, ProgressObserver, PROGRESS_CHUNK_SIZE};
 
-impl QuantizedVectorValuesImpl {
-    /// 创建新的量化向量值实例
-    pub fn new(
-        vectors: Vec<Vec<u8>>,
-        unpacked_vectors: Vec<Vec<u8>>,
-        corrections: Vec<QuantizationResult>,
-        centroid: Vec<f32>,
-    ) -> Self {
-        let dimension = centroid.len();
-        Self {
-            vectors,
-            unpacked_vectors,
-            corrections,
-            centroid,
-            dimension,
-        }
-    }
-}
+pub use crate::quantized_vector_values::{QuantizedVectorValues, QuantizedVectorValuesImpl};
 
-impl QuantizedVectorValues for QuantizedVectorValuesImpl {
-    fn dimension(&self) -> usize {
-        self.dimension
-    }
-    
-    fn size(&self) -> usize {
-        self.vectors.len()
-    }
-    
-    fn vector_value(&self, ord: usize) -> &[u8] {
-        &self.vectors[ord]
-    }
-    
-    fn get_unpacked_vector(&self, ord: usize) -> &[u8] {
-        &self.unpacked_vectors[ord]
-    }
-    
-    fn get_corrective_terms(&self, ord: usize) -> &QuantizationResult {
-        &self.corrections[ord]
-    }
-    
-    fn get_centroid(&self) -> &[f32] {
-        &self.centroid
-    }
-    
-    fn get_centroid_dp(&self, query_vector: Option<&[f32]>) -> f32 {
-        if let Some(qv) = query_vector {
-            crate::vector_utils::compute_dot_product(qv, &self.centroid)
-        } else {
-            crate::vector_utils::compute_dot_product(&self.centroid, &self.centroid)
-        }
-    }
-}
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
 
 /// 查询结果
 #[derive(Debug, Clone)]
@@ -113,6 +75,21 @@ pub struct QueryResult {
     pub score: f32,
     /// 原始分数（可选）
     pub original_score: Option<f32>,
+    /// 各种距离（仅在 `SearchParams::include_distances` 时计算）
+    pub distances: Option<HitDistances>,
+}
+
+/// 单个命中的各种距离，供分析使用
+#[derive(Debug, Clone, PartialEq)]
+pub struct HitDistances {
+    /// 量化分数（未经质量加权和重排）
+    pub quantized_score: f32,
+    /// 量化查询与索引向量的原始位点积
+    pub bit_dot_product: i32,
+    /// 查询符号位与索引向量的汉明距离（多位查询取最高位平面；多位索引没有符号位，为None）
+    pub hamming_distance: Option<u32>,
+    /// 原始向量上的精确分数（保留原始向量时才有）
+    pub exact_score: Option<f32>,
 }
 
 /// 量化索引配置
@@ -128,6 +105,55 @@ pub struct QuantizedIndexConfig {
     pub lambda: Option<f32>,
     /// 优化迭代次数（默认5）
     pub iters: Option<usize>,
+    /// 是否保留原始向量（重排和召回评估需要，默认false）
+    pub keep_original_vectors: bool,
+    /// 保留的原始向量的存储编码（默认f32，可压缩为f16或int8残差）
+    pub original_encoding: OriginalVectorEncoding,
+    /// 结果缓存容量（默认0，即不缓存）
+    pub result_cache_capacity: usize,
+    /// 修正项与评分的累加精度（默认f32）
+    pub correction_precision: CorrectionPrecision,
+    /// 评分公式使用严格公式还是快速近似（默认严格，启用fast-math特性时默认快速）
+    pub scoring_precision: ScoringPrecision,
+    /// 构建时如何处理退化向量（减去质心后为常量，包括全零向量）
+    pub degenerate_vectors: DegenerateVectorPolicy,
+    /// 修正项的存放布局（默认按向量存放），见 `benchmark_correction_layouts`；
+    /// 低维时可选半精度布局把修正项减半，召回率略有下降；
+    /// 规模很大时可选分组布局，区间按组共享，每个向量只占6字节，召回率下降更多
+    pub correction_layout: CorrectionLayout,
+    /// 是否像Lucene一样把维度对齐到64位后再打包（默认false）
+    ///
+    /// 对齐后打包向量的布局与Lucene相同，可以直接与ES/OpenSearch交换；
+    /// 补出的位为0，分数不变，只要求1位索引
+    pub discretize_dimensions: bool,
+    /// 声明输入已经是单位向量（只对余弦相似度有意义，默认false）
+    ///
+    /// 构建和搜索时不再复制并归一化向量，也不计算模长；上游已经归一化嵌入时可以减少构建时间和内存分配，
+    /// 输入不是单位向量时分数不正确
+    pub prenormalized: bool,
+    /// 构建时以 `promoted_bits` 额外量化的向量比例（默认0，即不提升）
+    ///
+    /// 按量化质量从低到高选择，搜索时这些向量改用高位数的量化值评分；
+    /// 也可以在构建后用 `promote_vectors` 标记重要的向量
+    pub promote_fraction: f32,
+    /// 提升的向量使用的量化位数（默认4，须大于index_bits）
+    pub promoted_bits: u8,
+}
+
+/// 退化向量的处理方式
+///
+/// 全零或常量向量（减去质心后各分量相同）无法进行区间优化，
+/// 量化时总是使用中性修正项（区间收缩为该常量、量化值全为0），
+/// 评分结果与按原向量计算的一致；此设置决定构建时是否保留它们
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DegenerateVectorPolicy {
+    /// 保留并参与搜索（默认）
+    #[default]
+    Keep,
+    /// 构建后立即标记为已删除，搜索时跳过
+    Skip,
+    /// 构建失败并报告第一个退化向量
+    Reject,
 }
 
 impl Default for QuantizedIndexConfig {
@@ -138,10 +164,181 @@ impl Default for QuantizedIndexConfig {
             similarity_function: SimilarityFunction::Cosine,
             lambda: None,
             iters: None,
+            keep_original_vectors: false,
+            original_encoding: OriginalVectorEncoding::F32,
+            result_cache_capacity: 0,
+            correction_precision: CorrectionPrecision::Single,
+            scoring_precision: ScoringPrecision::default(),
+            degenerate_vectors: DegenerateVectorPolicy::Keep,
+            correction_layout: CorrectionLayout::ArrayOfStructs,
+            discretize_dimensions: false,
+            prenormalized: false,
+            promote_fraction: 0.0,
+            promoted_bits: 4,
         }
     }
 }
 
+/// 重排过采样设置
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RescoreOversample {
+    /// 不重排（默认）
+    #[default]
+    Disabled,
+    /// 固定倍数：先取 k * 倍数 个候选，再用原始向量重排
+    Fixed(f32),
+    /// 使用索引校准得到的倍数，未校准时使用默认倍数
+    Adaptive,
+}
+
+/// 搜索参数
+#[derive(Debug, Clone, Default)]
+pub struct SearchParams {
+    /// Top-K分数归一化方式（默认不归一化）
+    pub normalization: ScoreNormalization,
+    /// 重排过采样（需要保留原始向量）
+    pub rescore_oversample: RescoreOversample,
+    /// 量化质量混合权重（0到1，默认0）
+    ///
+    /// 量化分数按 `score * (1 - w + w * quality)` 调整，使量化质量差的向量排名靠后；
+    /// 只作用于量化分数，重排后的精确分数不受影响
+    pub quality_weight: f32,
+    /// 批量评分的批大小，为None时按维度、k和缓存提示自动选择
+    pub batch_size: Option<usize>,
+    /// IVF列表探测策略（需要先调用build_ivf），为None时搜索全部向量
+    #[cfg(feature = "ivf")]
+    pub nprobe: Option<ProbeStrategy>,
+    /// 为每个结果附带位点积、汉明距离和精确分数（默认关闭，只对返回的k个结果计算）
+    pub include_distances: bool,
+}
+
+/// 抽样搜索结果
+#[derive(Debug, Clone)]
+pub struct SampledSearchResults {
+    /// 样本中的前k个结果（量化分数）
+    pub results: Vec<QueryResult>,
+    /// 实际评分的向量数量
+    pub sampled: usize,
+    /// 估计的召回率（相对完整的量化搜索）
+    ///
+    /// 每个真正的近邻以样本比例的概率落入样本，且落入样本后一定排在样本的前k个中，
+    /// 因此期望召回率等于实际评分的向量占未删除向量的比例
+    pub estimated_recall: f32,
+}
+
+/// 附带分数直方图的搜索结果
+#[derive(Debug, Clone)]
+pub struct HistogramSearchResults {
+    /// 前k个结果
+    pub results: Vec<QueryResult>,
+    /// 全部候选量化分数的直方图
+    pub histogram: ScoreHistogram,
+}
+
+/// 限时搜索结果
+#[derive(Debug, Clone)]
+pub struct BudgetedSearchResults {
+    /// 预算内能得到的前k个结果
+    pub results: Vec<QueryResult>,
+    /// 实际评分的向量数量
+    pub scored: usize,
+    /// 因预算不足未评分的候选数量
+    pub skipped: usize,
+    /// 因预算不足未探测的IVF列表数量（未使用IVF时为0）
+    pub probes_skipped: usize,
+    /// 请求的重排过采样倍数
+    pub oversample_requested: Option<f32>,
+    /// 实际使用的过采样倍数，预算不足以重排k个候选时为None
+    pub oversample_used: Option<f32>,
+    /// 估计的召回率（相对完整的量化搜索），不使用IVF时评分的向量是均匀样本
+    pub estimated_recall: f32,
+    /// 实际耗时（毫秒）
+    pub elapsed_ms: f64,
+    /// 是否因预算不足减少了工作量
+    pub budget_exhausted: bool,
+}
+
+/// 压缩报告
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactionReport {
+    /// 压缩后的代号
+    pub generation: u64,
+    /// 移除的向量数量
+    pub removed: usize,
+    /// 剩余的向量数量
+    pub remaining: usize,
+    /// 旧序号到新序号的映射，用于更新压缩前保存的序号
+    pub remap: OrdinalRemap,
+}
+
+/// 批量操作中的一项，见 `QuantizedIndex::apply_batch`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "op", rename_all = "snake_case"))]
+pub enum IndexOp {
+    /// 追加向量（按当前质心量化）并设置其属性
+    Insert {
+        vector: Vec<f32>,
+        #[cfg_attr(feature = "serde", serde(default))]
+        attributes: Attributes,
+    },
+    /// 用新向量替换序号处的向量，序号、属性、来源和过期时间不变
+    Update { ord: usize, vector: Vec<f32> },
+    /// 删除序号处的向量
+    Delete { ord: usize },
+}
+
+/// 批量操作报告
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchReport {
+    /// 应用后的代号
+    pub generation: u64,
+    /// 插入的向量得到的序号（按操作顺序）
+    pub inserted: Vec<usize>,
+    /// 更新的向量数量
+    pub updated: usize,
+    /// 本次新删除的向量数量
+    pub deleted: usize,
+}
+
+/// 单个向量的打包数据，供在别处按新查询重新评分
+#[derive(Debug, Clone)]
+pub struct PackedVectorRecord {
+    /// 序号
+    pub ord: usize,
+    /// 打包后的量化向量字节
+    pub packed: Vec<u8>,
+    /// 修正项
+    pub corrections: QuantizationResult,
+    /// 由量化值重建的向量（仅在请求时计算）
+    pub reconstruction: Option<Vec<f32>>,
+}
+
+/// 索引内存占用
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexMemoryStats {
+    /// 量化向量（打包和未打包）、修正项和质心占用的字节数
+    pub quantized_bytes: u64,
+    /// 保留的原始向量占用的字节数
+    pub original_bytes: u64,
+    /// 原始向量的存储编码，未保留时为None
+    pub original_encoding: Option<OriginalVectorEncoding>,
+    /// 搜索优化布局占用的字节数，未转换时为0
+    pub search_layout_bytes: u64,
+    /// 提升了位数的向量额外占用的字节数
+    pub promoted_bytes: u64,
+}
+
+impl IndexMemoryStats {
+    /// 总字节数
+    pub fn total_bytes(&self) -> u64 {
+        self.quantized_bytes
+            .saturating_add(self.original_bytes)
+            .saturating_add(self.search_layout_bytes)
+            .saturating_add(self.promoted_bytes)
+    }
+}
+
 /// 量化索引结构
 pub struct QuantizedIndex {
     /// 索引配置
@@ -150,37 +347,129 @@ pub struct QuantizedIndex {
     quantizer: OptimizedScalarQuantizer,
     /// 二值量化评分器
     scorer: BinaryQuantizedScorer,
-    /// 量化向量值
-    quantized_vectors: Option<Box<dyn QuantizedVectorValues>>,
+    /// 当前代（量化向量及所有按序号存放的数据），构建前为None
+    generation: RwLock<Option<Arc<IndexGeneration>>>,
+    /// 下一代的代号
+    next_generation: AtomicU64,
+    /// 校准得到的过采样倍数，重建索引时保留
+    learned_oversample: Option<f32>,
+    /// 最终分数变换，在归一化之后应用于所有搜索结果
+    score_transform: Option<ScoreTransform>,
+    /// 搜索结果缓存，索引变更时失效
+    result_cache: Mutex<ResultCache>,
+    /// 使用统计，重建索引时保留，压缩时随序号重新编号
+    warm_stats: Mutex<WarmStats>,
+    /// 可取代的搜索流，每个流最多一个进行中的增量搜索
+    search_streams: Mutex<SearchStreams>,
+    /// 进行中的搜索实验，为None时不抽样
+    experiment: Mutex<Option<ExperimentState>>,
+    /// 搜索复用的临时缓冲区
+    search_scratch: Mutex<SearchScratch>,
+    /// 进度观察者，为None时不发送事件
+    progress: Option<Arc<dyn ProgressObserver>>,
+    /// 是否已冻结为只读
+    frozen: AtomicBool,
 }
 
 impl QuantizedIndex {
     /// 创建新的量化索引实例
-    pub fn new(config: QuantizedIndexConfig) -> Result<Self, String> {
+    pub fn new(config: QuantizedIndexConfig) -> Result<Self, BbqError> {
         // 验证配置参数
-        if config.query_bits < 1 || config.query_bits > 8 {
-            return Err("query_bits必须在1-8之间".to_string());
+        if !(1..=8).contains(&config.query_bits) {
+            return Err(BbqError::out_of_range("query_bits必须在1-8之间", "query_bits must be within 1-8"));
         }
-        if config.index_bits < 1 || config.index_bits > 8 {
-            return Err("index_bits必须在1-8之间".to_string());
+        if !(1..=8).contains(&config.index_bits) {
+            return Err(BbqError::out_of_range("index_bits必须在1-8之间", "index_bits must be within 1-8"));
+        }
+        if config.discretize_dimensions && config.index_bits != 1 {
+            return Err(BbqError::invalid_argument("维度对齐只支持1位索引", "dimension discretization only supports 1-bit indexes"));
+        }
+        if !(0.0..=1.0).contains(&config.promote_fraction) {
+            return Err(BbqError::out_of_range(
+                format!("提升比例必须在0-1之间，当前为{}", config.promote_fraction),
+                format!("promotion fraction must be within 0-1, got {}", config.promote_fraction),
+            ));
+        }
+        if config.promote_fraction > 0.0 && config.promoted_bits <= config.index_bits {
+            return Err(BbqError::invalid_argument(
+                format!("提升的位数 {} 必须大于索引位数 {}", config.promoted_bits, config.index_bits),
+                format!("promotion bits {} must exceed the index bits {}", config.promoted_bits, config.index_bits),
+            ));
         }
 
         let quantizer = OptimizedScalarQuantizer::new(
             config.lambda,
             config.iters,
             Some(config.similarity_function),
-        );
+        ).with_correction_precision(config.correction_precision);
 
-        let scorer = BinaryQuantizedScorer::new(config.similarity_function);
+        let scorer = BinaryQuantizedScorer::new(config.similarity_function)
+            .with_correction_precision(config.correction_precision)
+            .with_scoring_precision(config.scoring_precision);
+        let result_cache = Mutex::new(ResultCache::new(config.result_cache_capacity));
 
         Ok(Self {
             config,
             quantizer,
             scorer,
-            quantized_vectors: None,
+            generation: RwLock::new(None),
+            next_generation: AtomicU64::new(1),
+            learned_oversample: None,
+            score_transform: None,
+            result_cache,
+            warm_stats: Mutex::new(WarmStats::default()),
+            search_streams: Mutex::new(SearchStreams::new()),
+            experiment: Mutex::new(None),
+            search_scratch: Mutex::new(SearchScratch::new()),
+            progress: None,
+            frozen: AtomicBool::new(false),
         })
     }
 
+    /// 冻结为只读：之后所有修改数据的接口（构建、删除、属性、过期、压缩、批量操作、IVF）都返回错误
+    ///
+    /// 用于从CDN等处加载的已发布索引，避免内存中的副本与规范快照悄悄分叉。
+    /// 搜索、缓存、分数变换等不改变存储数据的配置仍然可用
+    pub fn freeze(&self) {
+        self.frozen.store(true, Ordering::Release);
+    }
+
+    /// 解除只读冻结
+    pub fn unfreeze(&self) {
+        self.frozen.store(false, Ordering::Release);
+    }
+
+    /// 是否已冻结为只读
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.load(Ordering::Acquire)
+    }
+
+    /// 冻结时返回错误
+    fn ensure_writable(&self) -> Result<(), BbqError> {
+        if self.is_frozen() {
+            return Err(BbqError::read_only(Subject::new("索引", "index")));
+        }
+        Ok(())
+    }
+
+    /// 设置进度观察者，构建、压缩和搜索时接收进度事件
+    pub fn with_progress_observer(mut self, observer: Arc<dyn ProgressObserver>) -> Self {
+        self.progress = Some(observer);
+        self
+    }
+
+    /// 替换或移除进度观察者
+    pub fn set_progress_observer(&mut self, observer: Option<Arc<dyn ProgressObserver>>) {
+        self.progress = observer;
+    }
+
+    /// 向观察者发送事件
+    fn emit(&self, event: ProgressEvent) {
+        if let Some(observer) = &self.progress {
+            observer.on_progress(&event);
+        }
+    }
+
     /// 构建索引
     /// 
     /// # 参数
@@ -188,23 +477,95 @@ impl QuantizedIndex {
     /// 
     /// # 返回
     /// 量化向量值
-    pub fn build_index(&mut self, vectors: &[Vec<f32>]) -> Result<&dyn QuantizedVectorValues, String> {
-        if vectors.is_empty() {
-            return Err("向量集合不能为空".to_string());
+    pub fn build_index(&mut self, vectors: &[Vec<f32>]) -> Result<&dyn QuantizedVectorValues, BbqError> {
+        self.build_index_internal(vectors, None)
+    }
+
+    /// 使用预先给定的数据集统计量构建索引
+    ///
+    /// 质心取统计量中的均值，区间按数据集的合并标准差初始化；
+    /// 适用于首批数据量少或分布偏斜、但整体分布已知的流式构建
+    ///
+    /// # 参数
+    /// * `vectors` - 原始向量集合
+    /// * `statistics` - 预处理后（余弦时为归一化后）向量的每维统计量
+    pub fn build_index_with_statistics(
+        &mut self,
+        vectors: &[Vec<f32>],
+        statistics: &DimensionStatistics,
+    ) -> Result<&dyn QuantizedVectorValues, BbqError> {
+        statistics.validate()?;
+        self.build_index_internal(vectors, Some(statistics))
+    }
+
+    /// 使用代表性样本构建索引
+    ///
+    /// # 参数
+    /// * `vectors` - 原始向量集合
+    /// * `sample` - 代表整体分布的样本向量
+    pub fn build_index_with_sample(
+        &mut self,
+        vectors: &[Vec<f32>],
+        sample: &[Vec<f32>],
+    ) -> Result<&dyn QuantizedVectorValues, BbqError> {
+        let statistics = self.statistics_from_sample(sample)?;
+        self.build_index_internal(vectors, Some(&statistics))
+    }
+
+    /// 按索引的预处理方式（余弦时归一化）计算样本的每维统计量
+    pub fn statistics_from_sample(&self, sample: &[Vec<f32>]) -> Result<DimensionStatistics, BbqError> {
+        if sample.is_empty() {
+            return Err(BbqError::empty_input("样本不能为空", "the sample must not be empty"));
         }
+        compute_dimension_statistics(&self.preprocess_vectors(sample))
+    }
 
-        // 标准化向量（如果使用余弦相似度）
-        let processed_vectors = if self.config.similarity_function == SimilarityFunction::Cosine {
-            vectors.iter()
+    /// 保留原始向量时使用的编码
+    fn original_encoding(&self) -> Option<OriginalVectorEncoding> {
+        self.config.keep_original_vectors.then_some(self.config.original_encoding)
+    }
+
+    /// 是否需要归一化输入（余弦相似度且未声明输入已归一化）
+    fn normalizes_inputs(&self) -> bool {
+        self.config.similarity_function == SimilarityFunction::Cosine && !self.config.prenormalized
+    }
+
+    /// 标准化向量（如果使用余弦相似度），不需要时直接借用输入
+    fn preprocess_vectors<'a>(&self, vectors: &'a [Vec<f32>]) -> Cow<'a, [Vec<f32>]> {
+        if self.normalizes_inputs() {
+            Cow::Owned(vectors.iter()
                 .map(|vec| {
                     let mut vec_copy = vec.clone();
                     normalize_vector(&mut vec_copy);
                     vec_copy
                 })
-                .collect()
+                .collect())
         } else {
-            vectors.to_vec()
-        };
+            Cow::Borrowed(vectors)
+        }
+    }
+
+    /// 原始输入的模长，声明输入已归一化时全为1
+    fn input_norms(&self, vectors: &[Vec<f32>]) -> Vec<f32> {
+        if self.config.prenormalized && self.config.similarity_function == SimilarityFunction::Cosine {
+            vec![1.0; vectors.len()]
+        } else {
+            vectors.iter().map(|vector| fast_dot_product(vector, vector).sqrt()).collect()
+        }
+    }
+
+    fn build_index_internal(
+        &mut self,
+        vectors: &[Vec<f32>],
+        statistics: Option<&DimensionStatistics>,
+    ) -> Result<&dyn QuantizedVectorValues, BbqError> {
+        self.ensure_writable()?;
+        if vectors.is_empty() {
+            return Err(BbqError::empty_input("向量集合不能为空", "vector collection must not be empty"));
+        }
+
+        let processed_vectors = self.preprocess_vectors(vectors);
+        let norms = self.input_norms(vectors);
 
         let first_vector = &processed_vectors[0];
         let dimension = first_vector.len();
@@ -212,9 +573,9 @@ impl QuantizedIndex {
         // 检查所有向量维度是否一致
         for (i, vector) in processed_vectors.iter().enumerate() {
             if vector.len() != dimension {
-                return Err(format!(
-                    "向量 {} 维度 {} 与第一个向量维度 {} 不匹配",
-                    i, vector.len(), dimension
+                return Err(BbqError::dimension_mismatch(
+                    format!("向量 {} 维度 {} 与第一个向量维度 {} 不匹配", i, vector.len(), dimension),
+                    format!("vector {} has dimension {}, unlike the first vector's dimension {}", i, vector.len(), dimension),
                 ));
             }
         }
@@ -223,63 +584,232 @@ impl QuantizedIndex {
         for (i, vector) in processed_vectors.iter().enumerate() {
             for (j, &val) in vector.iter().enumerate() {
                 if !val.is_finite() {
-                    return Err(format!(
-                        "向量 {} 位置 {} 包含无效值: {}",
-                        i, j, val
+                    return Err(BbqError::invalid_value(
+                        format!("向量 {} 位置 {} 包含无效值: {}", i, j, val),
+                        format!("vector {} contains an invalid value at position {}: {}", i, j, val),
                     ));
                 }
             }
         }
 
-        // 1. 计算质心
-        let centroid = compute_centroid(&processed_vectors)?;
+        // 检查索引大小是否超出当前构建的寻址上限，并确认堆中能容纳构建缓冲区
+        reserve_headroom(
+            estimate_index_bytes(
+                processed_vectors.len(),
+                dimension,
+                self.config.index_bits,
+                self.original_encoding(),
+            ),
+            Subject::new("量化索引", "quantized index"),
+        )?;
+
+        self.emit(ProgressEvent::BuildStarted { total: processed_vectors.len(), dimension });
+
+        // 1. 计算质心（给定统计量时直接使用其均值）
+        let (centroid, initial_std) = match statistics {
+            Some(statistics) => {
+                if statistics.dimension() != dimension {
+                    return Err(BbqError::dimension_mismatch(
+                        format!("统计量维度 {} 与向量维度 {} 不匹配", statistics.dimension(), dimension),
+                        format!("statistics dimension {} does not match vector dimension {}", statistics.dimension(), dimension),
+                    ));
+                }
+                (statistics.mean.clone(), Some(statistics.pooled_std()))
+            }
+            #[cfg(feature = "lucene_parity")]
+            None => (crate::lucene_parity::centroid(&processed_vectors)?, None),
+            #[cfg(not(feature = "lucene_parity"))]
+            None => (compute_centroid_compensated(&processed_vectors)?, None),
+        };
+        self.emit(ProgressEvent::CentroidComputed { dimension });
+
+        // 2. 量化所有向量，量化质量最低的一部分再以提升的位数量化
+        let (values, quality_scores) = self.quantize_vectors(&processed_vectors, centroid, norms, initial_std, None)?;
+        let promoted = if self.config.promote_fraction > 0.0 {
+            let ords = lowest_quality_ordinals(&quality_scores, self.config.promote_fraction, |ord| {
+                !values.get_corrective_terms(ord).is_degenerate()
+            });
+            let mut promoted = PromotedVectors::new(self.config.promoted_bits)?;
+            let vectors = ords.iter().map(|&ord| (ord, processed_vectors[ord].as_slice()));
+            self.quantize_promoted(vectors, values.get_centroid(), &mut promoted)?;
+            Some(Arc::new(promoted))
+        } else {
+            None
+        };
+
+        // 3. 创建新的一代
+        let number = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        let original_vectors = match self.original_encoding() {
+            Some(encoding) => Some(OriginalVectors::encode(processed_vectors.into_owned(), encoding, &values, self.config.index_bits)?),
+            None => None,
+        };
+        let mut generation = IndexGeneration::new(number, number, Arc::new(values), original_vectors, quality_scores);
+        generation.promoted = promoted;
+        match self.config.degenerate_vectors {
+            DegenerateVectorPolicy::Keep => {}
+            DegenerateVectorPolicy::Skip => {
+                for ord in generation.degenerate_ordinals() {
+                    generation.deleted.insert(ord);
+                }
+            }
+            DegenerateVectorPolicy::Reject => {
+                if let Some(&ord) = generation.degenerate_ordinals().first() {
+                    return Err(BbqError::invalid_value(
+                        format!("向量 {} 减去质心后为常量（如全零向量），无法量化", ord),
+                        format!("vector {} is constant after centering (e.g. all zeros) and cannot be quantized", ord),
+                    ));
+                }
+            }
+        }
+        self.result_cache().invalidate();
+        let slot = self.generation.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner());
+        Ok(slot.insert(Arc::new(generation)).values())
+    }
 
-        // 2. 量化所有向量
+    /// 以给定质心量化一组预处理后的向量
+    ///
+    /// 分组布局下每个向量改用最接近的共享区间重新量化：给定 `shared_intervals` 时沿用这些区间
+    /// （向已有的索引写入时保持组数不变），否则由这批向量的区间聚类得到
+    ///
+    /// # 返回
+    /// (量化向量值, 每个向量的量化质量)
+    fn quantize_vectors(
+        &self,
+        processed_vectors: &[Vec<f32>],
+        centroid: Vec<f32>,
+        norms: Vec<f32>,
+        initial_std: Option<f32>,
+        shared_intervals: Option<Vec<(f32, f32)>>,
+    ) -> Result<(QuantizedVectorValuesImpl, Vec<f32>), BbqError> {
+        let dimension = centroid.len();
         let mut quantized_vectors = Vec::with_capacity(processed_vectors.len());
         let mut unpacked_vectors = Vec::with_capacity(processed_vectors.len());
         let mut corrections = Vec::with_capacity(processed_vectors.len());
+        let mut quality_scores = Vec::with_capacity(processed_vectors.len());
+        // 所有向量共用一个工作缓冲区，每个向量只分配最终保存的量化（和打包）结果
+        let mut scratch = QuantizationScratch::with_dimension(dimension);
 
-        for vector in &processed_vectors {
+        let total = processed_vectors.len();
+        for (i, vector) in processed_vectors.iter().enumerate() {
             // 量化索引向量
             let mut quantized_vector = vec![0u8; dimension];
-            let correction = self.quantizer.scalar_quantize(
-                vector,
-                &mut quantized_vector,
-                self.config.index_bits,
-                &centroid,
-            )?;
-
-            // 根据量化位数选择正确的处理方法
-            let processed_vector = if self.config.index_bits == 1 {
-                // 1位索引量化：使用二进制打包
-                let packed_size = (dimension + 7) / 8;
+            let (processed_vector, correction) = if self.config.index_bits == 1 {
+                // 1位索引量化：符号位直接写入打包缓冲区
+                let packed_size = OptimizedScalarQuantizer::packed_len(dimension, self.config.discretize_dimensions);
                 let mut packed_vector = vec![0u8; packed_size];
-                OptimizedScalarQuantizer::pack_as_binary(&quantized_vector, &mut packed_vector)
-                    .map_err(|e| format!("二进制打包失败: {}", e))?;
-                
-                // 保存未打包的1位向量（用于4位查询）
-                unpacked_vectors.push(quantized_vector.clone());
-                packed_vector
+                let correction = self.quantizer.scalar_quantize_packed_with_scratch(
+                    vector,
+                    &mut packed_vector,
+                    &centroid,
+                    initial_std,
+                    &mut scratch,
+                )?;
+                // 未打包的1位向量由打包结果解出（4位查询和量化质量读取它）
+                OptimizedScalarQuantizer::unpack_from_binary(&packed_vector, &mut quantized_vector)?;
+                (packed_vector, correction)
             } else {
                 // 其他位数：直接使用量化结果
-                unpacked_vectors.push(quantized_vector.clone());
-                quantized_vector
+                let correction = self.quantizer.scalar_quantize_with_scratch(
+                    vector,
+                    &mut quantized_vector,
+                    self.config.index_bits,
+                    &centroid,
+                    initial_std,
+                    &mut scratch,
+                )?;
+                (quantized_vector.clone(), correction)
             };
+            quality_scores.push(OptimizedScalarQuantizer::compute_quantization_quality(
+                vector,
+                &centroid,
+                &quantized_vector,
+                self.config.index_bits,
+                &correction,
+            ));
 
+            unpacked_vectors.push(quantized_vector);
             quantized_vectors.push(processed_vector);
             corrections.push(correction);
+            if (i + 1).is_multiple_of(PROGRESS_CHUNK_SIZE) || i + 1 == total {
+                self.emit(ProgressEvent::ChunkQuantized { n: i + 1, total });
+            }
+        }
+
+        if self.config.correction_layout == CorrectionLayout::Grouped {
+            let intervals = shared_intervals
+                .filter(|intervals| !intervals.is_empty())
+                .unwrap_or_else(|| cluster_intervals(&corrections, CORRECTION_GROUPS));
+            for (i, vector) in processed_vectors.iter().enumerate() {
+                // 退化向量保留自己的区间
+                if corrections[i].is_degenerate() {
+                    continue;
+                }
+                let correction = &mut corrections[i];
+                let Some(group) = nearest_interval(&intervals, (correction.lower_interval, correction.upper_interval)) else {
+                    break;
+                };
+                (correction.lower_interval, correction.upper_interval) = intervals[group];
+                correction.quantized_component_sum = OptimizedScalarQuantizer::quantize_with_interval(
+                    vector,
+                    &centroid,
+                    self.config.index_bits,
+                    intervals[group],
+                    &mut unpacked_vectors[i],
+                )?;
+                quality_scores[i] = OptimizedScalarQuantizer::compute_quantization_quality(
+                    vector,
+                    &centroid,
+                    &unpacked_vectors[i],
+                    self.config.index_bits,
+                    correction,
+                );
+                if self.config.index_bits == 1 {
+                    quantized_vectors[i].fill(0);
+                    OptimizedScalarQuantizer::pack_as_binary(&unpacked_vectors[i], &mut quantized_vectors[i])
+                        .map_err(|e| e.context(Subject::new("二进制打包失败", "binary packing failed")))?;
+                } else {
+                    quantized_vectors[i].copy_from_slice(&unpacked_vectors[i]);
+                }
+            }
         }
 
-        // 3. 创建量化向量值对象
-        let quantized_values = Box::new(QuantizedVectorValuesImpl::new(
+        let values = QuantizedVectorValuesImpl::new(
             quantized_vectors,
             unpacked_vectors,
             corrections,
             centroid,
-        ));
+            norms,
+        )
+        .with_correction_layout(self.config.correction_layout)?
+        .with_discretized_dimensions(self.config.discretize_dimensions);
+        Ok((values, quality_scores))
+    }
 
-        self.quantized_vectors = Some(quantized_values);
-        Ok(self.quantized_vectors.as_ref().unwrap().as_ref())
+    /// 以提升的位数量化一组向量，加入（或替换）promoted中对应序号的记录
+    ///
+    /// # 参数
+    /// * `vectors` - （序号, 预处理后的向量）
+    /// * `centroid` - 质心向量
+    fn quantize_promoted<'a>(
+        &self,
+        vectors: impl IntoIterator<Item = (usize, &'a [f32])>,
+        centroid: &[f32],
+        promoted: &mut PromotedVectors,
+    ) -> Result<(), BbqError> {
+        let mut scratch = QuantizationScratch::with_dimension(centroid.len());
+        for (ord, vector) in vectors {
+            let mut quantized = vec![0u8; centroid.len()];
+            let corrections = self.quantizer.scalar_quantize_with_scratch(
+                vector,
+                &mut quantized,
+                promoted.bits(),
+                centroid,
+                None,
+                &mut scratch,
+            )?;
+            promoted.insert(ord, PromotedVector { quantized, corrections });
+        }
+        Ok(())
     }
 
     /// 量化查询向量
@@ -294,9 +824,9 @@ impl QuantizedIndex {
         &self,
         query_vector: &[f32],
         centroid: &[f32],
-    ) -> Result<(Vec<u8>, QuantizationResult), String> {
+    ) -> Result<(Vec<u8>, QuantizationResult), BbqError> {
         // 标准化查询向量（如果使用余弦相似度）
-        let processed_query_vector = if self.config.similarity_function == SimilarityFunction::Cosine {
+        let processed_query_vector = if self.normalizes_inputs() {
             let mut query_copy = query_vector.to_vec();
             normalize_vector(&mut query_copy);
             query_copy
@@ -338,159 +868,4415 @@ impl QuantizedIndex {
         &self,
         query_vector: &[f32],
         k: usize,
-    ) -> Result<Vec<QueryResult>, String> {
-        let quantized_vectors = self.quantized_vectors.as_ref()
-            .ok_or("索引未构建，请先调用build_index")?;
+    ) -> Result<Vec<QueryResult>, BbqError> {
+        self.search_with_params(query_vector, k, &SearchParams::default())
+    }
 
-        // 参数验证
-        if query_vector.is_empty() {
-            return Err("查询向量不能为空".to_string());
-        }
-        if k == 0 {
-            return Ok(Vec::new());
-        }
-        if query_vector.len() != quantized_vectors.dimension() {
-            return Err("查询向量维度与索引维度不匹配".to_string());
-        }
+    /// 使用搜索参数搜索最近邻
+    ///
+    /// # 参数
+    /// * `query_vector` - 查询向量
+    /// * `k` - 返回的最近邻数量
+    /// * `params` - 搜索参数
+    ///
+    /// # 返回
+    /// 查询结果数组
+    pub fn search_with_params(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        params: &SearchParams,
+    ) -> Result<Vec<QueryResult>, BbqError> {
+        let generation = self.snapshot()?;
+        let context = self.prepare_query_in(&generation, query_vector)?;
+        let results = self.search_with_context_in(&generation, &context, k, params);
+        self.recycle_query(context);
+        results
+    }
 
-        let centroid = quantized_vectors.get_centroid();
+    /// 预处理查询向量
+    ///
+    /// 归一化、量化和centroid_dp只计算一次，结果可在多个批次或多次搜索中复用
+    ///
+    /// # 参数
+    /// * `query_vector` - 查询向量
+    ///
+    /// # 返回
+    /// 查询上下文
+    pub fn prepare_query(&self, query_vector: &[f32]) -> Result<QueryContext, BbqError> {
+        let generation = self.snapshot()?;
+        self.prepare_query_in(&generation, query_vector)
+    }
 
-        // 1. 量化查询向量
-        let (quantized_query, query_corrections) = self.quantize_query_vector(
-            query_vector,
-            centroid,
-        )?;
+    /// 预处理带维度权重的查询
+    ///
+    /// 重排按加权的精确度量计算（欧氏距离为 `Σ w(q - t)²`，点积为 `Σ w·q·t`）；
+    /// 量化评分阶段无法对目标向量加权，改为量化 `c + w ⊙ (q - c)`（c为质心），
+    /// 被屏蔽的维度退化为质心，只近似地降低其贡献，需要精确加权时应启用重排
+    ///
+    /// # 参数
+    /// * `query_vector` - 查询向量
+    /// * `weights` - 每个维度的权重（非负，0表示屏蔽）
+    pub fn prepare_weighted_query(&self, query_vector: &[f32], weights: &[f32]) -> Result<QueryContext, BbqError> {
+        let generation = self.snapshot()?;
+        self.prepare_weighted_query_in(&generation, query_vector, Some(weights))
+    }
 
-        // 2. 计算所有目标向量的分数
-        let vector_count = quantized_vectors.size();
-        let k = k.min(vector_count);
+    /// 使用维度权重搜索最近邻
+    ///
+    /// # 参数
+    /// * `weights` - 每个维度的权重（非负，0表示屏蔽），见 `prepare_weighted_query`
+    pub fn search_weighted(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        weights: &[f32],
+        params: &SearchParams,
+    ) -> Result<Vec<QueryResult>, BbqError> {
+        let generation = self.snapshot()?;
+        let context = self.prepare_weighted_query_in(&generation, query_vector, Some(weights))?;
+        let results = self.search_with_context_in(&generation, &context, k, params);
+        self.recycle_query(context);
+        results
+    }
 
-        // 批量计算分数
-        let batch_size = 1000;
-        let mut all_results = Vec::with_capacity(vector_count);
-
-        for batch_start in (0..vector_count).step_by(batch_size) {
-            let batch_end = (batch_start + batch_size).min(vector_count);
-            let batch_indices: Vec<usize> = (batch_start..batch_end).collect();
-
-            // 准备批量数据
-            // 关键修复：对于1位索引，需要使用打包后的向量格式
-            let batch_vectors: Vec<Vec<u8>> = if self.config.index_bits == 1 {
-                // 1位索引：使用打包后的向量
-                batch_indices.iter()
-                    .map(|&idx| quantized_vectors.vector_value(idx).to_vec())
-                    .collect()
-            } else {
-                // 其他位数：使用未打包的向量
-                batch_indices.iter()
-                    .map(|&idx| quantized_vectors.get_unpacked_vector(idx).to_vec())
-                    .collect()
-            };
-            
-            let batch_corrections: Vec<QuantizationResult> = batch_indices.iter()
-                .map(|&idx| quantized_vectors.get_corrective_terms(idx).clone())
-                .collect();
+    /// 基于指定的一代预处理查询向量
+    pub(crate) fn prepare_query_in(&self, generation: &IndexGeneration, query_vector: &[f32]) -> Result<QueryContext, BbqError> {
+        self.prepare_weighted_query_in(generation, query_vector, None)
+    }
 
-            let batch_results = self.scorer.compute_batch_quantized_scores(
-                &quantized_query,
-                &query_corrections,
-                &batch_vectors,
-                &batch_corrections,
-                &batch_indices,
-                self.config.query_bits,
-                quantized_vectors.dimension(),
-                quantized_vectors.get_centroid_dp(Some(query_vector)),
-            )?;
+    /// 基于指定的一代预处理查询向量，可选地附带维度权重
+    fn prepare_weighted_query_in(
+        &self,
+        generation: &IndexGeneration,
+        query_vector: &[f32],
+        weights: Option<&[f32]>,
+    ) -> Result<QueryContext, BbqError> {
+        let quantized_vectors = generation.values();
+
+        // 参数验证
+        if query_vector.is_empty() {
+            return Err(BbqError::empty_input("查询向量不能为空", "query vector must not be empty"));
+        }
+        if query_vector.len() != quantized_vectors.dimension() {
+            return Err(BbqError::dimension_mismatch("查询向量维度与索引维度不匹配", "query dimension does not match the index dimension"));
+        }
 
-            for (i, result) in batch_results.into_iter().enumerate() {
-                all_results.push((batch_start + i, result.score));
+        if let Some(weights) = weights {
+            if weights.len() != query_vector.len() {
+                return Err(BbqError::dimension_mismatch(
+                    format!("维度权重长度 {} 与查询维度 {} 不匹配", weights.len(), query_vector.len()),
+                    format!("dimension weight length {} does not match query dimension {}", weights.len(), query_vector.len()),
+                ));
+            }
+            if let Some(bad) = weights.iter().find(|w| !w.is_finite() || **w < 0.0) {
+                return Err(BbqError::invalid_value(
+                    format!("维度权重必须是非负有限值，当前包含{}", bad),
+                    format!("dimension weights must be finite and non-negative, found {}", bad),
+                ));
             }
         }
 
-        // 3. 使用部分排序找到前k个最大值
-        all_results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        // 查询缓冲区取自复用的临时缓冲区
+        let (mut processed_query_vector, mut quantized_query, packed_query) = {
+            let mut scratch = self.search_scratch();
+            (
+                std::mem::take(&mut scratch.query),
+                std::mem::take(&mut scratch.quantized_query),
+                std::mem::take(&mut scratch.packed_query),
+            )
+        };
 
-        // 4. 构建结果
-        let top_k_results: Vec<QueryResult> = all_results
-            .into_iter()
-            .take(k)
-            .map(|(index, score)| QueryResult {
-                index,
-                score,
-                original_score: None,
-            })
-            .collect();
+        // 标准化查询向量（如果使用余弦相似度）
+        processed_query_vector.clear();
+        processed_query_vector.extend_from_slice(query_vector);
+        if self.normalizes_inputs() {
+            normalize_vector(&mut processed_query_vector);
+        }
 
-        Ok(top_k_results)
-    }
+        // 加权时量化 c + w ⊙ (q - c)
+        let centroid = quantized_vectors.get_centroid();
+        let weighted_query: Option<Vec<f32>> = weights.map(|weights| {
+            processed_query_vector.iter()
+                .zip(centroid)
+                .zip(weights)
+                .map(|((&q, &c), &w)| c + w * (q - c))
+                .collect()
+        });
 
-    /// 获取配置
-    pub fn get_config(&self) -> &QuantizedIndexConfig {
-        &self.config
+        quantized_query.clear();
+        quantized_query.resize(processed_query_vector.len(), 0);
+        let query_corrections = self.quantizer.scalar_quantize(
+            weighted_query.as_deref().unwrap_or(&processed_query_vector),
+            &mut quantized_query,
+            self.config.query_bits,
+            centroid,
+        )?;
+
+        // Lucene的评分减去质心与自身的点积
+        #[cfg(feature = "lucene_parity")]
+        let centroid_dp = crate::lucene_parity::dot_product(centroid, centroid);
+        #[cfg(not(feature = "lucene_parity"))]
+        let centroid_dp = quantized_vectors.get_centroid_dp(Some(weighted_query.as_deref().unwrap_or(&processed_query_vector)));
+        let mut context = QueryContext::with_packed_buffer(
+            processed_query_vector,
+            quantized_query,
+            query_corrections,
+            centroid_dp,
+            self.config.query_bits,
+            packed_query,
+        )?;
+        context.centroid_epoch = Some(generation.centroid_epoch());
+        context.dimension_weights = weights.map(<[f32]>::to_vec);
+        Ok(context)
     }
 
-    /// 获取量化器
-    pub fn get_quantizer(&self) -> &OptimizedScalarQuantizer {
-        &self.quantizer
+    /// 当前质心对应的量化指纹，Worker或服务端据此确认可以复用主线程预处理的查询
+    pub fn quantization_fingerprint(&self) -> Result<u64, BbqError> {
+        let generation = self.snapshot()?;
+        Ok(quantization_fingerprint(
+            self.config.similarity_function,
+            self.config.query_bits,
+            generation.values().get_centroid(),
+        ))
     }
 
-    /// 获取评分器
-    pub fn get_scorer(&self) -> &BinaryQuantizedScorer {
-        &self.scorer
+    /// 序列化为自描述的二进制格式（配置、质心、打包向量、修正项、墓碑、属性等），见 `index_serialization`
+    ///
+    /// 保存的是量化后的状态，`deserialize` 直接恢复而不重新量化
+    pub fn serialize(&self) -> Result<Vec<u8>, BbqError> {
+        let generation = self.snapshot()?;
+        serialize_index(&self.config, self.learned_oversample, &generation)
     }
 
-    /// 获取量化向量值
-    pub fn get_quantized_vectors(&self) -> Option<&dyn QuantizedVectorValues> {
-        self.quantized_vectors.as_ref().map(|qv| qv.as_ref())
+    /// 从 `serialize` 写出的字节恢复索引
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, BbqError> {
+        let snapshot = deserialize_index(bytes)?;
+        let mut index = QuantizedIndex::new(snapshot.config.clone())?;
+        index.learned_oversample = snapshot.learned_oversample;
+        let number = index.next_generation.fetch_add(1, Ordering::Relaxed);
+        let generation = snapshot.into_generation(number)?;
+        *index.generation.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(Arc::new(generation));
+        Ok(index)
     }
-}
+
+    /// 索引内容的64位哈希，可作为导出查询包的ETag或比对两个副本是否一致
+    ///
+    /// 计入影响评分的量化配置（度量、查询和索引位数、修正项精度、lambda、迭代次数）、质心，
+    /// 以及按序号顺序排列的未删除向量的修正项和打包向量。已删除的向量不计入，压缩前后哈希不变；
+    /// 浮点数按规范形式计入，同样的内容在原生平台和WASM上得到同样的哈希
+    pub fn content_hash(&self) -> Result<u64, BbqError> {
+        let generation = self.snapshot()?;
+        let values = generation.values();
+        let mut hasher = Fnv1a::new();
+        hasher.write(&[
+            metric_to_code(self.config.similarity_function),
+            self.config.query_bits,
+            self.config.index_bits,
+            (self.config.correction_precision == CorrectionPrecision::Double) as u8,
+        ]);
+        hasher.write_f32(self.config.lambda.unwrap_or(f32::NAN));
+        hasher.write_u32(self.config.iters.map_or(u32::MAX, |iters| iters.min(u32::MAX as usize - 1) as u32));
+        hasher.write_u32(values.dimension() as u32);
+        for &value in values.get_centroid() {
+            hasher.write_f32(value);
+        }
+        for (_, packed, corrections) in values.iter().filter(|(ord, ..)| !generation.is_deleted(*ord)) {
+            for value in [
+                corrections.lower_interval,
+                corrections.upper_interval,
+                corrections.additional_correction,
+                corrections.quantized_component_sum,
+            ] {
+                hasher.write_f32(value);
+            }
+            hasher.write_u32(packed.len() as u32);
+            hasher.write(packed);
+        }
+        Ok(hasher.finish())
+    }
+
+    /// 从 `QueryContext::serialize` 生成的字节块重建查询上下文
+    ///
+    /// 字节块的量化指纹必须与本索引当前的一致，重建的上下文绑定到当前质心版本
+    pub fn deserialize_prepared_query(&self, bytes: &[u8]) -> Result<QueryContext, BbqError> {
+        let generation = self.snapshot()?;
+        let fingerprint = quantization_fingerprint(
+            self.config.similarity_function,
+            self.config.query_bits,
+            generation.values().get_centroid(),
+        );
+        let mut context = QueryContext::deserialize(bytes, fingerprint)?;
+        self.check_context(&generation, &context)?;
+        context.centroid_epoch = Some(generation.centroid_epoch());
+        Ok(context)
+    }
+
+    /// 使用预处理好的查询上下文搜索最近邻
+    ///
+    /// # 参数
+    /// * `context` - 查询上下文
+    /// * `k` - 返回的最近邻数量
+    /// * `params` - 搜索参数
+    ///
+    /// # 返回
+    /// 查询结果数组
+    pub fn search_with_context(
+        &self,
+        context: &QueryContext,
+        k: usize,
+        params: &SearchParams,
+    ) -> Result<Vec<QueryResult>, BbqError> {
+        let generation = self.snapshot()?;
+        self.search_with_context_in(&generation, context, k, params)
+    }
+
+    /// 在指定的一代上使用查询上下文搜索
+    fn search_with_context_in(
+        &self,
+        generation: &IndexGeneration,
+        context: &QueryContext,
+        k: usize,
+        params: &SearchParams,
+    ) -> Result<Vec<QueryResult>, BbqError> {
+        self.check_context(generation, context)?;
+        if k == 0 {
+            return Ok(Vec::new());
+        }
+
+        // 有向量已过期但尚未清理时，缓存中的结果可能包含过期向量
+        let expiry_pending = generation.next_expiry.is_some_and(|expiry| expiry <= now_ms());
+        let results = if let Some(run) = self.draw_experiment(generation) {
+            self.search_experiment_pair(generation, context, k, params, run)?
+        } else if self.config.result_cache_capacity == 0 || expiry_pending {
+            self.search_uncached(generation, context, k, params, None)?
+        } else {
+            let key = query_fingerprint(context, k, params, generation.number());
+            let cached = self.result_cache().get(key);
+            match cached {
+                Some(results) => results,
+                None => {
+                    let results = self.search_uncached(generation, context, k, params, None)?;
+                    self.result_cache().put(key, results.clone());
+                    results
+                }
+            }
+        };
+
+        // 以代号0计算指纹，使重建前后的相同查询仍被识别为重复
+        self.warm_stats().record_query(
+            query_fingerprint(context, k, params, 0),
+            k,
+            results.iter().map(|result| result.index),
+        );
+        Ok(results)
+    }
+
+    /// 带过滤条件搜索最近邻（不经过结果缓存）
+    ///
+    /// # 参数
+    /// * `query_vector` - 查询向量
+    /// * `k` - 返回的最近邻数量
+    /// * `filter` - 过滤条件，只返回满足条件的向量
+    /// * `params` - 搜索参数
+    ///
+    /// # 返回
+    /// 查询结果数组
+    pub fn search_filtered(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        filter: &Filter,
+        params: &SearchParams,
+    ) -> Result<Vec<QueryResult>, BbqError> {
+        let generation = self.snapshot()?;
+        let context = self.prepare_query_in(&generation, query_vector)?;
+        if k == 0 {
+            return Ok(Vec::new());
+        }
+        self.search_uncached(&generation, &context, k, params, Some(filter))
+    }
+
+    /// 只对等间隔抽取的部分向量评分，得到即时的预览结果
+    ///
+    /// 按 `1 / sample_fraction` 的步长抽样，步长内的偏移由查询决定，
+    /// 同一查询的预览结果稳定，不同查询覆盖不同的子集；
+    /// 界面可以先展示预览和估计召回率，同时异步执行完整搜索
+    ///
+    /// # 参数
+    /// * `query_vector` - 查询向量
+    /// * `k` - 返回的结果数量
+    /// * `sample_fraction` - 抽样比例，(0, 1]
+    pub fn search_sampled(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        sample_fraction: f32,
+    ) -> Result<SampledSearchResults, BbqError> {
+        if !(sample_fraction > 0.0 && sample_fraction <= 1.0) {
+            return Err(BbqError::out_of_range(
+                format!("抽样比例必须在(0, 1]之间，当前为{}", sample_fraction),
+                format!("sample fraction must be within (0, 1], got {}", sample_fraction),
+            ));
+        }
+        let generation = self.snapshot()?;
+        let context = self.prepare_query_in(&generation, query_vector)?;
+
+        let stride = ((1.0 / sample_fraction).round() as usize).max(1);
+        let offset = context.quantized_query.iter()
+            .fold(0usize, |acc, &q| acc.wrapping_mul(31).wrapping_add(q as usize)) % stride;
+        let mut ords = Vec::new();
+        self.search_candidates(&generation, (offset..generation.size()).step_by(stride), None, &mut ords);
+
+        let mut scored = Vec::with_capacity(ords.len());
+        let batch_size = recommended_batch_size(generation.values().dimension(), k, None);
+        for batch in ords.chunks(batch_size) {
+            scored.extend(self.scorer.compute_batch_scores_excluding(&context, generation.values(), batch, generation.tombstones())?);
+        }
+        generation.rescore_promoted(&self.scorer, &context, &mut scored)?;
+        let sampled = scored.len();
+        scored.sort_by(|a, b| descending_score_order(a.1, b.1));
+
+        let live = generation.live_count();
+        Ok(SampledSearchResults {
+            results: scored.into_iter()
+                .take(k)
+                .map(|(index, score)| QueryResult { index, score, original_score: None, distances: None })
+                .collect(),
+            sampled,
+            estimated_recall: if live == 0 { 1.0 } else { (sampled as f32 / live as f32).min(1.0) },
+        })
+    }
+
+    /// 搜索最近邻，同时返回全部候选量化分数的直方图（不经过结果缓存）
+    ///
+    /// 直方图统计的是质量加权、重排、归一化和分数变换之前的量化分数，
+    /// 不返回每个分数即可展示相关度分布或选择自适应阈值
+    ///
+    /// # 参数
+    /// * `query_vector` - 查询向量
+    /// * `k` - 返回的结果数量
+    /// * `params` - 搜索参数
+    /// * `buckets` - 直方图桶数量
+    /// * `range` - 直方图范围，为None时使用本次查询分数的最小值和最大值
+    pub fn search_with_score_histogram(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        params: &SearchParams,
+        buckets: usize,
+        range: Option<(f32, f32)>,
+    ) -> Result<HistogramSearchResults, BbqError> {
+        let generation = self.snapshot()?;
+        let context = self.prepare_query_in(&generation, query_vector)?;
+        let oversample = self.resolve_oversample(&generation, params.rescore_oversample)?;
+        if !(0.0..=1.0).contains(&params.quality_weight) {
+            return Err(BbqError::out_of_range(
+                format!("量化质量权重必须在0-1之间，当前为{}", params.quality_weight),
+                format!("quality weight must be within 0-1, got {}", params.quality_weight),
+            ));
+        }
+        // 先检查直方图参数，避免无效参数时白白评分
+        if let Some((min, max)) = range {
+            ScoreHistogram::new(buckets, min, max)?;
+        } else if buckets == 0 {
+            return Err(BbqError::invalid_argument("直方图桶数量必须大于0", "histogram bucket count must be greater than 0"));
+        }
+
+        let mut scratch = self.take_scratch();
+        let results = self.score_candidates(&generation, &context, k, params, None, &self.scorer, true, &mut scratch)
+            .and_then(|()| {
+                let histogram = ScoreHistogram::from_scores(scratch.scored.iter().map(|&(_, score)| score), buckets, range)?;
+                let results = self.rank_scored(&generation, &context, &mut scratch.scored, k, params, oversample, None)?;
+                Ok(HistogramSearchResults { results, histogram })
+            });
+        self.return_scratch(scratch);
+        self.recycle_query(context);
+        results
+    }
+
+    /// 开始增量搜索
+    ///
+    /// 返回的状态每次 `pump` 只评分几批向量，适合在requestAnimationFrame或空闲回调中推进，
+    /// 见 `IncrementalSearch`
+    pub fn start_incremental_search(&self, query_vector: &[f32], k: usize) -> Result<IncrementalSearch, BbqError> {
+        IncrementalSearch::new(self, query_vector, k)
+    }
+
+    /// 在搜索流上开始可取代的增量搜索
+    ///
+    /// 同一个流上开始新查询会取消之前进行中的查询，适合输入联想：
+    /// 每次输入调用一次，再在动画帧中用 `pump_superseding_for` 推进，返回None时停止推进
+    ///
+    /// # 参数
+    /// * `stream` - 流名称
+    /// * `query_id` - 查询ID，在流内必须递增
+    ///
+    /// # 返回
+    /// 被取消的查询ID
+    pub fn search_superseding(&self, stream: &str, query_id: u64, query_vector: &[f32], k: usize) -> Result<Option<u64>, BbqError> {
+        self.search_streams().start(self, stream, query_id, query_vector, k)
+    }
+
+    /// 推进流上的查询，最多评分 `max_batches` 批
+    ///
+    /// # 返回
+    /// 临时结果；查询已被取代、取消或已返回最终结果时为None
+    pub fn pump_superseding(&self, stream: &str, query_id: u64, max_batches: usize) -> Result<Option<ProgressiveResults>, BbqError> {
+        self.search_streams().pump(self, stream, query_id, max_batches)
+    }
+
+    /// 推进流上的查询，在 `budget_ms` 毫秒内尽量多地评分
+    ///
+    /// # 返回
+    /// 临时结果；查询已被取代、取消或已返回最终结果时为None
+    pub fn pump_superseding_for(&self, stream: &str, query_id: u64, budget_ms: f64) -> Result<Option<ProgressiveResults>, BbqError> {
+        self.search_streams().pump_for(self, stream, query_id, budget_ms)
+    }
+
+    /// 取消流上进行中的查询，返回被取消的查询ID
+    pub fn cancel_stream(&self, stream: &str) -> Option<u64> {
+        self.search_streams().cancel(stream)
+    }
+
+    /// 流上进行中的查询ID
+    pub fn stream_in_flight(&self, stream: &str) -> Option<u64> {
+        self.search_streams().in_flight(stream)
+    }
+
+    /// 移除流及其查询ID记录，之后该流的查询ID可以重新从任意值开始
+    pub fn remove_stream(&self, stream: &str) -> bool {
+        self.search_streams().remove(stream)
+    }
+
+    /// 伪相关反馈搜索
+    ///
+    /// 先搜索前feedback_k个结果，把查询与这些结果的质心按
+    /// `(1 - alpha) * 查询 + alpha * 质心` 混合后再搜索一次；
+    /// 结果向量优先使用保留的原始向量，否则由量化码和修正项重建
+    ///
+    /// # 参数
+    /// * `query_vector` - 查询向量
+    /// * `k` - 返回的结果数量
+    /// * `feedback_k` - 用于反馈的初始结果数量
+    /// * `alpha` - 质心的混合权重，[0, 1]
+    pub fn search_with_feedback(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        feedback_k: usize,
+        alpha: f32,
+    ) -> Result<Vec<QueryResult>, BbqError> {
+        if !(0.0..=1.0).contains(&alpha) {
+            return Err(BbqError::out_of_range(
+                format!("反馈混合权重必须在0-1之间，当前为{}", alpha),
+                format!("feedback blend weight must be within 0-1, got {}", alpha),
+            ));
+        }
+        if feedback_k == 0 {
+            return Err(BbqError::invalid_argument("反馈结果数量必须大于0", "feedback result count must be greater than 0"));
+        }
+        let generation = self.snapshot()?;
+        let params = SearchParams::default();
+        let context = self.prepare_query_in(&generation, query_vector)?;
+        let feedback = self.search_with_context_in(&generation, &context, feedback_k, &params)?;
+        if feedback.is_empty() || alpha == 0.0 {
+            return self.search_with_context_in(&generation, &context, k, &params);
+        }
+
+        let mut centroid = vec![0.0f32; context.query_vector.len()];
+        for result in &feedback {
+            let vector = match generation.original_vector(result.index) {
+                Some(vector) => vector.into_owned(),
+                None => reconstruct_vector(generation.values(), self.config.index_bits, result.index)?,
+            };
+            for (sum, value) in centroid.iter_mut().zip(vector) {
+                *sum += value;
+            }
+        }
+        let weight = alpha / feedback.len() as f32;
+        let expanded: Vec<f32> = context.query_vector.iter()
+            .zip(&centroid)
+            .map(|(&q, &c)| (1.0 - alpha) * q + weight * c)
+            .collect();
+        let context = self.prepare_query_in(&generation, &expanded)?;
+        self.search_with_context_in(&generation, &context, k, &params)
+    }
+
+    /// 对调用方指定的候选序号评分（不做Top-K选择）
+    ///
+    /// 用于候选来自其它来源（如关键词索引）的场景：按给定顺序返回每个候选的量化分数，
+    /// 已删除或已过期的候选被跳过（启用 `paranoid` 时不一致的向量同样跳过）
+    ///
+    /// # 参数
+    /// * `query_vector` - 查询向量
+    /// * `ords` - 候选向量序号
+    ///
+    /// # 返回
+    /// 与候选顺序一致的查询结果
+    pub fn score_ords(&self, query_vector: &[f32], ords: &[usize]) -> Result<Vec<QueryResult>, BbqError> {
+        let generation = self.snapshot()?;
+        if let Some(&bad) = ords.iter().find(|&&ord| ord >= generation.size()) {
+            return Err(BbqError::out_of_range(format!("序号 {} 超出索引范围", bad), format!("ordinal {} is outside the index", bad)));
+        }
+        let context = self.prepare_query_in(&generation, query_vector)?;
+
+        let mut unexpired = Vec::with_capacity(ords.len());
+        self.search_candidates(&generation, ords.iter().copied(), None, &mut unexpired);
+
+        let mut results = Vec::with_capacity(unexpired.len());
+        let batch_size = recommended_batch_size(generation.values().dimension(), 0, None);
+        for batch in unexpired.chunks(batch_size) {
+            let mut scores = self.scorer.compute_batch_scores_excluding(&context, generation.values(), batch, generation.tombstones())?;
+            generation.rescore_promoted(&self.scorer, &context, &mut scores)?;
+            results.extend(scores.into_iter().map(|(index, score)| QueryResult {
+                index,
+                score,
+                original_score: None,
+                distances: None,
+            }));
+        }
+        Ok(results)
+    }
+
+    /// 对序号区间 [start, end) 评分，跳过已删除和已过期的向量，返回（序号, 分数）
+    ///
+    /// 供分片索引把一个分片拆成多个评分任务
+    pub(crate) fn score_ord_range(
+        &self,
+        generation: &IndexGeneration,
+        context: &QueryContext,
+        start: usize,
+        end: usize,
+    ) -> Result<Vec<(usize, f32)>, BbqError> {
+        self.check_context(generation, context)?;
+        let mut ords = Vec::new();
+        self.search_candidates(generation, start..end.min(generation.size()), None, &mut ords);
+        let mut scored = self.scorer.compute_batch_scores_excluding(context, generation.values(), &ords, generation.tombstones())?;
+        generation.rescore_promoted(&self.scorer, context, &mut scored)?;
+        Ok(scored)
+    }
+
+    /// 检查查询上下文与这一代的维度和质心是否一致
+    fn check_context(&self, generation: &IndexGeneration, context: &QueryContext) -> Result<(), BbqError> {
+        if context.dimension() != generation.values().dimension() {
+            return Err(BbqError::dimension_mismatch("查询向量维度与索引维度不匹配", "query dimension does not match the index dimension"));
+        }
+        if context.centroid_epoch.is_some_and(|epoch| epoch != generation.centroid_epoch()) {
+            return Err(BbqError::invalid_argument("查询上下文基于已被替换的质心，请重新调用prepare_query", "the query context was prepared against a replaced centroid; call prepare_query again"));
+        }
+        Ok(())
+    }
+
+    /// 不经过结果缓存的搜索
+    fn search_uncached(
+        &self,
+        generation: &IndexGeneration,
+        context: &QueryContext,
+        k: usize,
+        params: &SearchParams,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<QueryResult>, BbqError> {
+        self.search_uncached_with(generation, context, k, params, filter, &self.scorer, true)
+    }
+
+    /// 使用指定的评分器搜索，`use_layout` 为false时即使已转换也走构建状态的路径
+    #[allow(clippy::too_many_arguments)]
+    fn search_uncached_with(
+        &self,
+        generation: &IndexGeneration,
+        context: &QueryContext,
+        k: usize,
+        params: &SearchParams,
+        filter: Option<&Filter>,
+        scorer: &BinaryQuantizedScorer,
+        use_layout: bool,
+    ) -> Result<Vec<QueryResult>, BbqError> {
+        let oversample = self.resolve_oversample(generation, params.rescore_oversample)?;
+        if !(0.0..=1.0).contains(&params.quality_weight) {
+            return Err(BbqError::out_of_range(
+                format!("量化质量权重必须在0-1之间，当前为{}", params.quality_weight),
+                format!("quality weight must be within 0-1, got {}", params.quality_weight),
+            ));
+        }
+        let mut scratch = self.take_scratch();
+        let results = self.score_candidates(generation, context, k, params, filter, scorer, use_layout, &mut scratch)
+            .and_then(|()| self.rank_scored(generation, context, &mut scratch.scored, k, params, oversample, None));
+        self.return_scratch(scratch);
+        results
+    }
+
+    /// 计算全部候选向量（未删除、未过期且满足过滤条件）的量化分数，（序号, 分数）写入 `scratch.scored`
+    #[allow(clippy::too_many_arguments)]
+    fn score_candidates(
+        &self,
+        generation: &IndexGeneration,
+        context: &QueryContext,
+        k: usize,
+        params: &SearchParams,
+        filter: Option<&Filter>,
+        scorer: &BinaryQuantizedScorer,
+        use_layout: bool,
+        scratch: &mut SearchScratch,
+    ) -> Result<(), BbqError> {
+        scratch.scored.clear();
+        let quantized_vectors = generation.values();
+
+        // 已转换为搜索布局时，无过滤、无探测、没有过期时间的搜索直接扫描布局
+        #[cfg(feature = "ivf")]
+        let probing = params.nprobe.is_some();
+        #[cfg(not(feature = "ivf"))]
+        let probing = false;
+        if let Some(layout) = generation.search_layout().filter(|_| use_layout) {
+            if filter.is_none() && !probing && generation.next_expiry.is_none() {
+                self.score_layout(scorer, layout, context, quantized_vectors.dimension(), k, params, &mut scratch.scored)?;
+                return generation.rescore_promoted(scorer, context, &mut scratch.scored);
+            }
+        }
+
+        // 1. 计算所有候选向量的分数
+        let vector_count = quantized_vectors.size();
+        #[cfg(feature = "ivf")]
+        let probed: Option<Vec<usize>> = match params.nprobe {
+            Some(strategy) => {
+                strategy.validate()?;
+                let ivf = generation.ivf().ok_or_else(|| BbqError::not_built("索引未建立IVF划分，请先调用build_ivf", "the index has no IVF partition; call build_ivf first"))?;
+                Some(ivf.probe_with(&context.query_vector, strategy).0)
+            }
+            None => None,
+        };
+        #[cfg(not(feature = "ivf"))]
+        let probed: Option<Vec<usize>> = None;
+        match probed {
+            Some(routed) => self.search_candidates(generation, routed, filter, &mut scratch.candidates),
+            None => self.search_candidates(generation, 0..vector_count, filter, &mut scratch.candidates),
+        }
+        let candidates = &scratch.candidates;
+
+        // 批量计算分数
+        let batch_size = recommended_batch_size(quantized_vectors.dimension(), k, params.batch_size);
+        scratch.scored.reserve(candidates.len());
+
+        let mut scored = 0;
+        for batch_indices in candidates.chunks(batch_size) {
+            scratch.scored.extend(scorer.compute_batch_scores_excluding(
+                context,
+                quantized_vectors,
+                batch_indices,
+                generation.tombstones(),
+            )?);
+            scored += batch_indices.len();
+            self.emit(ProgressEvent::SearchBatchScored { scored, total: candidates.len() });
+        }
+        generation.rescore_promoted(scorer, context, &mut scratch.scored)
+    }
+
+    /// 顺序扫描搜索布局中的全部向量，（序号, 分数）追加到 `all_results`
+    #[allow(clippy::too_many_arguments)]
+    fn score_layout(
+        &self,
+        scorer: &BinaryQuantizedScorer,
+        layout: &SearchLayout,
+        context: &QueryContext,
+        dimension: usize,
+        k: usize,
+        params: &SearchParams,
+        all_results: &mut Vec<(usize, f32)>,
+    ) -> Result<(), BbqError> {
+        let batch_size = recommended_batch_size(dimension, k, params.batch_size);
+        all_results.reserve(layout.len());
+        let mut start = 0;
+        while start < layout.len() {
+            let end = (start + batch_size).min(layout.len());
+            let (ords, packed, corrections) = layout.batch(start..end);
+            let scores = scorer.compute_batch_scores_packed(context, packed, corrections, dimension)?;
+            all_results.extend(ords.iter().copied().zip(scores));
+            start = end;
+            self.emit(ProgressEvent::SearchBatchScored { scored: start, total: layout.len() });
+        }
+        Ok(())
+    }
+
+    /// 为实验抽样；候选路径要求搜索布局而当前代未转换时记为跳过
+    fn draw_experiment(&self, generation: &IndexGeneration) -> Option<ExperimentRun> {
+        let mut experiment = self.experiment();
+        let state = experiment.as_mut()?;
+        let run = state.sample()?;
+        if run.layout == ExperimentLayout::SearchLayout && generation.search_layout().is_none() {
+            state.record_skipped();
+            return None;
+        }
+        Some(run)
+    }
+
+    /// 抽中的查询不经过结果缓存，分别计时运行对照路径和候选路径并记录成对统计
+    ///
+    /// 对照路径出错时返回错误；候选路径出错只计数，仍返回对照结果
+    fn search_experiment_pair(
+        &self,
+        generation: &IndexGeneration,
+        context: &QueryContext,
+        k: usize,
+        params: &SearchParams,
+        run: ExperimentRun,
+    ) -> Result<Vec<QueryResult>, BbqError> {
+        let timed = |scorer: &BinaryQuantizedScorer, use_layout: bool| {
+            let start = now_ms();
+            let results = self.search_uncached_with(generation, context, k, params, None, scorer, use_layout);
+            (results, elapsed_ms(start))
+        };
+        let candidate_layout = run.layout != ExperimentLayout::BuildState;
+        let ((control, control_ms), (candidate, candidate_ms)) = if run.control_first {
+            let control = timed(&self.scorer, true);
+            (control, timed(&run.scorer, candidate_layout))
+        } else {
+            let candidate = timed(&run.scorer, candidate_layout);
+            (timed(&self.scorer, true), candidate)
+        };
+        let control = control?;
+
+        let mut experiment = self.experiment();
+        match candidate {
+            Ok(candidate) => {
+                if let Some(state) = experiment.as_mut() {
+                    state.record(control_ms, candidate_ms, &control, &candidate);
+                }
+                Ok(if run.serve_candidate { candidate } else { control })
+            }
+            Err(_) => {
+                if let Some(state) = experiment.as_mut() {
+                    state.record_candidate_error();
+                }
+                Ok(control)
+            }
+        }
+    }
+
+    /// 批量搜索多个查询
+    ///
+    /// 所有查询在同一代上评分：候选按批收集一次，用分块内核与全部查询计算，
+    /// 每块目标只读取一次。结果与逐个调用 `search_with_params` 相同，但不经过结果缓存
+    ///
+    /// # 参数
+    /// * `queries` - 查询向量
+    /// * `k` - 每个查询返回的最近邻数量
+    /// * `params` - 搜索参数，对所有查询相同
+    ///
+    /// # 返回
+    /// 与 `queries` 一一对应的查询结果
+    pub fn search_batch(
+        &self,
+        queries: &[Vec<f32>],
+        k: usize,
+        params: &SearchParams,
+    ) -> Result<Vec<Vec<QueryResult>>, BbqError> {
+        let generation = self.snapshot()?;
+        let contexts = queries.iter()
+            .map(|query_vector| self.prepare_query_in(&generation, query_vector))
+            .collect::<Result<Vec<_>, _>>()?;
+        if k == 0 {
+            return Ok(contexts.iter().map(|_| Vec::new()).collect());
+        }
+        // 各查询探测的列表不同，没有共享的候选批次
+        #[cfg(feature = "ivf")]
+        if params.nprobe.is_some() {
+            return contexts.iter()
+                .map(|context| self.search_uncached(&generation, context, k, params, None))
+                .collect();
+        }
+
+        let quantized_vectors = generation.values();
+        let oversample = self.resolve_oversample(&generation, params.rescore_oversample)?;
+        if !(0.0..=1.0).contains(&params.quality_weight) {
+            return Err(BbqError::out_of_range(
+                format!("量化质量权重必须在0-1之间，当前为{}", params.quality_weight),
+                format!("quality weight must be within 0-1, got {}", params.quality_weight),
+            ));
+        }
+        let mut candidates = Vec::new();
+        self.search_candidates(&generation, 0..quantized_vectors.size(), None, &mut candidates);
+
+        let batch_size = recommended_batch_size(quantized_vectors.dimension(), k, params.batch_size);
+        let mut all_results: Vec<Vec<(usize, f32)>> = contexts.iter()
+            .map(|_| Vec::with_capacity(candidates.len()))
+            .collect();
+        for batch_indices in candidates.chunks(batch_size) {
+            let (kept, scores) = self.scorer.compute_block_scores_excluding(
+                &contexts,
+                quantized_vectors,
+                batch_indices,
+                generation.tombstones(),
+            )?;
+            for (results, scores) in all_results.iter_mut().zip(scores) {
+                results.extend(kept.iter().copied().zip(scores));
+            }
+        }
+        contexts.iter()
+            .zip(all_results)
+            .map(|(context, mut results)| {
+                generation.rescore_promoted(&self.scorer, context, &mut results)?;
+                self.rank_scored(&generation, context, &mut results, k, params, oversample, None)
+            })
+            .collect()
+    }
+
+    /// 搜索的候选序号：未过期且满足过滤条件，写入 `candidates`（先清空）
+    ///
+    /// 已删除的向量不在这里过滤，而是交给批量评分在打包前跳过
+    fn search_candidates(
+        &self,
+        generation: &IndexGeneration,
+        routed: impl IntoIterator<Item = usize>,
+        filter: Option<&Filter>,
+        candidates: &mut Vec<usize>,
+    ) {
+        let now = generation.next_expiry.map(|_| now_ms());
+        candidates.clear();
+        candidates.extend(
+            routed
+                .into_iter()
+                .filter(|&ord| !now.is_some_and(|now| generation.is_expired_at(ord, now)))
+                .filter(|&ord| filter.is_none_or(|filter| filter.matches(ord, generation.attributes(ord)))),
+        );
+        // 不一致的向量在评分前跳过，避免读取越界或产生无意义的分数
+        #[cfg(feature = "paranoid")]
+        consistency::retain_consistent_candidates(generation.values(), self.config.index_bits, candidates);
+    }
+
+    /// 由全部候选的量化分数得到最终结果：质量加权、排序、重排和构建结果
+    ///
+    /// `deadline` 不为None时（限时搜索）重排每次k个候选，预算用完即停
+    #[allow(clippy::too_many_arguments)]
+    fn rank_scored(
+        &self,
+        generation: &IndexGeneration,
+        context: &QueryContext,
+        all_results: &mut Vec<(usize, f32)>,
+        k: usize,
+        params: &SearchParams,
+        oversample: Option<f32>,
+        deadline: Option<&mut RescoreDeadline>,
+    ) -> Result<Vec<QueryResult>, BbqError> {
+        #[cfg(feature = "paranoid")]
+        consistency::retain_scores_in_envelope(self.config.similarity_function, all_results);
+        let k = k.min(all_results.len());
+        let candidate_count = match oversample {
+            Some(factor) => ((k as f32 * factor).ceil() as usize).clamp(k, all_results.len()),
+            None => k,
+        };
+
+        // 按量化质量调整分数
+        if params.quality_weight > 0.0 {
+            let weight = params.quality_weight;
+            for (index, score) in all_results.iter_mut() {
+                *score *= 1.0 - weight + weight * generation.quality_scores[*index];
+            }
+        }
+
+        // 2. 使用部分排序找到前k个最大值
+        // 候选按序号升序时（未经IVF探测）先选出需要的前几个再排序，同分按序号，与稳定的全排序结果相同
+        if all_results.windows(2).all(|pair| pair[0].0 < pair[1].0) {
+            let order = |a: &(usize, f32), b: &(usize, f32)| descending_score_order(a.1, b.1).then(a.0.cmp(&b.0));
+            if candidate_count < all_results.len() {
+                if candidate_count > 0 {
+                    all_results.select_nth_unstable_by(candidate_count - 1, order);
+                }
+                all_results.truncate(candidate_count);
+            }
+            all_results.sort_unstable_by(order);
+        } else {
+            all_results.sort_by(|a, b| descending_score_order(a.1, b.1));
+        }
+
+        // 3. 用原始向量重排候选
+        if oversample.is_some() {
+            all_results.truncate(candidate_count);
+            match deadline {
+                Some(deadline) => self.rescore_within(generation, context, all_results, k, deadline)?,
+                None => self.rescore(generation, context, all_results)?,
+            }
+        }
+
+        self.finish_results(generation, context, all_results, k, params)
+    }
+
+    /// 由排好序的（序号, 分数）构建前k个结果，附带距离并做归一化和最终分数变换
+    fn finish_results(
+        &self,
+        generation: &IndexGeneration,
+        context: &QueryContext,
+        sorted: &[(usize, f32)],
+        k: usize,
+        params: &SearchParams,
+    ) -> Result<Vec<QueryResult>, BbqError> {
+        // 4. 构建结果
+        let mut top_k_results = Vec::with_capacity(k);
+        for &(index, score) in sorted.iter().take(k) {
+            let distances = if params.include_distances {
+                Some(self.hit_distances(generation, context, index)?)
+            } else {
+                None
+            };
+            top_k_results.push(QueryResult { index, score, original_score: None, distances });
+        }
+
+        // 5. 分数归一化
+        normalize_scores(&mut top_k_results, params.normalization)?;
+
+        // 6. 最终分数变换（单调，不改变顺序）
+        if let Some(transform) = &self.score_transform {
+            transform.apply_to_results(&mut top_k_results);
+        }
+
+        Ok(top_k_results)
+    }
+
+    /// 在给定的时间预算内搜索，预算不足时减少工作量并报告跳过了什么
+    ///
+    /// 面向低端移动设备：按批评分，每批之后检查耗时，预计下一批会超出预算时停止。
+    /// 未使用IVF时按交错的批次扫描，使任意前缀都是均匀样本（已转换为搜索布局时按布局中连续的批评分，
+    /// 批的顺序交错，使任意前缀覆盖整个序号范围）；
+    /// 使用IVF时按与查询的接近程度逐个探测列表，预算不足时少探测后面的列表。
+    /// 需要重排时量化扫描只用预算的 `BUDGET_SCAN_SHARE`，剩余时间每次重排k个候选，
+    /// 用完即停，实际过采样倍数可能小于请求的倍数。
+    /// 无论预算多小都至少评分一批，因此总能返回结果。
+    /// 候选和排序与 `search_with_params` 经过同样的检查（过期、`paranoid` 下的一致性和分数范围）
+    ///
+    /// # 参数
+    /// * `query_vector` - 查询向量
+    /// * `k` - 返回的结果数量
+    /// * `budget_ms` - 时间预算（毫秒），必须为正数
+    /// * `params` - 搜索参数
+    pub fn search_within_budget(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        budget_ms: f64,
+        params: &SearchParams,
+    ) -> Result<BudgetedSearchResults, BbqError> {
+        self.search_within_budget_with(query_vector, k, budget_ms, None, params)
+    }
+
+    /// 带过滤条件的限时搜索，见 `search_within_budget`
+    ///
+    /// # 参数
+    /// * `filter` - 过滤条件，只返回满足条件的向量
+    pub fn search_within_budget_filtered(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        budget_ms: f64,
+        filter: &Filter,
+        params: &SearchParams,
+    ) -> Result<BudgetedSearchResults, BbqError> {
+        self.search_within_budget_with(query_vector, k, budget_ms, Some(filter), params)
+    }
+
+    fn search_within_budget_with(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        budget_ms: f64,
+        filter: Option<&Filter>,
+        params: &SearchParams,
+    ) -> Result<BudgetedSearchResults, BbqError> {
+        if !(budget_ms.is_finite() && budget_ms > 0.0) {
+            return Err(BbqError::invalid_argument(format!("时间预算必须为正数，当前为{}", budget_ms), format!("time budget must be positive, got {}", budget_ms)));
+        }
+        if !(0.0..=1.0).contains(&params.quality_weight) {
+            return Err(BbqError::out_of_range(
+                format!("量化质量权重必须在0-1之间，当前为{}", params.quality_weight),
+                format!("quality weight must be within 0-1, got {}", params.quality_weight),
+            ));
+        }
+        let start = now_ms();
+        let generation = self.snapshot()?;
+        let context = self.prepare_query_in(&generation, query_vector)?;
+        let values = generation.values();
+        let oversample = self.resolve_oversample(&generation, params.rescore_oversample)?;
+        let scan_budget = if oversample.is_some() { budget_ms * BUDGET_SCAN_SHARE } else { budget_ms };
+        let batch_size = recommended_batch_size(values.dimension(), k, params.batch_size);
+
+        // 1. 按评分顺序划分批次：IVF按列表，可以扫描布局时按布局区间，否则交错抽取
+        #[cfg(feature = "ivf")]
+        let probing = params.nprobe.is_some();
+        #[cfg(not(feature = "ivf"))]
+        let probing = false;
+        let layout = generation.search_layout()
+            .filter(|_| filter.is_none() && !probing && generation.next_expiry.is_none());
+        let (batches, lists_requested): (Vec<BudgetBatch>, usize) = if let Some(layout) = layout {
+            (interleaved_ranges(layout.len(), batch_size).into_iter().map(BudgetBatch::Layout).collect(), 0)
+        } else {
+            #[cfg(feature = "ivf")]
+            let probed: Option<Vec<Vec<usize>>> = match params.nprobe {
+                Some(strategy) => {
+                    strategy.validate()?;
+                    let ivf = generation.ivf().ok_or_else(|| BbqError::not_built("索引未建立IVF划分，请先调用build_ivf", "the index has no IVF partition; call build_ivf first"))?;
+                    let (_, nprobe) = ivf.probe_with(&context.query_vector, strategy);
+                    Some(ivf.rank_lists(&context.query_vector)
+                        .into_iter()
+                        .take(nprobe)
+                        .map(|(list, _)| {
+                            let mut candidates = Vec::new();
+                            self.search_candidates(&generation, ivf.list(list).iter().copied(), filter, &mut candidates);
+                            candidates
+                        })
+                        .collect())
+                }
+                None => None,
+            };
+            #[cfg(not(feature = "ivf"))]
+            let probed: Option<Vec<Vec<usize>>> = None;
+            let (lists, lists_requested) = match probed {
+                Some(lists) => {
+                    let requested = lists.len();
+                    (lists, requested)
+                }
+                None => {
+                    let mut candidates = Vec::new();
+                    self.search_candidates(&generation, 0..generation.size(), filter, &mut candidates);
+                    (interleaved_batches(&candidates, batch_size), 0)
+                }
+            };
+            (lists.into_iter().map(BudgetBatch::Ords).collect(), lists_requested)
+        };
+        let total: usize = batches.iter().map(BudgetBatch::len).sum();
+
+        // 2. 逐批评分，预计下一批会超出预算时停止
+        let mut all_results = Vec::with_capacity(total);
+        let mut batches_scored = 0;
+        let mut scanned = 0;
+        let mut last_batch_ms = 0.0;
+        for batch in &batches {
+            if batches_scored > 0 && elapsed_ms(start) + last_batch_ms > scan_budget {
+                break;
+            }
+            let batch_start = now_ms();
+            let mut scores = match (batch, layout) {
+                (BudgetBatch::Layout(range), Some(layout)) => {
+                    let (ords, packed, corrections) = layout.batch(range.clone());
+                    let scores = self.scorer.compute_batch_scores_packed(&context, packed, corrections, values.dimension())?;
+                    ords.iter().copied().zip(scores).collect()
+                }
+                (BudgetBatch::Ords(ords), _) => {
+                    self.scorer.compute_batch_scores_excluding(&context, values, ords, generation.tombstones())?
+                }
+                (BudgetBatch::Layout(_), None) => unreachable!("布局批次只在有布局时生成"),
+            };
+            generation.rescore_promoted(&self.scorer, &context, &mut scores)?;
+            all_results.extend(scores);
+            last_batch_ms = elapsed_ms(batch_start);
+            batches_scored += 1;
+            scanned += batch.len();
+            self.emit(ProgressEvent::SearchBatchScored { scored: scanned, total });
+        }
+        let scored = all_results.len();
+        let skipped = total - scanned;
+
+        // 3. 与完整搜索相同的排序和结果构建，重排限制在剩余的预算内
+        let mut deadline = RescoreDeadline { start, budget_ms, used: None, cut: false };
+        let results = self.rank_scored(&generation, &context, &mut all_results, k, params, oversample, Some(&mut deadline))?;
+        let probes_skipped = lists_requested.saturating_sub(batches_scored);
+        let budget_exhausted = skipped > 0 || deadline.cut;
+        Ok(BudgetedSearchResults {
+            results,
+            scored,
+            skipped,
+            probes_skipped,
+            oversample_requested: oversample,
+            oversample_used: deadline.used,
+            estimated_recall: if total == 0 { 1.0 } else { scanned as f32 / total as f32 },
+            elapsed_ms: elapsed_ms(start),
+            budget_exhausted,
+        })
+    }
+
+    /// 计算单个命中的各种距离
+    fn hit_distances(&self, generation: &IndexGeneration, context: &QueryContext, ord: usize) -> Result<HitDistances, BbqError> {
+        let values = generation.values();
+        // 已提升的向量按提升后的量化值计算，与搜索时的评分一致
+        let promoted = generation.promoted().and_then(|promoted| promoted.get(ord).map(|vector| (promoted.bits(), vector)));
+        let (quantized_score, bit_dot_product) = match promoted {
+            Some((bits, vector)) => (
+                self.scorer.compute_multi_bit_index_score(context, &vector.quantized, &vector.corrections, bits)?,
+                compute_int4_bit_dot_product(&context.quantized_query, &vector.quantized)?,
+            ),
+            None => {
+                let quantized = self.scorer.compute_quantized_score(
+                    &context.quantized_query,
+                    &context.query_corrections,
+                    values.try_get_unpacked_vector(ord)?,
+                    &values.try_get_corrective_terms(ord)?,
+                    context.query_bits,
+                    values.dimension(),
+                    context.centroid_dp,
+                    None,
+                )?;
+                (quantized.score, quantized.bit_dot_product)
+            }
+        };
+
+        // 只有1位索引保存的是打包的符号位
+        let hamming_distance = if self.config.index_bits == 1 {
+            let mut sign_bits = match &context.packed_query {
+                Some(packed) => packed.clone(),
+                None => {
+                    let shift = context.query_bits - 1;
+                    let top_plane: Vec<u8> = context.quantized_query.iter().map(|&q| q >> shift).collect();
+                    let mut packed = vec![0u8; top_plane.len().div_ceil(8)];
+                    OptimizedScalarQuantizer::pack_as_binary(&top_plane, &mut packed)?;
+                    packed
+                }
+            };
+            sign_bits.resize(values.packed_size(), 0);
+            Some(compute_packed_hamming_distance(&sign_bits, values.try_vector_value(ord)?)?)
+        } else {
+            None
+        };
+
+        let exact_score = generation.original_vector(ord).map(|target| match &context.dimension_weights {
+            Some(weights) => self.scorer.compute_weighted_exact_score(&context.query_vector, &target, weights),
+            None => self.scorer.compute_exact_score(&context.query_vector, &target),
+        });
+
+        Ok(HitDistances {
+            quantized_score,
+            bit_dot_product,
+            hamming_distance,
+            exact_score,
+        })
+    }
+
+    /// 解析本次搜索实际使用的过采样倍数
+    fn resolve_oversample(&self, generation: &IndexGeneration, oversample: RescoreOversample) -> Result<Option<f32>, BbqError> {
+        let factor = match oversample {
+            RescoreOversample::Disabled => return Ok(None),
+            RescoreOversample::Fixed(factor) => factor,
+            RescoreOversample::Adaptive => self.learned_oversample.unwrap_or(DEFAULT_RESCORE_OVERSAMPLE),
+        };
+        if !(factor.is_finite() && factor >= 1.0) {
+            return Err(BbqError::out_of_range(format!("过采样倍数必须不小于1，当前为{}", factor), format!("oversample factor must be at least 1, got {}", factor)));
+        }
+        if generation.original_vectors.is_none() {
+            return Err(BbqError::missing_original_vectors(Subject::new("重排", "rescoring")));
+        }
+        Ok(Some(factor))
+    }
+
+    /// 用原始向量的精确分数重排候选，并按新分数降序排列
+    fn rescore(&self, generation: &IndexGeneration, context: &QueryContext, candidates: &mut [(usize, f32)]) -> Result<(), BbqError> {
+        let Some(original_vectors) = generation.original_vectors.as_deref() else {
+            return Err(BbqError::missing_original_vectors(Subject::new("重排", "rescoring")));
+        };
+        let values = generation.values();
+        let query_norm_sq = fast_dot_product(&context.query_vector, &context.query_vector);
+        // 压缩存储时所有候选共用一个解码缓冲区
+        let mut buffer = Vec::new();
+        for candidate in candidates.iter_mut() {
+            let target = original_vectors.get_with_buffer(candidate.0, values, &mut buffer)
+                .ok_or_else(|| BbqError::corrupt_data(format!("原始向量 {} 不存在", candidate.0), format!("original vector {} is missing", candidate.0)))?;
+            candidate.1 = match &context.dimension_weights {
+                Some(weights) => self.scorer.compute_weighted_exact_score(&context.query_vector, target, weights),
+                None => self.scorer.compute_exact_score_with_norms(
+                    &context.query_vector,
+                    query_norm_sq,
+                    target,
+                    values.try_get_norm(candidate.0)?,
+                ),
+            };
+        }
+        candidates.sort_by(|a, b| descending_score_order(a.1, b.1));
+        Ok(())
+    }
+
+    /// 在剩余的预算内重排，每次k个候选，重排过的前缀即为新的候选集合
+    ///
+    /// 一个也没重排时保留量化分数的顺序
+    fn rescore_within(
+        &self,
+        generation: &IndexGeneration,
+        context: &QueryContext,
+        candidates: &mut Vec<(usize, f32)>,
+        k: usize,
+        deadline: &mut RescoreDeadline,
+    ) -> Result<(), BbqError> {
+        let requested = candidates.len();
+        let mut rescored = 0;
+        let mut last_chunk_ms = 0.0;
+        while rescored < requested {
+            let remaining = deadline.budget_ms - elapsed_ms(deadline.start);
+            if remaining <= 0.0 || (rescored > 0 && last_chunk_ms > remaining) {
+                deadline.cut = true;
+                break;
+            }
+            let end = (rescored + k).min(requested);
+            let chunk_start = now_ms();
+            self.rescore(generation, context, &mut candidates[rescored..end])?;
+            last_chunk_ms = elapsed_ms(chunk_start);
+            rescored = end;
+        }
+        if rescored > 0 {
+            candidates.truncate(rescored);
+            candidates.sort_by(|a, b| descending_score_order(a.1, b.1));
+            deadline.used = Some(rescored as f32 / k as f32);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "eval")]
+    /// 估计给定参数下的召回率
+    ///
+    /// 以原始向量的精确搜索结果为基准
+    ///
+    /// # 参数
+    /// * `queries` - 采样查询向量
+    /// * `k` - 评估的Top-K
+    /// * `params` - 搜索参数
+    ///
+    /// # 返回
+    /// 平均召回率
+    pub fn estimate_recall(
+        &self,
+        queries: &[Vec<f32>],
+        k: usize,
+        params: &SearchParams,
+    ) -> Result<f32, BbqError> {
+        let generation = self.snapshot()?;
+        let original_vectors = generation.require_original_vectors(Subject::new("召回评估", "recall evaluation"))?;
+
+        let mut approximate = Vec::with_capacity(queries.len());
+        let mut exact = Vec::with_capacity(queries.len());
+        for query in queries {
+            let context = self.prepare_query_in(&generation, query)?;
+            let results = self.search_with_context_in(&generation, &context, k, params)?;
+            approximate.push(results.into_iter().map(|r| r.index).collect::<Vec<_>>());
+            exact.push(compute_exact_top_k_where(
+                &original_vectors,
+                &context.query_vector,
+                k,
+                &self.scorer,
+                |ord| !generation.is_deleted(ord),
+            ));
+        }
+
+        Ok(mean_recall(&approximate, &exact))
+    }
+
+    #[cfg(feature = "eval")]
+    /// 抽样生成自查询真值并缓存在索引中
+    ///
+    /// 从未删除的向量中蓄水池抽样sample_size个作为查询，暴力计算其精确近邻；
+    /// 之后可通过 `estimate_cached_recall` 在删除、压缩后重新估计召回率。
+    /// 重新构建索引会清除缓存的真值
+    ///
+    /// # 参数
+    /// * `sample_size` - 抽样查询数量
+    /// * `k` - 评估使用的k
+    /// * `seed` - 随机种子
+    ///
+    /// # 返回
+    /// 实际抽样的查询数量
+    pub fn generate_ground_truth(&mut self, sample_size: usize, k: usize, seed: u64) -> Result<usize, BbqError> {
+        self.generate_ground_truth_with_rng(sample_size, k, &mut fastrand::Rng::with_seed(seed))
+    }
+
+    #[cfg(feature = "eval")]
+    /// 使用指定的随机来源抽样生成自查询真值，见 `generate_ground_truth`
+    pub fn generate_ground_truth_with_rng<R: RngSource + ?Sized>(
+        &mut self,
+        sample_size: usize,
+        k: usize,
+        rng: &mut R,
+    ) -> Result<usize, BbqError> {
+        if sample_size == 0 || k == 0 {
+            return Err(BbqError::invalid_argument("抽样数量和k必须大于0", "sample count and k must be greater than 0"));
+        }
+        let generation = self.snapshot()?;
+        let original_vectors = generation.require_original_vectors(Subject::new("生成真值", "ground truth"))?;
+
+        let live = (0..generation.size()).filter(|&ord| !generation.is_deleted(ord));
+        let queries = reservoir_sample(live, sample_size, rng);
+        let neighbors = queries.iter()
+            .map(|&query| compute_exact_top_k_where(
+                &original_vectors,
+                &original_vectors[query],
+                k * 2,
+                &self.scorer,
+                |ord| !generation.is_deleted(ord),
+            ))
+            .collect();
+
+        let sampled = queries.len();
+        drop(original_vectors);
+        drop(generation);
+        self.generation_mut()?.ground_truth = Some(GroundTruth { k, queries, neighbors });
+        Ok(sampled)
+    }
+
+    #[cfg(feature = "eval")]
+    /// 使用缓存的自查询真值估计当前召回率
+    ///
+    /// 已删除的查询被跳过，已删除的近邻从真值中移除后取前k个
+    ///
+    /// # 返回
+    /// 平均召回率（0到1之间）
+    pub fn estimate_cached_recall(&self, params: &SearchParams) -> Result<f32, BbqError> {
+        let generation = self.snapshot()?;
+        let truth = generation.ground_truth.as_ref()
+            .ok_or_else(|| BbqError::not_ready("尚未生成真值，请先调用generate_ground_truth", "no ground truth yet; call generate_ground_truth first"))?;
+        let original_vectors = generation.require_original_vectors(Subject::new("召回评估", "recall evaluation"))?;
+
+        let mut approximate = Vec::with_capacity(truth.queries.len());
+        let mut exact = Vec::with_capacity(truth.queries.len());
+        for (&query, neighbors) in truth.queries.iter().zip(&truth.neighbors) {
+            if generation.is_deleted(query) {
+                continue;
+            }
+            let context = self.prepare_query_in(&generation, &original_vectors[query])?;
+            let results = self.search_with_context_in(&generation, &context, truth.k, params)?;
+            approximate.push(results.into_iter().map(|r| r.index).collect::<Vec<_>>());
+            exact.push(neighbors.iter()
+                .copied()
+                .filter(|&ord| !generation.is_deleted(ord))
+                .take(truth.k)
+                .collect::<Vec<_>>());
+        }
+
+        Ok(mean_recall(&approximate, &exact))
+    }
+
+    #[cfg(feature = "eval")]
+    /// 校准自适应过采样倍数
+    ///
+    /// 从1倍开始逐步增大过采样，直到采样查询的召回率达到目标或达到上限；
+    /// 结果保存在索引中，供 `RescoreOversample::Adaptive` 使用
+    ///
+    /// # 参数
+    /// * `queries` - 采样查询向量
+    /// * `k` - 评估的Top-K
+    /// * `target_recall` - 目标召回率（0到1之间）
+    ///
+    /// # 返回
+    /// 校准得到的过采样倍数
+    pub fn calibrate_oversample(
+        &mut self,
+        queries: &[Vec<f32>],
+        k: usize,
+        target_recall: f32,
+    ) -> Result<f32, BbqError> {
+        if !(0.0..=1.0).contains(&target_recall) {
+            return Err(BbqError::out_of_range(
+                format!("目标召回率必须在0-1之间，当前为{}", target_recall),
+                format!("target recall must be within 0-1, got {}", target_recall),
+            ));
+        }
+        if queries.is_empty() {
+            return Err(BbqError::empty_input("校准查询不能为空", "calibration queries must not be empty"));
+        }
+
+        let mut factor = 1.0;
+        loop {
+            let params = SearchParams {
+                rescore_oversample: RescoreOversample::Fixed(factor),
+                ..SearchParams::default()
+            };
+            let recall = self.estimate_recall(queries, k, &params)?;
+            if recall >= target_recall || factor >= MAX_RESCORE_OVERSAMPLE {
+                break;
+            }
+            factor = (factor * 2.0).min(MAX_RESCORE_OVERSAMPLE);
+        }
+
+        self.learned_oversample = Some(factor);
+        self.result_cache().invalidate();
+        Ok(factor)
+    }
+
+    /// 获取校准得到的过采样倍数
+    pub fn get_learned_oversample(&self) -> Option<f32> {
+        self.learned_oversample
+    }
+
+    /// 设置过采样倍数（用于从持久化状态恢复）
+    pub fn set_learned_oversample(&mut self, factor: Option<f32>) {
+        self.learned_oversample = factor;
+        self.result_cache().invalidate();
+    }
+
+    /// 设置最终分数变换，为None时不变换
+    ///
+    /// 变换在归一化之后应用于所有搜索结果，用于把分数映射到应用的相关度刻度；
+    /// 变换单调不减，不改变Top-K的顺序
+    pub fn set_score_transform(&mut self, transform: Option<ScoreTransform>) {
+        self.score_transform = transform;
+        self.result_cache().invalidate();
+    }
+
+    /// 获取最终分数变换
+    pub fn get_score_transform(&self) -> Option<&ScoreTransform> {
+        self.score_transform.as_ref()
+    }
+
+    /// 预热索引
+    ///
+    /// 遍历所有打包缓冲区和修正项，并以质心为探测查询走通量化与评分内核，
+    /// 使首个用户查询不必承担缺页和初始化开销；探测查询不写入结果缓存
+    ///
+    /// # 返回
+    /// 预热报告（含耗时）
+    pub fn warmup(&self) -> Result<WarmupReport, BbqError> {
+        let generation = self.snapshot()?;
+        let quantized_vectors = generation.values();
+
+        let start = now_ms();
+        let (bytes_touched, _) = touch_vector_values(quantized_vectors)?;
+        let touch_ms = elapsed_ms(start);
+
+        let probe_start = now_ms();
+        let context = self.prepare_query_in(&generation, quantized_vectors.get_centroid())?;
+        self.search_uncached(&generation, &context, 1, &SearchParams::default(), None)?;
+        let probe_ms = elapsed_ms(probe_start);
+
+        Ok(WarmupReport {
+            vectors_touched: quantized_vectors.size(),
+            bytes_touched,
+            touch_ms,
+            probe_ms,
+            total_ms: elapsed_ms(start),
+        })
+    }
+
+    /// 只预热使用统计中最热的向量
+    ///
+    /// 只遍历最多 `limit` 个热点向量的打包缓冲区和修正项，并以统计中的平均k走一次探测查询，
+    /// 适合大索引重新加载后快速就绪；没有使用统计时退化为只做探测查询
+    ///
+    /// # 返回
+    /// 预热报告（含耗时）
+    pub fn warmup_hot(&self, limit: usize) -> Result<WarmupReport, BbqError> {
+        let generation = self.snapshot()?;
+        let quantized_vectors = generation.values();
+        let (hot, average_k) = {
+            let stats = self.warm_stats();
+            (stats.hot_ordinals(limit), stats.average_k())
+        };
+
+        let start = now_ms();
+        let mut vectors_touched = 0;
+        let mut bytes_touched = 0;
+        let mut checksum = 0u64;
+        // 统计可能来自更早的一代，超出当前范围的序号忽略
+        for ord in hot.into_iter().filter(|&ord| ord < quantized_vectors.size()) {
+            let vector = quantized_vectors.try_vector_value(ord)?;
+            checksum = vector.iter().fold(checksum, |sum, &byte| sum.wrapping_mul(31).wrapping_add(byte as u64));
+            let corrections = quantized_vectors.try_get_corrective_terms(ord)?;
+            checksum ^= corrections.lower_interval.to_bits() as u64;
+            bytes_touched += vector.len() + 4 * std::mem::size_of::<f32>();
+            vectors_touched += 1;
+        }
+        std::hint::black_box(checksum);
+        let touch_ms = elapsed_ms(start);
+
+        let probe_start = now_ms();
+        let k = average_k.map_or(1, |k| (k.round() as usize).max(1));
+        let context = self.prepare_query_in(&generation, quantized_vectors.get_centroid())?;
+        self.search_uncached(&generation, &context, k, &SearchParams::default(), None)?;
+        let probe_ms = elapsed_ms(probe_start);
+
+        Ok(WarmupReport {
+            vectors_touched,
+            bytes_touched,
+            touch_ms,
+            probe_ms,
+            total_ms: elapsed_ms(start),
+        })
+    }
+
+    /// 获取使用统计
+    pub fn get_warm_stats(&self) -> WarmStats {
+        self.warm_stats().clone()
+    }
+
+    /// 设置使用统计（用于从持久化状态恢复）
+    pub fn set_warm_stats(&mut self, stats: WarmStats) {
+        *self.warm_stats.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()) = stats;
+    }
+
+    /// 按保留的序号重新编号使用统计
+    pub(crate) fn remap_warm_stats(&mut self, live: &[usize]) {
+        self.warm_stats.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()).remap(live);
+    }
+
+    /// 调整结果缓存容量，已缓存的结果清空
+    ///
+    /// 可按 `WarmStats::suggested_result_cache_capacity` 设置
+    pub fn set_result_cache_capacity(&mut self, capacity: usize) {
+        self.config.result_cache_capacity = capacity;
+        *self.result_cache.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()) = ResultCache::new(capacity);
+    }
+
+    /// 获取结果缓存统计信息
+    pub fn get_result_cache_stats(&self) -> ResultCacheStats {
+        self.result_cache().stats()
+    }
+
+    /// 清空结果缓存
+    pub fn clear_result_cache(&self) {
+        self.result_cache().invalidate();
+    }
+
+    /// 释放搜索复用的临时缓冲区
+    ///
+    /// 缓冲区随最大的一次搜索增长（候选数量乘以每个候选的字节数），
+    /// 偶尔的大范围搜索之后可调用此方法回收内存，下次搜索时重新分配
+    pub fn reset_scratch(&self) {
+        *self.search_scratch() = SearchScratch::new();
+    }
+
+    /// 搜索临时缓冲区当前占用的字节数
+    pub fn scratch_bytes(&self) -> usize {
+        self.search_scratch().capacity_bytes()
+    }
+
+    /// 设置或移除搜索实验（见 `search_experiment`），设置时统计清零
+    ///
+    /// 实验只作用于经过结果缓存的搜索入口（`search_nearest_neighbors`、`search_with_params`、
+    /// `search_with_context`）；过滤、批量、限时等搜索不受影响
+    pub fn set_search_experiment(&self, experiment: Option<SearchExperiment>) -> Result<(), BbqError> {
+        let state = experiment.map(|config| ExperimentState::new(config, &self.scorer)).transpose()?;
+        *self.experiment() = state;
+        Ok(())
+    }
+
+    /// 当前的搜索实验配置
+    pub fn get_search_experiment(&self) -> Option<SearchExperiment> {
+        self.experiment().as_ref().map(|state| state.config().clone())
+    }
+
+    /// 当前搜索实验的累计统计，未设置实验时为None
+    pub fn get_search_experiment_stats(&self) -> Option<SearchExperimentStats> {
+        self.experiment().as_ref().map(|state| state.stats())
+    }
+
+    /// 获取结果缓存（锁损坏时仍可继续使用）
+    fn result_cache(&self) -> MutexGuard<'_, ResultCache> {
+        self.result_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// 获取搜索实验（锁损坏时仍可继续使用）
+    fn experiment(&self) -> MutexGuard<'_, Option<ExperimentState>> {
+        self.experiment.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// 获取使用统计（锁损坏时仍可继续使用）
+    fn warm_stats(&self) -> MutexGuard<'_, WarmStats> {
+        self.warm_stats.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// 获取搜索临时缓冲区（锁损坏时仍可继续使用）
+    fn search_scratch(&self) -> MutexGuard<'_, SearchScratch> {
+        self.search_scratch.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// 取走搜索临时缓冲区，并发搜索已取走时得到空的缓冲区
+    fn take_scratch(&self) -> SearchScratch {
+        std::mem::take(&mut *self.search_scratch())
+    }
+
+    /// 归还搜索临时缓冲区，与已归还的缓冲区合并
+    fn return_scratch(&self, scratch: SearchScratch) {
+        self.search_scratch().absorb(scratch);
+    }
+
+    /// 搜索结束后回收查询上下文的缓冲区
+    fn recycle_query(&self, context: QueryContext) {
+        let mut scratch = SearchScratch::new();
+        scratch.recycle_context(context);
+        self.return_scratch(scratch);
+    }
+
+    /// 获取搜索流（锁损坏时仍可继续使用）
+    fn search_streams(&self) -> MutexGuard<'_, SearchStreams> {
+        self.search_streams.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// 获取当前代的快照
+    ///
+    /// 搜索在开始时获取快照并在整个过程中只读这一代，
+    /// 期间完成的压缩不会影响正在进行的搜索
+    pub fn snapshot(&self) -> Result<Arc<IndexGeneration>, BbqError> {
+        let slot = self.generation.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        slot.clone().ok_or_else(|| BbqError::not_built("索引未构建，请先调用build_index", "the index has not been built; call build_index first"))
+    }
+
+    /// 当前代的代号（索引未构建时为None）
+    pub fn generation_number(&self) -> Option<u64> {
+        self.snapshot().ok().map(|generation| generation.number())
+    }
+
+    /// 可变访问当前代（仍被某个快照持有时先复制一份）
+    fn generation_mut(&mut self) -> Result<&mut IndexGeneration, BbqError> {
+        let slot = self.generation.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let generation = slot.as_mut()
+            .map(Arc::make_mut)
+            .ok_or_else(|| BbqError::not_built("索引未构建，请先调用build_index", "the index has not been built; call build_index first"))?;
+        // 修改后回到构建状态，见 `finalize_for_search`
+        generation.search_layout = None;
+        Ok(generation)
+    }
+
+    /// 把当前代转换为搜索优化布局（见 `search_layout`）
+    ///
+    /// 未删除向量的打包向量和修正项按序号连续复制一份，之后无过滤、无IVF探测、
+    /// 没有设置过期时间的搜索直接扫描布局，不再逐批跳过墓碑和收集修正项；结果与转换前逐位相同。
+    /// 布局额外占用约 `未删除数量 × (打包维度 + 16)` 字节，对当前代的任何修改都会丢弃布局，
+    /// 索引仍然可以继续修改和搜索，需要时再次调用即可。与压缩一样，新的一代在旁边构建后原子替换
+    pub fn finalize_for_search(&self) -> Result<(), BbqError> {
+        let current = self.snapshot()?;
+        if current.search_layout.is_some() {
+            return Ok(());
+        }
+        let live: Vec<usize> = (0..current.size()).filter(|&ord| !current.is_deleted(ord)).collect();
+        // 不一致的向量与构建状态的搜索一样跳过
+        #[cfg(feature = "paranoid")]
+        let live = {
+            let mut live = live;
+            consistency::retain_consistent_candidates(current.values(), self.config.index_bits, &mut live);
+            live
+        };
+        let layout = SearchLayout::build(current.values(), live)?;
+
+        // 内容不变，保留代号，结果缓存仍然有效
+        let mut generation = (*current).clone();
+        generation.search_layout = Some(Arc::new(layout));
+        let mut slot = self.generation.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if slot.as_ref().map(|generation| generation.number()) != Some(current.number()) {
+            return Err(BbqError::concurrent_modification(Subject::new("转换", "conversion")));
+        }
+        *slot = Some(Arc::new(generation));
+        Ok(())
+    }
+
+    /// 当前代是否已转换为搜索优化布局
+    pub fn is_finalized(&self) -> bool {
+        self.snapshot().is_ok_and(|generation| generation.search_layout.is_some())
+    }
+
+    /// 压缩索引：移除已删除的向量，可选地用剩余向量重新计算质心并重新量化
+    ///
+    /// 新的一代在旁边构建，完成后原子替换当前代；压缩期间的搜索继续读取旧的一代。
+    /// 压缩后序号会变化：剩余向量按原顺序重新编号，报告中的 `remap` 可转换之前保存的序号
+    ///
+    /// # 参数
+    /// * `refresh_centroid` - 是否重新计算质心（需要保留原始向量）
+    pub fn compact(&self, refresh_centroid: bool) -> Result<CompactionReport, BbqError> {
+        self.ensure_writable()?;
+        let current = self.snapshot()?;
+        let live: Vec<usize> = (0..current.size()).filter(|&ord| !current.is_deleted(ord)).collect();
+        if live.is_empty() {
+            return Err(BbqError::empty_input("压缩后索引为空，请重新构建索引", "the index is empty after compaction; rebuild it"));
+        }
+        // 新的一代与旧的一代同时存在，先确认堆中还能容纳
+        reserve_headroom(
+            estimate_index_bytes(live.len(), current.values().dimension(), self.config.index_bits, self.original_encoding()),
+            Subject::new("压缩后的索引", "compacted index"),
+        )?;
+
+        let (values, quality_scores, centroid_epoch, original_vectors): (Arc<dyn QuantizedVectorValues>, Vec<f32>, u64, _) = if refresh_centroid {
+            let all = current.require_original_vectors(Subject::new("刷新质心", "centroid refresh"))?;
+            let vectors: Vec<Vec<f32>> = live.iter().map(|&ord| all[ord].clone()).collect();
+            self.emit(ProgressEvent::BuildStarted { total: vectors.len(), dimension: current.values().dimension() });
+            let centroid = compute_centroid_compensated(&vectors)?;
+            self.emit(ProgressEvent::CentroidComputed { dimension: centroid.len() });
+            let norms = live.iter()
+                .map(|&ord| current.values().try_get_norm(ord))
+                .collect::<Result<Vec<_>, _>>()?;
+            let (values, quality_scores) = self.quantize_vectors(&vectors, centroid, norms, None, None)?;
+            // 质心变化后int8残差的基准也变了，按新的量化向量重新编码
+            let encoding = current.original_vectors.as_ref().map_or(OriginalVectorEncoding::F32, |originals| originals.encoding());
+            let originals = OriginalVectors::encode(vectors, encoding, &values, self.config.index_bits)?;
+            (Arc::new(values), quality_scores, current.centroid_epoch() + 1, Some(originals))
+        } else {
+            let values = current.values();
+            let values = QuantizedVectorValuesImpl::new(
+                live.iter().map(|&ord| values.try_vector_value(ord).map(<[u8]>::to_vec)).collect::<Result<_, _>>()?,
+                live.iter().map(|&ord| values.try_get_unpacked_vector(ord).map(<[u8]>::to_vec)).collect::<Result<_, _>>()?,
+                live.iter().map(|&ord| values.try_get_corrective_terms(ord)).collect::<Result<_, _>>()?,
+                values.get_centroid().to_vec(),
+                live.iter().map(|&ord| values.try_get_norm(ord)).collect::<Result<_, _>>()?,
+            )
+            .with_correction_layout(self.config.correction_layout)?
+            .with_discretized_dimensions(self.config.discretize_dimensions);
+            let quality_scores = live.iter().map(|&ord| current.quality_scores[ord]).collect();
+            let originals = current.original_vectors.as_ref().map(|originals| originals.select(&live));
+            (Arc::new(values), quality_scores, current.centroid_epoch(), originals)
+        };
+
+        let number = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        let mut generation = IndexGeneration::new(number, centroid_epoch, values, original_vectors, quality_scores);
+        generation.attributes = live.iter().map(|&ord| current.attributes[ord].clone()).collect();
+        generation.provenance = live.iter().map(|&ord| current.provenance[ord].clone()).collect();
+        generation.expires_at = live.iter().map(|&ord| current.expires_at[ord]).collect();
+        generation.next_expiry = generation.expires_at.iter().flatten()
+            .copied()
+            .reduce(f64::min);
+        // 质心刷新后真值仍然有效：精确近邻只依赖原始向量
+        #[cfg(feature = "eval")]
+        {
+            generation.ground_truth = current.ground_truth.as_ref().map(|truth| truth.remap(&live));
+        }
+        // IVF列表基于原始向量，质心刷新不影响划分
+        #[cfg(feature = "ivf")]
+        {
+            generation.ivf = current.ivf.as_ref().map(|ivf| Arc::new(ivf.remap(&live)));
+        }
+        // 提升的向量随序号重新编号，质心变化后按新质心重新量化
+        if let Some(promoted) = current.promoted() {
+            let mut remapped = promoted.remap(&live);
+            if refresh_centroid {
+                let vectors = generation.require_original_vectors(Subject::new("刷新质心", "centroid refresh"))?;
+                let ords = remapped.ordinals();
+                let vectors = ords.iter().map(|&ord| (ord, vectors[ord].as_slice()));
+                self.quantize_promoted(vectors, generation.values().get_centroid(), &mut remapped)?;
+            }
+            generation.promoted = Some(Arc::new(remapped));
+        }
+
+        let mut slot = self.generation.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if slot.as_ref().map(|generation| generation.number()) != Some(current.number()) {
+            return Err(BbqError::concurrent_modification(Subject::new("压缩", "compaction")));
+        }
+        *slot = Some(Arc::new(generation));
+        drop(slot);
+        self.result_cache().invalidate();
+        self.warm_stats().remap(&live);
+
+        let report = CompactionReport {
+            generation: number,
+            removed: current.size() - live.len(),
+            remaining: live.len(),
+            remap: OrdinalRemap::new(current.size(), live),
+        };
+        self.emit(ProgressEvent::Compacted {
+            generation: report.generation,
+            removed: report.removed,
+            remaining: report.remaining,
+        });
+        Ok(report)
+    }
+
+    /// 原子地应用一批插入、更新和删除
+    ///
+    /// 先按顺序校验全部操作（维度、数值、序号范围，后面的操作可以引用前面插入的序号），
+    /// 任一操作无效时返回错误且不做任何修改；之后与压缩一样在旁边构建新的一代并原子替换，
+    /// 并发的搜索要么看到全部修改，要么一个也看不到。
+    /// 写入的向量按当前质心量化，质心不变，已准备的查询上下文仍然有效；
+    /// 有写入时缓存的真值被清除（写入的向量可能成为精确近邻），IVF划分按现有中心分配写入的向量
+    ///
+    /// # 参数
+    /// * `ops` - 按顺序应用的操作
+    pub fn apply_batch(&self, ops: Vec<IndexOp>) -> Result<BatchReport, BbqError> {
+        self.ensure_writable()?;
+        let current = self.snapshot()?;
+        let dimension = current.values().dimension();
+        let check_vector = |i: usize, vector: &[f32]| -> Result<(), BbqError> {
+            if vector.len() != dimension {
+                return Err(BbqError::dimension_mismatch(
+                    format!("操作 {}: 向量维度 {} 与索引维度 {} 不匹配", i, vector.len(), dimension),
+                    format!("operation {}: vector dimension {} does not match index dimension {}", i, vector.len(), dimension),
+                ));
+            }
+            match vector.iter().position(|value| !value.is_finite()) {
+                Some(j) => Err(BbqError::invalid_value(
+                    format!("操作 {}: 向量位置 {} 包含无效值: {}", i, j, vector[j]),
+                    format!("operation {}: vector position {} contains an invalid value: {}", i, j, vector[j]),
+                )),
+                None => Ok(()),
+            }
+        };
+
+        // 1. 按顺序校验并得到最终状态：每个写入的序号对应的新向量
+        let mut size = current.size();
+        let mut deleted = current.deleted.clone();
+        let mut written: BTreeMap<usize, Vec<f32>> = BTreeMap::new();
+        let mut inserted_attributes = Vec::new();
+        let mut report = BatchReport { generation: current.number(), inserted: Vec::new(), updated: 0, deleted: 0 };
+        for (i, op) in ops.into_iter().enumerate() {
+            match op {
+                IndexOp::Insert { vector, attributes } => {
+                    check_vector(i, &vector)?;
+                    written.insert(size, vector);
+                    inserted_attributes.push(attributes);
+                    report.inserted.push(size);
+                    size += 1;
+                }
+                IndexOp::Update { ord, vector } => {
+                    check_vector(i, &vector)?;
+                    if ord >= size {
+                        return Err(BbqError::out_of_range(
+                            format!("操作 {}: 序号 {} 超出索引范围", i, ord),
+                            format!("operation {}: ordinal {} is outside the index", i, ord),
+                        ));
+                    }
+                    if deleted.contains(ord) {
+                        return Err(BbqError::invalid_argument(
+                            format!("操作 {}: 序号 {} 已删除，不能更新", i, ord),
+                            format!("operation {}: ordinal {} is deleted and cannot be updated", i, ord),
+                        ));
+                    }
+                    written.insert(ord, vector);
+                    report.updated += 1;
+                }
+                IndexOp::Delete { ord } => {
+                    if ord >= size {
+                        return Err(BbqError::out_of_range(
+                            format!("操作 {}: 序号 {} 超出索引范围", i, ord),
+                            format!("operation {}: ordinal {} is outside the index", i, ord),
+                        ));
+                    }
+                    if deleted.insert(ord) {
+                        report.deleted += 1;
+                    }
+                }
+            }
+        }
+        if written.is_empty() && report.deleted == 0 {
+            return Ok(report);
+        }
+        // 新的一代与旧的一代同时存在，先确认堆中还能容纳
+        reserve_headroom(
+            estimate_index_bytes(size, dimension, self.config.index_bits, self.original_encoding()),
+            Subject::new("批量操作后的索引", "index after the batch"),
+        )?;
+
+        // 2. 按当前质心量化写入的向量
+        let ords: Vec<usize> = written.keys().copied().collect();
+        let raw: Vec<Vec<f32>> = written.into_values().collect();
+        let processed = self.preprocess_vectors(&raw);
+        let norms = self.input_norms(&raw);
+        let values = current.values();
+        // 分组布局下新写入的向量沿用当前代的共享区间
+        // （退化向量各自保留的区间不参与共享）
+        let shared_intervals = values.shared_intervals().map(|intervals| {
+            intervals.iter().copied().filter(|&(lower, upper)| lower < upper).collect::<Vec<_>>()
+        });
+        let (fresh, fresh_quality) = self.quantize_vectors(&processed, values.get_centroid().to_vec(), norms, None, shared_intervals)?;
+        for (i, &ord) in ords.iter().enumerate() {
+            if fresh.try_get_corrective_terms(i)?.is_degenerate() {
+                match self.config.degenerate_vectors {
+                    DegenerateVectorPolicy::Keep => {}
+                    DegenerateVectorPolicy::Skip => {
+                        deleted.insert(ord);
+                    }
+                    DegenerateVectorPolicy::Reject => {
+                        return Err(BbqError::invalid_value(
+                            format!("向量 {} 减去质心后为常量（如全零向量），无法量化", ord),
+                            format!("vector {} is constant after centering (e.g. all zeros) and cannot be quantized", ord),
+                        ));
+                    }
+                }
+            }
+        }
+
+        // 3. 合并为新的一代：未写入的序号沿用当前代的数据
+        let mut source = vec![None; size];
+        for (i, &ord) in ords.iter().enumerate() {
+            source[ord] = Some(i);
+        }
+        let pick = |ord: usize| -> (&dyn QuantizedVectorValues, usize) {
+            match source[ord] {
+                Some(i) => (&fresh, i),
+                None => (values, ord),
+            }
+        };
+        let merged = QuantizedVectorValuesImpl::new(
+            (0..size).map(|ord| { let (from, at) = pick(ord); from.try_vector_value(at).map(<[u8]>::to_vec) }).collect::<Result<_, _>>()?,
+            (0..size).map(|ord| { let (from, at) = pick(ord); from.try_get_unpacked_vector(at).map(<[u8]>::to_vec) }).collect::<Result<_, _>>()?,
+            (0..size).map(|ord| { let (from, at) = pick(ord); from.try_get_corrective_terms(at) }).collect::<Result<_, _>>()?,
+            values.get_centroid().to_vec(),
+            (0..size).map(|ord| { let (from, at) = pick(ord); from.try_get_norm(at) }).collect::<Result<_, _>>()?,
+        )
+        .with_correction_layout(self.config.correction_layout)?
+        .with_discretized_dimensions(self.config.discretize_dimensions);
+        let quality_scores = (0..size)
+            .map(|ord| source[ord].map_or_else(|| current.quality_scores[ord], |i| fresh_quality[i]))
+            .collect();
+        let original_vectors = match &current.original_vectors {
+            Some(originals) => {
+                let decoded = originals.decode_all(values)?;
+                let vectors = (0..size)
+                    .map(|ord| source[ord].map_or_else(|| decoded[ord].clone(), |i| processed[i].clone()))
+                    .collect();
+                Some(OriginalVectors::encode(vectors, originals.encoding(), &merged, self.config.index_bits)?)
+            }
+            None => None,
+        };
+
+        let number = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        let mut generation = IndexGeneration::new(number, current.centroid_epoch(), Arc::new(merged), original_vectors, quality_scores);
+        generation.attributes = current.attributes.clone();
+        generation.attributes.extend(inserted_attributes);
+        generation.provenance = current.provenance.clone();
+        generation.provenance.resize(size, None);
+        generation.expires_at = current.expires_at.clone();
+        generation.expires_at.resize(size, None);
+        generation.next_expiry = current.next_expiry;
+        generation.deleted = deleted;
+        #[cfg(feature = "eval")]
+        {
+            generation.ground_truth = current.ground_truth.clone().filter(|_| ords.is_empty());
+        }
+        #[cfg(feature = "ivf")]
+        {
+            let written: Vec<(usize, &[f32])> = ords.iter().copied().zip(processed.iter().map(Vec::as_slice)).collect();
+            generation.ivf = current.ivf.as_ref().map(|ivf| Arc::new(ivf.reassign(&written)));
+        }
+        // 被替换的提升向量按新向量重新量化
+        if let Some(promoted) = current.promoted() {
+            let replaced: Vec<(usize, &[f32])> = ords.iter()
+                .zip(processed.iter())
+                .filter(|&(&ord, _)| promoted.contains(ord))
+                .map(|(&ord, vector)| (ord, vector.as_slice()))
+                .collect();
+            let mut promoted = promoted.clone();
+            self.quantize_promoted(replaced, values.get_centroid(), &mut promoted)?;
+            generation.promoted = Some(Arc::new(promoted));
+        }
+
+        let mut slot = self.generation.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if slot.as_ref().map(|generation| generation.number()) != Some(current.number()) {
+            return Err(BbqError::concurrent_modification(Subject::new("批量操作", "the batch")));
+        }
+        *slot = Some(Arc::new(generation));
+        drop(slot);
+        self.result_cache().invalidate();
+        report.generation = number;
+        Ok(report)
+    }
+
+    #[cfg(feature = "ivf")]
+    /// 建立IVF粗划分（需要保留原始向量）
+    ///
+    /// # 参数
+    /// * `nlist` - 列表数量
+    /// * `iterations` - k-means迭代次数
+    /// * `seed` - 选取初始中心的随机种子
+    ///
+    /// # 返回
+    /// 划分统计
+    pub fn build_ivf(&mut self, nlist: usize, iterations: usize, seed: u64) -> Result<IvfStatistics, BbqError> {
+        self.build_ivf_with_rng(nlist, iterations, &mut fastrand::Rng::with_seed(seed))
+    }
+
+    #[cfg(feature = "ivf")]
+    /// 使用指定的随机来源建立IVF粗划分，见 `build_ivf`
+    pub fn build_ivf_with_rng<R: RngSource + ?Sized>(
+        &mut self,
+        nlist: usize,
+        iterations: usize,
+        rng: &mut R,
+    ) -> Result<IvfStatistics, BbqError> {
+        self.ensure_writable()?;
+        let similarity_function = self.config.similarity_function;
+        let generation = self.generation_mut()?;
+        let partition = IvfPartition::train_with(
+            &generation.require_original_vectors(Subject::new("IVF划分", "IVF partitioning"))?,
+            nlist,
+            iterations,
+            rng,
+            similarity_function,
+        )?;
+        let deleted = &generation.deleted;
+        let statistics = partition.statistics(|ord| !deleted.contains(ord));
+        generation.ivf = Some(Arc::new(partition));
+        self.result_cache().invalidate();
+        Ok(statistics)
+    }
+
+    #[cfg(feature = "graph")]
+    /// 构建k近邻图（需要1位索引）：每对未删除的向量用1位码评分一次，结果同时计入两端
+    ///
+    /// # 参数
+    /// * `k` - 每个向量的近邻数量
+    pub fn build_knn_graph(&self, k: usize) -> Result<KnnGraph, BbqError> {
+        if self.config.index_bits != 1 {
+            return Err(BbqError::invalid_argument(
+                format!("k近邻图只支持1位索引，当前为{}位", self.config.index_bits),
+                format!("the k-NN graph only supports 1-bit indexes, got {} bits", self.config.index_bits),
+            ));
+        }
+        let generation = self.snapshot()?;
+        build_knn_graph(&generation, &self.scorer, k)
+    }
+
+    #[cfg(feature = "graph")]
+    /// 在k近邻图上按相似度阈值做连通分量聚类，返回每个序号的簇标签
+    ///
+    /// # 参数
+    /// * `k` - 构建k近邻图时每个向量的近邻数量
+    /// * `threshold` - 合并两端的最低分数
+    pub fn cluster(&self, k: usize, threshold: f32) -> Result<Clustering, BbqError> {
+        Ok(self.build_knn_graph(k)?.connected_components(threshold))
+    }
+
+    /// 每维中心化值的直方图和量化重建误差（需要保留原始向量，只统计未删除的向量）
+    ///
+    /// # 参数
+    /// * `bins` - 每维的桶数
+    pub fn dimension_quality(&self, bins: usize) -> Result<Vec<DimensionQuality>, BbqError> {
+        let generation = self.snapshot()?;
+        let originals = generation.require_original_vectors(Subject::new("维度统计", "dimension statistics"))?;
+        let values = generation.values();
+        let (vectors, reconstructions): (Vec<Vec<f32>>, Vec<Vec<f32>>) = values.iter_reconstructed(self.config.index_bits)
+            .filter(|(ord, _)| !generation.is_deleted(*ord))
+            .map(|(ord, reconstruction)| (originals[ord].clone(), reconstruction))
+            .unzip();
+        compute_dimension_quality(&vectors, &reconstructions, values.get_centroid(), bins)
+    }
+
+    #[cfg(feature = "graph")]
+    /// 二维投影：返回每个序号的x/y坐标，用于绘制嵌入分布图
+    ///
+    /// 没有保留原始向量时用量化值重建的向量做PCA；图细化需要1位索引。
+    /// 已删除的序号坐标为NaN
+    ///
+    /// # 参数
+    /// * `config` - 投影配置
+    pub fn project_2d(&self, config: &ProjectionConfig) -> Result<Vec<[f32; 2]>, BbqError> {
+        let generation = self.snapshot()?;
+        let live: Vec<usize> = (0..generation.size()).filter(|&ord| !generation.is_deleted(ord)).collect();
+        if live.is_empty() {
+            return Err(BbqError::empty_input("索引中没有可投影的向量", "the index has no vectors to project"));
+        }
+        let vectors = live.iter()
+            .map(|&ord| match generation.original_vector(ord) {
+                Some(vector) => Ok(vector.into_owned()),
+                None => reconstruct_vector(generation.values(), self.config.index_bits, ord),
+            })
+            .collect::<Result<Vec<_>, BbqError>>()?;
+
+        let mut points = vec![[f32::NAN; 2]; generation.size()];
+        for (&ord, point) in live.iter().zip(project_pca(&vectors, config.power_iterations, config.seed)?) {
+            points[ord] = point;
+        }
+        if config.refine_iterations > 0 {
+            let graph = self.build_knn_graph(config.neighbors)?;
+            refine_with_graph(&mut points, &graph, config.refine_iterations, config.seed)?;
+        }
+        Ok(points)
+    }
+
+    #[cfg(feature = "ivf")]
+    /// IVF划分统计（只统计未删除的向量）
+    pub fn ivf_statistics(&self) -> Result<IvfStatistics, BbqError> {
+        let generation = self.snapshot()?;
+        let ivf = generation.ivf().ok_or_else(|| BbqError::not_built("索引未建立IVF划分，请先调用build_ivf", "the index has no IVF partition; call build_ivf first"))?;
+        Ok(ivf.statistics(|ord| !generation.is_deleted(ord)))
+    }
+
+    #[cfg(feature = "ivf")]
+    /// 重新平衡IVF划分：从当前列表中心继续迭代，并把所有向量重新分配到最近的列表
+    ///
+    /// 数据漂移后列表会失衡，小nprobe下召回率下降；与压缩一样，新的一代在旁边构建后原子替换
+    ///
+    /// # 参数
+    /// * `iterations` - k-means迭代次数
+    pub fn rebalance(&self, iterations: usize) -> Result<IvfStatistics, BbqError> {
+        self.ensure_writable()?;
+        let current = self.snapshot()?;
+        let ivf = current.ivf().ok_or_else(|| BbqError::not_built("索引未建立IVF划分，请先调用build_ivf", "the index has no IVF partition; call build_ivf first"))?;
+        let vectors = current.require_original_vectors(Subject::new("IVF划分", "IVF partitioning"))?;
+        let partition = ivf.rebalance(&vectors, iterations, |ord| !current.is_deleted(ord));
+        let statistics = partition.statistics(|ord| !current.is_deleted(ord));
+
+        let mut generation = (*current).clone();
+        generation.number = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        generation.ivf = Some(Arc::new(partition));
+
+        let mut slot = self.generation.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if slot.as_ref().map(|generation| generation.number()) != Some(current.number()) {
+            return Err(BbqError::concurrent_modification(Subject::new("重新平衡", "rebalancing")));
+        }
+        *slot = Some(Arc::new(generation));
+        drop(slot);
+        self.result_cache().invalidate();
+        Ok(statistics)
+    }
+
+    /// 设置向量的属性（覆盖原有属性）
+    pub fn set_attributes(&mut self, ord: usize, attributes: Attributes) -> Result<(), BbqError> {
+        self.ensure_writable()?;
+        let slot = self.generation_mut()?.attributes.get_mut(ord)
+            .ok_or_else(|| BbqError::out_of_range(format!("序号 {} 超出索引范围", ord), format!("ordinal {} is outside the index", ord)))?;
+        *slot = attributes;
+        self.result_cache().invalidate();
+        Ok(())
+    }
+
+    /// 获取向量的属性
+    pub fn get_attributes(&self, ord: usize) -> Option<Attributes> {
+        self.snapshot().ok()?.attributes(ord).cloned()
+    }
+
+    /// 设置向量对应的文本块来源，None表示清除
+    ///
+    /// 来源只用于结果分组，不影响搜索结果，因此不会使结果缓存失效
+    pub fn set_provenance(&mut self, ord: usize, provenance: Option<ChunkProvenance>) -> Result<(), BbqError> {
+        self.ensure_writable()?;
+        let slot = self.generation_mut()?.provenance.get_mut(ord)
+            .ok_or_else(|| BbqError::out_of_range(format!("序号 {} 超出索引范围", ord), format!("ordinal {} is outside the index", ord)))?;
+        *slot = provenance;
+        Ok(())
+    }
+
+    /// 获取向量对应的文本块来源
+    pub fn get_provenance(&self, ord: usize) -> Option<ChunkProvenance> {
+        self.snapshot().ok()?.provenance(ord).cloned()
+    }
+
+    /// 把搜索结果中同一文档相邻或重叠的块合并，见 `group_adjacent_chunks`
+    pub fn group_results(&self, results: &[QueryResult], params: &ChunkGroupingParams) -> Result<Vec<GroupedHit>, BbqError> {
+        let generation = self.snapshot()?;
+        Ok(group_adjacent_chunks(results, |ord| generation.provenance(ord), params))
+    }
+
+    /// 搜索k个最相似的块，再按文档合并相邻的块
+    ///
+    /// 合并后的结果数量不超过k；需要k个合并结果时可以适当增大k
+    pub fn search_grouped(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        params: &SearchParams,
+        grouping: &ChunkGroupingParams,
+    ) -> Result<Vec<GroupedHit>, BbqError> {
+        let generation = self.snapshot()?;
+        let context = self.prepare_query_in(&generation, query_vector)?;
+        let results = self.search_with_context_in(&generation, &context, k, params)?;
+        Ok(group_adjacent_chunks(&results, |ord| generation.provenance(ord), grouping))
+    }
+
+    /// 删除向量（标记墓碑，之后的搜索不再返回）
+    ///
+    /// # 返回
+    /// 向量之前未被删除时返回true
+    pub fn delete(&mut self, ord: usize) -> Result<bool, BbqError> {
+        self.ensure_writable()?;
+        let generation = self.generation_mut()?;
+        if ord >= generation.size() {
+            return Err(BbqError::out_of_range(format!("序号 {} 超出索引范围", ord), format!("ordinal {} is outside the index", ord)));
+        }
+        let newly_deleted = generation.deleted.insert(ord);
+        if newly_deleted {
+            self.result_cache().invalidate();
+        }
+        Ok(newly_deleted)
+    }
+
+    /// 删除向量，同 `delete`
+    pub fn remove_vector(&mut self, ord: usize) -> Result<bool, BbqError> {
+        self.delete(ord)
+    }
+
+    /// 批量删除向量
+    ///
+    /// 先检查全部序号，有越界的序号时不删除任何向量；重复或已删除的序号不计入返回值。
+    /// 删除只标记墓碑，存储在 `compact` 时才回收；没有新删除的向量时当前代（包括搜索布局）保持不变
+    ///
+    /// # 返回
+    /// 本次新删除的向量数量
+    pub fn remove_vectors(&mut self, ords: &[usize]) -> Result<usize, BbqError> {
+        self.ensure_writable()?;
+        {
+            let current = self.snapshot()?;
+            if let Some(&ord) = ords.iter().find(|&&ord| ord >= current.size()) {
+                return Err(BbqError::out_of_range(format!("序号 {} 超出索引范围", ord), format!("ordinal {} is outside the index", ord)));
+            }
+            if ords.iter().all(|&ord| current.deleted.contains(ord)) {
+                return Ok(0);
+            }
+        }
+        let generation = self.generation_mut()?;
+        let deleted = ords.iter().filter(|&&ord| generation.deleted.insert(ord)).count();
+        if deleted > 0 {
+            self.result_cache().invalidate();
+        }
+        Ok(deleted)
+    }
+
+    /// 以 `promoted_bits` 额外量化指定的向量（混合精度），搜索时这些向量改用高位数的量化值评分
+    ///
+    /// 适合标记少量重要的向量；需要保留原始向量，有越界序号时整批不生效。
+    /// 被批量操作替换的提升向量会按新向量重新量化，压缩后随序号重新编号
+    ///
+    /// # 返回
+    /// 本次新提升的向量数量
+    pub fn promote_vectors(&mut self, ords: &[usize]) -> Result<usize, BbqError> {
+        self.ensure_writable()?;
+        if self.config.promoted_bits <= self.config.index_bits {
+            return Err(BbqError::invalid_argument(
+                format!("提升的位数 {} 必须大于索引位数 {}", self.config.promoted_bits, self.config.index_bits),
+                format!("promotion bits {} must exceed the index bits {}", self.config.promoted_bits, self.config.index_bits),
+            ));
+        }
+        let current = self.snapshot()?;
+        if let Some(&ord) = ords.iter().find(|&&ord| ord >= current.size()) {
+            return Err(BbqError::out_of_range(format!("序号 {} 超出索引范围", ord), format!("ordinal {} is outside the index", ord)));
+        }
+        let mut promoted = match current.promoted.as_deref() {
+            Some(promoted) => promoted.clone(),
+            None => PromotedVectors::new(self.config.promoted_bits)?,
+        };
+        let mut fresh: Vec<usize> = ords.iter().copied().filter(|&ord| !promoted.contains(ord)).collect();
+        fresh.sort_unstable();
+        fresh.dedup();
+        if fresh.is_empty() {
+            return Ok(0);
+        }
+        let vectors = current.require_original_vectors(Subject::new("提升精度", "promotion"))?;
+        let centroid = current.values().get_centroid();
+        self.quantize_promoted(fresh.iter().map(|&ord| (ord, vectors[ord].as_slice())), centroid, &mut promoted)?;
+        drop(vectors);
+        drop(current);
+        self.generation_mut()?.promoted = Some(Arc::new(promoted));
+        self.result_cache().invalidate();
+        Ok(fresh.len())
+    }
+
+    /// 按量化质量从低到高提升一部分未删除的向量，见 `promote_vectors`
+    ///
+    /// # 参数
+    /// * `fraction` - 提升的比例（大于0且不超过1），按未删除的非退化向量计算
+    ///
+    /// # 返回
+    /// 本次新提升的向量数量
+    pub fn promote_lowest_quality(&mut self, fraction: f32) -> Result<usize, BbqError> {
+        if !(fraction > 0.0 && fraction <= 1.0) {
+            return Err(BbqError::out_of_range(
+                format!("提升比例必须在0-1之间，当前为{}", fraction),
+                format!("promotion fraction must be within 0-1, got {}", fraction),
+            ));
+        }
+        let generation = self.snapshot()?;
+        let ords = lowest_quality_ordinals(&generation.quality_scores, fraction, |ord| {
+            !generation.is_deleted(ord) && !generation.is_degenerate(ord)
+        });
+        drop(generation);
+        self.promote_vectors(&ords)
+    }
+
+    /// 取消向量的提升，之后按索引位数评分
+    ///
+    /// # 返回
+    /// 本次取消提升的向量数量
+    pub fn demote_vectors(&mut self, ords: &[usize]) -> Result<usize, BbqError> {
+        self.ensure_writable()?;
+        // 没有要取消的提升时不修改当前代，搜索优化布局得以保留
+        let any_promoted = self.snapshot()?
+            .promoted()
+            .is_some_and(|promoted| ords.iter().any(|&ord| promoted.contains(ord)));
+        if !any_promoted {
+            return Ok(0);
+        }
+        let generation = self.generation_mut()?;
+        let Some(promoted) = generation.promoted.as_mut() else {
+            return Ok(0);
+        };
+        let promoted = Arc::make_mut(promoted);
+        let demoted = ords.iter().filter(|&&ord| promoted.remove(ord)).count();
+        self.result_cache().invalidate();
+        Ok(demoted)
+    }
+
+    /// 提升了位数的向量数量
+    pub fn promoted_count(&self) -> usize {
+        self.snapshot().ok().and_then(|generation| generation.promoted().map(PromotedVectors::len)).unwrap_or(0)
+    }
+
+    /// 删除所有满足过滤条件的向量
+    ///
+    /// # 返回
+    /// 本次新删除的向量数量
+    pub fn delete_where(&mut self, filter: &Filter) -> Result<usize, BbqError> {
+        self.ensure_writable()?;
+        let Ok(generation) = self.generation_mut() else {
+            return Ok(0);
+        };
+        let mut deleted = 0;
+        for ord in 0..generation.size() {
+            if !generation.deleted.contains(ord)
+                && filter.matches(ord, generation.attributes.get(ord))
+                && generation.deleted.insert(ord)
+            {
+                deleted += 1;
+            }
+        }
+        if deleted > 0 {
+            self.result_cache().invalidate();
+        }
+        Ok(deleted)
+    }
+
+    /// 设置向量的过期时间
+    ///
+    /// # 参数
+    /// * `ord` - 向量序号
+    /// * `expires_at` - 过期时间（毫秒时间戳，与 `Date.now()` 一致），None表示永不过期
+    pub fn set_expiry(&mut self, ord: usize, expires_at: Option<f64>) -> Result<(), BbqError> {
+        self.ensure_writable()?;
+        if let Some(expiry) = expires_at {
+            if !expiry.is_finite() {
+                return Err(BbqError::invalid_value(format!("无效的过期时间: {}", expiry), format!("invalid expiry: {}", expiry)));
+            }
+        }
+        let generation = self.generation_mut()?;
+        let slot = generation.expires_at.get_mut(ord)
+            .ok_or_else(|| BbqError::out_of_range(format!("序号 {} 超出索引范围", ord), format!("ordinal {} is outside the index", ord)))?;
+        *slot = expires_at;
+        if let Some(expiry) = expires_at {
+            generation.next_expiry = Some(generation.next_expiry.map_or(expiry, |next| next.min(expiry)));
+        }
+        self.result_cache().invalidate();
+        Ok(())
+    }
+
+    /// 获取向量的过期时间
+    pub fn get_expiry(&self, ord: usize) -> Option<f64> {
+        self.snapshot().ok()?.expiry(ord)
+    }
+
+    /// 将所有在now时刻已过期的向量标记为删除
+    ///
+    /// # 参数
+    /// * `now` - 当前时间（毫秒时间戳）
+    ///
+    /// # 返回
+    /// 本次新删除的向量数量
+    pub fn purge_expired(&mut self, now: f64) -> Result<usize, BbqError> {
+        self.ensure_writable()?;
+        let Ok(generation) = self.generation_mut() else {
+            return Ok(0);
+        };
+        let mut purged = 0;
+        let mut next_expiry: Option<f64> = None;
+        for ord in 0..generation.expires_at.len() {
+            if generation.deleted.contains(ord) {
+                continue;
+            }
+            if generation.is_expired_at(ord, now) {
+                generation.deleted.insert(ord);
+                purged += 1;
+            } else if let Some(expiry) = generation.expires_at[ord] {
+                next_expiry = Some(next_expiry.map_or(expiry, |next| next.min(expiry)));
+            }
+        }
+        generation.next_expiry = next_expiry;
+        if purged > 0 {
+            self.result_cache().invalidate();
+        }
+        Ok(purged)
+    }
+
+    /// 向量是否已删除
+    pub fn is_deleted(&self, ord: usize) -> bool {
+        self.snapshot().is_ok_and(|generation| generation.is_deleted(ord))
+    }
+
+    /// 已删除的序号
+    pub fn get_deleted(&self) -> OrdinalBitset {
+        self.snapshot().map(|generation| generation.deleted.clone()).unwrap_or_default()
+    }
+
+    /// 向量总数（含已删除）
+    pub fn size(&self) -> usize {
+        self.snapshot().map_or(0, |generation| generation.size())
+    }
+
+    /// 未删除的向量数量
+    pub fn live_count(&self) -> usize {
+        self.snapshot().map_or(0, |generation| generation.live_count())
+    }
+
+    /// 向量是否为退化向量（减去质心后为常量，包括全零向量）
+    pub fn is_degenerate(&self, ord: usize) -> bool {
+        self.snapshot().is_ok_and(|generation| generation.is_degenerate(ord))
+    }
+
+    /// 获取向量的量化质量（1 - 相对重建误差，0到1之间）
+    pub fn get_quality_score(&self, ord: usize) -> Option<f32> {
+        self.snapshot().ok()?.quality_score(ord)
+    }
+
+    /// 获取所有向量的量化质量
+    pub fn get_quality_scores(&self) -> Vec<f32> {
+        self.snapshot().map(|generation| generation.quality_scores.clone()).unwrap_or_default()
+    }
+
+    /// 获取向量的模长（余弦相似度时为归一化之前的模长）
+    pub fn get_norm(&self, ord: usize) -> Option<f32> {
+        let generation = self.snapshot().ok()?;
+        (ord < generation.size()).then(|| generation.values().get_norm(ord))
+    }
+
+    /// 获取预处理后的原始向量
+    pub fn get_original_vector(&self, ord: usize) -> Option<Vec<f32>> {
+        self.snapshot().ok()?.original_vector(ord).map(|vector| vector.to_vec())
+    }
+
+    /// 批量获取打包向量和修正项
+    ///
+    /// 所有记录读自同一代，适合把关键词检索得到的候选列表交给其他地方用新查询重新评分
+    ///
+    /// # 参数
+    /// * `ords` - 序号，顺序即返回顺序
+    /// * `include_reconstructions` - 是否同时返回由量化值重建的向量
+    ///
+    /// # 返回
+    /// 序号越界时返回错误；已删除的向量仍返回其数据
+    pub fn get_packed_vectors(&self, ords: &[usize], include_reconstructions: bool) -> Result<Vec<PackedVectorRecord>, BbqError> {
+        let generation = self.snapshot()?;
+        let values = generation.values();
+        ords.iter()
+            .map(|&ord| Ok(PackedVectorRecord {
+                ord,
+                packed: values.try_vector_value(ord)?.to_vec(),
+                corrections: values.try_get_corrective_terms(ord)?,
+                reconstruction: if include_reconstructions {
+                    Some(reconstruct_vector(values, self.config.index_bits, ord)?)
+                } else {
+                    None
+                },
+            }))
+            .collect()
+    }
+
+    /// 当前一代的内存占用，原始向量按实际的存储编码计算
+    pub fn memory_stats(&self) -> Result<IndexMemoryStats, BbqError> {
+        let generation = self.snapshot()?;
+        let dimension = generation.values().dimension();
+        let quantized_bytes = estimate_index_bytes(generation.size(), dimension, self.config.index_bits, None)
+            .saturating_add(dimension as u64 * 4);
+        let originals = generation.original_vectors.as_deref();
+        Ok(IndexMemoryStats {
+            quantized_bytes,
+            original_bytes: originals.map_or(0, |originals| originals.memory_bytes(dimension)),
+            original_encoding: originals.map(OriginalVectors::encoding),
+            search_layout_bytes: generation.search_layout().map_or(0, SearchLayout::memory_bytes),
+            promoted_bytes: generation.promoted().map_or(0, |promoted| promoted.memory_bytes() as u64),
+        })
+    }
+
+    /// 获取配置
+    pub fn get_config(&self) -> &QuantizedIndexConfig {
+        &self.config
+    }
+
+    /// 获取量化器
+    pub fn get_quantizer(&self) -> &OptimizedScalarQuantizer {
+        &self.quantizer
+    }
+
+    /// 获取评分器
+    pub fn get_scorer(&self) -> &BinaryQuantizedScorer {
+        &self.scorer
+    }
+
+    /// 获取量化向量值
+    pub fn get_quantized_vectors(&self) -> Option<Arc<dyn QuantizedVectorValues>> {
+        self.snapshot().ok().map(|generation| Arc::clone(&generation.values))
+    }
+}
+
+/// 限时搜索中重排阶段的预算和结果
+struct RescoreDeadline {
+    /// 搜索开始的时间（毫秒）
+    start: f64,
+    /// 整个搜索的预算（毫秒）
+    budget_ms: f64,
+    /// 实际使用的过采样倍数，一个也没重排时为None
+    used: Option<f32>,
+    /// 是否因预算不足提前停止重排
+    cut: bool,
+}
+
+/// 限时搜索的一批：构建状态的候选序号，或搜索布局中的连续区间
+enum BudgetBatch {
+    Ords(Vec<usize>),
+    Layout(Range<usize>),
+}
+
+impl BudgetBatch {
+    fn len(&self) -> usize {
+        match self {
+            BudgetBatch::Ords(ords) => ords.len(),
+            BudgetBatch::Layout(range) => range.len(),
+        }
+    }
+}
+
+/// 把候选序号交错地分成若干批：第j批为第 `j, j + n, j + 2n, ...` 个候选（n为批数），
+/// 每一批都是均匀样本
+fn interleaved_batches(candidates: &[usize], batch_size: usize) -> Vec<Vec<usize>> {
+    let batch_count = candidates.len().div_ceil(batch_size.max(1));
+    (0..batch_count)
+        .map(|batch| candidates.iter().skip(batch).step_by(batch_count).copied().collect())
+        .collect()
+}
+
+/// 把 `[0, len)` 分成大小为 `batch_size` 的连续区间，按交错的顺序排列：
+/// 区间分为约 `sqrt(n)` 组，依次取每组的第1个、第2个……，使任意前缀覆盖整个范围
+fn interleaved_ranges(len: usize, batch_size: usize) -> Vec<Range<usize>> {
+    let batch_size = batch_size.max(1);
+    let batch_count = len.div_ceil(batch_size);
+    let stride = (batch_count as f64).sqrt().ceil().max(1.0) as usize;
+    (0..stride)
+        .flat_map(|offset| (offset..batch_count).step_by(stride))
+        .map(|batch| batch * batch_size..((batch + 1) * batch_size).min(len))
+        .collect()
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::vector_utils::create_random_vector;
+    use crate::score_transform::ScoreCurve;
+    use crate::vector_utils::{compute_vector_magnitude, create_random_vector, generate_gaussian_mixture};
+
+    #[test]
+    fn test_quantized_index_creation() {
+        let config = QuantizedIndexConfig::default();
+        let index = QuantizedIndex::new(config).unwrap();
+        assert_eq!(index.get_config().query_bits, 4);
+        assert_eq!(index.get_config().index_bits, 1);
+    }
+
+    #[test]
+    fn test_build_index() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        
+        // 创建测试向量
+        let vectors: Vec<Vec<f32>> = (0..10)
+            .map(|_| create_random_vector(128, -1.0, 1.0))
+            .collect();
+        
+        let quantized_vectors = index.build_index(&vectors).unwrap();
+        assert_eq!(quantized_vectors.size(), 10);
+        assert_eq!(quantized_vectors.dimension(), 128);
+    }
+
+    #[test]
+    fn test_search_nearest_neighbors() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        
+        // 创建测试向量
+        let vectors: Vec<Vec<f32>> = (0..100)
+            .map(|_| create_random_vector(64, -1.0, 1.0))
+            .collect();
+        
+        index.build_index(&vectors).unwrap();
+        
+        let query_vector = create_random_vector(64, -1.0, 1.0);
+        let results = index.search_nearest_neighbors(&query_vector, 5).unwrap();
+        
+        assert_eq!(results.len(), 5);
+        
+        // 验证结果按分数降序排列
+        for i in 1..results.len() {
+            assert!(results[i-1].score >= results[i].score);
+        }
+    }
+
+    #[test]
+    fn test_search_with_normalization() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..50)
+            .map(|_| create_random_vector(32, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+
+        let query_vector = create_random_vector(32, -1.0, 1.0);
+        let raw = index.search_nearest_neighbors(&query_vector, 5).unwrap();
+        let params = SearchParams { normalization: ScoreNormalization::MinMax, ..SearchParams::default() };
+        let normalized = index.search_with_params(&query_vector, 5, &params).unwrap();
+
+        assert_eq!(normalized[0].index, raw[0].index);
+        assert_eq!(normalized[0].original_score, Some(raw[0].score));
+        assert!((normalized[0].score - 1.0).abs() < 1e-6);
+        assert!(normalized[4].score.abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_score_transform_keeps_order() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            result_cache_capacity: 8,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..50)
+            .map(|_| create_random_vector(32, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+
+        let raw = index.search_nearest_neighbors(&vectors[3], 10).unwrap();
+        let transform = ScoreTransform::new(ScoreCurve::logistic(0.5, 10.0).unwrap(), 0.0, 100.0).unwrap();
+        index.set_score_transform(Some(transform.clone()));
+        // 设置变换后缓存失效，不会返回变换前的结果
+        let transformed = index.search_nearest_neighbors(&vectors[3], 10).unwrap();
+        for (before, after) in raw.iter().zip(&transformed) {
+            assert_eq!(before.index, after.index);
+            assert_eq!(after.original_score, Some(before.score));
+            assert!((after.score - transform.apply(before.score)).abs() < 1e-4);
+            assert!((0.0..=100.0).contains(&after.score));
+        }
+        assert!(transformed.windows(2).all(|pair| pair[0].score >= pair[1].score));
+
+        index.set_score_transform(None);
+        assert_eq!(index.search_nearest_neighbors(&vectors[3], 10).unwrap()[0].score, raw[0].score);
+    }
+
+    #[test]
+    fn test_search_with_reused_context() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        // 超过一个批次，覆盖跨批次的序号映射
+        let vectors: Vec<Vec<f32>> = (0..2500)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+
+        let query_vector = create_random_vector(16, -1.0, 1.0);
+        let context = index.prepare_query(&query_vector).unwrap();
+        let first = index.search_with_context(&context, 10, &SearchParams::default()).unwrap();
+        let second = index.search_nearest_neighbors(&query_vector, 10).unwrap();
+
+        assert_eq!(first.len(), 10);
+        for (a, b) in first.iter().zip(second.iter()) {
+            assert_eq!(a.index, b.index);
+            assert_eq!(a.score, b.score);
+        }
+    }
+
+    #[cfg(feature = "eval")]
+    #[test]
+    fn test_rescore_and_calibrate_oversample() {
+        let config = QuantizedIndexConfig { keep_original_vectors: true, ..QuantizedIndexConfig::default() };
+        let mut index = QuantizedIndex::new(config).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..300)
+            .map(|_| create_random_vector(32, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+
+        let queries: Vec<Vec<f32>> = (0..5)
+            .map(|_| create_random_vector(32, -1.0, 1.0))
+            .collect();
+        let exhaustive = SearchParams {
+            rescore_oversample: RescoreOversample::Fixed(300.0),
+            ..SearchParams::default()
+        };
+        // 全量重排等价于精确搜索
+        assert_eq!(index.estimate_recall(&queries, 10, &exhaustive).unwrap(), 1.0);
+
+        let factor = index.calibrate_oversample(&queries, 10, 0.9).unwrap();
+        assert!((1.0..=MAX_RESCORE_OVERSAMPLE).contains(&factor));
+        assert_eq!(index.get_learned_oversample(), Some(factor));
+
+        // 重建索引后保留校准结果
+        index.build_index(&vectors).unwrap();
+        let adaptive = SearchParams { rescore_oversample: RescoreOversample::Adaptive, ..SearchParams::default() };
+        let results = index.search_with_params(&queries[0], 10, &adaptive).unwrap();
+        assert_eq!(results.len(), 10);
+    }
+
+    #[test]
+    fn test_score_ords() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..50)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+        index.delete(9).unwrap();
+
+        let query = create_random_vector(16, -1.0, 1.0);
+        let full = index.search_nearest_neighbors(&query, 49).unwrap();
+        let scored = index.score_ords(&query, &[30, 9, 2, 30]).unwrap();
+        assert_eq!(scored.iter().map(|r| r.index).collect::<Vec<_>>(), vec![30, 2, 30]);
+        for result in &scored {
+            let expected = full.iter().find(|r| r.index == result.index).unwrap();
+            assert_eq!(result.score, expected.score);
+        }
+        assert!(index.score_ords(&query, &[50]).is_err());
+    }
+
+    #[cfg(feature = "ivf")]
+    #[test]
+    fn test_ivf_search_statistics_and_rebalance() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            similarity_function: SimilarityFunction::Euclidean,
+            keep_original_vectors: true,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        let vectors = generate_gaussian_mixture(600, 16, 6, 0.05, 11).unwrap();
+        index.build_index(&vectors).unwrap();
+        let probed = SearchParams {
+            nprobe: Some(ProbeStrategy::Fixed(2)),
+            rescore_oversample: RescoreOversample::Fixed(100.0),
+            ..SearchParams::default()
+        };
+        assert!(index.search_with_params(&vectors[0], 5, &probed).is_err());
+
+        let stats = index.build_ivf(6, 10, 5).unwrap();
+        assert_eq!(stats.list_sizes.iter().sum::<usize>(), 600);
+        assert!(stats.imbalance_factor >= 1.0);
+
+        // 只在查询所在的列表中搜索，结果应来自该列表且包含查询自身
+        let results = index.search_with_params(&vectors[42], 5, &probed).unwrap();
+        assert_eq!(results[0].index, 42);
+        let ivf = index.snapshot().unwrap().ivf().unwrap().probe(&index.get_original_vector(42).unwrap(), 2);
+        assert!(results.iter().all(|r| ivf.contains(&r.index)));
+
+        for ord in 0..300 {
+            index.delete(ord).unwrap();
+        }
+        assert_eq!(index.ivf_statistics().unwrap().list_sizes.iter().sum::<usize>(), 300);
+        let before = index.generation_number();
+        index.rebalance(5).unwrap();
+        assert_ne!(index.generation_number(), before);
+
+        index.compact(false).unwrap();
+        let results = index.search_with_params(&vectors[342], 5, &probed).unwrap();
+        assert_eq!(results[0].index, 42);
+        assert_eq!(index.ivf_statistics().unwrap().list_sizes.iter().sum::<usize>(), 300);
+
+        let adaptive = SearchParams {
+            nprobe: Some(ProbeStrategy::adaptive(1, 6)),
+            rescore_oversample: RescoreOversample::Fixed(100.0),
+            ..SearchParams::default()
+        };
+        assert_eq!(index.search_with_params(&vectors[342], 5, &adaptive).unwrap()[0].index, 42);
+    }
+
+    #[cfg(feature = "ivf")]
+    #[test]
+    fn test_search_within_budget_probes_nearest_lists_first() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            similarity_function: SimilarityFunction::Euclidean,
+            keep_original_vectors: true,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        let vectors = generate_gaussian_mixture(600, 16, 6, 0.05, 11).unwrap();
+        index.build_index(&vectors).unwrap();
+        index.build_ivf(6, 10, 5).unwrap();
+        let params = SearchParams { nprobe: Some(ProbeStrategy::Fixed(3)), ..SearchParams::default() };
+
+        // 预算极小时只探测最近的列表
+        let tight = index.search_within_budget(&vectors[42], 5, 1e-9, &params).unwrap();
+        assert_eq!(tight.probes_skipped, 2);
+        assert!(tight.budget_exhausted && tight.skipped > 0);
+        let nearest = index.snapshot().unwrap().ivf().unwrap().probe(&index.get_original_vector(42).unwrap(), 1);
+        assert!(tight.results.iter().all(|r| nearest.contains(&r.index)));
+
+        let full = index.search_within_budget(&vectors[42], 5, 60_000.0, &params).unwrap();
+        assert_eq!((full.probes_skipped, full.skipped), (0, 0));
+    }
+
+    #[test]
+    fn test_weighted_query_masks_dimensions() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            similarity_function: SimilarityFunction::Euclidean,
+            keep_original_vectors: true,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..200)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+
+        // 后半维度被噪声破坏，屏蔽后应按前半维度精确找回原向量
+        let mut query = vectors[17].clone();
+        for value in &mut query[8..] {
+            *value += 5.0;
+        }
+        let weights: Vec<f32> = (0..16).map(|d| if d < 8 { 1.0 } else { 0.0 }).collect();
+        let params = SearchParams { rescore_oversample: RescoreOversample::Fixed(200.0), ..SearchParams::default() };
+        let results = index.search_weighted(&query, 3, &weights, &params).unwrap();
+        assert_eq!(results[0].index, 17);
+        assert_eq!(results[0].score, 1.0);
+
+        assert!(index.search_weighted(&query, 3, &weights[..4], &params).is_err());
+        assert!(index.search_weighted(&query, 3, &[-1.0; 16], &params).is_err());
+    }
+
+    #[test]
+    fn test_unit_weights_match_unweighted_scores() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            similarity_function: SimilarityFunction::Cosine,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..100)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+
+        // 未归一化的查询，余弦时两条路径都应使用归一化后的向量计算质心点积
+        let query: Vec<f32> = vectors[5].iter().map(|v| v * 3.0).collect();
+        let params = SearchParams::default();
+        let plain = index.search_with_params(&query, 10, &params).unwrap();
+        let weighted = index.search_weighted(&query, 10, &[1.0; 16], &params).unwrap();
+        assert_eq!(plain.len(), weighted.len());
+        for (a, b) in plain.iter().zip(&weighted) {
+            assert_eq!(a.index, b.index);
+            assert!((a.score - b.score).abs() < 1e-5, "{} vs {}", a.score, b.score);
+        }
+    }
+
+    #[test]
+    fn test_checked_accessors_on_corrupted_values() {
+        let correction = QuantizationResult {
+            lower_interval: -1.0,
+            upper_interval: 1.0,
+            additional_correction: 0.0,
+            quantized_component_sum: 4.0,
+        };
+        // 修正项和模长比向量少一个，模拟损坏的持久化数据
+        let values = QuantizedVectorValuesImpl::new(
+            vec![vec![0xff], vec![0x0f]],
+            vec![vec![1; 8], vec![0; 8]],
+            vec![correction],
+            vec![0.0; 8],
+            vec![1.0],
+        );
+        assert!(values.try_vector_value(1).is_ok());
+        assert!(values.try_get_corrective_terms(1).is_err());
+        assert!(values.try_get_norm(1).is_err());
+        assert!(values.try_get_unpacked_vector(2).is_err());
+
+        let context = QueryContext::new(vec![0.0; 8], vec![1; 8], QuantizationResult {
+            lower_interval: -1.0,
+            upper_interval: 1.0,
+            additional_correction: 0.0,
+            quantized_component_sum: 8.0,
+        }, 0.0, 1).unwrap();
+        let scorer = BinaryQuantizedScorer::new(SimilarityFunction::Euclidean);
+        assert!(scorer.compute_batch_scores_with_context(&context, &values, &[0]).is_ok());
+        assert!(scorer.compute_batch_scores_with_context(&context, &values, &[0, 1]).is_err());
+    }
+
+    // Lucene的质心按f32直接累加，正负向量不能精确抵消，常量向量不再退化
+    #[cfg(not(feature = "lucene_parity"))]
+    #[test]
+    fn test_degenerate_vectors() {
+        let mut vectors: Vec<Vec<f32>> = (0..50)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        // 使质心为零，再加入全零向量和常量向量
+        vectors.extend(vectors.clone().into_iter().map(|v| v.iter().map(|x| -x).collect::<Vec<f32>>()));
+        vectors.push(vec![0.0; 16]);
+        vectors.push(vec![0.5; 16]);
+
+        let config = QuantizedIndexConfig {
+            similarity_function: SimilarityFunction::Euclidean,
+            keep_original_vectors: true,
+            ..QuantizedIndexConfig::default()
+        };
+        let mut index = QuantizedIndex::new(config.clone()).unwrap();
+        index.build_index(&vectors).unwrap();
+        assert!(index.is_degenerate(100) && index.is_degenerate(101));
+        assert!(!index.is_degenerate(0));
+
+        // 常量向量的分数有限，且自身是最近邻
+        let results = index.search_nearest_neighbors(&[0.5; 16], 3).unwrap();
+        assert_eq!(results[0].index, 101);
+        assert!(results.iter().all(|r| r.score.is_finite() && r.score > 0.0));
+        let zero = index.search_nearest_neighbors(&[0.0; 16], 1).unwrap();
+        assert_eq!(zero[0].index, 100);
+
+        let mut skipping = QuantizedIndex::new(QuantizedIndexConfig {
+            degenerate_vectors: DegenerateVectorPolicy::Skip,
+            ..config.clone()
+        }).unwrap();
+        skipping.build_index(&vectors).unwrap();
+        assert!(skipping.is_deleted(100) && skipping.is_deleted(101));
+
+        let mut rejecting = QuantizedIndex::new(QuantizedIndexConfig {
+            degenerate_vectors: DegenerateVectorPolicy::Reject,
+            ..config
+        }).unwrap();
+        assert!(rejecting.build_index(&vectors).is_err());
+    }
+
+    #[test]
+    fn test_search_sampled() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..1000)
+            .map(|_| create_random_vector(32, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+
+        let preview = index.search_sampled(&vectors[0], 10, 0.25).unwrap();
+        assert_eq!(preview.sampled, 250);
+        assert!((preview.estimated_recall - 0.25).abs() < 1e-6);
+        assert_eq!(preview.results.len(), 10);
+
+        // 全量抽样与完整搜索一致
+        let full = index.search_sampled(&vectors[0], 10, 1.0).unwrap();
+        let expected = index.search_nearest_neighbors(&vectors[0], 10).unwrap();
+        assert_eq!(full.estimated_recall, 1.0);
+        assert_eq!(
+            full.results.iter().map(|r| r.score).collect::<Vec<_>>(),
+            expected.iter().map(|r| r.score).collect::<Vec<_>>(),
+        );
+        assert!(index.search_sampled(&vectors[0], 10, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_search_with_score_histogram() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..300)
+            .map(|_| create_random_vector(32, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+        index.delete(7).unwrap();
+
+        let params = SearchParams::default();
+        let searched = index.search_with_score_histogram(&vectors[0], 5, &params, 16, Some((0.0, 1.0))).unwrap();
+        let expected = index.search_with_params(&vectors[0], 5, &params).unwrap();
+        assert_eq!(
+            searched.results.iter().map(|r| (r.index, r.score)).collect::<Vec<_>>(),
+            expected.iter().map(|r| (r.index, r.score)).collect::<Vec<_>>(),
+        );
+        // 已删除的向量不计入，最高的非空桶包含最高分
+        let histogram = &searched.histogram;
+        assert_eq!(histogram.total(), 299);
+        assert_eq!((histogram.counts.len(), histogram.below, histogram.above), (16, 0, 0));
+        let top = histogram.counts.iter().rposition(|&count| count > 0).unwrap();
+        assert!(expected[0].score >= histogram.bucket_start(top));
+        assert!(expected[0].score <= histogram.bucket_start(top) + histogram.bucket_width());
+
+        let observed = index.search_with_score_histogram(&vectors[0], 5, &params, 8, None).unwrap();
+        assert_eq!(observed.histogram.max, expected[0].score);
+        assert_eq!(observed.histogram.total(), 299);
+        assert!(index.search_with_score_histogram(&vectors[0], 5, &params, 0, None).is_err());
+    }
+
+    #[test]
+    fn test_search_batch_matches_single_queries() {
+        let vectors: Vec<Vec<f32>> = (0..700)
+            .map(|_| create_random_vector(40, -1.0, 1.0))
+            .collect();
+        let queries: Vec<Vec<f32>> = vectors.iter().step_by(37).cloned().collect();
+        for query_bits in [4, 1] {
+            let mut index = QuantizedIndex::new(QuantizedIndexConfig { query_bits, ..QuantizedIndexConfig::default() }).unwrap();
+            index.build_index(&vectors).unwrap();
+            index.delete(37).unwrap();
+
+            let params = SearchParams { batch_size: Some(100), ..SearchParams::default() };
+            let batched = index.search_batch(&queries, 10, &params).unwrap();
+            assert_eq!(batched.len(), queries.len());
+            for (query, results) in queries.iter().zip(&batched) {
+                let expected = index.search_with_params(query, 10, &params).unwrap();
+                assert_eq!(
+                    results.iter().map(|r| (r.index, r.score)).collect::<Vec<_>>(),
+                    expected.iter().map(|r| (r.index, r.score)).collect::<Vec<_>>(),
+                );
+                assert!(results.iter().all(|r| r.index != 37));
+            }
+            assert!(index.search_batch(&queries, 0, &params).unwrap().iter().all(Vec::is_empty));
+            assert!(index.search_batch(&[vec![0.0; 3]], 10, &params).is_err());
+        }
+    }
+
+    #[test]
+    fn test_search_within_budget() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            keep_original_vectors: true,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..1000)
+            .map(|_| create_random_vector(32, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+        let params = SearchParams { rescore_oversample: RescoreOversample::Fixed(4.0), batch_size: Some(100), ..SearchParams::default() };
+
+        // 预算充足时与完整搜索一致
+        let full = index.search_within_budget(&vectors[0], 10, 60_000.0, &params).unwrap();
+        let expected = index.search_with_params(&vectors[0], 10, &params).unwrap();
+        assert!(!full.budget_exhausted);
+        assert_eq!((full.scored, full.skipped), (1000, 0));
+        assert_eq!(full.oversample_used, Some(4.0));
+        assert_eq!(
+            full.results.iter().map(|r| r.index).collect::<Vec<_>>(),
+            expected.iter().map(|r| r.index).collect::<Vec<_>>(),
+        );
+
+        // 预算极小时只评分一批且不重排，仍返回k个结果
+        let tight = index.search_within_budget(&vectors[0], 10, 1e-9, &params).unwrap();
+        assert!(tight.budget_exhausted);
+        assert_eq!((tight.scored, tight.skipped), (100, 900));
+        assert!((tight.estimated_recall - 0.1).abs() < 1e-6);
+        assert_eq!(tight.oversample_requested, Some(4.0));
+        assert_eq!(tight.oversample_used, None);
+        assert_eq!(tight.results.len(), 10);
+        assert!(tight.results.windows(2).all(|pair| pair[0].score >= pair[1].score));
+
+        assert!(index.search_within_budget(&vectors[0], 10, 0.0, &params).is_err());
+
+        // 转换为搜索布局后扫描布局，预算充足时结果与完整搜索逐位相同
+        let plain = SearchParams { batch_size: Some(100), ..SearchParams::default() };
+        index.finalize_for_search().unwrap();
+        let layout = index.search_within_budget(&vectors[0], 10, 60_000.0, &plain).unwrap();
+        let expected = index.search_with_params(&vectors[0], 10, &plain).unwrap();
+        assert_eq!(layout.scored, 1000);
+        assert_eq!(
+            layout.results.iter().map(|r| (r.index, r.score)).collect::<Vec<_>>(),
+            expected.iter().map(|r| (r.index, r.score)).collect::<Vec<_>>(),
+        );
+        let tight = index.search_within_budget(&vectors[0], 10, 1e-9, &plain).unwrap();
+        assert_eq!((tight.scored, tight.skipped), (100, 900));
+    }
+
+    #[test]
+    fn test_search_within_budget_filtered() {
+        use crate::filter::{AttributeValue, Attributes, Predicate};
+
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..200)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+        for ord in 0..200 {
+            let mut attributes = Attributes::new();
+            attributes.insert("even".to_string(), AttributeValue::Bool(ord % 2 == 0));
+            index.set_attributes(ord, attributes).unwrap();
+        }
+        let even = Filter::Attribute {
+            key: "even".to_string(),
+            predicate: Predicate::Eq(AttributeValue::Bool(true)),
+        };
+
+        let params = SearchParams::default();
+        let budgeted = index.search_within_budget_filtered(&vectors[1], 10, 60_000.0, &even, &params).unwrap();
+        let expected = index.search_filtered(&vectors[1], 10, &even, &params).unwrap();
+        assert_eq!(budgeted.scored, 100);
+        assert!(budgeted.results.iter().all(|r| r.index % 2 == 0));
+        assert_eq!(
+            budgeted.results.iter().map(|r| (r.index, r.score)).collect::<Vec<_>>(),
+            expected.iter().map(|r| (r.index, r.score)).collect::<Vec<_>>(),
+        );
+    }
+
+    #[test]
+    fn test_include_distances() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            keep_original_vectors: true,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..100)
+            .map(|_| create_random_vector(32, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+
+        let plain = index.search_nearest_neighbors(&vectors[5], 5).unwrap();
+        assert!(plain.iter().all(|r| r.distances.is_none()));
+
+        let params = SearchParams { include_distances: true, ..SearchParams::default() };
+        let results = index.search_with_params(&vectors[5], 5, &params).unwrap();
+        for (result, expected) in results.iter().zip(&plain) {
+            let distances = result.distances.as_ref().unwrap();
+            assert_eq!(result.score, expected.score);
+            assert!((distances.quantized_score - result.score).abs() < 1e-5);
+            assert!(distances.hamming_distance.unwrap() <= 32);
+            let exact = index.get_scorer().compute_exact_score(
+                &index.get_original_vector(5).unwrap(),
+                &index.get_original_vector(result.index).unwrap(),
+            );
+            assert_eq!(distances.exact_score, Some(exact));
+        }
+
+        // 多位查询取最高位平面作为符号位；多位索引没有汉明距离
+        for query_bits in [2, 8] {
+            let mut index = QuantizedIndex::new(QuantizedIndexConfig { query_bits, ..QuantizedIndexConfig::default() }).unwrap();
+            index.build_index(&vectors).unwrap();
+            let context = index.prepare_query(&vectors[5]).unwrap();
+            let top_plane: Vec<u8> = context.quantized_query.iter().map(|&q| q >> (query_bits - 1)).collect();
+            let mut sign_bits = vec![0u8; 4];
+            OptimizedScalarQuantizer::pack_as_binary(&top_plane, &mut sign_bits).unwrap();
+            let generation = index.snapshot().unwrap();
+            for result in index.search_with_params(&vectors[5], 5, &params).unwrap() {
+                let expected = compute_packed_hamming_distance(&sign_bits, generation.values().vector_value(result.index)).unwrap();
+                assert_eq!(result.distances.unwrap().hamming_distance, Some(expected), "query_bits {}", query_bits);
+            }
+        }
+        let mut multi_bit = QuantizedIndex::new(QuantizedIndexConfig { index_bits: 2, ..QuantizedIndexConfig::default() }).unwrap();
+        multi_bit.build_index(&vectors).unwrap();
+        let results = multi_bit.search_with_params(&vectors[5], 5, &params).unwrap();
+        assert!(results.iter().all(|r| r.distances.as_ref().unwrap().hamming_distance.is_none()));
+    }
+
+    #[test]
+    fn test_cached_norms_in_rescore() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            similarity_function: SimilarityFunction::Euclidean,
+            keep_original_vectors: true,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..100)
+            .map(|_| create_random_vector(24, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+
+        let norm = index.get_norm(7).unwrap();
+        assert!((norm - compute_vector_magnitude(&vectors[7])).abs() < 1e-5);
+        assert_eq!(index.get_norm(100), None);
+
+        // 用模长展开的欧氏距离与直接计算的精确分数一致
+        let query = create_random_vector(24, -1.0, 1.0);
+        let params = SearchParams { rescore_oversample: RescoreOversample::Fixed(100.0), ..SearchParams::default() };
+        for result in index.search_with_params(&query, 10, &params).unwrap() {
+            let exact = index.get_scorer().compute_exact_score(&query, &vectors[result.index]);
+            assert!((result.score - exact).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_rescore_requires_original_vectors() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..10)
+            .map(|_| create_random_vector(8, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+
+        let params = SearchParams { rescore_oversample: RescoreOversample::Fixed(2.0), ..SearchParams::default() };
+        assert!(index.search_with_params(&vectors[0], 3, &params).is_err());
+    }
+
+    #[test]
+    fn test_result_cache_hits_and_invalidation() {
+        let config = QuantizedIndexConfig { result_cache_capacity: 4, ..QuantizedIndexConfig::default() };
+        let mut index = QuantizedIndex::new(config).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..50)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+
+        let query_vector = create_random_vector(16, -1.0, 1.0);
+        let first = index.search_nearest_neighbors(&query_vector, 5).unwrap();
+        let second = index.search_nearest_neighbors(&query_vector, 5).unwrap();
+        assert_eq!(first.iter().map(|r| r.index).collect::<Vec<_>>(), second.iter().map(|r| r.index).collect::<Vec<_>>());
+
+        let stats = index.get_result_cache_stats();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 1);
+
+        // 重建索引使缓存失效
+        index.build_index(&vectors[..10]).unwrap();
+        assert_eq!(index.get_result_cache_stats().len, 0);
+        assert!(index.search_nearest_neighbors(&query_vector, 5).unwrap().iter().all(|r| r.index < 10));
+    }
+
+    #[test]
+    fn test_warmup() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        assert!(index.warmup().is_err());
+
+        let vectors: Vec<Vec<f32>> = (0..20)
+            .map(|_| create_random_vector(24, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+
+        let report = index.warmup().unwrap();
+        assert_eq!(report.vectors_touched, 20);
+        assert!(report.bytes_touched >= 20 * 3);
+        assert!(report.total_ms >= report.touch_ms);
+    }
+
+    #[test]
+    fn test_build_with_sample_statistics() {
+        let config = QuantizedIndexConfig {
+            similarity_function: SimilarityFunction::Euclidean,
+            ..QuantizedIndexConfig::default()
+        };
+        let mut index = QuantizedIndex::new(config).unwrap();
+        let sample: Vec<Vec<f32>> = (0..200)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        // 首批数据全部偏向正半轴
+        let first_chunk: Vec<Vec<f32>> = (0..5)
+            .map(|_| create_random_vector(16, 0.5, 1.0))
+            .collect();
+
+        let statistics = index.statistics_from_sample(&sample).unwrap();
+        index.build_index_with_sample(&first_chunk, &sample).unwrap();
+        let values = index.get_quantized_vectors().unwrap();
+        assert_eq!(values.get_centroid(), statistics.mean.as_slice());
+        assert_eq!(index.search_nearest_neighbors(&first_chunk[0], 5).unwrap().len(), 5);
+
+        let wrong = DimensionStatistics { mean: vec![0.0; 8], std: vec![1.0; 8] };
+        assert!(index.build_index_with_statistics(&first_chunk, &wrong).is_err());
+        assert!(index.build_index_with_sample(&first_chunk, &[]).is_err());
+    }
+
+    #[test]
+    fn test_quality_scores_and_blending() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..30)
+            .map(|_| create_random_vector(32, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+
+        assert_eq!(index.get_quality_scores().len(), 30);
+        assert!(index.get_quality_scores().iter().all(|q| (0.0..=1.0).contains(q)));
+        assert!(index.get_quality_score(30).is_none());
+
+        let plain = index.search_nearest_neighbors(&vectors[0], 30).unwrap();
+        let params = SearchParams {
+            quality_weight: 1.0,
+            ..SearchParams::default()
+        };
+        let blended = index.search_with_params(&vectors[0], 30, &params).unwrap();
+        for result in &blended {
+            let original = plain.iter().find(|r| r.index == result.index).unwrap();
+            let expected = original.score * index.get_quality_score(result.index).unwrap();
+            assert!((result.score - expected).abs() < 1e-5);
+        }
+
+        let invalid = SearchParams {
+            quality_weight: 1.5,
+            ..SearchParams::default()
+        };
+        assert!(index.search_with_params(&vectors[0], 3, &invalid).is_err());
+    }
+
+    #[test]
+    fn test_filtered_search_and_delete_where() {
+        use crate::filter::{AttributeValue, Predicate};
+
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            result_cache_capacity: 8,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..40)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+        for ord in 0..40 {
+            let mut attributes = Attributes::new();
+            let tenant = if ord % 2 == 0 { "even" } else { "odd" };
+            attributes.insert("tenant".to_string(), AttributeValue::Text(tenant.to_string()));
+            index.set_attributes(ord, attributes).unwrap();
+        }
+
+        let even = Filter::Attribute {
+            key: "tenant".to_string(),
+            predicate: Predicate::Eq(AttributeValue::Text("even".to_string())),
+        };
+        let results = index.search_filtered(&vectors[1], 40, &even, &SearchParams::default()).unwrap();
+        assert_eq!(results.len(), 20);
+        assert!(results.iter().all(|r| r.index % 2 == 0));
+
+        // 先缓存一次未过滤的结果，删除后缓存必须失效
+        assert_eq!(index.search_nearest_neighbors(&vectors[1], 40).unwrap().len(), 40);
+        assert_eq!(index.delete_where(&even).unwrap(), 20);
+        assert_eq!(index.delete_where(&even).unwrap(), 0);
+        assert_eq!(index.live_count(), 20);
+        let results = index.search_nearest_neighbors(&vectors[1], 40).unwrap();
+        assert_eq!(results.len(), 20);
+        assert!(results.iter().all(|r| r.index % 2 == 1));
+
+        assert!(index.delete(1).unwrap());
+        assert!(!index.delete(1).unwrap());
+        assert!(index.delete(40).is_err());
+        assert!(index.search_nearest_neighbors(&vectors[1], 40).unwrap().iter().all(|r| r.index != 1));
+    }
+
+    #[test]
+    fn test_expiry_and_purge() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            result_cache_capacity: 4,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..10)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+
+        let now = now_ms();
+        index.set_expiry(0, Some(now - 1.0)).unwrap();
+        index.set_expiry(1, Some(now + 3_600_000.0)).unwrap();
+        assert!(index.set_expiry(10, None).is_err());
+        assert!(index.set_expiry(2, Some(f64::NAN)).is_err());
+
+        // 过期向量在清理前就不会出现在结果中
+        let results = index.search_nearest_neighbors(&vectors[0], 10).unwrap();
+        assert_eq!(results.len(), 9);
+        assert!(results.iter().all(|r| r.index != 0));
+
+        assert_eq!(index.purge_expired(now).unwrap(), 1);
+        assert!(index.is_deleted(0));
+        assert!(!index.is_deleted(1));
+        assert_eq!(index.purge_expired(now + 7_200_000.0).unwrap(), 1);
+        assert_eq!(index.live_count(), 8);
+    }
 
     #[test]
-    fn test_quantized_index_creation() {
-        let config = QuantizedIndexConfig::default();
-        let index = QuantizedIndex::new(config);
-        assert_eq!(index.get_config().query_bits, 4);
-        assert_eq!(index.get_config().index_bits, 1);
+    fn test_provenance_and_grouped_search() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..12)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+        // 每个文档3个相邻的块
+        for ord in 0..12 {
+            let provenance = ChunkProvenance { doc_id: format!("doc{}", ord / 3), chunk_offset: (ord % 3) * 64, length: 64 };
+            index.set_provenance(ord, Some(provenance)).unwrap();
+        }
+        assert!(index.set_provenance(12, None).is_err());
+
+        let params = SearchParams::default();
+        let hits = index.search_grouped(&vectors[4], 12, &params, &ChunkGroupingParams::default()).unwrap();
+        assert_eq!(hits.len(), 4);
+        assert_eq!(hits[0].doc_id.as_deref(), Some("doc1"));
+        assert!(hits.iter().all(|hit| hit.ords.len() == 3 && hit.length == 192));
+
+        // 压缩后来源随向量迁移
+        index.delete(0).unwrap();
+        index.compact(false).unwrap();
+        assert_eq!(index.get_provenance(0).unwrap().chunk_offset, 64);
+        assert_eq!(index.get_provenance(10).unwrap().doc_id, "doc3");
+        let results = index.search_nearest_neighbors(&vectors[4], 11).unwrap();
+        let hits = index.group_results(&results, &ChunkGroupingParams::default()).unwrap();
+        assert_eq!(hits.iter().find(|hit| hit.doc_id.as_deref() == Some("doc0")).unwrap().chunk_offset, 64);
     }
 
     #[test]
-    fn test_build_index() {
-        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default());
-        
-        // 创建测试向量
-        let vectors: Vec<Vec<f32>> = (0..10)
-            .map(|_| create_random_vector(128, -1.0, 1.0))
+    fn test_finalized_search_matches_build_state() {
+        let vectors: Vec<Vec<f32>> = (0..400)
+            .map(|_| create_random_vector(72, -1.0, 1.0))
             .collect();
-        
-        let quantized_vectors = index.build_index(&vectors).unwrap();
-        assert_eq!(quantized_vectors.size(), 10);
-        assert_eq!(quantized_vectors.dimension(), 128);
+        let summary = |results: Vec<QueryResult>| results.into_iter().map(|r| (r.index, r.score)).collect::<Vec<_>>();
+        for (similarity_function, query_bits, discretize_dimensions) in [
+            (SimilarityFunction::Euclidean, 4, false),
+            (SimilarityFunction::Cosine, 1, false),
+            (SimilarityFunction::MaximumInnerProduct, 4, true),
+        ] {
+            let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+                similarity_function,
+                query_bits,
+                discretize_dimensions,
+                result_cache_capacity: 0,
+                ..QuantizedIndexConfig::default()
+            }).unwrap();
+            index.build_index(&vectors).unwrap();
+            for ord in (0..400).step_by(7) {
+                index.delete(ord).unwrap();
+            }
+            let params = SearchParams { batch_size: Some(64), ..SearchParams::default() };
+            let expected = summary(index.search_with_params(&vectors[3], 20, &params).unwrap());
+
+            assert!(!index.is_finalized());
+            index.finalize_for_search().unwrap();
+            assert!(index.is_finalized());
+            assert!(index.memory_stats().unwrap().search_layout_bytes > 0);
+            assert_eq!(summary(index.search_with_params(&vectors[3], 20, &params).unwrap()), expected);
+
+            // 修改后回到构建状态，搜索结果仍然正确
+            index.delete(1).unwrap();
+            assert!(!index.is_finalized());
+            assert!(index.search_nearest_neighbors(&vectors[1], 5).unwrap().iter().all(|r| r.index != 1));
+        }
     }
 
     #[test]
-    fn test_search_nearest_neighbors() {
-        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default());
-        
-        // 创建测试向量
-        let vectors: Vec<Vec<f32>> = (0..100)
+    fn test_search_experiment_pairs_paths() {
+        let vectors: Vec<Vec<f32>> = (0..300)
             .map(|_| create_random_vector(64, -1.0, 1.0))
             .collect();
-        
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            result_cache_capacity: 8,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
         index.build_index(&vectors).unwrap();
-        
-        let query_vector = create_random_vector(64, -1.0, 1.0);
-        let results = index.search_nearest_neighbors(&query_vector, 5).unwrap();
-        
-        assert_eq!(results.len(), 5);
-        
-        // 验证结果按分数降序排列
-        for i in 1..results.len() {
-            assert!(results[i-1].score >= results[i].score);
+        assert!(index.get_search_experiment_stats().is_none());
+
+        // 只换布局的候选路径结果与对照逐位相同
+        index.set_search_experiment(Some(SearchExperiment {
+            sample_rate: 1.0,
+            layout: ExperimentLayout::SearchLayout,
+            ..SearchExperiment::default()
+        })).unwrap();
+        index.search_nearest_neighbors(&vectors[0], 10).unwrap();
+        let stats = index.get_search_experiment_stats().unwrap();
+        assert_eq!((stats.queries, stats.sampled, stats.skipped), (1, 0, 1));
+
+        index.finalize_for_search().unwrap();
+        let expected = index.search_nearest_neighbors(&vectors[1], 10).unwrap();
+        for query in &vectors[..5] {
+            index.search_nearest_neighbors(query, 10).unwrap();
+        }
+        let stats = index.get_search_experiment_stats().unwrap();
+        assert_eq!((stats.queries, stats.sampled, stats.identical), (7, 6, 6));
+        assert_eq!((stats.max_score_diff, stats.mean_overlap()), (0.0, 1.0));
+        // 只有跳过的那次查询进入了结果缓存
+        assert_eq!(index.get_result_cache_stats().len, 1);
+
+        // 快速评分公式只改变分数，结果仍与对照配对比较；默认返回对照结果
+        index.set_search_experiment(Some(SearchExperiment {
+            sample_rate: 1.0,
+            scoring_precision: Some(ScoringPrecision::Fast),
+            layout: ExperimentLayout::BuildState,
+            ..SearchExperiment::default()
+        })).unwrap();
+        let results = index.search_nearest_neighbors(&vectors[1], 10).unwrap();
+        assert_eq!(
+            results.iter().map(|r| (r.index, r.score)).collect::<Vec<_>>(),
+            expected.iter().map(|r| (r.index, r.score)).collect::<Vec<_>>()
+        );
+        let stats = index.get_search_experiment_stats().unwrap();
+        assert_eq!((stats.queries, stats.sampled), (1, 1));
+        assert!(stats.mean_overlap() > 0.5);
+
+        assert!(index.set_search_experiment(Some(SearchExperiment { sample_rate: -0.1, ..SearchExperiment::default() })).is_err());
+        index.set_search_experiment(None).unwrap();
+        assert!(index.get_search_experiment().is_none());
+        index.search_nearest_neighbors(&vectors[1], 10).unwrap();
+        assert_eq!(index.get_result_cache_stats().len, 2);
+    }
+
+    #[test]
+    fn test_search_during_compaction() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            keep_original_vectors: true,
+            result_cache_capacity: 8,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..300)
+            .map(|_| create_random_vector(32, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+        for ord in (0..300).step_by(3) {
+            index.delete(ord).unwrap();
+        }
+        let first_generation = index.generation_number().unwrap();
+        let kept = index.get_original_vector(4);
+
+        let (index, queries) = (&index, &vectors);
+        std::thread::scope(|scope| {
+            let searchers: Vec<_> = (0..4)
+                .map(|t| {
+                    scope.spawn(move || {
+                        for i in 0..50 {
+                            let query = &queries[(t * 50 + i) % queries.len()];
+                            let results = index.search_nearest_neighbors(query, 10).unwrap();
+                            // 每次搜索只读一代：结果数量完整、分数有序、序号都在该代范围内
+                            assert_eq!(results.len(), 10);
+                            assert!(results.windows(2).all(|pair| pair[0].score >= pair[1].score));
+                            assert!(results.iter().all(|r| r.index < 300 && r.score.is_finite()));
+                        }
+                    })
+                })
+                .collect();
+
+            let report = index.compact(false).unwrap();
+            assert_eq!((report.removed, report.remaining), (100, 200));
+            for _ in 0..3 {
+                let report = index.compact(true).unwrap();
+                assert_eq!((report.removed, report.remaining), (0, 200));
+            }
+            for searcher in searchers {
+                searcher.join().unwrap();
+            }
+        });
+
+        assert_eq!(index.size(), 200);
+        assert_eq!(index.live_count(), 200);
+        assert_eq!(index.generation_number(), Some(first_generation + 4));
+        // 压缩后按原顺序重新编号：原序号4变为新序号2
+        assert_eq!(index.get_original_vector(2), kept);
+    }
+
+    #[test]
+    fn test_content_hash_tracks_logical_contents() {
+        let vectors: Vec<Vec<f32>> = (0..80)
+            .map(|_| create_random_vector(24, -1.0, 1.0))
+            .collect();
+        let build = |config: QuantizedIndexConfig| {
+            let mut index = QuantizedIndex::new(config).unwrap();
+            index.build_index(&vectors).unwrap();
+            index
+        };
+        let mut index = build(QuantizedIndexConfig::default());
+        let hash = index.content_hash().unwrap();
+        assert_eq!(build(QuantizedIndexConfig::default()).content_hash().unwrap(), hash);
+        let four_bit = build(QuantizedIndexConfig { index_bits: 4, ..QuantizedIndexConfig::default() });
+        assert_ne!(four_bit.content_hash().unwrap(), hash);
+
+        index.delete(5).unwrap();
+        let deleted = index.content_hash().unwrap();
+        assert_ne!(deleted, hash);
+        // 压缩只改变序号，不改变内容
+        index.compact(false).unwrap();
+        assert_eq!(index.content_hash().unwrap(), deleted);
+
+        assert!(QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap().content_hash().is_err());
+    }
+
+    #[test]
+    fn test_discretized_dimensions_keep_scores() {
+        let vectors: Vec<Vec<f32>> = (0..120)
+            .map(|_| create_random_vector(40, -1.0, 1.0))
+            .collect();
+        for query_bits in [4, 1] {
+            let build = |discretize_dimensions: bool| {
+                let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+                    query_bits,
+                    discretize_dimensions,
+                    ..QuantizedIndexConfig::default()
+                }).unwrap();
+                index.build_index(&vectors).unwrap();
+                index
+            };
+            let (compact, discretized) = (build(false), build(true));
+            assert_eq!(compact.get_packed_vectors(&[3], false).unwrap()[0].packed.len(), 5);
+            let packed = discretized.get_packed_vectors(&[3], false).unwrap().remove(0).packed;
+            assert_eq!(packed.len(), 8);
+            assert!(packed[5..].iter().all(|&byte| byte == 0));
+
+            // 补出的位不改变分数和距离
+            let params = SearchParams { include_distances: true, ..SearchParams::default() };
+            let expected = compact.search_with_params(&vectors[3], 10, &params).unwrap();
+            let actual = discretized.search_with_params(&vectors[3], 10, &params).unwrap();
+            assert_eq!(
+                actual.iter().map(|r| (r.index, r.score, r.distances.as_ref().unwrap().hamming_distance)).collect::<Vec<_>>(),
+                expected.iter().map(|r| (r.index, r.score, r.distances.as_ref().unwrap().hamming_distance)).collect::<Vec<_>>(),
+            );
+
+            // Lucene的查询布局：4个位平面，每个对齐到64位
+            let lucene_query = discretized.prepare_query(&vectors[0]).unwrap().to_lucene_query();
+            if query_bits == 4 {
+                assert_eq!(lucene_query.unwrap().len(), 32);
+            } else {
+                assert!(lucene_query.is_err());
+            }
+        }
+        assert!(QuantizedIndex::new(QuantizedIndexConfig {
+            index_bits: 4,
+            discretize_dimensions: true,
+            ..QuantizedIndexConfig::default()
+        }).is_err());
+    }
+
+    #[test]
+    fn test_apply_batch_is_all_or_nothing() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            keep_original_vectors: true,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..50)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+        let before = index.snapshot().unwrap();
+
+        // 无效的操作使整批失败，索引保持不变
+        let invalid = index.apply_batch(vec![
+            IndexOp::Insert { vector: create_random_vector(16, -1.0, 1.0), attributes: Attributes::new() },
+            IndexOp::Update { ord: 99, vector: vectors[0].clone() },
+        ]);
+        assert!(invalid.is_err());
+        assert!(index.apply_batch(vec![IndexOp::Insert { vector: vec![0.0; 3], attributes: Attributes::new() }]).is_err());
+        assert_eq!(index.generation_number(), Some(before.number()));
+        assert_eq!(index.size(), 50);
+
+        let inserted = create_random_vector(16, -1.0, 1.0);
+        let mut attributes = Attributes::new();
+        attributes.insert("tag".to_string(), crate::filter::AttributeValue::Text("new".to_string()));
+        let report = index.apply_batch(vec![
+            IndexOp::Insert { vector: inserted.clone(), attributes: attributes.clone() },
+            IndexOp::Update { ord: 3, vector: vectors[10].clone() },
+            IndexOp::Delete { ord: 5 },
+            // 可以引用同一批中插入的序号
+            IndexOp::Delete { ord: 50 },
+            IndexOp::Delete { ord: 5 },
+        ]).unwrap();
+        assert_eq!((report.inserted.clone(), report.updated, report.deleted), (vec![50], 1, 2));
+        assert_eq!(index.generation_number(), Some(report.generation));
+
+        // 之前取得的快照仍是完整的旧一代
+        assert_eq!(before.size(), 50);
+        assert!(!before.is_deleted(5));
+        assert_eq!(index.size(), 51);
+        assert!(index.is_deleted(5) && index.is_deleted(50));
+        assert_eq!(index.get_attributes(50), Some(attributes));
+        assert_eq!(index.get_original_vector(3), index.get_original_vector(10));
+
+        let results = index.search_nearest_neighbors(&vectors[10], 2).unwrap();
+        let mut top: Vec<usize> = results.iter().map(|result| result.index).collect();
+        top.sort_unstable();
+        assert_eq!(top, vec![3, 10]);
+        assert_eq!(results[0].score, results[1].score);
+
+        let report = index.apply_batch(vec![IndexOp::Insert { vector: inserted.clone(), attributes: Attributes::new() }]).unwrap();
+        assert_eq!(report.inserted, vec![51]);
+        assert_eq!(index.search_nearest_neighbors(&inserted, 1).unwrap()[0].index, 51);
+    }
+
+    #[test]
+    fn test_prenormalized_matches_normalizing_build() {
+        let vectors: Vec<Vec<f32>> = (0..60)
+            .map(|_| {
+                let mut vector = create_random_vector(16, -1.0, 1.0);
+                normalize_vector(&mut vector);
+                vector
+            })
+            .collect();
+        let build = |prenormalized| {
+            let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+                keep_original_vectors: true,
+                prenormalized,
+                ..QuantizedIndexConfig::default()
+            }).unwrap();
+            index.build_index(&vectors).unwrap();
+            index
+        };
+        let (normalizing, prenormalized) = (build(false), build(true));
+        let params = SearchParams { rescore_oversample: RescoreOversample::Fixed(3.0), ..SearchParams::default() };
+        for query in vectors.iter().take(5) {
+            let expected = normalizing.search_with_params(query, 5, &params).unwrap();
+            let actual = prenormalized.search_with_params(query, 5, &params).unwrap();
+            let ords = |results: &[QueryResult]| results.iter().map(|result| result.index).collect::<Vec<_>>();
+            assert_eq!(ords(&actual), ords(&expected));
+            for (actual, expected) in actual.iter().zip(&expected) {
+                assert!((actual.score - expected.score).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_scratch_reused_and_reset() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            keep_original_vectors: true,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..200)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+        assert_eq!(index.scratch_bytes(), 0);
+
+        let params = SearchParams { rescore_oversample: RescoreOversample::Fixed(2.0), ..SearchParams::default() };
+        let first = index.search_with_params(&vectors[3], 10, &params).unwrap();
+        let bytes = index.scratch_bytes();
+        assert!(bytes >= 200 * std::mem::size_of::<(usize, f32)>());
+
+        // 复用缓冲区不改变结果，也不再增长
+        for query in vectors.iter().take(20) {
+            index.clear_result_cache();
+            index.search_with_params(query, 10, &params).unwrap();
+        }
+        assert_eq!(index.scratch_bytes(), bytes);
+        index.clear_result_cache();
+        let again = index.search_with_params(&vectors[3], 10, &params).unwrap();
+        let ords = |results: &[QueryResult]| results.iter().map(|result| (result.index, result.score)).collect::<Vec<_>>();
+        assert_eq!(ords(&again), ords(&first));
+
+        index.reset_scratch();
+        assert_eq!(index.scratch_bytes(), 0);
+        index.clear_result_cache();
+        assert_eq!(ords(&index.search_with_params(&vectors[3], 10, &params).unwrap()), ords(&first));
+    }
+
+    #[test]
+    fn test_remove_vectors_then_compact() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..30)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+
+        // 有越界序号时整批不生效
+        assert!(index.remove_vectors(&[2, 30]).is_err());
+        assert!(!index.is_deleted(2));
+
+        // 越界或没有新删除的向量时保留搜索布局
+        index.remove_vector(9).unwrap();
+        index.finalize_for_search().unwrap();
+        assert!(index.remove_vectors(&[30]).is_err());
+        assert_eq!(index.remove_vectors(&[9, 9]).unwrap(), 0);
+        assert_eq!(index.remove_vectors(&[]).unwrap(), 0);
+        assert!(index.is_finalized());
+
+        assert!(index.remove_vector(5).unwrap());
+        assert_eq!(index.remove_vectors(&[2, 5, 7, 7]).unwrap(), 2);
+        for ord in [2, 5, 7] {
+            assert!(index.is_deleted(ord));
+            assert!(index.search_nearest_neighbors(&vectors[ord], 30).unwrap().iter().all(|hit| hit.index != ord));
+        }
+
+        assert!(!index.is_finalized());
+
+        let report = index.compact(false).unwrap();
+        assert_eq!((report.removed, report.remaining), (4, 26));
+        assert_eq!(index.size(), 26);
+        let new_ord = report.remap.translate(8).unwrap();
+        assert_eq!(index.search_nearest_neighbors(&vectors[8], 1).unwrap()[0].index, new_ord);
+    }
+
+    #[test]
+    fn test_index_serialize_round_trip() {
+        use crate::filter::{AttributeValue, Predicate};
+
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            keep_original_vectors: true,
+            original_encoding: OriginalVectorEncoding::F16,
+            correction_layout: CorrectionLayout::StructOfArrays,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..50)
+            .map(|_| create_random_vector(20, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+        index.delete(4).unwrap();
+        index.set_expiry(9, Some(f64::MAX)).unwrap();
+        let mut attributes = Attributes::new();
+        attributes.insert("lang".to_string(), AttributeValue::Text("zh".to_string()));
+        attributes.insert("year".to_string(), AttributeValue::Number(2024.0));
+        index.set_attributes(12, attributes).unwrap();
+        index.set_provenance(12, Some(ChunkProvenance { doc_id: "doc".to_string(), chunk_offset: 40, length: 10 })).unwrap();
+        index.set_learned_oversample(Some(3.0));
+
+        let bytes = index.serialize().unwrap();
+        let restored = QuantizedIndex::deserialize(&bytes).unwrap();
+        assert_eq!(restored.size(), 50);
+        assert!(restored.is_deleted(4));
+        assert_eq!(restored.get_learned_oversample(), Some(3.0));
+        assert_eq!(restored.content_hash().unwrap(), index.content_hash().unwrap());
+        // 同样的状态序列化出同样的字节
+        assert_eq!(restored.serialize().unwrap(), bytes);
+
+        let params = SearchParams { rescore_oversample: RescoreOversample::Fixed(3.0), ..SearchParams::default() };
+        for query in vectors.iter().take(10) {
+            let expected = index.search_with_params(query, 5, &params).unwrap();
+            let actual = restored.search_with_params(query, 5, &params).unwrap();
+            assert_eq!(
+                actual.iter().map(|r| (r.index, r.score)).collect::<Vec<_>>(),
+                expected.iter().map(|r| (r.index, r.score)).collect::<Vec<_>>(),
+            );
+        }
+        let filter = Filter::Attribute {
+            key: "lang".to_string(),
+            predicate: Predicate::Eq(AttributeValue::Text("zh".to_string())),
+        };
+        let hits = restored.search_filtered(&vectors[0], 5, &filter, &SearchParams::default()).unwrap();
+        assert_eq!(hits.iter().map(|r| r.index).collect::<Vec<_>>(), vec![12]);
+
+        assert!(QuantizedIndex::deserialize(&bytes[..bytes.len() - 1]).is_err());
+        let mut extra = bytes.clone();
+        extra.push(0);
+        assert!(QuantizedIndex::deserialize(&extra).is_err());
+        let mut magic = bytes;
+        magic[0] = b'X';
+        assert!(QuantizedIndex::deserialize(&magic).is_err());
+        assert!(QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap().serialize().is_err());
+    }
+
+    #[test]
+    fn test_frozen_index_rejects_mutations() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            keep_original_vectors: true,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..20)
+            .map(|_| create_random_vector(8, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+        index.freeze();
+        assert!(index.is_frozen());
+
+        assert!(index.build_index(&vectors).is_err());
+        assert!(index.delete(0).is_err());
+        assert!(index.remove_vectors(&[0, 1]).is_err());
+        assert!(index.delete_where(&Filter::All).is_err());
+        assert!(index.set_attributes(0, Attributes::new()).is_err());
+        assert!(index.set_expiry(0, Some(0.0)).is_err());
+        assert!(index.purge_expired(f64::MAX).is_err());
+        assert!(index.compact(false).is_err());
+        assert!(index.apply_batch(vec![IndexOp::Delete { ord: 1 }]).is_err());
+        #[cfg(feature = "ivf")]
+        assert!(index.build_ivf(2, 3, 7).is_err());
+        assert_eq!(index.size(), 20);
+        assert!(!index.is_deleted(0));
+
+        // 搜索不受影响，解冻后可以继续修改
+        assert_eq!(index.search_nearest_neighbors(&vectors[4], 1).unwrap()[0].index, 4);
+        index.unfreeze();
+        assert!(index.delete(0).unwrap());
+    }
+
+    #[test]
+    fn test_compaction_remap_translates_results() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            keep_original_vectors: true,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..60)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+        let mut results = index.search_nearest_neighbors(&vectors[30], 10).unwrap();
+        let hit = results[1].index;
+        let mut deleted = vec![hit];
+        deleted.extend([0, 7, 9].into_iter().filter(|&ord| ord != hit).take(2));
+        for &ord in &deleted {
+            index.delete(ord).unwrap();
+        }
+        let originals: Vec<_> = results.iter()
+            .filter(|result| !deleted.contains(&result.index))
+            .map(|result| index.get_original_vector(result.index))
+            .collect();
+
+        let report = index.compact(false).unwrap();
+        assert_eq!(report.remap.removed(), 3);
+        assert!(deleted.iter().all(|&ord| report.remap.translate(ord).is_none()));
+        let dropped = results.iter().filter(|result| deleted.contains(&result.index)).count();
+        assert_eq!(report.remap.translate_results(&mut results), dropped);
+        // 转换后的序号指向压缩后同一个向量
+        for (result, original) in results.iter().zip(&originals) {
+            assert_eq!(&index.get_original_vector(result.index), original);
+        }
+
+        let second = index.compact(false).unwrap();
+        assert!(second.remap.is_identity());
+        let combined = report.remap.then(&second.remap).unwrap();
+        assert_eq!(combined.translate_all(&deleted), vec![None; 3]);
+        let last = (0..60).rev().find(|ord| !deleted.contains(ord)).unwrap();
+        assert_eq!(combined.translate(last), Some(56));
+    }
+
+    #[cfg(feature = "paranoid")]
+    #[test]
+    fn test_paranoid_search_skips_inconsistent_vectors() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..20)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+
+        // 模拟手工构造的缓冲区：序号3长度错误，序号5修正项为NaN
+        let generation = index.generation_mut().unwrap();
+        let values = generation.values();
+        let mut packed: Vec<Vec<u8>> = (0..20).map(|ord| values.vector_value(ord).to_vec()).collect();
+        let mut corrections: Vec<QuantizationResult> = (0..20).map(|ord| values.get_corrective_terms(ord)).collect();
+        packed[3].push(0);
+        corrections[5].additional_correction = f32::NAN;
+        generation.values = Arc::new(QuantizedVectorValuesImpl::new(
+            packed,
+            (0..20).map(|ord| values.get_unpacked_vector(ord).to_vec()).collect(),
+            corrections,
+            values.get_centroid().to_vec(),
+            (0..20).map(|ord| values.get_norm(ord)).collect(),
+        ));
+
+        let results = index.search_nearest_neighbors(&vectors[0], 20).unwrap();
+        assert_eq!(results.len(), 18);
+        assert!(results.iter().all(|result| result.index != 3 && result.index != 5 && result.score.is_finite()));
+
+        // 直接评分的入口同样跳过
+        let ords: Vec<usize> = (0..20).collect();
+        let scored = index.score_ords(&vectors[0], &ords).unwrap();
+        assert_eq!(scored.iter().map(|result| result.index).collect::<Vec<_>>(),
+            ords.iter().copied().filter(|&ord| ord != 3 && ord != 5).collect::<Vec<_>>());
+        let sampled = index.search_sampled(&vectors[0], 20, 1.0).unwrap();
+        assert_eq!(sampled.sampled, 18);
+        let generation = index.snapshot().unwrap();
+        let context = index.prepare_query(&vectors[0]).unwrap();
+        let range = index.score_ord_range(&generation, &context, 0, 20).unwrap();
+        assert_eq!(range.len(), 18);
+        assert!(range.iter().all(|&(ord, score)| ord != 3 && ord != 5 && score.is_finite()));
+
+        let budgeted = index.search_within_budget(&vectors[0], 20, 60_000.0, &SearchParams::default()).unwrap();
+        assert_eq!(budgeted.scored, 18);
+        assert!(budgeted.results.iter().all(|result| result.index != 3 && result.index != 5 && result.score.is_finite()));
+    }
+
+    #[test]
+    fn test_stale_context_after_centroid_refresh() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            keep_original_vectors: true,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..50)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+        index.delete(0).unwrap();
+
+        let context = index.prepare_query(&vectors[1]).unwrap();
+        let params = SearchParams::default();
+        index.compact(false).unwrap();
+        // 只移除墓碑时质心不变，旧上下文仍可使用
+        assert_eq!(index.search_with_context(&context, 5, &params).unwrap().len(), 5);
+
+        index.compact(true).unwrap();
+        assert!(index.search_with_context(&context, 5, &params).is_err());
+        let context = index.prepare_query(&vectors[1]).unwrap();
+        assert_eq!(index.search_with_context(&context, 5, &params).unwrap()[0].index, 0);
+    }
+
+    #[cfg(feature = "eval")]
+    #[test]
+    fn test_cached_ground_truth_recall() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            keep_original_vectors: true,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        // 聚类数据比均匀随机向量更难达到高召回率
+        let vectors = generate_gaussian_mixture(200, 32, 8, 0.2, 11).unwrap();
+        index.build_index(&vectors).unwrap();
+        assert!(index.estimate_cached_recall(&SearchParams::default()).is_err());
+        assert_eq!(index.generate_ground_truth(20, 5, 42).unwrap(), 20);
+
+        let params = SearchParams {
+            rescore_oversample: RescoreOversample::Fixed(8.0),
+            ..SearchParams::default()
+        };
+        assert!(index.estimate_cached_recall(&params).unwrap() > 0.8);
+
+        // 删除和压缩后无需重新生成真值
+        for ord in (0..200).step_by(2) {
+            index.delete(ord).unwrap();
+        }
+        assert!(index.estimate_cached_recall(&params).unwrap() > 0.8);
+        index.compact(true).unwrap();
+        assert!(index.estimate_cached_recall(&params).unwrap() > 0.8);
+    }
+
+    #[test]
+    fn test_progress_events() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            keep_original_vectors: true,
+            ..QuantizedIndexConfig::default()
+        }).unwrap().with_progress_observer(Arc::new(move |event: &ProgressEvent| {
+            sink.lock().unwrap().push(*event);
+        }));
+        let count = PROGRESS_CHUNK_SIZE + 100;
+        let vectors: Vec<Vec<f32>> = (0..count)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+        assert_eq!(*events.lock().unwrap(), vec![
+            ProgressEvent::BuildStarted { total: count, dimension: 16 },
+            ProgressEvent::CentroidComputed { dimension: 16 },
+            ProgressEvent::ChunkQuantized { n: PROGRESS_CHUNK_SIZE, total: count },
+            ProgressEvent::ChunkQuantized { n: count, total: count },
+        ]);
+
+        events.lock().unwrap().clear();
+        index.search_nearest_neighbors(&vectors[0], 5).unwrap();
+        let last = *events.lock().unwrap().last().unwrap();
+        assert_eq!(last, ProgressEvent::SearchBatchScored { scored: count, total: count });
+
+        index.delete(0).unwrap();
+        events.lock().unwrap().clear();
+        let report = index.compact(false).unwrap();
+        assert_eq!(*events.lock().unwrap(), vec![ProgressEvent::Compacted {
+            generation: report.generation,
+            removed: 1,
+            remaining: count - 1,
+        }]);
+    }
+
+    #[test]
+    fn test_compressed_original_vectors() {
+        let vectors = generate_gaussian_mixture(300, 64, 6, 0.4, 21).unwrap();
+        let params = SearchParams { rescore_oversample: RescoreOversample::Fixed(4.0), ..SearchParams::default() };
+        let mut f32_bytes = 0;
+        for (encoding, tolerance) in [
+            (OriginalVectorEncoding::F32, 0.0),
+            (OriginalVectorEncoding::F16, 1e-3),
+            (OriginalVectorEncoding::Int8Residual, 1e-2),
+        ] {
+            let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+                keep_original_vectors: true,
+                original_encoding: encoding,
+                similarity_function: SimilarityFunction::Euclidean,
+                ..QuantizedIndexConfig::default()
+            }).unwrap();
+            index.build_index(&vectors).unwrap();
+            index.delete(3).unwrap();
+            for _ in 0..2 {
+                for (ord, original) in [(0, &vectors[0]), (2, &vectors[2])] {
+                    let decoded = index.get_original_vector(ord).unwrap();
+                    let error = decoded.iter().zip(original).map(|(a, b)| (a - b).abs()).fold(0.0f32, f32::max);
+                    let scale = original.iter().map(|x| x.abs()).fold(0.0f32, f32::max);
+                    assert!(error <= tolerance * scale, "{:?}: {}", encoding, error);
+                }
+                // 重排使用解码后的原始向量：查询自身时精确距离接近0
+                let results = index.search_with_params(&vectors[0], 1, &params).unwrap();
+                assert_eq!(results[0].index, 0);
+                // 压缩并刷新质心后int8残差按新的量化向量重新编码
+                index.compact(true).unwrap();
+            }
+
+            let stats = index.memory_stats().unwrap();
+            assert_eq!(stats.original_encoding, Some(encoding));
+            match encoding {
+                OriginalVectorEncoding::F32 => f32_bytes = stats.original_bytes,
+                OriginalVectorEncoding::F16 => assert_eq!(stats.original_bytes * 2, f32_bytes),
+                OriginalVectorEncoding::Int8Residual => assert!(stats.original_bytes * 3 < f32_bytes),
+            }
+        }
+    }
+
+    #[cfg(feature = "eval")]
+    #[test]
+    fn test_f16_corrections_recall_matches_f32() {
+        use crate::evaluation::compute_exact_top_k;
+
+        // 低维且不重排，量化分数直接决定排序
+        let build = |layout, similarity_function, vectors: &[Vec<f32>]| {
+            let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+                correction_layout: layout,
+                similarity_function,
+                ..QuantizedIndexConfig::default()
+            }).unwrap();
+            let built = index.build_index(vectors).map(|_| ());
+            built.map(|_| index)
+        };
+        let recall = |layout, similarity_function, vectors: &[Vec<f32>], queries: &[Vec<f32>]| {
+            let index = build(layout, similarity_function, vectors).unwrap();
+            let processed = index.preprocess_vectors(vectors);
+            let (approximate, exact): (Vec<_>, Vec<_>) = queries.iter()
+                .map(|query| {
+                    let results = index.search_nearest_neighbors(query, 10).unwrap();
+                    let query = index.preprocess_vectors(std::slice::from_ref(query))[0].clone();
+                    (
+                        results.iter().map(|r| r.index).collect::<Vec<_>>(),
+                        compute_exact_top_k(&processed, &query, 10, index.get_scorer()),
+                    )
+                })
+                .unzip();
+            mean_recall(&approximate, &exact)
+        };
+
+        // 低维且不重排，量化分数直接决定排序；欧氏距离下数据放大到附加修正在数百量级
+        let vectors = generate_gaussian_mixture(1000, 32, 10, 0.5, 41).unwrap();
+        let queries = generate_gaussian_mixture(50, 32, 10, 0.5, 42).unwrap();
+        let scale = |vectors: &[Vec<f32>]| -> Vec<Vec<f32>> {
+            vectors.iter().map(|vector| vector.iter().map(|x| x * 20.0).collect()).collect()
+        };
+        for (similarity, vectors, queries) in [
+            (SimilarityFunction::Cosine, vectors.clone(), queries.clone()),
+            (SimilarityFunction::Euclidean, scale(&vectors), scale(&queries)),
+        ] {
+            let f32_recall = recall(CorrectionLayout::ArrayOfStructs, similarity, &vectors, &queries);
+            let f16_recall = recall(CorrectionLayout::HalfPrecision, similarity, &vectors, &queries);
+            assert!((f32_recall - f16_recall).abs() <= 0.02, "{:?}: f32 {} vs f16 {}", similarity, f32_recall, f16_recall);
+        }
+        assert_eq!(CorrectionLayout::HalfPrecision.bytes_per_vector() * 2, CorrectionLayout::ArrayOfStructs.bytes_per_vector());
+
+        // 类似SIFT的数据（0-255）在欧氏距离下附加修正超出f16范围，拒绝构建而不是得到无穷的分数
+        let mut rng = fastrand::Rng::with_seed(43);
+        let sift: Vec<Vec<f32>> = (0..500).map(|_| (0..128).map(|_| rng.u8(..) as f32).collect()).collect();
+        assert!(build(CorrectionLayout::HalfPrecision, SimilarityFunction::Euclidean, &sift).is_err());
+        let aos = build(CorrectionLayout::ArrayOfStructs, SimilarityFunction::Euclidean, &sift).unwrap();
+        let hits = aos.search_nearest_neighbors(&sift[7], 10).unwrap();
+        assert!(hits.iter().all(|hit| hit.score.is_finite() && hit.score > 0.0));
+        // Lucene按向量均值缩放初始区间，这类数据上1位估计的排序与默认实现不同
+        if !cfg!(feature = "lucene_parity") {
+            assert_eq!(hits[0].index, 7);
+        }
+    }
+
+    #[test]
+    fn test_promoted_vectors_score_closer_to_exact() {
+        let vectors = generate_gaussian_mixture(400, 48, 8, 0.5, 61).unwrap();
+        let query = generate_gaussian_mixture(1, 48, 8, 0.5, 62).unwrap().remove(0);
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            keep_original_vectors: true,
+            promote_fraction: 0.1,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        index.build_index(&vectors).unwrap();
+        assert_eq!(index.promoted_count(), 40);
+        assert!(index.memory_stats().unwrap().promoted_bytes >= 40 * 48);
+        assert!(QuantizedIndex::new(QuantizedIndexConfig {
+            promote_fraction: 0.1,
+            promoted_bits: 1,
+            ..QuantizedIndexConfig::default()
+        }).is_err());
+
+        // 提升的向量与原始向量上精确分数的偏差更稳定（两种评分共有的常数偏移不影响排序）
+        let generation = index.snapshot().unwrap();
+        let promoted = generation.promoted().unwrap().ordinals();
+        drop(generation);
+        let mut normalized = query.clone();
+        normalize_vector(&mut normalized);
+        let error = |index: &QuantizedIndex| -> f32 {
+            let differences: Vec<f32> = index.score_ords(&query, &promoted).unwrap().iter()
+                .map(|result| result.score - index.get_scorer().compute_exact_score(&normalized, &index.get_original_vector(result.index).unwrap()))
+                .collect();
+            let mean = differences.iter().sum::<f32>() / differences.len() as f32;
+            (differences.iter().map(|d| (d - mean).powi(2)).sum::<f32>() / differences.len() as f32).sqrt()
+        };
+        let promoted_error = error(&index);
+        let bytes = index.serialize().unwrap();
+        assert_eq!(index.demote_vectors(&promoted).unwrap(), 40);
+        assert_eq!(index.promoted_count(), 0);
+        assert!(promoted_error < error(&index) * 0.5, "{} vs {}", promoted_error, error(&index));
+
+        // 序列化保留提升的向量；替换、压缩后仍按新序号提升
+        let mut restored = QuantizedIndex::deserialize(&bytes).unwrap();
+        assert_eq!(restored.promoted_count(), 40);
+        assert_eq!(restored.promote_vectors(&[promoted[0], 399]).unwrap(), usize::from(!promoted.contains(&399)));
+        assert!(restored.promote_vectors(&[400]).is_err());
+        restored.apply_batch(vec![IndexOp::Update { ord: promoted[1], vector: query.clone() }]).unwrap();
+        assert_eq!(restored.search_nearest_neighbors(&query, 1).unwrap()[0].index, promoted[1]);
+        restored.delete(promoted[0]).unwrap();
+        restored.compact(true).unwrap();
+        let expected = 40 + usize::from(!promoted.contains(&399)) - 1;
+        assert_eq!(restored.promoted_count(), expected);
+        assert_eq!(restored.promote_lowest_quality(0.5).unwrap() + expected, restored.promoted_count());
+    }
+
+    #[test]
+    fn test_promoted_vectors_across_search_paths() {
+        let vectors = generate_gaussian_mixture(1000, 32, 8, 0.5, 63).unwrap();
+        let query = generate_gaussian_mixture(1, 32, 8, 0.5, 64).unwrap().remove(0);
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            keep_original_vectors: true,
+            promote_fraction: 0.25,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        index.build_index(&vectors).unwrap();
+        let top = |results: &[QueryResult]| results.iter().map(|r| (r.index, r.score)).collect::<Vec<_>>();
+
+        // 预算充足的搜索和增量搜索与完整搜索的前k个分数相同
+        let params = SearchParams { include_distances: true, batch_size: Some(100), ..SearchParams::default() };
+        let expected = index.search_with_params(&query, 50, &params).unwrap();
+        let budgeted = index.search_within_budget(&query, 50, 60_000.0, &params).unwrap();
+        assert_eq!(top(&budgeted.results), top(&expected));
+        let batch = index.search_batch(&[query.clone(), vectors[0].clone()], 50, &params).unwrap();
+        assert_eq!(top(&batch[0]), top(&expected));
+        assert_eq!(top(&batch[1]), top(&index.search_with_params(&vectors[0], 50, &params).unwrap()));
+        let mut incremental = index.start_incremental_search(&query, 50).unwrap();
+        assert_eq!(top(&incremental.pump(&index, usize::MAX).unwrap().results), top(&index.search_nearest_neighbors(&query, 50).unwrap()));
+
+        // 命中的量化距离按提升后的量化值计算
+        let generation = index.snapshot().unwrap();
+        let promoted = generation.promoted().unwrap();
+        assert!(expected.iter().any(|r| promoted.contains(r.index)));
+        for result in &expected {
+            assert!((result.distances.as_ref().unwrap().quantized_score - result.score).abs() < 1e-5);
+        }
+        let promoted = promoted.ordinals();
+        drop(generation);
+
+        // 没有可取消的提升时不修改当前代，搜索布局保留
+        index.finalize_for_search().unwrap();
+        let unpromoted = (0..1000).find(|ord| !promoted.contains(ord)).unwrap();
+        assert_eq!(index.demote_vectors(&[unpromoted]).unwrap(), 0);
+        assert!(index.is_finalized());
+        assert_eq!(index.demote_vectors(&[promoted[0], unpromoted]).unwrap(), 1);
+        assert!(!index.is_finalized());
+    }
+
+    #[cfg(feature = "eval")]
+    #[test]
+    fn test_promoted_vectors_improve_recall() {
+        let vectors = generate_gaussian_mixture(1000, 32, 10, 0.5, 71).unwrap();
+        let queries = generate_gaussian_mixture(30, 32, 10, 0.5, 72).unwrap();
+        let params = SearchParams::default();
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            keep_original_vectors: true,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        index.build_index(&vectors).unwrap();
+        let baseline = index.estimate_recall(&queries, 10, &params).unwrap();
+        index.promote_lowest_quality(0.25).unwrap();
+        let promoted = index.estimate_recall(&queries, 10, &params).unwrap();
+        assert!(promoted > baseline, "{} vs {}", promoted, baseline);
+    }
+
+    #[cfg(feature = "eval")]
+    #[test]
+    fn test_grouped_corrections_recall() {
+        let vectors = generate_gaussian_mixture(2000, 64, 10, 0.5, 51).unwrap();
+        let queries = generate_gaussian_mixture(30, 64, 10, 0.5, 52).unwrap();
+        let params = SearchParams::default();
+        let build = |layout| {
+            let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+                correction_layout: layout,
+                keep_original_vectors: true,
+                ..QuantizedIndexConfig::default()
+            }).unwrap();
+            index.build_index(&vectors).unwrap();
+            index
+        };
+        let aos = build(CorrectionLayout::ArrayOfStructs);
+        let grouped = build(CorrectionLayout::Grouped);
+        let values = grouped.get_quantized_vectors().unwrap();
+        assert_eq!(values.correction_layout(), CorrectionLayout::Grouped);
+        let intervals = |values: &dyn QuantizedVectorValues| {
+            let mut intervals: Vec<(u32, u32)> = (0..values.size())
+                .map(|ord| values.get_corrective_terms(ord))
+                .map(|c| (c.lower_interval.to_bits(), c.upper_interval.to_bits()))
+                .collect();
+            intervals.sort_unstable();
+            intervals.dedup();
+            intervals
+        };
+        let groups = intervals(values.as_ref());
+        assert!(groups.len() <= CORRECTION_GROUPS, "{}", groups.len());
+        assert_eq!(values.shared_intervals().map(<[(f32, f32)]>::len), Some(groups.len()));
+
+        let aos_recall = aos.estimate_recall(&queries, 10, &params).unwrap();
+        let grouped_recall = grouped.estimate_recall(&queries, 10, &params).unwrap();
+        assert!(grouped_recall >= aos_recall - 0.1, "aos {} vs grouped {}", aos_recall, grouped_recall);
+
+        // 写入的新向量沿用已有的共享区间，组数不变
+        grouped.apply_batch(vec![
+            IndexOp::Insert { vector: queries[0].clone(), attributes: Attributes::new() },
+            IndexOp::Update { ord: 3, vector: queries[1].clone() },
+        ]).unwrap();
+        let values = grouped.get_quantized_vectors().unwrap();
+        assert_eq!(intervals(values.as_ref()), groups);
+        assert_eq!(grouped.search_nearest_neighbors(&queries[1], 1).unwrap()[0].index, 3);
+
+        // 序列化后修正项不变
+        let restored = QuantizedIndex::deserialize(&grouped.serialize().unwrap()).unwrap();
+        let restored_values = restored.get_quantized_vectors().unwrap();
+        assert_eq!(restored_values.correction_layout(), CorrectionLayout::Grouped);
+        for ord in [0, 3, values.size() - 1] {
+            let (restored, expected) = (restored_values.get_corrective_terms(ord), values.get_corrective_terms(ord));
+            assert_eq!(restored.lower_interval, expected.lower_interval);
+            assert_eq!(restored.additional_correction, expected.additional_correction);
+            assert_eq!(restored.quantized_component_sum, expected.quantized_component_sum);
+        }
+    }
+
+    #[cfg(feature = "eval")]
+    #[test]
+    fn test_f16_rescoring_recall_matches_f32() {
+        use crate::evaluation::compute_exact_top_k;
+
+        let vectors = generate_gaussian_mixture(1000, 64, 10, 0.5, 33).unwrap();
+        let queries = generate_gaussian_mixture(30, 64, 10, 0.5, 34).unwrap();
+        let params = SearchParams { rescore_oversample: RescoreOversample::Fixed(10.0), ..SearchParams::default() };
+        let recall = |encoding| {
+            let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+                keep_original_vectors: true,
+                original_encoding: encoding,
+                ..QuantizedIndexConfig::default()
+            }).unwrap();
+            index.build_index(&vectors).unwrap();
+            // 真值始终按f32原始向量计算
+            let normalized = index.preprocess_vectors(&vectors);
+            let (approximate, exact): (Vec<_>, Vec<_>) = queries.iter()
+                .map(|query| {
+                    let results = index.search_with_params(query, 10, &params).unwrap();
+                    let mut query = query.clone();
+                    normalize_vector(&mut query);
+                    (
+                        results.iter().map(|r| r.index).collect::<Vec<_>>(),
+                        compute_exact_top_k(&normalized, &query, 10, index.get_scorer()),
+                    )
+                })
+                .unzip();
+            mean_recall(&approximate, &exact)
+        };
+
+        let f32_recall = recall(OriginalVectorEncoding::F32);
+        let f16_recall = recall(OriginalVectorEncoding::F16);
+        assert!(f32_recall > 0.8, "f32重排召回率 {}", f32_recall);
+        assert!((f32_recall - f16_recall).abs() <= 0.01, "f32 {} vs f16 {}", f32_recall, f16_recall);
+    }
+
+    #[test]
+    fn test_search_with_feedback() {
+        let vectors = generate_gaussian_mixture(400, 32, 8, 0.3, 5).unwrap();
+        for keep_original_vectors in [false, true] {
+            let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+                keep_original_vectors,
+                ..QuantizedIndexConfig::default()
+            }).unwrap();
+            index.build_index(&vectors).unwrap();
+            let query = &vectors[17];
+
+            // alpha为0时与普通搜索相同
+            let plain = index.search_nearest_neighbors(query, 10).unwrap();
+            let unchanged = index.search_with_feedback(query, 10, 5, 0.0).unwrap();
+            assert_eq!(
+                plain.iter().map(|r| r.index).collect::<Vec<_>>(),
+                unchanged.iter().map(|r| r.index).collect::<Vec<_>>(),
+            );
+
+            // 扩展后的查询仍落在同一个簇中：大部分结果与初始结果重合
+            let expanded = index.search_with_feedback(query, 10, 5, 0.5).unwrap();
+            assert_eq!(expanded.len(), 10);
+            let overlap = expanded.iter().filter(|r| plain.iter().any(|p| p.index == r.index)).count();
+            assert!(overlap >= 5, "重合 {}", overlap);
+
+            assert!(index.search_with_feedback(query, 10, 0, 0.5).is_err());
+            assert!(index.search_with_feedback(query, 10, 5, 1.5).is_err());
+        }
+    }
+
+    #[cfg(feature = "graph")]
+    #[test]
+    fn test_project_2d() {
+        let vectors = generate_gaussian_mixture(150, 32, 3, 0.1, 9).unwrap();
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        index.build_index(&vectors).unwrap();
+        index.delete(4).unwrap();
+
+        for refine_iterations in [0, 50] {
+            let config = ProjectionConfig { refine_iterations, neighbors: 8, ..ProjectionConfig::default() };
+            let points = index.project_2d(&config).unwrap();
+            assert_eq!(points.len(), 150);
+            assert!(points[4][0].is_nan() && points[4][1].is_nan());
+            assert!(points.iter().enumerate().all(|(ord, p)| ord == 4 || (p[0].is_finite() && p[1].is_finite())));
+            // 每个点在图上的最近点大多来自同一个簇（以原始空间中的最近邻判断）
+            let nearest = |ord: usize| (0..150)
+                .filter(|&o| o != ord && o != 4)
+                .min_by(|&a, &b| {
+                    let d = |o: usize| (points[o][0] - points[ord][0]).powi(2) + (points[o][1] - points[ord][1]).powi(2);
+                    d(a).total_cmp(&d(b))
+                })
+                .unwrap();
+            let same = (0..150).filter(|&ord| ord != 4).filter(|&ord| {
+                let other = nearest(ord);
+                let distance: f32 = vectors[ord].iter().zip(&vectors[other]).map(|(a, b)| (a - b).powi(2)).sum();
+                distance < 32.0 * 0.1 * 0.1 * 4.0
+            }).count();
+            assert!(same >= 140, "同簇 {}", same);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_dimension_quality() {
+        // 第0维有很大的独立方差，其余维度方差很小
+        let mut vectors = generate_gaussian_mixture(200, 16, 1, 0.05, 13).unwrap();
+        let mut rng = fastrand::Rng::with_seed(1);
+        for vector in &mut vectors {
+            vector[0] += rng.f32() * 10.0 - 5.0;
+        }
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        index.build_index(&vectors).unwrap();
+        assert!(index.dimension_quality(8).is_err());
+
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            keep_original_vectors: true,
+            similarity_function: SimilarityFunction::Euclidean,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        index.build_index(&vectors).unwrap();
+        index.delete(0).unwrap();
+        let quality = index.dimension_quality(8).unwrap();
+        assert_eq!(quality.len(), 16);
+        assert!(quality.iter().all(|q| q.histogram.counts.iter().sum::<u32>() == 199));
+        assert!(quality.iter().all(|q| q.explained_variance <= 1.0 && q.reconstruction_mse >= 0.0));
+        // 方差占主导的维度被1位量化保留得最好
+        let best = quality.iter().enumerate()
+            .max_by(|a, b| a.1.explained_variance.total_cmp(&b.1.explained_variance))
+            .unwrap().0;
+        assert_eq!(best, 0);
+    }
+}
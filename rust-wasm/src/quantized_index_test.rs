@@ -1,10 +1,9 @@
-/// 量化索引测试
-/// 
-/// 测试量化索引的构建和查询功能
+//! 量化索引测试
+//! 
+//! 测试量化索引的构建和查询功能
 
 #[cfg(test)]
 mod tests {
-    use super::*;
     use crate::vector_utils::create_random_vector;
     use crate::quantized_index::{QuantizedIndex, QuantizedIndexConfig};
     use crate::vector_similarity::SimilarityFunction;
@@ -12,7 +11,7 @@ mod tests {
     #[test]
     fn test_quantized_index_basic_functionality() {
         // 创建量化索引
-        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default());
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
         
         // 创建测试向量
         let vectors: Vec<Vec<f32>> = (0..100)
@@ -52,6 +51,7 @@ mod tests {
                 similarity_function: SimilarityFunction::Cosine,
                 lambda: Some(0.1),
                 iters: Some(10),
+                ..QuantizedIndexConfig::default()
             },
             QuantizedIndexConfig {
                 query_bits: 1,
@@ -59,11 +59,12 @@ mod tests {
                 similarity_function: SimilarityFunction::Euclidean,
                 lambda: None,
                 iters: None,
+                ..QuantizedIndexConfig::default()
             },
         ];
         
         for config in configs {
-            let mut index = QuantizedIndex::new(config.clone());
+            let mut index = QuantizedIndex::new(config.clone()).unwrap();
             
             // 创建小规模测试向量
             let vectors: Vec<Vec<f32>> = (0..10)
@@ -83,7 +84,7 @@ mod tests {
 
     #[test]
     fn test_quantized_index_edge_cases() {
-        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default());
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
         
         // 测试空向量集合
         let empty_vectors: Vec<Vec<f32>> = vec![];
@@ -107,7 +108,7 @@ mod tests {
 
     #[test]
     fn test_quantized_index_query_validation() {
-        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default());
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
         
         let vectors: Vec<Vec<f32>> = (0..10)
             .map(|_| create_random_vector(32, -1.0, 1.0))
@@ -119,12 +120,12 @@ mod tests {
         let wrong_query = create_random_vector(16, -1.0, 1.0); // 错误维度
         let result = index.search_nearest_neighbors(&wrong_query, 3);
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("查询向量维度与索引维度不匹配"));
+        assert!(result.unwrap_err().detail().contains("查询向量维度与索引维度不匹配"));
         
         // 测试空查询向量
         let empty_query: Vec<f32> = vec![];
         let result = index.search_nearest_neighbors(&empty_query, 3);
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("查询向量不能为空"));
+        assert!(result.unwrap_err().detail().contains("查询向量不能为空"));
     }
 }
\ No newline at end of file
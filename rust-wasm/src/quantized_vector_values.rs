@@ -0,0 +1,463 @@
+//! 量化向量值
+//!
+//! 评分器通过 `QuantizedVectorValues` 读取索引向量的量化码、修正项和质心，
+//! 不依赖索引本身，只需要评分的构建也可以直接使用
+
+use std::ops::Range;
+
+use crate::error_codes::BbqError;
+use crate::correction_layout::{CorrectionLayout, CorrectionStore};
+use crate::optimized_scalar_quantizer::{OptimizedScalarQuantizer, QuantizationResult};
+use crate::vector_similarity::fast_dot_product;
+
+/// 量化向量值接口
+///
+/// 要求Send + Sync，使索引的一代可以在多个线程的搜索之间共享
+pub trait QuantizedVectorValues: Send + Sync {
+    /// 获取向量维度
+    fn dimension(&self) -> usize;
+    
+    /// 获取向量数量
+    fn size(&self) -> usize;
+    
+    /// 获取量化向量值
+    fn vector_value(&self, ord: usize) -> &[u8];
+    
+    /// 获取未打包的1位向量（用于4位查询）
+    fn get_unpacked_vector(&self, ord: usize) -> &[u8];
+    
+    /// 获取修正项（按值返回，分列存放时由各列拼出）
+    fn get_corrective_terms(&self, ord: usize) -> QuantizationResult;
+
+    /// 每个1位打包向量的字节数，按Lucene的布局时维度对齐到64位
+    fn packed_size(&self) -> usize {
+        self.dimension().div_ceil(8)
+    }
+
+    /// 序号连续的一段打包向量在存储中的连续字节
+    ///
+    /// 向量连续存放时批量评分可以直接读取这段字节，不需要先收集到临时缓冲区；
+    /// 存储不连续或范围越界时返回None，调用方退回按序号收集
+    fn packed_slice(&self, _range: Range<usize>) -> Option<&[u8]> {
+        None
+    }
+
+    /// 修正项的存放布局，批量评分时按同一布局收集
+    fn correction_layout(&self) -> CorrectionLayout {
+        CorrectionLayout::ArrayOfStructs
+    }
+
+    /// 分组布局下各组共享的区间，其他布局返回None
+    fn shared_intervals(&self) -> Option<&[(f32, f32)]> {
+        None
+    }
+    
+    /// 获取质心向量
+    fn get_centroid(&self) -> &[f32];
+
+    /// 获取向量的模长（构建时计算；余弦相似度时为归一化之前的模长）
+    fn get_norm(&self, ord: usize) -> f32;
+
+    /// 获取质心的模长
+    fn get_centroid_norm(&self) -> f32;
+    
+    /// 计算查询向量与质心的点积
+    fn get_centroid_dp(&self, query_vector: Option<&[f32]>) -> f32;
+
+    /// 获取量化向量值，序号越界时返回错误而不是panic
+    fn try_vector_value(&self, ord: usize) -> Result<&[u8], BbqError> {
+        check_ordinal(ord, self.size())?;
+        Ok(self.vector_value(ord))
+    }
+
+    /// 获取未打包的1位向量，序号越界时返回错误而不是panic
+    fn try_get_unpacked_vector(&self, ord: usize) -> Result<&[u8], BbqError> {
+        check_ordinal(ord, self.size())?;
+        Ok(self.get_unpacked_vector(ord))
+    }
+
+    /// 获取修正项，序号越界时返回错误而不是panic
+    fn try_get_corrective_terms(&self, ord: usize) -> Result<QuantizationResult, BbqError> {
+        check_ordinal(ord, self.size())?;
+        Ok(self.get_corrective_terms(ord))
+    }
+
+    /// 获取向量的模长，序号越界时返回错误而不是panic
+    fn try_get_norm(&self, ord: usize) -> Result<f32, BbqError> {
+        check_ordinal(ord, self.size())?;
+        Ok(self.get_norm(ord))
+    }
+}
+
+impl dyn QuantizedVectorValues + '_ {
+    /// 按序号遍历全部向量，产生（序号, 打包向量, 修正项）
+    ///
+    /// 包括已删除的序号（删除记录在索引的一代中，不在量化向量值里），需要时由调用方过滤
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (usize, &[u8], QuantizationResult)> + DoubleEndedIterator + '_ {
+        (0..self.size()).map(move |ord| (ord, self.vector_value(ord), self.get_corrective_terms(ord)))
+    }
+
+    /// 按序号遍历由量化值和修正项重建（反量化）的向量，产生（序号, 重建向量）
+    ///
+    /// 重建向量在预处理后的空间中（余弦相似度时为归一化后的向量），每个向量分配一次
+    ///
+    /// # 参数
+    /// * `index_bits` - 构建时的索引位数（量化向量值本身不记录位数）
+    pub fn iter_reconstructed(&self, index_bits: u8) -> impl ExactSizeIterator<Item = (usize, Vec<f32>)> + DoubleEndedIterator + '_ {
+        (0..self.size()).map(move |ord| {
+            let reconstruction = OptimizedScalarQuantizer::dequantize(
+                self.get_unpacked_vector(ord),
+                index_bits,
+                &self.get_corrective_terms(ord),
+                self.get_centroid(),
+            );
+            (ord, reconstruction)
+        })
+    }
+}
+
+/// 检查序号是否在范围内
+fn check_ordinal(ord: usize, size: usize) -> Result<(), BbqError> {
+    if ord < size {
+        Ok(())
+    } else {
+        Err(BbqError::out_of_range(format!("序号 {} 超出量化向量范围 {}", ord, size), format!("ordinal {} is outside the quantized vectors ({})", ord, size)))
+    }
+}
+
+/// 打包向量的存储
+///
+/// 长度一致时（正常构建的索引）连续存放在一个缓冲区中，批量评分可以直接切片；
+/// 手工构造或损坏的数据长度不一时逐个存放
+enum PackedVectors {
+    Contiguous { data: Vec<u8>, stride: usize, count: usize },
+    Ragged(Vec<Vec<u8>>),
+}
+
+impl PackedVectors {
+    fn new(vectors: Vec<Vec<u8>>) -> Self {
+        let stride = vectors.first().map_or(0, Vec::len);
+        if vectors.iter().all(|vector| vector.len() == stride) {
+            let count = vectors.len();
+            Self::Contiguous { data: vectors.concat(), stride, count }
+        } else {
+            Self::Ragged(vectors)
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Contiguous { count, .. } => *count,
+            Self::Ragged(vectors) => vectors.len(),
+        }
+    }
+
+    fn get(&self, ord: usize) -> Option<&[u8]> {
+        match self {
+            Self::Contiguous { data, stride, count } => {
+                (ord < *count).then(|| &data[ord * stride..(ord + 1) * stride])
+            }
+            Self::Ragged(vectors) => vectors.get(ord).map(Vec::as_slice),
+        }
+    }
+
+    fn slice(&self, range: Range<usize>) -> Option<&[u8]> {
+        match self {
+            Self::Contiguous { data, stride, count } if range.start <= range.end && range.end <= *count => {
+                Some(&data[range.start * stride..range.end * stride])
+            }
+            _ => None,
+        }
+    }
+
+    /// 每个向量补0到 `stride` 字节
+    fn pad_to(&mut self, new_stride: usize) {
+        match self {
+            Self::Contiguous { data, stride, count } if *stride < new_stride => {
+                let mut padded = vec![0u8; *count * new_stride];
+                for (target, source) in padded.chunks_exact_mut(new_stride).zip(data.chunks_exact((*stride).max(1))) {
+                    target[..source.len()].copy_from_slice(source);
+                }
+                *data = padded;
+                *stride = new_stride;
+            }
+            Self::Contiguous { .. } => {}
+            Self::Ragged(vectors) => {
+                for vector in vectors.iter_mut() {
+                    vector.resize(new_stride.max(vector.len()), 0);
+                }
+            }
+        }
+    }
+}
+
+/// 量化向量值实现
+pub struct QuantizedVectorValuesImpl {
+    /// 量化向量（打包格式）
+    vectors: PackedVectors,
+    /// 未打包的1位向量数组（用于4位查询）
+    unpacked_vectors: Vec<Vec<u8>>,
+    /// 修正项
+    corrections: CorrectionStore,
+    /// 质心向量
+    centroid: Vec<f32>,
+    /// 每个向量的模长
+    norms: Vec<f32>,
+    /// 质心的模长
+    centroid_norm: f32,
+    /// 向量维度
+    dimension: usize,
+    /// 每个打包向量的字节数
+    packed_size: usize,
+}
+
+impl QuantizedVectorValuesImpl {
+    /// 创建新的量化向量值实例
+    ///
+    /// # 参数
+    /// * `norms` - 每个向量的模长（余弦相似度时为归一化之前的模长）
+    pub fn new(
+        vectors: Vec<Vec<u8>>,
+        unpacked_vectors: Vec<Vec<u8>>,
+        corrections: Vec<QuantizationResult>,
+        centroid: Vec<f32>,
+        norms: Vec<f32>,
+    ) -> Self {
+        let dimension = centroid.len();
+        let centroid_norm = fast_dot_product(&centroid, &centroid).sqrt();
+        Self {
+            vectors: PackedVectors::new(vectors),
+            unpacked_vectors,
+            corrections: CorrectionStore::ArrayOfStructs(corrections),
+            centroid,
+            norms,
+            centroid_norm,
+            dimension,
+            packed_size: dimension.div_ceil(8),
+        }
+    }
+
+    /// 改为按给定布局存放修正项
+    ///
+    /// 修正项无法按所选布局存放（分组失败或超出f16范围）时返回错误，而不是悄悄退回按向量存放
+    pub fn with_correction_layout(mut self, layout: CorrectionLayout) -> Result<Self, BbqError> {
+        self.corrections = self.corrections.into_layout(layout);
+        if self.corrections.layout() != layout {
+            return Err(BbqError::invalid_argument(
+                format!("修正项无法按{}布局存放：区间种类过多，或有修正项超出该布局能表示的范围", layout.name()),
+                format!("corrections cannot be stored in the {} layout: too many distinct intervals, or a correction is outside the range the layout can represent", layout.name()),
+            ));
+        }
+        Ok(self)
+    }
+
+    /// 按Lucene的布局把1位打包向量补0对齐到64位（仅用于1位索引）
+    pub fn with_discretized_dimensions(mut self, discretized: bool) -> Self {
+        if discretized {
+            self.packed_size = OptimizedScalarQuantizer::packed_len(self.dimension, true);
+            self.vectors.pad_to(self.packed_size);
+        }
+        self
+    }
+}
+
+impl QuantizedVectorValues for QuantizedVectorValuesImpl {
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+    
+    fn size(&self) -> usize {
+        self.vectors.len()
+    }
+    
+    fn vector_value(&self, ord: usize) -> &[u8] {
+        self.vectors.get(ord).unwrap_or_else(|| panic!("序号 {} 超出量化向量范围 {}", ord, self.vectors.len()))
+    }
+    
+    fn get_unpacked_vector(&self, ord: usize) -> &[u8] {
+        &self.unpacked_vectors[ord]
+    }
+    
+    fn get_corrective_terms(&self, ord: usize) -> QuantizationResult {
+        self.corrections.get(ord).unwrap_or_else(|| panic!("序号 {} 超出修正项范围 {}", ord, self.corrections.len()))
+    }
+
+    fn packed_size(&self) -> usize {
+        self.packed_size
+    }
+
+    fn packed_slice(&self, range: Range<usize>) -> Option<&[u8]> {
+        self.vectors.slice(range)
+    }
+
+    fn correction_layout(&self) -> CorrectionLayout {
+        self.corrections.layout()
+    }
+
+    fn shared_intervals(&self) -> Option<&[(f32, f32)]> {
+        self.corrections.shared_intervals()
+    }
+    
+    fn get_centroid(&self) -> &[f32] {
+        &self.centroid
+    }
+    
+    fn get_norm(&self, ord: usize) -> f32 {
+        self.norms[ord]
+    }
+
+    fn get_centroid_norm(&self) -> f32 {
+        self.centroid_norm
+    }
+
+    fn get_centroid_dp(&self, query_vector: Option<&[f32]>) -> f32 {
+        if let Some(qv) = query_vector {
+            crate::vector_utils::compute_dot_product(qv, &self.centroid)
+        } else {
+            self.centroid_norm * self.centroid_norm
+        }
+    }
+
+    // 各数组长度可能因数据损坏而不一致，逐个按实际长度检查
+    fn try_vector_value(&self, ord: usize) -> Result<&[u8], BbqError> {
+        self.vectors.get(ord)
+            .ok_or_else(|| BbqError::out_of_range(
+                format!("序号 {} 超出量化向量范围 {}", ord, self.vectors.len()),
+                format!("ordinal {} is outside the quantized vectors ({})", ord, self.vectors.len()),
+            ))
+    }
+
+    fn try_get_unpacked_vector(&self, ord: usize) -> Result<&[u8], BbqError> {
+        self.unpacked_vectors.get(ord)
+            .map(Vec::as_slice)
+            .ok_or_else(|| BbqError::out_of_range(
+                format!("序号 {} 超出未打包向量范围 {}", ord, self.unpacked_vectors.len()),
+                format!("ordinal {} is outside the unpacked vectors ({})", ord, self.unpacked_vectors.len()),
+            ))
+    }
+
+    fn try_get_corrective_terms(&self, ord: usize) -> Result<QuantizationResult, BbqError> {
+        self.corrections.get(ord)
+            .ok_or_else(|| BbqError::out_of_range(
+                format!("序号 {} 超出修正项范围 {}", ord, self.corrections.len()),
+                format!("ordinal {} is outside the corrections ({})", ord, self.corrections.len()),
+            ))
+    }
+
+    fn try_get_norm(&self, ord: usize) -> Result<f32, BbqError> {
+        self.norms.get(ord)
+            .copied()
+            .ok_or_else(|| BbqError::out_of_range(
+                format!("序号 {} 超出模长范围 {}", ord, self.norms.len()),
+                format!("ordinal {} is outside the norms ({})", ord, self.norms.len()),
+            ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary_quantized_scorer::BinaryQuantizedScorer;
+    use crate::query_context::QueryContext;
+    use crate::vector_similarity::SimilarityFunction;
+
+    fn corrections(count: usize) -> Vec<QuantizationResult> {
+        (0..count)
+            .map(|i| QuantizationResult {
+                lower_interval: -1.0,
+                upper_interval: 1.0 + i as f32 * 0.1,
+                additional_correction: 0.5,
+                quantized_component_sum: 4.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_packed_slice_reads_contiguous_storage() {
+        let vectors: Vec<Vec<u8>> = (0..6u8).map(|i| vec![i, 0xf0 | i]).collect();
+        let values = QuantizedVectorValuesImpl::new(
+            vectors.clone(),
+            vec![vec![0; 16]; 6],
+            corrections(6),
+            vec![0.0; 16],
+            vec![1.0; 6],
+        );
+        assert_eq!(values.packed_slice(2..4), Some(&[2, 0xf2, 3, 0xf3][..]));
+        assert_eq!(values.packed_slice(5..7), None);
+        assert_eq!(values.vector_value(5), &vectors[5][..]);
+
+        // 连续序号直接切片，与按序号收集的分数一致
+        let context = QueryContext::new(vec![0.0; 16], (0..16).map(|i| i % 16).collect(), corrections(1)[0].clone(), 0.0, 4).unwrap();
+        let scorer = BinaryQuantizedScorer::new(SimilarityFunction::Euclidean);
+        let sliced = scorer.compute_batch_scores_with_context(&context, &values, &[1, 2, 3, 4]).unwrap();
+        let gathered = scorer.compute_batch_scores_with_context(&context, &values, &[4, 3, 2, 1]).unwrap();
+        assert_eq!(sliced, gathered.into_iter().rev().collect::<Vec<_>>());
+
+        // 对齐到64位后仍连续存放
+        let discretized = QuantizedVectorValuesImpl::new(vectors, vec![vec![0; 16]; 6], corrections(6), vec![0.0; 16], vec![1.0; 6])
+            .with_discretized_dimensions(true);
+        assert_eq!(discretized.vector_value(3), &[3, 0xf3, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(discretized.packed_slice(0..6).map(<[u8]>::len), Some(48));
+
+        // 长度不一的向量逐个存放，不能切片
+        let ragged = QuantizedVectorValuesImpl::new(
+            vec![vec![0; 2], vec![0; 3]],
+            vec![vec![0; 16]; 2],
+            corrections(2),
+            vec![0.0; 16],
+            vec![1.0; 2],
+        );
+        assert_eq!(ragged.packed_slice(0..1), None);
+        assert_eq!(ragged.vector_value(1).len(), 3);
+    }
+
+    #[test]
+    fn test_grouped_layout_exposes_intervals() {
+        let build = |corrections| QuantizedVectorValuesImpl::new(vec![vec![0; 2]; 3], vec![vec![0; 16]; 3], corrections, vec![0.0; 16], vec![1.0; 3]);
+        let grouped = build(corrections(3)).with_correction_layout(CorrectionLayout::Grouped).unwrap();
+        assert_eq!(grouped.correction_layout(), CorrectionLayout::Grouped);
+        assert_eq!(grouped.shared_intervals().map(<[(f32, f32)]>::len), Some(3));
+        let aos = build(corrections(3)).with_correction_layout(CorrectionLayout::ArrayOfStructs).unwrap();
+        assert_eq!(aos.shared_intervals(), None);
+
+        // 量化分量和不是整数时无法分组，报错而不是退回按向量存放
+        let mut fractional = corrections(3);
+        fractional[1].quantized_component_sum = 0.5;
+        assert!(build(fractional).with_correction_layout(CorrectionLayout::Grouped).is_err());
+    }
+
+    #[test]
+    fn test_iter_visits_every_ordinal() {
+        let unpacked: Vec<Vec<u8>> = (0..4u8).map(|i| (0..8).map(|j| (i >> (j % 3)) & 1).collect()).collect();
+        let values = QuantizedVectorValuesImpl::new(
+            (0..4u8).map(|i| vec![i]).collect(),
+            unpacked,
+            corrections(4),
+            vec![0.5; 8],
+            vec![1.0; 4],
+        );
+        let values: &dyn QuantizedVectorValues = &values;
+
+        let visited: Vec<(usize, Vec<u8>, f32)> = values.iter()
+            .map(|(ord, packed, corrections)| (ord, packed.to_vec(), corrections.upper_interval))
+            .collect();
+        assert_eq!(visited.len(), 4);
+        for (ord, packed, upper_interval) in visited {
+            assert_eq!(packed, values.vector_value(ord));
+            assert_eq!(upper_interval, values.get_corrective_terms(ord).upper_interval);
+        }
+        assert_eq!(values.iter().next_back().map(|(ord, ..)| ord), Some(3));
+
+        let reconstructed = values.iter_reconstructed(1);
+        assert_eq!(reconstructed.len(), 4);
+        for (ord, vector) in reconstructed {
+            let expected = OptimizedScalarQuantizer::dequantize(
+                values.get_unpacked_vector(ord),
+                1,
+                &values.get_corrective_terms(ord),
+                values.get_centroid(),
+            );
+            assert_eq!(vector, expected);
+        }
+    }
+}
@@ -0,0 +1,363 @@
+//! 查询上下文
+//!
+//! 缓存一次查询中与目标向量无关的计算结果（归一化、量化、打包、centroid_dp），
+//! 在多个批次、分片以及重排阶段之间复用，避免重复的O(dim)计算。
+//! 上下文可以序列化为紧凑的字节块，由主线程量化查询后交给其他Worker或服务端的分片只做评分；
+//! 字节块携带量化指纹（度量、查询位数、维度和质心），反序列化时与接收方的指纹比对
+
+use crate::error_codes::{BbqError, Subject};
+use crate::byte_reader::{metric_to_code, write_f32, ByteReader, Fnv1a};
+use crate::optimized_scalar_quantizer::{OptimizedScalarQuantizer, QuantizationResult};
+use crate::vector_similarity::SimilarityFunction;
+use crate::vector_utils::normalize_vector;
+
+/// 查询上下文字节块魔数
+const PREPARED_QUERY_MAGIC: &[u8; 4] = b"BBQC";
+
+/// 查询上下文字节块格式版本
+const PREPARED_QUERY_FORMAT_VERSION: u8 = 1;
+
+/// 标记：包含维度权重
+const FLAG_HAS_WEIGHTS: u8 = 1;
+
+/// 计算量化指纹
+///
+/// 评分要求查询与索引使用同一度量、查询位数和质心，这里用FNV-1a对它们取64位哈希，
+/// 结果与平台和编译版本无关，可以在浏览器和服务端之间比对
+pub fn quantization_fingerprint(
+    similarity_function: SimilarityFunction,
+    query_bits: u8,
+    centroid: &[f32],
+) -> u64 {
+    let mut hasher = Fnv1a::new();
+    hasher.write(&[metric_to_code(similarity_function), query_bits]);
+    hasher.write_u32(centroid.len() as u32);
+    for &value in centroid {
+        hasher.write_f32(value);
+    }
+    hasher.finish()
+}
+
+/// 预处理后的查询
+#[derive(Debug, Clone)]
+pub struct QueryContext {
+    /// 预处理后的查询向量（余弦相似度时已归一化）
+    pub query_vector: Vec<f32>,
+    /// 量化后的查询向量（未打包格式）
+    pub quantized_query: Vec<u8>,
+    /// 打包后的查询向量（仅1位查询）
+    pub packed_query: Option<Vec<u8>>,
+    /// 查询修正项
+    pub query_corrections: QuantizationResult,
+    /// 查询向量与质心的点积
+    pub centroid_dp: f32,
+    /// 查询向量位数
+    pub query_bits: u8,
+    /// 量化时所用质心的版本（由索引设置，质心刷新后旧上下文不能再使用）
+    pub centroid_epoch: Option<u64>,
+    /// 每个维度的权重（0表示屏蔽该维度），重排时按加权的精确度量计算
+    pub dimension_weights: Option<Vec<f32>>,
+}
+
+impl QueryContext {
+    /// 由已量化的查询创建上下文
+    ///
+    /// # 参数
+    /// * `query_vector` - 预处理后的查询向量
+    /// * `quantized_query` - 量化后的查询向量（未打包格式）
+    /// * `query_corrections` - 查询修正项
+    /// * `centroid_dp` - 查询向量与质心的点积
+    /// * `query_bits` - 查询向量位数
+    pub fn new(
+        query_vector: Vec<f32>,
+        quantized_query: Vec<u8>,
+        query_corrections: QuantizationResult,
+        centroid_dp: f32,
+        query_bits: u8,
+    ) -> Result<Self, BbqError> {
+        Self::with_packed_buffer(query_vector, quantized_query, query_corrections, centroid_dp, query_bits, Vec::new())
+    }
+
+    /// 同 `new`，1位查询打包到复用的缓冲区中
+    pub(crate) fn with_packed_buffer(
+        query_vector: Vec<f32>,
+        quantized_query: Vec<u8>,
+        query_corrections: QuantizationResult,
+        centroid_dp: f32,
+        query_bits: u8,
+        mut packed: Vec<u8>,
+    ) -> Result<Self, BbqError> {
+        let packed_query = if query_bits == 1 {
+            packed.clear();
+            packed.resize(quantized_query.len().div_ceil(8), 0);
+            OptimizedScalarQuantizer::pack_as_binary(&quantized_query, &mut packed)
+                .map_err(|e| e.context(Subject::new("查询向量打包失败", "query packing failed")))?;
+            Some(packed)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            query_vector,
+            quantized_query,
+            packed_query,
+            query_corrections,
+            centroid_dp,
+            query_bits,
+            centroid_epoch: None,
+            dimension_weights: None,
+        })
+    }
+
+    /// 预处理并量化原始查询（余弦相似度时先归一化），与索引构建时的量化方式一致
+    ///
+    /// # 参数
+    /// * `query_vector` - 原始查询向量
+    /// * `similarity_function` - 相似性函数
+    /// * `quantizer` - 量化器
+    /// * `query_bits` - 查询向量位数
+    /// * `centroid` - 索引的质心
+    pub fn quantize(
+        query_vector: &[f32],
+        similarity_function: SimilarityFunction,
+        quantizer: &OptimizedScalarQuantizer,
+        query_bits: u8,
+        centroid: &[f32],
+    ) -> Result<Self, BbqError> {
+        if query_vector.len() != centroid.len() {
+            return Err(BbqError::dimension_mismatch("查询向量维度与索引维度不匹配", "query dimension does not match the index dimension"));
+        }
+        let mut processed_query_vector = query_vector.to_vec();
+        if similarity_function == SimilarityFunction::Cosine {
+            normalize_vector(&mut processed_query_vector);
+        }
+
+        let mut quantized_query = vec![0u8; processed_query_vector.len()];
+        let query_corrections = quantizer.scalar_quantize(
+            &processed_query_vector,
+            &mut quantized_query,
+            query_bits,
+            centroid,
+        )?;
+
+        // Lucene的评分减去质心与自身的点积
+        #[cfg(feature = "lucene_parity")]
+        let centroid_dp = crate::lucene_parity::dot_product(centroid, centroid);
+        #[cfg(not(feature = "lucene_parity"))]
+        let centroid_dp = crate::vector_utils::compute_dot_product(&processed_query_vector, centroid);
+        Self::new(
+            processed_query_vector,
+            quantized_query,
+            query_corrections,
+            centroid_dp,
+            query_bits,
+        )
+    }
+
+    /// 获取向量维度
+    pub fn dimension(&self) -> usize {
+        self.quantized_query.len()
+    }
+
+    /// 按Lucene的布局输出4位量化查询
+    ///
+    /// 维度对齐到64位后转置为4个位平面（`transposeHalfByte`），
+    /// 与Lucene二值量化评分器使用的查询字节相同，可以配合修正项发给ES/OpenSearch的分片
+    pub fn to_lucene_query(&self) -> Result<Vec<u8>, BbqError> {
+        if self.query_bits != 4 {
+            return Err(BbqError::invalid_argument(
+                format!("Lucene的查询布局只支持4位查询，当前为{}位", self.query_bits),
+                format!("the Lucene query layout only supports 4-bit queries, got {} bits", self.query_bits),
+            ));
+        }
+        let mut transposed = vec![0u8; 4 * OptimizedScalarQuantizer::packed_len(self.dimension(), true)];
+        OptimizedScalarQuantizer::transpose_half_byte(&self.quantized_query, &mut transposed)?;
+        Ok(transposed)
+    }
+
+    /// 序列化为紧凑的字节块
+    ///
+    /// 量化查询按位数打包（1位每字节8维，4位每字节2维），
+    /// 预处理后的查询向量以f32保存，供接收方重排使用；质心版本只在本地有效，不写入
+    ///
+    /// # 参数
+    /// * `fingerprint` - 生成该上下文的索引的量化指纹，见 `quantization_fingerprint`
+    pub fn serialize(&self, fingerprint: u64) -> Result<Vec<u8>, BbqError> {
+        if self.query_bits != 1 && self.query_bits != 4 {
+            return Err(BbqError::invalid_argument(
+                format!("查询上下文只能序列化1位和4位查询，当前为{}位", self.query_bits),
+                format!("only 1-bit and 4-bit query contexts can be serialized, got {} bits", self.query_bits),
+            ));
+        }
+        let dimension = self.dimension();
+        if dimension > u32::MAX as usize {
+            return Err(BbqError::out_of_range("查询维度超出u32范围", "query dimension exceeds the u32 range"));
+        }
+        let mut bytes = Vec::with_capacity(40 + dimension * 4 + dimension.div_ceil(2));
+        bytes.extend_from_slice(PREPARED_QUERY_MAGIC);
+        bytes.push(PREPARED_QUERY_FORMAT_VERSION);
+        bytes.push(self.query_bits);
+        bytes.push(if self.dimension_weights.is_some() { FLAG_HAS_WEIGHTS } else { 0 });
+        bytes.push(0);
+        bytes.extend_from_slice(&fingerprint.to_le_bytes());
+        bytes.extend_from_slice(&(dimension as u32).to_le_bytes());
+        for value in [
+            self.query_corrections.lower_interval,
+            self.query_corrections.upper_interval,
+            self.query_corrections.additional_correction,
+            self.query_corrections.quantized_component_sum,
+            self.centroid_dp,
+        ] {
+            write_f32(&mut bytes, value);
+        }
+
+        match &self.packed_query {
+            Some(packed) => bytes.extend_from_slice(packed),
+            None => bytes.extend(
+                self.quantized_query
+                    .chunks(2)
+                    .map(|pair| (pair[0] << 4) | pair.get(1).copied().unwrap_or(0)),
+            ),
+        }
+        for &value in &self.query_vector {
+            write_f32(&mut bytes, value);
+        }
+        if let Some(weights) = &self.dimension_weights {
+            for &value in weights {
+                write_f32(&mut bytes, value);
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// 从字节块重建上下文
+    ///
+    /// # 参数
+    /// * `bytes` - `serialize` 生成的字节块
+    /// * `expected_fingerprint` - 接收方索引的量化指纹，与字节块中的不一致时返回错误
+    pub fn deserialize(bytes: &[u8], expected_fingerprint: u64) -> Result<Self, BbqError> {
+        let mut reader = ByteReader::new(bytes);
+        if reader.take(4)? != PREPARED_QUERY_MAGIC {
+            return Err(BbqError::corrupt_data("无效的查询上下文：魔数不匹配", "invalid query context: magic mismatch"));
+        }
+        let format_version = reader.read_u8()?;
+        if format_version != PREPARED_QUERY_FORMAT_VERSION {
+            return Err(BbqError::unsupported_version(
+                format!("不支持的查询上下文版本: {}", format_version),
+                format!("unsupported query context version: {}", format_version),
+            ));
+        }
+        let query_bits = reader.read_u8()?;
+        if query_bits != 1 && query_bits != 4 {
+            return Err(BbqError::corrupt_data(
+                format!("无效的查询上下文：不支持的查询位数 {}", query_bits),
+                format!("invalid query context: unsupported query bits {}", query_bits),
+            ));
+        }
+        let flags = reader.read_u8()?;
+        reader.read_u8()?;
+        let fingerprint = reader.read_u64()?;
+        if fingerprint != expected_fingerprint {
+            return Err(BbqError::invalid_argument(
+                format!("查询上下文的量化指纹 {:016x} 与索引 {:016x} 不匹配，请在当前索引上重新预处理查询", fingerprint, expected_fingerprint),
+                format!("query context fingerprint {:016x} does not match the index {:016x}; prepare the query again on this index", fingerprint, expected_fingerprint),
+            ));
+        }
+        let dimension = reader.read_u32()? as usize;
+        if dimension == 0 {
+            return Err(BbqError::corrupt_data("无效的查询上下文：维度为0", "invalid query context: dimension is 0"));
+        }
+        let query_corrections = QuantizationResult {
+            lower_interval: reader.read_f32()?,
+            upper_interval: reader.read_f32()?,
+            additional_correction: reader.read_f32()?,
+            quantized_component_sum: reader.read_f32()?,
+        };
+        let centroid_dp = reader.read_f32()?;
+
+        // 先按维度确认剩余长度，避免按伪造的维度分配
+        let quantized_len = if query_bits == 1 { dimension.div_ceil(8) } else { dimension.div_ceil(2) };
+        let weights_len = if flags & FLAG_HAS_WEIGHTS != 0 { dimension } else { 0 };
+        let expected_remaining = dimension
+            .checked_add(weights_len)
+            .and_then(|floats| floats.checked_mul(4))
+            .and_then(|len| len.checked_add(quantized_len));
+        if expected_remaining != Some(reader.remaining()) {
+            return Err(BbqError::corrupt_data("无效的查询上下文：长度与维度不符", "invalid query context: length does not match the dimension"));
+        }
+
+        let quantized = reader.take(quantized_len)?;
+        let quantized_query: Vec<u8> = if query_bits == 1 {
+            OptimizedScalarQuantizer::unpack_binary(quantized, dimension)?
+        } else {
+            (0..dimension).map(|i| if i % 2 == 0 { quantized[i / 2] >> 4 } else { quantized[i / 2] & 0x0f }).collect()
+        };
+        let query_vector = (0..dimension).map(|_| reader.read_f32()).collect::<Result<Vec<_>, _>>()?;
+        let dimension_weights = if weights_len > 0 {
+            Some((0..dimension).map(|_| reader.read_f32()).collect::<Result<Vec<_>, _>>()?)
+        } else {
+            None
+        };
+
+        let mut context = Self::new(query_vector, quantized_query, query_corrections, centroid_dp, query_bits)?;
+        context.dimension_weights = dimension_weights;
+        Ok(context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corrections() -> QuantizationResult {
+        QuantizationResult {
+            lower_interval: -1.0,
+            upper_interval: 1.0,
+            additional_correction: 0.0,
+            quantized_component_sum: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_one_bit_query_is_prepacked() {
+        let context = QueryContext::new(
+            vec![0.0; 10],
+            vec![1, 0, 1, 0, 1, 0, 1, 0, 1, 1],
+            corrections(),
+            0.5,
+            1,
+        ).unwrap();
+        assert_eq!(context.packed_query, Some(vec![0b10101010, 0b11000000]));
+        assert_eq!(context.dimension(), 10);
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let mut context = QueryContext::new(
+            vec![0.25, -0.5, 1.0],
+            vec![15, 3, 7],
+            corrections(),
+            0.5,
+            4,
+        ).unwrap();
+        context.dimension_weights = Some(vec![1.0, 0.0, 2.0]);
+        let fingerprint = quantization_fingerprint(SimilarityFunction::Euclidean, 4, &[0.1, 0.2, 0.3]);
+        let bytes = context.serialize(fingerprint).unwrap();
+
+        let restored = QueryContext::deserialize(&bytes, fingerprint).unwrap();
+        assert_eq!(restored.quantized_query, context.quantized_query);
+        assert_eq!(restored.query_vector, context.query_vector);
+        assert_eq!(restored.dimension_weights, context.dimension_weights);
+        assert_eq!(restored.centroid_dp, 0.5);
+
+        let other = quantization_fingerprint(SimilarityFunction::Euclidean, 4, &[0.1, 0.2, 0.4]);
+        assert!(QueryContext::deserialize(&bytes, other).is_err());
+        assert!(QueryContext::deserialize(&bytes[..bytes.len() - 1], fingerprint).is_err());
+    }
+
+    #[test]
+    fn test_four_bit_query_is_not_packed() {
+        let context = QueryContext::new(vec![0.0; 4], vec![15, 3, 0, 7], corrections(), 0.0, 4).unwrap();
+        assert!(context.packed_query.is_none());
+    }
+}
@@ -0,0 +1,808 @@
+//! 只读查询包
+//!
+//! 只保留搜索必需的数据（质心、修正项、连续的1位打包向量和序号映射），
+//! 去掉未打包副本、原始向量和构建期状态，得到适合CDN分发的最小产物；
+//! 加载时按块整体读取，搜索直接在连续缓冲区上批量评分，无需逐向量收集
+
+use crate::error_codes::{BbqError, Subject};
+use crate::batch_sizing::recommended_batch_size;
+use crate::byte_reader::{metric_from_code, metric_to_code, write_f32, ByteReader};
+use crate::binary_quantized_scorer::BinaryQuantizedScorer;
+use crate::memory_limits::{checked_region_len, try_with_capacity};
+use crate::optimized_scalar_quantizer::{CorrectionPrecision, OptimizedScalarQuantizer, QuantizationResult};
+use crate::quantized_index::{QuantizedIndex, QueryResult, RescoreOversample, SearchParams};
+use crate::query_context::{quantization_fingerprint, QueryContext};
+use crate::score_normalization::normalize_scores;
+use crate::vector_similarity::{descending_score_order, SimilarityFunction};
+
+/// 查询包魔数
+pub(crate) const QUERY_PACK_MAGIC: &[u8; 4] = b"BBQP";
+
+/// 查询包格式版本
+const QUERY_PACK_FORMAT_VERSION: u8 = 1;
+
+/// 标记：包含序号映射
+const FLAG_HAS_ORDINALS: u8 = 1;
+
+/// 标记：修正项与评分使用f64累加
+const FLAG_DOUBLE_PRECISION: u8 = 2;
+
+/// 未设置迭代次数时写入的值
+const ITERS_UNSET: u32 = u32::MAX;
+
+/// 只读查询包
+pub struct QueryPack {
+    similarity_function: SimilarityFunction,
+    query_bits: u8,
+    dimension: usize,
+    quantizer: OptimizedScalarQuantizer,
+    scorer: BinaryQuantizedScorer,
+    centroid: Vec<f32>,
+    corrections: Vec<QuantizationResult>,
+    /// 连续存放的1位打包向量
+    packed: Vec<u8>,
+    /// 包内位置 → 原索引序号；为None时两者相同
+    ordinals: Option<Vec<u32>>,
+    /// 查询包中的向量总数（部分加载时大于已加载数量）
+    total_count: usize,
+    /// 已加载的包内序号区间
+    loaded_ranges: Vec<(usize, usize)>,
+}
+
+/// 将已构建的索引导出为查询包
+///
+/// # 参数
+/// * `index` - 已构建的1位量化索引
+/// * `live_ordinals` - 需要保留的序号（升序）；为None时保留全部未删除的向量
+///
+/// # 返回
+/// 查询包字节
+///
+/// 格式（小端）：魔数 | 格式版本 | 度量 | 查询位数 | 标记 | 维度 | 数量 |
+/// lambda（NaN表示未设置）| 迭代次数 | 质心 | 修正项 | 打包向量 | [序号映射]
+pub fn export_query_pack(index: &QuantizedIndex, live_ordinals: Option<&[usize]>) -> Result<Vec<u8>, BbqError> {
+    let generation = index.snapshot()?;
+    let values = generation.values();
+    let config = index.get_config();
+    if config.index_bits != 1 {
+        return Err(BbqError::invalid_argument(
+            format!("查询包只支持1位索引，当前为{}位", config.index_bits),
+            format!("query packs only support 1-bit indexes, got {} bits", config.index_bits),
+        ));
+    }
+    if config.query_bits != 1 && config.query_bits != 4 {
+        return Err(BbqError::invalid_argument(
+            format!("查询包只支持1位和4位查询，当前为{}位", config.query_bits),
+            format!("query packs only support 1-bit and 4-bit queries, got {} bits", config.query_bits),
+        ));
+    }
+
+    let dimension = values.dimension();
+    let packed_size = dimension.div_ceil(8);
+    let all: Vec<usize>;
+    let ordinals = match live_ordinals {
+        Some(ordinals) => {
+            if let Some(&bad) = ordinals.iter().find(|&&ord| ord >= values.size()) {
+                return Err(BbqError::out_of_range(
+                    format!("序号 {} 超出索引范围 {}", bad, values.size()),
+                    format!("ordinal {} is outside the index ({})", bad, values.size()),
+                ));
+            }
+            if ordinals.windows(2).any(|pair| pair[0] >= pair[1]) {
+                return Err(BbqError::invalid_argument("保留的序号必须严格升序", "kept ordinals must be strictly increasing"));
+            }
+            if ordinals.len() > u32::MAX as usize {
+                return Err(BbqError::out_of_range("查询包向量数量超出u32范围", "query pack vector count exceeds the u32 range"));
+            }
+            ordinals
+        }
+        None => {
+            all = (0..values.size()).filter(|&ord| !generation.is_deleted(ord)).collect();
+            &all
+        }
+    };
+    let identity = ordinals.len() == values.size();
+
+    let mut bytes = try_with_capacity(
+        checked_region_len(ordinals.len(), 16 + packed_size + if identity { 0 } else { 4 }, Subject::new("查询包", "query pack"))?
+            .saturating_add(HEADER_LEN + dimension * 4),
+        Subject::new("查询包", "query pack"),
+    )?;
+    bytes.extend_from_slice(QUERY_PACK_MAGIC);
+    bytes.push(QUERY_PACK_FORMAT_VERSION);
+    bytes.push(metric_to_code(config.similarity_function));
+    bytes.push(config.query_bits);
+    let mut flags = if identity { 0 } else { FLAG_HAS_ORDINALS };
+    if config.correction_precision == CorrectionPrecision::Double {
+        flags |= FLAG_DOUBLE_PRECISION;
+    }
+    bytes.push(flags);
+    bytes.extend_from_slice(&(dimension as u32).to_le_bytes());
+    bytes.extend_from_slice(&(ordinals.len() as u32).to_le_bytes());
+    write_f32(&mut bytes, config.lambda.unwrap_or(f32::NAN));
+    let iters = config.iters.map(|iters| iters.min(ITERS_UNSET as usize - 1) as u32).unwrap_or(ITERS_UNSET);
+    bytes.extend_from_slice(&iters.to_le_bytes());
+
+    for &value in values.get_centroid() {
+        write_f32(&mut bytes, value);
+    }
+    for &ord in ordinals {
+        let correction = values.try_get_corrective_terms(ord)?;
+        for value in [
+            correction.lower_interval,
+            correction.upper_interval,
+            correction.additional_correction,
+            correction.quantized_component_sum,
+        ] {
+            write_f32(&mut bytes, value);
+        }
+    }
+    for &ord in ordinals {
+        let vector = values.try_vector_value(ord)?;
+        if vector.len() != packed_size {
+            return Err(BbqError::corrupt_data(
+                format!("向量 {} 的打包长度 {} 与预期 {} 不符", ord, vector.len(), packed_size),
+                format!("packed length of vector {} is {}, expected {}", ord, vector.len(), packed_size),
+            ));
+        }
+        bytes.extend_from_slice(vector);
+    }
+    if !identity {
+        for &ord in ordinals {
+            bytes.extend_from_slice(&(ord as u32).to_le_bytes());
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// 查询包头部长度
+const HEADER_LEN: usize = 24;
+
+/// 查询包各区域的字节布局
+///
+/// 修正项、打包向量和序号映射都是定长步长，只读头部就能算出任意序号区间所在的字节范围，
+/// 网络加载时可以按区间发起Range请求，逐块交给 `QueryPack::deserialize_range`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryPackLayout {
+    /// 向量维度
+    pub dimension: usize,
+    /// 包内向量总数
+    pub count: usize,
+    /// 质心起始偏移
+    pub centroid_offset: usize,
+    /// 修正项起始偏移，每个向量16字节
+    pub corrections_offset: usize,
+    /// 打包向量起始偏移，每个向量 `packed_size()` 字节
+    pub packed_offset: usize,
+    /// 序号映射起始偏移，每个向量4字节；没有序号映射时为None
+    pub ordinals_offset: Option<usize>,
+    /// 查询包总字节数
+    pub total_len: usize,
+}
+
+impl QueryPackLayout {
+    /// 从查询包头部解析布局，只需要前24字节
+    pub fn parse(bytes: &[u8]) -> Result<Self, BbqError> {
+        Ok(read_header(bytes)?.layout)
+    }
+
+    /// 每个打包向量的字节数
+    pub fn packed_size(&self) -> usize {
+        self.dimension.div_ceil(8)
+    }
+
+    /// 加载序号区间 [start, end) 需要的字节范围（头部和质心、修正项、打包向量、序号映射）
+    pub fn range_regions(&self, start: usize, end: usize) -> Result<Vec<std::ops::Range<usize>>, BbqError> {
+        if start > end || end > self.count {
+            return Err(BbqError::out_of_range(
+                format!("序号区间 [{}, {}) 超出查询包范围 {}", start, end, self.count),
+                format!("ordinal range [{}, {}) is outside the query pack ({})", start, end, self.count),
+            ));
+        }
+        // 各区域长度已在解析头部时校验过不会溢出
+        let packed_size = self.packed_size();
+        let mut regions = vec![
+            0..self.corrections_offset,
+            self.corrections_offset + start * 16..self.corrections_offset + end * 16,
+            self.packed_offset + start * packed_size..self.packed_offset + end * packed_size,
+        ];
+        if let Some(offset) = self.ordinals_offset {
+            regions.push(offset + start * 4..offset + end * 4);
+        }
+        Ok(regions)
+    }
+}
+
+/// 查询包头部
+struct PackHeader {
+    similarity_function: SimilarityFunction,
+    query_bits: u8,
+    precision: CorrectionPrecision,
+    lambda: Option<f32>,
+    iters: Option<usize>,
+    layout: QueryPackLayout,
+}
+
+/// 读取并校验头部，计算各区域偏移
+fn read_header(bytes: &[u8]) -> Result<PackHeader, BbqError> {
+    let mut reader = ByteReader::new(bytes);
+    if reader.take(4)? != QUERY_PACK_MAGIC {
+        return Err(BbqError::corrupt_data("无效的查询包：魔数不匹配", "invalid query pack: magic mismatch"));
+    }
+    let format_version = reader.read_u8()?;
+    if format_version != QUERY_PACK_FORMAT_VERSION {
+        return Err(BbqError::unsupported_version(
+            format!("不支持的查询包版本: {}", format_version),
+            format!("unsupported query pack version: {}", format_version),
+        ));
+    }
+    let similarity_function = metric_from_code(reader.read_u8()?)?;
+    let query_bits = reader.read_u8()?;
+    if query_bits != 1 && query_bits != 4 {
+        return Err(BbqError::corrupt_data(
+            format!("无效的查询包：不支持的查询位数 {}", query_bits),
+            format!("invalid query pack: unsupported query bits {}", query_bits),
+        ));
+    }
+    let flags = reader.read_u8()?;
+    let dimension = reader.read_u32()? as usize;
+    let count = reader.read_u32()? as usize;
+    let lambda = Some(reader.read_f32()?).filter(|lambda| !lambda.is_nan());
+    let iters = Some(reader.read_u32()?).filter(|&iters| iters != ITERS_UNSET).map(|iters| iters as usize);
+    if dimension == 0 {
+        return Err(BbqError::corrupt_data("无效的查询包：维度为0", "invalid query pack: dimension is 0"));
+    }
+
+    // 在分配之前确认各区域长度不会溢出
+    let packed_size = dimension.div_ceil(8);
+    let centroid_len = checked_region_len(dimension, 4, Subject::new("查询包质心", "query pack centroid"))?;
+    let corrections_len = checked_region_len(count, 16, Subject::new("查询包修正项", "query pack corrections"))?;
+    let packed_len = checked_region_len(count, packed_size, Subject::new("查询包打包向量", "query pack packed vectors"))?;
+    let ordinals_len = if flags & FLAG_HAS_ORDINALS != 0 { checked_region_len(count, 4, Subject::new("查询包序号", "query pack ordinals"))? } else { 0 };
+    let overflow = || BbqError::corrupt_data("无效的查询包：长度计算溢出", "invalid query pack: length overflow");
+    let corrections_offset = HEADER_LEN.checked_add(centroid_len).ok_or_else(overflow)?;
+    let packed_offset = corrections_offset.checked_add(corrections_len).ok_or_else(overflow)?;
+    let ordinals_offset = packed_offset.checked_add(packed_len).ok_or_else(overflow)?;
+    let total_len = ordinals_offset.checked_add(ordinals_len).ok_or_else(overflow)?;
+
+    let precision = if flags & FLAG_DOUBLE_PRECISION != 0 {
+        CorrectionPrecision::Double
+    } else {
+        CorrectionPrecision::Single
+    };
+
+    Ok(PackHeader {
+        similarity_function,
+        query_bits,
+        precision,
+        lambda,
+        iters,
+        layout: QueryPackLayout {
+            dimension,
+            count,
+            centroid_offset: HEADER_LEN,
+            corrections_offset,
+            packed_offset,
+            ordinals_offset: (ordinals_len > 0).then_some(ordinals_offset),
+            total_len,
+        },
+    })
+}
+
+impl QueryPack {
+    /// 加载查询包
+    pub fn load(bytes: &[u8]) -> Result<Self, BbqError> {
+        let header = read_header(bytes)?;
+        if header.layout.total_len != bytes.len() {
+            return Err(BbqError::corrupt_data(
+                format!("无效的查询包：数据长度 {} 与声明的 {} 不符", bytes.len().saturating_sub(HEADER_LEN), header.layout.total_len - HEADER_LEN),
+                format!("invalid query pack: data length {} does not match the declared {}", bytes.len().saturating_sub(HEADER_LEN), header.layout.total_len - HEADER_LEN),
+            ));
+        }
+        let count = header.layout.count;
+        Self::from_header(header, bytes, 0, count)
+    }
+
+    /// 只加载序号区间 [start_ord, end_ord) 内的向量
+    ///
+    /// bytes是整个查询包的缓冲区，但只需要头部、质心以及该区间的修正项、打包向量和序号映射
+    /// 已经到达（见 `QueryPackLayout::range_regions`），其余位置的内容不会被读取，
+    /// 缓冲区也可以只是尚未下载完的前缀。结果中的index仍为原索引序号，
+    /// 之后到达的区间用 `extend_range` 追加，搜索质量随加载的区间增多而提高
+    pub fn deserialize_range(bytes: &[u8], start_ord: usize, end_ord: usize) -> Result<Self, BbqError> {
+        let header = read_header(bytes)?;
+        Self::from_header(header, bytes, start_ord, end_ord)
+    }
+
+    /// 追加加载同一查询包中另一个序号区间，区间不能与已加载的区间重叠
+    pub fn extend_range(&mut self, bytes: &[u8], start_ord: usize, end_ord: usize) -> Result<(), BbqError> {
+        let header = read_header(bytes)?;
+        if header.layout.dimension != self.dimension
+            || header.layout.count != self.total_count
+            || header.similarity_function != self.similarity_function
+            || header.query_bits != self.query_bits
+        {
+            return Err(BbqError::invalid_argument("追加的区间与已加载的查询包不一致", "the appended range does not match the loaded query pack"));
+        }
+        if let Some(&(start, end)) = self.loaded_ranges.iter().find(|&&(start, end)| start < end_ord && start_ord < end) {
+            return Err(BbqError::invalid_argument(
+                format!("序号区间 [{}, {}) 与已加载的区间 [{}, {}) 重叠", start_ord, end_ord, start, end),
+                format!("ordinal range [{}, {}) overlaps the loaded range [{}, {})", start_ord, end_ord, start, end),
+            ));
+        }
+
+        let part = Self::from_header(header, bytes, start_ord, end_ord)?;
+        let existing = self.len();
+        let ordinals = self.ordinals.get_or_insert_with(|| (0..existing as u32).collect());
+        ordinals.extend(part.ordinals.unwrap_or_else(|| (start_ord as u32..end_ord as u32).collect()));
+        self.corrections.extend(part.corrections);
+        self.packed.extend(part.packed);
+        self.loaded_ranges.extend(part.loaded_ranges);
+        Ok(())
+    }
+
+    /// 由已解析的头部物化序号区间 [start, end) 内的向量
+    fn from_header(header: PackHeader, bytes: &[u8], start: usize, end: usize) -> Result<Self, BbqError> {
+        let layout = header.layout;
+        let regions = layout.range_regions(start, end)?;
+        if layout.total_len < bytes.len() {
+            return Err(BbqError::corrupt_data(
+                format!("无效的查询包：数据长度 {} 超过声明的 {}", bytes.len(), layout.total_len),
+                format!("invalid query pack: data length {} exceeds the declared {}", bytes.len(), layout.total_len),
+            ));
+        }
+        if let Some(missing) = regions.iter().find(|region| region.end > bytes.len()) {
+            return Err(BbqError::not_ready(
+                format!("查询包数据尚未到达：需要字节 [{}, {})，当前只有 {}", missing.start, missing.end, bytes.len()),
+                format!("query pack data has not arrived: bytes [{}, {}) are needed, only {} are loaded", missing.start, missing.end, bytes.len()),
+            ));
+        }
+
+        let centroid = read_f32_block(&bytes[layout.centroid_offset..layout.corrections_offset]);
+        let corrections = read_f32_block(&bytes[regions[1].clone()])
+            .chunks_exact(4)
+            .map(|chunk| QuantizationResult {
+                lower_interval: chunk[0],
+                upper_interval: chunk[1],
+                additional_correction: chunk[2],
+                quantized_component_sum: chunk[3],
+            })
+            .collect();
+        let packed = bytes[regions[2].clone()].to_vec();
+        let ordinals = match regions.get(3) {
+            Some(region) => Some(bytes[region.clone()]
+                .chunks_exact(4)
+                .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+                .collect()),
+            None if start == 0 && end == layout.count => None,
+            None => Some((start as u32..end as u32).collect()),
+        };
+
+        let similarity_function = header.similarity_function;
+        Ok(Self {
+            similarity_function,
+            query_bits: header.query_bits,
+            dimension: layout.dimension,
+            quantizer: OptimizedScalarQuantizer::new(header.lambda, header.iters, Some(similarity_function))
+                .with_correction_precision(header.precision),
+            scorer: BinaryQuantizedScorer::new(similarity_function).with_correction_precision(header.precision),
+            centroid,
+            corrections,
+            packed,
+            ordinals,
+            total_count: layout.count,
+            loaded_ranges: vec![(start, end)],
+        })
+    }
+
+    /// 由已解码的各部分创建完整加载的查询包（供读取其他格式的量化向量使用）
+    ///
+    /// # 参数
+    /// * `packed` - 连续存放的1位打包向量，每个 `dimension.div_ceil(8)` 字节
+    pub(crate) fn from_parts(
+        similarity_function: SimilarityFunction,
+        query_bits: u8,
+        centroid: Vec<f32>,
+        corrections: Vec<QuantizationResult>,
+        packed: Vec<u8>,
+    ) -> Result<Self, BbqError> {
+        let dimension = centroid.len();
+        if dimension == 0 {
+            return Err(BbqError::empty_input("质心维度不能为0", "centroid dimension must not be 0"));
+        }
+        if packed.len() != corrections.len() * dimension.div_ceil(8) {
+            return Err(BbqError::dimension_mismatch(
+                format!("打包向量长度 {} 与 {} 个 {} 维向量不符", packed.len(), corrections.len(), dimension),
+                format!("packed length {} does not fit {} vectors of {} dimensions", packed.len(), corrections.len(), dimension),
+            ));
+        }
+        let count = corrections.len();
+        Ok(Self {
+            similarity_function,
+            query_bits,
+            dimension,
+            quantizer: OptimizedScalarQuantizer::new(None, None, Some(similarity_function)),
+            scorer: BinaryQuantizedScorer::new(similarity_function),
+            centroid,
+            corrections,
+            packed,
+            ordinals: None,
+            total_count: count,
+            loaded_ranges: vec![(0, count)],
+        })
+    }
+
+    /// 向量数量
+    pub fn len(&self) -> usize {
+        self.corrections.len()
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.corrections.is_empty()
+    }
+
+    /// 查询包中的向量总数，部分加载时包括尚未加载的向量
+    pub fn total_count(&self) -> usize {
+        self.total_count
+    }
+
+    /// 是否已加载全部向量
+    pub fn is_complete(&self) -> bool {
+        self.len() == self.total_count
+    }
+
+    /// 向量维度
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// 相似性函数
+    pub fn similarity_function(&self) -> SimilarityFunction {
+        self.similarity_function
+    }
+
+    /// 搜索最近邻，结果中的index为原索引序号
+    pub fn search_nearest_neighbors(&self, query_vector: &[f32], k: usize) -> Result<Vec<QueryResult>, BbqError> {
+        self.search_with_params(query_vector, k, &SearchParams::default())
+    }
+
+    /// 使用搜索参数搜索最近邻（查询包没有原始向量，不支持重排）
+    pub fn search_with_params(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        params: &SearchParams,
+    ) -> Result<Vec<QueryResult>, BbqError> {
+        if query_vector.len() != self.dimension {
+            return Err(BbqError::dimension_mismatch("查询向量维度与索引维度不匹配", "query dimension does not match the index dimension"));
+        }
+        let context = self.prepare_query(query_vector)?;
+        self.search_with_context(&context, k, params)
+    }
+
+    /// 与生成查询包的索引一致的量化指纹
+    pub fn quantization_fingerprint(&self) -> u64 {
+        quantization_fingerprint(self.similarity_function, self.query_bits, &self.centroid)
+    }
+
+    /// 从 `QueryContext::serialize` 生成的字节块重建查询上下文，指纹必须与本查询包一致
+    pub fn deserialize_prepared_query(&self, bytes: &[u8]) -> Result<QueryContext, BbqError> {
+        QueryContext::deserialize(bytes, self.quantization_fingerprint())
+    }
+
+    /// 使用预处理好的查询上下文搜索最近邻（查询包没有原始向量，不支持重排）
+    pub fn search_with_context(
+        &self,
+        context: &QueryContext,
+        k: usize,
+        params: &SearchParams,
+    ) -> Result<Vec<QueryResult>, BbqError> {
+        if params.rescore_oversample != RescoreOversample::Disabled {
+            return Err(BbqError::invalid_argument("查询包不包含原始向量，不支持重排", "query packs have no original vectors and cannot rescore"));
+        }
+        if params.quality_weight != 0.0 {
+            return Err(BbqError::invalid_argument("查询包不包含量化质量，不支持质量混合", "query packs have no quantization quality and cannot blend it"));
+        }
+        if context.dimension() != self.dimension || context.query_bits != self.query_bits {
+            return Err(BbqError::dimension_mismatch("查询上下文的维度或位数与查询包不匹配", "query context dimension or bits do not match the query pack"));
+        }
+        if k == 0 || self.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let batch_size = recommended_batch_size(self.dimension, k, params.batch_size);
+        let mut all_results = self.score_positions(context, 0, batch_size)?;
+        all_results.sort_by(|a, b| descending_score_order(a.1, b.1));
+        let mut results: Vec<QueryResult> = all_results
+            .into_iter()
+            .take(k)
+            .map(|(index, score)| QueryResult { index, score, original_score: None, distances: None })
+            .collect();
+        normalize_scores(&mut results, params.normalization)?;
+        Ok(results)
+    }
+
+    /// 由1位码和修正项重建包内位置上的向量（余弦时为归一化后的方向）
+    ///
+    /// # 返回
+    /// (原索引序号, 重建的向量)
+    pub fn reconstruct(&self, position: usize) -> Result<(usize, Vec<f32>), BbqError> {
+        let correction = self.corrections.get(position)
+            .ok_or_else(|| BbqError::out_of_range(
+                format!("位置 {} 超出查询包范围 {}", position, self.len()),
+                format!("position {} is outside the query pack ({})", position, self.len()),
+            ))?;
+        let packed_size = self.dimension.div_ceil(8);
+        let packed = &self.packed[position * packed_size..(position + 1) * packed_size];
+        let bits = OptimizedScalarQuantizer::unpack_binary(packed, self.dimension)?;
+        let ord = self.ordinals.as_ref().map_or(position, |ordinals| ordinals[position] as usize);
+        Ok((ord, OptimizedScalarQuantizer::dequantize(&bits, 1, correction, &self.centroid)))
+    }
+
+    /// 尚未加载的包内序号区间（升序），可据此决定下一批请求的区间
+    pub fn missing_ranges(&self) -> Vec<(usize, usize)> {
+        let mut loaded = self.loaded_ranges.clone();
+        loaded.sort_unstable();
+        let mut missing = Vec::new();
+        let mut cursor = 0;
+        for (start, end) in loaded {
+            if start > cursor {
+                missing.push((cursor, start));
+            }
+            cursor = cursor.max(end);
+        }
+        if cursor < self.total_count {
+            missing.push((cursor, self.total_count));
+        }
+        missing
+    }
+
+    /// 开始一次渐进搜索
+    ///
+    /// 先对已加载的区间评分并返回临时结果，之后每追加一批区间调用一次
+    /// `ProgressiveSearch::advance`，只对新到达的向量评分并合并到已有的前k个结果中
+    pub fn start_progressive_search(&self, query_vector: &[f32], k: usize) -> Result<ProgressiveSearch, BbqError> {
+        if query_vector.len() != self.dimension {
+            return Err(BbqError::dimension_mismatch("查询向量维度与索引维度不匹配", "query dimension does not match the index dimension"));
+        }
+        Ok(ProgressiveSearch {
+            context: self.prepare_query(query_vector)?,
+            k,
+            dimension: self.dimension,
+            total_count: self.total_count,
+            scored: 0,
+            top: Vec::new(),
+        })
+    }
+
+    /// 对从包内位置from开始的所有已加载向量评分，返回（原索引序号, 分数）
+    fn score_positions(&self, context: &QueryContext, from: usize, batch_size: usize) -> Result<Vec<(usize, f32)>, BbqError> {
+        let packed_size = self.dimension.div_ceil(8);
+        let mut scored = Vec::with_capacity(self.len() - from);
+        for (batch, corrections) in self.packed[from * packed_size..]
+            .chunks(batch_size * packed_size)
+            .zip(self.corrections[from..].chunks(batch_size))
+        {
+            let offset = from + scored.len();
+            let scores = self.scorer.compute_batch_scores_packed(context, batch, corrections, self.dimension)?;
+            scored.extend(scores.into_iter().enumerate().map(|(i, score)| {
+                let position = offset + i;
+                (self.ordinals.as_ref().map_or(position, |ordinals| ordinals[position] as usize), score)
+            }));
+        }
+        Ok(scored)
+    }
+
+    /// 预处理查询向量，与 `QuantizedIndex::prepare_query` 保持一致
+    fn prepare_query(&self, query_vector: &[f32]) -> Result<QueryContext, BbqError> {
+        QueryContext::quantize(query_vector, self.similarity_function, &self.quantizer, self.query_bits, &self.centroid)
+    }
+}
+
+/// 渐进搜索的临时结果（查询包上的渐进搜索和索引上的增量搜索共用）
+#[derive(Debug, Clone)]
+pub struct ProgressiveResults {
+    /// 当前已评分向量中的前k个结果，index为原索引序号
+    pub results: Vec<QueryResult>,
+    /// 已评分向量占总数的比例
+    pub coverage: f32,
+    /// 所有向量都已评分，结果不会再变化
+    pub is_final: bool,
+}
+
+/// 渐进搜索状态
+///
+/// 保存预处理后的查询和当前前k个结果；查询包只会追加区间，
+/// 因此只需记住已评分到的包内位置
+pub struct ProgressiveSearch {
+    context: QueryContext,
+    k: usize,
+    dimension: usize,
+    total_count: usize,
+    /// 已评分的包内位置数
+    scored: usize,
+    /// 当前前k个（原索引序号, 分数），按分数降序
+    top: Vec<(usize, f32)>,
+}
+
+impl ProgressiveSearch {
+    /// 对上次调用之后新加载的向量评分，返回合并后的临时结果
+    pub fn advance(&mut self, pack: &QueryPack) -> Result<ProgressiveResults, BbqError> {
+        if pack.dimension != self.dimension || pack.total_count != self.total_count || pack.len() < self.scored {
+            return Err(BbqError::invalid_argument("渐进搜索只能用于开始搜索时的同一个查询包", "a progressive search can only be advanced with the query pack it started on"));
+        }
+        if self.k > 0 && pack.len() > self.scored {
+            let batch_size = recommended_batch_size(self.dimension, self.k, None);
+            let fresh = pack.score_positions(&self.context, self.scored, batch_size)?;
+            self.top.extend(fresh);
+            self.top.sort_by(|a, b| descending_score_order(a.1, b.1));
+            self.top.truncate(self.k);
+        }
+        self.scored = pack.len();
+
+        Ok(ProgressiveResults {
+            results: self.top.iter()
+                .map(|&(index, score)| QueryResult { index, score, original_score: None, distances: None })
+                .collect(),
+            coverage: if self.total_count == 0 { 1.0 } else { self.scored as f32 / self.total_count as f32 },
+            is_final: self.scored == self.total_count,
+        })
+    }
+}
+
+fn read_f32_block(bytes: &[u8]) -> Vec<f32> {
+    bytes.chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quantized_index::QuantizedIndexConfig;
+    use crate::vector_utils::create_random_vector;
+
+    fn build_index(count: usize, dimension: usize) -> (QuantizedIndex, Vec<Vec<f32>>) {
+        let vectors: Vec<Vec<f32>> = (0..count)
+            .map(|_| create_random_vector(dimension, -1.0, 1.0))
+            .collect();
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig {
+            keep_original_vectors: true,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        index.build_index(&vectors).unwrap();
+        (index, vectors)
+    }
+
+    #[test]
+    fn test_pack_matches_index() {
+        let (index, vectors) = build_index(1200, 40);
+        let bytes = export_query_pack(&index, None).unwrap();
+        let pack = QueryPack::load(&bytes).unwrap();
+        assert_eq!(pack.len(), 1200);
+
+        // 只有质心、修正项和打包向量
+        assert_eq!(bytes.len(), 24 + 40 * 4 + 1200 * (16 + 5));
+
+        let expected = index.search_nearest_neighbors(&vectors[3], 10).unwrap();
+        let actual = pack.search_nearest_neighbors(&vectors[3], 10).unwrap();
+        let expected: Vec<(usize, f32)> = expected.iter().map(|r| (r.index, r.score)).collect();
+        let actual: Vec<(usize, f32)> = actual.iter().map(|r| (r.index, r.score)).collect();
+        assert_eq!(actual, expected);
+
+        // 主线程预处理查询，查询包只做评分
+        assert_eq!(pack.quantization_fingerprint(), index.quantization_fingerprint().unwrap());
+        let blob = index.prepare_query(&vectors[3]).unwrap()
+            .serialize(index.quantization_fingerprint().unwrap()).unwrap();
+        let context = pack.deserialize_prepared_query(&blob).unwrap();
+        let dispatched = pack.search_with_context(&context, 10, &SearchParams::default()).unwrap();
+        let dispatched: Vec<(usize, f32)> = dispatched.iter().map(|r| (r.index, r.score)).collect();
+        assert_eq!(dispatched, expected);
+        assert!(index.deserialize_prepared_query(&blob).is_ok());
+    }
+
+    #[test]
+    fn test_pack_bytes_are_deterministic() {
+        let (index, vectors) = build_index(300, 24);
+        let mut rebuilt = QuantizedIndex::new(QuantizedIndexConfig {
+            keep_original_vectors: true,
+            ..QuantizedIndexConfig::default()
+        }).unwrap();
+        rebuilt.build_index(&vectors).unwrap();
+        let bytes = export_query_pack(&index, None).unwrap();
+        assert_eq!(export_query_pack(&rebuilt, None).unwrap(), bytes);
+
+        let context = index.prepare_query(&vectors[0]).unwrap();
+        let fingerprint = index.quantization_fingerprint().unwrap();
+        assert_eq!(context.serialize(fingerprint).unwrap(), rebuilt.prepare_query(&vectors[0]).unwrap().serialize(fingerprint).unwrap());
+    }
+
+    #[test]
+    fn test_pack_with_live_ordinals() {
+        let (index, vectors) = build_index(50, 16);
+        let live: Vec<usize> = (0..50).filter(|ord| ord % 3 != 0).collect();
+        let pack = QueryPack::load(&export_query_pack(&index, Some(&live)).unwrap()).unwrap();
+        assert_eq!(pack.len(), live.len());
+
+        let results = pack.search_nearest_neighbors(&vectors[4], 50).unwrap();
+        assert_eq!(results.len(), live.len());
+        assert!(results.iter().all(|r| r.index % 3 != 0));
+
+        assert!(export_query_pack(&index, Some(&[3, 2])).is_err());
+        assert!(export_query_pack(&index, Some(&[50])).is_err());
+    }
+
+    #[test]
+    fn test_load_rejects_corrupt_pack() {
+        let (index, _) = build_index(10, 16);
+        let bytes = export_query_pack(&index, None).unwrap();
+        assert!(QueryPack::load(&bytes[..bytes.len() - 1]).is_err());
+        let mut forged = bytes.clone();
+        forged[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(QueryPack::load(&forged).is_err());
+
+        let pack = QueryPack::load(&bytes).unwrap();
+        let params = SearchParams {
+            rescore_oversample: RescoreOversample::Fixed(2.0),
+            ..SearchParams::default()
+        };
+        assert!(pack.search_with_params(&[0.0; 16], 3, &params).is_err());
+    }
+
+    #[test]
+    fn test_progressive_range_loading() {
+        let (index, vectors) = build_index(300, 24);
+        let bytes = export_query_pack(&index, None).unwrap();
+        let full = QueryPack::load(&bytes).unwrap();
+        let layout = QueryPackLayout::parse(&bytes[..24]).unwrap();
+        assert_eq!(layout.total_len, bytes.len());
+
+        // 模拟按区间到达：缓冲区中只有头部、质心和 [100, 200) 所需的字节
+        let mut arriving = vec![0u8; bytes.len()];
+        for region in layout.range_regions(100, 200).unwrap() {
+            arriving[region.clone()].copy_from_slice(&bytes[region]);
+        }
+        let mut pack = QueryPack::deserialize_range(&arriving, 100, 200).unwrap();
+        assert_eq!((pack.len(), pack.total_count()), (100, 300));
+        let partial = pack.search_nearest_neighbors(&vectors[150], 100).unwrap();
+        assert!(partial.iter().all(|r| (100..200).contains(&r.index)));
+        assert_eq!(partial[0].index, 150);
+
+        assert!(QueryPack::deserialize_range(&bytes[..layout.packed_offset], 0, 10).is_err());
+        assert!(pack.extend_range(&bytes, 150, 250).is_err());
+        pack.extend_range(&bytes, 200, 300).unwrap();
+        pack.extend_range(&bytes, 0, 100).unwrap();
+        assert!(pack.is_complete());
+
+        assert!(pack.missing_ranges().is_empty());
+        let expected = full.search_nearest_neighbors(&vectors[7], 10).unwrap();
+        let actual = pack.search_nearest_neighbors(&vectors[7], 10).unwrap();
+        let expected: Vec<(usize, f32)> = expected.iter().map(|r| (r.index, r.score)).collect();
+        let actual: Vec<(usize, f32)> = actual.iter().map(|r| (r.index, r.score)).collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_progressive_search() {
+        let (index, vectors) = build_index(400, 32);
+        let bytes = export_query_pack(&index, None).unwrap();
+        let expected = QueryPack::load(&bytes).unwrap().search_nearest_neighbors(&vectors[42], 5).unwrap();
+
+        let mut pack = QueryPack::deserialize_range(&bytes, 300, 400).unwrap();
+        assert_eq!(pack.missing_ranges(), vec![(0, 300)]);
+        let mut search = pack.start_progressive_search(&vectors[42], 5).unwrap();
+        let provisional = search.advance(&pack).unwrap();
+        assert_eq!(provisional.coverage, 0.25);
+        assert!(!provisional.is_final);
+        assert!(provisional.results.iter().all(|r| r.index >= 300));
+
+        pack.extend_range(&bytes, 0, 300).unwrap();
+        let complete = search.advance(&pack).unwrap();
+        assert!(complete.is_final);
+        let expected: Vec<(usize, f32)> = expected.iter().map(|r| (r.index, r.score)).collect();
+        let actual: Vec<(usize, f32)> = complete.results.iter().map(|r| (r.index, r.score)).collect();
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,446 @@
+//! 多副本同步
+//!
+//! 面向多标签页浏览器应用：每个标签页持有同一索引的一个副本，
+//! 本地写入后通过BroadcastChannel把变更字节广播给其他标签页，
+//! 新打开的标签页从紧凑快照启动。Rust侧只负责编码/合并，通道由JS管理。
+//!
+//! 冲突规则：每次写入（添加/覆盖/删除）带有 (Lamport时钟, 副本ID) 戳，
+//! 同一ID的并发写入以时钟较大者为准，时钟相同时副本ID字典序较大者胜出；
+//! 删除同样参与比较，因此所有副本收到相同变更后收敛到相同状态
+
+use std::collections::BTreeMap;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::error_codes::{BbqError, Subject};
+use crate::bbq::{Bbq, BbqHit};
+use crate::byte_reader::{metric_from_code, metric_to_code, write_f32, ByteReader};
+use crate::memory_limits::checked_region_len;
+use crate::vector_similarity::SimilarityFunction;
+
+/// 副本快照魔数
+const REPLICA_SNAPSHOT_MAGIC: &[u8; 4] = b"BBQR";
+
+/// 副本变更魔数
+const REPLICA_CHANGES_MAGIC: &[u8; 4] = b"BBQC";
+
+/// 副本格式版本
+const REPLICA_FORMAT_VERSION: u8 = 1;
+
+/// 写入操作标记
+const OP_UPSERT: u8 = 0;
+const OP_DELETE: u8 = 1;
+
+/// 写入戳：(Lamport时钟, 副本ID)，按字典序比较
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct WriteStamp {
+    /// Lamport时钟
+    pub clock: u64,
+    /// 产生写入的副本
+    pub replica: String,
+}
+
+/// 版本向量：副本ID → 已见到的该副本最大时钟
+pub type VersionVector = BTreeMap<String, u64>;
+
+/// 合并结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct MergeSummary {
+    /// 生效的写入数量
+    pub applied: usize,
+    /// 因冲突规则或已见过而忽略的写入数量
+    pub ignored: usize,
+}
+
+/// 单条变更
+struct ReplicaOp {
+    id: String,
+    stamp: WriteStamp,
+    /// None表示删除
+    vector: Option<Vec<f32>>,
+}
+
+/// 可同步的索引副本
+pub struct Replica {
+    replica_id: String,
+    store: Bbq,
+    /// 每个ID（含已删除的）最后一次生效写入的戳
+    stamps: BTreeMap<String, WriteStamp>,
+    versions: VersionVector,
+    clock: u64,
+}
+
+impl Replica {
+    /// 创建空副本
+    ///
+    /// # 参数
+    /// * `replica_id` - 副本ID，每个标签页必须唯一
+    /// * `dims` - 向量维度
+    /// * `metric` - 度量方式
+    pub fn new(replica_id: &str, dims: usize, metric: SimilarityFunction) -> Result<Self, BbqError> {
+        if replica_id.is_empty() {
+            return Err(BbqError::empty_input("副本ID不能为空", "replica ID must not be empty"));
+        }
+        Ok(Self {
+            replica_id: replica_id.to_string(),
+            store: Bbq::new(dims, metric)?,
+            stamps: BTreeMap::new(),
+            versions: VersionVector::new(),
+            clock: 0,
+        })
+    }
+
+    /// 副本ID
+    pub fn replica_id(&self) -> &str {
+        &self.replica_id
+    }
+
+    /// 版本向量
+    pub fn version_vector(&self) -> &VersionVector {
+        &self.versions
+    }
+
+    /// 向量数量
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+
+    /// 获取指定ID的原始向量
+    pub fn get(&self, id: &str) -> Option<&[f32]> {
+        self.store.get(id)
+    }
+
+    /// 本地添加或覆盖向量
+    pub fn add(&mut self, id: &str, vector: &[f32]) -> Result<(), BbqError> {
+        self.store.add(id, vector)?;
+        let stamp = self.next_stamp();
+        self.stamps.insert(id.to_string(), stamp);
+        Ok(())
+    }
+
+    /// 本地删除向量，ID存在时返回true
+    pub fn remove(&mut self, id: &str) -> Result<bool, BbqError> {
+        if !self.store.remove(id)? {
+            return Ok(false);
+        }
+        let stamp = self.next_stamp();
+        self.stamps.insert(id.to_string(), stamp);
+        Ok(true)
+    }
+
+    /// 查询最相似的k个向量
+    pub fn query(&mut self, vector: &[f32], k: usize) -> Result<Vec<BbqHit>, BbqError> {
+        self.store.query(vector, k)
+    }
+
+    /// 导出对方尚未见过的变更
+    ///
+    /// 格式（小端）：魔数 | 格式版本 | 度量 | 维度 | 版本向量 | 变更数量 |
+    /// (ID, 时钟, 副本ID, 操作, [向量])*
+    ///
+    /// # 参数
+    /// * `since` - 接收方的版本向量；空向量表示导出全部状态
+    pub fn export_changes(&self, since: &VersionVector) -> Vec<u8> {
+        let mut bytes = self.header(REPLICA_CHANGES_MAGIC);
+        self.write_ops(&mut bytes, since);
+        bytes
+    }
+
+    /// 合并其他副本导出的变更
+    ///
+    /// 整个变更先完整解析校验，失败时不修改任何数据
+    pub fn merge(&mut self, bytes: &[u8]) -> Result<MergeSummary, BbqError> {
+        let mut reader = ByteReader::new(bytes);
+        let remote_versions = self.read_header(&mut reader, REPLICA_CHANGES_MAGIC)?;
+        let ops = self.read_ops(&mut reader)?;
+        if !reader.is_empty() {
+            return Err(BbqError::corrupt_data("无效的副本变更：存在多余数据", "invalid replica changes: trailing data"));
+        }
+        self.apply_ops(ops, remote_versions)
+    }
+
+    /// 导出紧凑快照，供新打开的标签页启动
+    ///
+    /// 格式与变更相同，只是魔数不同并在版本向量后附带Lamport时钟；
+    /// 快照包含全部存活向量、删除记录的写入戳和版本向量
+    pub fn snapshot(&self) -> Vec<u8> {
+        let mut bytes = self.header(REPLICA_SNAPSHOT_MAGIC);
+        bytes.extend_from_slice(&self.clock.to_le_bytes());
+        self.write_ops(&mut bytes, &VersionVector::new());
+        bytes
+    }
+
+    /// 从快照创建新副本
+    ///
+    /// # 参数
+    /// * `bytes` - 其他副本导出的快照
+    /// * `replica_id` - 新副本的ID，不能与快照来源或其他副本相同
+    pub fn from_snapshot(bytes: &[u8], replica_id: &str) -> Result<Self, BbqError> {
+        let mut reader = ByteReader::new(bytes);
+        if reader.take(4)? != REPLICA_SNAPSHOT_MAGIC {
+            return Err(BbqError::corrupt_data("无效的副本快照：魔数不匹配", "invalid replica snapshot: magic mismatch"));
+        }
+        let format_version = reader.read_u8()?;
+        if format_version != REPLICA_FORMAT_VERSION {
+            return Err(BbqError::unsupported_version(
+                format!("不支持的副本格式版本: {}", format_version),
+                format!("unsupported replica format version: {}", format_version),
+            ));
+        }
+        let metric = metric_from_code(reader.read_u8()?)?;
+        let dims = reader.read_u32()? as usize;
+
+        let mut replica = Self::new(replica_id, dims, metric)?;
+        let versions = read_versions(&mut reader)?;
+        let clock = reader.read_u64()?;
+        let ops = replica.read_ops(&mut reader)?;
+        if !reader.is_empty() {
+            return Err(BbqError::corrupt_data("无效的副本快照：存在多余数据", "invalid replica snapshot: trailing data"));
+        }
+
+        replica.apply_ops(ops, versions)?;
+        replica.clock = replica.clock.max(clock);
+        Ok(replica)
+    }
+
+    fn next_stamp(&mut self) -> WriteStamp {
+        self.clock += 1;
+        self.versions.insert(self.replica_id.clone(), self.clock);
+        WriteStamp {
+            clock: self.clock,
+            replica: self.replica_id.clone(),
+        }
+    }
+
+    /// 按冲突规则应用变更并合并版本向量
+    fn apply_ops(&mut self, ops: Vec<ReplicaOp>, remote_versions: VersionVector) -> Result<MergeSummary, BbqError> {
+        let mut summary = MergeSummary::default();
+        for op in ops {
+            if self.apply_op(op)? {
+                summary.applied += 1;
+            } else {
+                summary.ignored += 1;
+            }
+        }
+        for (replica, clock) in remote_versions {
+            let seen = self.versions.entry(replica).or_insert(0);
+            *seen = (*seen).max(clock);
+        }
+        Ok(summary)
+    }
+
+    /// 按冲突规则应用单条变更，返回是否生效
+    fn apply_op(&mut self, op: ReplicaOp) -> Result<bool, BbqError> {
+        self.clock = self.clock.max(op.stamp.clock);
+        if let Some(current) = self.stamps.get(&op.id) {
+            if *current >= op.stamp {
+                return Ok(false);
+            }
+        }
+        match op.vector {
+            Some(vector) => {
+                self.store.add(&op.id, &vector)?;
+            }
+            None => {
+                self.store.remove(&op.id)?;
+            }
+        }
+        self.stamps.insert(op.id, op.stamp);
+        Ok(true)
+    }
+
+    fn header(&self, magic: &[u8; 4]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(magic);
+        bytes.push(REPLICA_FORMAT_VERSION);
+        bytes.push(metric_to_code(self.store.metric()));
+        bytes.extend_from_slice(&(self.store.dims() as u32).to_le_bytes());
+        write_versions(&mut bytes, &self.versions);
+        bytes
+    }
+
+    fn read_header(&self, reader: &mut ByteReader, magic: &[u8; 4]) -> Result<VersionVector, BbqError> {
+        if reader.take(4)? != magic {
+            return Err(BbqError::corrupt_data("无效的副本变更：魔数不匹配", "invalid replica changes: magic mismatch"));
+        }
+        let format_version = reader.read_u8()?;
+        if format_version != REPLICA_FORMAT_VERSION {
+            return Err(BbqError::unsupported_version(
+                format!("不支持的副本格式版本: {}", format_version),
+                format!("unsupported replica format version: {}", format_version),
+            ));
+        }
+        let metric = metric_from_code(reader.read_u8()?)?;
+        let dims = reader.read_u32()? as usize;
+        if metric != self.store.metric() || dims != self.store.dims() {
+            return Err(BbqError::dimension_mismatch(
+                format!("副本变更的度量/维度 ({:?}, {}) 与当前副本 ({:?}, {}) 不一致", metric, dims, self.store.metric(), self.store.dims()),
+                format!("replica changes metric/dimension ({:?}, {}) differ from this replica ({:?}, {})", metric, dims, self.store.metric(), self.store.dims()),
+            ));
+        }
+        read_versions(reader)
+    }
+
+    fn write_ops(&self, bytes: &mut Vec<u8>, since: &VersionVector) {
+        let pending: Vec<(&String, &WriteStamp)> = self.stamps.iter()
+            .filter(|(_, stamp)| stamp.clock > since.get(&stamp.replica).copied().unwrap_or(0))
+            .collect();
+
+        bytes.extend_from_slice(&(pending.len() as u32).to_le_bytes());
+        for (id, stamp) in pending {
+            write_string(bytes, id);
+            bytes.extend_from_slice(&stamp.clock.to_le_bytes());
+            write_string(bytes, &stamp.replica);
+            match self.store.get(id) {
+                Some(vector) => {
+                    bytes.push(OP_UPSERT);
+                    for &value in vector {
+                        write_f32(bytes, value);
+                    }
+                }
+                None => bytes.push(OP_DELETE),
+            }
+        }
+    }
+
+    fn read_ops(&self, reader: &mut ByteReader) -> Result<Vec<ReplicaOp>, BbqError> {
+        let count = reader.read_u32()? as usize;
+        // 每条变更至少包含：ID长度 + 时钟 + 副本ID长度 + 操作
+        if checked_region_len(count, 17, Subject::new("副本变更", "replica changes"))? > reader.remaining() {
+            return Err(BbqError::corrupt_data("无效的副本变更：数据被截断", "invalid replica changes: data is truncated"));
+        }
+        let mut ops = Vec::with_capacity(count);
+        for _ in 0..count {
+            ops.push(self.read_op(reader)?);
+        }
+        Ok(ops)
+    }
+
+    fn read_op(&self, reader: &mut ByteReader) -> Result<ReplicaOp, BbqError> {
+        let id = reader.read_id()?;
+        let clock = reader.read_u64()?;
+        let replica = reader.read_id()?;
+        let vector = match reader.read_u8()? {
+            OP_UPSERT => {
+                let mut vector = Vec::with_capacity(self.store.dims());
+                for _ in 0..self.store.dims() {
+                    vector.push(reader.read_f32()?);
+                }
+                // 合并前完成校验，避免应用到一半失败
+                if vector.iter().any(|v| !v.is_finite()) {
+                    return Err(BbqError::invalid_value(
+                        format!("无效的副本变更：ID {} 的向量包含无效值", id),
+                        format!("invalid replica changes: vector of ID {} contains invalid values", id),
+                    ));
+                }
+                Some(vector)
+            }
+            OP_DELETE => None,
+            other => return Err(BbqError::corrupt_data(
+                format!("无效的副本变更：未知的操作 {}", other),
+                format!("invalid replica changes: unknown operation {}", other),
+            )),
+        };
+        Ok(ReplicaOp {
+            id,
+            stamp: WriteStamp { clock, replica },
+            vector,
+        })
+    }
+}
+
+fn write_string(bytes: &mut Vec<u8>, value: &str) {
+    bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(value.as_bytes());
+}
+
+fn write_versions(bytes: &mut Vec<u8>, versions: &VersionVector) {
+    bytes.extend_from_slice(&(versions.len() as u32).to_le_bytes());
+    for (replica, clock) in versions {
+        write_string(bytes, replica);
+        bytes.extend_from_slice(&clock.to_le_bytes());
+    }
+}
+
+fn read_versions(reader: &mut ByteReader) -> Result<VersionVector, BbqError> {
+    let count = reader.read_u32()? as usize;
+    if checked_region_len(count, 12, Subject::new("版本向量", "version vector"))? > reader.remaining() {
+        return Err(BbqError::corrupt_data("无效的副本变更：数据被截断", "invalid replica changes: data is truncated"));
+    }
+    let mut versions = VersionVector::new();
+    for _ in 0..count {
+        let replica = reader.read_id()?;
+        let clock = reader.read_u64()?;
+        versions.insert(replica, clock);
+    }
+    Ok(versions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted_state(replica: &Replica) -> Vec<(String, Vec<f32>)> {
+        replica.stamps.keys()
+            .filter_map(|id| replica.get(id).map(|vector| (id.clone(), vector.to_vec())))
+            .collect()
+    }
+
+    #[test]
+    fn test_replicas_converge() {
+        let mut a = Replica::new("tab-a", 2, SimilarityFunction::Cosine).unwrap();
+        let mut b = Replica::new("tab-b", 2, SimilarityFunction::Cosine).unwrap();
+        a.add("x", &[1.0, 0.0]).unwrap();
+        a.add("y", &[0.0, 1.0]).unwrap();
+        b.merge(&a.export_changes(b.version_vector())).unwrap();
+        assert_eq!(b.len(), 2);
+
+        // 并发写入同一ID：时钟相同，副本ID较大的tab-b胜出
+        a.add("x", &[0.5, 0.5]).unwrap();
+        b.add("x", &[0.2, 0.8]).unwrap();
+        b.remove("y").unwrap();
+        let from_a = a.export_changes(b.version_vector());
+        let from_b = b.export_changes(a.version_vector());
+        let summary = b.merge(&from_a).unwrap();
+        assert_eq!(summary, MergeSummary { applied: 0, ignored: 1 });
+        a.merge(&from_b).unwrap();
+
+        assert_eq!(sorted_state(&a), sorted_state(&b));
+        assert_eq!(a.get("x").unwrap(), &[0.2, 0.8]);
+        assert!(a.get("y").is_none());
+        assert_eq!(a.version_vector(), b.version_vector());
+
+        // 已同步后没有待导出的变更
+        let idle = a.export_changes(b.version_vector());
+        assert_eq!(b.merge(&idle).unwrap(), MergeSummary::default());
+    }
+
+    #[test]
+    fn test_snapshot_bootstrap() {
+        let mut a = Replica::new("tab-a", 3, SimilarityFunction::Euclidean).unwrap();
+        a.add("p", &[1.0, 2.0, 3.0]).unwrap();
+        a.add("q", &[3.0, 2.0, 1.0]).unwrap();
+        a.remove("q").unwrap();
+
+        let mut c = Replica::from_snapshot(&a.snapshot(), "tab-c").unwrap();
+        assert_eq!(c.len(), 1);
+        assert_eq!(c.version_vector(), a.version_vector());
+        assert_eq!(c.query(&[1.0, 2.0, 3.0], 1).unwrap()[0].id, "p");
+
+        // 新副本的写入时钟在快照之后，能覆盖来源副本的旧写入
+        c.add("p", &[0.0, 0.0, 1.0]).unwrap();
+        a.merge(&c.export_changes(a.version_vector())).unwrap();
+        assert_eq!(a.get("p").unwrap(), &[0.0, 0.0, 1.0]);
+
+        assert!(Replica::from_snapshot(&a.snapshot()[..12], "tab-d").is_err());
+        assert!(Replica::new("", 3, SimilarityFunction::Euclidean).is_err());
+    }
+}
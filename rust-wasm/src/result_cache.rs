@@ -0,0 +1,210 @@
+//! 搜索结果缓存
+//!
+//! 以量化查询字节和搜索参数的指纹为键的LRU缓存，
+//! 适合界面重渲染等反复发出相同查询的场景；索引变更时整体失效
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+#[cfg(feature = "ivf")]
+use crate::ivf::ProbeStrategy;
+use crate::quantized_index::{QueryResult, RescoreOversample, SearchParams};
+use crate::query_context::QueryContext;
+use crate::score_normalization::ScoreNormalization;
+
+/// 缓存统计信息
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ResultCacheStats {
+    /// 命中次数
+    pub hits: u64,
+    /// 未命中次数
+    pub misses: u64,
+    /// 当前缓存条目数
+    pub len: usize,
+    /// 最大缓存条目数
+    pub capacity: usize,
+}
+
+/// 缓存条目
+struct CacheEntry {
+    results: Vec<QueryResult>,
+    last_used: u64,
+}
+
+/// LRU结果缓存
+pub struct ResultCache {
+    capacity: usize,
+    entries: HashMap<u64, CacheEntry>,
+    /// 逻辑时钟，用于LRU淘汰
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl ResultCache {
+    /// 创建指定容量的缓存
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::with_capacity(capacity),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// 查找缓存结果
+    pub fn get(&mut self, key: u64) -> Option<Vec<QueryResult>> {
+        self.tick += 1;
+        match self.entries.get_mut(&key) {
+            Some(entry) => {
+                entry.last_used = self.tick;
+                self.hits += 1;
+                Some(entry.results.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// 写入缓存，超出容量时淘汰最久未使用的条目
+    pub fn put(&mut self, key: u64, results: Vec<QueryResult>) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            if let Some(&oldest) = self.entries.iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key)
+            {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, CacheEntry { results, last_used: self.tick });
+    }
+
+    /// 清空缓存（索引变更时调用）
+    pub fn invalidate(&mut self) {
+        self.entries.clear();
+    }
+
+    /// 获取统计信息
+    pub fn stats(&self) -> ResultCacheStats {
+        ResultCacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            len: self.entries.len(),
+            capacity: self.capacity,
+        }
+    }
+}
+
+/// 计算查询指纹
+///
+/// 包含量化查询字节、查询修正项、k、搜索参数以及索引的代号；
+/// 启用重排时结果依赖原始查询，因此同时计入原始查询向量
+pub fn query_fingerprint(context: &QueryContext, k: usize, params: &SearchParams, generation: u64) -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    generation.hash(&mut hasher);
+
+    context.quantized_query.hash(&mut hasher);
+    context.query_bits.hash(&mut hasher);
+    context.query_corrections.lower_interval.to_bits().hash(&mut hasher);
+    context.query_corrections.upper_interval.to_bits().hash(&mut hasher);
+    context.query_corrections.additional_correction.to_bits().hash(&mut hasher);
+    context.query_corrections.quantized_component_sum.to_bits().hash(&mut hasher);
+    context.centroid_dp.to_bits().hash(&mut hasher);
+    k.hash(&mut hasher);
+
+    match params.normalization {
+        ScoreNormalization::None => 0u8.hash(&mut hasher),
+        ScoreNormalization::Softmax { temperature } => {
+            1u8.hash(&mut hasher);
+            temperature.to_bits().hash(&mut hasher);
+        }
+        ScoreNormalization::MinMax => 2u8.hash(&mut hasher),
+        ScoreNormalization::ZScore => 3u8.hash(&mut hasher),
+    }
+
+    match params.rescore_oversample {
+        RescoreOversample::Disabled => 0u8.hash(&mut hasher),
+        RescoreOversample::Fixed(factor) => {
+            1u8.hash(&mut hasher);
+            factor.to_bits().hash(&mut hasher);
+        }
+        RescoreOversample::Adaptive => 2u8.hash(&mut hasher),
+    }
+    params.quality_weight.to_bits().hash(&mut hasher);
+    params.include_distances.hash(&mut hasher);
+    if let Some(weights) = &context.dimension_weights {
+        for weight in weights {
+            weight.to_bits().hash(&mut hasher);
+        }
+    }
+    #[cfg(feature = "ivf")]
+    match params.nprobe {
+        None => 0u8.hash(&mut hasher),
+        Some(ProbeStrategy::Fixed(nprobe)) => {
+            1u8.hash(&mut hasher);
+            nprobe.hash(&mut hasher);
+        }
+        Some(ProbeStrategy::Adaptive { min, max, margin }) => {
+            2u8.hash(&mut hasher);
+            min.hash(&mut hasher);
+            max.hash(&mut hasher);
+            margin.to_bits().hash(&mut hasher);
+        }
+    }
+    if params.rescore_oversample != RescoreOversample::Disabled {
+        for value in &context.query_vector {
+            value.to_bits().hash(&mut hasher);
+        }
+    }
+
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results(index: usize) -> Vec<QueryResult> {
+        vec![QueryResult { index, score: 1.0, original_score: None, distances: None }]
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut cache = ResultCache::new(2);
+        cache.put(1, results(1));
+        cache.put(2, results(2));
+        // 访问1后，2成为最久未使用
+        assert!(cache.get(1).is_some());
+        cache.put(3, results(3));
+
+        assert!(cache.get(2).is_none());
+        assert_eq!(cache.get(1).unwrap()[0].index, 1);
+        assert_eq!(cache.get(3).unwrap()[0].index, 3);
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 3);
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.len, 2);
+    }
+
+    #[test]
+    fn test_invalidate_and_zero_capacity() {
+        let mut cache = ResultCache::new(4);
+        cache.put(1, results(1));
+        cache.invalidate();
+        assert!(cache.get(1).is_none());
+
+        let mut disabled = ResultCache::new(0);
+        disabled.put(1, results(1));
+        assert_eq!(disabled.stats().len, 0);
+    }
+}
@@ -0,0 +1,261 @@
+//! 查询结果的紧凑二进制编码
+//!
+//! Worker中算出的结果编码为一个 `ArrayBuffer` 后可以直接转移给主线程，
+//! 不必对大量小对象做结构化克隆。
+//!
+//! 布局（小端）：12字节头部（魔数、版本、标志、2字节保留、结果数量u32）之后按列存放，
+//! 每列都从4字节对齐的位置开始，JS可以不经解码直接用 `Uint32Array` / `Float32Array` 读取：
+//! 序号u32列、分数f32列；有原始分数时追加原始分数列；有距离时追加量化分数、位点积（i32）、
+//! 汉明距离、精确分数四列；有任一可选列时最后是每个结果一个字节的存在标记，
+//! 标记缺失的值在列中写为0。版本1没有汉明距离的存在标记，有距离时总有汉明距离
+
+use crate::error_codes::{BbqError, Subject};
+use crate::byte_reader::{write_f32, ByteReader};
+use crate::memory_limits::checked_region_len;
+use crate::quantized_index::{HitDistances, QueryResult};
+
+/// 结果编码魔数
+const RESULTS_MAGIC: &[u8; 4] = b"BBQK";
+
+/// 结果编码格式版本
+const RESULTS_FORMAT_VERSION: u8 = 2;
+
+/// 头部字节数
+const HEADER_BYTES: usize = 12;
+
+/// 标志：包含原始分数列
+const HAS_ORIGINAL_SCORES: u8 = 1;
+/// 标志：包含距离列
+const HAS_DISTANCES: u8 = 2;
+
+/// 存在标记：该结果有原始分数
+const PRESENT_ORIGINAL_SCORE: u8 = 1;
+/// 存在标记：该结果有距离
+const PRESENT_DISTANCES: u8 = 2;
+/// 存在标记：该结果的距离中有精确分数
+const PRESENT_EXACT_SCORE: u8 = 4;
+/// 存在标记：该结果的距离中有汉明距离（版本2起）
+const PRESENT_HAMMING_DISTANCE: u8 = 8;
+
+/// 把查询结果编码为紧凑的二进制格式
+///
+/// 序号超过u32范围时返回错误
+pub fn encode_query_results(results: &[QueryResult]) -> Result<Vec<u8>, BbqError> {
+    let mut flags = 0;
+    if results.iter().any(|result| result.original_score.is_some()) {
+        flags |= HAS_ORIGINAL_SCORES;
+    }
+    if results.iter().any(|result| result.distances.is_some()) {
+        flags |= HAS_DISTANCES;
+    }
+    let count = u32::try_from(results.len()).map_err(|_| BbqError::out_of_range(
+        format!("结果数量 {} 超出u32范围", results.len()),
+        format!("result count {} exceeds the u32 range", results.len()),
+    ))?;
+
+    let mut bytes = Vec::with_capacity(HEADER_BYTES + results.len() * 29);
+    bytes.extend_from_slice(RESULTS_MAGIC);
+    bytes.push(RESULTS_FORMAT_VERSION);
+    bytes.push(flags);
+    bytes.extend_from_slice(&[0, 0]);
+    bytes.extend_from_slice(&count.to_le_bytes());
+
+    for result in results {
+        let index = u32::try_from(result.index).map_err(|_| BbqError::out_of_range(
+            format!("序号 {} 超出u32范围", result.index),
+            format!("ordinal {} exceeds the u32 range", result.index),
+        ))?;
+        bytes.extend_from_slice(&index.to_le_bytes());
+    }
+    for result in results {
+        write_f32(&mut bytes, result.score);
+    }
+    if flags & HAS_ORIGINAL_SCORES != 0 {
+        for result in results {
+            write_f32(&mut bytes, result.original_score.unwrap_or(0.0));
+        }
+    }
+    if flags & HAS_DISTANCES != 0 {
+        let distances = || results.iter().map(|result| result.distances.as_ref());
+        for hit in distances() {
+            write_f32(&mut bytes, hit.map_or(0.0, |hit| hit.quantized_score));
+        }
+        for hit in distances() {
+            bytes.extend_from_slice(&hit.map_or(0, |hit| hit.bit_dot_product).to_le_bytes());
+        }
+        for hit in distances() {
+            bytes.extend_from_slice(&hit.and_then(|hit| hit.hamming_distance).unwrap_or(0).to_le_bytes());
+        }
+        for hit in distances() {
+            write_f32(&mut bytes, hit.and_then(|hit| hit.exact_score).unwrap_or(0.0));
+        }
+    }
+    if flags != 0 {
+        for result in results {
+            let mut present = 0;
+            if result.original_score.is_some() {
+                present |= PRESENT_ORIGINAL_SCORE;
+            }
+            if let Some(hit) = &result.distances {
+                present |= PRESENT_DISTANCES;
+                if hit.exact_score.is_some() {
+                    present |= PRESENT_EXACT_SCORE;
+                }
+                if hit.hamming_distance.is_some() {
+                    present |= PRESENT_HAMMING_DISTANCE;
+                }
+            }
+            bytes.push(present);
+        }
+    }
+    Ok(bytes)
+}
+
+/// 解码 `encode_query_results` 产生的字节
+pub fn decode_query_results(bytes: &[u8]) -> Result<Vec<QueryResult>, BbqError> {
+    let mut reader = ByteReader::new(bytes);
+    if reader.take(4)? != RESULTS_MAGIC {
+        return Err(BbqError::corrupt_data("无效的结果编码：魔数不匹配", "invalid result encoding: magic mismatch"));
+    }
+    let format_version = reader.read_u8()?;
+    if !(1..=RESULTS_FORMAT_VERSION).contains(&format_version) {
+        return Err(BbqError::unsupported_version(
+            format!("不支持的结果编码版本: {}", format_version),
+            format!("unsupported result encoding version: {}", format_version),
+        ));
+    }
+    let flags = reader.read_u8()?;
+    if flags & !(HAS_ORIGINAL_SCORES | HAS_DISTANCES) != 0 || reader.take(2)? != [0, 0] {
+        return Err(BbqError::corrupt_data(format!("无效的结果编码：未知的标志 {:#04x}", flags), format!("invalid result encoding: unknown flags {:#04x}", flags)));
+    }
+    let count = reader.read_u32()? as usize;
+
+    let mut columns = 2;
+    if flags & HAS_ORIGINAL_SCORES != 0 {
+        columns += 1;
+    }
+    if flags & HAS_DISTANCES != 0 {
+        columns += 4;
+    }
+    let stride = columns * 4 + usize::from(flags != 0);
+    if checked_region_len(count, stride, Subject::new("结果编码", "result encoding"))? != reader.remaining() {
+        return Err(BbqError::corrupt_data("无效的结果编码：长度与结果数量不一致", "invalid result encoding: length does not match the result count"));
+    }
+
+    let read_column = |reader: &mut ByteReader| -> Result<Vec<u32>, BbqError> {
+        (0..count).map(|_| reader.read_u32()).collect()
+    };
+    let indices = read_column(&mut reader)?;
+    let scores = read_column(&mut reader)?;
+    let original_scores = if flags & HAS_ORIGINAL_SCORES != 0 { read_column(&mut reader)? } else { Vec::new() };
+    let distances = if flags & HAS_DISTANCES != 0 {
+        (0..4).map(|_| read_column(&mut reader)).collect::<Result<Vec<_>, _>>()?
+    } else {
+        Vec::new()
+    };
+    let present = if flags != 0 { reader.take(count)? } else { &[] };
+
+    let distance_marks = if format_version == 1 {
+        PRESENT_DISTANCES | PRESENT_EXACT_SCORE
+    } else {
+        PRESENT_DISTANCES | PRESENT_EXACT_SCORE | PRESENT_HAMMING_DISTANCE
+    };
+    let allowed = if flags & HAS_ORIGINAL_SCORES != 0 { PRESENT_ORIGINAL_SCORE } else { 0 }
+        | if flags & HAS_DISTANCES != 0 { distance_marks } else { 0 };
+    let mut results = Vec::with_capacity(count);
+    for i in 0..count {
+        let mut marks = present.get(i).copied().unwrap_or(0);
+        if marks & !allowed != 0
+            || (marks & (PRESENT_EXACT_SCORE | PRESENT_HAMMING_DISTANCE) != 0 && marks & PRESENT_DISTANCES == 0)
+        {
+            return Err(BbqError::corrupt_data(
+                format!("无效的结果编码：结果 {} 的存在标记 {:#04x} 无效", i, marks),
+                format!("invalid result encoding: presence marks {1:#04x} of result {0} are invalid", i, marks),
+            ));
+        }
+        if format_version == 1 && marks & PRESENT_DISTANCES != 0 {
+            marks |= PRESENT_HAMMING_DISTANCE;
+        }
+        results.push(QueryResult {
+            index: indices[i] as usize,
+            score: f32::from_bits(scores[i]),
+            original_score: (marks & PRESENT_ORIGINAL_SCORE != 0).then(|| f32::from_bits(original_scores[i])),
+            distances: (marks & PRESENT_DISTANCES != 0).then(|| HitDistances {
+                quantized_score: f32::from_bits(distances[0][i]),
+                bit_dot_product: distances[1][i] as i32,
+                hamming_distance: (marks & PRESENT_HAMMING_DISTANCE != 0).then(|| distances[2][i]),
+                exact_score: (marks & PRESENT_EXACT_SCORE != 0).then(|| f32::from_bits(distances[3][i])),
+            }),
+        });
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(index: usize, score: f32) -> QueryResult {
+        QueryResult { index, score, original_score: None, distances: None }
+    }
+
+    fn assert_same(decoded: &[QueryResult], expected: &[QueryResult]) {
+        assert_eq!(decoded.len(), expected.len());
+        for (decoded, expected) in decoded.iter().zip(expected) {
+            assert_eq!((decoded.index, decoded.score, decoded.original_score), (expected.index, expected.score, expected.original_score));
+            assert_eq!(decoded.distances, expected.distances);
+        }
+    }
+
+    #[test]
+    fn test_plain_results_round_trip() {
+        let results = vec![result(7, 0.9), result(3, 0.5), result(1 << 20, -0.25)];
+        let bytes = encode_query_results(&results).unwrap();
+        // 只有头部和两列
+        assert_eq!(bytes.len(), HEADER_BYTES + 3 * 8);
+        assert_eq!(&bytes[12..16], &7u32.to_le_bytes());
+        assert_eq!(&bytes[24..28], &0.9f32.to_le_bytes());
+        assert_same(&decode_query_results(&bytes).unwrap(), &results);
+        assert!(decode_query_results(&encode_query_results(&[]).unwrap()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_optional_fields_round_trip() {
+        let mut rescored = result(2, 0.8);
+        rescored.original_score = Some(0.75);
+        rescored.distances = Some(HitDistances { quantized_score: 0.7, bit_dot_product: -12, hamming_distance: Some(40), exact_score: Some(0.81) });
+        let mut approximate = result(5, 0.6);
+        approximate.distances = Some(HitDistances { quantized_score: 0.6, bit_dot_product: 30, hamming_distance: None, exact_score: None });
+        let results = vec![rescored, approximate, result(9, 0.1)];
+
+        let bytes = encode_query_results(&results).unwrap();
+        assert_eq!(bytes.len(), HEADER_BYTES + 3 * (7 * 4 + 1));
+        assert_same(&decode_query_results(&bytes).unwrap(), &results);
+
+        // 版本1有距离时总有汉明距离
+        let mut version_one = bytes.clone();
+        version_one[4] = 1;
+        let present = version_one.len() - 3;
+        version_one[present] &= !PRESENT_HAMMING_DISTANCE;
+        let decoded = decode_query_results(&version_one).unwrap();
+        assert_eq!(decoded[0].distances.as_ref().unwrap().hamming_distance, Some(40));
+        assert_eq!(decoded[1].distances.as_ref().unwrap().hamming_distance, Some(0));
+        version_one[present] |= PRESENT_HAMMING_DISTANCE;
+        assert!(decode_query_results(&version_one).is_err());
+    }
+
+    #[test]
+    fn test_rejects_malformed_bytes() {
+        let bytes = encode_query_results(&[result(1, 0.5)]).unwrap();
+        assert!(decode_query_results(&bytes[..bytes.len() - 1]).is_err());
+        let mut extra = bytes.clone();
+        extra.push(0);
+        assert!(decode_query_results(&extra).is_err());
+        let mut flags = bytes.clone();
+        flags[5] = 0x80;
+        assert!(decode_query_results(&flags).is_err());
+        let mut magic = bytes;
+        magic[0] = b'X';
+        assert!(decode_query_results(&magic).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+//! 随机数来源
+//!
+//! 随机向量生成、k-means初始中心、真值抽样和搜索实验抽样都通过 `RngSource` 取随机数，
+//! 宿主可以传入自己的来源（加密安全的随机数、可复现的序列、JS中的 `crypto.getRandomValues`）。
+//! 只给种子的接口仍然使用以该种子初始化的 `fastrand::Rng`，结果与之前相同。
+//!
+//! 不接受随机源参数的 `create_random_vector` 使用当前线程的默认来源：
+//! 通过 `set_default_rng` 安装的来源，未安装时为线程各自的 `fastrand::Rng`。
+//! 默认来源按线程安装（WASM中只有一个线程），来源本身不需要 `Send`
+
+use std::cell::RefCell;
+use std::ops::Range;
+
+/// 随机数来源
+pub trait RngSource {
+    /// 下一个均匀分布的64位随机数
+    fn next_u64(&mut self) -> u64;
+
+    /// [0, 1) 内均匀分布的f32
+    fn f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 * (1.0 / (1u64 << 24) as f32)
+    }
+
+    /// [0, 1) 内均匀分布的f64
+    fn f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// 范围内均匀分布的整数（拒绝采样，无取模偏差）；范围为空时panic
+    fn usize(&mut self, range: Range<usize>) -> usize {
+        assert!(range.start < range.end, "空的随机数范围 {:?}", range);
+        let span = (range.end - range.start) as u64;
+        // 拒绝落在最后一个不完整区间中的值
+        let zone = u64::MAX - (u64::MAX - span + 1) % span;
+        loop {
+            let value = self.next_u64();
+            if value <= zone {
+                return range.start + (value % span) as usize;
+            }
+        }
+    }
+}
+
+/// fastrand的实现保持原有的取值方式，以种子初始化时序列与之前完全相同
+impl RngSource for fastrand::Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.u64(..)
+    }
+
+    fn f32(&mut self) -> f32 {
+        fastrand::Rng::f32(self)
+    }
+
+    fn f64(&mut self) -> f64 {
+        fastrand::Rng::f64(self)
+    }
+
+    fn usize(&mut self, range: Range<usize>) -> usize {
+        fastrand::Rng::usize(self, range)
+    }
+}
+
+impl<R: RngSource + ?Sized> RngSource for Box<R> {
+    fn next_u64(&mut self) -> u64 {
+        (**self).next_u64()
+    }
+
+    fn f32(&mut self) -> f32 {
+        (**self).f32()
+    }
+
+    fn f64(&mut self) -> f64 {
+        (**self).f64()
+    }
+
+    fn usize(&mut self, range: Range<usize>) -> usize {
+        (**self).usize(range)
+    }
+}
+
+/// 由闭包提供64位随机数的来源
+pub struct FnRng<F: FnMut() -> u64>(pub F);
+
+impl<F: FnMut() -> u64> RngSource for FnRng<F> {
+    fn next_u64(&mut self) -> u64 {
+        (self.0)()
+    }
+}
+
+thread_local! {
+    /// 当前线程安装的默认来源
+    static DEFAULT_RNG: RefCell<Option<Box<dyn RngSource>>> = const { RefCell::new(None) };
+    /// 未安装来源时使用的fastrand
+    static THREAD_RNG: RefCell<fastrand::Rng> = RefCell::new(fastrand::Rng::new());
+}
+
+/// 为当前线程安装或移除默认来源
+pub fn set_default_rng(source: Option<Box<dyn RngSource>>) {
+    DEFAULT_RNG.with(|installed| *installed.borrow_mut() = source);
+}
+
+/// 当前线程是否安装了默认来源
+pub fn has_default_rng() -> bool {
+    DEFAULT_RNG.with(|installed| installed.borrow().is_some())
+}
+
+/// 使用当前线程的默认来源执行 `f`
+///
+/// 来源在 `f` 执行期间被借出，`f` 内不能再次调用本函数
+pub fn with_default_rng<T>(f: impl FnOnce(&mut dyn RngSource) -> T) -> T {
+    DEFAULT_RNG.with(|installed| match installed.borrow_mut().as_mut() {
+        Some(source) => f(source.as_mut()),
+        None => THREAD_RNG.with(|rng| f(&mut *rng.borrow_mut())),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 线性同余序列，只用于测试
+    fn counter() -> FnRng<impl FnMut() -> u64> {
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        FnRng(move || {
+            state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            state
+        })
+    }
+
+    #[test]
+    fn test_default_methods_stay_in_range() {
+        let mut rng = counter();
+        let mut hits = [0usize; 3];
+        for _ in 0..3000 {
+            let x = RngSource::f32(&mut rng);
+            let y = RngSource::f64(&mut rng);
+            assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y));
+            hits[RngSource::usize(&mut rng, 5..8) - 5] += 1;
+        }
+        assert!(hits.iter().all(|&count| (800..1200).contains(&count)), "{:?}", hits);
+        assert_eq!(RngSource::usize(&mut rng, 4..5), 4);
+    }
+
+    #[test]
+    fn test_fastrand_source_matches_fastrand() {
+        let mut expected = fastrand::Rng::with_seed(11);
+        let mut source: Box<dyn RngSource> = Box::new(fastrand::Rng::with_seed(11));
+        assert_eq!(source.f32(), expected.f32());
+        assert_eq!(source.usize(0..10), expected.usize(0..10));
+        assert_eq!(source.next_u64(), expected.u64(..));
+    }
+
+    #[test]
+    fn test_installed_default_source() {
+        set_default_rng(Some(Box::new(FnRng(|| 0))));
+        assert!(has_default_rng());
+        assert_eq!(with_default_rng(|rng| (rng.f32(), rng.usize(3..9))), (0.0, 3));
+        set_default_rng(None);
+        assert!(!has_default_rng());
+        assert!(with_default_rng(|rng| rng.f32()) < 1.0);
+    }
+}
@@ -0,0 +1,47 @@
+//! 运行时的延迟初始化
+//!
+//! 模块实例化（`WebAssembly.instantiateStreaming`）时只执行 `#[wasm_bindgen(start)]`，
+//! 其中只设置panic hook，不做任何耗时操作，页面可以尽早可交互。
+//! 较重的初始化（目前是内核校准）默认推迟到第一次批量评分时执行；
+//! 确定会搜索的页面也可以在空闲时显式调用 `initialize_runtime` 提前完成
+
+use crate::kernel_dispatch::{kernels_selected, selected_kernels, KernelSelection};
+use crate::timer::{elapsed_ms, now_ms};
+
+/// 初始化结果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RuntimeInitReport {
+    /// 选中的内核
+    pub kernels: KernelSelection,
+    /// 本次调用实际完成了初始化（之前已初始化时为false）
+    pub initialized_now: bool,
+    /// 本次调用的耗时（毫秒）
+    pub elapsed_ms: f64,
+}
+
+/// 运行时是否已经完成延迟初始化
+pub fn is_runtime_initialized() -> bool {
+    kernels_selected()
+}
+
+/// 立即完成延迟初始化；已初始化时直接返回
+pub fn initialize_runtime() -> RuntimeInitReport {
+    let start = now_ms();
+    let initialized_now = !is_runtime_initialized();
+    let kernels = selected_kernels();
+    RuntimeInitReport { kernels, initialized_now, elapsed_ms: elapsed_ms(start) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_initialize_runtime_is_idempotent() {
+        initialize_runtime();
+        assert!(is_runtime_initialized());
+        let again = initialize_runtime();
+        assert!(!again.initialized_now);
+        assert_eq!(again.kernels, selected_kernels());
+    }
+}
@@ -0,0 +1,220 @@
+//! 混合检索分数融合
+//!
+//! 将向量搜索结果与外部（如BM25词法检索）分数合并，
+//! 支持加权求和与倒数排名融合（RRF）
+
+use std::collections::HashMap;
+
+use crate::error_codes::BbqError;
+use crate::quantized_index::QueryResult;
+use crate::score_normalization::min_max_values;
+use crate::vector_similarity::descending_score_order;
+
+/// RRF默认平滑常数
+pub const DEFAULT_RRF_K: f32 = 60.0;
+
+/// 融合策略
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FusionStrategy {
+    /// 两路分数各自min-max归一化后加权求和
+    WeightedSum { vector_weight: f32, external_weight: f32 },
+    /// 倒数排名融合：score = Σ 1 / (k + rank)，rank从1开始
+    ReciprocalRank { k: f32 },
+}
+
+impl FusionStrategy {
+    /// 根据名称解析融合策略
+    ///
+    /// # 参数
+    /// * `name` - "weighted_sum" | "rrf"
+    /// * `param` - weighted_sum时为向量分数权重alpha（外部权重为1-alpha，默认0.5），
+    ///   rrf时为平滑常数k（默认60）
+    pub fn from_name(name: &str, param: Option<f32>) -> Result<Self, BbqError> {
+        match name.to_lowercase().as_str() {
+            "weighted_sum" | "weighted" => {
+                let alpha = param.unwrap_or(0.5);
+                if !(0.0..=1.0).contains(&alpha) {
+                    return Err(BbqError::out_of_range(
+                        format!("加权融合的alpha必须在0-1之间，当前为{}", alpha),
+                        format!("weighted fusion alpha must be within 0-1, got {}", alpha),
+                    ));
+                }
+                Ok(FusionStrategy::WeightedSum {
+                    vector_weight: alpha,
+                    external_weight: 1.0 - alpha,
+                })
+            }
+            "rrf" | "reciprocal_rank" => {
+                let k = param.unwrap_or(DEFAULT_RRF_K);
+                if !(k.is_finite() && k >= 0.0) {
+                    return Err(BbqError::invalid_argument(
+                        format!("RRF常数k必须为非负数，当前为{}", k),
+                        format!("RRF constant k must be non-negative, got {}", k),
+                    ));
+                }
+                Ok(FusionStrategy::ReciprocalRank { k })
+            }
+            _ => Err(BbqError::invalid_argument(format!("不支持的融合策略: {}", name), format!("unsupported fusion strategy: {}", name))),
+        }
+    }
+}
+
+/// 融合向量搜索结果与外部分数
+///
+/// 只出现在一路中的文档在另一路贡献为0；外部分数中重复的文档只保留最高分；融合结果按分数降序排列，
+/// `original_score` 保存该文档的向量搜索分数（仅外部命中时为None）
+///
+/// # 参数
+/// * `results` - 向量搜索结果
+/// * `external` - 外部分数 (向量索引, 分数)
+/// * `strategy` - 融合策略
+///
+/// # 返回
+/// 融合后的结果
+pub fn fuse_with_external_scores(
+    results: &[QueryResult],
+    external: &[(usize, f32)],
+    strategy: FusionStrategy,
+) -> Vec<QueryResult> {
+    // 保持首次出现的顺序，保证同分时结果稳定
+    let mut order: Vec<usize> = Vec::with_capacity(results.len() + external.len());
+    let mut fused: HashMap<usize, (f32, Option<f32>)> = HashMap::new();
+
+    let external = dedupe_max(external);
+    let vector_scores: Vec<f32> = results.iter().map(|r| r.score).collect();
+    let external_scores: Vec<f32> = external.iter().map(|&(_, s)| s).collect();
+
+    let (vector_contrib, external_contrib): (Vec<f32>, Vec<f32>) = match strategy {
+        FusionStrategy::WeightedSum { vector_weight, external_weight } => (
+            min_max_values(&vector_scores)
+                .into_iter().map(|s| s * vector_weight).collect(),
+            min_max_values(&external_scores)
+                .into_iter().map(|s| s * external_weight).collect(),
+        ),
+        FusionStrategy::ReciprocalRank { k } => (
+            reciprocal_ranks(&vector_scores, k),
+            reciprocal_ranks(&external_scores, k),
+        ),
+    };
+
+    for (result, contrib) in results.iter().zip(vector_contrib) {
+        let entry = fused.entry(result.index).or_insert_with(|| {
+            order.push(result.index);
+            (0.0, None)
+        });
+        entry.0 += contrib;
+        entry.1 = Some(result.original_score.unwrap_or(result.score));
+    }
+
+    for (&(index, _), contrib) in external.iter().zip(external_contrib) {
+        let entry = fused.entry(index).or_insert_with(|| {
+            order.push(index);
+            (0.0, None)
+        });
+        entry.0 += contrib;
+    }
+
+    let mut fused_results: Vec<QueryResult> = order.into_iter()
+        .map(|index| {
+            let (score, original_score) = fused[&index];
+            QueryResult { index, score, original_score, distances: None }
+        })
+        .collect();
+
+    fused_results.sort_by(|a, b| descending_score_order(a.score, b.score));
+    fused_results
+}
+
+/// 去掉重复的文档，保留首次出现的位置和最高分
+fn dedupe_max(hits: &[(usize, f32)]) -> Vec<(usize, f32)> {
+    let mut position: HashMap<usize, usize> = HashMap::with_capacity(hits.len());
+    let mut deduped: Vec<(usize, f32)> = Vec::with_capacity(hits.len());
+    for &(index, score) in hits {
+        match position.get(&index) {
+            Some(&i) => deduped[i].1 = deduped[i].1.max(score),
+            None => {
+                position.insert(index, deduped.len());
+                deduped.push((index, score));
+            }
+        }
+    }
+    deduped
+}
+
+/// 按分数降序排名后计算 1 / (k + rank)
+fn reciprocal_ranks(scores: &[f32], k: f32) -> Vec<f32> {
+    let mut ranked: Vec<usize> = (0..scores.len()).collect();
+    ranked.sort_by(|&a, &b| descending_score_order(scores[a], scores[b]));
+
+    let mut contrib = vec![0.0; scores.len()];
+    for (rank, &i) in ranked.iter().enumerate() {
+        contrib[i] = 1.0 / (k + (rank + 1) as f32);
+    }
+    contrib
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results(hits: &[(usize, f32)]) -> Vec<QueryResult> {
+        hits.iter()
+            .map(|&(index, score)| QueryResult { index, score, original_score: None, distances: None })
+            .collect()
+    }
+
+    #[test]
+    fn test_reciprocal_rank_fusion() {
+        let vector = results(&[(1, 0.9), (2, 0.8), (3, 0.7)]);
+        let external = [(3, 12.0), (4, 8.0)];
+        let fused = fuse_with_external_scores(&vector, &external, FusionStrategy::ReciprocalRank { k: 60.0 });
+
+        assert_eq!(fused.len(), 4);
+        // 文档3在两路都命中，排名第一
+        assert_eq!(fused[0].index, 3);
+        assert!((fused[0].score - (1.0 / 63.0 + 1.0 / 61.0)).abs() < 1e-6);
+        assert_eq!(fused[0].original_score, Some(0.7));
+        let doc4 = fused.iter().find(|r| r.index == 4).unwrap();
+        assert_eq!(doc4.original_score, None);
+    }
+
+    #[test]
+    fn test_weighted_sum_fusion() {
+        let vector = results(&[(1, 0.9), (2, 0.5)]);
+        let external = [(2, 20.0), (1, 10.0)];
+        let strategy = FusionStrategy::WeightedSum { vector_weight: 0.3, external_weight: 0.7 };
+        let fused = fuse_with_external_scores(&vector, &external, strategy);
+
+        assert_eq!(fused[0].index, 2);
+        assert!((fused[0].score - 0.7).abs() < 1e-6);
+        assert!((fused[1].score - 0.3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_duplicate_external_ids_keep_max() {
+        let vector = results(&[(1, 0.9), (2, 0.8)]);
+        let external = [(2, 5.0), (3, 4.0), (2, 9.0)];
+        let rrf = fuse_with_external_scores(&vector, &external, FusionStrategy::ReciprocalRank { k: 60.0 });
+        assert_eq!(rrf.len(), 3);
+        // 文档2的外部分数取9.0，排名第一，只计一次
+        let doc2 = rrf.iter().find(|r| r.index == 2).unwrap();
+        assert!((doc2.score - (1.0 / 62.0 + 1.0 / 61.0)).abs() < 1e-6);
+        let doc3 = rrf.iter().find(|r| r.index == 3).unwrap();
+        assert!((doc3.score - 1.0 / 62.0).abs() < 1e-6);
+
+        let strategy = FusionStrategy::WeightedSum { vector_weight: 0.5, external_weight: 0.5 };
+        let weighted = fuse_with_external_scores(&vector, &external, strategy);
+        let doc2 = weighted.iter().find(|r| r.index == 2).unwrap();
+        assert!((doc2.score - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(
+            FusionStrategy::from_name("rrf", None).unwrap(),
+            FusionStrategy::ReciprocalRank { k: DEFAULT_RRF_K }
+        );
+        assert!(FusionStrategy::from_name("weighted_sum", Some(1.5)).is_err());
+        assert!(FusionStrategy::from_name("max", None).is_err());
+    }
+}
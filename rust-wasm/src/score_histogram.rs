@@ -0,0 +1,172 @@
+//! 分数直方图
+//!
+//! 搜索时对全部候选的量化分数分桶计数，界面可以据此展示相关度分布，
+//! 或在不把每个分数传给JS的情况下选择自适应的阈值
+
+use crate::error_codes::BbqError;
+
+/// 等宽分桶的分数直方图
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreHistogram {
+    /// 第一个桶的下界
+    pub min: f32,
+    /// 最后一个桶的上界
+    pub max: f32,
+    /// 每个桶内的分数数量
+    pub counts: Vec<u32>,
+    /// 小于min的分数数量
+    pub below: u32,
+    /// 大于max的分数数量
+    pub above: u32,
+}
+
+impl ScoreHistogram {
+    /// 在 [min, max] 上创建空直方图
+    ///
+    /// # 参数
+    /// * `buckets` - 桶数量
+    /// * `min` - 下界
+    /// * `max` - 上界，必须大于min
+    pub fn new(buckets: usize, min: f32, max: f32) -> Result<Self, BbqError> {
+        if buckets == 0 {
+            return Err(BbqError::invalid_argument("直方图桶数量必须大于0", "histogram bucket count must be greater than 0"));
+        }
+        if !(min.is_finite() && max.is_finite() && min < max) {
+            return Err(BbqError::invalid_argument(format!("无效的直方图范围: [{}, {}]", min, max), format!("invalid histogram range: [{}, {}]", min, max)));
+        }
+        Ok(Self { min, max, counts: vec![0; buckets], below: 0, above: 0 })
+    }
+
+    /// 对一组分数建立直方图
+    ///
+    /// 未给出范围时使用分数自身的最小值和最大值（全部相同或为空时取该值附近的单位区间），
+    /// 给出固定范围时不同查询的直方图可以直接比较
+    pub fn from_scores(scores: impl Iterator<Item = f32> + Clone, buckets: usize, range: Option<(f32, f32)>) -> Result<Self, BbqError> {
+        let (min, max) = match range {
+            Some(range) => range,
+            None => {
+                let (low, high) = scores.clone()
+                    .filter(|score| score.is_finite())
+                    .fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), score| (low.min(score), high.max(score)));
+                if low < high {
+                    (low, high)
+                } else if low.is_finite() {
+                    (low - 0.5, low + 0.5)
+                } else {
+                    (0.0, 1.0)
+                }
+            }
+        };
+        let mut histogram = Self::new(buckets, min, max)?;
+        for score in scores {
+            histogram.add(score);
+        }
+        Ok(histogram)
+    }
+
+    /// 计入一个分数；等于max的分数落入最后一个桶，NaN不计入
+    pub fn add(&mut self, score: f32) {
+        if score.is_nan() {
+            return;
+        }
+        if score < self.min {
+            self.below += 1;
+        } else if score > self.max {
+            self.above += 1;
+        } else {
+            let bucket = ((score - self.min) / self.bucket_width()) as usize;
+            let last = self.counts.len() - 1;
+            self.counts[bucket.min(last)] += 1;
+        }
+    }
+
+    /// 桶宽度
+    pub fn bucket_width(&self) -> f32 {
+        (self.max - self.min) / self.counts.len() as f32
+    }
+
+    /// 第i个桶的下界
+    pub fn bucket_start(&self, bucket: usize) -> f32 {
+        self.min + bucket as f32 * self.bucket_width()
+    }
+
+    /// 计入的分数总数（含范围外的分数）
+    pub fn total(&self) -> u64 {
+        self.counts.iter().map(|&count| count as u64).sum::<u64>() + self.below as u64 + self.above as u64
+    }
+
+    /// 估计不低于threshold的分数数量（桶内按均匀分布插值）
+    pub fn count_at_least(&self, threshold: f32) -> f64 {
+        if threshold <= self.min {
+            return (self.total() - self.below as u64) as f64;
+        }
+        if threshold > self.max {
+            return self.above as f64;
+        }
+        let width = self.bucket_width();
+        let mut count = self.above as f64;
+        for (bucket, &bucket_count) in self.counts.iter().enumerate() {
+            let start = self.bucket_start(bucket);
+            let covered = ((start + width - threshold) / width).clamp(0.0, 1.0);
+            count += bucket_count as f64 * covered as f64;
+        }
+        count
+    }
+
+    /// 估计使约count个分数不低于它的阈值（桶内按均匀分布插值）
+    ///
+    /// 可用于自适应阈值：例如只保留分布最高的一部分结果。
+    /// count超过范围内的分数总数时返回min，落在max以上的分数已经够count个时返回max
+    pub fn threshold_for_count(&self, count: u64) -> f32 {
+        let mut remaining = count as f64 - self.above as f64;
+        if remaining <= 0.0 {
+            return self.max;
+        }
+        let width = self.bucket_width();
+        for (bucket, &bucket_count) in self.counts.iter().enumerate().rev() {
+            let bucket_count = bucket_count as f64;
+            if bucket_count >= remaining {
+                let fraction = (remaining / bucket_count) as f32;
+                return self.bucket_start(bucket) + width * (1.0 - fraction);
+            }
+            remaining -= bucket_count;
+        }
+        self.min
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_range_buckets() {
+        let scores = [0.05f32, 0.15, 0.15, 0.95, 1.0, -0.1, 1.5, f32::NAN];
+        let histogram = ScoreHistogram::from_scores(scores.iter().copied(), 10, Some((0.0, 1.0))).unwrap();
+        assert_eq!(histogram.counts, vec![1, 2, 0, 0, 0, 0, 0, 0, 0, 2]);
+        assert_eq!((histogram.below, histogram.above), (1, 1));
+        assert_eq!(histogram.total(), 7);
+
+        assert!((histogram.count_at_least(0.9) - 3.0).abs() < 1e-5);
+        assert!((histogram.count_at_least(0.15) - 4.0).abs() < 1e-5);
+        assert_eq!(histogram.count_at_least(-1.0), 6.0);
+        assert!((histogram.threshold_for_count(3) - 0.9).abs() < 1e-5);
+        assert_eq!(histogram.threshold_for_count(1), 1.0);
+        assert_eq!(histogram.threshold_for_count(100), 0.0);
+
+        assert!(ScoreHistogram::new(0, 0.0, 1.0).is_err());
+        assert!(ScoreHistogram::new(4, 1.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_observed_range() {
+        let histogram = ScoreHistogram::from_scores([2.0f32, 4.0, 3.0].into_iter(), 2, None).unwrap();
+        assert_eq!((histogram.min, histogram.max), (2.0, 4.0));
+        assert_eq!(histogram.counts, vec![1, 2]);
+
+        let constant = ScoreHistogram::from_scores([0.7f32; 3].into_iter(), 4, None).unwrap();
+        assert_eq!(constant.total(), 3);
+        let empty = ScoreHistogram::from_scores(std::iter::empty(), 4, None).unwrap();
+        assert_eq!((empty.min, empty.max, empty.total()), (0.0, 1.0, 0));
+    }
+}
@@ -0,0 +1,182 @@
+//! Top-K分数归一化
+//!
+//! 对搜索返回的分数做后处理，便于与BM25等其他排序器的分数融合
+
+use crate::error_codes::BbqError;
+use crate::quantized_index::QueryResult;
+
+/// 分数归一化方式
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ScoreNormalization {
+    /// 不做归一化（默认）
+    #[default]
+    None,
+    /// softmax，temperature越小分布越尖锐
+    Softmax { temperature: f32 },
+    /// 线性缩放到[0, 1]
+    MinMax,
+    /// 减均值除以标准差
+    ZScore,
+}
+
+impl ScoreNormalization {
+    /// 根据名称解析归一化方式
+    ///
+    /// # 参数
+    /// * `name` - "none" | "softmax" | "min_max" | "z_score"
+    /// * `temperature` - softmax温度（默认1.0）
+    pub fn from_name(name: &str, temperature: Option<f32>) -> Result<Self, BbqError> {
+        match name.to_lowercase().as_str() {
+            "none" => Ok(ScoreNormalization::None),
+            "softmax" => {
+                let normalization = ScoreNormalization::Softmax { temperature: temperature.unwrap_or(1.0) };
+                normalization.validate()?;
+                Ok(normalization)
+            }
+            "min_max" | "minmax" => Ok(ScoreNormalization::MinMax),
+            "z_score" | "zscore" => Ok(ScoreNormalization::ZScore),
+            _ => Err(BbqError::invalid_argument(format!("不支持的分数归一化方式: {}", name), format!("unsupported score normalization: {}", name))),
+        }
+    }
+
+    /// 检查参数是否有效（softmax温度必须为有限正数）
+    pub fn validate(&self) -> Result<(), BbqError> {
+        if let ScoreNormalization::Softmax { temperature } = *self {
+            if !(temperature.is_finite() && temperature > 0.0) {
+                return Err(BbqError::invalid_argument(
+                    format!("softmax温度必须为正数，当前为{}", temperature),
+                    format!("softmax temperature must be positive, got {}", temperature),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 原地归一化结果分数
+///
+/// 归一化前的分数保存到 `original_score`，结果顺序保持不变
+///
+/// # 参数
+/// * `results` - 搜索结果（会被修改）
+/// * `normalization` - 归一化方式
+pub fn normalize_scores(results: &mut [QueryResult], normalization: ScoreNormalization) -> Result<(), BbqError> {
+    normalization.validate()?;
+    if results.is_empty() || normalization == ScoreNormalization::None {
+        return Ok(());
+    }
+
+    let scores: Vec<f32> = results.iter().map(|r| r.score).collect();
+    let normalized = normalize_values(&scores, normalization)?;
+
+    for (result, score) in results.iter_mut().zip(normalized) {
+        result.original_score = Some(result.score);
+        result.score = score;
+    }
+    Ok(())
+}
+
+/// 归一化一组分数
+///
+/// 参数无效时（如直接构造的softmax温度不是正数）返回错误
+pub fn normalize_values(scores: &[f32], normalization: ScoreNormalization) -> Result<Vec<f32>, BbqError> {
+    normalization.validate()?;
+    if scores.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    Ok(match normalization {
+        ScoreNormalization::None => scores.to_vec(),
+        ScoreNormalization::Softmax { temperature } => {
+            // 减去最大值保证exp不溢出
+            let max = scores.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+            let exps: Vec<f32> = scores.iter()
+                .map(|&s| ((s - max) / temperature).exp())
+                .collect();
+            let sum: f32 = exps.iter().sum();
+            exps.into_iter().map(|e| e / sum).collect()
+        }
+        ScoreNormalization::MinMax => min_max_values(scores),
+        ScoreNormalization::ZScore => {
+            let n = scores.len() as f32;
+            let mean = scores.iter().sum::<f32>() / n;
+            let variance = scores.iter().map(|&s| (s - mean) * (s - mean)).sum::<f32>() / n;
+            let std = variance.sqrt();
+            if std > 0.0 {
+                scores.iter().map(|&s| (s - mean) / std).collect()
+            } else {
+                vec![0.0; scores.len()]
+            }
+        }
+    })
+}
+
+/// 线性缩放到[0, 1]，分数全部相同时统一视为满分
+pub(crate) fn min_max_values(scores: &[f32]) -> Vec<f32> {
+    let min = scores.iter().cloned().fold(f32::INFINITY, f32::min);
+    let max = scores.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let range = max - min;
+    if range > 0.0 {
+        scores.iter().map(|&s| (s - min) / range).collect()
+    } else {
+        vec![1.0; scores.len()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results(scores: &[f32]) -> Vec<QueryResult> {
+        scores.iter()
+            .enumerate()
+            .map(|(index, &score)| QueryResult { index, score, original_score: None, distances: None })
+            .collect()
+    }
+
+    #[test]
+    fn test_min_max() {
+        let mut r = results(&[0.9, 0.7, 0.5]);
+        normalize_scores(&mut r, ScoreNormalization::MinMax).unwrap();
+        assert!((r[0].score - 1.0).abs() < 1e-6);
+        assert!((r[1].score - 0.5).abs() < 1e-6);
+        assert!((r[2].score - 0.0).abs() < 1e-6);
+        assert_eq!(r[0].original_score, Some(0.9));
+    }
+
+    #[test]
+    fn test_softmax_sums_to_one() {
+        let normalized = normalize_values(&[3.0, 2.0, 1.0], ScoreNormalization::Softmax { temperature: 0.5 }).unwrap();
+        let sum: f32 = normalized.iter().sum();
+        assert!((sum - 1.0).abs() < 1e-6);
+        assert!(normalized[0] > normalized[1] && normalized[1] > normalized[2]);
+    }
+
+    #[test]
+    fn test_z_score() {
+        let normalized = normalize_values(&[2.0, 4.0, 6.0], ScoreNormalization::ZScore).unwrap();
+        assert!((normalized.iter().sum::<f32>()).abs() < 1e-6);
+        assert!(normalized[0] < 0.0 && normalized[2] > 0.0);
+
+        let constant = normalize_values(&[1.0, 1.0], ScoreNormalization::ZScore).unwrap();
+        assert_eq!(constant, vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(ScoreNormalization::from_name("min_max", None).unwrap(), ScoreNormalization::MinMax);
+        assert!(ScoreNormalization::from_name("softmax", Some(0.0)).is_err());
+        assert!(ScoreNormalization::from_name("rank", None).is_err());
+    }
+
+    #[test]
+    fn test_invalid_softmax_temperature_rejected() {
+        for temperature in [0.0, -1.0, f32::NAN] {
+            let normalization = ScoreNormalization::Softmax { temperature };
+            assert!(normalize_values(&[3.0, 2.0], normalization).is_err());
+            let mut r = results(&[0.9, 0.7]);
+            assert!(normalize_scores(&mut r, normalization).is_err());
+            assert_eq!(r[0].score, 0.9);
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! 最终分数变换
+//!
+//! 把BBQ分数映射到应用自己的相关度刻度（例如0-100）。
+//! 曲线先把分数单调地映射到[0, 1]，再线性缩放到 `[floor, ceiling]`；
+//! 曲线单调不减，因此变换不会改变Top-K的顺序
+
+use crate::error_codes::BbqError;
+use crate::quantized_index::QueryResult;
+
+/// 单调不减的分数曲线，输出在[0, 1]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum ScoreCurve {
+    /// 原样使用分数，截断到[0, 1]（默认）
+    #[default]
+    Identity,
+    /// `1 / (1 + exp(-steepness * (score - midpoint)))`
+    Logistic { midpoint: f32, steepness: f32 },
+    /// 按控制点 (分数, 输出) 线性插值，两端之外取端点的输出
+    PiecewiseLinear(Vec<(f32, f32)>),
+}
+
+impl ScoreCurve {
+    /// 创建logistic曲线
+    ///
+    /// # 参数
+    /// * `midpoint` - 输出为0.5时的分数
+    /// * `steepness` - 陡峭程度，必须为正数
+    pub fn logistic(midpoint: f32, steepness: f32) -> Result<Self, BbqError> {
+        if !midpoint.is_finite() {
+            return Err(BbqError::invalid_argument(
+                format!("logistic中点必须为有限值，当前为{}", midpoint),
+                format!("logistic midpoint must be finite, got {}", midpoint),
+            ));
+        }
+        if !(steepness.is_finite() && steepness > 0.0) {
+            return Err(BbqError::invalid_argument(
+                format!("logistic陡峭程度必须为正数，当前为{}", steepness),
+                format!("logistic steepness must be positive, got {}", steepness),
+            ));
+        }
+        Ok(ScoreCurve::Logistic { midpoint, steepness })
+    }
+
+    /// 创建分段线性曲线
+    ///
+    /// # 参数
+    /// * `points` - 控制点 (分数, 输出)，分数严格递增，输出在[0, 1]内且单调不减
+    pub fn piecewise_linear(points: Vec<(f32, f32)>) -> Result<Self, BbqError> {
+        if points.is_empty() {
+            return Err(BbqError::empty_input("分段线性曲线至少需要一个控制点", "a piecewise linear curve needs at least one control point"));
+        }
+        if let Some(&(x, y)) = points.iter().find(|&&(x, y)| !x.is_finite() || !(0.0..=1.0).contains(&y)) {
+            return Err(BbqError::invalid_argument(
+                format!("无效的控制点 ({}, {})：分数必须为有限值，输出必须在0-1之间", x, y),
+                format!("invalid control point ({}, {}): the score must be finite and the output within 0-1", x, y),
+            ));
+        }
+        if points.windows(2).any(|pair| pair[1].0 <= pair[0].0 || pair[1].1 < pair[0].1) {
+            return Err(BbqError::invalid_argument("控制点的分数必须严格递增，输出必须单调不减", "control point scores must be strictly increasing and outputs non-decreasing"));
+        }
+        Ok(ScoreCurve::PiecewiseLinear(points))
+    }
+
+    /// 曲线在某个分数处的输出
+    pub fn value(&self, score: f32) -> f32 {
+        match self {
+            ScoreCurve::Identity => score.clamp(0.0, 1.0),
+            ScoreCurve::Logistic { midpoint, steepness } => 1.0 / (1.0 + (-steepness * (score - midpoint)).exp()),
+            ScoreCurve::PiecewiseLinear(points) => {
+                let upper = points.partition_point(|&(x, _)| x <= score);
+                if upper == 0 {
+                    return points[0].1;
+                }
+                if upper == points.len() {
+                    return points[upper - 1].1;
+                }
+                let (x0, y0) = points[upper - 1];
+                let (x1, y1) = points[upper];
+                y0 + (y1 - y0) * (score - x0) / (x1 - x0)
+            }
+        }
+    }
+}
+
+/// 最终分数变换：`floor + (ceiling - floor) * curve(score)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreTransform {
+    curve: ScoreCurve,
+    floor: f32,
+    ceiling: f32,
+}
+
+impl ScoreTransform {
+    /// 创建分数变换
+    ///
+    /// # 参数
+    /// * `curve` - 分数曲线
+    /// * `floor` - 曲线输出为0时的分数
+    /// * `ceiling` - 曲线输出为1时的分数，必须大于floor
+    pub fn new(curve: ScoreCurve, floor: f32, ceiling: f32) -> Result<Self, BbqError> {
+        if !(floor.is_finite() && ceiling.is_finite() && floor < ceiling) {
+            return Err(BbqError::invalid_argument(
+                format!("分数下限必须小于上限，当前为[{}, {}]", floor, ceiling),
+                format!("the score floor must be below the ceiling, got [{}, {}]", floor, ceiling),
+            ));
+        }
+        Ok(Self { curve, floor, ceiling })
+    }
+
+    /// 根据名称创建分数变换
+    ///
+    /// # 参数
+    /// * `name` - "identity" | "logistic" | "piecewise_linear"
+    /// * `params` - logistic为 `[midpoint, steepness]`，分段线性为 `[x0, y0, x1, y1, ...]`
+    /// * `floor` / `ceiling` - 输出范围（默认0和1）
+    pub fn from_name(name: &str, params: &[f32], floor: Option<f32>, ceiling: Option<f32>) -> Result<Self, BbqError> {
+        let curve = match name.to_lowercase().as_str() {
+            "identity" => ScoreCurve::Identity,
+            "logistic" => match params {
+                &[midpoint, steepness] => ScoreCurve::logistic(midpoint, steepness)?,
+                _ => return Err(BbqError::invalid_argument(
+                    format!("logistic需要2个参数 [midpoint, steepness]，当前为{}个", params.len()),
+                    format!("logistic takes 2 parameters [midpoint, steepness], got {}", params.len()),
+                )),
+            },
+            "piecewise_linear" | "piecewise" => {
+                if !params.len().is_multiple_of(2) {
+                    return Err(BbqError::invalid_argument(
+                        format!("分段线性的参数必须成对出现，当前为{}个", params.len()),
+                        format!("piecewise linear parameters must come in pairs, got {}", params.len()),
+                    ));
+                }
+                ScoreCurve::piecewise_linear(params.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect())?
+            }
+            _ => return Err(BbqError::invalid_argument(format!("不支持的分数变换: {}", name), format!("unsupported score transform: {}", name))),
+        };
+        Self::new(curve, floor.unwrap_or(0.0), ceiling.unwrap_or(1.0))
+    }
+
+    /// 分数曲线
+    pub fn curve(&self) -> &ScoreCurve {
+        &self.curve
+    }
+
+    /// 输出范围 `(floor, ceiling)`
+    pub fn range(&self) -> (f32, f32) {
+        (self.floor, self.ceiling)
+    }
+
+    /// 变换单个分数
+    pub fn apply(&self, score: f32) -> f32 {
+        self.floor + (self.ceiling - self.floor) * self.curve.value(score)
+    }
+
+    /// 原地变换结果分数
+    ///
+    /// 变换前的分数保存到 `original_score`（已归一化的结果保留归一化前的分数），结果顺序保持不变
+    pub fn apply_to_results(&self, results: &mut [QueryResult]) {
+        for result in results {
+            result.original_score.get_or_insert(result.score);
+            result.score = self.apply(result.score);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_curves_are_monotonic() {
+        let scores: Vec<f32> = (0..=40).map(|i| i as f32 * 0.05 - 0.5).collect();
+        let curves = [
+            ScoreCurve::Identity,
+            ScoreCurve::logistic(0.7, 12.0).unwrap(),
+            ScoreCurve::piecewise_linear(vec![(0.5, 0.0), (0.8, 0.6), (0.9, 1.0)]).unwrap(),
+        ];
+        for curve in &curves {
+            let values: Vec<f32> = scores.iter().map(|&score| curve.value(score)).collect();
+            assert!(values.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", curve);
+            assert!(values.iter().all(|value| (0.0..=1.0).contains(value)));
+        }
+        assert!((curves[1].value(0.7) - 0.5).abs() < 1e-6);
+        assert!((curves[2].value(0.65) - 0.3).abs() < 1e-6);
+        assert_eq!(curves[2].value(2.0), 1.0);
+    }
+
+    #[test]
+    fn test_transform_results() {
+        let transform = ScoreTransform::from_name("piecewise_linear", &[0.5, 0.0, 1.0, 1.0], None, Some(100.0)).unwrap();
+        let mut results: Vec<QueryResult> = [0.9, 0.75, 0.4]
+            .into_iter()
+            .enumerate()
+            .map(|(index, score)| QueryResult { index, score, original_score: None, distances: None })
+            .collect();
+        transform.apply_to_results(&mut results);
+        let scores: Vec<f32> = results.iter().map(|result| result.score).collect();
+        assert!((scores[0] - 80.0).abs() < 1e-4 && (scores[1] - 50.0).abs() < 1e-4);
+        assert_eq!(scores[2], 0.0);
+        assert_eq!(results[0].original_score, Some(0.9));
+    }
+
+    #[test]
+    fn test_invalid_transforms() {
+        assert!(ScoreCurve::logistic(0.5, 0.0).is_err());
+        assert!(ScoreCurve::piecewise_linear(vec![(0.5, 0.5), (0.4, 0.6)]).is_err());
+        assert!(ScoreCurve::piecewise_linear(vec![(0.5, 0.5), (0.6, 0.4)]).is_err());
+        assert!(ScoreCurve::piecewise_linear(vec![(0.5, 1.5)]).is_err());
+        assert!(ScoreTransform::new(ScoreCurve::Identity, 1.0, 1.0).is_err());
+        assert!(ScoreTransform::from_name("logistic", &[0.5], None, None).is_err());
+        assert!(ScoreTransform::from_name("rank", &[], None, None).is_err());
+    }
+}
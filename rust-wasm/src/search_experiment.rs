@@ -0,0 +1,329 @@
+//! 搜索实验（A/B对比）
+//!
+//! 新的内核或布局在基准测试中更快，不代表在用户的设备和数据上同样更快、结果同样正确。
+//! 设置实验后，按比例抽中的查询会同时走对照路径（索引当前的评分器和布局）和候选路径
+//! （指定的内核、评分公式或布局），分别计时并比较两组结果，累计成对的延迟和分数差异统计：
+//!
+//! - 默认返回对照路径的结果，候选路径只在旁边运行，出错也不影响查询
+//! - 抽中的查询不经过结果缓存，两条路径的先后顺序交替，避免缓存预热偏向某一方
+//! - 没有抽中的查询与未设置实验时完全相同
+
+use std::collections::HashMap;
+
+use crate::error_codes::BbqError;
+use crate::binary_quantized_scorer::{BinaryQuantizedScorer, ScoringPrecision};
+use crate::kernel_dispatch::KernelSelection;
+use crate::quantized_index::QueryResult;
+
+/// 候选路径使用的布局
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExperimentLayout {
+    /// 与对照路径相同
+    #[default]
+    Control,
+    /// 始终走构建状态的路径，即使索引已转换为搜索布局
+    BuildState,
+    /// 使用搜索布局（见 `finalize_for_search`），索引未转换时跳过该次对比
+    SearchLayout,
+}
+
+impl ExperimentLayout {
+    /// 根据名称解析布局: "control" | "build_state" | "search_layout"
+    pub fn from_name(name: &str) -> Result<Self, BbqError> {
+        match name.to_lowercase().as_str() {
+            "control" | "same" => Ok(ExperimentLayout::Control),
+            "build_state" | "build" => Ok(ExperimentLayout::BuildState),
+            "search_layout" | "finalized" => Ok(ExperimentLayout::SearchLayout),
+            _ => Err(BbqError::invalid_argument(format!("未知的实验布局: {}", name), format!("unknown experiment layout: {}", name))),
+        }
+    }
+}
+
+/// 搜索实验配置
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchExperiment {
+    /// 同时运行候选路径的查询比例（0到1）
+    pub sample_rate: f32,
+    /// 候选路径使用的内核，为None时与对照路径相同
+    pub kernels: Option<KernelSelection>,
+    /// 候选路径使用的评分公式，为None时与对照路径相同
+    pub scoring_precision: Option<ScoringPrecision>,
+    /// 候选路径使用的布局
+    pub layout: ExperimentLayout,
+    /// 抽中的查询返回候选路径的结果（候选路径出错时仍返回对照结果）
+    pub serve_candidate: bool,
+    /// 抽样的随机种子
+    pub seed: u64,
+}
+
+impl Default for SearchExperiment {
+    fn default() -> Self {
+        Self {
+            sample_rate: 0.01,
+            kernels: None,
+            scoring_precision: None,
+            layout: ExperimentLayout::Control,
+            serve_candidate: false,
+            seed: 0,
+        }
+    }
+}
+
+impl SearchExperiment {
+    /// 检查配置是否有效
+    pub fn validate(&self) -> Result<(), BbqError> {
+        if !(0.0..=1.0).contains(&self.sample_rate) {
+            return Err(BbqError::out_of_range(
+                format!("实验抽样比例必须在0-1之间，当前为{}", self.sample_rate),
+                format!("experiment sample rate must be within 0-1, got {}", self.sample_rate),
+            ));
+        }
+        if let Some(kernels) = self.kernels {
+            for variant in [kernels.one_bit, kernels.four_bit] {
+                if !variant.is_available() {
+                    return Err(BbqError::invalid_argument(
+                        format!("内核 {} 在当前构建中不可用", variant.name()),
+                        format!("kernel {} is not available in this build", variant.name()),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 成对的实验统计
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SearchExperimentStats {
+    /// 实验期间的查询数量
+    pub queries: u64,
+    /// 两条路径都运行并完成对比的查询数量
+    pub sampled: u64,
+    /// 抽中但候选路径不适用而跳过的查询数量（如索引未转换为搜索布局）
+    pub skipped: u64,
+    /// 候选路径出错的查询数量
+    pub candidate_errors: u64,
+    /// 对照路径的总耗时（毫秒）
+    pub control_ms: f64,
+    /// 候选路径的总耗时（毫秒）
+    pub candidate_ms: f64,
+    /// 成对耗时差（候选 - 对照）的平方和，用于计算标准差
+    pub latency_diff_sq_ms: f64,
+    /// 候选路径更快的查询数量
+    pub candidate_faster: u64,
+    /// 两条路径结果（序号和顺序）完全相同的查询数量
+    pub identical: u64,
+    /// 每次查询结果重合比例之和（两组结果共有的序号数 / 对照结果数量）
+    pub overlap_sum: f64,
+    /// 共有序号上分数绝对差之和
+    pub score_diff_sum: f64,
+    /// 参与分数比较的共有序号数量
+    pub score_pairs: u64,
+    /// 共有序号上分数的最大绝对差
+    pub max_score_diff: f32,
+}
+
+impl SearchExperimentStats {
+    /// 对照路径的平均耗时（毫秒）
+    pub fn mean_control_ms(&self) -> f64 {
+        mean(self.control_ms, self.sampled)
+    }
+
+    /// 候选路径的平均耗时（毫秒）
+    pub fn mean_candidate_ms(&self) -> f64 {
+        mean(self.candidate_ms, self.sampled)
+    }
+
+    /// 平均成对耗时差（候选 - 对照，毫秒），负数表示候选路径更快
+    pub fn mean_latency_diff_ms(&self) -> f64 {
+        self.mean_candidate_ms() - self.mean_control_ms()
+    }
+
+    /// 成对耗时差的样本标准差（毫秒），少于两次对比时为0
+    pub fn latency_diff_stddev_ms(&self) -> f64 {
+        if self.sampled < 2 {
+            return 0.0;
+        }
+        let n = self.sampled as f64;
+        let mean_diff = self.mean_latency_diff_ms();
+        ((self.latency_diff_sq_ms - n * mean_diff * mean_diff) / (n - 1.0)).max(0.0).sqrt()
+    }
+
+    /// 对照平均耗时 / 候选平均耗时，大于1表示候选路径更快；没有对比时为1
+    pub fn speedup(&self) -> f64 {
+        let candidate = self.mean_candidate_ms();
+        if self.sampled == 0 || candidate <= 0.0 {
+            return 1.0;
+        }
+        self.mean_control_ms() / candidate
+    }
+
+    /// 平均结果重合比例，没有对比时为1
+    pub fn mean_overlap(&self) -> f64 {
+        if self.sampled == 0 {
+            return 1.0;
+        }
+        self.overlap_sum / self.sampled as f64
+    }
+
+    /// 共有序号上分数的平均绝对差
+    pub fn mean_score_diff(&self) -> f64 {
+        mean(self.score_diff_sum, self.score_pairs)
+    }
+
+    /// 记录一次对比
+    fn record(&mut self, control_ms: f64, candidate_ms: f64, control: &[QueryResult], candidate: &[QueryResult]) {
+        self.sampled += 1;
+        self.control_ms += control_ms;
+        self.candidate_ms += candidate_ms;
+        let diff = candidate_ms - control_ms;
+        self.latency_diff_sq_ms += diff * diff;
+        if candidate_ms < control_ms {
+            self.candidate_faster += 1;
+        }
+        if control.len() == candidate.len() && control.iter().zip(candidate).all(|(a, b)| a.index == b.index) {
+            self.identical += 1;
+        }
+
+        let control_scores: HashMap<usize, f32> = control.iter().map(|result| (result.index, result.score)).collect();
+        let mut shared = 0usize;
+        for result in candidate {
+            if let Some(&score) = control_scores.get(&result.index) {
+                let diff = (result.score - score).abs();
+                shared += 1;
+                self.score_diff_sum += diff as f64;
+                self.max_score_diff = self.max_score_diff.max(diff);
+            }
+        }
+        self.score_pairs += shared as u64;
+        self.overlap_sum += if control.is_empty() { 1.0 } else { shared as f64 / control.len() as f64 };
+    }
+}
+
+fn mean(sum: f64, count: u64) -> f64 {
+    if count == 0 { 0.0 } else { sum / count as f64 }
+}
+
+/// 一次抽中的对比
+pub(crate) struct ExperimentRun {
+    /// 是否先运行对照路径
+    pub(crate) control_first: bool,
+    /// 候选路径的评分器
+    pub(crate) scorer: BinaryQuantizedScorer,
+    /// 候选路径的布局
+    pub(crate) layout: ExperimentLayout,
+    /// 是否返回候选路径的结果
+    pub(crate) serve_candidate: bool,
+}
+
+/// 进行中的实验：配置、抽样状态和累计统计
+pub(crate) struct ExperimentState {
+    config: SearchExperiment,
+    /// 候选路径的评分器
+    scorer: BinaryQuantizedScorer,
+    rng: fastrand::Rng,
+    /// 下一次抽中时是否先运行对照路径
+    control_first: bool,
+    stats: SearchExperimentStats,
+}
+
+impl ExperimentState {
+    /// 由对照路径的评分器派生候选路径的评分器
+    pub(crate) fn new(config: SearchExperiment, control: &BinaryQuantizedScorer) -> Result<Self, BbqError> {
+        config.validate()?;
+        let mut scorer = control.clone();
+        if let Some(kernels) = config.kernels {
+            scorer = scorer.with_kernel_selection(Some(kernels));
+        }
+        if let Some(precision) = config.scoring_precision {
+            scorer = scorer.with_scoring_precision(precision);
+        }
+        let rng = fastrand::Rng::with_seed(config.seed);
+        Ok(Self { config, scorer, rng, control_first: true, stats: SearchExperimentStats::default() })
+    }
+
+    pub(crate) fn config(&self) -> &SearchExperiment {
+        &self.config
+    }
+
+    pub(crate) fn stats(&self) -> SearchExperimentStats {
+        self.stats
+    }
+
+    /// 记录一次查询并决定是否抽中
+    pub(crate) fn sample(&mut self) -> Option<ExperimentRun> {
+        self.stats.queries += 1;
+        let hit = self.config.sample_rate >= 1.0 || self.rng.f32() < self.config.sample_rate;
+        if !hit {
+            return None;
+        }
+        // 每次抽中后交换先后顺序
+        let control_first = self.control_first;
+        self.control_first = !control_first;
+        Some(ExperimentRun {
+            control_first,
+            scorer: self.scorer.clone(),
+            layout: self.config.layout,
+            serve_candidate: self.config.serve_candidate,
+        })
+    }
+
+    pub(crate) fn record(&mut self, control_ms: f64, candidate_ms: f64, control: &[QueryResult], candidate: &[QueryResult]) {
+        self.stats.record(control_ms, candidate_ms, control, candidate);
+    }
+
+    pub(crate) fn record_skipped(&mut self) {
+        self.stats.skipped += 1;
+    }
+
+    pub(crate) fn record_candidate_error(&mut self) {
+        self.stats.candidate_errors += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel_dispatch::KernelVariant;
+    use crate::vector_similarity::SimilarityFunction;
+
+    fn result(index: usize, score: f32) -> QueryResult {
+        QueryResult { index, score, original_score: None, distances: None }
+    }
+
+    #[test]
+    fn test_paired_statistics() {
+        let mut stats = SearchExperimentStats::default();
+        let control = vec![result(1, 0.9), result(2, 0.8), result(3, 0.7)];
+        stats.record(4.0, 2.0, &control, &control);
+        stats.record(4.0, 3.0, &control, &[result(1, 0.95), result(4, 0.85), result(2, 0.8)]);
+        assert_eq!((stats.sampled, stats.identical, stats.candidate_faster, stats.score_pairs), (2, 1, 2, 5));
+        assert!((stats.mean_latency_diff_ms() + 1.5).abs() < 1e-9);
+        assert!((stats.latency_diff_stddev_ms() - 0.5f64.sqrt()).abs() < 1e-9);
+        assert!((stats.speedup() - 1.6).abs() < 1e-9);
+        assert!((stats.mean_overlap() - 5.0 / 6.0).abs() < 1e-9);
+        assert!((stats.max_score_diff - 0.05).abs() < 1e-6);
+        assert!((stats.mean_score_diff() - 0.01).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_sampling_and_validation() {
+        let scorer = BinaryQuantizedScorer::new(SimilarityFunction::Cosine);
+        let config = SearchExperiment { sample_rate: 0.25, seed: 3, ..Default::default() };
+        let mut state = ExperimentState::new(config, &scorer).unwrap();
+        let runs: Vec<bool> = (0..4000).filter_map(|_| state.sample()).map(|run| run.control_first).collect();
+        let hits = runs.len();
+        assert!((800..1200).contains(&hits), "抽中 {} 次", hits);
+        assert_eq!(state.stats().queries, 4000);
+        assert!(runs.windows(2).all(|pair| pair[0] != pair[1]));
+
+        assert!(ExperimentState::new(SearchExperiment { sample_rate: 1.5, ..Default::default() }, &scorer).is_err());
+        let simd = KernelSelection { one_bit: KernelVariant::Simd128, four_bit: KernelVariant::Scalar };
+        assert_eq!(
+            SearchExperiment { kernels: Some(simd), ..Default::default() }.validate().is_ok(),
+            KernelVariant::Simd128.is_available()
+        );
+        assert_eq!(ExperimentLayout::from_name("finalized").unwrap(), ExperimentLayout::SearchLayout);
+        assert!(ExperimentLayout::from_name("gpu").is_err());
+    }
+}
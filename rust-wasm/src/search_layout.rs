@@ -0,0 +1,78 @@
+//! 搜索优化布局
+//!
+//! 构建状态的索引按序号存放数据以支持删除、过期和压缩：每次搜索都要生成候选序号、
+//! 逐批跳过墓碑、按序号收集修正项，序号不连续时还要复制打包向量。
+//! `QuantizedIndex::finalize_for_search` 把当前代转换为只读的搜索布局：
+//!
+//! - 未删除向量的序号预先算好
+//! - 打包向量按同样的顺序连续存放，每批直接切片交给批量内核，不再收集或复制
+//! - 修正项按同样的顺序组成查找表，与打包向量一一对应
+//!
+//! 无过滤、无IVF探测、没有设置过期时间的搜索直接顺序扫描布局，结果与构建状态逐位相同；
+//! 其他搜索仍走构建状态的路径。布局属于它所在的一代，对该代的任何修改
+//! （删除、设置属性或过期时间等）都会丢弃布局回到构建状态，压缩和重建产生的新一代也没有布局
+
+use std::ops::Range;
+
+use crate::error_codes::{BbqError, Subject};
+use crate::memory_limits::{checked_region_len, try_alloc_zeroed, try_with_capacity};
+use crate::optimized_scalar_quantizer::QuantizationResult;
+use crate::quantized_vector_values::QuantizedVectorValues;
+
+/// 只读的搜索布局
+pub struct SearchLayout {
+    /// 布局中向量的原序号，升序
+    ords: Vec<usize>,
+    /// 按 `ords` 顺序连续存放的打包向量
+    packed: Vec<u8>,
+    /// 每个打包向量的字节数，与量化向量值的 `packed_size` 相同
+    packed_size: usize,
+    /// 按 `ords` 顺序存放的修正项
+    corrections: Vec<QuantizationResult>,
+}
+
+impl SearchLayout {
+    /// 由量化向量值构建布局
+    ///
+    /// # 参数
+    /// * `values` - 量化向量值
+    /// * `ords` - 进入布局的序号（升序，调用方已排除删除的向量）
+    pub(crate) fn build(values: &dyn QuantizedVectorValues, ords: Vec<usize>) -> Result<Self, BbqError> {
+        let packed_size = values.packed_size();
+        let mut packed = try_alloc_zeroed(checked_region_len(ords.len(), packed_size, Subject::new("搜索布局", "search layout"))?, Subject::new("搜索布局", "search layout"))?;
+        let mut corrections = try_with_capacity(ords.len(), Subject::new("搜索布局修正项", "search layout corrections"))?;
+        for (chunk, &ord) in packed.chunks_exact_mut(packed_size.max(1)).zip(&ords) {
+            let vector = values.try_vector_value(ord)?;
+            let len = packed_size.min(vector.len());
+            chunk[..len].copy_from_slice(&vector[..len]);
+            corrections.push(values.try_get_corrective_terms(ord)?);
+        }
+        Ok(Self { ords, packed, packed_size, corrections })
+    }
+
+    /// 布局中的向量数量
+    pub fn len(&self) -> usize {
+        self.ords.len()
+    }
+
+    /// 布局是否为空
+    pub fn is_empty(&self) -> bool {
+        self.ords.is_empty()
+    }
+
+    /// 布局占用的字节数
+    pub fn memory_bytes(&self) -> u64 {
+        (self.ords.len() * std::mem::size_of::<usize>()
+            + self.packed.len()
+            + self.corrections.len() * std::mem::size_of::<QuantizationResult>()) as u64
+    }
+
+    /// 布局中一段位置的（原序号, 打包向量, 修正项）
+    pub(crate) fn batch(&self, range: Range<usize>) -> (&[usize], &[u8], &[QuantizationResult]) {
+        (
+            &self.ords[range.clone()],
+            &self.packed[range.start * self.packed_size..range.end * self.packed_size],
+            &self.corrections[range],
+        )
+    }
+}
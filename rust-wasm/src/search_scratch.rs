@@ -0,0 +1,65 @@
+//! 搜索临时缓冲区
+//!
+//! 每次搜索都要用到几块与维度或候选数量成正比的临时向量（预处理的查询、量化和打包的查询、
+//! 候选序号、全部候选的分数）。高频查询时每次重新分配会带来大量分配和释放，
+//! 这里把它们放在每个索引一份的缓冲区里复用。
+//!
+//! 搜索开始时整体取走缓冲区，结束时归还；并发搜索取不到时使用临时的空缓冲区，
+//! 结果不受影响，只是这次不复用
+
+use crate::query_context::QueryContext;
+use std::mem::size_of;
+
+/// 搜索复用的临时缓冲区
+#[derive(Debug, Default)]
+pub struct SearchScratch {
+    /// 预处理后的查询向量
+    pub(crate) query: Vec<f32>,
+    /// 量化后的查询向量（未打包格式）
+    pub(crate) quantized_query: Vec<u8>,
+    /// 打包后的查询向量（仅1位查询）
+    pub(crate) packed_query: Vec<u8>,
+    /// 候选序号
+    pub(crate) candidates: Vec<usize>,
+    /// 全部候选的（序号, 分数）
+    pub(crate) scored: Vec<(usize, f32)>,
+}
+
+impl SearchScratch {
+    /// 创建空的缓冲区
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 缓冲区当前占用的字节数（按容量计）
+    pub fn capacity_bytes(&self) -> usize {
+        self.query.capacity() * size_of::<f32>()
+            + self.quantized_query.capacity()
+            + self.packed_query.capacity()
+            + self.candidates.capacity() * size_of::<usize>()
+            + self.scored.capacity() * size_of::<(usize, f32)>()
+    }
+
+    /// 回收查询上下文持有的查询缓冲区，供下一次预处理查询复用
+    pub(crate) fn recycle_context(&mut self, context: QueryContext) {
+        self.query = context.query_vector;
+        self.quantized_query = context.quantized_query;
+        if let Some(packed) = context.packed_query {
+            self.packed_query = packed;
+        }
+    }
+
+    /// 合并另一份缓冲区，每块保留容量更大的一份
+    pub(crate) fn absorb(&mut self, other: SearchScratch) {
+        fn keep_larger<T>(slot: &mut Vec<T>, other: Vec<T>) {
+            if other.capacity() > slot.capacity() {
+                *slot = other;
+            }
+        }
+        keep_larger(&mut self.query, other.query);
+        keep_larger(&mut self.quantized_query, other.quantized_query);
+        keep_larger(&mut self.packed_query, other.packed_query);
+        keep_larger(&mut self.candidates, other.candidates);
+        keep_larger(&mut self.scored, other.scored);
+    }
+}
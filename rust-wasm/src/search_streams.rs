@@ -0,0 +1,191 @@
+//! 可取代的搜索流
+//!
+//! 输入联想中每次按键都会发起新查询，此时上一个尚未完成的查询已经没有意义。
+//! 搜索流按名称记录每个流最新的查询ID和进行中的增量搜索：在同一个流上开始新查询
+//! 会自动取消之前的查询，推进已被取代的查询返回None，调用方据此停止它的动画帧循环，
+//! 不需要在JS中自己维护取消令牌
+//!
+//! 查询ID在每个流内必须递增，防抖或异步调度导致较早的查询晚到时会被拒绝
+
+use std::collections::HashMap;
+
+use crate::error_codes::BbqError;
+use crate::incremental_search::IncrementalSearch;
+use crate::quantized_index::QuantizedIndex;
+use crate::query_pack::ProgressiveResults;
+
+/// 单个流的状态
+struct StreamState {
+    /// 该流上开始过的最新查询ID
+    latest_query_id: u64,
+    /// 进行中的搜索，完成或取消后为None
+    search: Option<IncrementalSearch>,
+}
+
+/// 按名称管理的搜索流
+#[derive(Default)]
+pub struct SearchStreams {
+    streams: HashMap<String, StreamState>,
+}
+
+impl SearchStreams {
+    /// 创建空的搜索流集合
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 在流上开始新查询，取消该流上进行中的查询
+    ///
+    /// # 参数
+    /// * `stream` - 流名称，例如输入框的ID
+    /// * `query_id` - 查询ID，必须大于该流上之前的查询ID
+    ///
+    /// # 返回
+    /// 被取消的查询ID，没有进行中的查询时为None
+    pub fn start(
+        &mut self,
+        index: &QuantizedIndex,
+        stream: &str,
+        query_id: u64,
+        query_vector: &[f32],
+        k: usize,
+    ) -> Result<Option<u64>, BbqError> {
+        if let Some(state) = self.streams.get(stream) {
+            if query_id <= state.latest_query_id {
+                return Err(BbqError::invalid_argument(
+                    format!("查询{}已过期：流 {} 上已开始查询{}", query_id, stream, state.latest_query_id),
+                    format!("query {} is stale: stream {} has already started query {}", query_id, stream, state.latest_query_id),
+                ));
+            }
+        }
+        let search = index.start_incremental_search(query_vector, k)?;
+        let previous = self.streams.insert(
+            stream.to_string(),
+            StreamState { latest_query_id: query_id, search: Some(search) },
+        );
+        Ok(previous.and_then(|state| state.search.map(|_| state.latest_query_id)))
+    }
+
+    /// 最多评分 `max_batches` 批
+    ///
+    /// # 返回
+    /// 查询已被取代、取消或已返回最终结果时为None
+    pub fn pump(
+        &mut self,
+        index: &QuantizedIndex,
+        stream: &str,
+        query_id: u64,
+        max_batches: usize,
+    ) -> Result<Option<ProgressiveResults>, BbqError> {
+        self.advance(stream, query_id, |search| search.pump(index, max_batches))
+    }
+
+    /// 在 `budget_ms` 毫秒内尽量多地评分，见 `IncrementalSearch::pump_for`
+    ///
+    /// # 返回
+    /// 查询已被取代、取消或已返回最终结果时为None
+    pub fn pump_for(
+        &mut self,
+        index: &QuantizedIndex,
+        stream: &str,
+        query_id: u64,
+        budget_ms: f64,
+    ) -> Result<Option<ProgressiveResults>, BbqError> {
+        self.advance(stream, query_id, |search| search.pump_for(index, budget_ms))
+    }
+
+    /// 取消流上进行中的查询，之后的查询ID仍须大于被取消的ID
+    ///
+    /// # 返回
+    /// 被取消的查询ID
+    pub fn cancel(&mut self, stream: &str) -> Option<u64> {
+        let state = self.streams.get_mut(stream)?;
+        state.search.take().map(|_| state.latest_query_id)
+    }
+
+    /// 流上进行中的查询ID
+    pub fn in_flight(&self, stream: &str) -> Option<u64> {
+        self.streams
+            .get(stream)
+            .filter(|state| state.search.is_some())
+            .map(|state| state.latest_query_id)
+    }
+
+    /// 进行中的查询数量
+    pub fn in_flight_count(&self) -> usize {
+        self.streams.values().filter(|state| state.search.is_some()).count()
+    }
+
+    /// 移除流及其查询ID记录
+    pub fn remove(&mut self, stream: &str) -> bool {
+        self.streams.remove(stream).is_some()
+    }
+
+    /// 移除所有流
+    pub fn clear(&mut self) {
+        self.streams.clear();
+    }
+
+    /// 推进流上的当前查询，返回最终结果后释放搜索（及其持有的代快照）
+    fn advance(
+        &mut self,
+        stream: &str,
+        query_id: u64,
+        pump: impl FnOnce(&mut IncrementalSearch) -> Result<ProgressiveResults, BbqError>,
+    ) -> Result<Option<ProgressiveResults>, BbqError> {
+        let Some(state) = self.streams.get_mut(stream) else {
+            return Ok(None);
+        };
+        if state.latest_query_id != query_id {
+            return Ok(None);
+        }
+        let Some(search) = state.search.as_mut() else {
+            return Ok(None);
+        };
+        let progress = pump(search)?;
+        if progress.is_final {
+            state.search = None;
+        }
+        Ok(Some(progress))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quantized_index::QuantizedIndexConfig;
+    use crate::vector_utils::create_random_vector;
+
+    #[test]
+    fn test_new_query_supersedes_previous() {
+        let mut index = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+        let vectors: Vec<Vec<f32>> = (0..500)
+            .map(|_| create_random_vector(16, -1.0, 1.0))
+            .collect();
+        index.build_index(&vectors).unwrap();
+
+        let mut streams = SearchStreams::new();
+        assert_eq!(streams.start(&index, "box", 1, &vectors[0], 5).unwrap(), None);
+        assert_eq!(streams.start(&index, "other", 1, &vectors[1], 5).unwrap(), None);
+        assert_eq!(streams.start(&index, "box", 2, &vectors[2], 5).unwrap(), Some(1));
+        assert!(streams.start(&index, "box", 2, &vectors[3], 5).is_err());
+        assert_eq!(streams.in_flight("box"), Some(2));
+        assert_eq!(streams.in_flight_count(), 2);
+
+        // 被取代的查询不再推进
+        assert!(streams.pump(&index, "box", 1, 1).unwrap().is_none());
+        let last = streams.pump(&index, "box", 2, usize::MAX).unwrap().unwrap();
+        assert!(last.is_final);
+        assert_eq!(last.results[0].index, 2);
+        // 最终结果返回后释放搜索
+        assert!(streams.pump(&index, "box", 2, 1).unwrap().is_none());
+        assert_eq!(streams.in_flight("box"), None);
+
+        assert_eq!(streams.cancel("other"), Some(1));
+        assert_eq!(streams.cancel("other"), None);
+        assert!(streams.pump_for(&index, "other", 1, 4.0).unwrap().is_none());
+        assert!(streams.start(&index, "other", 1, &vectors[1], 5).is_err());
+        assert!(streams.remove("other"));
+        assert_eq!(streams.start(&index, "other", 1, &vectors[1], 5).unwrap(), None);
+    }
+}
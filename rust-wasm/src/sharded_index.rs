@@ -0,0 +1,282 @@
+//! 分片索引
+//!
+//! 多个 `QuantizedIndex` 分片共同回答一次查询。每个分片按序号切成定长的评分任务，
+//! 所有任务放进同一个队列，由线程池中的工作线程按原子游标依次领取（工作窃取），
+//! 评分结果并入共享的并发Top-K，这样一个很大的分片不会让整个查询串行化。
+//! 没有线程支持的WASM构建（未启用atomics）在当前线程上按同样的任务顺序执行
+
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::BinaryHeap;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::error_codes::BbqError;
+use crate::index_generation::IndexGeneration;
+use crate::quantized_index::QuantizedIndex;
+use crate::query_context::QueryContext;
+
+/// 默认每个评分任务包含的向量数
+pub const DEFAULT_TASK_SIZE: usize = 4096;
+
+/// 分片搜索结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShardHit {
+    /// 分片编号
+    pub shard: usize,
+    /// 分片内的向量序号
+    pub index: usize,
+    /// 量化分数
+    pub score: f32,
+}
+
+/// 堆中的条目，按分数从小到大排序，堆顶是当前前k个中的最低分
+struct HeapEntry(ShardHit);
+
+impl PartialEq for HeapEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for HeapEntry {}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        other.0.score.total_cmp(&self.0.score)
+            .then_with(|| (self.0.shard, self.0.index).cmp(&(other.0.shard, other.0.index)))
+    }
+}
+
+/// 并发Top-K
+///
+/// 堆满后把堆顶分数发布到原子阈值，任务先与阈值比较，低于阈值的分数不必加锁
+pub struct ConcurrentTopK {
+    k: usize,
+    heap: Mutex<BinaryHeap<HeapEntry>>,
+    threshold: AtomicU32,
+}
+
+impl ConcurrentTopK {
+    /// 创建容量为k的Top-K
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            heap: Mutex::new(BinaryHeap::with_capacity(k + 1)),
+            threshold: AtomicU32::new(f32::NEG_INFINITY.to_bits()),
+        }
+    }
+
+    /// 当前进入前k个所需的最低分数
+    pub fn threshold(&self) -> f32 {
+        f32::from_bits(self.threshold.load(Ordering::Relaxed))
+    }
+
+    /// 合并一批结果
+    pub fn offer(&self, shard: usize, scores: impl IntoIterator<Item = (usize, f32)>) {
+        if self.k == 0 {
+            return;
+        }
+        let threshold = self.threshold();
+        let candidates: Vec<ShardHit> = scores.into_iter()
+            .filter(|&(_, score)| score >= threshold)
+            .map(|(index, score)| ShardHit { shard, index, score })
+            .collect();
+        if candidates.is_empty() {
+            return;
+        }
+
+        let mut heap = self.heap.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        for hit in candidates {
+            heap.push(HeapEntry(hit));
+            if heap.len() > self.k {
+                heap.pop();
+            }
+        }
+        if heap.len() == self.k {
+            if let Some(lowest) = heap.peek() {
+                self.threshold.store(lowest.0.score.to_bits(), Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// 按分数降序取出结果
+    pub fn into_sorted(self) -> Vec<ShardHit> {
+        let heap = self.heap.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner());
+        heap.into_sorted_vec().into_iter().map(|entry| entry.0).collect()
+    }
+}
+
+/// 分片索引
+pub struct ShardedIndex {
+    shards: Vec<Arc<QuantizedIndex>>,
+    task_size: usize,
+}
+
+impl ShardedIndex {
+    /// 由已构建的分片创建分片索引，所有分片的维度必须一致
+    pub fn new(shards: Vec<QuantizedIndex>) -> Result<Self, BbqError> {
+        if shards.is_empty() {
+            return Err(BbqError::empty_input("分片索引至少需要一个分片", "a sharded index needs at least one shard"));
+        }
+        let mut dimension = None;
+        for (i, shard) in shards.iter().enumerate() {
+            let shard_dimension = shard.snapshot()?.values().dimension();
+            if dimension.is_some_and(|dimension| dimension != shard_dimension) {
+                return Err(BbqError::dimension_mismatch(
+                    format!("分片 {} 的维度 {} 与其他分片不一致", i, shard_dimension),
+                    format!("shard {} has dimension {}, unlike the other shards", i, shard_dimension),
+                ));
+            }
+            dimension = Some(shard_dimension);
+        }
+        Ok(Self {
+            shards: shards.into_iter().map(Arc::new).collect(),
+            task_size: DEFAULT_TASK_SIZE,
+        })
+    }
+
+    /// 设置每个评分任务包含的向量数
+    pub fn with_task_size(mut self, task_size: usize) -> Self {
+        self.task_size = task_size.max(1);
+        self
+    }
+
+    /// 分片数量
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// 获取分片
+    pub fn shard(&self, shard: usize) -> Option<&QuantizedIndex> {
+        self.shards.get(shard).map(|shard| shard.as_ref())
+    }
+
+    /// 搜索所有分片
+    ///
+    /// # 参数
+    /// * `query_vector` - 查询向量
+    /// * `k` - 返回的结果数量
+    /// * `threads` - 工作线程数，0表示使用可用的并行度
+    ///
+    /// # 返回
+    /// 按量化分数降序排列的结果，带分片编号
+    pub fn search(&self, query_vector: &[f32], k: usize, threads: usize) -> Result<Vec<ShardHit>, BbqError> {
+        // 每个分片固定一代并预处理查询（各分片质心不同）
+        let mut prepared: Vec<(Arc<IndexGeneration>, QueryContext)> = Vec::with_capacity(self.shards.len());
+        for shard in &self.shards {
+            let generation = shard.snapshot()?;
+            let context = shard.prepare_query_in(&generation, query_vector)?;
+            prepared.push((generation, context));
+        }
+        if k == 0 {
+            return Ok(Vec::new());
+        }
+
+        let tasks: Vec<(usize, usize, usize)> = prepared.iter()
+            .enumerate()
+            .flat_map(|(shard, (generation, _))| {
+                let size = generation.size();
+                (0..size).step_by(self.task_size)
+                    .map(move |start| (shard, start, (start + self.task_size).min(size)))
+            })
+            .collect();
+
+        let top_k = ConcurrentTopK::new(k);
+        let cursor = AtomicUsize::new(0);
+        let error: Mutex<Option<BbqError>> = Mutex::new(None);
+        let worker = || loop {
+            let next = cursor.fetch_add(1, Ordering::Relaxed);
+            let Some(&(shard, start, end)) = tasks.get(next) else { break };
+            let (generation, context) = &prepared[shard];
+            match self.shards[shard].score_ord_range(generation, context, start, end) {
+                Ok(scores) => top_k.offer(shard, scores),
+                Err(e) => {
+                    error.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).get_or_insert(e);
+                    // 让其他线程尽快退出
+                    cursor.store(tasks.len(), Ordering::Relaxed);
+                    break;
+                }
+            }
+        };
+
+        let threads = worker_count(threads).min(tasks.len());
+        if threads <= 1 {
+            worker();
+        } else {
+            std::thread::scope(|scope| {
+                for _ in 1..threads {
+                    scope.spawn(worker);
+                }
+                worker();
+            });
+        }
+
+        if let Some(e) = error.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner()) {
+            return Err(e);
+        }
+        Ok(top_k.into_sorted())
+    }
+}
+
+/// 实际使用的工作线程数
+#[cfg(not(all(target_arch = "wasm32", not(target_feature = "atomics"))))]
+fn worker_count(requested: usize) -> usize {
+    match requested {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+}
+
+/// 没有线程支持的WASM构建只在当前线程执行
+#[cfg(all(target_arch = "wasm32", not(target_feature = "atomics")))]
+fn worker_count(_requested: usize) -> usize {
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quantized_index::QuantizedIndexConfig;
+    use crate::vector_utils::create_random_vector;
+
+    #[test]
+    fn test_sharded_search_matches_per_shard_search() {
+        let vectors: Vec<Vec<f32>> = (0..900)
+            .map(|_| create_random_vector(24, -1.0, 1.0))
+            .collect();
+        // 一个大分片和两个小分片
+        let mut shards = Vec::new();
+        for range in [0..700, 700..800, 800..900] {
+            let mut shard = QuantizedIndex::new(QuantizedIndexConfig::default()).unwrap();
+            shard.build_index(&vectors[range]).unwrap();
+            shards.push(shard);
+        }
+        shards[0].delete(3).unwrap();
+        let sharded = ShardedIndex::new(shards).unwrap().with_task_size(64);
+
+        let query = &vectors[3];
+        let mut expected: Vec<ShardHit> = (0..3)
+            .flat_map(|shard| {
+                sharded.shard(shard).unwrap()
+                    .search_nearest_neighbors(query, 10).unwrap()
+                    .into_iter()
+                    .map(move |r| ShardHit { shard, index: r.index, score: r.score })
+            })
+            .collect();
+        expected.sort_by(|a, b| b.score.total_cmp(&a.score));
+        expected.truncate(10);
+
+        let single = sharded.search(query, 10, 1).unwrap();
+        let pooled = sharded.search(query, 10, 4).unwrap();
+        let scores = |hits: &[ShardHit]| hits.iter().map(|hit| hit.score).collect::<Vec<_>>();
+        assert_eq!(scores(&single), scores(&expected));
+        assert_eq!(scores(&pooled), scores(&expected));
+        assert!(pooled.iter().all(|hit| !(hit.shard == 0 && hit.index == 3)));
+    }
+}
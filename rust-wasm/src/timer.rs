@@ -0,0 +1,37 @@
+//! 计时工具
+//!
+//! wasm32-unknown-unknown 下 `std::time::Instant` 不可用，
+//! 在浏览器中改用 `Date.now()`
+
+/// 获取当前时间戳（毫秒）
+#[cfg(target_arch = "wasm32")]
+pub fn now_ms() -> f64 {
+    js_sys::Date::now()
+}
+
+/// 获取当前时间戳（毫秒）
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now_ms() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .unwrap_or(0.0)
+}
+
+/// 计算自 `start_ms` 以来经过的毫秒数
+pub fn elapsed_ms(start_ms: f64) -> f64 {
+    (now_ms() - start_ms).max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elapsed_is_non_negative() {
+        let start = now_ms();
+        assert!(start > 0.0);
+        assert!(elapsed_ms(start) >= 0.0);
+        assert_eq!(elapsed_ms(f64::MAX), 0.0);
+    }
+}
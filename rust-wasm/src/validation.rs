@@ -0,0 +1,142 @@
+//! 向量导入校验
+//!
+//! 构建索引遇到非法数据时直接报错，且只报告第一个问题；导入流水线希望在开始
+//! 耗时的构建之前一次性看到数据的全部问题。这里只扫描数据、不构建索引，
+//! 返回各类问题的数量和部分示例行号
+
+use crate::error_codes::BbqError;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// 每类问题最多记录的示例行号数量
+pub const MAX_EXAMPLE_ROWS: usize = 10;
+
+/// 向量校验报告
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    /// 完整的向量行数
+    pub rows: usize,
+    /// 包含NaN或无穷值的行数
+    pub non_finite_rows: usize,
+    /// 数组末尾不足一个维度的值的个数（非0表示长度不是维度的整数倍）
+    pub trailing_values: usize,
+    /// 全零向量的行数（余弦相似度下无法归一化）
+    pub zero_rows: usize,
+    /// 各分量都相同的非零向量行数（量化区间退化）
+    pub constant_rows: usize,
+    /// 与之前某行完全相同的行数
+    pub duplicate_rows: usize,
+    /// 非有限值行的示例行号
+    pub non_finite_examples: Vec<usize>,
+    /// 全零行的示例行号
+    pub zero_examples: Vec<usize>,
+    /// 常量行的示例行号
+    pub constant_examples: Vec<usize>,
+    /// 重复行的示例（行号, 首次出现的行号）
+    pub duplicate_examples: Vec<(usize, usize)>,
+}
+
+impl ValidationReport {
+    /// 没有发现任何问题
+    pub fn is_clean(&self) -> bool {
+        self.non_finite_rows == 0
+            && self.trailing_values == 0
+            && self.zero_rows == 0
+            && self.constant_rows == 0
+            && self.duplicate_rows == 0
+    }
+
+    /// 数据能否直接用于构建（非有限值和长度不整齐会导致构建失败，其余问题只影响质量）
+    pub fn is_buildable(&self) -> bool {
+        self.rows > 0 && self.non_finite_rows == 0 && self.trailing_values == 0
+    }
+}
+
+/// 记录示例行号，超过上限后只计数
+fn push_example<T>(examples: &mut Vec<T>, example: T) {
+    if examples.len() < MAX_EXAMPLE_ROWS {
+        examples.push(example);
+    }
+}
+
+/// 校验扁平存放的向量数据
+///
+/// # 参数
+/// * `vectors` - 按行连续存放的向量
+/// * `dimension` - 向量维度
+pub fn validate_vectors(vectors: &[f32], dimension: usize) -> Result<ValidationReport, BbqError> {
+    if dimension == 0 {
+        return Err(BbqError::invalid_argument("维度必须大于0", "dimension must be greater than 0"));
+    }
+
+    let mut report = ValidationReport {
+        rows: vectors.len() / dimension,
+        trailing_values: vectors.len() % dimension,
+        ..ValidationReport::default()
+    };
+
+    // 按内容哈希分桶，再逐个比较确认是否完全相同
+    let mut seen: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (row, vector) in vectors.chunks_exact(dimension).enumerate() {
+        if vector.iter().any(|value| !value.is_finite()) {
+            report.non_finite_rows += 1;
+            push_example(&mut report.non_finite_examples, row);
+            continue;
+        }
+        if vector.iter().all(|&value| value == 0.0) {
+            report.zero_rows += 1;
+            push_example(&mut report.zero_examples, row);
+        } else if dimension > 1 && vector.iter().all(|&value| value == vector[0]) {
+            report.constant_rows += 1;
+            push_example(&mut report.constant_examples, row);
+        }
+
+        let mut hasher = DefaultHasher::new();
+        for value in vector {
+            // +0.0与-0.0视为相同
+            (value + 0.0).to_bits().hash(&mut hasher);
+        }
+        let bucket = seen.entry(hasher.finish()).or_default();
+        let row_vector = |row: usize| &vectors[row * dimension..(row + 1) * dimension];
+        match bucket.iter().find(|&&first| row_vector(first) == vector) {
+            Some(&first) => {
+                report.duplicate_rows += 1;
+                push_example(&mut report.duplicate_examples, (row, first));
+            }
+            None => bucket.push(row),
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation_report() {
+        let vectors = [
+            1.0, 2.0, 3.0,
+            0.0, 0.0, 0.0,
+            f32::NAN, 1.0, 1.0,
+            5.0, 5.0, 5.0,
+            1.0, 2.0, 3.0,
+            -0.0, 0.0, 0.0,
+            7.0,
+        ];
+        let report = validate_vectors(&vectors, 3).unwrap();
+        assert_eq!(report.rows, 6);
+        assert_eq!(report.trailing_values, 1);
+        assert_eq!((report.non_finite_rows, report.non_finite_examples.clone()), (1, vec![2]));
+        assert_eq!((report.zero_rows, report.zero_examples.clone()), (2, vec![1, 5]));
+        assert_eq!((report.constant_rows, report.constant_examples.clone()), (1, vec![3]));
+        assert_eq!(report.duplicate_examples, vec![(4, 0), (5, 1)]);
+        assert!(!report.is_buildable());
+
+        let clean = validate_vectors(&[1.0, 2.0, 3.0, 4.0], 2).unwrap();
+        assert!(clean.is_clean() && clean.is_buildable());
+        assert!(validate_vectors(&vectors, 0).is_err());
+    }
+}
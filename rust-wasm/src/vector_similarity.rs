@@ -1,8 +1,12 @@
-/// 向量相似性计算
-/// 对应TypeScript中的vectorSimilarity.ts
+//! 向量相似性计算
+//! 对应TypeScript中的vectorSimilarity.ts
 
 use wasm_bindgen::prelude::*;
 
+use crate::error_codes::BbqError;
+use crate::float::Float;
+use crate::vector_utils::CompensatedSum;
+
 /// 相似性函数类型
 #[wasm_bindgen]
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -12,6 +16,16 @@ pub enum SimilarityFunction {
     MaximumInnerProduct,
 }
 
+/// 解析度量方式名称
+pub fn parse_metric(name: &str) -> Result<SimilarityFunction, BbqError> {
+    match name.to_lowercase().as_str() {
+        "euclidean" => Ok(SimilarityFunction::Euclidean),
+        "cosine" => Ok(SimilarityFunction::Cosine),
+        "dot_product" | "maximum_inner_product" => Ok(SimilarityFunction::MaximumInnerProduct),
+        _ => Err(BbqError::invalid_argument(format!("不支持的相似性类型: {}", name), format!("unsupported similarity: {}", name))),
+    }
+}
+
 /// 计算欧几里得距离
 /// 
 /// # 参数
@@ -20,12 +34,15 @@ pub enum SimilarityFunction {
 /// 
 /// # 返回
 /// 欧几里得距离
-pub fn compute_euclidean_distance(a: &[f32], b: &[f32]) -> Result<f32, String> {
+pub fn compute_euclidean_distance(a: &[f32], b: &[f32]) -> Result<f32, BbqError> {
     if a.is_empty() || b.is_empty() {
-        return Err("向量不能为空".to_string());
+        return Err(BbqError::empty_input("向量不能为空", "vectors must not be empty"));
     }
     if a.len() != b.len() {
-        return Err("向量维度不匹配".to_string());
+        return Err(BbqError::dimension_mismatch(
+            format!("向量维度不匹配：{} 与 {}", a.len(), b.len()),
+            format!("vector dimensions differ: {} vs {}", a.len(), b.len()),
+        ));
     }
     
     let sum: f32 = a.iter()
@@ -48,7 +65,7 @@ pub fn compute_euclidean_distance(a: &[f32], b: &[f32]) -> Result<f32, String> {
 /// 
 /// # 返回
 /// 欧几里得相似性分数
-pub fn compute_euclidean_similarity(a: &[f32], b: &[f32]) -> Result<f32, String> {
+pub fn compute_euclidean_similarity(a: &[f32], b: &[f32]) -> Result<f32, BbqError> {
     let distance = compute_euclidean_distance(a, b)?;
     Ok(1.0 / (1.0 + distance))
 }
@@ -61,12 +78,15 @@ pub fn compute_euclidean_similarity(a: &[f32], b: &[f32]) -> Result<f32, String>
 /// 
 /// # 返回
 /// 余弦相似性分数（-1到1之间）
-pub fn compute_cosine_similarity(a: &[f32], b: &[f32]) -> Result<f32, String> {
+pub fn compute_cosine_similarity(a: &[f32], b: &[f32]) -> Result<f32, BbqError> {
     if a.is_empty() || b.is_empty() {
-        return Err("向量不能为空".to_string());
+        return Err(BbqError::empty_input("向量不能为空", "vectors must not be empty"));
     }
     if a.len() != b.len() {
-        return Err("向量维度不匹配".to_string());
+        return Err(BbqError::dimension_mismatch(
+            format!("向量维度不匹配：{} 与 {}", a.len(), b.len()),
+            format!("vector dimensions differ: {} vs {}", a.len(), b.len()),
+        ));
     }
     
     let mut dot_product = 0.0_f32;
@@ -94,12 +114,15 @@ pub fn compute_cosine_similarity(a: &[f32], b: &[f32]) -> Result<f32, String> {
 /// 
 /// # 返回
 /// 最大内积值
-pub fn compute_maximum_inner_product(a: &[f32], b: &[f32]) -> Result<f32, String> {
+pub fn compute_maximum_inner_product(a: &[f32], b: &[f32]) -> Result<f32, BbqError> {
     if a.is_empty() || b.is_empty() {
-        return Err("向量不能为空".to_string());
+        return Err(BbqError::empty_input("向量不能为空", "vectors must not be empty"));
     }
     if a.len() != b.len() {
-        return Err("向量维度不匹配".to_string());
+        return Err(BbqError::dimension_mismatch(
+            format!("向量维度不匹配：{} 与 {}", a.len(), b.len()),
+            format!("vector dimensions differ: {} vs {}", a.len(), b.len()),
+        ));
     }
     
     let dot_product: f32 = a.iter()
@@ -123,7 +146,7 @@ pub fn compute_similarity(
     a: &[f32],
     b: &[f32],
     similarity_function: SimilarityFunction,
-) -> Result<f32, String> {
+) -> Result<f32, BbqError> {
     match similarity_function {
         SimilarityFunction::Euclidean => compute_euclidean_similarity(a, b),
         SimilarityFunction::Cosine => compute_cosine_similarity(a, b),
@@ -131,6 +154,263 @@ pub fn compute_similarity(
     }
 }
 
+/// 以f64累加计算f32或f64向量的相似性
+///
+/// 公式与 `compute_similarity` 相同，用于f64数据或需要高精度的精确评分
+///
+/// # 参数
+/// * `a` - 向量a
+/// * `b` - 向量b
+/// * `similarity_function` - 相似性函数类型
+///
+/// # 返回
+/// 相似性分数（f64）
+pub fn compute_similarity_precise<T: Float>(
+    a: &[T],
+    b: &[T],
+    similarity_function: SimilarityFunction,
+) -> Result<f64, BbqError> {
+    if a.is_empty() || b.is_empty() {
+        return Err(BbqError::empty_input("向量不能为空", "vectors must not be empty"));
+    }
+    if a.len() != b.len() {
+        return Err(BbqError::dimension_mismatch(
+            format!("向量维度不匹配：{} 与 {}", a.len(), b.len()),
+            format!("vector dimensions differ: {} vs {}", a.len(), b.len()),
+        ));
+    }
+
+    let pairs = a.iter().zip(b).map(|(&x, &y)| (x.to_f64(), y.to_f64()));
+    match similarity_function {
+        SimilarityFunction::Euclidean => {
+            let distance = pairs.map(|(x, y)| (x - y) * (x - y)).sum::<f64>().sqrt();
+            Ok(1.0 / (1.0 + distance))
+        }
+        SimilarityFunction::Cosine => {
+            let (dot, norm_a, norm_b) = pairs.fold((0.0, 0.0, 0.0), |(dot, norm_a, norm_b), (x, y)| {
+                (dot + x * y, norm_a + x * x, norm_b + y * y)
+            });
+            if norm_a == 0.0 || norm_b == 0.0 {
+                return Ok(0.0);
+            }
+            Ok(dot / (norm_a.sqrt() * norm_b.sqrt()))
+        }
+        SimilarityFunction::MaximumInnerProduct => Ok(pairs.map(|(x, y)| x * y).sum()),
+    }
+}
+
+/// 精确相似度内核的并行通道数
+///
+/// 第j个通道累加下标为 4k+j 的元素，展开实现与SIMD128实现的通道划分一致，
+/// 因此两者结果逐位相同
+const EXACT_LANES: usize = 4;
+
+/// 单个通道的Kahan补偿累加
+#[derive(Clone, Copy, Default)]
+struct KahanLane {
+    sum: f32,
+    compensation: f32,
+}
+
+impl KahanLane {
+    #[inline(always)]
+    fn add(&mut self, value: f32) {
+        let y = value - self.compensation;
+        let t = self.sum + y;
+        self.compensation = (t - self.sum) - y;
+        self.sum = t;
+    }
+}
+
+/// 合并各通道的和与尾部元素
+#[inline]
+fn finish_lanes(sums: [f32; EXACT_LANES], compensations: [f32; EXACT_LANES], tail: impl Iterator<Item = f32>) -> f32 {
+    let mut total = CompensatedSum::new();
+    for (sum, compensation) in sums.into_iter().zip(compensations) {
+        total.add(sum);
+        total.add(-compensation);
+    }
+    for value in tail {
+        total.add(value);
+    }
+    total.value()
+}
+
+/// 4路展开的点积（每个通道Kahan补偿）
+///
+/// 供重排阶段的精确评分使用：比逐元素 `iter().zip()` 快，同时保持补偿求和的精度
+pub fn dot_product_unrolled(a: &[f32], b: &[f32]) -> f32 {
+    let len = a.len().min(b.len());
+    let main = len - len % EXACT_LANES;
+    let mut lanes = [KahanLane::default(); EXACT_LANES];
+    for (a_chunk, b_chunk) in a[..main].chunks_exact(EXACT_LANES).zip(b[..main].chunks_exact(EXACT_LANES)) {
+        for j in 0..EXACT_LANES {
+            lanes[j].add(a_chunk[j] * b_chunk[j]);
+        }
+    }
+    finish_lanes(
+        lanes.map(|lane| lane.sum),
+        lanes.map(|lane| lane.compensation),
+        (main..len).map(|i| a[i] * b[i]),
+    )
+}
+
+/// 4路展开的平方欧氏距离（每个通道Kahan补偿）
+pub fn squared_distance_unrolled(a: &[f32], b: &[f32]) -> f32 {
+    let len = a.len().min(b.len());
+    let main = len - len % EXACT_LANES;
+    let mut lanes = [KahanLane::default(); EXACT_LANES];
+    for (a_chunk, b_chunk) in a[..main].chunks_exact(EXACT_LANES).zip(b[..main].chunks_exact(EXACT_LANES)) {
+        for j in 0..EXACT_LANES {
+            let diff = a_chunk[j] - b_chunk[j];
+            lanes[j].add(diff * diff);
+        }
+    }
+    finish_lanes(
+        lanes.map(|lane| lane.sum),
+        lanes.map(|lane| lane.compensation),
+        (main..len).map(|i| (a[i] - b[i]) * (a[i] - b[i])),
+    )
+}
+
+/// WASM SIMD128精确相似度内核
+/// 仅在以 `-C target-feature=+simd128` 编译时可用
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+pub mod simd128 {
+    use core::arch::wasm32::*;
+
+    /// 点积（SIMD128实现，每个通道Kahan补偿）
+    pub fn dot_product_simd128(a: &[f32], b: &[f32]) -> f32 {
+        kahan_simd(a, b, |x, y| f32x4_mul(x, y), |x, y| x * y)
+    }
+
+    /// 平方欧氏距离（SIMD128实现，每个通道Kahan补偿）
+    pub fn squared_distance_simd128(a: &[f32], b: &[f32]) -> f32 {
+        kahan_simd(
+            a,
+            b,
+            |x, y| {
+                let diff = f32x4_sub(x, y);
+                f32x4_mul(diff, diff)
+            },
+            |x, y| (x - y) * (x - y),
+        )
+    }
+
+    fn kahan_simd(a: &[f32], b: &[f32], term: fn(v128, v128) -> v128, scalar: fn(f32, f32) -> f32) -> f32 {
+        let len = a.len().min(b.len());
+        let chunks = len / super::EXACT_LANES;
+        let mut sum = f32x4_splat(0.0);
+        let mut compensation = f32x4_splat(0.0);
+
+        for c in 0..chunks {
+            // SAFETY: c * 4 + 4 <= len，两个切片都至少有4个可读的f32；wasm的v128_load允许非对齐地址
+            let (x, y) = unsafe {
+                (
+                    v128_load(a.as_ptr().add(c * 4) as *const v128),
+                    v128_load(b.as_ptr().add(c * 4) as *const v128),
+                )
+            };
+            let value = f32x4_sub(term(x, y), compensation);
+            let t = f32x4_add(sum, value);
+            compensation = f32x4_sub(f32x4_sub(t, sum), value);
+            sum = t;
+        }
+
+        let lanes = |v: v128| [
+            f32x4_extract_lane::<0>(v),
+            f32x4_extract_lane::<1>(v),
+            f32x4_extract_lane::<2>(v),
+            f32x4_extract_lane::<3>(v),
+        ];
+        super::finish_lanes(
+            lanes(sum),
+            lanes(compensation),
+            (chunks * super::EXACT_LANES..len).map(|i| scalar(a[i], b[i])),
+        )
+    }
+}
+
+/// 精确点积（按构建选择SIMD128或展开实现）
+#[inline]
+pub fn fast_dot_product(a: &[f32], b: &[f32]) -> f32 {
+    #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+    {
+        simd128::dot_product_simd128(a, b)
+    }
+    #[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+    {
+        dot_product_unrolled(a, b)
+    }
+}
+
+/// 精确平方欧氏距离（按构建选择SIMD128或展开实现）
+#[inline]
+pub fn fast_squared_distance(a: &[f32], b: &[f32]) -> f32 {
+    #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+    {
+        simd128::squared_distance_simd128(a, b)
+    }
+    #[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+    {
+        squared_distance_unrolled(a, b)
+    }
+}
+
+/// 按分数降序排列的比较函数，NaN排在最后
+///
+/// `partial_cmp` 遇到NaN时不满足全序，标准库排序可能因此panic，
+/// 所有按分数排序的地方都应使用这个比较函数
+pub fn descending_score_order(a: f32, b: f32) -> std::cmp::Ordering {
+    let key = |score: f32| if score.is_nan() { f32::NEG_INFINITY } else { score };
+    key(b).total_cmp(&key(a))
+}
+
+/// 检查连续存放的向量缓冲区
+fn check_contiguous(query: &[f32], vectors: &[f32], dimension: usize) -> Result<(), BbqError> {
+    if dimension == 0 || query.len() != dimension {
+        return Err(BbqError::dimension_mismatch(
+            format!("查询向量维度 {} 与指定维度 {} 不匹配", query.len(), dimension),
+            format!("query dimension {} does not match dimension {}", query.len(), dimension),
+        ));
+    }
+    if !vectors.len().is_multiple_of(dimension) {
+        return Err(BbqError::dimension_mismatch(
+            format!("向量缓冲区长度 {} 不是维度 {} 的整数倍", vectors.len(), dimension),
+            format!("vector buffer length {} is not a multiple of dimension {}", vectors.len(), dimension),
+        ));
+    }
+    Ok(())
+}
+
+/// 批量点积：一个查询对连续存放的多个向量
+///
+/// # 参数
+/// * `query` - 查询向量
+/// * `vectors` - 连续存放的向量，每个向量 `dimension` 个f32
+/// * `dimension` - 向量维度
+///
+/// # 返回
+/// 每个向量与查询的点积
+pub fn compute_batch_dot_products(query: &[f32], vectors: &[f32], dimension: usize) -> Result<Vec<f32>, BbqError> {
+    check_contiguous(query, vectors, dimension)?;
+    Ok(vectors.chunks_exact(dimension).map(|vector| fast_dot_product(query, vector)).collect())
+}
+
+/// 批量平方欧氏距离：一个查询对连续存放的多个向量
+///
+/// # 参数
+/// * `query` - 查询向量
+/// * `vectors` - 连续存放的向量，每个向量 `dimension` 个f32
+/// * `dimension` - 向量维度
+///
+/// # 返回
+/// 每个向量与查询的平方欧氏距离
+pub fn compute_batch_squared_distances(query: &[f32], vectors: &[f32], dimension: usize) -> Result<Vec<f32>, BbqError> {
+    check_contiguous(query, vectors, dimension)?;
+    Ok(vectors.chunks_exact(dimension).map(|vector| fast_squared_distance(query, vector)).collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -157,6 +437,26 @@ mod tests {
         assert_eq!(similarity, 0.0);
     }
 
+    #[test]
+    fn test_exact_kernels() {
+        let mut rng = fastrand::Rng::with_seed(5);
+        let query: Vec<f32> = (0..37).map(|_| rng.f32() - 0.5).collect();
+        let vectors: Vec<f32> = (0..37 * 3).map(|_| rng.f32() - 0.5).collect();
+
+        let dots = compute_batch_dot_products(&query, &vectors, 37).unwrap();
+        let distances = compute_batch_squared_distances(&query, &vectors, 37).unwrap();
+        for (i, vector) in vectors.chunks_exact(37).enumerate() {
+            let naive_dot = compute_maximum_inner_product(&query, vector).unwrap();
+            let naive_distance = compute_euclidean_distance(&query, vector).unwrap().powi(2);
+            assert!((dots[i] - naive_dot).abs() < 1e-5);
+            assert!((distances[i] - naive_distance).abs() < 1e-5);
+        }
+        assert!(compute_batch_dot_products(&query, &vectors[1..], 37).is_err());
+
+        // 通道补偿使抵消严重的和仍然精确
+        assert_eq!(dot_product_unrolled(&[1e8, 1.0, 0.5, 0.25, -1e8], &[1.0; 5]), 1.75);
+    }
+
     #[test]
     fn test_maximum_inner_product() {
         let a = vec![1.0, 2.0, 3.0];
@@ -165,4 +465,22 @@ mod tests {
         // 1*4 + 2*5 + 3*6 = 4 + 10 + 18 = 32
         assert_eq!(product, 32.0);
     }
+
+    #[test]
+    fn test_precise_similarity() {
+        let a = [1.0f32, 2.0, 3.0];
+        let b = [4.0f32, 5.0, 6.0];
+        for function in [SimilarityFunction::Euclidean, SimilarityFunction::Cosine, SimilarityFunction::MaximumInnerProduct] {
+            let single = compute_similarity(&a, &b, function).unwrap();
+            let precise = compute_similarity_precise(&a, &b, function).unwrap();
+            assert!((single as f64 - precise).abs() < 1e-6);
+        }
+
+        // f64输入中远离原点的小差异不会被f32舍入抹掉
+        let a = [1e9f64, 0.0];
+        let b = [1e9f64 + 1.0, 0.0];
+        let precise = compute_similarity_precise(&a, &b, SimilarityFunction::Euclidean).unwrap();
+        assert_eq!(precise, 0.5);
+        assert!(compute_similarity_precise(&a, &b[..1], SimilarityFunction::Cosine).is_err());
+    }
 }
@@ -1,5 +1,9 @@
-/// 向量工具函数
-/// 对应TypeScript中的vectorUtils.ts
+//! 向量工具函数
+//! 对应TypeScript中的vectorUtils.ts
+
+use crate::error_codes::BbqError;
+use crate::float::Float;
+use crate::rng::{with_default_rng, RngSource};
 
 /// 计算向量幅度（模长）
 /// 
@@ -15,7 +19,7 @@ pub fn compute_vector_magnitude(vector: &[f32]) -> f32 {
     sum.sqrt()
 }
 
-/// 创建随机向量
+/// 创建随机向量（使用当前线程的默认随机来源，见 `rng`）
 /// 
 /// # 参数
 /// * `dimension` - 向量维度
@@ -25,19 +29,145 @@ pub fn compute_vector_magnitude(vector: &[f32]) -> f32 {
 /// # 返回
 /// 随机向量
 pub fn create_random_vector(dimension: usize, min: f32, max: f32) -> Vec<f32> {
-    use std::cell::RefCell;
-    thread_local! {
-        static RNG: RefCell<fastrand::Rng> = RefCell::new(fastrand::Rng::new());
+    with_default_rng(|rng| create_random_vector_with(dimension, min, max, rng))
+}
+
+/// 使用指定的随机来源创建随机向量
+pub fn create_random_vector_with<R: RngSource + ?Sized>(dimension: usize, min: f32, max: f32, rng: &mut R) -> Vec<f32> {
+    (0..dimension)
+        .map(|_| rng.f32() * (max - min) + min)
+        .collect()
+}
+
+/// 标准正态分布采样（Box-Muller变换）
+fn sample_standard_normal<R: RngSource + ?Sized>(rng: &mut R) -> f32 {
+    // 1 - f64() 落在 (0, 1]，避免对0取对数
+    let u1 = 1.0 - rng.f64();
+    let u2 = rng.f64();
+    ((-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()) as f32
+}
+
+/// 生成高斯混合分布的聚类向量
+///
+/// 聚类中心在 [-1, 1] 内均匀分布，每个点属于随机的一个聚类，
+/// 在中心附近按标准差spread做各向同性的高斯扰动；
+/// 比均匀随机向量更接近真实嵌入的分布，召回率也更难达到
+///
+/// # 参数
+/// * `count` - 向量数量
+/// * `dimension` - 向量维度
+/// * `clusters` - 聚类数量
+/// * `spread` - 聚类内的标准差
+/// * `seed` - 随机种子
+///
+/// # 返回
+/// 向量集合
+pub fn generate_gaussian_mixture(
+    count: usize,
+    dimension: usize,
+    clusters: usize,
+    spread: f32,
+    seed: u64,
+) -> Result<Vec<Vec<f32>>, BbqError> {
+    generate_gaussian_mixture_with(count, dimension, clusters, spread, &mut fastrand::Rng::with_seed(seed))
+}
+
+/// 使用指定的随机来源生成高斯混合分布的聚类向量，见 `generate_gaussian_mixture`
+pub fn generate_gaussian_mixture_with<R: RngSource + ?Sized>(
+    count: usize,
+    dimension: usize,
+    clusters: usize,
+    spread: f32,
+    rng: &mut R,
+) -> Result<Vec<Vec<f32>>, BbqError> {
+    if clusters == 0 {
+        return Err(BbqError::invalid_argument("聚类数量必须大于0", "cluster count must be greater than 0"));
+    }
+    if !spread.is_finite() || spread < 0.0 {
+        return Err(BbqError::invalid_argument(format!("无效的聚类标准差: {}", spread), format!("invalid cluster spread: {}", spread)));
+    }
+    let centers: Vec<Vec<f32>> = (0..clusters)
+        .map(|_| (0..dimension).map(|_| rng.f32() * 2.0 - 1.0).collect())
+        .collect();
+    Ok((0..count)
+        .map(|_| {
+            let center = &centers[rng.usize(0..clusters)];
+            center.iter()
+                .map(|&c| c + spread * sample_standard_normal(rng))
+                .collect()
+        })
+        .collect())
+}
+
+/// 生成单位球面上均匀分布的向量
+///
+/// # 参数
+/// * `count` - 向量数量
+/// * `dimension` - 向量维度
+/// * `seed` - 随机种子
+///
+/// # 返回
+/// 模长为1的向量集合
+pub fn generate_unit_sphere(count: usize, dimension: usize, seed: u64) -> Vec<Vec<f32>> {
+    generate_unit_sphere_with(count, dimension, &mut fastrand::Rng::with_seed(seed))
+}
+
+/// 使用指定的随机来源生成单位球面上均匀分布的向量
+pub fn generate_unit_sphere_with<R: RngSource + ?Sized>(count: usize, dimension: usize, rng: &mut R) -> Vec<Vec<f32>> {
+    (0..count)
+        .map(|_| {
+            let mut vector: Vec<f32> = (0..dimension).map(|_| sample_standard_normal(rng)).collect();
+            normalize_vector(&mut vector);
+            vector
+        })
+        .collect()
+}
+
+/// 生成重尾分布（学生t分布）的向量
+///
+/// 自由度越小尾部越重，少数维度会出现远大于其它维度的离群值，
+/// 用于检验量化区间在离群值下的表现
+///
+/// # 参数
+/// * `count` - 向量数量
+/// * `dimension` - 向量维度
+/// * `degrees_of_freedom` - 自由度（大于0）
+/// * `seed` - 随机种子
+///
+/// # 返回
+/// 向量集合
+pub fn generate_heavy_tailed(
+    count: usize,
+    dimension: usize,
+    degrees_of_freedom: u32,
+    seed: u64,
+) -> Result<Vec<Vec<f32>>, BbqError> {
+    generate_heavy_tailed_with(count, dimension, degrees_of_freedom, &mut fastrand::Rng::with_seed(seed))
+}
+
+/// 使用指定的随机来源生成重尾分布的向量，见 `generate_heavy_tailed`
+pub fn generate_heavy_tailed_with<R: RngSource + ?Sized>(
+    count: usize,
+    dimension: usize,
+    degrees_of_freedom: u32,
+    rng: &mut R,
+) -> Result<Vec<Vec<f32>>, BbqError> {
+    if degrees_of_freedom == 0 {
+        return Err(BbqError::invalid_argument("自由度必须大于0", "degrees of freedom must be greater than 0"));
     }
-    
-    RNG.with(|rng| {
-        let mut r = rng.borrow_mut();
-        (0..dimension)
-            .map(|_| {
-                r.f32() * (max - min) + min
-            })
-            .collect()
-    })
+    Ok((0..count)
+        .map(|_| {
+            (0..dimension)
+                .map(|_| {
+                    let chi_squared: f32 = (0..degrees_of_freedom)
+                        .map(|_| sample_standard_normal(rng).powi(2))
+                        .sum();
+                    let z = sample_standard_normal(rng);
+                    z / (chi_squared / degrees_of_freedom as f32).sqrt().max(f32::MIN_POSITIVE)
+                })
+                .collect()
+        })
+        .collect())
 }
 
 /// 创建零向量
@@ -71,9 +201,9 @@ pub fn normalize_vector(vector: &mut [f32]) {
 ///
 /// # 返回
 /// 质心向量
-pub fn compute_centroid(vectors: &[Vec<f32>]) -> Result<Vec<f32>, String> {
+pub fn compute_centroid(vectors: &[Vec<f32>]) -> Result<Vec<f32>, BbqError> {
     if vectors.is_empty() {
-        return Err("向量集合不能为空".to_string());
+        return Err(BbqError::empty_input("向量集合不能为空", "vector collection must not be empty"));
     }
 
     let first_vector = &vectors[0];
@@ -81,26 +211,216 @@ pub fn compute_centroid(vectors: &[Vec<f32>]) -> Result<Vec<f32>, String> {
     let mut centroid = vec![0.0; dimension];
 
     // 初始化质心为第一个向量
-    for i in 0..dimension {
-        centroid[i] = first_vector[i];
-    }
+    centroid.copy_from_slice(first_vector);
 
     // 从第二个向量开始累加
     for vector in vectors.iter().skip(1) {
-        for i in 0..dimension {
-            centroid[i] += vector[i];
+        for (c, v) in centroid.iter_mut().zip(vector.iter()) {
+            *c += v;
         }
     }
 
     // 除以向量数量
     let num_vectors = vectors.len() as f32;
-    for i in 0..dimension {
-        centroid[i] /= num_vectors;
+    for c in centroid.iter_mut() {
+        *c /= num_vectors;
     }
 
     Ok(centroid)
 }
 
+/// 以f64累加计算f32或f64向量集合的质心，结果与输入同类型
+///
+/// f64数据可以保留全部精度，再用 `OptimizedScalarQuantizer::scalar_quantize_generic` 量化
+///
+/// # 参数
+/// * `vectors` - 向量集合
+///
+/// # 返回
+/// 质心向量
+pub fn compute_centroid_precise<T: Float>(vectors: &[Vec<T>]) -> Result<Vec<T>, BbqError> {
+    if vectors.is_empty() {
+        return Err(BbqError::empty_input("向量集合不能为空", "vector collection must not be empty"));
+    }
+
+    let dimension = vectors[0].len();
+    let mut sums = vec![0.0f64; dimension];
+    for vector in vectors {
+        if vector.len() != dimension {
+            return Err(BbqError::dimension_mismatch(
+                format!("向量维度 {} 与第一个向量的维度 {} 不一致", vector.len(), dimension),
+                format!("vector dimension {} differs from the first vector's dimension {}", vector.len(), dimension),
+            ));
+        }
+        for (sum, &v) in sums.iter_mut().zip(vector) {
+            *sum += v.to_f64();
+        }
+    }
+
+    let num_vectors = vectors.len() as f64;
+    Ok(sums.into_iter().map(|sum| T::from_f64(sum / num_vectors)).collect())
+}
+
+/// 补偿求和（Neumaier算法）
+///
+/// 在f32累加的同时记录每一步的舍入误差并在最后补回，
+/// 百万级累加时误差与单次加法同量级，而朴素累加的误差随次数线性增长
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompensatedSum {
+    sum: f32,
+    compensation: f32,
+}
+
+impl CompensatedSum {
+    /// 创建值为0的累加器
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 累加一个值
+    #[inline]
+    pub fn add(&mut self, value: f32) {
+        let total = self.sum + value;
+        if self.sum.abs() >= value.abs() {
+            self.compensation += (self.sum - total) + value;
+        } else {
+            self.compensation += (value - total) + self.sum;
+        }
+        self.sum = total;
+    }
+
+    /// 补偿后的和
+    #[inline]
+    pub fn value(&self) -> f32 {
+        self.sum + self.compensation
+    }
+}
+
+/// 使用补偿求和计算向量集合的质心
+///
+/// 大规模语料下朴素累加会使质心产生可观的偏差，索引构建使用此版本
+///
+/// # 参数
+/// * `vectors` - 向量集合
+///
+/// # 返回
+/// 质心向量
+pub fn compute_centroid_compensated(vectors: &[Vec<f32>]) -> Result<Vec<f32>, BbqError> {
+    if vectors.is_empty() {
+        return Err(BbqError::empty_input("向量集合不能为空", "vector collection must not be empty"));
+    }
+
+    let dimension = vectors[0].len();
+    let mut sums = vec![CompensatedSum::new(); dimension];
+    for vector in vectors {
+        for (sum, &v) in sums.iter_mut().zip(vector.iter()) {
+            sum.add(v);
+        }
+    }
+
+    let num_vectors = vectors.len() as f32;
+    Ok(sums.iter().map(|sum| sum.value() / num_vectors).collect())
+}
+
+/// 每维统计量（均值与标准差）
+#[derive(Debug, Clone, PartialEq)]
+pub struct DimensionStatistics {
+    /// 每维均值
+    pub mean: Vec<f32>,
+    /// 每维标准差
+    pub std: Vec<f32>,
+}
+
+impl DimensionStatistics {
+    /// 向量维度
+    pub fn dimension(&self) -> usize {
+        self.mean.len()
+    }
+
+    /// 检查统计量是否有效
+    pub fn validate(&self) -> Result<(), BbqError> {
+        if self.mean.is_empty() {
+            return Err(BbqError::empty_input("统计量维度不能为0", "statistics dimension must not be 0"));
+        }
+        if self.mean.len() != self.std.len() {
+            return Err(BbqError::dimension_mismatch(
+                format!("均值维度 {} 与标准差维度 {} 不一致", self.mean.len(), self.std.len()),
+                format!("mean dimension {} differs from std dimension {}", self.mean.len(), self.std.len()),
+            ));
+        }
+        if let Some(j) = self.mean.iter().position(|v| !v.is_finite()) {
+            return Err(BbqError::invalid_value(
+                format!("均值位置 {} 包含无效值: {}", j, self.mean[j]),
+                format!("mean at position {} is invalid: {}", j, self.mean[j]),
+            ));
+        }
+        if let Some(j) = self.std.iter().position(|v| !v.is_finite() || *v < 0.0) {
+            return Err(BbqError::invalid_value(
+                format!("标准差位置 {} 无效: {}", j, self.std[j]),
+                format!("std at position {} is invalid: {}", j, self.std[j]),
+            ));
+        }
+        Ok(())
+    }
+
+    /// 中心化后各分量的期望标准差：sqrt(mean(std_i^2))
+    pub fn pooled_std(&self) -> f32 {
+        let sum_sq: f32 = self.std.iter().map(|s| s * s).sum();
+        (sum_sq / self.std.len().max(1) as f32).sqrt()
+    }
+}
+
+/// 计算向量集合的每维统计量
+///
+/// # 参数
+/// * `vectors` - 向量集合
+///
+/// # 返回
+/// 每维均值与标准差
+pub fn compute_dimension_statistics(vectors: &[Vec<f32>]) -> Result<DimensionStatistics, BbqError> {
+    let mean = compute_centroid_compensated(vectors)?;
+    let dimension = mean.len();
+    let mut variance = vec![0.0f32; dimension];
+    for vector in vectors {
+        if vector.len() != dimension {
+            return Err(BbqError::dimension_mismatch(
+                format!("向量维度 {} 与第一个向量维度 {} 不匹配", vector.len(), dimension),
+                format!("vector dimension {} does not match the first vector's dimension {}", vector.len(), dimension),
+            ));
+        }
+        for ((var, v), m) in variance.iter_mut().zip(vector.iter()).zip(mean.iter()) {
+            *var += (v - m) * (v - m);
+        }
+    }
+    let count = vectors.len() as f32;
+    let std = variance.into_iter().map(|var| (var / count).sqrt()).collect();
+    Ok(DimensionStatistics { mean, std })
+}
+
+/// 蓄水池抽样：从序列中等概率抽取最多sample_size个元素
+///
+/// # 返回
+/// 按升序排列的抽样结果
+pub fn reservoir_sample<I: IntoIterator<Item = usize>, R: RngSource + ?Sized>(
+    items: I,
+    sample_size: usize,
+    rng: &mut R,
+) -> Vec<usize> {
+    let mut reservoir = Vec::with_capacity(sample_size);
+    for (seen, item) in items.into_iter().enumerate() {
+        if reservoir.len() < sample_size {
+            reservoir.push(item);
+        } else {
+            let slot = rng.usize(0..seen + 1);
+            if slot < sample_size {
+                reservoir[slot] = item;
+            }
+        }
+    }
+    reservoir.sort_unstable();
+    reservoir
+}
+
 /// 计算向量点积
 /// 
 /// # 参数
@@ -116,6 +436,24 @@ pub fn compute_dot_product(a: &[f32], b: &[f32]) -> f32 {
         .sum()
 }
 
+/// 使用补偿求和计算向量点积
+///
+/// 精确重排路径使用此版本，避免高维时的累加误差影响候选的排序
+///
+/// # 参数
+/// * `a` - 向量a
+/// * `b` - 向量b
+///
+/// # 返回
+/// 点积结果
+pub fn compute_dot_product_compensated(a: &[f32], b: &[f32]) -> f32 {
+    let mut sum = CompensatedSum::new();
+    for (av, bv) in a.iter().zip(b.iter()) {
+        sum.add(av * bv);
+    }
+    sum.value()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -142,6 +480,61 @@ mod tests {
         assert!((magnitude - 1.0).abs() < 0.0001);
     }
 
+    #[test]
+    fn test_dimension_statistics() {
+        let vectors = vec![vec![1.0, 0.0], vec![3.0, 0.0]];
+        let stats = compute_dimension_statistics(&vectors).unwrap();
+        assert_eq!(stats.mean, vec![2.0, 0.0]);
+        assert_eq!(stats.std, vec![1.0, 0.0]);
+        assert!((stats.pooled_std() - 0.5f32.sqrt()).abs() < 1e-6);
+        assert!(DimensionStatistics { mean: vec![0.0], std: vec![-1.0] }.validate().is_err());
+    }
+
+    #[test]
+    fn test_synthetic_generators() {
+        let mixture = generate_gaussian_mixture(100, 8, 4, 0.05, 7).unwrap();
+        assert_eq!(mixture, generate_gaussian_mixture(100, 8, 4, 0.05, 7).unwrap());
+        assert_eq!(mixture.len(), 100);
+        assert!(generate_gaussian_mixture(10, 8, 0, 0.1, 7).is_err());
+
+        let sphere = generate_unit_sphere(20, 16, 7);
+        assert!(sphere.iter().all(|v| (compute_vector_magnitude(v) - 1.0).abs() < 1e-5));
+
+        // 低自由度的t分布比高斯分布更容易出现离群值
+        let heavy = generate_heavy_tailed(200, 16, 1, 7).unwrap();
+        let max_abs = heavy.iter().flatten().fold(0.0f32, |m, v| m.max(v.abs()));
+        assert!(max_abs > 10.0 && max_abs.is_finite());
+    }
+
+    #[test]
+    fn test_injected_rng_source() {
+        // 只给种子的接口与直接传入同一种子的fastrand结果一致
+        let seeded = generate_unit_sphere(5, 8, 3);
+        assert_eq!(seeded, generate_unit_sphere_with(5, 8, &mut fastrand::Rng::with_seed(3)));
+
+        // 常数来源得到确定的结果
+        let mut constant = crate::rng::FnRng(|| 0);
+        assert_eq!(create_random_vector_with(3, -1.0, 1.0, &mut constant), vec![-1.0; 3]);
+        // 每个后续元素都替换第0个槽位
+        assert_eq!(reservoir_sample(0..10, 4, &mut constant), vec![1, 2, 3, 9]);
+    }
+
+    #[test]
+    fn test_compensated_summation() {
+        // 1e8 + 1 在f32下丢失了1，补偿求和可以找回
+        let a = [1e8, 1.0, -1e8];
+        assert_eq!(compute_dot_product(&a, &[1.0; 3]), 0.0);
+        assert_eq!(compute_dot_product_compensated(&a, &[1.0; 3]), 1.0);
+
+        let mut rng = fastrand::Rng::with_seed(3);
+        let vectors: Vec<Vec<f32>> = (0..200_000).map(|_| vec![1.0 + rng.f32()]).collect();
+        let mean = (vectors.iter().map(|v| v[0] as f64).sum::<f64>() / vectors.len() as f64) as f32;
+        let naive_error = (compute_centroid(&vectors).unwrap()[0] - mean).abs();
+        let compensated_error = (compute_centroid_compensated(&vectors).unwrap()[0] - mean).abs();
+        assert!(compensated_error <= f32::EPSILON * 2.0, "{}", compensated_error);
+        assert!(naive_error > compensated_error * 10.0, "{} {}", naive_error, compensated_error);
+    }
+
     #[test]
     fn test_dot_product() {
         let a = vec![1.0, 2.0, 3.0];
@@ -0,0 +1,208 @@
+//! 索引使用统计
+//!
+//! 记录查询次数、平均k、重复查询和命中最多的序号，随索引一起保存。
+//! 重新加载后据此只预热热点向量、选择结果缓存容量，
+//! 缩短回访用户的冷启动延迟；统计是近似的，只用于调优而不影响搜索结果
+
+use std::collections::{HashMap, HashSet};
+
+use crate::error_codes::{BbqError, Subject};
+use crate::byte_reader::ByteReader;
+use crate::memory_limits::checked_region_len;
+
+/// 使用统计魔数
+const WARM_STATS_MAGIC: &[u8; 4] = b"BBQW";
+
+/// 使用统计格式版本
+const WARM_STATS_FORMAT_VERSION: u8 = 1;
+
+/// 保存时保留的热点序号上限
+pub const MAX_PERSISTED_HOT_ORDINALS: usize = 1024;
+
+/// 用于识别重复查询的指纹上限，超出后新查询不再计入
+const MAX_TRACKED_QUERIES: usize = 4096;
+
+/// 建议的结果缓存容量上限
+const MAX_SUGGESTED_CACHE_CAPACITY: usize = 256;
+
+/// 索引使用统计
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WarmStats {
+    /// 查询次数
+    query_count: u64,
+    /// 所有查询的k之和
+    total_k: u64,
+    /// 不同查询的数量（近似：重新加载后已见过的查询会再计一次）
+    distinct_queries: u64,
+    /// 与之前某次查询完全相同的查询次数
+    repeat_queries: u64,
+    /// 序号 → 出现在结果中的次数
+    ordinal_hits: HashMap<usize, u64>,
+    /// 本次会话见过的查询指纹，不保存
+    seen_queries: HashSet<u64>,
+}
+
+impl WarmStats {
+    /// 记录一次查询
+    ///
+    /// # 参数
+    /// * `fingerprint` - 查询指纹，用于识别重复查询
+    /// * `k` - 请求的结果数量
+    /// * `ordinals` - 返回结果的序号
+    pub fn record_query(&mut self, fingerprint: u64, k: usize, ordinals: impl IntoIterator<Item = usize>) {
+        self.query_count += 1;
+        self.total_k += k as u64;
+        if self.seen_queries.contains(&fingerprint) {
+            self.repeat_queries += 1;
+        } else if self.seen_queries.len() < MAX_TRACKED_QUERIES {
+            self.seen_queries.insert(fingerprint);
+            self.distinct_queries += 1;
+        }
+        for ord in ordinals {
+            *self.ordinal_hits.entry(ord).or_insert(0) += 1;
+        }
+    }
+
+    /// 查询次数
+    pub fn query_count(&self) -> u64 {
+        self.query_count
+    }
+
+    /// 平均k，没有查询时为None
+    pub fn average_k(&self) -> Option<f64> {
+        (self.query_count > 0).then(|| self.total_k as f64 / self.query_count as f64)
+    }
+
+    /// 重复查询占全部查询的比例
+    pub fn repeat_rate(&self) -> f64 {
+        if self.query_count == 0 {
+            0.0
+        } else {
+            self.repeat_queries as f64 / self.query_count as f64
+        }
+    }
+
+    /// 命中次数最多的序号，按次数降序（次数相同时序号小的在前）
+    pub fn hot_ordinals(&self, limit: usize) -> Vec<usize> {
+        let mut hits: Vec<(usize, u64)> = self.ordinal_hits.iter().map(|(&ord, &count)| (ord, count)).collect();
+        hits.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hits.into_iter().take(limit).map(|(ord, _)| ord).collect()
+    }
+
+    /// 建议的结果缓存容量
+    ///
+    /// 从未出现重复查询时缓存没有收益，返回0；否则按不同查询的数量给出，不超过256
+    pub fn suggested_result_cache_capacity(&self) -> usize {
+        if self.repeat_queries == 0 {
+            return 0;
+        }
+        (self.distinct_queries as usize).min(MAX_SUGGESTED_CACHE_CAPACITY)
+    }
+
+    /// 按压缩后保留的序号重新编号，被移除的序号的统计丢弃
+    ///
+    /// # 参数
+    /// * `live` - 保留的旧序号（升序），新序号为其在数组中的位置
+    pub fn remap(&mut self, live: &[usize]) {
+        self.ordinal_hits = self.ordinal_hits.drain()
+            .filter_map(|(ord, count)| live.binary_search(&ord).ok().map(|new_ord| (new_ord, count)))
+            .collect();
+    }
+
+    /// 序列化为字节数组，只保留最热的 `MAX_PERSISTED_HOT_ORDINALS` 个序号
+    ///
+    /// 格式（小端）：魔数 | 格式版本 | 查询次数 | k之和 | 不同查询数 | 重复查询数 | 热点数量 | (序号, 次数)*
+    pub fn serialize(&self) -> Vec<u8> {
+        let hot = self.hot_ordinals(MAX_PERSISTED_HOT_ORDINALS);
+        let mut bytes = Vec::with_capacity(41 + hot.len() * 12);
+        bytes.extend_from_slice(WARM_STATS_MAGIC);
+        bytes.push(WARM_STATS_FORMAT_VERSION);
+        bytes.extend_from_slice(&self.query_count.to_le_bytes());
+        bytes.extend_from_slice(&self.total_k.to_le_bytes());
+        bytes.extend_from_slice(&self.distinct_queries.to_le_bytes());
+        bytes.extend_from_slice(&self.repeat_queries.to_le_bytes());
+        bytes.extend_from_slice(&(hot.len() as u32).to_le_bytes());
+        for ord in hot {
+            bytes.extend_from_slice(&(ord as u32).to_le_bytes());
+            bytes.extend_from_slice(&self.ordinal_hits[&ord].to_le_bytes());
+        }
+        bytes
+    }
+
+    /// 从字节数组恢复
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, BbqError> {
+        let mut reader = ByteReader::new(bytes);
+        if reader.take(4)? != WARM_STATS_MAGIC {
+            return Err(BbqError::corrupt_data("无效的使用统计：魔数不匹配", "invalid usage statistics: magic mismatch"));
+        }
+        let format_version = reader.read_u8()?;
+        if format_version != WARM_STATS_FORMAT_VERSION {
+            return Err(BbqError::unsupported_version(
+                format!("不支持的使用统计版本: {}", format_version),
+                format!("unsupported usage statistics version: {}", format_version),
+            ));
+        }
+        let query_count = reader.read_u64()?;
+        let total_k = reader.read_u64()?;
+        let distinct_queries = reader.read_u64()?;
+        let repeat_queries = reader.read_u64()?;
+
+        let hot_count = reader.read_u32()? as usize;
+        if checked_region_len(hot_count, 12, Subject::new("使用统计", "usage statistics"))? != reader.remaining() {
+            return Err(BbqError::corrupt_data("无效的使用统计：热点数量与数据长度不符", "invalid usage statistics: hot entry count does not match the data length"));
+        }
+        let mut ordinal_hits = HashMap::with_capacity(hot_count);
+        for _ in 0..hot_count {
+            let ord = reader.read_u32()? as usize;
+            ordinal_hits.insert(ord, reader.read_u64()?);
+        }
+
+        Ok(Self {
+            query_count,
+            total_k,
+            distinct_queries,
+            repeat_queries,
+            ordinal_hits,
+            seen_queries: HashSet::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_round_trip() {
+        let mut stats = WarmStats::default();
+        assert_eq!(stats.average_k(), None);
+        assert_eq!(stats.suggested_result_cache_capacity(), 0);
+
+        stats.record_query(1, 10, [3, 5, 7]);
+        stats.record_query(2, 20, [5, 7]);
+        stats.record_query(1, 10, [7]);
+        assert_eq!(stats.query_count(), 3);
+        assert!((stats.average_k().unwrap() - 40.0 / 3.0).abs() < 1e-9);
+        assert!((stats.repeat_rate() - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(stats.hot_ordinals(2), vec![7, 5]);
+        assert_eq!(stats.suggested_result_cache_capacity(), 2);
+
+        let restored = WarmStats::deserialize(&stats.serialize()).unwrap();
+        assert_eq!(restored.query_count(), 3);
+        assert_eq!(restored.hot_ordinals(10), vec![7, 5, 3]);
+        assert_eq!(restored.suggested_result_cache_capacity(), 2);
+
+        let bytes = stats.serialize();
+        assert!(WarmStats::deserialize(&bytes[..bytes.len() - 1]).is_err());
+        assert!(WarmStats::deserialize(b"BBQF").is_err());
+    }
+
+    #[test]
+    fn test_remap_drops_removed_ordinals() {
+        let mut stats = WarmStats::default();
+        stats.record_query(1, 3, [0, 2, 4]);
+        stats.record_query(2, 1, [4]);
+        stats.remap(&[0, 1, 3, 4]);
+        assert_eq!(stats.hot_ordinals(10), vec![3, 0]);
+    }
+}
@@ -0,0 +1,76 @@
+//! 索引预热
+//!
+//! 在首次查询前遍历所有打包缓冲区和修正项，使内存页和缓存就绪，
+//! 并用一次探测查询走通评分内核，避免首个用户查询承担初始化开销
+
+use crate::error_codes::BbqError;
+use crate::quantized_index::QuantizedVectorValues;
+
+/// 预热报告
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WarmupReport {
+    /// 遍历的向量数量
+    pub vectors_touched: usize,
+    /// 遍历的字节数（打包向量 + 修正项）
+    pub bytes_touched: usize,
+    /// 遍历缓冲区耗时（毫秒）
+    pub touch_ms: f64,
+    /// 探测查询耗时（毫秒）
+    pub probe_ms: f64,
+    /// 总耗时（毫秒）
+    pub total_ms: f64,
+}
+
+/// 遍历所有量化向量与修正项
+///
+/// # 参数
+/// * `values` - 量化向量值
+///
+/// # 返回
+/// (遍历的字节数, 校验和)；校验和仅用于防止读取被优化掉
+pub fn touch_vector_values(values: &dyn QuantizedVectorValues) -> Result<(usize, u64), BbqError> {
+    let mut bytes = 0usize;
+    let mut checksum = 0u64;
+
+    for (_, vector, corrections) in values.iter() {
+        bytes += vector.len();
+        for &byte in vector {
+            checksum = checksum.wrapping_mul(31).wrapping_add(byte as u64);
+        }
+
+        bytes += 4 * std::mem::size_of::<f32>();
+        checksum ^= corrections.lower_interval.to_bits() as u64
+            ^ corrections.upper_interval.to_bits() as u64
+            ^ corrections.additional_correction.to_bits() as u64
+            ^ corrections.quantized_component_sum.to_bits() as u64;
+    }
+
+    bytes += std::mem::size_of_val(values.get_centroid());
+    Ok((bytes, std::hint::black_box(checksum)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimized_scalar_quantizer::QuantizationResult;
+    use crate::quantized_index::QuantizedVectorValuesImpl;
+
+    #[test]
+    fn test_touch_counts_bytes() {
+        let correction = QuantizationResult {
+            lower_interval: 0.0,
+            upper_interval: 1.0,
+            additional_correction: 0.0,
+            quantized_component_sum: 0.0,
+        };
+        let values = QuantizedVectorValuesImpl::new(
+            vec![vec![1, 2], vec![3, 4]],
+            vec![vec![0; 16], vec![0; 16]],
+            vec![correction.clone(), correction],
+            vec![0.0; 16],
+            vec![1.0, 1.0],
+        );
+        let (bytes, _) = touch_vector_values(&values).unwrap();
+        assert_eq!(bytes, 2 * 2 + 2 * 16 + 16 * 4);
+    }
+}
@@ -1,8 +1,8 @@
-/// WASM接口层
-/// 将Rust函数导出为JavaScript可调用的WASM函数
+//! WASM接口层
+//! 将Rust函数导出为JavaScript可调用的WASM函数
 
 use wasm_bindgen::prelude::*;
-use crate::vector_similarity::{SimilarityFunction, compute_similarity};
+use crate::vector_similarity::{SimilarityFunction, compute_similarity, compute_similarity_precise};
 use crate::bitwise_dot_product::{
     compute_quantized_dot_product,
     compute_int4_bit_dot_product,
@@ -12,9 +12,87 @@ use crate::batch_dot_product::{
     compute_batch_four_bit_dot_product_direct_packed,
     compute_batch_one_bit_dot_product_direct_packed,
 };
-use crate::optimized_scalar_quantizer::{OptimizedScalarQuantizer, QuantizationResult};
-use crate::binary_quantized_scorer::BinaryQuantizedScorer;
-use crate::quantized_index::{QuantizedIndex, QuantizedIndexConfig};
+use crate::optimized_scalar_quantizer::OptimizedScalarQuantizer;
+#[cfg(feature = "scorer")]
+use crate::optimized_scalar_quantizer::CorrectionPrecision;
+use crate::optimized_scalar_quantizer::QuantizationResult;
+use crate::validation::validate_vectors;
+use crate::rng::{set_default_rng, RngSource};
+#[cfg(feature = "scorer")]
+use crate::binary_quantized_scorer::{benchmark_scoring_precision, BinaryQuantizedScorer};
+#[cfg(feature = "index")]
+use crate::binary_quantized_scorer::ScoringPrecision;
+#[cfg(feature = "index")]
+use crate::quantized_index::{BudgetedSearchResults, DegenerateVectorPolicy, PackedVectorRecord, QuantizedIndex, QuantizedIndexConfig, QueryResult, RescoreOversample, SearchParams};
+#[cfg(all(feature = "index", feature = "serde"))]
+use crate::quantized_index::IndexOp;
+#[cfg(feature = "index")]
+use crate::search_experiment::{ExperimentLayout, SearchExperiment};
+#[cfg(feature = "index")]
+use crate::score_normalization::ScoreNormalization;
+#[cfg(feature = "index")]
+use crate::score_transform::ScoreTransform;
+#[cfg(feature = "index")]
+use crate::score_fusion::{FusionStrategy, fuse_with_external_scores};
+#[cfg(feature = "index")]
+use crate::result_encoding::{decode_query_results, encode_query_results};
+#[cfg(all(feature = "index", feature = "serde"))]
+use crate::chunk_grouping::{ChunkGroupingParams, ChunkProvenance, ChunkScoreCombine};
+#[cfg(all(feature = "index", feature = "serde"))]
+use crate::bbq::{Bbq, BbqOptions, DuplicatePolicy};
+#[cfg(all(feature = "index", feature = "serde"))]
+use crate::embedding_cache::EmbeddingCache;
+use crate::vector_similarity::parse_metric;
+#[cfg(feature = "scorer")]
+use crate::edge_scorer::EdgeScorer;
+#[cfg(feature = "scorer")]
+use crate::correction_layout::benchmark_correction_layouts;
+#[cfg(feature = "index")]
+use crate::correction_layout::CorrectionLayout;
+#[cfg(feature = "index")]
+use crate::query_pack::{ProgressiveResults, ProgressiveSearch, QueryPack, QueryPackLayout, export_query_pack};
+#[cfg(feature = "index")]
+use crate::incremental_search::IncrementalSearch;
+#[cfg(feature = "index")]
+use crate::es_segment::{load_lucene_bbq_vectors, similarity_from_es_name};
+#[cfg(feature = "index")]
+use crate::vector_utils::DimensionStatistics;
+#[cfg(all(feature = "index", feature = "serde"))]
+use crate::filter::{Attributes, Filter};
+#[cfg(all(feature = "index", feature = "serde"))]
+use crate::filter_expression::parse_filter_expression;
+#[cfg(feature = "index")]
+use crate::timer::now_ms;
+#[cfg(feature = "index")]
+use crate::warm_stats::WarmStats;
+#[cfg(feature = "index")]
+use crate::warmup::WarmupReport;
+#[cfg(all(feature = "index", feature = "serde"))]
+use crate::replica::{Replica, VersionVector};
+#[cfg(feature = "serde")]
+use crate::capabilities::capabilities;
+use crate::batch_sizing::{benchmark_batch_sizes, recommended_batch_size, set_cache_size_hint};
+#[cfg(feature = "ivf")]
+use crate::ivf::{IvfStatistics, ProbeStrategy, DEFAULT_PROBE_MARGIN};
+#[cfg(feature = "index")]
+use crate::original_vectors::OriginalVectorEncoding;
+#[cfg(feature = "index")]
+use crate::migration::{migrate, MigrationSource};
+#[cfg(feature = "graph")]
+use crate::projection::ProjectionConfig;
+#[cfg(feature = "index")]
+use crate::progress::{ProgressEvent, ProgressObserver};
+use crate::error_reporting::{install_panic_hook, report_error, set_error_sink, ErrorKind, ErrorReport, ErrorSink};
+#[cfg(feature = "index")]
+use crate::error_reporting::{config_fingerprint, OperationScope};
+use crate::runtime_init::{initialize_runtime, is_runtime_initialized};
+use crate::memory_limits::{heap_stats, OutOfMemory};
+use crate::error_codes::{error_locale, set_error_locale, BbqError, ErrorLocale};
+#[cfg(feature = "serde")]
+use crate::error_codes::ErrorCode;
+#[cfg(all(feature = "index", feature = "serde"))]
+use crate::error_codes::Subject;
+use crate::kernel_dispatch::{KernelSelection, KernelVariant, calibrate_kernels, selected_kernels, set_kernel_selection};
 
 /// WASM: 计算向量相似性
 /// 
@@ -35,25 +113,35 @@ pub fn wasm_compute_similarity(
         "euclidean" => SimilarityFunction::Euclidean,
         "cosine" => SimilarityFunction::Cosine,
         "dot_product" | "maximum_inner_product" => SimilarityFunction::MaximumInnerProduct,
-        _ => return Err(JsValue::from_str(&format!("不支持的相似性类型: {}", similarity_type))),
+        _ => return Err(js_error(BbqError::invalid_argument(
+            format!("不支持的相似性类型: {}", similarity_type),
+            format!("unsupported similarity: {}", similarity_type),
+        ))),
     };
 
     compute_similarity(a, b, sim_func)
-        .map_err(|e| JsValue::from_str(&e))
+        .map_err(js_error)
+}
+
+/// WASM: 以f64累加计算Float64Array向量的相似性
+#[wasm_bindgen]
+pub fn wasm_compute_similarity_f64(a: &[f64], b: &[f64], similarity_type: &str) -> Result<f64, JsValue> {
+    let sim_func = parse_metric(similarity_type).map_err(js_error)?;
+    compute_similarity_precise(a, b, sim_func).map_err(js_error)
 }
 
 /// WASM: 计算欧几里得距离
 #[wasm_bindgen]
 pub fn wasm_compute_euclidean_distance(a: &[f32], b: &[f32]) -> Result<f32, JsValue> {
     crate::vector_similarity::compute_euclidean_distance(a, b)
-        .map_err(|e| JsValue::from_str(&e))
+        .map_err(js_error)
 }
 
 /// WASM: 计算余弦相似度
 #[wasm_bindgen]
 pub fn wasm_compute_cosine_similarity(a: &[f32], b: &[f32]) -> Result<f32, JsValue> {
     crate::vector_similarity::compute_cosine_similarity(a, b)
-        .map_err(|e| JsValue::from_str(&e))
+        .map_err(js_error)
 }
 
 /// WASM: 计算点积
@@ -62,6 +150,20 @@ pub fn wasm_compute_dot_product(a: &[f32], b: &[f32]) -> f32 {
     crate::vector_utils::compute_dot_product(a, b)
 }
 
+/// WASM: 批量点积（查询对连续存放的多个向量）
+#[wasm_bindgen]
+pub fn wasm_compute_batch_dot_products(query: &[f32], vectors: &[f32], dimension: usize) -> Result<Vec<f32>, JsValue> {
+    crate::vector_similarity::compute_batch_dot_products(query, vectors, dimension)
+        .map_err(js_error)
+}
+
+/// WASM: 批量平方欧氏距离（查询对连续存放的多个向量）
+#[wasm_bindgen]
+pub fn wasm_compute_batch_squared_distances(query: &[f32], vectors: &[f32], dimension: usize) -> Result<Vec<f32>, JsValue> {
+    crate::vector_similarity::compute_batch_squared_distances(query, vectors, dimension)
+        .map_err(js_error)
+}
+
 /// WASM: 计算向量模长
 #[wasm_bindgen]
 pub fn wasm_compute_vector_magnitude(vector: &[f32]) -> f32 {
@@ -72,21 +174,21 @@ pub fn wasm_compute_vector_magnitude(vector: &[f32]) -> f32 {
 #[wasm_bindgen]
 pub fn wasm_compute_quantized_dot_product(q: &[u8], d: &[u8]) -> Result<i32, JsValue> {
     compute_quantized_dot_product(q, d)
-        .map_err(|e| JsValue::from_str(&e))
+        .map_err(js_error)
 }
 
 /// WASM: 计算4位-1位点积
 #[wasm_bindgen]
 pub fn wasm_compute_int4_bit_dot_product(q: &[u8], d: &[u8]) -> Result<i32, JsValue> {
     compute_int4_bit_dot_product(q, d)
-        .map_err(|e| JsValue::from_str(&e))
+        .map_err(js_error)
 }
 
 /// WASM: 计算1位-1位点积
 #[wasm_bindgen]
 pub fn wasm_compute_int1_bit_dot_product(q: &[u8], d: &[u8]) -> Result<i32, JsValue> {
     compute_int1_bit_dot_product(q, d)
-        .map_err(|e| JsValue::from_str(&e))
+        .map_err(js_error)
 }
 
 /// WASM: 批量计算4位点积
@@ -121,10 +223,408 @@ pub fn wasm_compute_batch_one_bit_dot_product(
     )
 }
 
-/// WASM: 创建随机向量
+#[cfg(feature = "serde")]
+/// WASM: 报告模块编译时启用的特性和运行环境支持的特性
+///
+/// # 返回
+/// `{ version, build: { simd128, threads, memory64, panicHook, kernels }, runtime: { simd128, threads, sharedArrayBuffer, memory64 } }`
+#[wasm_bindgen]
+pub fn wasm_capabilities() -> Result<JsValue, JsValue> {
+    serde_wasm_bindgen::to_value(&capabilities())
+        .map_err(conversion_error)
+}
+
+/// WASM: 对可用内核计时并选出每种操作最快的实现
+///
+/// # 返回
+/// `{ oneBit, fourBit, timings: [{ operation, kernel, msPerBatch }] }`
+#[wasm_bindgen]
+pub fn wasm_calibrate_kernels() -> Result<JsValue, JsValue> {
+    let report = calibrate_kernels();
+    let js_report = kernel_selection_to_js(report.selection)?;
+
+    let js_timings = js_sys::Array::new();
+    for timing in &report.timings {
+        let js_timing = js_sys::Object::new();
+        js_sys::Reflect::set(&js_timing, &JsValue::from_str("operation"), &JsValue::from_str(timing.operation))?;
+        js_sys::Reflect::set(&js_timing, &JsValue::from_str("kernel"), &JsValue::from_str(timing.variant.name()))?;
+        js_sys::Reflect::set(&js_timing, &JsValue::from_str("msPerBatch"), &JsValue::from_f64(timing.ms_per_batch))?;
+        js_timings.push(&js_timing);
+    }
+    js_sys::Reflect::set(&js_report, &JsValue::from_str("timings"), &js_timings)?;
+
+    Ok(js_report)
+}
+
+/// 把错误转换为JS异常，同时报告给已注册的错误接收器
+///
+/// 抛出 `name` 为 "BbqError" 的Error对象，`message` 按当前错误消息语言给出（默认英文），
+/// 附带与语言无关的 `code`（见 `ErrorCode::name`）和原始中文消息 `detail`。
+/// 内存不足时 `name` 为 "OutOfMemoryError"，另外附带 `what`、`requestedBytes`、
+/// `heapBytes`（非WASM构建中为null）和 `maxBytes`，宿主可据此降载或分片
+fn js_error(error: BbqError) -> JsValue {
+    let message = error.message();
+    let js_error = if let Some(details) = error.out_of_memory_details() {
+        report_error(ErrorKind::OutOfMemory, message);
+        out_of_memory_error(message, details)
+    } else {
+        report_error(ErrorKind::Error, message);
+        let js_error = js_sys::Error::new(message);
+        js_error.set_name("BbqError");
+        js_error
+    };
+    // Error对象总是可扩展的，设置属性不会失败
+    let _ = js_sys::Reflect::set(&js_error, &JsValue::from_str("code"), &JsValue::from_str(error.code().name()));
+    let _ = js_sys::Reflect::set(&js_error, &JsValue::from_str("detail"), &JsValue::from_str(error.detail()));
+    js_error.into()
+}
+
+/// WASM: 设置错误消息的语言："en"（默认）| "zh"
+#[wasm_bindgen(js_name = setErrorLocale)]
+pub fn wasm_set_error_locale(locale: &str) -> Result<(), JsValue> {
+    set_error_locale(ErrorLocale::from_name(locale).map_err(js_error)?);
+    Ok(())
+}
+
+/// WASM: 当前错误消息的语言
+#[wasm_bindgen(js_name = errorLocale)]
+pub fn wasm_error_locale() -> String {
+    error_locale().name().to_string()
+}
+
+/// 构造内存不足的JS异常，`what` 按当前错误消息语言给出
+fn out_of_memory_error(message: &str, details: &OutOfMemory) -> js_sys::Error {
+    let error = js_sys::Error::new(message);
+    error.set_name("OutOfMemoryError");
+    let heap_bytes = details.heap.heap_bytes.map_or(JsValue::NULL, |bytes| JsValue::from_f64(bytes as f64));
+    let fields = [
+        ("what", JsValue::from_str(details.what.in_locale(error_locale()))),
+        ("requestedBytes", JsValue::from_f64(details.requested_bytes as f64)),
+        ("heapBytes", heap_bytes),
+        ("maxBytes", JsValue::from_f64(details.heap.max_bytes as f64)),
+    ];
+    for (key, value) in fields {
+        // Error对象总是可扩展的，设置属性不会失败
+        let _ = js_sys::Reflect::set(&error, &JsValue::from_str(key), &value);
+    }
+    error
+}
+
+/// JS传入的参数无法反序列化
+#[cfg(all(feature = "index", feature = "serde"))]
+fn invalid_input(what: Subject, error: serde_wasm_bindgen::Error) -> JsValue {
+    js_error(BbqError::invalid_argument(
+        format!("无效的{}: {}", what.zh, error),
+        format!("invalid {}: {}", what.en, error),
+    ))
+}
+
+/// 结果无法转换为JS值
+#[cfg(feature = "serde")]
+fn conversion_error(error: serde_wasm_bindgen::Error) -> JsValue {
+    js_error(BbqError::new(
+        ErrorCode::Unknown,
+        format!("结果无法转换为JS值: {}", error),
+        format!("result cannot be converted to a JS value: {}", error),
+    ))
+}
+
+/// WASM: 当前堆状态 `{ heapBytes, maxBytes }`，非WASM构建中heapBytes为null
+#[wasm_bindgen(js_name = heapStats)]
+pub fn wasm_heap_stats() -> Result<JsValue, JsValue> {
+    let stats = heap_stats();
+    let result = js_sys::Object::new();
+    let heap_bytes = stats.heap_bytes.map_or(JsValue::NULL, |bytes| JsValue::from_f64(bytes as f64));
+    js_sys::Reflect::set(&result, &JsValue::from_str("heapBytes"), &heap_bytes)?;
+    js_sys::Reflect::set(&result, &JsValue::from_str("maxBytes"), &JsValue::from_f64(stats.max_bytes as f64))?;
+    Ok(result.into())
+}
+
+/// 把错误报告转发给JS回调
+#[cfg(not(target_feature = "atomics"))]
+struct JsErrorSink {
+    callback: js_sys::Function,
+}
+
+// 未启用atomics的WASM只有一个线程，回调不会离开创建它的线程
+#[cfg(not(target_feature = "atomics"))]
+unsafe impl Send for JsErrorSink {}
+#[cfg(not(target_feature = "atomics"))]
+unsafe impl Sync for JsErrorSink {}
+
+#[cfg(not(target_feature = "atomics"))]
+impl ErrorSink for JsErrorSink {
+    fn report(&self, report: &ErrorReport) {
+        let object = js_sys::Object::new();
+        let operation = report.operation.map_or(JsValue::NULL, JsValue::from_str);
+        // 指纹是64位整数，超出JS数值的精确范围，以十六进制字符串给出
+        let fingerprint = report.config_fingerprint
+            .map_or(JsValue::NULL, |fingerprint| JsValue::from_str(&format!("{:016x}", fingerprint)));
+        let fields = [
+            ("kind", JsValue::from_str(report.kind.name())),
+            ("operation", operation),
+            ("message", JsValue::from_str(&report.message)),
+            ("configFingerprint", fingerprint),
+        ];
+        for (key, value) in fields {
+            if js_sys::Reflect::set(&object, &JsValue::from_str(key), &value).is_err() {
+                return;
+            }
+        }
+        // 回调抛出的异常不影响出错的操作本身的错误传播
+        let _ = self.callback.call1(&JsValue::NULL, &object);
+    }
+}
+
+/// WASM: 注册错误接收器，传入null时移除
+///
+/// 之后每次操作返回错误或发生panic时，回调收到 `{ kind: "error" | "panic", operation, message, configFingerprint }`；
+/// panic时回调在模块中止之前同步调用
+#[cfg(not(target_feature = "atomics"))]
+#[wasm_bindgen(js_name = setErrorSink)]
+pub fn wasm_set_error_sink(callback: Option<js_sys::Function>) {
+    install_panic_hook();
+    set_error_sink(callback.map(|callback| std::sync::Arc::new(JsErrorSink { callback }) as std::sync::Arc<dyn ErrorSink>));
+}
+
+/// WASM: 立即完成延迟初始化（内核校准等），已初始化时直接返回
+///
+/// 不调用时会在第一次批量评分时自动执行；页面可以在空闲时调用，避免第一次搜索变慢
+///
+/// # 返回
+/// `{ oneBit, fourBit, initializedNow, elapsedMs }`
+#[wasm_bindgen(js_name = initializeRuntime)]
+pub fn wasm_initialize_runtime() -> Result<JsValue, JsValue> {
+    let report = initialize_runtime();
+    let js_report = kernel_selection_to_js(report.kernels)?;
+    js_sys::Reflect::set(&js_report, &JsValue::from_str("initializedNow"), &JsValue::from_bool(report.initialized_now))?;
+    js_sys::Reflect::set(&js_report, &JsValue::from_str("elapsedMs"), &JsValue::from_f64(report.elapsed_ms))?;
+    Ok(js_report)
+}
+
+/// WASM: 运行时是否已经完成延迟初始化
+#[wasm_bindgen(js_name = isRuntimeInitialized)]
+pub fn wasm_is_runtime_initialized() -> bool {
+    is_runtime_initialized()
+}
+
+/// WASM: 获取当前选中的内核 `{ oneBit, fourBit }`
+#[wasm_bindgen]
+pub fn wasm_selected_kernels() -> Result<JsValue, JsValue> {
+    kernel_selection_to_js(selected_kernels())
+}
+
+/// WASM: 手动指定内核
+///
+/// # 参数
+/// * `one_bit` / `four_bit` - "scalar" | "u64_popcount" | "simd128"
+#[wasm_bindgen]
+pub fn wasm_set_kernels(one_bit: &str, four_bit: &str) -> Result<(), JsValue> {
+    let selection = KernelSelection {
+        one_bit: KernelVariant::from_name(one_bit).map_err(js_error)?,
+        four_bit: KernelVariant::from_name(four_bit).map_err(js_error)?,
+    };
+    set_kernel_selection(selection);
+    Ok(())
+}
+
+/// WASM: 设置缓存大小提示（字节），传入0恢复默认值
+#[wasm_bindgen]
+pub fn wasm_set_cache_size_hint(bytes: usize) {
+    set_cache_size_hint(bytes);
+}
+
+/// WASM: 按维度和k计算推荐的批大小
+#[wasm_bindgen]
+pub fn wasm_recommended_batch_size(dimension: usize, k: usize) -> usize {
+    recommended_batch_size(dimension, k, None)
+}
+
+/// WASM: 对候选批大小计时
+///
+/// # 返回
+/// `[{ batchSize, usPerVector }]`
+#[wasm_bindgen]
+pub fn wasm_benchmark_batch_sizes(dimension: usize, batch_sizes: Vec<u32>) -> Result<JsValue, JsValue> {
+    let batch_sizes: Vec<usize> = batch_sizes.into_iter().map(|size| size as usize).collect();
+    let timings = benchmark_batch_sizes(dimension, &batch_sizes)
+        .map_err(js_error)?;
+
+    let js_timings = js_sys::Array::new();
+    for timing in &timings {
+        let js_timing = js_sys::Object::new();
+        js_sys::Reflect::set(&js_timing, &JsValue::from_str("batchSize"), &JsValue::from_f64(timing.batch_size as f64))?;
+        js_sys::Reflect::set(&js_timing, &JsValue::from_str("usPerVector"), &JsValue::from_f64(timing.us_per_vector))?;
+        js_timings.push(&js_timing);
+    }
+    Ok(js_timings.into())
+}
+
+#[cfg(feature = "scorer")]
+/// WASM: 对比AoS与SoA两种修正项布局的批量评分耗时
+///
+/// # 返回
+/// `[{ dimension, k, batchSize, aosNsPerVector, soaNsPerVector, faster }]`，`faster` 为 "aos" 或 "soa"
+#[wasm_bindgen]
+pub fn wasm_benchmark_correction_layouts(dimensions: Vec<u32>, ks: Vec<u32>) -> Result<JsValue, JsValue> {
+    let dimensions: Vec<usize> = dimensions.into_iter().map(|dimension| dimension as usize).collect();
+    let ks: Vec<usize> = ks.into_iter().map(|k| k as usize).collect();
+    let timings = benchmark_correction_layouts(&dimensions, &ks)
+        .map_err(js_error)?;
+
+    let js_timings = js_sys::Array::new();
+    for timing in &timings {
+        let js_timing = js_sys::Object::new();
+        js_sys::Reflect::set(&js_timing, &JsValue::from_str("dimension"), &JsValue::from_f64(timing.dimension as f64))?;
+        js_sys::Reflect::set(&js_timing, &JsValue::from_str("k"), &JsValue::from_f64(timing.k as f64))?;
+        js_sys::Reflect::set(&js_timing, &JsValue::from_str("batchSize"), &JsValue::from_f64(timing.batch_size as f64))?;
+        js_sys::Reflect::set(&js_timing, &JsValue::from_str("aosNsPerVector"), &JsValue::from_f64(timing.aos_ns_per_vector))?;
+        js_sys::Reflect::set(&js_timing, &JsValue::from_str("soaNsPerVector"), &JsValue::from_f64(timing.soa_ns_per_vector))?;
+        js_sys::Reflect::set(&js_timing, &JsValue::from_str("faster"), &JsValue::from_str(timing.faster().name()))?;
+        js_timings.push(&js_timing);
+    }
+    Ok(js_timings.into())
+}
+
+#[cfg(feature = "scorer")]
+/// WASM: 对比严格评分公式与快速近似的耗时和误差
+///
+/// # 返回
+/// `{ strictNsPerScore, fastNsPerScore, maxRelativeError }`
+#[wasm_bindgen]
+pub fn wasm_benchmark_scoring_precision(similarity_type: &str, dimension: usize, count: usize) -> Result<JsValue, JsValue> {
+    let sim_func = match similarity_type.to_lowercase().as_str() {
+        "euclidean" => SimilarityFunction::Euclidean,
+        "cosine" => SimilarityFunction::Cosine,
+        "dot_product" | "maximum_inner_product" => SimilarityFunction::MaximumInnerProduct,
+        _ => return Err(js_error(BbqError::invalid_argument(
+            format!("不支持的相似性类型: {}", similarity_type),
+            format!("unsupported similarity: {}", similarity_type),
+        ))),
+    };
+    let report = benchmark_scoring_precision(sim_func, dimension, count)
+        .map_err(js_error)?;
+
+    let js_report = js_sys::Object::new();
+    js_sys::Reflect::set(&js_report, &JsValue::from_str("strictNsPerScore"), &JsValue::from_f64(report.strict_ns_per_score))?;
+    js_sys::Reflect::set(&js_report, &JsValue::from_str("fastNsPerScore"), &JsValue::from_f64(report.fast_ns_per_score))?;
+    js_sys::Reflect::set(&js_report, &JsValue::from_str("maxRelativeError"), &JsValue::from_f64(report.max_relative_error as f64))?;
+    Ok(js_report.into())
+}
+
+#[cfg(feature = "ivf")]
+fn ivf_statistics_to_js(statistics: &IvfStatistics) -> Result<JsValue, JsValue> {
+    let list_sizes: Vec<u32> = statistics.list_sizes.iter().map(|&size| size as u32).collect();
+    let js_statistics = js_sys::Object::new();
+    js_sys::Reflect::set(&js_statistics, &JsValue::from_str("listSizes"), &js_sys::Uint32Array::from(&list_sizes[..]))?;
+    js_sys::Reflect::set(&js_statistics, &JsValue::from_str("imbalanceFactor"), &JsValue::from_f64(statistics.imbalance_factor as f64))?;
+    js_sys::Reflect::set(&js_statistics, &JsValue::from_str("meanIntraListDistance"), &JsValue::from_f64(statistics.mean_intra_list_distance as f64))?;
+    js_sys::Reflect::set(&js_statistics, &JsValue::from_str("emptyLists"), &JsValue::from_f64(statistics.empty_lists as f64))?;
+    Ok(js_statistics.into())
+}
+
+fn kernel_selection_to_js(selection: KernelSelection) -> Result<JsValue, JsValue> {
+    let js_selection = js_sys::Object::new();
+    js_sys::Reflect::set(&js_selection, &JsValue::from_str("oneBit"), &JsValue::from_str(selection.one_bit.name()))?;
+    js_sys::Reflect::set(&js_selection, &JsValue::from_str("fourBit"), &JsValue::from_str(selection.four_bit.name()))?;
+    Ok(js_selection.into())
+}
+
+thread_local! {
+    /// JS随机来源第一次返回无效值或抛出的异常，由调用方在操作结束后取出
+    static JS_RNG_ERROR: std::cell::RefCell<Option<JsValue>> = const { std::cell::RefCell::new(None) };
+}
+
+/// 由JS函数提供随机数的来源，函数每次调用返回 [0, 1) 内的数
+///
+/// 函数抛出异常或返回无效值时记下错误并返回0，操作结束后由 `check_js_rng` 转为JS错误
+struct JsRng {
+    callback: js_sys::Function,
+}
+
+impl JsRng {
+    /// 取一个32位随机数
+    fn next_u32(&mut self) -> u32 {
+        let unit = self.callback.call0(&JsValue::NULL)
+            .and_then(|value| value.as_f64()
+                .filter(|unit| (0.0..1.0).contains(unit))
+                .ok_or_else(|| js_error(BbqError::invalid_value(
+                    format!("随机来源必须返回 [0, 1) 内的数，实际为 {:?}", value),
+                    format!("the random source must return a number in [0, 1), got {:?}", value),
+                ))));
+        match unit {
+            Ok(unit) => (unit * 4_294_967_296.0) as u32,
+            Err(error) => {
+                JS_RNG_ERROR.with(|slot| {
+                    slot.borrow_mut().get_or_insert(error);
+                });
+                0
+            }
+        }
+    }
+}
+
+impl RngSource for JsRng {
+    fn next_u64(&mut self) -> u64 {
+        ((self.next_u32() as u64) << 32) | self.next_u32() as u64
+    }
+}
+
+/// 在使用JS随机来源的操作之后调用：返回期间记录的第一个错误
+fn check_js_rng() -> Result<(), JsValue> {
+    match JS_RNG_ERROR.with(|slot| slot.borrow_mut().take()) {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+/// WASM: 设置 `createRandomVector` 使用的随机来源，传入null时恢复内置的随机数
+///
+/// 函数每次调用返回 [0, 1) 内的数，例如基于 `crypto.getRandomValues` 的实现或可复现的序列
+#[wasm_bindgen(js_name = setRandomSource)]
+pub fn wasm_set_random_source(source: Option<js_sys::Function>) {
+    set_default_rng(source.map(|callback| Box::new(JsRng { callback }) as Box<dyn RngSource>));
+}
+
+/// WASM: 创建随机向量（使用 `setRandomSource` 设置的来源）
+#[wasm_bindgen]
+pub fn wasm_create_random_vector(dimension: usize, min: f32, max: f32) -> Result<Vec<f32>, JsValue> {
+    let vector = crate::vector_utils::create_random_vector(dimension, min, max);
+    check_js_rng()?;
+    Ok(vector)
+}
+
+/// WASM: 生成高斯混合分布的聚类向量（按行展平）
+#[wasm_bindgen]
+pub fn wasm_generate_gaussian_mixture(
+    count: usize,
+    dimension: usize,
+    clusters: usize,
+    spread: f32,
+    seed: u32,
+) -> Result<Vec<f32>, JsValue> {
+    crate::vector_utils::generate_gaussian_mixture(count, dimension, clusters, spread, seed as u64)
+        .map(|vectors| vectors.concat())
+        .map_err(js_error)
+}
+
+/// WASM: 生成单位球面上均匀分布的向量（按行展平）
+#[wasm_bindgen]
+pub fn wasm_generate_unit_sphere(count: usize, dimension: usize, seed: u32) -> Vec<f32> {
+    crate::vector_utils::generate_unit_sphere(count, dimension, seed as u64).concat()
+}
+
+/// WASM: 生成重尾分布（学生t分布）的向量（按行展平）
 #[wasm_bindgen]
-pub fn wasm_create_random_vector(dimension: usize, min: f32, max: f32) -> Vec<f32> {
-    crate::vector_utils::create_random_vector(dimension, min, max)
+pub fn wasm_generate_heavy_tailed(
+    count: usize,
+    dimension: usize,
+    degrees_of_freedom: u32,
+    seed: u32,
+) -> Result<Vec<f32>, JsValue> {
+    crate::vector_utils::generate_heavy_tailed(count, dimension, degrees_of_freedom, seed as u64)
+        .map(|vectors| vectors.concat())
+        .map_err(js_error)
 }
 
 /// WASM: 创建零向量
@@ -173,11 +673,14 @@ impl WasmVector {
             "euclidean" => SimilarityFunction::Euclidean,
             "cosine" => SimilarityFunction::Cosine,
             "dot_product" | "maximum_inner_product" => SimilarityFunction::MaximumInnerProduct,
-            _ => return Err(JsValue::from_str(&format!("不支持的相似性类型: {}", similarity_type))),
+            _ => return Err(js_error(BbqError::invalid_argument(
+                format!("不支持的相似性类型: {}", similarity_type),
+                format!("unsupported similarity: {}", similarity_type),
+            ))),
         };
 
         compute_similarity(&self.data, &other.data, sim_func)
-            .map_err(|e| JsValue::from_str(&e))
+            .map_err(js_error)
     }
 
     pub fn dot(&self, other: &WasmVector) -> f32 {
@@ -231,7 +734,7 @@ impl WasmScalarQuantizer {
                 "euclidean" => Some(SimilarityFunction::Euclidean),
                 "cosine" => Some(SimilarityFunction::Cosine),
                 "dot_product" | "maximum_inner_product" => Some(SimilarityFunction::MaximumInnerProduct),
-                _ => return Err(JsValue::from_str(&format!("不支持的相似性类型: {}", st))),
+                _ => return Err(js_error(BbqError::invalid_argument(format!("不支持的相似性类型: {}", st), format!("unsupported similarity: {}", st)))),
             }
         } else {
             None
@@ -251,43 +754,174 @@ impl WasmScalarQuantizer {
     ) -> Result<JsValue, JsValue> {
         let mut destination = vec![0u8; vector.len()];
         let result = self.inner.scalar_quantize(vector, &mut destination, bits, centroid)
-            .map_err(|e| JsValue::from_str(&e))?;
+            .map_err(js_error)?;
+        quantization_to_js(&destination, &result)
+    }
 
-        // 返回包含量化向量和修正因子的对象
-        let js_result = js_sys::Object::new();
-        
-        // 设置量化向量
-        let js_destination = js_sys::Uint8Array::from(&destination[..]);
-        js_sys::Reflect::set(&js_result, &JsValue::from_str("quantizedVector"), &js_destination)?;
-        
-        // 设置修正因子
-        let js_correction = WasmQuantizationResult::new(
-            result.lower_interval,
-            result.upper_interval,
-            result.additional_correction,
-            result.quantized_component_sum,
-        );
-        js_sys::Reflect::set(&js_result, &JsValue::from_str("correction"), &JsValue::from(js_correction))?;
+    /// 1位量化并直接打包，返回 `{ quantizedVector, correction }`，其中quantizedVector为打包后的字节
+    ///
+    /// 只需要打包向量时（如为 `EdgeScorer.search_packed` 准备数据）省去逐维的中间数组和单独的打包
+    pub fn scalar_quantize_packed(&self, vector: &[f32], centroid: &[f32]) -> Result<JsValue, JsValue> {
+        let mut packed = vec![0u8; vector.len().div_ceil(8)];
+        let result = self.inner.scalar_quantize_packed(vector, &mut packed, centroid)
+            .map_err(js_error)?;
+        quantization_to_js(&packed, &result)
+    }
 
-        Ok(js_result.into())
+    /// 对Float64Array输入标量量化：以f64减去质心并累加统计量，适合远离原点的f64数据
+    pub fn scalar_quantize_f64(
+        &self,
+        vector: &[f64],
+        bits: u8,
+        centroid: &[f64],
+    ) -> Result<JsValue, JsValue> {
+        let mut destination = vec![0u8; vector.len()];
+        let result = self.inner.scalar_quantize_generic(vector, &mut destination, bits, centroid)
+            .map_err(js_error)?;
+        quantization_to_js(&destination, &result)
     }
 
     /// 二进制打包
     pub fn pack_as_binary(vector: &[u8]) -> Result<Vec<u8>, JsValue> {
-        let packed_len = (vector.len() + 7) / 8;
+        let packed_len = vector.len().div_ceil(8);
         let mut packed = vec![0u8; packed_len];
         OptimizedScalarQuantizer::pack_as_binary(vector, &mut packed)
-            .map_err(|e| JsValue::from_str(&e))?;
+            .map_err(js_error)?;
         Ok(packed)
     }
+
+    /// 二进制解包，返回每维一个0/1值
+    pub fn unpack_from_binary(packed: &[u8], dimension: usize) -> Result<Vec<u8>, JsValue> {
+        let mut unpacked = vec![0u8; dimension];
+        OptimizedScalarQuantizer::unpack_from_binary(packed, &mut unpacked)
+            .map_err(js_error)?;
+        Ok(unpacked)
+    }
+}
+
+/// 预热报告转为 `{ vectorsTouched, bytesTouched, touchMs, probeMs, totalMs }`
+#[cfg(feature = "index")]
+fn warmup_report_to_js(report: &WarmupReport) -> Result<JsValue, JsValue> {
+    let js_report = js_sys::Object::new();
+    js_sys::Reflect::set(&js_report, &JsValue::from_str("vectorsTouched"), &JsValue::from_f64(report.vectors_touched as f64))?;
+    js_sys::Reflect::set(&js_report, &JsValue::from_str("bytesTouched"), &JsValue::from_f64(report.bytes_touched as f64))?;
+    js_sys::Reflect::set(&js_report, &JsValue::from_str("touchMs"), &JsValue::from_f64(report.touch_ms))?;
+    js_sys::Reflect::set(&js_report, &JsValue::from_str("probeMs"), &JsValue::from_f64(report.probe_ms))?;
+    js_sys::Reflect::set(&js_report, &JsValue::from_str("totalMs"), &JsValue::from_f64(report.total_ms))?;
+    Ok(js_report.into())
+}
+
+/// 使用统计转为JS对象，`averageK` 在没有查询时为null
+#[cfg(feature = "index")]
+fn warm_stats_to_js(stats: &WarmStats, hot_limit: usize) -> Result<JsValue, JsValue> {
+    let js_stats = js_sys::Object::new();
+    js_sys::Reflect::set(&js_stats, &JsValue::from_str("queryCount"), &JsValue::from_f64(stats.query_count() as f64))?;
+    js_sys::Reflect::set(&js_stats, &JsValue::from_str("averageK"), &stats.average_k().map_or(JsValue::NULL, JsValue::from_f64))?;
+    js_sys::Reflect::set(&js_stats, &JsValue::from_str("repeatRate"), &JsValue::from_f64(stats.repeat_rate()))?;
+    js_sys::Reflect::set(&js_stats, &JsValue::from_str("suggestedResultCacheCapacity"), &JsValue::from_f64(stats.suggested_result_cache_capacity() as f64))?;
+    let hot: Vec<u32> = stats.hot_ordinals(hot_limit).into_iter().map(|ord| ord as u32).collect();
+    js_sys::Reflect::set(&js_stats, &JsValue::from_str("hotOrdinals"), &js_sys::Uint32Array::from(hot.as_slice()))?;
+    Ok(js_stats.into())
+}
+
+/// 包含量化向量和修正因子的对象 `{ quantizedVector, correction }`
+fn quantization_to_js(destination: &[u8], result: &QuantizationResult) -> Result<JsValue, JsValue> {
+    let js_result = js_sys::Object::new();
+
+    // 设置量化向量
+    let js_destination = js_sys::Uint8Array::from(destination);
+    js_sys::Reflect::set(&js_result, &JsValue::from_str("quantizedVector"), &js_destination)?;
+
+    // 设置修正因子
+    let js_correction = WasmQuantizationResult::new(
+        result.lower_interval,
+        result.upper_interval,
+        result.additional_correction,
+        result.quantized_component_sum,
+    );
+    js_sys::Reflect::set(&js_result, &JsValue::from_str("correction"), &JsValue::from(js_correction))?;
+
+    Ok(js_result.into())
+}
+
+/// 限时搜索结果转为 `{ results: [{ index, score }], scored, skipped, probesSkipped,
+/// oversampleRequested, oversampleUsed, estimatedRecall, elapsedMs, budgetExhausted }`，
+/// 未重排时过采样倍数为null
+#[cfg(feature = "index")]
+fn budgeted_results_to_js(budgeted: BudgetedSearchResults) -> Result<JsValue, JsValue> {
+    let results = js_sys::Array::new();
+    for result in budgeted.results {
+        results.push(&JsValue::from(WasmQueryResult::new(result.index, result.score)));
+    }
+    let factor_to_js = |factor: Option<f32>| factor.map_or(JsValue::NULL, |factor| JsValue::from_f64(factor as f64));
+    let obj = js_sys::Object::new();
+    js_sys::Reflect::set(&obj, &JsValue::from_str("results"), &results)?;
+    js_sys::Reflect::set(&obj, &JsValue::from_str("scored"), &JsValue::from_f64(budgeted.scored as f64))?;
+    js_sys::Reflect::set(&obj, &JsValue::from_str("skipped"), &JsValue::from_f64(budgeted.skipped as f64))?;
+    js_sys::Reflect::set(&obj, &JsValue::from_str("probesSkipped"), &JsValue::from_f64(budgeted.probes_skipped as f64))?;
+    js_sys::Reflect::set(&obj, &JsValue::from_str("oversampleRequested"), &factor_to_js(budgeted.oversample_requested))?;
+    js_sys::Reflect::set(&obj, &JsValue::from_str("oversampleUsed"), &factor_to_js(budgeted.oversample_used))?;
+    js_sys::Reflect::set(&obj, &JsValue::from_str("estimatedRecall"), &JsValue::from_f64(budgeted.estimated_recall as f64))?;
+    js_sys::Reflect::set(&obj, &JsValue::from_str("elapsedMs"), &JsValue::from_f64(budgeted.elapsed_ms))?;
+    js_sys::Reflect::set(&obj, &JsValue::from_str("budgetExhausted"), &JsValue::from_bool(budgeted.budget_exhausted))?;
+    Ok(obj.into())
+}
+
+/// 查询结果转为 `{ index, score, originalScore?, quantizedScore?, bitDotProduct?, hammingDistance?, exactScore? }`
+#[cfg(feature = "index")]
+fn query_result_to_js(result: &QueryResult) -> Result<JsValue, JsValue> {
+    let obj = js_sys::Object::new();
+    js_sys::Reflect::set(&obj, &JsValue::from_str("index"), &JsValue::from_f64(result.index as f64))?;
+    js_sys::Reflect::set(&obj, &JsValue::from_str("score"), &JsValue::from_f64(result.score as f64))?;
+    if let Some(original) = result.original_score {
+        js_sys::Reflect::set(&obj, &JsValue::from_str("originalScore"), &JsValue::from_f64(original as f64))?;
+    }
+    if let Some(distances) = &result.distances {
+        js_sys::Reflect::set(&obj, &JsValue::from_str("quantizedScore"), &JsValue::from_f64(distances.quantized_score as f64))?;
+        js_sys::Reflect::set(&obj, &JsValue::from_str("bitDotProduct"), &JsValue::from_f64(distances.bit_dot_product as f64))?;
+        if let Some(hamming) = distances.hamming_distance {
+            js_sys::Reflect::set(&obj, &JsValue::from_str("hammingDistance"), &JsValue::from_f64(hamming as f64))?;
+        }
+        if let Some(exact) = distances.exact_score {
+            js_sys::Reflect::set(&obj, &JsValue::from_str("exactScore"), &JsValue::from_f64(exact as f64))?;
+        }
+    }
+    Ok(obj.into())
+}
+
+#[cfg(feature = "index")]
+/// WASM: 解码 `search_encoded` 产生的结果字节，返回与 `search_nearest_neighbors_with_distances` 相同形状的对象数组
+///
+/// 主线程收到Worker转移来的 `ArrayBuffer` 后调用；只需要序号和分数时也可以直接用类型数组读取各列
+#[wasm_bindgen(js_name = decodeQueryResults)]
+pub fn wasm_decode_query_results(bytes: &[u8]) -> Result<js_sys::Array, JsValue> {
+    let results = decode_query_results(bytes).map_err(js_error)?;
+    let array = js_sys::Array::new();
+    for result in &results {
+        array.push(&query_result_to_js(result)?);
+    }
+    Ok(array)
 }
 
+/// 打包向量记录转为 `{ ord, quantizedVector, correction, reconstruction? }`
+#[cfg(feature = "index")]
+fn packed_record_to_js(record: &PackedVectorRecord) -> Result<JsValue, JsValue> {
+    let js_record = quantization_to_js(&record.packed, &record.corrections)?;
+    js_sys::Reflect::set(&js_record, &JsValue::from_str("ord"), &JsValue::from_f64(record.ord as f64))?;
+    if let Some(reconstruction) = &record.reconstruction {
+        js_sys::Reflect::set(&js_record, &JsValue::from_str("reconstruction"), &js_sys::Float32Array::from(reconstruction.as_slice()))?;
+    }
+    Ok(js_record)
+}
+
+#[cfg(feature = "scorer")]
 /// WASM包装类：二值量化评分器
 #[wasm_bindgen]
 pub struct WasmBinaryQuantizedScorer {
     inner: BinaryQuantizedScorer,
 }
 
+#[cfg(feature = "scorer")]
 #[wasm_bindgen]
 impl WasmBinaryQuantizedScorer {
     #[wasm_bindgen(constructor)]
@@ -296,7 +930,10 @@ impl WasmBinaryQuantizedScorer {
             "euclidean" => SimilarityFunction::Euclidean,
             "cosine" => SimilarityFunction::Cosine,
             "dot_product" | "maximum_inner_product" => SimilarityFunction::MaximumInnerProduct,
-            _ => return Err(JsValue::from_str(&format!("不支持的相似性类型: {}", similarity_type))),
+            _ => return Err(js_error(BbqError::invalid_argument(
+                format!("不支持的相似性类型: {}", similarity_type),
+                format!("unsupported similarity: {}", similarity_type),
+            ))),
         };
 
         Ok(WasmBinaryQuantizedScorer {
@@ -305,6 +942,7 @@ impl WasmBinaryQuantizedScorer {
     }
 
     /// 计算量化相似性分数
+    #[allow(clippy::too_many_arguments)]
     pub fn compute_quantized_score(
         &self,
         quantized_query: &[u8],
@@ -344,12 +982,71 @@ impl WasmBinaryQuantizedScorer {
             dimension,
             centroid_dp,
             None,
-        ).map_err(|e| JsValue::from_str(&e))?;
+        ).map_err(js_error)?;
 
         Ok(result.score)
     }
 }
 
+/// WASM包装类：边缘评分器，只做查询量化和打包缓冲区评分
+///
+/// 只启用 `scorer` 特性构建时即为边缘运行时的最小模块
+#[cfg(feature = "scorer")]
+#[wasm_bindgen(js_name = EdgeScorer)]
+pub struct WasmEdgeScorer {
+    inner: EdgeScorer,
+}
+
+#[cfg(feature = "scorer")]
+#[wasm_bindgen(js_class = EdgeScorer)]
+impl WasmEdgeScorer {
+    /// 创建边缘评分器，参数需与构建索引时一致
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        similarity_type: &str,
+        query_bits: u8,
+        centroid: Vec<f32>,
+        lambda: Option<f32>,
+        iters: Option<usize>,
+        double_precision: Option<bool>,
+    ) -> Result<WasmEdgeScorer, JsValue> {
+        let precision = if double_precision.unwrap_or(false) {
+            CorrectionPrecision::Double
+        } else {
+            CorrectionPrecision::Single
+        };
+        let inner = EdgeScorer::new(parse_metric(similarity_type).map_err(js_error)?, query_bits, centroid)
+            .map_err(js_error)?
+            .with_correction_precision(precision)
+            .with_quantizer_params(lambda, iters);
+        Ok(WasmEdgeScorer { inner })
+    }
+
+    /// 对连续的1位打包向量评分，`corrections` 每个向量4个值（下界、上界、附加修正、量化分量和）
+    pub fn score_packed(&self, query_vector: &[f32], packed: &[u8], corrections: &[f32]) -> Result<Vec<f32>, JsValue> {
+        let context = self.inner.prepare_query(query_vector).map_err(js_error)?;
+        self.inner.score_packed(&context, packed, corrections).map_err(js_error)
+    }
+
+    /// 分数最高的k个向量 `{ indices: Uint32Array, scores: Float32Array }`，按分数降序
+    pub fn search_packed(&self, query_vector: &[f32], packed: &[u8], corrections: &[f32], k: usize) -> Result<JsValue, JsValue> {
+        let hits = self.inner.search_packed(query_vector, packed, corrections, k).map_err(js_error)?;
+        let indices: Vec<u32> = hits.iter().map(|&(index, _)| index as u32).collect();
+        let scores: Vec<f32> = hits.iter().map(|&(_, score)| score).collect();
+        let result = js_sys::Object::new();
+        js_sys::Reflect::set(&result, &JsValue::from_str("indices"), &js_sys::Uint32Array::from(&indices[..]))?;
+        js_sys::Reflect::set(&result, &JsValue::from_str("scores"), &js_sys::Float32Array::from(&scores[..]))?;
+        Ok(result.into())
+    }
+
+    /// 向量维度
+    #[wasm_bindgen(getter)]
+    pub fn dimension(&self) -> usize {
+        self.inner.dimension()
+    }
+}
+
+#[cfg(feature = "index")]
 /// WASM包装类：量化索引配置
 #[wasm_bindgen]
 pub struct WasmQuantizedIndexConfig {
@@ -358,8 +1055,20 @@ pub struct WasmQuantizedIndexConfig {
     similarity_function: String,
     lambda: Option<f32>,
     iters: Option<usize>,
+    keep_original_vectors: bool,
+    result_cache_capacity: usize,
+    double_precision: bool,
+    fast_math: bool,
+    degenerate_vectors: String,
+    original_encoding: String,
+    correction_layout: String,
+    discretize_dimensions: bool,
+    prenormalized: bool,
+    promote_fraction: f32,
+    promoted_bits: u8,
 }
 
+#[cfg(feature = "index")]
 #[wasm_bindgen]
 impl WasmQuantizedIndexConfig {
     #[wasm_bindgen(constructor)]
@@ -376,6 +1085,17 @@ impl WasmQuantizedIndexConfig {
             similarity_function: similarity_function.unwrap_or_else(|| "cosine".to_string()),
             lambda,
             iters,
+            keep_original_vectors: false,
+            result_cache_capacity: 0,
+            double_precision: false,
+            fast_math: cfg!(feature = "fast-math"),
+            degenerate_vectors: "keep".to_string(),
+            original_encoding: "f32".to_string(),
+            correction_layout: "aos".to_string(),
+            discretize_dimensions: false,
+            prenormalized: false,
+            promote_fraction: 0.0,
+            promoted_bits: 4,
         }
     }
 
@@ -428,8 +1148,129 @@ impl WasmQuantizedIndexConfig {
     pub fn set_iters(&mut self, value: Option<usize>) {
         self.iters = value;
     }
+
+    #[wasm_bindgen(getter)]
+    pub fn keep_original_vectors(&self) -> bool {
+        self.keep_original_vectors
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_keep_original_vectors(&mut self, value: bool) {
+        self.keep_original_vectors = value;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn result_cache_capacity(&self) -> usize {
+        self.result_cache_capacity
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_result_cache_capacity(&mut self, value: usize) {
+        self.result_cache_capacity = value;
+    }
+
+    /// 修正项与评分是否以f64累加（高维时更精确）
+    #[wasm_bindgen(getter)]
+    pub fn double_precision(&self) -> bool {
+        self.double_precision
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_double_precision(&mut self, value: bool) {
+        self.double_precision = value;
+    }
+
+    /// 评分是否使用快速近似（欧氏与最大内积的相对误差不超过2.6e-3，分数不再截断到0）
+    #[wasm_bindgen(getter)]
+    pub fn fast_math(&self) -> bool {
+        self.fast_math
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_fast_math(&mut self, value: bool) {
+        self.fast_math = value;
+    }
+
+    /// 退化向量（减去质心后为常量，包括全零向量）的处理方式："keep"、"skip"或"reject"
+    #[wasm_bindgen(getter)]
+    pub fn degenerate_vectors(&self) -> String {
+        self.degenerate_vectors.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_degenerate_vectors(&mut self, value: String) {
+        self.degenerate_vectors = value;
+    }
+
+    /// 保留的原始向量的存储编码："f32"、"f16"或"int8"（相对量化重建值的int8残差）
+    #[wasm_bindgen(getter)]
+    pub fn original_encoding(&self) -> String {
+        self.original_encoding.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_original_encoding(&mut self, value: String) {
+        self.original_encoding = value;
+    }
+
+    /// 修正项的存放布局："aos"（按向量）、"soa"（按字段分列）、"f16"（按向量以半精度存放）
+    /// 或"grouped"（相近的区间分组共享，召回率有所下降），见 `wasm_benchmark_correction_layouts`
+    #[wasm_bindgen(getter)]
+    pub fn correction_layout(&self) -> String {
+        self.correction_layout.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_correction_layout(&mut self, value: String) {
+        self.correction_layout = value;
+    }
+
+    /// 是否像Lucene一样把维度对齐到64位后再打包，打包向量可以直接与ES/OpenSearch交换
+    #[wasm_bindgen(getter)]
+    pub fn discretize_dimensions(&self) -> bool {
+        self.discretize_dimensions
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_discretize_dimensions(&mut self, value: bool) {
+        self.discretize_dimensions = value;
+    }
+
+    /// 输入是否已经是单位向量（余弦相似度时跳过归一化），上游已归一化嵌入时使用
+    #[wasm_bindgen(getter)]
+    pub fn prenormalized(&self) -> bool {
+        self.prenormalized
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_prenormalized(&mut self, value: bool) {
+        self.prenormalized = value;
+    }
+
+    /// 构建时按量化质量从低到高以promotedBits额外量化的向量比例（0到1之间，默认0即不提升）
+    #[wasm_bindgen(getter)]
+    pub fn promote_fraction(&self) -> f32 {
+        self.promote_fraction
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_promote_fraction(&mut self, value: f32) {
+        self.promote_fraction = value;
+    }
+
+    /// 提升的向量使用的量化位数（默认4，须大于indexBits）
+    #[wasm_bindgen(getter)]
+    pub fn promoted_bits(&self) -> u8 {
+        self.promoted_bits
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_promoted_bits(&mut self, value: u8) {
+        self.promoted_bits = value;
+    }
 }
 
+#[cfg(feature = "index")]
 /// WASM包装类：查询结果
 #[wasm_bindgen]
 pub struct WasmQueryResult {
@@ -437,6 +1278,7 @@ pub struct WasmQueryResult {
     pub score: f32,
 }
 
+#[cfg(feature = "index")]
 #[wasm_bindgen]
 impl WasmQueryResult {
     #[wasm_bindgen(constructor)]
@@ -445,45 +1287,288 @@ impl WasmQueryResult {
     }
 }
 
-/// WASM包装类：量化索引
+#[cfg(feature = "index")]
+/// WASM: 融合向量搜索结果与外部分数
+///
+/// # 参数
+/// * `result_indices` / `result_scores` - 向量搜索结果（平行数组）
+/// * `external_indices` / `external_scores` - 外部分数（平行数组）
+/// * `strategy` - "weighted_sum" | "rrf"
+/// * `param` - weighted_sum时为向量分数权重alpha，rrf时为平滑常数k
 #[wasm_bindgen]
-pub struct WasmQuantizedIndex {
-    inner: QuantizedIndex,
+pub fn wasm_fuse_with_external_scores(
+    result_indices: &[u32],
+    result_scores: &[f32],
+    external_indices: &[u32],
+    external_scores: &[f32],
+    strategy: &str,
+    param: Option<f32>,
+) -> Result<Vec<JsValue>, JsValue> {
+    if result_indices.len() != result_scores.len() || external_indices.len() != external_scores.len() {
+        return Err(js_error(BbqError::dimension_mismatch("索引数组与分数数组长度不匹配", "index and score arrays differ in length")));
+    }
+    let strategy = FusionStrategy::from_name(strategy, param)
+        .map_err(js_error)?;
+
+    let results: Vec<QueryResult> = result_indices.iter()
+        .zip(result_scores.iter())
+        .map(|(&index, &score)| QueryResult { index: index as usize, score, original_score: None, distances: None })
+        .collect();
+    let external: Vec<(usize, f32)> = external_indices.iter()
+        .zip(external_scores.iter())
+        .map(|(&index, &score)| (index as usize, score))
+        .collect();
+
+    Ok(fuse_with_external_scores(&results, &external, strategy)
+        .into_iter()
+        .map(|result| JsValue::from(WasmQueryResult::new(result.index, result.score)))
+        .collect())
 }
 
+/// WASM: 校验扁平存放的向量数据，不构建索引
+///
+/// # 返回
+/// `{ rows, nonFiniteRows, trailingValues, zeroRows, constantRows, duplicateRows,
+///    nonFiniteExamples, zeroExamples, constantExamples, duplicateExamples: [[row, firstRow]] }`
 #[wasm_bindgen]
-impl WasmQuantizedIndex {
-    /// 创建新的量化索引
-    #[wasm_bindgen(constructor)]
-    pub fn new(config: &WasmQuantizedIndexConfig) -> Result<WasmQuantizedIndex, JsValue> {
-        let similarity_function = match config.similarity_function().to_lowercase().as_str() {
-            "euclidean" => SimilarityFunction::Euclidean,
-            "cosine" => SimilarityFunction::Cosine,
-            "dot_product" | "maximum_inner_product" => SimilarityFunction::MaximumInnerProduct,
-            _ => return Err(JsValue::from_str(&format!("不支持的相似性类型: {}", config.similarity_function()))),
-        };
+pub fn wasm_validate_vectors(vectors: &[f32], dimension: usize) -> Result<JsValue, JsValue> {
+    let report = validate_vectors(vectors, dimension)
+        .map_err(js_error)?;
 
-        let index_config = QuantizedIndexConfig {
-            query_bits: config.query_bits(),
-            index_bits: config.index_bits(),
-            similarity_function,
-            lambda: config.lambda(),
-            iters: config.iters(),
-        };
+    let rows_to_js = |rows: &[usize]| {
+        let rows: Vec<u32> = rows.iter().map(|&row| row as u32).collect();
+        js_sys::Uint32Array::from(&rows[..])
+    };
+    let duplicate_examples = js_sys::Array::new();
+    for &(row, first) in &report.duplicate_examples {
+        duplicate_examples.push(&rows_to_js(&[row, first]));
+    }
 
-        let index = QuantizedIndex::new(index_config)
-            .map_err(|e| JsValue::from_str(&e))?;
-        
-        Ok(WasmQuantizedIndex {
-            inner: index,
-        })
+    let js_report = js_sys::Object::new();
+    for (key, value) in [
+        ("rows", report.rows),
+        ("nonFiniteRows", report.non_finite_rows),
+        ("trailingValues", report.trailing_values),
+        ("zeroRows", report.zero_rows),
+        ("constantRows", report.constant_rows),
+        ("duplicateRows", report.duplicate_rows),
+    ] {
+        js_sys::Reflect::set(&js_report, &JsValue::from_str(key), &JsValue::from_f64(value as f64))?;
+    }
+    js_sys::Reflect::set(&js_report, &JsValue::from_str("nonFiniteExamples"), &rows_to_js(&report.non_finite_examples))?;
+    js_sys::Reflect::set(&js_report, &JsValue::from_str("zeroExamples"), &rows_to_js(&report.zero_examples))?;
+    js_sys::Reflect::set(&js_report, &JsValue::from_str("constantExamples"), &rows_to_js(&report.constant_examples))?;
+    js_sys::Reflect::set(&js_report, &JsValue::from_str("duplicateExamples"), &duplicate_examples)?;
+    Ok(js_report.into())
+}
+
+#[cfg(feature = "index")]
+/// 将扁平数组按维度切分为向量集合
+fn split_flat_vectors(vectors: &[f32], dimension: usize) -> Result<Vec<Vec<f32>>, JsValue> {
+    if dimension == 0 {
+        return Err(js_error(BbqError::invalid_argument("维度必须大于0", "dimension must be greater than 0")));
+    }
+    if !vectors.len().is_multiple_of(dimension) {
+        return Err(js_error(BbqError::dimension_mismatch("向量数组长度必须是维度的整数倍", "vector array length must be a multiple of the dimension")));
+    }
+    Ok(vectors.chunks(dimension).map(|vector| vector.to_vec()).collect())
+}
+
+#[cfg(all(feature = "index", feature = "serde"))]
+/// 解析过滤条件：字符串按过滤表达式编译（如 `"tag:news AND price<100"`），其他按JSON描述解析
+fn parse_filter(filter: JsValue) -> Result<Filter, JsValue> {
+    if let Some(expression) = filter.as_string() {
+        return parse_filter_expression(&expression)
+            .map_err(js_error);
+    }
+    serde_wasm_bindgen::from_value(filter)
+        .map_err(|e| invalid_input(Subject::new("过滤条件", "filter"), e))
+}
+
+#[cfg(feature = "index")]
+/// 原始向量编码在JS中的名称
+fn original_encoding_name(encoding: OriginalVectorEncoding) -> &'static str {
+    match encoding {
+        OriginalVectorEncoding::F32 => "f32",
+        OriginalVectorEncoding::F16 => "f16",
+        OriginalVectorEncoding::Int8Residual => "int8",
+    }
+}
+
+#[cfg(feature = "index")]
+impl WasmQuantizedIndexConfig {
+    /// 转换为索引配置
+    fn to_index_config(&self) -> Result<QuantizedIndexConfig, JsValue> {
+        let similarity_function = match self.similarity_function().to_lowercase().as_str() {
+            "euclidean" => SimilarityFunction::Euclidean,
+            "cosine" => SimilarityFunction::Cosine,
+            "dot_product" | "maximum_inner_product" => SimilarityFunction::MaximumInnerProduct,
+            _ => return Err(js_error(BbqError::invalid_argument(
+                format!("不支持的相似性类型: {}", self.similarity_function()),
+                format!("unsupported similarity: {}", self.similarity_function()),
+            ))),
+        };
+
+        Ok(QuantizedIndexConfig {
+            query_bits: self.query_bits(),
+            index_bits: self.index_bits(),
+            similarity_function,
+            lambda: self.lambda(),
+            iters: self.iters(),
+            keep_original_vectors: self.keep_original_vectors(),
+            result_cache_capacity: self.result_cache_capacity(),
+            correction_precision: if self.double_precision() {
+                CorrectionPrecision::Double
+            } else {
+                CorrectionPrecision::Single
+            },
+            scoring_precision: if self.fast_math() {
+                ScoringPrecision::Fast
+            } else {
+                ScoringPrecision::Strict
+            },
+            degenerate_vectors: match self.degenerate_vectors().to_lowercase().as_str() {
+                "keep" => DegenerateVectorPolicy::Keep,
+                "skip" => DegenerateVectorPolicy::Skip,
+                "reject" => DegenerateVectorPolicy::Reject,
+                _ => return Err(js_error(BbqError::invalid_argument(
+                    format!("不支持的退化向量处理方式: {}", self.degenerate_vectors()),
+                    format!("unsupported degenerate vector policy: {}", self.degenerate_vectors()),
+                ))),
+            },
+            original_encoding: match self.original_encoding().to_lowercase().as_str() {
+                "f32" => OriginalVectorEncoding::F32,
+                "f16" => OriginalVectorEncoding::F16,
+                "int8" => OriginalVectorEncoding::Int8Residual,
+                _ => return Err(js_error(BbqError::invalid_argument(
+                    format!("不支持的原始向量编码: {}", self.original_encoding()),
+                    format!("unsupported original vector encoding: {}", self.original_encoding()),
+                ))),
+            },
+            correction_layout: CorrectionLayout::parse(&self.correction_layout).map_err(js_error)?,
+            discretize_dimensions: self.discretize_dimensions,
+            prenormalized: self.prenormalized,
+            promote_fraction: self.promote_fraction,
+            promoted_bits: self.promoted_bits,
+        })
+    }
+}
+
+#[cfg(feature = "index")]
+/// WASM包装类：量化索引
+#[wasm_bindgen]
+pub struct WasmQuantizedIndex {
+    inner: QuantizedIndex,
+}
+
+/// 把进度事件转发给JS回调
+#[cfg(all(feature = "index", not(target_feature = "atomics")))]
+struct JsProgressObserver {
+    callback: js_sys::Function,
+}
+
+// 未启用atomics的WASM只有一个线程，回调不会离开创建它的线程
+#[cfg(all(feature = "index", not(target_feature = "atomics")))]
+unsafe impl Send for JsProgressObserver {}
+#[cfg(all(feature = "index", not(target_feature = "atomics")))]
+unsafe impl Sync for JsProgressObserver {}
+
+#[cfg(all(feature = "index", not(target_feature = "atomics")))]
+impl ProgressObserver for JsProgressObserver {
+    fn on_progress(&self, event: &ProgressEvent) {
+        // 回调抛出的异常不影响正在进行的操作
+        if let Ok(object) = progress_event_to_js(event) {
+            let _ = self.callback.call1(&JsValue::NULL, &object);
+        }
+    }
+}
+
+/// 进度事件对象 `{ type, ... }`，字段与事件的字段同名（驼峰）
+#[cfg(all(feature = "index", not(target_feature = "atomics")))]
+fn progress_event_to_js(event: &ProgressEvent) -> Result<JsValue, JsValue> {
+    let object = js_sys::Object::new();
+    js_sys::Reflect::set(&object, &JsValue::from_str("type"), &JsValue::from_str(event.name()))?;
+    let fields: &[(&str, f64)] = match *event {
+        ProgressEvent::BuildStarted { total, dimension } => &[("total", total as f64), ("dimension", dimension as f64)],
+        ProgressEvent::CentroidComputed { dimension } => &[("dimension", dimension as f64)],
+        ProgressEvent::ChunkQuantized { n, total } => &[("n", n as f64), ("total", total as f64)],
+        ProgressEvent::SearchBatchScored { scored, total } => &[("scored", scored as f64), ("total", total as f64)],
+        ProgressEvent::Compacted { generation, removed, remaining } => &[
+            ("generation", generation as f64),
+            ("removed", removed as f64),
+            ("remaining", remaining as f64),
+        ],
+    };
+    for &(key, value) in fields {
+        js_sys::Reflect::set(&object, &JsValue::from_str(key), &JsValue::from_f64(value))?;
+    }
+    Ok(object.into())
+}
+
+#[cfg(feature = "index")]
+impl WasmQuantizedIndex {
+    /// 进入一个带配置指纹的操作作用域，作用域内的错误和panic都会带上操作名上报
+    fn operation_scope(&self, operation: &'static str) -> OperationScope {
+        OperationScope::enter(operation, Some(config_fingerprint(self.inner.get_config())))
+    }
+}
+
+#[cfg(feature = "index")]
+#[wasm_bindgen]
+impl WasmQuantizedIndex {
+    /// 创建新的量化索引
+    #[wasm_bindgen(constructor)]
+    pub fn new(config: &WasmQuantizedIndexConfig) -> Result<WasmQuantizedIndex, JsValue> {
+        let index_config = config.to_index_config()?;
+        let index = QuantizedIndex::new(index_config)
+            .map_err(js_error)?;
+        
+        Ok(WasmQuantizedIndex {
+            inner: index,
+        })
+    }
+
+    /// 序列化为二进制，可存入IndexedDB，页面加载时用from_bytes恢复而不必重建
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
+        let _scope = self.operation_scope("to_bytes");
+        self.inner.serialize()
+            .map_err(js_error)
+    }
+
+    /// 从to_bytes写出的字节恢复索引
+    pub fn from_bytes(bytes: &[u8]) -> Result<WasmQuantizedIndex, JsValue> {
+        let index = QuantizedIndex::deserialize(bytes)
+            .map_err(js_error)?;
+        Ok(WasmQuantizedIndex { inner: index })
+    }
+
+    /// 按新配置重新量化BBQ快照或查询包
+    ///
+    /// 返回 `{ index, source: "originals" | "reconstructed", originalOrdinals }`
+    pub fn migrate(index_bytes: &[u8], new_config: &WasmQuantizedIndexConfig) -> Result<JsValue, JsValue> {
+        let (index, report) = migrate(index_bytes, new_config.to_index_config()?)
+            .map_err(js_error)?;
+        let source = match report.source {
+            MigrationSource::Originals => "originals",
+            MigrationSource::Reconstructed => "reconstructed",
+        };
+        let ordinals: Vec<u32> = report.original_ordinals.iter().map(|&ord| ord as u32).collect();
+        let result = js_sys::Object::new();
+        js_sys::Reflect::set(&result, &JsValue::from_str("index"), &JsValue::from(WasmQuantizedIndex { inner: index }))?;
+        js_sys::Reflect::set(&result, &JsValue::from_str("source"), &JsValue::from_str(source))?;
+        js_sys::Reflect::set(&result, &JsValue::from_str("originalOrdinals"), &js_sys::Uint32Array::from(&ordinals[..]))?;
+        Ok(result.into())
     }
 
     /// 构建索引
     pub fn build_index(&mut self, vectors: &[f32], dimension: usize) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("build_index");
         // 将扁平的向量数组转换为向量集合
-        if vectors.len() % dimension != 0 {
-            return Err(JsValue::from_str("向量数组长度必须是维度的整数倍"));
+        if dimension == 0 {
+            return Err(js_error(BbqError::invalid_argument("维度必须大于0", "dimension must be greater than 0")));
+        }
+        if !vectors.len().is_multiple_of(dimension) {
+            return Err(js_error(BbqError::dimension_mismatch("向量数组长度必须是维度的整数倍", "vector array length must be a multiple of the dimension")));
         }
 
         let vector_count = vectors.len() / dimension;
@@ -497,13 +1582,71 @@ impl WasmQuantizedIndex {
 
         self.inner.build_index(&vector_collection)
             .map(|_| JsValue::NULL)
-            .map_err(|e| JsValue::from_str(&e))
+            .map_err(js_error)
+    }
+
+    /// 使用代表性样本（扁平数组）初始化质心与区间后构建索引
+    pub fn build_index_with_sample(&mut self, vectors: &[f32], sample: &[f32], dimension: usize) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("build_index_with_sample");
+        let vector_collection = split_flat_vectors(vectors, dimension)?;
+        let sample = split_flat_vectors(sample, dimension)?;
+        self.inner.build_index_with_sample(&vector_collection, &sample)
+            .map(|_| JsValue::NULL)
+            .map_err(js_error)
+    }
+
+    /// 使用预先计算的每维均值/标准差构建索引
+    pub fn build_index_with_statistics(
+        &mut self,
+        vectors: &[f32],
+        dimension: usize,
+        mean: Vec<f32>,
+        std: Vec<f32>,
+    ) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("build_index_with_statistics");
+        let vector_collection = split_flat_vectors(vectors, dimension)?;
+        let statistics = DimensionStatistics { mean, std };
+        self.inner.build_index_with_statistics(&vector_collection, &statistics)
+            .map(|_| JsValue::NULL)
+            .map_err(js_error)
     }
 
     /// 搜索最近邻
     pub fn search_nearest_neighbors(&self, query_vector: &[f32], k: usize) -> Result<Vec<JsValue>, JsValue> {
+        let _scope = self.operation_scope("search_nearest_neighbors");
         let results = self.inner.search_nearest_neighbors(query_vector, k)
-            .map_err(|e| JsValue::from_str(&e))?;
+            .map_err(js_error)?;
+
+        let js_results: Vec<JsValue> = results.into_iter()
+            .map(|result| {
+                let js_result = WasmQueryResult::new(result.index, result.score);
+                JsValue::from(js_result)
+            })
+            .collect();
+
+        Ok(js_results)
+    }
+
+    /// 搜索最近邻并归一化分数
+    ///
+    /// # 参数
+    /// * `normalization` - "none" | "softmax" | "min_max" | "z_score"
+    /// * `temperature` - softmax温度（默认1.0）
+    pub fn search_nearest_neighbors_normalized(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        normalization: &str,
+        temperature: Option<f32>,
+    ) -> Result<Vec<JsValue>, JsValue> {
+        let _scope = self.operation_scope("search_nearest_neighbors_normalized");
+        let params = SearchParams {
+            normalization: ScoreNormalization::from_name(normalization, temperature)
+                .map_err(js_error)?,
+            ..SearchParams::default()
+        };
+        let results = self.inner.search_with_params(query_vector, k, &params)
+            .map_err(js_error)?;
 
         let js_results: Vec<JsValue> = results.into_iter()
             .map(|result| {
@@ -515,6 +1658,1038 @@ impl WasmQuantizedIndex {
         Ok(js_results)
     }
 
+    /// 搜索最近邻并用原始向量重排候选
+    ///
+    /// # 参数
+    /// * `oversample` - 过采样倍数，省略时使用校准得到的倍数
+    pub fn search_nearest_neighbors_rescored(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        oversample: Option<f32>,
+    ) -> Result<Vec<JsValue>, JsValue> {
+        let _scope = self.operation_scope("search_nearest_neighbors_rescored");
+        let params = SearchParams {
+            rescore_oversample: match oversample {
+                Some(factor) => RescoreOversample::Fixed(factor),
+                None => RescoreOversample::Adaptive,
+            },
+            ..SearchParams::default()
+        };
+        let results = self.inner.search_with_params(query_vector, k, &params)
+            .map_err(js_error)?;
+
+        Ok(results.into_iter()
+            .map(|result| JsValue::from(WasmQueryResult::new(result.index, result.score)))
+            .collect())
+    }
+
+    /// 使用维度权重搜索最近邻
+    ///
+    /// # 参数
+    /// * `weights` - 每个维度的权重（非负，0表示屏蔽该维度）
+    /// * `oversample` - 重排过采样倍数，省略时不重排（量化阶段只近似加权）
+    pub fn search_nearest_neighbors_weighted(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        weights: &[f32],
+        oversample: Option<f32>,
+    ) -> Result<Vec<JsValue>, JsValue> {
+        let _scope = self.operation_scope("search_nearest_neighbors_weighted");
+        let params = SearchParams {
+            rescore_oversample: oversample.map_or(RescoreOversample::Disabled, RescoreOversample::Fixed),
+            ..SearchParams::default()
+        };
+        let results = self.inner.search_weighted(query_vector, k, weights, &params)
+            .map_err(js_error)?;
+
+        Ok(results.into_iter()
+            .map(|result| JsValue::from(WasmQueryResult::new(result.index, result.score)))
+            .collect())
+    }
+
+    /// 预处理查询并序列化为字节块，可通过postMessage交给Worker中的分片只做评分
+    pub fn prepare_query_bytes(&self, query_vector: &[f32]) -> Result<Vec<u8>, JsValue> {
+        let _scope = self.operation_scope("prepare_query_bytes");
+        let fingerprint = self.inner.quantization_fingerprint()
+            .map_err(js_error)?;
+        self.inner.prepare_query(query_vector)
+            .and_then(|context| context.serialize(fingerprint))
+            .map_err(js_error)
+    }
+
+    /// 使用 `prepare_query_bytes` 生成的字节块搜索最近邻
+    pub fn search_prepared(&self, prepared: &[u8], k: usize) -> Result<Vec<JsValue>, JsValue> {
+        let _scope = self.operation_scope("search_prepared");
+        let context = self.inner.deserialize_prepared_query(prepared)
+            .map_err(js_error)?;
+        let results = self.inner.search_with_context(&context, k, &SearchParams::default())
+            .map_err(js_error)?;
+        Ok(results.into_iter()
+            .map(|result| JsValue::from(WasmQueryResult::new(result.index, result.score)))
+            .collect())
+    }
+
+    /// 量化指纹（16位十六进制字符串），索引未构建时报错
+    pub fn quantization_fingerprint(&self) -> Result<String, JsValue> {
+        let _scope = self.operation_scope("quantization_fingerprint");
+        self.inner.quantization_fingerprint()
+            .map(|fingerprint| format!("{:016x}", fingerprint))
+            .map_err(js_error)
+    }
+
+    /// 索引内容哈希（16位十六进制字符串），可作为查询包的ETag，索引未构建时报错
+    pub fn content_hash(&self) -> Result<String, JsValue> {
+        let _scope = self.operation_scope("content_hash");
+        self.inner.content_hash()
+            .map(|hash| format!("{:016x}", hash))
+            .map_err(js_error)
+    }
+
+    /// 批量搜索多个查询，候选只读取一次即与全部查询评分
+    ///
+    /// # 参数
+    /// * `queries` - 扁平存放的查询向量
+    /// * `dimension` - 向量维度
+    ///
+    /// # 返回
+    /// 每个查询一个 `[{ index, score }]` 数组
+    pub fn search_batch(&self, queries: &[f32], dimension: usize, k: usize) -> Result<Vec<JsValue>, JsValue> {
+        let _scope = self.operation_scope("search_batch");
+        let queries = split_flat_vectors(queries, dimension)?;
+        let batched = self.inner.search_batch(&queries, k, &SearchParams::default())
+            .map_err(js_error)?;
+
+        Ok(batched.into_iter()
+            .map(|results| {
+                let js_results = js_sys::Array::new();
+                for result in results {
+                    js_results.push(&JsValue::from(WasmQueryResult::new(result.index, result.score)));
+                }
+                js_results.into()
+            })
+            .collect())
+    }
+
+    /// 只对部分向量评分的预览搜索
+    ///
+    /// # 返回
+    /// `{ results: [{ index, score }], sampled, estimatedRecall }`
+    pub fn search_sampled(&self, query_vector: &[f32], k: usize, sample_fraction: f32) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("search_sampled");
+        let preview = self.inner.search_sampled(query_vector, k, sample_fraction)
+            .map_err(js_error)?;
+
+        let results = js_sys::Array::new();
+        for result in preview.results {
+            results.push(&JsValue::from(WasmQueryResult::new(result.index, result.score)));
+        }
+        let obj = js_sys::Object::new();
+        js_sys::Reflect::set(&obj, &JsValue::from_str("results"), &results)?;
+        js_sys::Reflect::set(&obj, &JsValue::from_str("sampled"), &JsValue::from_f64(preview.sampled as f64))?;
+        js_sys::Reflect::set(&obj, &JsValue::from_str("estimatedRecall"), &JsValue::from_f64(preview.estimated_recall as f64))?;
+        Ok(obj.into())
+    }
+
+    /// 搜索最近邻并返回全部候选量化分数的直方图
+    ///
+    /// 返回 `{ results: [{ index, score }], histogram: { min, max, bucketWidth, counts: Uint32Array, below, above, total } }`；
+    /// 同时给出min和max时使用固定范围，否则使用本次查询分数的范围
+    ///
+    /// # 参数
+    /// * `buckets` - 桶数量
+    /// * `min` / `max` - 直方图范围（可选）
+    /// * `oversample` - 重排过采样倍数，省略时不重排
+    pub fn search_with_score_histogram(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        buckets: usize,
+        min: Option<f32>,
+        max: Option<f32>,
+        oversample: Option<f32>,
+    ) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("search_with_score_histogram");
+        let range = match (min, max) {
+            (Some(min), Some(max)) => Some((min, max)),
+            (None, None) => None,
+            _ => return Err(js_error(BbqError::invalid_argument("直方图范围需要同时给出min和max", "a histogram range needs both min and max"))),
+        };
+        let params = SearchParams {
+            rescore_oversample: oversample.map_or(RescoreOversample::Disabled, RescoreOversample::Fixed),
+            ..SearchParams::default()
+        };
+        let searched = self.inner.search_with_score_histogram(query_vector, k, &params, buckets, range)
+            .map_err(js_error)?;
+
+        let results = js_sys::Array::new();
+        for result in searched.results {
+            results.push(&JsValue::from(WasmQueryResult::new(result.index, result.score)));
+        }
+        let histogram = &searched.histogram;
+        let js_histogram = js_sys::Object::new();
+        js_sys::Reflect::set(&js_histogram, &JsValue::from_str("min"), &JsValue::from_f64(histogram.min as f64))?;
+        js_sys::Reflect::set(&js_histogram, &JsValue::from_str("max"), &JsValue::from_f64(histogram.max as f64))?;
+        js_sys::Reflect::set(&js_histogram, &JsValue::from_str("bucketWidth"), &JsValue::from_f64(histogram.bucket_width() as f64))?;
+        js_sys::Reflect::set(&js_histogram, &JsValue::from_str("counts"), &js_sys::Uint32Array::from(&histogram.counts[..]))?;
+        js_sys::Reflect::set(&js_histogram, &JsValue::from_str("below"), &JsValue::from_f64(histogram.below as f64))?;
+        js_sys::Reflect::set(&js_histogram, &JsValue::from_str("above"), &JsValue::from_f64(histogram.above as f64))?;
+        js_sys::Reflect::set(&js_histogram, &JsValue::from_str("total"), &JsValue::from_f64(histogram.total() as f64))?;
+        let obj = js_sys::Object::new();
+        js_sys::Reflect::set(&obj, &JsValue::from_str("results"), &results)?;
+        js_sys::Reflect::set(&obj, &JsValue::from_str("histogram"), &js_histogram)?;
+        Ok(obj.into())
+    }
+
+    /// 开始增量搜索，之后在requestAnimationFrame或空闲回调中反复调用 `pump` / `pumpFor`
+    pub fn start_incremental_search(&self, query_vector: &[f32], k: usize) -> Result<WasmIncrementalSearch, JsValue> {
+        let _scope = self.operation_scope("start_incremental_search");
+        let inner = self.inner.start_incremental_search(query_vector, k)
+            .map_err(js_error)?;
+        Ok(WasmIncrementalSearch { inner })
+    }
+
+    /// 在搜索流上开始可取代的增量搜索，同一个流上进行中的查询会被取消
+    ///
+    /// 返回被取消的查询ID（没有时为undefined）；query_id在流内必须递增
+    pub fn search_superseding(&self, stream: &str, query_id: u32, query_vector: &[f32], k: usize) -> Result<Option<f64>, JsValue> {
+        let _scope = self.operation_scope("search_superseding");
+        let cancelled = self.inner.search_superseding(stream, query_id as u64, query_vector, k)
+            .map_err(js_error)?;
+        Ok(cancelled.map(|id| id as f64))
+    }
+
+    /// 推进流上的查询，最多评分max_batches批
+    ///
+    /// 返回 `{ results, coverage, isFinal }`；查询已被取代、取消或已返回最终结果时返回null
+    pub fn pump_superseding(&self, stream: &str, query_id: u32, max_batches: usize) -> Result<JsValue, JsValue> {
+        let progress = self.inner.pump_superseding(stream, query_id as u64, max_batches)
+            .map_err(js_error)?;
+        progress.map_or(Ok(JsValue::NULL), progressive_results_to_js)
+    }
+
+    /// 推进流上的查询，在budget_ms毫秒内尽量多地评分，返回值同 `pump_superseding`
+    pub fn pump_superseding_for(&self, stream: &str, query_id: u32, budget_ms: f64) -> Result<JsValue, JsValue> {
+        let progress = self.inner.pump_superseding_for(stream, query_id as u64, budget_ms)
+            .map_err(js_error)?;
+        progress.map_or(Ok(JsValue::NULL), progressive_results_to_js)
+    }
+
+    /// 取消流上进行中的查询，返回被取消的查询ID
+    pub fn cancel_stream(&self, stream: &str) -> Option<f64> {
+        self.inner.cancel_stream(stream).map(|id| id as f64)
+    }
+
+    /// 移除流及其查询ID记录
+    pub fn remove_stream(&self, stream: &str) -> bool {
+        self.inner.remove_stream(stream)
+    }
+
+    /// 在时间预算（毫秒）内搜索，预算不足时减少评分的向量和重排的候选
+    ///
+    /// # 参数
+    /// * `oversample` - 重排过采样倍数，省略时不重排
+    ///
+    /// # 返回
+    /// `{ results, scored, skipped, probesSkipped, oversampleRequested, oversampleUsed, estimatedRecall, elapsedMs, budgetExhausted }`
+    pub fn search_within_budget(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        budget_ms: f64,
+        oversample: Option<f32>,
+    ) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("search_within_budget");
+        let params = SearchParams {
+            rescore_oversample: oversample.map_or(RescoreOversample::Disabled, RescoreOversample::Fixed),
+            ..SearchParams::default()
+        };
+        let budgeted = self.inner.search_within_budget(query_vector, k, budget_ms, &params)
+            .map_err(js_error)?;
+        budgeted_results_to_js(budgeted)
+    }
+
+    /// 伪相关反馈搜索：查询与前feedback_k个结果的质心按alpha混合后重新搜索
+    pub fn search_with_feedback(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        feedback_k: usize,
+        alpha: f32,
+    ) -> Result<Vec<JsValue>, JsValue> {
+        let _scope = self.operation_scope("search_with_feedback");
+        let results = self.inner.search_with_feedback(query_vector, k, feedback_k, alpha)
+            .map_err(js_error)?;
+        Ok(results.into_iter()
+            .map(|result| JsValue::from(WasmQueryResult::new(result.index, result.score)))
+            .collect())
+    }
+
+    /// 搜索最近邻并为每个结果附带各种距离
+    ///
+    /// 返回对象包含 index、score、quantizedScore、bitDotProduct，
+    /// 1位索引时还有 hammingDistance，保留原始向量时还有 exactScore
+    ///
+    /// # 参数
+    /// * `oversample` - 重排过采样倍数，不传时不重排
+    pub fn search_nearest_neighbors_with_distances(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        oversample: Option<f32>,
+    ) -> Result<js_sys::Array, JsValue> {
+        let _scope = self.operation_scope("search_nearest_neighbors_with_distances");
+        let params = SearchParams {
+            rescore_oversample: oversample.map_or(RescoreOversample::Disabled, RescoreOversample::Fixed),
+            include_distances: true,
+            ..SearchParams::default()
+        };
+        let results = self.inner.search_with_params(query_vector, k, &params)
+            .map_err(js_error)?;
+
+        let array = js_sys::Array::new();
+        for result in &results {
+            array.push(&query_result_to_js(result)?);
+        }
+        Ok(array)
+    }
+
+    /// 搜索最近邻并把结果编码为紧凑的二进制格式
+    ///
+    /// 在Worker中调用后把返回的 `Uint8Array.buffer` 转移给主线程，主线程用 `decodeQueryResults` 解码
+    ///
+    /// # 参数
+    /// * `oversample` - 重排过采样倍数，不传时不重排
+    /// * `include_distances` - 是否附带各种距离（默认false）
+    pub fn search_encoded(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        oversample: Option<f32>,
+        include_distances: Option<bool>,
+    ) -> Result<Vec<u8>, JsValue> {
+        let _scope = self.operation_scope("search_encoded");
+        let params = SearchParams {
+            rescore_oversample: oversample.map_or(RescoreOversample::Disabled, RescoreOversample::Fixed),
+            include_distances: include_distances.unwrap_or(false),
+            ..SearchParams::default()
+        };
+        let results = self.inner.search_with_params(query_vector, k, &params)
+            .map_err(js_error)?;
+        encode_query_results(&results)
+            .map_err(js_error)
+    }
+
+    /// 按量化质量调整分数后搜索最近邻
+    ///
+    /// # 参数
+    /// * `quality_weight` - 量化质量混合权重（0到1）
+    pub fn search_nearest_neighbors_quality_weighted(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        quality_weight: f32,
+    ) -> Result<Vec<JsValue>, JsValue> {
+        let _scope = self.operation_scope("search_nearest_neighbors_quality_weighted");
+        let params = SearchParams {
+            quality_weight,
+            ..SearchParams::default()
+        };
+        let results = self.inner.search_with_params(query_vector, k, &params)
+            .map_err(js_error)?;
+
+        Ok(results.into_iter()
+            .map(|result| JsValue::from(WasmQueryResult::new(result.index, result.score)))
+            .collect())
+    }
+
+    #[cfg(feature = "serde")]
+    /// 带过滤条件搜索最近邻
+    ///
+    /// # 参数
+    /// * `filter` - 过滤条件，如 `{ attribute: { key: "tenant", predicate: { eq: "a" } } }`，
+    ///   或过滤表达式字符串，如 `"tenant:a AND day>=10"`
+    pub fn search_filtered(&self, query_vector: &[f32], k: usize, filter: JsValue) -> Result<Vec<JsValue>, JsValue> {
+        let _scope = self.operation_scope("search_filtered");
+        let filter = parse_filter(filter)?;
+        let results = self.inner.search_filtered(query_vector, k, &filter, &SearchParams::default())
+            .map_err(js_error)?;
+
+        Ok(results.into_iter()
+            .map(|result| JsValue::from(WasmQueryResult::new(result.index, result.score)))
+            .collect())
+    }
+
+    /// 对指定的候选序号评分，按候选顺序返回（跳过已删除和已过期的候选）
+    pub fn score_ords(&self, query_vector: &[f32], ords: Vec<u32>) -> Result<Vec<JsValue>, JsValue> {
+        let _scope = self.operation_scope("score_ords");
+        let ords: Vec<usize> = ords.into_iter().map(|ord| ord as usize).collect();
+        let results = self.inner.score_ords(query_vector, &ords)
+            .map_err(js_error)?;
+
+        Ok(results.into_iter()
+            .map(|result| JsValue::from(WasmQueryResult::new(result.index, result.score)))
+            .collect())
+    }
+
+    #[cfg(feature = "serde")]
+    /// 设置向量属性，attributes为 `{ [key]: number | string | boolean }`
+    pub fn set_attributes(&mut self, ord: usize, attributes: JsValue) -> Result<(), JsValue> {
+        let _scope = self.operation_scope("set_attributes");
+        let attributes: Attributes = serde_wasm_bindgen::from_value(attributes)
+            .map_err(|e| invalid_input(Subject::new("属性", "attributes"), e))?;
+        self.inner.set_attributes(ord, attributes)
+            .map_err(js_error)
+    }
+
+    #[cfg(feature = "serde")]
+    /// 设置向量对应的文本块来源，provenance为 `{ docId, chunkOffset, length }`，传null表示清除
+    pub fn set_provenance(&mut self, ord: usize, provenance: JsValue) -> Result<(), JsValue> {
+        let provenance: Option<ChunkProvenance> = serde_wasm_bindgen::from_value(provenance)
+            .map_err(|e| invalid_input(Subject::new("来源", "provenance"), e))?;
+        self.inner.set_provenance(ord, provenance)
+            .map_err(js_error)
+    }
+
+    #[cfg(feature = "serde")]
+    /// 获取向量对应的文本块来源，没有来源时返回null
+    pub fn get_provenance(&self, ord: usize) -> Result<JsValue, JsValue> {
+        serde_wasm_bindgen::to_value(&self.inner.get_provenance(ord))
+            .map_err(conversion_error)
+    }
+
+    #[cfg(feature = "serde")]
+    /// 搜索k个最相似的块并合并同一文档中相邻的块，
+    /// 返回 `{ docId, chunkOffset, length, score, ords }[]`；
+    /// combine为 "max" | "sum" | "mean"（默认max），max_gap为允许的最大间隔（默认0）
+    pub fn search_grouped(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        max_gap: Option<usize>,
+        combine: Option<String>,
+    ) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("search_grouped");
+        let combine = match combine.as_deref() {
+            Some(name) => ChunkScoreCombine::from_name(name).map_err(js_error)?,
+            None => ChunkScoreCombine::default(),
+        };
+        let grouping = ChunkGroupingParams { max_gap: max_gap.unwrap_or(0), combine };
+        let hits = self.inner.search_grouped(query_vector, k, &SearchParams::default(), &grouping)
+            .map_err(js_error)?;
+        serde_wasm_bindgen::to_value(&hits)
+            .map_err(conversion_error)
+    }
+
+    /// 删除向量，之前未被删除时返回true
+    pub fn delete(&mut self, ord: usize) -> Result<bool, JsValue> {
+        let _scope = self.operation_scope("delete");
+        self.inner.delete(ord)
+            .map_err(js_error)
+    }
+
+    /// 删除向量，同delete
+    pub fn remove_vector(&mut self, ord: usize) -> Result<bool, JsValue> {
+        self.delete(ord)
+    }
+
+    /// 批量删除向量，有越界序号时整批不生效，返回新删除的数量；调用compact回收存储
+    pub fn remove_vectors(&mut self, ords: Vec<u32>) -> Result<usize, JsValue> {
+        let _scope = self.operation_scope("remove_vectors");
+        let ords: Vec<usize> = ords.into_iter().map(|ord| ord as usize).collect();
+        self.inner.remove_vectors(&ords)
+            .map_err(js_error)
+    }
+
+    /// 以配置的promotedBits额外量化指定的向量（需要保留原始向量），返回新提升的数量
+    pub fn promote_vectors(&mut self, ords: Vec<u32>) -> Result<usize, JsValue> {
+        let _scope = self.operation_scope("promote_vectors");
+        let ords: Vec<usize> = ords.into_iter().map(|ord| ord as usize).collect();
+        self.inner.promote_vectors(&ords)
+            .map_err(js_error)
+    }
+
+    /// 按量化质量从低到高提升一部分未删除的向量，返回新提升的数量
+    pub fn promote_lowest_quality(&mut self, fraction: f32) -> Result<usize, JsValue> {
+        let _scope = self.operation_scope("promote_lowest_quality");
+        self.inner.promote_lowest_quality(fraction)
+            .map_err(js_error)
+    }
+
+    /// 取消向量的提升，返回取消提升的数量
+    pub fn demote_vectors(&mut self, ords: Vec<u32>) -> Result<usize, JsValue> {
+        let _scope = self.operation_scope("demote_vectors");
+        let ords: Vec<usize> = ords.into_iter().map(|ord| ord as usize).collect();
+        self.inner.demote_vectors(&ords)
+            .map_err(js_error)
+    }
+
+    /// 提升了位数的向量数量
+    #[wasm_bindgen(getter)]
+    pub fn promoted_count(&self) -> usize {
+        self.inner.promoted_count()
+    }
+
+    #[cfg(feature = "serde")]
+    /// 删除所有满足过滤条件（JSON描述或过滤表达式字符串）的向量，返回新删除的数量
+    pub fn delete_where(&mut self, filter: JsValue) -> Result<usize, JsValue> {
+        let filter = parse_filter(filter)?;
+        self.inner.delete_where(&filter)
+            .map_err(js_error)
+    }
+
+    /// 设置向量过期时间（毫秒时间戳），传undefined表示永不过期
+    pub fn set_expiry(&mut self, ord: usize, expires_at: Option<f64>) -> Result<(), JsValue> {
+        let _scope = self.operation_scope("set_expiry");
+        self.inner.set_expiry(ord, expires_at)
+            .map_err(js_error)
+    }
+
+    /// 删除已过期的向量，返回删除数量；now省略时使用当前时间
+    pub fn purge_expired(&mut self, now: Option<f64>) -> Result<usize, JsValue> {
+        self.inner.purge_expired(now.unwrap_or_else(now_ms))
+            .map_err(js_error)
+    }
+
+    /// 转换为搜索优化布局：之后无过滤的搜索直接扫描连续存放的向量，结果不变；
+    /// 任何修改都会回到构建状态，需要时再次调用
+    pub fn finalize_for_search(&self) -> Result<(), JsValue> {
+        let _scope = self.operation_scope("finalize_for_search");
+        self.inner.finalize_for_search()
+            .map_err(js_error)
+    }
+
+    /// 是否已转换为搜索优化布局
+    pub fn is_finalized(&self) -> bool {
+        self.inner.is_finalized()
+    }
+
+    /// 冻结为只读：之后构建、删除、属性、过期、压缩、批量操作和IVF等修改数据的接口都抛出错误
+    pub fn freeze(&self) {
+        self.inner.freeze();
+    }
+
+    /// 解除只读冻结
+    pub fn unfreeze(&self) {
+        self.inner.unfreeze();
+    }
+
+    /// 是否已冻结为只读
+    pub fn is_frozen(&self) -> bool {
+        self.inner.is_frozen()
+    }
+
+    /// 设置搜索实验：按比例抽中的查询同时运行当前路径（对照）和候选路径，记录成对的耗时和分数差异
+    ///
+    /// # 参数
+    /// * `sample_rate` - 抽样比例（0到1）
+    /// * `one_bit_kernel` / `four_bit_kernel` - 候选路径的内核，为undefined时沿用当前选中的内核
+    /// * `fast_math` - 候选路径是否使用快速评分公式，为undefined时与对照相同
+    /// * `layout` - 候选路径的布局: "control" | "build_state" | "search_layout"（默认"control"）
+    /// * `serve_candidate` - 抽中的查询是否返回候选路径的结果（默认false）
+    #[allow(clippy::too_many_arguments)]
+    pub fn set_search_experiment(
+        &self,
+        sample_rate: f32,
+        one_bit_kernel: Option<String>,
+        four_bit_kernel: Option<String>,
+        fast_math: Option<bool>,
+        layout: Option<String>,
+        serve_candidate: Option<bool>,
+    ) -> Result<(), JsValue> {
+        let kernels = if one_bit_kernel.is_some() || four_bit_kernel.is_some() {
+            let current = selected_kernels();
+            Some(KernelSelection {
+                one_bit: one_bit_kernel.map_or(Ok(current.one_bit), |name| KernelVariant::from_name(&name)).map_err(js_error)?,
+                four_bit: four_bit_kernel.map_or(Ok(current.four_bit), |name| KernelVariant::from_name(&name)).map_err(js_error)?,
+            })
+        } else {
+            None
+        };
+        let experiment = SearchExperiment {
+            sample_rate,
+            kernels,
+            scoring_precision: fast_math.map(|fast| if fast { ScoringPrecision::Fast } else { ScoringPrecision::Strict }),
+            layout: layout.map_or(Ok(ExperimentLayout::Control), |name| ExperimentLayout::from_name(&name)).map_err(js_error)?,
+            serve_candidate: serve_candidate.unwrap_or(false),
+            seed: 0,
+        };
+        self.inner.set_search_experiment(Some(experiment))
+            .map_err(js_error)
+    }
+
+    /// 停止搜索实验并丢弃统计
+    pub fn clear_search_experiment(&self) -> Result<(), JsValue> {
+        self.inner.set_search_experiment(None)
+            .map_err(js_error)
+    }
+
+    /// 搜索实验的统计，未设置实验时返回null
+    ///
+    /// 返回 `{ queries, sampled, skipped, candidateErrors, meanControlMs, meanCandidateMs, meanLatencyDiffMs,
+    /// latencyDiffStddevMs, speedup, candidateFaster, identical, meanOverlap, meanScoreDiff, maxScoreDiff }`
+    pub fn get_search_experiment_stats(&self) -> Result<JsValue, JsValue> {
+        let Some(stats) = self.inner.get_search_experiment_stats() else {
+            return Ok(JsValue::NULL);
+        };
+        let result = js_sys::Object::new();
+        for (key, value) in [
+            ("queries", stats.queries as f64),
+            ("sampled", stats.sampled as f64),
+            ("skipped", stats.skipped as f64),
+            ("candidateErrors", stats.candidate_errors as f64),
+            ("meanControlMs", stats.mean_control_ms()),
+            ("meanCandidateMs", stats.mean_candidate_ms()),
+            ("meanLatencyDiffMs", stats.mean_latency_diff_ms()),
+            ("latencyDiffStddevMs", stats.latency_diff_stddev_ms()),
+            ("speedup", stats.speedup()),
+            ("candidateFaster", stats.candidate_faster as f64),
+            ("identical", stats.identical as f64),
+            ("meanOverlap", stats.mean_overlap()),
+            ("meanScoreDiff", stats.mean_score_diff()),
+            ("maxScoreDiff", stats.max_score_diff as f64),
+        ] {
+            js_sys::Reflect::set(&result, &JsValue::from_str(key), &JsValue::from_f64(value))?;
+        }
+        Ok(result.into())
+    }
+
+    #[cfg(feature = "serde")]
+    /// 原子地应用一批操作，任一操作无效时不做任何修改
+    ///
+    /// ops为 `{ op: "insert", vector, attributes? } | { op: "update", ord, vector } | { op: "delete", ord }` 的数组，
+    /// 返回 `{ generation, inserted: Uint32Array, updated, deleted }`，inserted为插入的向量得到的序号
+    pub fn apply_batch(&self, ops: JsValue) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("apply_batch");
+        let ops: Vec<IndexOp> = serde_wasm_bindgen::from_value(ops)
+            .map_err(|e| invalid_input(Subject::new("批量操作", "batch operations"), e))?;
+        let report = self.inner.apply_batch(ops)
+            .map_err(js_error)?;
+        let inserted: Vec<u32> = report.inserted.iter().map(|&ord| ord as u32).collect();
+        let result = js_sys::Object::new();
+        js_sys::Reflect::set(&result, &JsValue::from_str("generation"), &JsValue::from_f64(report.generation as f64))?;
+        js_sys::Reflect::set(&result, &JsValue::from_str("inserted"), &js_sys::Uint32Array::from(inserted.as_slice()))?;
+        js_sys::Reflect::set(&result, &JsValue::from_str("updated"), &JsValue::from_f64(report.updated as f64))?;
+        js_sys::Reflect::set(&result, &JsValue::from_str("deleted"), &JsValue::from_f64(report.deleted as f64))?;
+        Ok(result.into())
+    }
+
+    /// 压缩索引：移除已删除的向量，refresh_centroid为true时用剩余向量重新计算质心；
+    /// 压缩后序号会变化，返回 { generation, removed, remaining, remap }；
+    /// remap是按旧序号索引的Int32Array，值为新序号，已移除的为-1
+    pub fn compact(&self, refresh_centroid: bool) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("compact");
+        let report = self.inner.compact(refresh_centroid)
+            .map_err(js_error)?;
+        let result = js_sys::Object::new();
+        js_sys::Reflect::set(&result, &JsValue::from_str("generation"), &JsValue::from_f64(report.generation as f64))?;
+        js_sys::Reflect::set(&result, &JsValue::from_str("removed"), &JsValue::from_f64(report.removed as f64))?;
+        js_sys::Reflect::set(&result, &JsValue::from_str("remaining"), &JsValue::from_f64(report.remaining as f64))?;
+        let remap: Vec<i32> = report.remap.to_table()
+            .into_iter()
+            .map(|ord| ord.map_or(-1, |ord| ord as i32))
+            .collect();
+        js_sys::Reflect::set(&result, &JsValue::from_str("remap"), &js_sys::Int32Array::from(remap.as_slice()))?;
+        Ok(result.into())
+    }
+
+    /// 设置进度回调 `(event) => void`，传入undefined移除；
+    /// 构建、压缩和搜索时同步调用，回调抛出的异常会被忽略
+    #[cfg(not(target_feature = "atomics"))]
+    pub fn set_progress_callback(&mut self, callback: Option<js_sys::Function>) {
+        self.inner.set_progress_observer(callback.map(|callback| {
+            std::sync::Arc::new(JsProgressObserver { callback }) as std::sync::Arc<dyn ProgressObserver>
+        }));
+    }
+
+    #[cfg(feature = "ivf")]
+    /// 建立IVF粗划分（需要保留原始向量），返回划分统计
+    pub fn build_ivf(&mut self, nlist: usize, iterations: usize, seed: u32) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("build_ivf");
+        let statistics = self.inner.build_ivf(nlist, iterations, seed as u64)
+            .map_err(js_error)?;
+        ivf_statistics_to_js(&statistics)
+    }
+
+    #[cfg(feature = "ivf")]
+    /// 建立IVF粗划分，初始中心由JS函数提供的随机数选取（函数返回 [0, 1) 内的数）
+    pub fn build_ivf_with_random_source(&mut self, nlist: usize, iterations: usize, source: js_sys::Function) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("build_ivf");
+        let statistics = self.inner.build_ivf_with_rng(nlist, iterations, &mut JsRng { callback: source });
+        check_js_rng()?;
+        ivf_statistics_to_js(&statistics.map_err(js_error)?)
+    }
+
+    #[cfg(feature = "graph")]
+    /// 构建k近邻图，返回 `{ k, neighbors: Uint32Array[], scores: Float32Array[] }`，
+    /// 第i项为序号i的近邻（按分数降序），已删除的序号为空数组
+    pub fn build_knn_graph(&self, k: usize) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("build_knn_graph");
+        let graph = self.inner.build_knn_graph(k).map_err(js_error)?;
+        let neighbors = js_sys::Array::new();
+        let scores = js_sys::Array::new();
+        for list in &graph.neighbors {
+            let ords: Vec<u32> = list.iter().map(|&(ord, _)| ord as u32).collect();
+            let values: Vec<f32> = list.iter().map(|&(_, score)| score).collect();
+            neighbors.push(&js_sys::Uint32Array::from(&ords[..]));
+            scores.push(&js_sys::Float32Array::from(&values[..]));
+        }
+        let result = js_sys::Object::new();
+        js_sys::Reflect::set(&result, &JsValue::from_str("k"), &JsValue::from_f64(graph.k as f64))?;
+        js_sys::Reflect::set(&result, &JsValue::from_str("neighbors"), &neighbors)?;
+        js_sys::Reflect::set(&result, &JsValue::from_str("scores"), &scores)?;
+        Ok(result.into())
+    }
+
+    #[cfg(feature = "graph")]
+    /// 在k近邻图上按相似度阈值聚类 `{ labels, sizes }`，已删除的序号标签为-1
+    pub fn cluster(&self, k: usize, threshold: f32) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("cluster");
+        let clustering = self.inner.cluster(k, threshold).map_err(js_error)?;
+        let labels: Vec<i32> = clustering.labels.iter()
+            .map(|label| label.map_or(-1, |label| label as i32))
+            .collect();
+        let sizes: Vec<u32> = clustering.sizes.iter().map(|&size| size as u32).collect();
+        let result = js_sys::Object::new();
+        js_sys::Reflect::set(&result, &JsValue::from_str("labels"), &js_sys::Int32Array::from(&labels[..]))?;
+        js_sys::Reflect::set(&result, &JsValue::from_str("sizes"), &js_sys::Uint32Array::from(&sizes[..]))?;
+        Ok(result.into())
+    }
+
+    /// 每维中心化值的直方图和量化质量
+    /// `[{ min, max, counts, mean, std, reconstructionMse, explainedVariance }]`
+    pub fn dimension_quality(&self, bins: usize) -> Result<js_sys::Array, JsValue> {
+        let _scope = self.operation_scope("dimension_quality");
+        let quality = self.inner.dimension_quality(bins).map_err(js_error)?;
+        let result = js_sys::Array::new();
+        for dimension in &quality {
+            let histogram = &dimension.histogram;
+            let obj = js_sys::Object::new();
+            js_sys::Reflect::set(&obj, &JsValue::from_str("min"), &JsValue::from_f64(histogram.min as f64))?;
+            js_sys::Reflect::set(&obj, &JsValue::from_str("max"), &JsValue::from_f64(histogram.max as f64))?;
+            js_sys::Reflect::set(&obj, &JsValue::from_str("counts"), &js_sys::Uint32Array::from(&histogram.counts[..]))?;
+            js_sys::Reflect::set(&obj, &JsValue::from_str("mean"), &JsValue::from_f64(histogram.mean as f64))?;
+            js_sys::Reflect::set(&obj, &JsValue::from_str("std"), &JsValue::from_f64(histogram.std as f64))?;
+            js_sys::Reflect::set(&obj, &JsValue::from_str("reconstructionMse"), &JsValue::from_f64(dimension.reconstruction_mse as f64))?;
+            js_sys::Reflect::set(&obj, &JsValue::from_str("explainedVariance"), &JsValue::from_f64(dimension.explained_variance as f64))?;
+            result.push(&obj);
+        }
+        Ok(result)
+    }
+
+    #[cfg(feature = "graph")]
+    /// 二维投影 `{ x, y }`，已删除的序号坐标为NaN
+    ///
+    /// # 参数
+    /// * `refine_iterations` - k近邻图上力导向细化的迭代次数，省略时只做PCA
+    /// * `neighbors` - 细化使用的近邻数量，省略时使用默认值
+    /// * `seed` - 随机种子，省略时使用默认值
+    pub fn project_2d(
+        &self,
+        refine_iterations: Option<usize>,
+        neighbors: Option<usize>,
+        seed: Option<u64>,
+    ) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("project_2d");
+        let defaults = ProjectionConfig::default();
+        let config = ProjectionConfig {
+            refine_iterations: refine_iterations.unwrap_or(defaults.refine_iterations),
+            neighbors: neighbors.unwrap_or(defaults.neighbors),
+            seed: seed.unwrap_or(defaults.seed),
+            ..defaults
+        };
+        let points = self.inner.project_2d(&config).map_err(js_error)?;
+        let x: Vec<f32> = points.iter().map(|point| point[0]).collect();
+        let y: Vec<f32> = points.iter().map(|point| point[1]).collect();
+        let result = js_sys::Object::new();
+        js_sys::Reflect::set(&result, &JsValue::from_str("x"), &js_sys::Float32Array::from(&x[..]))?;
+        js_sys::Reflect::set(&result, &JsValue::from_str("y"), &js_sys::Float32Array::from(&y[..]))?;
+        Ok(result.into())
+    }
+
+    #[cfg(feature = "ivf")]
+    /// IVF划分统计 `{ listSizes, imbalanceFactor, meanIntraListDistance, emptyLists }`
+    pub fn ivf_statistics(&self) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("ivf_statistics");
+        let statistics = self.inner.ivf_statistics()
+            .map_err(js_error)?;
+        ivf_statistics_to_js(&statistics)
+    }
+
+    #[cfg(feature = "ivf")]
+    /// 重新平衡IVF划分，返回新的划分统计
+    pub fn rebalance(&self, iterations: usize) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("rebalance");
+        let statistics = self.inner.rebalance(iterations)
+            .map_err(js_error)?;
+        ivf_statistics_to_js(&statistics)
+    }
+
+    #[cfg(feature = "ivf")]
+    /// 只在与查询最接近的IVF列表中搜索最近邻
+    ///
+    /// # 参数
+    /// * `nprobe` - 探测的列表数；提供max_nprobe时为最少探测数，路由有歧义时自动扩展到最多max_nprobe个
+    /// * `margin` - 自适应探测的相对差距阈值，省略时使用默认值
+    pub fn search_nearest_neighbors_ivf(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        nprobe: usize,
+        max_nprobe: Option<usize>,
+        margin: Option<f32>,
+    ) -> Result<Vec<JsValue>, JsValue> {
+        let _scope = self.operation_scope("search_nearest_neighbors_ivf");
+        let strategy = match max_nprobe {
+            Some(max) => ProbeStrategy::Adaptive { min: nprobe, max, margin: margin.unwrap_or(DEFAULT_PROBE_MARGIN) },
+            None => ProbeStrategy::Fixed(nprobe),
+        };
+        let params = SearchParams {
+            nprobe: Some(strategy),
+            ..SearchParams::default()
+        };
+        let results = self.inner.search_with_params(query_vector, k, &params)
+            .map_err(js_error)?;
+
+        Ok(results.into_iter()
+            .map(|result| JsValue::from(WasmQueryResult::new(result.index, result.score)))
+            .collect())
+    }
+
+    #[cfg(feature = "ivf")]
+    /// 在时间预算（毫秒）内只在最接近的nprobe个IVF列表中搜索，预算不足时少探测后面的列表，
+    /// 返回值同 `search_within_budget`
+    pub fn search_within_budget_ivf(
+        &self,
+        query_vector: &[f32],
+        k: usize,
+        budget_ms: f64,
+        nprobe: usize,
+        oversample: Option<f32>,
+    ) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("search_within_budget_ivf");
+        let params = SearchParams {
+            nprobe: Some(ProbeStrategy::Fixed(nprobe)),
+            rescore_oversample: oversample.map_or(RescoreOversample::Disabled, RescoreOversample::Fixed),
+            ..SearchParams::default()
+        };
+        let budgeted = self.inner.search_within_budget(query_vector, k, budget_ms, &params)
+            .map_err(js_error)?;
+        budgeted_results_to_js(budgeted)
+    }
+
+    /// 未删除的向量数量
+    #[wasm_bindgen(getter)]
+    pub fn live_count(&self) -> usize {
+        self.inner.live_count()
+    }
+
+    /// 内存占用 `{ quantizedBytes, originalBytes, searchLayoutBytes, promotedBytes, totalBytes, originalEncoding }`，
+    /// 未保留原始向量时originalEncoding为null
+    pub fn memory_stats(&self) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("memory_stats");
+        let stats = self.inner.memory_stats().map_err(js_error)?;
+        let result = js_sys::Object::new();
+        js_sys::Reflect::set(&result, &JsValue::from_str("quantizedBytes"), &JsValue::from_f64(stats.quantized_bytes as f64))?;
+        js_sys::Reflect::set(&result, &JsValue::from_str("originalBytes"), &JsValue::from_f64(stats.original_bytes as f64))?;
+        js_sys::Reflect::set(&result, &JsValue::from_str("searchLayoutBytes"), &JsValue::from_f64(stats.search_layout_bytes as f64))?;
+        js_sys::Reflect::set(&result, &JsValue::from_str("promotedBytes"), &JsValue::from_f64(stats.promoted_bytes as f64))?;
+        js_sys::Reflect::set(&result, &JsValue::from_str("totalBytes"), &JsValue::from_f64(stats.total_bytes() as f64))?;
+        let encoding = stats.original_encoding.map_or(JsValue::NULL, |encoding| JsValue::from_str(original_encoding_name(encoding)));
+        js_sys::Reflect::set(&result, &JsValue::from_str("originalEncoding"), &encoding)?;
+        Ok(result.into())
+    }
+
+    /// 向量的模长（余弦相似度时为归一化之前的模长），序号越界时返回undefined
+    pub fn get_norm(&self, ord: usize) -> Option<f32> {
+        self.inner.get_norm(ord)
+    }
+
+    /// 每个向量的量化质量（1 - 相对重建误差）
+    pub fn get_quality_scores(&self) -> Vec<f32> {
+        self.inner.get_quality_scores()
+    }
+
+    #[cfg(feature = "eval")]
+    /// 用采样查询校准自适应过采样倍数
+    ///
+    /// # 参数
+    /// * `queries` - 扁平的采样查询数组
+    /// * `dimension` - 向量维度
+    /// * `k` - 评估的Top-K
+    /// * `target_recall` - 目标召回率
+    pub fn calibrate_oversample(
+        &mut self,
+        queries: &[f32],
+        dimension: usize,
+        k: usize,
+        target_recall: f32,
+    ) -> Result<f32, JsValue> {
+        let _scope = self.operation_scope("calibrate_oversample");
+        if dimension == 0 || !queries.len().is_multiple_of(dimension) {
+            return Err(js_error(BbqError::dimension_mismatch("查询数组长度必须是维度的整数倍", "query array length must be a multiple of the dimension")));
+        }
+        let queries: Vec<Vec<f32>> = queries.chunks(dimension).map(|q| q.to_vec()).collect();
+        self.inner.calibrate_oversample(&queries, k, target_recall)
+            .map_err(js_error)
+    }
+
+    #[cfg(feature = "eval")]
+    /// 抽样生成自查询真值并缓存在索引中，返回实际抽样的查询数量
+    pub fn generate_ground_truth(&mut self, sample_size: usize, k: usize, seed: u32) -> Result<usize, JsValue> {
+        let _scope = self.operation_scope("generate_ground_truth");
+        self.inner.generate_ground_truth(sample_size, k, seed as u64)
+            .map_err(js_error)
+    }
+
+    #[cfg(feature = "eval")]
+    /// 抽样生成自查询真值，查询由JS函数提供的随机数抽取（函数返回 [0, 1) 内的数）
+    pub fn generate_ground_truth_with_random_source(&mut self, sample_size: usize, k: usize, source: js_sys::Function) -> Result<usize, JsValue> {
+        let _scope = self.operation_scope("generate_ground_truth");
+        let sampled = self.inner.generate_ground_truth_with_rng(sample_size, k, &mut JsRng { callback: source });
+        check_js_rng()?;
+        sampled.map_err(js_error)
+    }
+
+    #[cfg(feature = "eval")]
+    /// 使用缓存的真值估计当前召回率
+    ///
+    /// # 参数
+    /// * `oversample` - 重排过采样倍数，省略时不重排
+    pub fn estimate_cached_recall(&self, oversample: Option<f32>) -> Result<f32, JsValue> {
+        let _scope = self.operation_scope("estimate_cached_recall");
+        let params = SearchParams {
+            rescore_oversample: oversample.map_or(RescoreOversample::Disabled, RescoreOversample::Fixed),
+            ..SearchParams::default()
+        };
+        self.inner.estimate_cached_recall(&params)
+            .map_err(js_error)
+    }
+
+    /// 校准得到的过采样倍数
+    #[wasm_bindgen(getter)]
+    pub fn learned_oversample(&self) -> Option<f32> {
+        self.inner.get_learned_oversample()
+    }
+
+    /// 设置最终分数变换，把分数映射到 `[floor, ceiling]`（默认0-1），不改变结果顺序
+    ///
+    /// # 参数
+    /// * `curve` - "identity" | "logistic" | "piecewise_linear"，省略时移除变换
+    /// * `params` - logistic为 `[midpoint, steepness]`，分段线性为 `[x0, y0, x1, y1, ...]`
+    pub fn set_score_transform(
+        &mut self,
+        curve: Option<String>,
+        params: Option<Vec<f32>>,
+        floor: Option<f32>,
+        ceiling: Option<f32>,
+    ) -> Result<(), JsValue> {
+        let transform = match curve {
+            Some(curve) => Some(ScoreTransform::from_name(&curve, params.as_deref().unwrap_or(&[]), floor, ceiling)
+                .map_err(js_error)?),
+            None => None,
+        };
+        self.inner.set_score_transform(transform);
+        Ok(())
+    }
+
+    /// 结果缓存统计：`{ hits, misses, len, capacity }`
+    pub fn get_result_cache_stats(&self) -> Result<JsValue, JsValue> {
+        let stats = self.inner.get_result_cache_stats();
+        let js_stats = js_sys::Object::new();
+        js_sys::Reflect::set(&js_stats, &JsValue::from_str("hits"), &JsValue::from_f64(stats.hits as f64))?;
+        js_sys::Reflect::set(&js_stats, &JsValue::from_str("misses"), &JsValue::from_f64(stats.misses as f64))?;
+        js_sys::Reflect::set(&js_stats, &JsValue::from_str("len"), &JsValue::from_f64(stats.len as f64))?;
+        js_sys::Reflect::set(&js_stats, &JsValue::from_str("capacity"), &JsValue::from_f64(stats.capacity as f64))?;
+        Ok(js_stats.into())
+    }
+
+    /// 清空结果缓存
+    pub fn clear_result_cache(&self) {
+        self.inner.clear_result_cache();
+    }
+
+    /// 释放搜索复用的临时缓冲区
+    pub fn reset_scratch(&self) {
+        self.inner.reset_scratch();
+    }
+
+    /// 搜索临时缓冲区当前占用的字节数
+    pub fn scratch_bytes(&self) -> usize {
+        self.inner.scratch_bytes()
+    }
+
+    /// 批量获取打包向量和修正项，返回 `{ ord, quantizedVector, correction, reconstruction? }[]`
+    ///
+    /// # 参数
+    /// * `ords` - 序号
+    /// * `include_reconstructions` - 是否同时返回由量化值重建的向量
+    pub fn get_packed_vectors(&self, ords: &[u32], include_reconstructions: bool) -> Result<js_sys::Array, JsValue> {
+        let ords: Vec<usize> = ords.iter().map(|&ord| ord as usize).collect();
+        let records = self.inner.get_packed_vectors(&ords, include_reconstructions)
+            .map_err(js_error)?;
+        let result = js_sys::Array::new();
+        for record in &records {
+            result.push(&packed_record_to_js(record)?);
+        }
+        Ok(result)
+    }
+
+    /// 预热索引，返回 `{ vectorsTouched, bytesTouched, touchMs, probeMs, totalMs }`
+    pub fn warmup(&self) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("warmup");
+        let report = self.inner.warmup()
+            .map_err(js_error)?;
+        warmup_report_to_js(&report)
+    }
+
+    /// 只预热使用统计中最热的 `limit` 个向量，返回值同 `warmup`
+    pub fn warmup_hot(&self, limit: usize) -> Result<JsValue, JsValue> {
+        let _scope = self.operation_scope("warmup_hot");
+        let report = self.inner.warmup_hot(limit)
+            .map_err(js_error)?;
+        warmup_report_to_js(&report)
+    }
+
+    /// 使用统计：`{ queryCount, averageK, repeatRate, suggestedResultCacheCapacity, hotOrdinals }`
+    ///
+    /// # 参数
+    /// * `hot_limit` - 返回的热点序号数量
+    pub fn get_warm_stats(&self, hot_limit: usize) -> Result<JsValue, JsValue> {
+        warm_stats_to_js(&self.inner.get_warm_stats(), hot_limit)
+    }
+
+    /// 导出使用统计，可与索引数据一起保存
+    pub fn export_warm_stats(&self) -> Vec<u8> {
+        self.inner.get_warm_stats().serialize()
+    }
+
+    /// 导入之前导出的使用统计
+    pub fn import_warm_stats(&mut self, bytes: &[u8]) -> Result<(), JsValue> {
+        let stats = WarmStats::deserialize(bytes)
+            .map_err(js_error)?;
+        self.inner.set_warm_stats(stats);
+        Ok(())
+    }
+
+    /// 调整结果缓存容量，已缓存的结果清空
+    pub fn set_result_cache_capacity(&mut self, capacity: usize) {
+        self.inner.set_result_cache_capacity(capacity);
+    }
+
+    /// 导出只读查询包
+    ///
+    /// # 参数
+    /// * `live_ordinals` - 需要保留的序号（升序），省略时保留全部向量
+    pub fn export_query_pack(&self, live_ordinals: Option<Vec<u32>>) -> Result<Vec<u8>, JsValue> {
+        let _scope = self.operation_scope("export_query_pack");
+        let live_ordinals: Option<Vec<usize>> = live_ordinals
+            .map(|ordinals| ordinals.into_iter().map(|ord| ord as usize).collect());
+        export_query_pack(&self.inner, live_ordinals.as_deref())
+            .map_err(js_error)
+    }
+
     /// 获取配置信息
     pub fn get_config(&self) -> Result<JsValue, JsValue> {
         let config = self.inner.get_config();
@@ -528,7 +2703,558 @@ impl WasmQuantizedIndex {
             },
             lambda: config.lambda,
             iters: config.iters,
+            keep_original_vectors: config.keep_original_vectors,
+            result_cache_capacity: config.result_cache_capacity,
+            double_precision: config.correction_precision == CorrectionPrecision::Double,
+            fast_math: config.scoring_precision == ScoringPrecision::Fast,
+            degenerate_vectors: match config.degenerate_vectors {
+                DegenerateVectorPolicy::Keep => "keep".to_string(),
+                DegenerateVectorPolicy::Skip => "skip".to_string(),
+                DegenerateVectorPolicy::Reject => "reject".to_string(),
+            },
+            original_encoding: original_encoding_name(config.original_encoding).to_string(),
+            correction_layout: config.correction_layout.name().to_string(),
+            discretize_dimensions: config.discretize_dimensions,
+            prenormalized: config.prenormalized,
+            promote_fraction: config.promote_fraction,
+            promoted_bits: config.promoted_bits,
         };
         Ok(JsValue::from(js_config))
     }
 }
+
+
+#[cfg(feature = "index")]
+/// WASM包装类：只读查询包
+#[wasm_bindgen]
+pub struct WasmQueryPack {
+    inner: QueryPack,
+}
+
+#[cfg(feature = "index")]
+#[wasm_bindgen]
+impl WasmQueryPack {
+    /// 加载查询包
+    pub fn load(bytes: &[u8]) -> Result<WasmQueryPack, JsValue> {
+        let inner = QueryPack::load(bytes)
+            .map_err(js_error)?;
+        Ok(WasmQueryPack { inner })
+    }
+
+    /// 加载ES/OpenSearch BBQ段中某个字段的量化向量区域（.veb），离线搜索下载的段
+    ///
+    /// similarity为映射中的名称（"l2_norm" | "dot_product" | "cosine" | "max_inner_product"），
+    /// centroid为元数据中的质心
+    pub fn load_es_segment(bytes: &[u8], similarity: &str, centroid: &[f32]) -> Result<WasmQueryPack, JsValue> {
+        let similarity_function = similarity_from_es_name(similarity)
+            .map_err(js_error)?;
+        let inner = load_lucene_bbq_vectors(bytes, similarity_function, centroid)
+            .map_err(js_error)?;
+        Ok(WasmQueryPack { inner })
+    }
+
+    /// 只加载序号区间 [start_ord, end_ord) 内的向量，缓冲区只需包含该区间所需的字节
+    pub fn deserialize_range(bytes: &[u8], start_ord: usize, end_ord: usize) -> Result<WasmQueryPack, JsValue> {
+        let inner = QueryPack::deserialize_range(bytes, start_ord, end_ord)
+            .map_err(js_error)?;
+        Ok(WasmQueryPack { inner })
+    }
+
+    /// 追加加载另一个序号区间
+    pub fn extend_range(&mut self, bytes: &[u8], start_ord: usize, end_ord: usize) -> Result<(), JsValue> {
+        self.inner.extend_range(bytes, start_ord, end_ord)
+            .map_err(js_error)
+    }
+
+    /// 计算加载序号区间所需的字节范围，返回 `[start0, end0, start1, end1, ...]`
+    ///
+    /// header只需包含查询包的前24字节
+    pub fn range_regions(header: &[u8], start_ord: usize, end_ord: usize) -> Result<Vec<f64>, JsValue> {
+        let layout = QueryPackLayout::parse(header)
+            .map_err(js_error)?;
+        let regions = layout.range_regions(start_ord, end_ord)
+            .map_err(js_error)?;
+        Ok(regions.into_iter()
+            .flat_map(|region| [region.start as f64, region.end as f64])
+            .collect())
+    }
+
+    /// 搜索最近邻，结果中的index为原索引序号
+    pub fn search_nearest_neighbors(&self, query_vector: &[f32], k: usize) -> Result<Vec<JsValue>, JsValue> {
+        let results = self.inner.search_nearest_neighbors(query_vector, k)
+            .map_err(js_error)?;
+        Ok(results.into_iter()
+            .map(|result| JsValue::from(WasmQueryResult::new(result.index, result.score)))
+            .collect())
+    }
+
+    /// 使用主线程 `prepare_query_bytes` 生成的字节块搜索，只做评分
+    pub fn search_prepared(&self, prepared: &[u8], k: usize) -> Result<Vec<JsValue>, JsValue> {
+        let context = self.inner.deserialize_prepared_query(prepared)
+            .map_err(js_error)?;
+        let results = self.inner.search_with_context(&context, k, &SearchParams::default())
+            .map_err(js_error)?;
+        Ok(results.into_iter()
+            .map(|result| JsValue::from(WasmQueryResult::new(result.index, result.score)))
+            .collect())
+    }
+
+    /// 量化指纹（16位十六进制字符串）
+    #[wasm_bindgen(getter)]
+    pub fn quantization_fingerprint(&self) -> String {
+        format!("{:016x}", self.inner.quantization_fingerprint())
+    }
+
+    /// 向量数量
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// 向量维度
+    #[wasm_bindgen(getter)]
+    pub fn dimension(&self) -> usize {
+        self.inner.dimension()
+    }
+
+    /// 查询包中的向量总数，包括尚未加载的向量
+    #[wasm_bindgen(getter)]
+    pub fn total_count(&self) -> usize {
+        self.inner.total_count()
+    }
+
+    /// 是否已加载全部向量
+    #[wasm_bindgen(getter)]
+    pub fn is_complete(&self) -> bool {
+        self.inner.is_complete()
+    }
+
+    /// 尚未加载的序号区间，返回 `[start0, end0, start1, end1, ...]`
+    pub fn missing_ranges(&self) -> Vec<u32> {
+        self.inner.missing_ranges()
+            .into_iter()
+            .flat_map(|(start, end)| [start as u32, end as u32])
+            .collect()
+    }
+
+    /// 开始渐进搜索，之后每加载一批区间调用一次 `advance`
+    pub fn start_progressive_search(&self, query_vector: &[f32], k: usize) -> Result<WasmProgressiveSearch, JsValue> {
+        let inner = self.inner.start_progressive_search(query_vector, k)
+            .map_err(js_error)?;
+        Ok(WasmProgressiveSearch { inner })
+    }
+}
+
+#[cfg(feature = "index")]
+/// WASM包装类：查询包上的渐进搜索
+#[wasm_bindgen]
+pub struct WasmProgressiveSearch {
+    inner: ProgressiveSearch,
+}
+
+#[cfg(feature = "index")]
+#[wasm_bindgen]
+impl WasmProgressiveSearch {
+    /// 对新加载的向量评分，返回 `{ results, coverage, isFinal }`
+    pub fn advance(&mut self, pack: &WasmQueryPack) -> Result<JsValue, JsValue> {
+        let progress = self.inner.advance(&pack.inner)
+            .map_err(js_error)?;
+        progressive_results_to_js(progress)
+    }
+}
+
+#[cfg(feature = "index")]
+/// 渐进/增量搜索的临时结果转为 `{ results: [{ index, score }], coverage, isFinal }`
+fn progressive_results_to_js(progress: ProgressiveResults) -> Result<JsValue, JsValue> {
+    let js_results = js_sys::Array::new();
+    for result in progress.results {
+        js_results.push(&JsValue::from(WasmQueryResult::new(result.index, result.score)));
+    }
+    let js_progress = js_sys::Object::new();
+    js_sys::Reflect::set(&js_progress, &JsValue::from_str("results"), &js_results)?;
+    js_sys::Reflect::set(&js_progress, &JsValue::from_str("coverage"), &JsValue::from_f64(progress.coverage as f64))?;
+    js_sys::Reflect::set(&js_progress, &JsValue::from_str("isFinal"), &JsValue::from_bool(progress.is_final))?;
+    Ok(js_progress.into())
+}
+
+#[cfg(feature = "index")]
+/// WASM包装类：索引上的增量搜索
+///
+/// JS用法：在requestAnimationFrame回调中调用 `pumpFor(index, 4)`，直到 `isFinal` 为true
+#[wasm_bindgen]
+pub struct WasmIncrementalSearch {
+    inner: IncrementalSearch,
+}
+
+#[cfg(feature = "index")]
+#[wasm_bindgen]
+impl WasmIncrementalSearch {
+    /// 最多评分max_batches批，返回 `{ results, coverage, isFinal }`
+    pub fn pump(&mut self, index: &WasmQuantizedIndex, max_batches: usize) -> Result<JsValue, JsValue> {
+        let progress = self.inner.pump(&index.inner, max_batches)
+            .map_err(js_error)?;
+        progressive_results_to_js(progress)
+    }
+
+    /// 在budget_ms毫秒内尽量多地评分（至少一批），返回 `{ results, coverage, isFinal }`
+    #[wasm_bindgen(js_name = pumpFor)]
+    pub fn pump_for(&mut self, index: &WasmQuantizedIndex, budget_ms: f64) -> Result<JsValue, JsValue> {
+        let progress = self.inner.pump_for(&index.inner, budget_ms)
+            .map_err(js_error)?;
+        progressive_results_to_js(progress)
+    }
+
+    /// 设置每批评分的向量数
+    #[wasm_bindgen(js_name = setBatchSize)]
+    pub fn set_batch_size(&mut self, batch_size: usize) -> Result<(), JsValue> {
+        self.inner.set_batch_size(batch_size).map_err(js_error)
+    }
+
+    /// 是否所有向量都已评分
+    #[wasm_bindgen(getter)]
+    pub fn is_complete(&self) -> bool {
+        self.inner.is_complete()
+    }
+}
+
+#[cfg(all(feature = "index", feature = "serde"))]
+/// WASM包装类：高层门面
+///
+/// JS用法：`new BBQ({ dims, metric })`、`add(id, vector)`、`query(vector, k)`、`save()`、`BBQ.load(bytes)`
+#[wasm_bindgen(js_name = BBQ)]
+pub struct WasmBbq {
+    inner: Bbq,
+}
+
+#[cfg(all(feature = "index", feature = "serde"))]
+#[wasm_bindgen(js_class = BBQ)]
+impl WasmBbq {
+    /// 创建门面实例，options为 `{ dims: number, metric?: string, duplicates?: "replace" | "ignore" | "version" }`
+    #[wasm_bindgen(constructor)]
+    pub fn new(options: JsValue) -> Result<WasmBbq, JsValue> {
+        let options: BbqOptions = serde_wasm_bindgen::from_value(options)
+            .map_err(|e| invalid_input(Subject::new("BBQ选项", "BBQ options"), e))?;
+        let inner = Bbq::from_options(&options)
+            .map_err(js_error)?;
+        Ok(WasmBbq { inner })
+    }
+
+    /// 添加向量，ID已存在时按重复ID策略处理；忽略策略下ID已存在时返回false
+    pub fn add(&mut self, id: &str, vector: &[f32]) -> Result<bool, JsValue> {
+        self.inner.add(id, vector)
+            .map_err(js_error)
+    }
+
+    /// 重复ID策略: "replace" | "ignore" | "version"
+    #[wasm_bindgen(getter, js_name = duplicatePolicy)]
+    pub fn duplicate_policy(&self) -> String {
+        self.inner.duplicate_policy().name().to_string()
+    }
+
+    /// 设置重复ID策略，只影响之后的写入
+    #[wasm_bindgen(setter, js_name = duplicatePolicy)]
+    pub fn set_duplicate_policy(&mut self, policy: &str) -> Result<(), JsValue> {
+        self.inner.set_duplicate_policy(DuplicatePolicy::from_name(policy).map_err(js_error)?);
+        Ok(())
+    }
+
+    /// 冻结为只读：之后add、remove、applyDelta等写入都抛出错误，用于从CDN加载的已发布快照
+    pub fn freeze(&mut self) {
+        self.inner.freeze();
+    }
+
+    /// 解除只读冻结
+    pub fn unfreeze(&mut self) {
+        self.inner.unfreeze();
+    }
+
+    /// 是否已冻结为只读
+    #[wasm_bindgen(getter, js_name = isFrozen)]
+    pub fn is_frozen(&self) -> bool {
+        self.inner.is_frozen()
+    }
+
+    /// 指定ID的全部版本（最后一项为当前版本），返回 `{ version, vector: Float32Array }[]`
+    pub fn versions(&self, id: &str) -> Result<js_sys::Array, JsValue> {
+        let versions = js_sys::Array::new();
+        for (version, vector) in self.inner.versions(id) {
+            let entry = js_sys::Object::new();
+            js_sys::Reflect::set(&entry, &JsValue::from_str("version"), &JsValue::from_f64(version as f64))?;
+            js_sys::Reflect::set(&entry, &JsValue::from_str("vector"), &js_sys::Float32Array::from(vector))?;
+            versions.push(&entry);
+        }
+        Ok(versions)
+    }
+
+    /// 文本的嵌入已缓存时直接添加并返回true；未命中时返回false，
+    /// 由调用方计算嵌入后 `cache.put(text, vector)` 再 `add(id, vector)`
+    #[wasm_bindgen(js_name = addCached)]
+    pub fn add_cached(&mut self, id: &str, text: &str, cache: &mut WasmEmbeddingCache) -> Result<bool, JsValue> {
+        self.inner.add_cached(id, text, &mut cache.inner)
+            .map_err(js_error)
+    }
+
+    /// 删除向量，ID存在时返回true
+    pub fn remove(&mut self, id: &str) -> Result<bool, JsValue> {
+        self.inner.remove(id)
+            .map_err(js_error)
+    }
+
+    /// 查询最相似的k个向量，返回 `{ id, score }[]`
+    pub fn query(&mut self, vector: &[f32], k: usize) -> Result<JsValue, JsValue> {
+        let hits = self.inner.query(vector, k)
+            .map_err(js_error)?;
+        serde_wasm_bindgen::to_value(&hits)
+            .map_err(conversion_error)
+    }
+
+    /// 按外部ID批量获取打包向量和修正项，
+    /// 返回与ids对应的 `{ ord, quantizedVector, correction, reconstruction? }` 数组，不存在的ID为null
+    #[wasm_bindgen(js_name = getVectorsByIds)]
+    pub fn get_vectors_by_ids(&mut self, ids: Vec<String>, include_reconstructions: bool) -> Result<js_sys::Array, JsValue> {
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        let records = self.inner.get_vectors_by_ids(&ids, include_reconstructions)
+            .map_err(js_error)?;
+        let result = js_sys::Array::new();
+        for record in &records {
+            match record {
+                Some(record) => result.push(&packed_record_to_js(record)?),
+                None => result.push(&JsValue::NULL),
+            };
+        }
+        Ok(result)
+    }
+
+    /// 序列化为字节数组（包含使用统计）
+    pub fn save(&self) -> Result<Vec<u8>, JsValue> {
+        self.inner.save()
+            .map_err(js_error)
+    }
+
+    /// 按快照中的使用统计预热最热的 `limit` 个向量并调整结果缓存容量，
+    /// 返回 `{ vectorsTouched, bytesTouched, touchMs, probeMs, totalMs }`
+    pub fn warmup(&mut self, limit: usize) -> Result<JsValue, JsValue> {
+        let report = self.inner.warmup(limit)
+            .map_err(js_error)?;
+        warmup_report_to_js(&report)
+    }
+
+    /// 使用统计：`{ queryCount, averageK, repeatRate, suggestedResultCacheCapacity, hotOrdinals }`
+    #[wasm_bindgen(js_name = warmStats)]
+    pub fn warm_stats(&self, hot_limit: usize) -> Result<JsValue, JsValue> {
+        warm_stats_to_js(&self.inner.warm_stats(), hot_limit)
+    }
+
+    /// 从字节数组加载
+    pub fn load(bytes: &[u8]) -> Result<WasmBbq, JsValue> {
+        let inner = Bbq::load(bytes)
+            .map_err(js_error)?;
+        Ok(WasmBbq { inner })
+    }
+
+    /// 导出sinceVersion之后的增量变更
+    #[wasm_bindgen(js_name = exportDelta)]
+    pub fn export_delta(&self, since_version: f64) -> Result<Vec<u8>, JsValue> {
+        if !since_version.is_finite() || since_version < 0.0 || since_version.fract() != 0.0 {
+            return Err(js_error(BbqError::invalid_argument(format!("无效的版本号: {}", since_version), format!("invalid version: {}", since_version))));
+        }
+        self.inner.export_delta(since_version as u64)
+            .map_err(js_error)
+    }
+
+    /// 应用增量变更，返回 `{ fromVersion, toVersion, upserts, deletes }`
+    #[wasm_bindgen(js_name = applyDelta)]
+    pub fn apply_delta(&mut self, bytes: &[u8]) -> Result<JsValue, JsValue> {
+        let summary = self.inner.apply_delta(bytes)
+            .map_err(js_error)?;
+        serde_wasm_bindgen::to_value(&summary)
+            .map_err(conversion_error)
+    }
+
+    /// 当前版本号
+    #[wasm_bindgen(getter)]
+    pub fn version(&self) -> f64 {
+        self.inner.version() as f64
+    }
+
+    /// 向量数量
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// 向量维度
+    #[wasm_bindgen(getter)]
+    pub fn dims(&self) -> usize {
+        self.inner.dims()
+    }
+}
+
+#[cfg(all(feature = "index", feature = "serde"))]
+/// WASM包装类：以文本哈希为键的嵌入缓存
+///
+/// JS用法：`new EmbeddingCache(dims, capacity)`、`get(text)`、`put(text, vector)`、`save()`、`EmbeddingCache.load(bytes)`
+#[wasm_bindgen(js_name = EmbeddingCache)]
+pub struct WasmEmbeddingCache {
+    inner: EmbeddingCache,
+}
+
+#[cfg(all(feature = "index", feature = "serde"))]
+#[wasm_bindgen(js_class = EmbeddingCache)]
+impl WasmEmbeddingCache {
+    /// 创建缓存，最多保存capacity个嵌入
+    #[wasm_bindgen(constructor)]
+    pub fn new(dims: usize, capacity: usize) -> Result<WasmEmbeddingCache, JsValue> {
+        let inner = EmbeddingCache::new(dims, capacity)
+            .map_err(js_error)?;
+        Ok(WasmEmbeddingCache { inner })
+    }
+
+    /// 查找文本的嵌入，未命中时返回undefined
+    pub fn get(&mut self, text: &str) -> Option<Vec<f32>> {
+        self.inner.get(text)
+    }
+
+    /// 写入文本的嵌入，返回半精度量化后的嵌入（之后命中时返回的值）
+    pub fn put(&mut self, text: &str, vector: &[f32]) -> Result<Vec<f32>, JsValue> {
+        self.inner.put(text, vector)
+            .map_err(js_error)
+    }
+
+    /// 删除文本的嵌入，存在时返回true
+    pub fn remove(&mut self, text: &str) -> bool {
+        self.inner.remove(text)
+    }
+
+    /// 清空缓存（更换嵌入模型时调用）
+    pub fn clear(&mut self) {
+        self.inner.clear()
+    }
+
+    /// 统计信息：`{ hits, misses, len, capacity }`
+    pub fn stats(&self) -> Result<JsValue, JsValue> {
+        let stats = self.inner.stats();
+        let js_stats = js_sys::Object::new();
+        js_sys::Reflect::set(&js_stats, &JsValue::from_str("hits"), &JsValue::from_f64(stats.hits as f64))?;
+        js_sys::Reflect::set(&js_stats, &JsValue::from_str("misses"), &JsValue::from_f64(stats.misses as f64))?;
+        js_sys::Reflect::set(&js_stats, &JsValue::from_str("len"), &JsValue::from_f64(stats.len as f64))?;
+        js_sys::Reflect::set(&js_stats, &JsValue::from_str("capacity"), &JsValue::from_f64(stats.capacity as f64))?;
+        Ok(js_stats.into())
+    }
+
+    /// 序列化为字节数组
+    pub fn save(&self) -> Result<Vec<u8>, JsValue> {
+        self.inner.save()
+            .map_err(js_error)
+    }
+
+    /// 从字节数组加载
+    pub fn load(bytes: &[u8]) -> Result<WasmEmbeddingCache, JsValue> {
+        let inner = EmbeddingCache::load(bytes)
+            .map_err(js_error)?;
+        Ok(WasmEmbeddingCache { inner })
+    }
+
+    /// 缓存条目数
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+#[cfg(all(feature = "index", feature = "serde"))]
+/// 多标签页同步副本（变更字节由JS通过BroadcastChannel转发）
+#[wasm_bindgen(js_name = BBQReplica)]
+pub struct WasmReplica {
+    inner: Replica,
+}
+
+#[cfg(all(feature = "index", feature = "serde"))]
+#[wasm_bindgen(js_class = BBQReplica)]
+impl WasmReplica {
+    /// 创建空副本，options为 `{ dims: number, metric?: string }`
+    #[wasm_bindgen(constructor)]
+    pub fn new(replica_id: &str, options: JsValue) -> Result<WasmReplica, JsValue> {
+        let options: BbqOptions = serde_wasm_bindgen::from_value(options)
+            .map_err(|e| invalid_input(Subject::new("BBQ选项", "BBQ options"), e))?;
+        let metric = match options.metric.as_deref() {
+            Some(name) => parse_metric(name).map_err(js_error)?,
+            None => SimilarityFunction::Cosine,
+        };
+        let inner = Replica::new(replica_id, options.dims, metric)
+            .map_err(js_error)?;
+        Ok(WasmReplica { inner })
+    }
+
+    /// 从其他副本的快照创建新副本
+    #[wasm_bindgen(js_name = fromSnapshot)]
+    pub fn from_snapshot(bytes: &[u8], replica_id: &str) -> Result<WasmReplica, JsValue> {
+        let inner = Replica::from_snapshot(bytes, replica_id)
+            .map_err(js_error)?;
+        Ok(WasmReplica { inner })
+    }
+
+    /// 本地添加或覆盖向量
+    pub fn add(&mut self, id: &str, vector: &[f32]) -> Result<(), JsValue> {
+        self.inner.add(id, vector)
+            .map_err(js_error)
+    }
+
+    /// 本地删除向量，ID存在时返回true
+    pub fn remove(&mut self, id: &str) -> Result<bool, JsValue> {
+        self.inner.remove(id)
+            .map_err(js_error)
+    }
+
+    /// 查询最相似的k个向量，返回 `{ id, score }[]`
+    pub fn query(&mut self, vector: &[f32], k: usize) -> Result<JsValue, JsValue> {
+        let hits = self.inner.query(vector, k)
+            .map_err(js_error)?;
+        serde_wasm_bindgen::to_value(&hits)
+            .map_err(conversion_error)
+    }
+
+    /// 导出对方（版本向量为since）尚未见过的变更；since为空时导出全部
+    #[wasm_bindgen(js_name = exportChanges)]
+    pub fn export_changes(&self, since: JsValue) -> Result<Vec<u8>, JsValue> {
+        let since: VersionVector = if since.is_undefined() || since.is_null() {
+            VersionVector::new()
+        } else {
+            serde_wasm_bindgen::from_value(since)
+                .map_err(|e| invalid_input(Subject::new("版本向量", "version vector"), e))?
+        };
+        Ok(self.inner.export_changes(&since))
+    }
+
+    /// 合并其他副本的变更，返回 `{ applied, ignored }`
+    pub fn merge(&mut self, bytes: &[u8]) -> Result<JsValue, JsValue> {
+        let summary = self.inner.merge(bytes)
+            .map_err(js_error)?;
+        serde_wasm_bindgen::to_value(&summary)
+            .map_err(conversion_error)
+    }
+
+    /// 导出紧凑快照
+    pub fn snapshot(&self) -> Vec<u8> {
+        self.inner.snapshot()
+    }
+
+    /// 版本向量 `{ [replicaId]: clock }`
+    #[wasm_bindgen(getter, js_name = versionVector)]
+    pub fn version_vector(&self) -> Result<JsValue, JsValue> {
+        serde_wasm_bindgen::to_value(self.inner.version_vector())
+            .map_err(conversion_error)
+    }
+
+    /// 副本ID
+    #[wasm_bindgen(getter, js_name = replicaId)]
+    pub fn replica_id(&self) -> String {
+        self.inner.replica_id().to_string()
+    }
+
+    /// 向量数量
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.inner.len()
+    }
+}
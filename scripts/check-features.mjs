@@ -0,0 +1,34 @@
+import { execSync } from 'child_process';
+import * as path from 'path';
+import { fileURLToPath } from 'url';
+
+// 依次检查每种特性组合：clippy（WASM目标）+ 测试（本机目标）
+const __dirname = path.dirname(fileURLToPath(import.meta.url));
+const crateDir = path.resolve(__dirname, '../rust-wasm');
+
+const combinations = [
+    '',                     // 只有量化器
+    'scorer',               // 查询侧量化 + 评分
+    'scorer,serde',
+    'lucene_parity',        // 与Lucene逐位一致的评分（不含索引）
+    'index',
+    'index,ivf',
+    'index,graph',
+    'index,eval',
+    'index,serde',
+    'serde',
+    'default',
+];
+
+const run = (command) => {
+    console.log(`$ ${command}`);
+    execSync(command, { cwd: crateDir, stdio: 'inherit' });
+};
+
+for (const features of combinations) {
+    const flags = features === 'default' ? '' : `--no-default-features${features ? ` --features ${features}` : ''}`;
+    console.log(`\n== 特性组合: ${features || '(无)'}`);
+    run(`cargo clippy --target wasm32-unknown-unknown ${flags} -- -D warnings`);
+    run(`cargo test --target x86_64-unknown-linux-gnu ${flags}`);
+}
+console.log('\n所有特性组合检查通过');
@@ -2,15 +2,27 @@ import init, * as wasm from '../../wasm-dist/better_binary_quantization.js';
 
 export type WasmModule = typeof wasm;
 
+export interface WasmInitOptions {
+    /** 实例化后立即完成内核校准等较重的初始化，默认推迟到第一次搜索 */
+    eager?: boolean;
+}
+
 export class WasmProvider {
     private static instance: WasmModule | null = null;
     private static initializationPromise: Promise<WasmModule> | null = null;
 
     /**
      * 初始化WASM模块
+     *
+     * 传入URL、Request或Response（包括fetch返回的Promise）时使用 WebAssembly.instantiateStreaming，
+     * 边下载边编译；内核校准等较重的初始化默认推迟到第一次搜索，传入 eager 时在实例化后立即完成。
      * @param wasmUrlOrBuffer 可选的WASM文件URL或Buffer。如果在Node环境，可能需要传入Buffer。
+     * @param options 初始化选项
      */
-    public static async init(wasmUrlOrBuffer?: string | Request | Response | ArrayBuffer | ArrayBufferView | WebAssembly.Module): Promise<WasmModule> {
+    public static async init(
+        wasmUrlOrBuffer?: string | URL | Request | Response | Promise<Response> | ArrayBuffer | ArrayBufferView | WebAssembly.Module,
+        options: WasmInitOptions = {},
+    ): Promise<WasmModule> {
         if (this.instance) {
             return this.instance;
         }
@@ -20,7 +32,10 @@ export class WasmProvider {
         }
 
         this.initializationPromise = (async () => {
-            await init(wasmUrlOrBuffer);
+            await init({ module_or_path: wasmUrlOrBuffer });
+            if (options.eager) {
+                wasm.initializeRuntime();
+            }
             this.instance = wasm;
             return wasm;
         })();
@@ -38,6 +53,66 @@ export class WasmProvider {
     public static isInitialized(): boolean {
         return !!this.instance;
     }
+
+    /**
+     * 在空闲时完成推迟的初始化（已完成时直接返回），避免第一次搜索变慢
+     */
+    public static warmUp(): void {
+        const module = this.getModule();
+        if (!module.isRuntimeInitialized()) {
+            module.initializeRuntime();
+        }
+    }
+}
+
+/** 与语言无关的错误码，见 rust-wasm/src/error_codes.rs 的 `ErrorCode::name` */
+export type BbqErrorCode =
+    | 'out_of_memory'
+    | 'read_only'
+    | 'concurrent_modification'
+    | 'unsupported_version'
+    | 'corrupt_data'
+    | 'not_ready'
+    | 'not_built'
+    | 'missing_original_vectors'
+    | 'dimension_mismatch'
+    | 'out_of_range'
+    | 'invalid_value'
+    | 'empty_input'
+    | 'invalid_argument'
+    | 'unknown';
+
+/**
+ * WASM接口抛出的错误
+ *
+ * 破坏性变更：此前除内存不足外的错误都直接抛出中文消息字符串，`catch (e)` 得到的就是字符串本身；
+ * 现在抛出 `name` 为 "BbqError" 的Error对象，`message` 默认为英文。
+ * - 原来的中文消息保存在 `detail` 中，依赖旧字符串的调用方改为读取 `detail`，
+ *   或调用 `setErrorLocale('zh')` 让 `message` 恢复为中文
+ * - 区分错误种类时使用 `code`，不要匹配消息文本
+ * - 内存不足时 `name` 为 "OutOfMemoryError"，附带的 `what` 同样跟随错误消息语言（此前总是中文）
+ */
+export interface BbqError extends Error {
+    name: 'BbqError' | 'OutOfMemoryError';
+    code: BbqErrorCode;
+    /** 中文消息，与此前抛出的字符串相同 */
+    detail: string;
+}
+
+/** 内存不足时抛出的错误 */
+export interface BbqOutOfMemoryError extends BbqError {
+    name: 'OutOfMemoryError';
+    code: 'out_of_memory';
+    what: string;
+    requestedBytes: number;
+    /** 非WASM构建中为null */
+    heapBytes: number | null;
+    maxBytes: number;
+}
+
+/** 判断捕获的异常是否为WASM接口抛出的错误 */
+export function isBbqError(error: unknown): error is BbqError {
+    return error instanceof Error && typeof (error as Partial<BbqError>).code === 'string' && typeof (error as Partial<BbqError>).detail === 'string';
 }
 
 export * from '../../wasm-dist/better_binary_quantization.js';